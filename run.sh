@@ -16,6 +16,8 @@ export JAVA_HOME=/Users/douchuan/work/prj_rust/jvm/test/zulu8/jre
 #cargo run -- --cp $JDK:$MY_TEST HelloWorld 123 456 789
 #cargo run -- --cp $JDK:$MY_TEST Ex
 #cargo run -- --cp $JDK:$MY_TEST MyFile
+#cargo run -- --cp $JDK:$MY_TEST ProducerConsumer
+#cargo run --release -- --cp $JDK:$MY_TEST SwitchBench
 
 ### fix Overflow
 #cargo run -- --cp $JDK:$MY_TEST SubOverflow
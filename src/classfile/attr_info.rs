@@ -68,6 +68,12 @@ pub enum AttrType {
     MethodParameters {
         parameters: Vec<MethodParameter>,
     },
+    Record {
+        components: Vec<RecordComponent>,
+    },
+    PermittedSubclasses {
+        classes: Vec<U2>,
+    },
     Unknown,
 }
 
@@ -96,6 +102,8 @@ pub enum AttrTag {
     AnnotationDefault,
     BootstrapMethods,
     MethodParameters,
+    Record,
+    PermittedSubclasses,
     Unknown,
 }
 
@@ -127,6 +135,8 @@ impl From<&[u8]> for AttrTag {
             b"AnnotationDefault" => AttrTag::AnnotationDefault,
             b"BootstrapMethods" => AttrTag::BootstrapMethods,
             b"MethodParameters" => AttrTag::MethodParameters,
+            b"Record" => AttrTag::Record,
+            b"PermittedSubclasses" => AttrTag::PermittedSubclasses,
             _ => {
                 info!("Unknown attr {}", String::from_utf8_lossy(raw));
                 AttrTag::Unknown
@@ -154,7 +164,7 @@ pub struct CodeException {
 
 impl CodeException {
     pub fn contains(&self, pc: U2) -> bool {
-        (self.start_pc..self.end_pc + 1).contains(&pc)
+        (self.start_pc..self.end_pc).contains(&pc)
     }
 
     pub fn is_finally(&self) -> bool {
@@ -335,6 +345,13 @@ pub struct MethodParameter {
     pub acc_flags: U2,
 }
 
+#[derive(Debug, Clone)]
+pub struct RecordComponent {
+    pub name_index: U2,
+    pub descriptor_index: U2,
+    pub attrs: Vec<AttrType>,
+}
+
 #[derive(Debug, Clone)]
 pub enum VerificationTypeInfo {
     Top,
@@ -57,6 +57,11 @@ pub enum ConstantType {
     MethodType {
         desc_index: u16,
     },
+    //CONSTANT_Dynamic, condy, resolved by a bootstrap method
+    Dynamic {
+        bootstrap_method_attr_index: u16,
+        name_and_type_index: u16,
+    },
     InvokeDynamic {
         bootstrap_method_attr_index: u16,
         name_and_type_index: u16,
@@ -325,8 +330,12 @@ impl Checker for ConstantType {
                 }) => Ok(()),
                 _ => Err(checker::Err::InvalidCpStrStrIdx),
             },
-            ConstantType::InvokeDynamic {
-                bootstrap_method_attr_index,
+            ConstantType::Dynamic {
+                bootstrap_method_attr_index: _,
+                name_and_type_index,
+            }
+            | ConstantType::InvokeDynamic {
+                bootstrap_method_attr_index: _,
                 name_and_type_index,
             } => {
                 //todo: bootstrap_method_attr_index
@@ -358,6 +367,7 @@ pub enum ConstantTag {
     Utf8,
     MethodHandle,
     MethodType,
+    Dynamic,
     InvokeDynamic,
     Unknown,
 }
@@ -378,6 +388,7 @@ impl From<u8> for ConstantTag {
             1 => ConstantTag::Utf8,
             15 => ConstantTag::MethodHandle,
             16 => ConstantTag::MethodType,
+            17 => ConstantTag::Dynamic,
             18 => ConstantTag::InvokeDynamic,
             _ => ConstantTag::Unknown,
         }
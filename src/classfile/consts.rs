@@ -17,10 +17,12 @@ pub const MAX_ARRAY_DIMENSIONS: u16 = 255;
 
 pub const J_OBJECT: &[u8] = b"java/lang/Object";
 pub const J_CLONEABLE: &[u8] = b"java/lang/Cloneable";
+pub const J_CLONE_NOT_SUPPORTED: &[u8] = b"java/lang/CloneNotSupportedException";
 pub const J_SERIALIZABLE: &[u8] = b"java/io/Serializable";
 pub const J_CLASS: &[u8] = b"java/lang/Class";
 pub const J_STRING: &[u8] = b"java/lang/String";
 pub const J_THREAD: &[u8] = b"java/lang/Thread";
+pub const J_COMPLETABLE_FUTURE: &[u8] = b"java/util/concurrent/CompletableFuture";
 pub const J_THREAD_GROUP: &[u8] = b"java/lang/ThreadGroup";
 pub const J_SYSTEM: &[u8] = b"java/lang/System";
 
@@ -31,20 +33,54 @@ pub const J_SECURITY_MANAGER: &[u8] = b"java/lang/SecurityManager";
 pub const J_FIELD: &[u8] = b"java/lang/reflect/Field";
 pub const J_METHOD: &[u8] = b"java/lang/reflect/Method";
 pub const J_METHOD_CTOR: &[u8] = b"java/lang/reflect/Constructor";
+pub const J_PARAMETER: &[u8] = b"java/lang/reflect/Parameter";
+pub const J_PARAMETER_ARY: &[u8] = b"[Ljava/lang/reflect/Parameter;";
+pub const J_RECORD: &[u8] = b"java/lang/Record";
+pub const J_RECORD_COMPONENT: &[u8] = b"java/lang/reflect/RecordComponent";
+pub const J_RECORD_COMPONENT_ARY: &[u8] = b"[Ljava/lang/reflect/RecordComponent;";
+pub const J_CLEANER: &[u8] = b"sun/misc/Cleaner";
+pub const J_FINALIZER: &[u8] = b"java/lang/ref/Finalizer";
+pub const J_REFERENCE: &[u8] = b"java/lang/ref/Reference";
 pub const J_ACCESSIBLE_OBJECT: &[u8] = b"java/lang/reflect/AccessibleObject";
 pub const J_METHODHANDLE: &[u8] = b"java/lang/invoke/MethodHandle";
 pub const J_METHODTYPE: &[u8] = b"java/lang/invoke/MethodType";
+pub const J_METHOD_HANDLE_NATIVES: &[u8] = b"java/lang/invoke/MethodHandleNatives";
+pub const J_METHOD_HANDLES_LOOKUP: &[u8] = b"java/lang/invoke/MethodHandles$Lookup";
 
 pub const J_INTERNAL_ERROR: &[u8] = b"java/lang/InternalError";
 pub const J_NPE: &[u8] = b"java/lang/NullPointerException";
 pub const J_IOEXCEPTION: &[u8] = b"java/io/IOException";
+pub const J_FILE_NOT_FOUND: &[u8] = b"java/io/FileNotFoundException";
+pub const J_INDEX_OUT_OF_BOUNDS: &[u8] = b"java/lang/IndexOutOfBoundsException";
 pub const J_ARRAY_INDEX_OUT_OF_BOUNDS: &[u8] = b"java/lang/ArrayIndexOutOfBoundsException";
+pub const J_STRING_INDEX_OUT_OF_BOUNDS: &[u8] = b"java/lang/StringIndexOutOfBoundsException";
+pub const J_ARRAY_STORE: &[u8] = b"java/lang/ArrayStoreException";
 pub const J_CLASS_NOT_FOUND: &[u8] = b"java/lang/ClassNotFoundException";
 pub const J_ARITHMETIC_EX: &[u8] = b"java/lang/ArithmeticException";
 pub const J_SOE: &[u8] = b"java/lang/StackOverflowError";
+pub const J_OOM: &[u8] = b"java/lang/OutOfMemoryError";
+pub const J_UNSATISFIED_LINK: &[u8] = b"java/lang/UnsatisfiedLinkError";
+pub const J_BOOTSTRAP_METHOD_ERROR: &[u8] = b"java/lang/BootstrapMethodError";
 pub const J_NASE: &[u8] = b"java/lang/NegativeArraySizeException";
 pub const J_CCE: &[u8] = b"java/lang/ClassCastException";
+pub const J_ICCE: &[u8] = b"java/lang/IncompatibleClassChangeError";
+pub const J_NO_SUCH_METHOD_ERROR: &[u8] = b"java/lang/NoSuchMethodError";
+pub const J_NO_SUCH_FIELD_ERROR: &[u8] = b"java/lang/NoSuchFieldError";
+pub const J_AME: &[u8] = b"java/lang/AbstractMethodError";
+pub const J_IMSE: &[u8] = b"java/lang/IllegalMonitorStateException";
+pub const J_IAE: &[u8] = b"java/lang/IllegalArgumentException";
+pub const J_INTERRUPTED: &[u8] = b"java/lang/InterruptedException";
+pub const J_ILLEGAL_ACCESS: &[u8] = b"java/lang/IllegalAccessException";
+pub const J_INSTANTIATION: &[u8] = b"java/lang/InstantiationException";
+pub const J_INVOCATION_TARGET: &[u8] = b"java/lang/reflect/InvocationTargetException";
 pub const J_THROWABLE: &[u8] = b"java/lang/Throwable";
+pub const J_ERROR: &[u8] = b"java/lang/Error";
+pub const J_NO_CLASS_DEF_FOUND: &[u8] = b"java/lang/NoClassDefFoundError";
+pub const J_EXCEPTION_IN_INITIALIZER: &[u8] = b"java/lang/ExceptionInInitializerError";
+pub const J_CLASS_FORMAT_ERROR: &[u8] = b"java/lang/ClassFormatError";
+pub const J_UNSUPPORTED_CLASS_VERSION: &[u8] = b"java/lang/UnsupportedClassVersionError";
+pub const J_VERIFY_ERROR: &[u8] = b"java/lang/VerifyError";
+pub const J_LINKAGE_ERROR: &[u8] = b"java/lang/LinkageError";
 
 pub const CONSTANT_METHOD_REF_TAG: u8 = 10;
 pub const CONSTANT_INTERFACE_METHOD_REF_TAG: u8 = 11;
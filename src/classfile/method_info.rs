@@ -1,4 +1,4 @@
-use crate::classfile::attr_info::{AttrType, Code, LineNumber};
+use crate::classfile::attr_info::{AttrType, Code, LineNumber, MethodParameter};
 use crate::classfile::constant_pool;
 use crate::types::{BytesRef, ConstantPool, U2};
 use std::collections::HashMap;
@@ -46,6 +46,13 @@ impl MethodInfo {
         line_num_table
     }
 
+    pub fn get_method_parameters(&self) -> Option<&[MethodParameter]> {
+        self.attrs.iter().find_map(|it| match it {
+            AttrType::MethodParameters { parameters } => Some(parameters.as_slice()),
+            _ => None,
+        })
+    }
+
     pub fn get_src_file(&self, cp: &ConstantPool) -> Option<BytesRef> {
         for it in self.attrs.iter() {
             match it {
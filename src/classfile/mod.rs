@@ -41,6 +41,12 @@ impl ClassFile {
     }
 }
 
+//major versions the parser knows the structures of, Java 1.1 to 17
+//(Record, PermittedSubclasses, CONSTANT_Dynamic). The class library is
+//Java 8's, java.class.version is 52.0
+pub const MIN_MAJOR_VERSION: U2 = 45;
+pub const MAX_MAJOR_VERSION: U2 = 61;
+
 #[derive(Debug)]
 pub struct Version {
     pub minor: U2,
@@ -223,6 +223,16 @@ pub enum OpCode {
 
 impl From<u8> for OpCode {
     fn from(v: u8) -> Self {
+        match OpCode::decode(v) {
+            Some(op) => op,
+            None => unreachable!("unknown opcode 0x{:x}", v),
+        }
+    }
+}
+
+impl OpCode {
+    //None for the unassigned opcodes 0xcb ~ 0xfd
+    pub fn decode(v: u8) -> Option<Self> {
         let codes = vec![
             OpCode::nop,
             OpCode::aconst_null,
@@ -429,16 +439,151 @@ impl From<u8> for OpCode {
             OpCode::breakpoint,
         ];
         match codes.get(v as usize) {
-            Some(op) => *op,
+            Some(op) => Some(*op),
             None => match v {
-                254 => OpCode::impdep1,
-                255 => OpCode::impdep2,
-                _ => unreachable!(),
+                254 => Some(OpCode::impdep1),
+                255 => Some(OpCode::impdep2),
+                _ => None,
             },
         }
     }
 }
 
+impl OpCode {
+    /*
+    The minimum number of operand stack slots consumed by the instruction
+    (JVMS, chapter 6). long & double take 2 slots.
+
+    For instructions whose stack effect depends on a descriptor or operand
+    (putfield, invoke*, multianewarray...) this is a lower bound.
+    */
+    pub fn min_stack_pops(&self) -> usize {
+        match self {
+            OpCode::iaload
+            | OpCode::laload
+            | OpCode::faload
+            | OpCode::daload
+            | OpCode::aaload
+            | OpCode::baload
+            | OpCode::caload
+            | OpCode::saload => 2,
+            OpCode::istore
+            | OpCode::fstore
+            | OpCode::astore
+            | OpCode::istore_0
+            | OpCode::istore_1
+            | OpCode::istore_2
+            | OpCode::istore_3
+            | OpCode::fstore_0
+            | OpCode::fstore_1
+            | OpCode::fstore_2
+            | OpCode::fstore_3
+            | OpCode::astore_0
+            | OpCode::astore_1
+            | OpCode::astore_2
+            | OpCode::astore_3 => 1,
+            OpCode::lstore
+            | OpCode::dstore
+            | OpCode::lstore_0
+            | OpCode::lstore_1
+            | OpCode::lstore_2
+            | OpCode::lstore_3
+            | OpCode::dstore_0
+            | OpCode::dstore_1
+            | OpCode::dstore_2
+            | OpCode::dstore_3 => 2,
+            OpCode::iastore
+            | OpCode::fastore
+            | OpCode::aastore
+            | OpCode::bastore
+            | OpCode::castore
+            | OpCode::sastore => 3,
+            OpCode::lastore | OpCode::dastore => 4,
+            OpCode::pop | OpCode::dup => 1,
+            OpCode::pop2 | OpCode::dup_x1 | OpCode::dup2 | OpCode::swap => 2,
+            OpCode::dup_x2 | OpCode::dup2_x1 => 3,
+            OpCode::dup2_x2 => 4,
+            OpCode::iadd
+            | OpCode::fadd
+            | OpCode::isub
+            | OpCode::fsub
+            | OpCode::imul
+            | OpCode::fmul
+            | OpCode::idiv
+            | OpCode::fdiv
+            | OpCode::irem
+            | OpCode::frem
+            | OpCode::ishl
+            | OpCode::ishr
+            | OpCode::iushr
+            | OpCode::iand
+            | OpCode::ior
+            | OpCode::ixor => 2,
+            OpCode::ladd
+            | OpCode::dadd
+            | OpCode::lsub
+            | OpCode::dsub
+            | OpCode::lmul
+            | OpCode::dmul
+            | OpCode::ldiv
+            | OpCode::ddiv
+            | OpCode::lrem
+            | OpCode::drem
+            | OpCode::land
+            | OpCode::lor
+            | OpCode::lxor => 4,
+            OpCode::lshl | OpCode::lshr | OpCode::lushr => 3,
+            OpCode::ineg | OpCode::fneg => 1,
+            OpCode::lneg | OpCode::dneg => 2,
+            OpCode::i2l
+            | OpCode::i2f
+            | OpCode::i2d
+            | OpCode::f2i
+            | OpCode::f2l
+            | OpCode::f2d
+            | OpCode::i2b
+            | OpCode::i2c
+            | OpCode::i2s => 1,
+            OpCode::l2i | OpCode::l2f | OpCode::l2d | OpCode::d2i | OpCode::d2l | OpCode::d2f => 2,
+            OpCode::lcmp | OpCode::dcmpl | OpCode::dcmpg => 4,
+            OpCode::fcmpl | OpCode::fcmpg => 2,
+            OpCode::ifeq
+            | OpCode::ifne
+            | OpCode::iflt
+            | OpCode::ifge
+            | OpCode::ifgt
+            | OpCode::ifle
+            | OpCode::ifnull
+            | OpCode::ifnonnull => 1,
+            OpCode::if_icmpeq
+            | OpCode::if_icmpne
+            | OpCode::if_icmplt
+            | OpCode::if_icmpge
+            | OpCode::if_icmpgt
+            | OpCode::if_icmple
+            | OpCode::if_acmpeq
+            | OpCode::if_acmpne => 2,
+            OpCode::tableswitch | OpCode::lookupswitch => 1,
+            OpCode::ireturn | OpCode::freturn | OpCode::areturn => 1,
+            OpCode::lreturn | OpCode::dreturn => 2,
+            OpCode::putstatic => 1,
+            OpCode::getfield => 1,
+            OpCode::putfield => 2,
+            OpCode::invokevirtual | OpCode::invokespecial | OpCode::invokeinterface => 1,
+            OpCode::newarray
+            | OpCode::anewarray
+            | OpCode::arraylength
+            | OpCode::athrow
+            | OpCode::checkcast
+            | OpCode::instanceof
+            | OpCode::monitorenter
+            | OpCode::monitorexit
+            | OpCode::multianewarray => 1,
+            _ => 0,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::classfile::opcode::OpCode;
@@ -702,5 +847,9 @@ mod tests {
         assert_eq!(OpCode::impdep1, OpCode::from(254));
         assert_eq!(OpCode::impdep2, OpCode::from(255));
         //        assert_eq!(OpCode::, OpCode::from(256));
+
+        assert_eq!(None, OpCode::decode(203));
+        assert_eq!(None, OpCode::decode(253));
+        assert_eq!(Some(OpCode::impdep1), OpCode::decode(254));
     }
 }
@@ -1,24 +1,9 @@
-extern crate bytes;
 extern crate clap;
-#[macro_use]
-extern crate lazy_static;
-#[macro_use]
-extern crate log;
 extern crate env_logger;
 
 use clap::{App, Arg};
-
-#[macro_use]
-mod util;
-
-mod classfile;
-mod native;
-mod oop;
-mod parser;
-mod runtime;
-mod types;
-
-use crate::runtime::thread::JavaMainThread;
+use jvm::prelude::{Config, Vm};
+use jvm::{classfile, native, runtime, util};
 
 /*
 todo list
@@ -46,16 +31,14 @@ todo list
         现在，java_lang_Class::forName0暂且跳过"sun/nio/cs/ext/ExtendedCharsets"
 */
 
-fn init_vm() {
-    oop::init();
-    runtime::init();
-    native::init();
-}
-
 fn main() {
     env_logger::init();
-    init_vm();
 
+    //-verbose:class, clap has no single dash long options
+    let args = std::env::args().map(|it| match it.as_str() {
+        "-verbose:class" => "-XX:+TraceClassLoading".to_string(),
+        _ => it,
+    });
     let matches = App::new("")
         .arg(
             Arg::with_name("cp")
@@ -69,26 +52,88 @@ fn main() {
                 .help("class search path of directories and zip/jar files")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("X")
+                .short("X")
+                .help("non-standard vm options, e.g. -XX:+VerifyStack, -XX:+UseMarkSweepGC, -XX:+SingleStep, -XX:-OmitStackTraceInFastThrow, -XX:+PrefetchClasses, -XX:+TrackHeapObjects, -Xss16m, -Xms64m, -Xmx512m")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("events-to-json")
+                .long("events-to-json")
+                .help("print an event log, recorded by -XX:EventLog, as json")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("version")
+                .long("version")
+                .help("print the vm version & the class file versions it supports, then exit"),
+        )
+        .arg(
+            Arg::with_name("show-version")
+                .long("show-version")
+                .help("print the vm version, then run the main class"),
+        )
+        .arg(
+            Arg::with_name("list-natives")
+                .long("list-natives")
+                .help("print the implemented native methods by class, then exit"),
+        )
         .arg(
             Arg::with_name("MAIN_CLASS")
                 .help("to execute a class")
-                .required(true)
+                .required_unless_one(&["events-to-json", "version", "list-natives"])
                 .index(1),
         )
         .arg(Arg::with_name("ARGS").multiple(true).help("[args...]"))
-        .get_matches();
-
-    //todo: add '.' auto
-    let cp = matches.value_of("cp");
-    if let Some(cp) = cp {
-        runtime::add_class_paths(cp);
+        .get_matches_from(args);
+
+    if let Some(path) = matches.value_of("events-to-json") {
+        let buf = util::read(path);
+        match runtime::events::decode(buf.as_slice()) {
+            Ok(events) => println!("{}", runtime::events::to_json(events.as_slice())),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+        return;
     }
 
-    let classpath = matches.value_of("classpath");
-    if let Some(classpath) = classpath {
-        runtime::add_class_path(classpath);
+    if matches.is_present("version") {
+        print!("{}", banner());
+        return;
+    }
+    if matches.is_present("list-natives") {
+        print!("{}", list_natives());
+        return;
+    }
+    if matches.is_present("show-version") {
+        eprint!("{}", banner());
     }
 
+    let options = match matches.values_of("X") {
+        Some(opts) => opts.map(|it| format!("-X{}", it)).collect(),
+        None => vec![],
+    };
+    //todo: add '.' auto
+    let class_path = matches
+        .value_of("cp")
+        .or_else(|| matches.value_of("classpath"))
+        .map(|it| it.to_string());
+    let vm = match Vm::new(Config {
+        class_path,
+        options,
+    }) {
+        Ok(vm) => vm,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     let class = matches.value_of_lossy("MAIN_CLASS").unwrap().to_string();
     /*
     为了避免"<clinit>"被执行 2 次，这里不允许用路径分隔符
@@ -111,8 +156,10 @@ fn main() {
     let args = matches.values_of_lossy("ARGS");
     println!("main class: {}, args: {:?}", class, args);
 
-    let mut thread = JavaMainThread::new(class, args);
-    thread.run();
+    let status = vm.run_main(&class, args);
+    if status != 0 {
+        std::process::exit(status);
+    }
 
     /*
     let path = "test/Test.class";
@@ -126,179 +173,38 @@ fn main() {
     */
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::oop::OopDesc;
-    use std::hash::{Hash, Hasher};
-
-    #[test]
-    fn t_basic() {
-        match 5 {
-            1..=5 => assert!(true),
-            _ => assert!(false),
-        }
-
-        let s1: &[u8] = b"12345";
-        let s2: &[u8] = b"67890";
-        let s3: &[u8] = b"abcde";
-        let sep: &[u8] = b":";
-        assert_eq!(vec![s1, s2, s3].join(sep), b"12345:67890:abcde");
-
-        let mut v = Vec::new();
-        v.insert(0, "aaa");
-        v.push("bbb");
-
-        assert_eq!(v[0], "aaa");
-
-        let mut v = Vec::with_capacity(10);
-        unsafe {
-            v.set_len(10);
-        }
-        v[9] = 9;
-        println!("v[0] = {}", v[0]);
-        println!("v[9] = {}", v[9]);
-
-        let mut v = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 0];
-        v[1..5].clone_from_slice(&[5, 4, 3, 2][..]);
-        assert_eq!(v, vec![1, 5, 4, 3, 2, 6, 7, 8, 9, 0]);
-
-        let mut ary = Box::new(Vec::new());
-        ary.push(100);
-        ary.push(99);
-        ary.push(98);
-        ary.push(97);
-        ary.push(96);
-        assert_eq!(ary.as_slice(), vec![100, 99, 98, 97, 96].as_slice());
-    }
-
-    #[test]
-    fn t_arc() {
-        use std::sync::{Arc, Mutex};
-        struct TestArc {
-            bytes: Arc<Vec<u8>>,
-        }
-
-        let mut ref_bytes = None;
-        {
-            let bytes = Arc::new(vec![1, 2, 3, 4]);
-            let t = TestArc { bytes };
-            ref_bytes = Some(t.bytes.clone());
-            assert_eq!(2, Arc::strong_count(&t.bytes));
-        }
-        assert!(ref_bytes.is_some());
-        assert_eq!(ref_bytes, Some(Arc::new(vec![1, 2, 3, 4])));
-        assert_eq!(1, Arc::strong_count(&ref_bytes.unwrap()));
-
-        use crate::oop::Oop;
-        let null1 = Arc::new(Oop::Null);
-        let null2 = Arc::new(Oop::Null);
-        assert!(!Arc::ptr_eq(&null1, &null2));
-        let null11 = null1.clone();
-        assert!(Arc::ptr_eq(&null1, &null11));
-
-        let str1 = Vec::from("hello, world");
-        let str1 = new_ref!(str1);
-        let v1 = Arc::new(Mutex::new(Box::new(OopDesc::new_const_utf8(str1))));
-        let v2 = v1.clone();
-        assert!(Arc::ptr_eq(&v1, &v2));
-
-        //raw arc eq
-        let v1 = Arc::new(Mutex::new(Box::new(1000)));
-        let v1_clone = v1.clone();
-        let v1 = Arc::into_raw(v1) as i32;
-        let v2 = Arc::into_raw(v1_clone) as i32;
-        assert_eq!(v1, v2);
-
-        //raw Arc not eq
-        let v1 = Arc::new(Mutex::new(Box::new(1000)));
-        let v2 = Arc::new(Mutex::new(Box::new(1000)));
-        let v1 = Arc::into_raw(v1) as i32;
-        let v2 = Arc::into_raw(v2) as i32;
-        assert_ne!(v1, v2);
-
-        //hash eq
-        let s1 = String::from_utf8_lossy(b"abcde").to_string();
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        s1.hash(&mut hasher);
-        let s1_hash = hasher.finish();
-        let s2 = String::from_utf8_lossy(b"abcde").to_string();
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        s2.hash(&mut hasher);
-        let s2_hash = hasher.finish();
-        assert_eq!(s1_hash, s2_hash);
-
-        //hash not eq
-        let s1 = String::from_utf8_lossy(b"abcde").to_string();
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        s1.hash(&mut hasher);
-        let s1_hash = hasher.finish();
-        let s2 = String::from_utf8_lossy(b"abcde2").to_string();
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        s2.hash(&mut hasher);
-        let s2_hash = hasher.finish();
-        assert_ne!(s1_hash, s2_hash);
-
-        //hash eq
-        let s1 = "abcde".as_bytes();
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        s1.hash(&mut hasher);
-        let s1_hash = hasher.finish();
-        let s2 = String::from_utf8_lossy(b"abcde").to_string();
-        let s2 = s2.as_bytes();
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        s2.hash(&mut hasher);
-        let s2_hash = hasher.finish();
-        assert_eq!(s1_hash, s2_hash);
-    }
-
-    #[test]
-    fn t_libc() {
-        let ptr = unsafe { libc::malloc(std::mem::size_of::<u8>() * 8) as *mut u8 };
-
-        let l = 0x0102030405060708i64;
-        let v = l.to_be_bytes();
-        let v = vec![v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]];
-        unsafe {
-            libc::memcpy(
-                ptr as *mut libc::c_void,
-                v.as_ptr() as *const libc::c_void,
-                8,
-            );
-        }
-
-        let v = unsafe { *ptr } as u8;
-        assert_eq!(v, 0x01u8);
-    }
+fn banner() -> String {
+    let build = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    format!(
+        "jvm {} ({} build, {}-{})\nclass file versions {}.0 - {}.0, class library java 1.8\n",
+        env!("CARGO_PKG_VERSION"),
+        build,
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        classfile::MIN_MAJOR_VERSION,
+        classfile::MAX_MAJOR_VERSION
+    )
+}
 
-    #[test]
-    fn t_hash() {
-        use std::collections::hash_map::DefaultHasher;
-        fn calc_hash(s: String) -> u64 {
-            let mut hasher = DefaultHasher::new();
-            s.hash(&mut hasher);
-            hasher.finish()
+//the natives of the vm, not those an embedder registers or overrides. A
+//class missing here, with native methods, throws UnsatisfiedLinkError
+//(see -XX:+BestEffortNatives)
+fn list_natives() -> String {
+    native::init();
+    let classes = native::implemented();
+    let mut s = String::new();
+    let mut n = 0;
+    for (class, methods) in classes.iter() {
+        s.push_str(&format!("{}\n", class.replace('/', ".")));
+        for it in methods {
+            s.push_str(&format!("  {}\n", it));
         }
-
-        let s1 = String::from_utf8_lossy(b"abcde").to_string();
-        let s2 = String::from_utf8_lossy(b"abcde").to_string();
-        let hash1 = calc_hash(s1);
-        let hash2 = calc_hash(s2);
-        assert_eq!(hash1, hash2);
-
-        let mut s1 = String::from_utf8_lossy(b"abcde").to_string();
-        s1.push_str("1");
-        s1.push_str("12");
-        s1.push_str("123");
-        let mut s2 = String::from_utf8_lossy(b"abcde").to_string();
-        s2.push_str("112123");
-        let hash1 = calc_hash(s1);
-        let hash2 = calc_hash(s2);
-        assert_eq!(hash1, hash2);
-
-        let s1 = String::from_utf8_lossy(b"abcde1").to_string();
-        let s2 = String::from_utf8_lossy(b"abcde2").to_string();
-        let hash1 = calc_hash(s1);
-        let hash2 = calc_hash(s2);
-        assert_ne!(hash1, hash2)
+        n += methods.len();
     }
+    s.push_str(&format!("{} natives in {} classes\n", n, classes.len()));
+    s
 }
@@ -2,7 +2,7 @@
 use crate::classfile;
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
 use crate::oop::{Oop, OopDesc, TypeArrayValue};
-use crate::runtime::{self, require_class3, JavaThread};
+use crate::runtime::{self, require_class3, vfs, JavaThread};
 use crate::types::OopRef;
 use crate::util;
 
@@ -20,23 +20,29 @@ fn jvm_initIDs(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIRes
     Ok(None)
 }
 
-fn jvm_open0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+fn jvm_open0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let this = args.get(0).unwrap();
     let name = {
         let v = args.get(1).unwrap();
         util::oop::extract_str(v.clone())
     };
-    let fd = unsafe {
-        use std::ffi::CString;
-        let name = CString::new(name).unwrap();
-        libc::open(name.as_ptr(), libc::O_RDONLY)
-    };
-
-    set_file_descriptor_fd(this.clone(), fd);
-
-    Ok(None)
+    match vfs::open(&name, vfs::OpenMode::Read) {
+        Ok(fd) => {
+            set_file_descriptor_fd(this.clone(), fd);
+            Ok(None)
+        }
+        Err(e) => {
+            let msg = format!("{} ({})", name, vfs::reason(&e));
+            Err(runtime::exception::new(
+                jt,
+                classfile::consts::J_FILE_NOT_FOUND,
+                Some(msg),
+            ))
+        }
+    }
 }
 
+//-1 at the end of the file
 fn jvm_readBytes(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let this = args.get(0).unwrap();
     let fd = get_file_descriptor_fd(this.clone());
@@ -49,91 +55,63 @@ fn jvm_readBytes(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIRes
         let v = args.get(3).unwrap();
         util::oop::extract_int(v.clone())
     };
+    //io_util.c's readBytes, IndexOutOfBoundsException
+    let ary_len = match &byte_ary.lock().unwrap().v {
+        Oop::TypeArray(ary) => ary.len(),
+        _ => unreachable!(),
+    };
+    let range = match runtime::exception::in_bounds(ary_len, off, len) {
+        Some(range) => range,
+        None => {
+            let ex = runtime::exception::new(jt, classfile::consts::J_INDEX_OUT_OF_BOUNDS, None);
+            return Err(ex);
+        }
+    };
+    if range.is_empty() {
+        return Ok(Some(OopDesc::new_int(0)));
+    }
 
     let mut byte_ary = byte_ary.lock().unwrap();
-    let n = match &mut byte_ary.v {
-        Oop::TypeArray(ary) => match ary {
-            TypeArrayValue::Byte(ary) => {
-                let (_, ptr) = ary.split_at_mut(off as usize);
-                let ptr = ptr.as_mut_ptr() as *mut libc::c_void;
-                let n = unsafe { libc::read(fd, ptr, len as usize) };
-                // error!("readBytes n = {}", n);
-                if n > 0 {
-                    n as i32
-                } else if n == -1 {
-                    let ex = runtime::exception::new(
-                        jt,
-                        classfile::consts::J_IOEXCEPTION,
-                        Some(String::from("Read Error")),
-                    );
-                    error!("read error");
-                    return Err(ex);
-                } else {
-                    -1
-                }
-            }
-            _ => unreachable!(),
-        },
+    let r = match &mut byte_ary.v {
+        //stdin may block, a collection doesn't wait
+        Oop::TypeArray(TypeArrayValue::Byte(ary)) => {
+            runtime::safepoint::safe_region(|| vfs::read(fd, &mut ary[range]))
+        }
         _ => unreachable!(),
     };
 
-    Ok(Some(OopDesc::new_int(n)))
+    match r {
+        Ok(0) => Ok(Some(OopDesc::new_int(-1))),
+        Ok(n) => Ok(Some(OopDesc::new_int(n as i32))),
+        Err(e) => {
+            error!("read error: {}", e);
+            Err(runtime::exception::new(
+                jt,
+                classfile::consts::J_IOEXCEPTION,
+                Some(vfs::reason(&e)),
+            ))
+        }
+    }
 }
 
-fn jvm_available0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+fn jvm_available0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let this = args.get(0).unwrap();
     let fd = get_file_descriptor_fd(this.clone());
-
-    if fd == -1 {
-        unimplemented!("Stream Closed");
+    match vfs::available(fd) {
+        Ok(n) => Ok(Some(OopDesc::new_int(n.min(i32::MAX as u64) as i32))),
+        Err(e) => Err(runtime::exception::new(
+            jt,
+            classfile::consts::J_IOEXCEPTION,
+            Some(vfs::reason(&e)),
+        )),
     }
-
-    let mut size = -1i64;
-    let mut current = -1i64;
-
-    unsafe {
-        let mut stat: libc::stat = std::mem::zeroed();
-        if libc::fstat(fd, &mut stat) != -1 {
-            let mode = stat.st_mode;
-            if (mode & libc::S_IFIFO == libc::S_IFIFO)
-                || (mode & libc::S_IFCHR == libc::S_IFCHR)
-                || (mode & libc::S_IFSOCK == libc::S_IFSOCK)
-            {
-                let mut n = 0;
-                if libc::ioctl(fd, libc::FIONREAD, &mut n) >= 0 {
-                    return Ok(Some(OopDesc::new_int(n)));
-                }
-            } else if mode & libc::S_IFREG == libc::S_IFREG {
-                size = stat.st_size;
-            }
-        }
-
-        current = libc::lseek(fd, 0, libc::SEEK_CUR);
-        if current == -1 {
-            return Ok(Some(OopDesc::new_int(0)));
-        }
-
-        if size < current {
-            size = libc::lseek(fd, 0, libc::SEEK_END);
-            if size == -1 {
-                return Ok(Some(OopDesc::new_int(0)));
-            }
-
-            if libc::lseek(fd, current, libc::SEEK_SET) == -1 {
-                return Ok(Some(OopDesc::new_int(0)));
-            }
-        }
-    }
-
-    return Ok(Some(OopDesc::new_int((size - current) as i32)));
 }
 
 fn jvm_close0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let this = args.get(0).unwrap();
     let fd = get_file_descriptor_fd(this.clone());
-    unsafe {
-        libc::close(fd);
-    }
+    vfs::close(fd);
+    set_file_descriptor_fd(this.clone(), -1);
     Ok(None)
 }
 
@@ -1,8 +1,9 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::consts;
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
 use crate::oop::{self, Oop, OopDesc};
-use crate::runtime::{require_class3, JavaThread};
+use crate::runtime::{exception, require_class3, vfs, JavaThread};
 use crate::types::OopRef;
 use crate::util;
 
@@ -18,7 +19,8 @@ fn jvm_initIDs(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIRes
     Ok(None)
 }
 
-fn jvm_writeBytes(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+//an append stream is opened O_APPEND, writes go to the end
+fn jvm_writeBytes(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let os = args.get(0).unwrap();
     let fd = get_file_descriptor_fd(os.clone());
     let byte_ary = args.get(1).unwrap();
@@ -30,60 +32,49 @@ fn jvm_writeBytes(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIR
         let v = args.get(3).unwrap();
         util::oop::extract_int(v.clone())
     };
-    let append = {
-        let v = args.get(4).unwrap();
-        util::oop::extract_int(v.clone())
-    };
-
-    trace!("append = {}", append);
-
-    let v = byte_ary.lock().unwrap();
-    match &v.v {
-        Oop::TypeArray(ary) => match ary {
-            oop::TypeArrayValue::Byte(ary) => {
-                let (_, ary) = ary.split_at(off as usize);
-                let len = len as usize;
 
-                unsafe {
-                    if append == 1 {
-                        libc::lseek(fd, 0, libc::SEEK_END);
-                    }
-
-                    if -1 == libc::write(fd, ary.as_ptr() as *const libc::c_void, len) {
-                        panic!("write failed");
-                    }
-                }
+    //io_util.c's writeBytes, IndexOutOfBoundsException
+    let r = {
+        let v = byte_ary.lock().unwrap();
+        match &v.v {
+            Oop::TypeArray(oop::TypeArrayValue::Byte(ary)) => {
+                exception::in_bounds(ary.len(), off, len).map(|r| vfs::write_all(fd, &ary[r]))
             }
             t => unreachable!("t = {:?}", t),
-        },
-        _ => unreachable!(),
-    }
+        }
+    };
+    let r = match r {
+        Some(r) => r,
+        None => return Err(exception::new(jt, consts::J_INDEX_OUT_OF_BOUNDS, None)),
+    };
 
-    Ok(None)
+    match r {
+        Ok(()) => Ok(None),
+        Err(e) => Err(exception::new(
+            jt,
+            consts::J_IOEXCEPTION,
+            Some(vfs::reason(&e)),
+        )),
+    }
 }
 
-fn jvm_open0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+fn jvm_open0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let this = args.get(0).unwrap();
     let name = util::oop::extract_str(args.get(1).unwrap().clone());
     let append = {
         let v = args.get(2).unwrap();
         util::oop::extract_int(v.clone()) == 1
     };
-    let fd = unsafe {
-        use std::ffi::CString;
-        let name = CString::new(name).unwrap();
-        let mut flag = libc::O_WRONLY | libc::O_CREAT;
-        if append {
-            flag |= libc::O_APPEND;
-        } else {
-            flag |= libc::O_TRUNC;
+    match vfs::open(&name, vfs::OpenMode::Write { append }) {
+        Ok(fd) => {
+            set_file_descriptor_fd(this.clone(), fd);
+            Ok(None)
         }
-        libc::open(name.as_ptr(), flag)
-    };
-
-    set_file_descriptor_fd(this.clone(), fd);
-
-    Ok(None)
+        Err(e) => {
+            let msg = format!("{} ({})", name, vfs::reason(&e));
+            Err(exception::new(jt, consts::J_FILE_NOT_FOUND, Some(msg)))
+        }
+    }
 }
 
 fn get_file_descriptor_fd(fos: OopRef) -> i32 {
@@ -14,6 +14,22 @@ pub fn get_primitive_class_mirror(key: &str) -> Option<OopRef> {
     util::sync_call(&PRIM_MIRROS, |mirros| mirros.get(key).map(|it| it.clone()))
 }
 
+//the mirrors of the primitive types & their arrays, by key, see
+//runtime::snapshot
+pub fn primitive_class_mirrors() -> Vec<(String, OopRef)> {
+    let mut v: Vec<(String, OopRef)> = util::sync_call(&PRIM_MIRROS, |mirros| {
+        mirros.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    });
+    v.sort_by(|a, b| a.0.cmp(&b.0));
+    v
+}
+
+pub fn set_primitive_class_mirror(key: &str, mirror: OopRef) {
+    util::sync_call_ctx(&PRIM_MIRROS, |mirros| {
+        mirros.insert(key.to_string(), mirror);
+    });
+}
+
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
     vec![
         new_fn("registerNatives", "()V", Box::new(jvm_registerNatives)),
@@ -50,6 +66,11 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
             "(Z)[Ljava/lang/reflect/Constructor;",
             Box::new(jvm_getDeclaredConstructors0),
         ),
+        new_fn(
+            "getDeclaredMethods0",
+            "(Z)[Ljava/lang/reflect/Method;",
+            Box::new(jvm_getDeclaredMethods0),
+        ),
         new_fn("getModifiers", "()I", Box::new(jvm_getModifiers)),
         new_fn(
             "getSuperclass",
@@ -77,6 +98,17 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
             "(Ljava/lang/Object;)Z",
             Box::new(jvm_isInstance),
         ),
+        new_fn("isRecord0", "()Z", Box::new(jvm_isRecord0)),
+        new_fn(
+            "getRecordComponents0",
+            "()[Ljava/lang/reflect/RecordComponent;",
+            Box::new(jvm_getRecordComponents0),
+        ),
+        new_fn(
+            "getPermittedSubclasses0",
+            "()[Ljava/lang/Class;",
+            Box::new(jvm_getPermittedSubclasses0),
+        ),
     ]
 }
 
@@ -138,9 +170,10 @@ pub fn create_mirror(cls: ClassRef) {
     if is_fixed {
         let mirror = OopDesc::new_mirror(cls.clone());
         let mut cls = cls.lock().unwrap();
-        trace!("mirror created: {}", unsafe {
-            std::str::from_utf8_unchecked(cls.name.as_slice())
-        });
+        trace!(
+            "mirror created: {}",
+            util::mutf8::display(cls.name.as_slice())
+        );
         cls.set_mirror(mirror);
     } else {
         let cls_back = cls.clone();
@@ -363,18 +396,20 @@ fn jvm_forName0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResu
 
     match cls {
         Some(cls) => {
-            {
-                let mut cls = cls.lock().unwrap();
-                cls.init_class(jt);
-                //                trace!("finish init_class: {}", String::from_utf8_lossy(*c));
-            }
             oop::class::init_class_fully(jt, cls.clone());
+            if let Some(ex) = jt.take_ex() {
+                return Err(ex);
+            }
 
             let mirror = { cls.lock().unwrap().get_mirror() };
 
             Ok(Some(mirror))
         }
         None => {
+            //found, but not loaded: ClassFormatError, UnsupportedClassVersionError
+            if let Some((cls, msg)) = runtime::take_load_error() {
+                return Err(runtime::exception::new(jt, cls, Some(msg)));
+            }
             error!("forName0, NotFound: {}", java_name);
             let ex = runtime::exception::new(jt, classfile::consts::J_CLASS_NOT_FOUND, None);
             Err(ex)
@@ -494,6 +529,43 @@ fn jvm_getDeclaredConstructors0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<Oop
     Ok(Some(OopDesc::new_ref_ary2(ary_cls, methods)))
 }
 
+fn jvm_getDeclaredMethods0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let mirror_target = {
+        let arg0 = args[0].lock().unwrap();
+        match &arg0.v {
+            Oop::Mirror(mirror) => mirror.target.clone(),
+            _ => unreachable!(),
+        }
+    };
+
+    let arg1 = args.get(1).unwrap();
+    let public_only = util::oop::extract_int(arg1.clone()) == 1;
+
+    //primitive & array classes declare no methods
+    let all_methods = match &mirror_target {
+        Some(target) => {
+            let cls = target.lock().unwrap();
+            match &cls.kind {
+                oop::class::ClassKind::Instance(inst) => Some(inst.all_methods.clone()),
+                _ => None,
+            }
+        }
+        None => None,
+    };
+
+    let mut methods = Vec::new();
+    for (_, m) in all_methods.unwrap_or_default() {
+        let name = m.method.name.as_slice();
+        if name == b"<init>" || name == b"<clinit>" || (public_only && !m.method.is_public()) {
+            continue;
+        }
+        methods.push(runtime::reflect::new_method(jt, m));
+    }
+
+    let ary_cls = require_class3(None, b"[Ljava/lang/reflect/Method;").unwrap();
+    Ok(Some(OopDesc::new_ref_ary2(ary_cls, methods)))
+}
+
 pub fn jvm_getModifiers(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let v = args.get(0).unwrap();
     let v = v.lock().unwrap();
@@ -567,8 +639,8 @@ fn jvm_getComponentType(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -
     let v = match &cls.kind {
         oop::class::ClassKind::TypeArray(type_ary_cls) => {
             let vt = type_ary_cls.value_type.into();
-            let key = unsafe { std::str::from_utf8_unchecked(vt) };
-            util::sync_call(&PRIM_MIRROS, |mirros| mirros.get(key).map(|it| it.clone()))
+            let key = util::mutf8::display(vt);
+            util::sync_call(&PRIM_MIRROS, |mirros| mirros.get(key.as_ref()).cloned())
         }
         oop::class::ClassKind::ObjectArray(obj_ary_cls) => {
             let component = obj_ary_cls.component.clone().unwrap();
@@ -620,11 +692,9 @@ fn jvm_getEnclosingMethod0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>)
     if em.method_index != 0 {
         let (name, desc) = constant_pool::get_name_and_type(&cls_file.cp, em.method_index as usize);
         let name = name.unwrap();
-        let name = unsafe { std::str::from_utf8_unchecked(name.as_slice()) };
         let desc = desc.unwrap();
-        let desc = unsafe { std::str::from_utf8_unchecked(desc.as_slice()) };
-        elms.push(util::oop::new_java_lang_string2(jt, name));
-        elms.push(util::oop::new_java_lang_string2(jt, desc));
+        elms.push(util::oop::new_java_lang_string3(jt, name.as_slice()));
+        elms.push(util::oop::new_java_lang_string3(jt, desc.as_slice()));
     } else {
         elms.push(oop::consts::get_null());
         elms.push(oop::consts::get_null());
@@ -713,3 +783,72 @@ fn jvm_isInstance(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIR
 
     Ok(Some(OopDesc::new_int(v)))
 }
+
+/*
+Records & sealed classes, Class natives of JDK 16+: the class library of
+a newer JDK asks for them, the JDK 8 one never does. A primitive or array
+mirror is neither.
+*/
+pub fn mirror_target(mirror: &OopRef) -> Option<ClassRef> {
+    match &mirror.lock().unwrap().v {
+        Oop::Mirror(mirror) => mirror.target.clone(),
+        _ => None,
+    }
+}
+
+fn jvm_isRecord0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let v = match mirror_target(args.first().unwrap()) {
+        Some(target) => target.lock().unwrap().record_components().is_some(),
+        None => false,
+    };
+    Ok(Some(OopDesc::new_int(v as i32)))
+}
+
+//null if not a record
+fn jvm_getRecordComponents0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let target = match mirror_target(args.first().unwrap()) {
+        Some(target) => target,
+        None => return Ok(Some(oop::consts::get_null())),
+    };
+    let (cp, components) = {
+        let cls = target.lock().unwrap();
+        match (&cls.kind, cls.record_components()) {
+            (ClassKind::Instance(inst), Some(components)) => {
+                (inst.class_file.cp.clone(), components.clone())
+            }
+            _ => return Ok(Some(oop::consts::get_null())),
+        }
+    };
+
+    let elms = components
+        .iter()
+        .map(|it| runtime::reflect::new_record_component(jt, &target, &cp, it))
+        .collect();
+    let ary_cls = require_class3(None, classfile::consts::J_RECORD_COMPONENT_ARY).unwrap();
+    Ok(Some(OopDesc::new_ref_ary2(ary_cls, elms)))
+}
+
+//null if not sealed, the subclasses that fail to load are left out
+fn jvm_getPermittedSubclasses0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let target = match mirror_target(args.first().unwrap()) {
+        Some(target) => target,
+        None => return Ok(Some(oop::consts::get_null())),
+    };
+    let (cp, classes) = {
+        let cls = target.lock().unwrap();
+        match (&cls.kind, cls.permitted_subclasses()) {
+            (ClassKind::Instance(inst), Some(classes)) => {
+                (inst.class_file.cp.clone(), classes.clone())
+            }
+            _ => return Ok(Some(oop::consts::get_null())),
+        }
+    };
+
+    let mirrors = classes
+        .iter()
+        .filter_map(|&it| require_class2(it, &cp))
+        .map(|cls| cls.lock().unwrap().get_mirror())
+        .collect();
+    let ary_cls = require_class3(None, b"[Ljava/lang/Class;").unwrap();
+    Ok(Some(OopDesc::new_ref_ary2(ary_cls, mirrors)))
+}
@@ -1,8 +1,9 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::{constant_pool, consts};
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
-use crate::oop;
-use crate::runtime::{self, JavaThread};
+use crate::oop::{self, Oop, TypeArrayValue};
+use crate::runtime::{self, exception, JavaThread};
 use crate::types::OopRef;
 use crate::util;
 
@@ -24,6 +25,11 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
             "(Ljava/lang/String;)Ljava/lang/Class;",
             Box::new(jvm_findBootstrapClass),
         ),
+        new_fn(
+            "defineClass1",
+            "(Ljava/lang/String;[BIILjava/security/ProtectionDomain;Ljava/lang/String;)Ljava/lang/Class;",
+            Box::new(jvm_defineClass1),
+        ),
     ]
 }
 
@@ -38,12 +44,15 @@ fn jvm_findBuiltinLib(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) ->
     Ok(None)
 }
 
+//a class the loader defined, or a loaded one
 fn jvm_findLoadedClass0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let name = args.get(1).unwrap();
     let name = util::oop::extract_str(name.clone());
     info!("findLoadedClass0: {}", name);
     let name = name.replace(".", util::FILE_SEP);
-    let v = match runtime::sys_dic_find(name.as_bytes()) {
+    let class = runtime::find_defined(&args[0], name.as_bytes())
+        .or_else(|| runtime::sys_dic_find(name.as_bytes()));
+    let v = match class {
         Some(cls) => {
             let cls = cls.lock().unwrap();
             cls.get_mirror()
@@ -58,3 +67,69 @@ fn jvm_findBootstrapClass(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>)
     info!("findBootstrapClass");
     jvm_findLoadedClass0(_jt, _env, args)
 }
+
+//defineClass1(name, b, off, len, pd, source), see runtime::define_class
+fn jvm_defineClass1(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let loader = &args[0];
+    let name = args.get(1).unwrap();
+    let off = util::oop::extract_int(args.get(3).unwrap().clone());
+    let len = util::oop::extract_int(args.get(4).unwrap().clone());
+
+    let buf = {
+        let v = args.get(2).unwrap().lock().unwrap();
+        match &v.v {
+            Oop::TypeArray(TypeArrayValue::Byte(ary)) => {
+                Some(exception::in_bounds(ary.len(), off, len).map(|r| ary[r].to_vec()))
+            }
+            Oop::Null => None,
+            t => unreachable!("t = {:?}", t),
+        }
+    };
+    let buf = match buf {
+        Some(Some(buf)) => buf,
+        Some(None) => {
+            let ex = exception::new(jt, consts::J_ARRAY_INDEX_OUT_OF_BOUNDS, None);
+            return Err(ex);
+        }
+        None => return Err(exception::new(jt, consts::J_NPE, None)),
+    };
+
+    let cf = match runtime::parse_class(buf.as_slice()) {
+        Ok(cf) => cf,
+        Err((cls, msg)) => {
+            let ex = exception::new(jt, cls, Some(msg));
+            return Err(ex);
+        }
+    };
+
+    //the name is checked against the bytes, if given
+    let actual = constant_pool::get_class_name(&cf.cp, cf.this_class as usize).unwrap();
+    let is_null = matches!(name.lock().unwrap().v, Oop::Null);
+    if !is_null {
+        let name = util::oop::extract_str(name.clone()).replace('.', util::FILE_SEP);
+        if name.as_bytes() != actual.as_slice() {
+            let msg = format!(
+                "{} (wrong name: {})",
+                name,
+                String::from_utf8_lossy(actual.as_slice())
+            );
+            let ex = exception::new(jt, consts::J_NO_CLASS_DEF_FOUND, Some(msg));
+            return Err(ex);
+        }
+    }
+
+    match runtime::define_class(loader, cf) {
+        Ok(cls) => {
+            let mirror = cls.lock().unwrap().get_mirror();
+            Ok(Some(mirror))
+        }
+        Err(name) => {
+            let msg = format!(
+                "duplicate class definition: {}",
+                String::from_utf8_lossy(name.as_slice())
+            );
+            let ex = exception::new(jt, consts::J_LINKAGE_ERROR, Some(msg));
+            Err(ex)
+        }
+    }
+}
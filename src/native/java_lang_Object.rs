@@ -1,10 +1,15 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::consts;
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
 use crate::oop::{Oop, OopDesc};
-use crate::runtime::JavaThread;
+use crate::runtime::gc::refs;
+use crate::runtime::monitor::{self, WaitErr};
+use crate::runtime::{cmp, exception, require_class3, JavaThread};
 use crate::types::OopRef;
 use crate::util;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
     vec![
@@ -12,6 +17,8 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
         new_fn("hashCode", "()I", Box::new(jvm_hashCode)),
         new_fn("clone", "()Ljava/lang/Object;", Box::new(jvm_clone)),
         new_fn("getClass", "()Ljava/lang/Class;", Box::new(jvm_getClass)),
+        new_fn("wait", "(J)V", Box::new(jvm_wait)),
+        new_fn("notify", "()V", Box::new(jvm_notify)),
         new_fn("notifyAll", "()V", Box::new(jvm_notifyAll)),
     ]
 }
@@ -20,31 +27,53 @@ fn jvm_registerNatives(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -
     Ok(None)
 }
 
+//the identity hash, kept in the header, see OopDesc::identity_hash
 pub fn jvm_hashCode(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
-    let use_cache = true;
-    let v = args.get(0).unwrap();
+    let v = args.first().unwrap();
+    let hash = v.lock().unwrap().identity_hash();
+    Ok(Some(OopDesc::new_int(hash)))
+}
 
-    if use_cache {
-        let hash = { v.lock().unwrap().hash_code.clone() };
-        match hash {
-            Some(hash) => Ok(Some(OopDesc::new_int(hash))),
-            None => {
-                let hash = util::oop::hash_code(v.clone()) as i32;
-                let mut v = v.lock().unwrap();
-                v.hash_code = Some(hash);
-                Ok(Some(OopDesc::new_int(hash)))
-            }
+//a shallow copy, arrays are always Cloneable, a Class never is
+fn jvm_clone(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let this = args.first().unwrap();
+    let (v, cls) = {
+        let v = this.lock().unwrap();
+        match &v.v {
+            Oop::Inst(inst) => (Some(v.v.clone()), Some(inst.class.clone())),
+            Oop::Array(_) | Oop::TypeArray(_) => (Some(v.v.clone()), None),
+            Oop::Mirror(_) => (None, None),
+            t => unreachable!("t = {:?}", t),
         }
-    } else {
-        let hash = util::oop::hash_code(v.clone()) as i32;
-        Ok(Some(OopDesc::new_int(hash)))
-    }
-}
+    };
 
-fn jvm_clone(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
-    //    let java_lang_Cloneable = require_class3(None, b"java/lang/Cloneable").unwrap();
-    let this_obj = args.get(0).unwrap();
-    Ok(Some(this_obj.clone()))
+    let cloneable = match &cls {
+        Some(cls) => {
+            let cloneable = require_class3(None, consts::J_CLONEABLE).unwrap();
+            cmp::instance_of(cls.clone(), cloneable)
+        }
+        None => v.is_some(),
+    };
+    let v = match v {
+        Some(v) if cloneable => v,
+        _ => {
+            let name = match &cls {
+                Some(cls) => cls.lock().unwrap().name.clone(),
+                None => Arc::new(Box::new(consts::J_CLASS.to_vec())),
+            };
+            let msg = util::mutf8::display(name.as_slice()).replace('/', ".");
+            return Err(exception::new(jt, consts::J_CLONE_NOT_SUPPORTED, Some(msg)));
+        }
+    };
+
+    let copy = match OopDesc::try_new_clone(v) {
+        Ok(copy) => copy,
+        Err(e) => return Err(exception::out_of_memory(jt, e.msg())),
+    };
+    if cls.is_some_and(|cls| cls.lock().unwrap().has_finalizer()) {
+        refs::register_finalizer(jt, &copy);
+    }
+    Ok(Some(copy))
 }
 
 fn jvm_getClass(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
@@ -76,6 +105,45 @@ fn jvm_getClass(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIRes
     Ok(Some(mirror))
 }
 
-fn jvm_notifyAll(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
-    Ok(None)
+//wait(long), timeout in millis, 0 waits until notified
+fn jvm_wait(jt: &mut JavaThread, env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let this = &args[0];
+    let millis = util::oop::extract_long(args[1].clone());
+    if millis < 0 {
+        let msg = Some("timeout value is negative".to_string());
+        return Err(exception::new(jt, consts::J_IAE, msg));
+    }
+
+    let timeout = if millis == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(millis as u64))
+    };
+    let thread = env.lock().unwrap().java_thread_obj.clone();
+    match monitor::wait(this, thread.as_ref(), timeout) {
+        Ok(()) => Ok(None),
+        Err(WaitErr::NotOwner) => Err(not_owner(jt)),
+        Err(WaitErr::Interrupted) => Err(exception::new(jt, consts::J_INTERRUPTED, None)),
+    }
+}
+
+fn jvm_notify(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    notify(jt, &args[0], false)
+}
+
+fn jvm_notifyAll(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    notify(jt, &args[0], true)
+}
+
+fn notify(jt: &mut JavaThread, this: &OopRef, all: bool) -> JNIResult {
+    match monitor::notify(this, all) {
+        Ok(()) => Ok(None),
+        Err(()) => Err(not_owner(jt)),
+    }
+}
+
+//as hotspot says it
+fn not_owner(jt: &mut JavaThread) -> OopRef {
+    let msg = Some("current thread is not owner".to_string());
+    exception::new(jt, consts::J_IMSE, msg)
 }
@@ -1,19 +1,72 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::consts;
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
 use crate::oop::OopDesc;
-use crate::runtime::JavaThread;
+use crate::runtime::{self, JavaCall, JavaThread, Stack};
 use crate::types::OopRef;
+use crate::util;
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
-    vec![new_fn(
-        "availableProcessors",
-        "()I",
-        Box::new(jvm_availableProcessors),
-    )]
+    vec![
+        new_fn(
+            "availableProcessors",
+            "()I",
+            Box::new(jvm_availableProcessors),
+        ),
+        new_fn("gc", "()V", Box::new(jvm_gc)),
+        new_fn("runFinalization0", "()V", Box::new(jvm_runFinalization0)),
+        new_fn("maxMemory", "()J", Box::new(jvm_maxMemory)),
+        new_fn("totalMemory", "()J", Box::new(jvm_totalMemory)),
+        new_fn("freeMemory", "()J", Box::new(jvm_freeMemory)),
+    ]
 }
 
 //fixme:
 fn jvm_availableProcessors(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
     Ok(Some(OopDesc::new_int(1)))
 }
+
+//the pending references go to the Reference Handler, see gc::refs
+fn jvm_gc(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    if runtime::flags::is_use_gc() {
+        runtime::gc::collect();
+    }
+    Ok(None)
+}
+
+//Finalizer.runFinalization, finalize() of those queued, on this thread
+fn jvm_runFinalization0(jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    let mir = runtime::sys_dic_find(consts::J_FINALIZER).and_then(|cls| {
+        let id = util::new_method_id(b"runFinalization", b"()V");
+        cls.lock().unwrap().get_static_method(id).ok()
+    });
+    if let Some(mir) = mir {
+        let mut stack = Stack::new(0);
+        let mut jc = JavaCall::new_with_args(jt, mir, vec![]);
+        jc.invoke(jt, &mut stack, true);
+    }
+    Ok(None)
+}
+
+//Long.MAX_VALUE without -Xmx, as hotspot for an unlimited heap
+fn jvm_maxMemory(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    let max = runtime::flags::max_heap_size().map_or(i64::MAX, |it| it as i64);
+    Ok(Some(OopDesc::new_long(max)))
+}
+
+fn jvm_totalMemory(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    let used = runtime::gc::heap_used();
+    Ok(Some(OopDesc::new_long(total_memory(used) as i64)))
+}
+
+fn jvm_freeMemory(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    let used = runtime::gc::heap_used();
+    let free = total_memory(used) - used;
+    Ok(Some(OopDesc::new_long(free as i64)))
+}
+
+//the heap grows on demand, at least -Xms
+fn total_memory(used: usize) -> usize {
+    used.max(runtime::flags::initial_heap_size())
+}
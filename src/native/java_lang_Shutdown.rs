@@ -0,0 +1,24 @@
+#![allow(non_snake_case)]
+
+use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
+use crate::runtime::{self, JavaThread};
+use crate::types::OopRef;
+use crate::util;
+
+pub fn get_native_methods() -> Vec<JNINativeMethod> {
+    vec![
+        new_fn("beforeHalt", "()V", Box::new(jvm_beforeHalt)),
+        new_fn("halt0", "(I)V", Box::new(jvm_halt0)),
+    ]
+}
+
+fn jvm_beforeHalt(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    Ok(None)
+}
+
+//System.exit & Runtime.halt, after the shutdown hooks ran, never returns:
+//the vm stops at a safepoint, see runtime::safepoint
+fn jvm_halt0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let status = util::oop::extract_int(args[0].clone());
+    runtime::safepoint::halt(status)
+}
@@ -3,6 +3,14 @@
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
 use crate::runtime::JavaThread;
 use crate::types::OopRef;
+use crate::util;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+lazy_static! {
+    //string table, chars -> the canonical String
+    static ref INTERNED: Mutex<HashMap<Vec<u16>, OopRef>> = Mutex::new(HashMap::new());
+}
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
     vec![new_fn(
@@ -14,5 +22,20 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
 
 fn jvm_intern(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let v = args.get(0).unwrap();
-    Ok(Some(v.clone()))
+    Ok(Some(intern(v.clone())))
+}
+
+//the canonical String with the chars of v, v itself the first time
+pub fn intern(v: OopRef) -> OopRef {
+    let chars = util::oop::extract_java_lang_string_value(v.clone());
+    util::sync_call_ctx(&INTERNED, |it| it.entry(chars).or_insert(v).clone())
+}
+
+//the canonical Strings in the order of their chars, see runtime::snapshot
+pub fn interned() -> Vec<OopRef> {
+    let mut v: Vec<(Vec<u16>, OopRef)> = util::sync_call_ctx(&INTERNED, |it| {
+        it.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    });
+    v.sort_by(|a, b| a.0.cmp(&b.0));
+    v.into_iter().map(|it| it.1).collect()
 }
@@ -1,12 +1,14 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::consts;
 use crate::native::{self, new_fn, JNIEnv, JNINativeMethod, JNIResult};
-use crate::oop::{self, Oop, OopDesc};
+use crate::oop::class::ClassKind;
+use crate::oop::{Oop, OopDesc, TypeArrayValue};
 use crate::runtime::JavaCall;
-use crate::runtime::{self, JavaThread};
-use crate::types::OopRef;
+use crate::runtime::{self, cmp, exception, JavaThread};
+use crate::types::{ClassRef, OopRef};
 use crate::util;
-use std::sync::Arc;
+use std::ops::Range;
 use std::time::SystemTime;
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
@@ -51,40 +53,149 @@ fn jvm_registerNatives(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -
     Ok(None)
 }
 
-fn jvm_arraycopy(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+fn jvm_arraycopy(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let src = args.get(0).unwrap();
     let src_pos = util::oop::extract_int(args.get(1).unwrap().clone());
     let dest = args.get(2).unwrap();
     let dest_pos = util::oop::extract_int(args.get(3).unwrap().clone());
     let length = util::oop::extract_int(args.get(4).unwrap().clone());
 
-    //todo: do check & throw exception
+    arraycopy(jt, src, src_pos, dest, dest_pos, length)?;
+    Ok(None)
+}
+
+//an arraycopy operand
+enum Ary {
+    Null,
+    NotArray,
+    //"[I"..., the length
+    Type(&'static [u8], usize),
+    Object(ClassRef, usize),
+}
+
+fn ary_of(v: &OopRef) -> Ary {
+    match &v.lock().unwrap().v {
+        Oop::Null => Ary::Null,
+        Oop::TypeArray(ary) => Ary::Type(ary.desc(), ary.len()),
+        Oop::Array(ary) => Ary::Object(ary.class.clone(), ary.elements.len()),
+        _ => Ary::NotArray,
+    }
+}
+
+/*
+also the System.arraycopy intrinsic, see runtime::intrinsics
 
+The checks of hotspot 8, its ArrayStoreException &
+ArrayIndexOutOfBoundsException have no message. The elements of an
+object array are checked against dest's component type one by one, unless
+src's type is dest's or a subtype, the ones before a failing element are
+copied.
+
+The region is copied out of src before dest is locked: never two arrays
+locked at once, src & dest may be the same array.
+*/
+pub fn arraycopy(
+    jt: &mut JavaThread,
+    src: &OopRef,
+    src_pos: i32,
+    dest: &OopRef,
+    dest_pos: i32,
+    length: i32,
+) -> Result<(), OopRef> {
+    let (src_len, dest_len, classes) = match (ary_of(src), ary_of(dest)) {
+        (Ary::Null, _) | (_, Ary::Null) => return Err(exception::new(jt, consts::J_NPE, None)),
+        (Ary::Type(s, src_len), Ary::Type(d, dest_len)) if s == d => (src_len, dest_len, None),
+        (Ary::Object(s, src_len), Ary::Object(d, dest_len)) => (src_len, dest_len, Some((s, d))),
+        _ => return Err(exception::new(jt, consts::J_ARRAY_STORE, None)),
+    };
+    let src_range = exception::check_range(jt, src_len, src_pos, length)?;
+    let dest_range = exception::check_range(jt, dest_len, dest_pos, length)?;
     if length == 0 {
-        return Ok(None);
+        return Ok(());
     }
 
-    //优化：同一个对象，不可同时上锁，所以需要多一次临时拷贝，对大数组是个考验
-    let is_same_obj = Arc::ptr_eq(src, dest);
-    if is_same_obj {
-        arraycopy_same_obj(
-            src.clone(),
-            src_pos as usize,
-            dest.clone(),
-            dest_pos as usize,
-            length as usize,
-        );
-    } else {
-        arraycopy_diff_obj(
-            src.clone(),
-            src_pos as usize,
-            dest.clone(),
-            dest_pos as usize,
-            length as usize,
-        );
+    match classes {
+        None => {
+            let region = match &src.lock().unwrap().v {
+                Oop::TypeArray(ary) => copy_of_range(ary, src_range),
+                _ => unreachable!(),
+            };
+            match &mut dest.lock().unwrap().v {
+                Oop::TypeArray(ary) => copy_into(ary, dest_range.start, &region),
+                _ => unreachable!(),
+            }
+            Ok(())
+        }
+        Some((src_cls, dest_cls)) => {
+            let region = match &src.lock().unwrap().v {
+                Oop::Array(ary) => ary.elements[src_range].to_vec(),
+                _ => unreachable!(),
+            };
+            let n = if cmp::instance_of(src_cls, dest_cls.clone()) {
+                region.len()
+            } else {
+                let component = element_class(&dest_cls);
+                region
+                    .iter()
+                    .take_while(|v| match cmp::oop_class(v) {
+                        Some(cls) => cmp::instance_of(cls, component.clone()),
+                        None => true,
+                    })
+                    .count()
+            };
+            match &mut dest.lock().unwrap().v {
+                Oop::Array(ary) => {
+                    let at = dest_range.start;
+                    ary.elements[at..at + n].clone_from_slice(&region[..n]);
+                }
+                _ => unreachable!(),
+            }
+            match n == region.len() {
+                true => Ok(()),
+                false => Err(exception::new(jt, consts::J_ARRAY_STORE, None)),
+            }
+        }
     }
+}
 
-    Ok(None)
+//String of String[], int[] of int[][]
+fn element_class(ary_cls: &ClassRef) -> ClassRef {
+    match &ary_cls.lock().unwrap().kind {
+        ClassKind::ObjectArray(ary) | ClassKind::TypeArray(ary) => ary
+            .get_down_type()
+            .or_else(|| ary.component.clone())
+            .unwrap(),
+        ClassKind::Instance(_) => unreachable!(),
+    }
+}
+
+fn copy_of_range(ary: &TypeArrayValue, r: Range<usize>) -> TypeArrayValue {
+    match ary {
+        TypeArrayValue::Byte(ary) => TypeArrayValue::Byte(Box::new(ary[r].to_vec())),
+        TypeArrayValue::Bool(ary) => TypeArrayValue::Bool(Box::new(ary[r].to_vec())),
+        TypeArrayValue::Char(ary) => TypeArrayValue::Char(Box::new(ary[r].to_vec())),
+        TypeArrayValue::Short(ary) => TypeArrayValue::Short(Box::new(ary[r].to_vec())),
+        TypeArrayValue::Float(ary) => TypeArrayValue::Float(Box::new(ary[r].to_vec())),
+        TypeArrayValue::Double(ary) => TypeArrayValue::Double(Box::new(ary[r].to_vec())),
+        TypeArrayValue::Int(ary) => TypeArrayValue::Int(Box::new(ary[r].to_vec())),
+        TypeArrayValue::Long(ary) => TypeArrayValue::Long(Box::new(ary[r].to_vec())),
+    }
+}
+
+//the element types are checked the same
+fn copy_into(dest: &mut TypeArrayValue, at: usize, src: &TypeArrayValue) {
+    let n = src.len();
+    match (dest, src) {
+        (TypeArrayValue::Byte(d), TypeArrayValue::Byte(s)) => d[at..at + n].copy_from_slice(s),
+        (TypeArrayValue::Bool(d), TypeArrayValue::Bool(s)) => d[at..at + n].copy_from_slice(s),
+        (TypeArrayValue::Char(d), TypeArrayValue::Char(s)) => d[at..at + n].copy_from_slice(s),
+        (TypeArrayValue::Short(d), TypeArrayValue::Short(s)) => d[at..at + n].copy_from_slice(s),
+        (TypeArrayValue::Float(d), TypeArrayValue::Float(s)) => d[at..at + n].copy_from_slice(s),
+        (TypeArrayValue::Double(d), TypeArrayValue::Double(s)) => d[at..at + n].copy_from_slice(s),
+        (TypeArrayValue::Int(d), TypeArrayValue::Int(s)) => d[at..at + n].copy_from_slice(s),
+        (TypeArrayValue::Long(d), TypeArrayValue::Long(s)) => d[at..at + n].copy_from_slice(s),
+        (d, s) => unreachable!("{:?} <- {:?}", d.desc(), s.desc()),
+    }
 }
 
 fn jvm_initProperties(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
@@ -294,155 +405,6 @@ fn jvm_getProperty(jt: &mut JavaThread, env: JNIEnv, args: Vec<OopRef>) -> JNIRe
 }
 */
 
-/*
-同一个对象不可同时lock，需要tmp buf做中转
-
-todo optimize: 如何做到不用中转，就达到copy的目的
-*/
-fn arraycopy_same_obj(src: OopRef, src_pos: usize, dest: OopRef, dest_pos: usize, length: usize) {
-    let is_type_ary = {
-        let src = src.lock().unwrap();
-        match &src.v {
-            Oop::TypeArray(_) => true,
-            Oop::Array(_) => false,
-            _ => unreachable!(),
-        }
-    };
-
-    if is_type_ary {
-        let tmp = {
-            let src = src.lock().unwrap();
-
-            let mut tmp = Vec::with_capacity(length);
-            for _ in 0..length {
-                tmp.push(0);
-            }
-
-            //just choose the needed region
-            match &src.v {
-                Oop::TypeArray(s) => match s {
-                    oop::TypeArrayValue::Char(ary) => {
-                        let (_, ary) = ary.split_at(src_pos);
-                        ary[..length].iter().for_each(|v| {
-                            tmp.push(*v);
-                        });
-                    }
-                    oop::TypeArrayValue::Byte(ary) => {
-                        let (_, ary) = ary.split_at(src_pos);
-                        ary[..length].iter().for_each(|v| {
-                            tmp.push(*v as u16);
-                        });
-                    }
-                    t => unreachable!("t = {:?}", t),
-                },
-                _ => unreachable!(),
-            }
-
-            tmp
-        };
-
-        let mut dest = dest.lock().unwrap();
-        match &mut dest.v {
-            Oop::TypeArray(ary) => match ary {
-                oop::TypeArrayValue::Char(dest) => {
-                    let (_, dest) = dest.split_at_mut(dest_pos);
-                    dest[..length].clone_from_slice(&tmp[..]);
-                }
-                oop::TypeArrayValue::Byte(dest) => {
-                    let src: Vec<u8> = tmp.iter().map(|v| *v as u8).collect();
-                    let (_, dest) = dest.split_at_mut(dest_pos);
-                    dest[..length].clone_from_slice(&src[..]);
-                }
-                t => unreachable!("t = {:?}", t),
-            },
-
-            _ => unreachable!(),
-        }
-    } else {
-        let tmp = {
-            let ary = src.lock().unwrap();
-            match &ary.v {
-                Oop::Array(ary) => {
-                    let mut tmp = Vec::with_capacity(length);
-                    for _ in 0..length {
-                        tmp.push(oop::consts::get_null());
-                    }
-
-                    let (_, ary) = ary.elements.split_at(src_pos);
-                    tmp.clone_from_slice(&ary[..length]);
-                    tmp
-                }
-                _ => unreachable!(),
-            }
-        };
-
-        let mut dest = dest.lock().unwrap();
-        match &mut dest.v {
-            Oop::Array(ary) => {
-                let (_, ary) = ary.elements.split_at_mut(dest_pos);
-                ary[..length].clone_from_slice(&tmp[..]);
-            }
-            _ => unreachable!(),
-        }
-    }
-}
-
-fn arraycopy_diff_obj(src: OopRef, src_pos: usize, dest: OopRef, dest_pos: usize, length: usize) {
-    let src = src.lock().unwrap();
-    let mut dest = dest.lock().unwrap();
-
-    let is_type_ary = {
-        match &src.v {
-            Oop::TypeArray(_) => true,
-            Oop::Array(_) => false,
-            _ => unreachable!(),
-        }
-    };
-
-    if is_type_ary {
-        match &src.v {
-            Oop::TypeArray(src_ary) => match src_ary {
-                oop::TypeArrayValue::Char(src_ary) => match &mut dest.v {
-                    Oop::TypeArray(dest_ary) => match dest_ary {
-                        oop::TypeArrayValue::Char(dest_ary) => {
-                            dest_ary[dest_pos..(dest_pos + length)]
-                                .clone_from_slice(&src_ary[src_pos..(src_pos + length)]);
-                        }
-                        _ => unreachable!(),
-                    },
-                    _ => unreachable!(),
-                },
-                oop::TypeArrayValue::Byte(src_ary) => match &mut dest.v {
-                    Oop::TypeArray(dest_ary) => match dest_ary {
-                        oop::TypeArrayValue::Byte(dest_ary) => {
-                            dest_ary[dest_pos..(dest_pos + length)]
-                                .clone_from_slice(&src_ary[src_pos..(src_pos + length)]);
-                        }
-                        _ => unreachable!(),
-                    },
-                    _ => unreachable!(),
-                },
-
-                t => unreachable!("t = {:?}", t),
-            },
-            _ => unreachable!(),
-        }
-    } else {
-        match &src.v {
-            Oop::Array(src) => match &mut dest.v {
-                Oop::Array(dest) => {
-                    dest.elements[dest_pos as usize..(dest_pos + length) as usize]
-                        .clone_from_slice(
-                            &src.elements[src_pos as usize..(src_pos + length) as usize],
-                        );
-                }
-                _ => unreachable!(),
-            },
-            _ => unreachable!(),
-        }
-    }
-}
-
 fn jvm_nanoTime(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
     let v = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
         Ok(n) => n.as_nanos(),
@@ -460,3 +422,28 @@ fn jvm_currentTimeMillis(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>)
 
     Ok(Some(OopDesc::new_long(v as i64)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_copy_into() {
+        //overlapping, the same array: as if by a temporary copy
+        let mut ary = TypeArrayValue::Int(Box::new(vec![1, 2, 3, 4]));
+        let region = copy_of_range(&ary, 0..3);
+        copy_into(&mut ary, 1, &region);
+        match &ary {
+            TypeArrayValue::Int(ary) => assert_eq!(ary.as_slice(), &[1, 1, 2, 3]),
+            _ => unreachable!(),
+        }
+
+        let mut dest = TypeArrayValue::Double(Box::new(vec![0.0; 3]));
+        let src = TypeArrayValue::Double(Box::new(vec![1.5, 2.5]));
+        copy_into(&mut dest, 1, &copy_of_range(&src, 1..2));
+        match &dest {
+            TypeArrayValue::Double(ary) => assert_eq!(ary.as_slice(), &[0.0, 2.5, 0.0]),
+            _ => unreachable!(),
+        }
+    }
+}
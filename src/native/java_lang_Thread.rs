@@ -1,10 +1,13 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::consts;
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
 use crate::oop::{self, OopDesc};
-use crate::runtime::{JavaCall, JavaThread, Stack};
+use crate::runtime::{self, exception, JavaThread};
 use crate::types::OopRef;
-use crate::util::new_method_id;
+use crate::util;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
     vec![
@@ -17,6 +20,21 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
         new_fn("setPriority0", "(I)V", Box::new(jvm_setPriority0)),
         new_fn("isAlive", "()Z", Box::new(jvm_isAlive)),
         new_fn("start0", "()V", Box::new(jvm_start0)),
+        new_fn("setName0", "(Ljava/lang/String;)V", Box::new(jvm_setName0)),
+        new_fn("interrupt0", "()V", Box::new(jvm_interrupt0)),
+        new_fn("isInterrupted", "(Z)Z", Box::new(jvm_isInterrupted)),
+        new_fn("sleep", "(J)V", Box::new(jvm_sleep)),
+        new_fn("yield", "()V", Box::new(jvm_yield)),
+        new_fn(
+            "holdsLock",
+            "(Ljava/lang/Object;)Z",
+            Box::new(jvm_holdsLock),
+        ),
+        new_fn(
+            "getThreads",
+            "()[Ljava/lang/Thread;",
+            Box::new(jvm_getThreads),
+        ),
     ]
 }
 
@@ -30,47 +48,110 @@ fn jvm_currentThread(_jt: &mut JavaThread, env: JNIEnv, _args: Vec<OopRef>) -> J
 }
 
 fn jvm_setPriority0(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
-    //todo: set native thread's priority
+    //Thread.priority is set by setPriority, host threads keep the default
+    //priority, as HotSpot without -XX:ThreadPriorityPolicy
     Ok(None)
 }
 
-fn jvm_isAlive(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
-    //todo: impl
-    Ok(Some(OopDesc::new_int(0)))
+fn jvm_isAlive(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let alive = runtime::thread::is_alive(&args[0]);
+    Ok(Some(OopDesc::new_int(alive as i32)))
 }
 
-fn jvm_start0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+fn jvm_start0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let thread_oop = args.get(0).unwrap();
-    let cls = {
-        let v = thread_oop.lock().unwrap();
-        match &v.v {
-            oop::Oop::Inst(inst) => inst.class.clone(),
-            _ => unreachable!(),
+    let thread_name = guest_thread_name(thread_oop).unwrap_or_default();
+    match runtime::thread::start(thread_oop.clone(), thread_name) {
+        Ok(()) => Ok(None),
+        Err(e) => {
+            error!("start0 failed: {}", e);
+            let msg = Some("unable to create new native thread".to_string());
+            Err(exception::new(jt, consts::J_OOM, msg))
         }
-    };
+    }
+}
 
-    let name = {
-        let cls = cls.lock().unwrap();
-        cls.name.clone()
-    };
+//Thread.setName of a started thread, renames the host thread too
+fn jvm_setName0(_jt: &mut JavaThread, env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let (this, name) = (&args[0], &args[1]);
+    let name = util::oop::extract_str(name.clone());
+    let current = env.lock().unwrap().java_thread_obj.clone();
+    match current {
+        Some(current) if Arc::ptr_eq(&current, this) => util::set_thread_name(&name),
+        _ => runtime::thread::set_host_name(this, &name),
+    }
+    Ok(None)
+}
+
+fn jvm_interrupt0(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    runtime::monitor::interrupt(&args[0]);
+    runtime::park::unpark(&args[0]);
+    Ok(None)
+}
+
+fn jvm_isInterrupted(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let clear = util::oop::extract_int(args[1].clone()) != 0;
+    let v = runtime::monitor::is_thread_interrupted(&args[0], clear);
+    Ok(Some(OopDesc::new_int(v as i32)))
+}
 
-    if name.as_slice() == "java/lang/ref/Reference$ReferenceHandler".as_bytes() {
-        Ok(None)
-    } else {
-        //todo: impl threads manager
+//static sleep(long), InterruptedException if interrupted before or
+//while sleeping
+fn jvm_sleep(jt: &mut JavaThread, env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let millis = util::oop::extract_long(args[0].clone());
+    if millis < 0 {
+        let msg = Some("timeout value is negative".to_string());
+        return Err(exception::new(jt, consts::J_IAE, msg));
+    }
 
-        let mir = {
-            let cls = cls.lock().unwrap();
-            let id = new_method_id(b"run", b"()V");
-            cls.get_virtual_method(id).unwrap()
-        };
+    let thread = env.lock().unwrap().java_thread_obj.clone();
+    let timeout = Duration::from_millis(millis as u64);
+    match runtime::monitor::sleep(thread.as_ref(), timeout) {
+        Ok(()) => Ok(None),
+        Err(()) => {
+            let msg = Some("sleep interrupted".to_string());
+            Err(exception::new(jt, consts::J_INTERRUPTED, msg))
+        }
+    }
+}
 
-        let mut jt = JavaThread::new();
-        let mut stack = Stack::new(0);
-        let args = vec![thread_oop.clone()];
-        let mut jc = JavaCall::new_with_args(&mut jt, mir, args);
-        jc.invoke(&mut jt, &mut stack, false);
+fn jvm_yield(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    std::thread::yield_now();
+    Ok(None)
+}
 
-        Ok(None)
+//Thread.name, a char[]
+//static holdsLock(obj)
+fn jvm_holdsLock(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let obj = &args[0];
+    let is_null = matches!(obj.lock().unwrap().v, oop::Oop::Null);
+    if is_null {
+        return Err(exception::new(jt, consts::J_NPE, None));
+    }
+    let v = runtime::monitor::holds_lock(obj);
+    Ok(Some(OopDesc::new_int(v as i32)))
+}
+
+//the started & not terminated threads, for Thread.getAllStackTraces
+fn jvm_getThreads(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    let threads = runtime::thread::live_threads();
+    let cls = runtime::require_class3(None, b"[Ljava/lang/Thread;").unwrap();
+    Ok(Some(OopDesc::new_ref_ary2(cls, threads)))
+}
+
+pub fn guest_thread_name(thread_oop: &OopRef) -> Option<String> {
+    let cls = runtime::require_class3(None, b"java/lang/Thread")?;
+    let name = {
+        let cls = cls.lock().unwrap();
+        let id = cls.get_field_id(b"name", b"[C", false);
+        cls.get_field_value(thread_oop.clone(), id)
+    };
+
+    let name = name.lock().unwrap();
+    match &name.v {
+        oop::Oop::TypeArray(oop::TypeArrayValue::Char(ary)) => {
+            Some(String::from_utf16_lossy(ary.as_slice()))
+        }
+        _ => None,
     }
 }
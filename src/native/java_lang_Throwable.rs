@@ -1,10 +1,13 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::consts;
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
-use crate::oop::{self, Oop, OopDesc};
-use crate::runtime::{self, require_class3, JavaThread};
-use crate::types::OopRef;
+use crate::oop::{self, Oop, OopDesc, TypeArrayValue};
+use crate::runtime::{self, exception, require_class3, JavaThread};
+use crate::types::{ClassRef, OopRef};
 use crate::util;
+use std::convert::TryFrom;
+use std::time::Instant;
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
     vec![
@@ -27,113 +30,95 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
 }
 
 fn jvm_fillInStackTrace(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
-    let elm_cls = oop::class::load_and_init(jt, b"java/lang/StackTraceElement");
-    let ary_cls = require_class3(None, b"[Ljava/lang/StackTraceElement;").unwrap();
-
+    let start = Instant::now();
     let throwable_oop = args.get(0).unwrap();
-    let callers = jt.callers.clone();
-
-    let mut traces = Vec::new();
-    for mir in callers.iter().rev() {
-        let cls_name = { mir.method.class.lock().unwrap().name.clone() };
-        let cls_name = unsafe { std::str::from_utf8_unchecked(cls_name.as_slice()) };
-        let method_name = mir.method.name.clone();
-        let method_name = unsafe { std::str::from_utf8_unchecked(method_name.as_slice()) };
-        let src_file = mir.method.src_file.clone();
-        let src_file = match src_file {
-            Some(name) => {
-                let name = unsafe { std::str::from_utf8_unchecked(name.as_slice()) };
-                util::oop::new_java_lang_string2(jt, name)
-            }
-            None => util::oop::new_java_lang_string2(jt, ""),
-        };
-
-        let elm = OopDesc::new_inst(elm_cls.clone());
-        let args = vec![
-            elm.clone(),
-            util::oop::new_java_lang_string2(jt, &cls_name),
-            util::oop::new_java_lang_string2(jt, &method_name),
-            src_file,
-            OopDesc::new_int(0),
-        ];
-        runtime::java_call::invoke_ctor(
-            jt,
-            elm_cls.clone(),
-            b"(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;I)V",
-            args,
-        );
+    let cls = exception_class(throwable_oop);
+    runtime::ex_break::on_construct(jt, throwable_oop, &cls);
 
-        traces.push(elm);
-    }
-
-    let stack_trace_ary = OopDesc::new_ref_ary2(ary_cls, traces);
-    let throwable_cls = require_class3(None, b"java/lang/Throwable").unwrap();
+    let backtrace = OopDesc::new_long_ary2(exception::capture_backtrace(jt, &cls));
+    let throwable_cls = require_class3(None, consts::J_THROWABLE).unwrap();
     {
         let cls = throwable_cls.lock().unwrap();
         let id = cls.get_field_id(b"stackTrace", b"[Ljava/lang/StackTraceElement;", false);
         cls.put_field_value(throwable_oop.clone(), id, oop::consts::get_null());
         let id = cls.get_field_id(b"backtrace", b"Ljava/lang/Object;", false);
-        cls.put_field_value(throwable_oop.clone(), id, stack_trace_ary);
+        cls.put_field_value(throwable_oop.clone(), id, backtrace);
     }
+    exception::record_fill(start);
 
     Ok(Some(throwable_oop.clone()))
 }
 
-fn jvm_getStackTraceDepth(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
-    let throwable = args.get(0).unwrap();
-    let cls = {
-        let v = throwable.lock().unwrap();
-        match &v.v {
-            Oop::Inst(inst) => inst.class.clone(),
-            _ => unreachable!(),
-        }
-    };
+fn exception_class(throwable: &OopRef) -> ClassRef {
+    let v = throwable.lock().unwrap();
+    match &v.v {
+        Oop::Inst(inst) => inst.class.clone(),
+        _ => unreachable!(),
+    }
+}
+
+//the backtrace entries, none for a preallocated instance
+fn backtrace(throwable: &OopRef) -> Vec<i64> {
+    let throwable_cls = require_class3(None, consts::J_THROWABLE).unwrap();
     let backtrace = {
-        let cls = cls.lock().unwrap();
+        let cls = throwable_cls.lock().unwrap();
         let id = cls.get_field_id(b"backtrace", b"Ljava/lang/Object;", false);
         cls.get_field_value(throwable.clone(), id)
     };
 
     let v = backtrace.lock().unwrap();
-    let v = match &v.v {
-        Oop::Array(ary) => {
-            //            error!("backtrace len = {}", ary.elements.len());
-            OopDesc::new_int(ary.elements.len() as i32)
-        }
-        Oop::Null => OopDesc::new_int(0),
-        _ => unreachable!(),
-    };
+    match &v.v {
+        Oop::TypeArray(TypeArrayValue::Long(ary)) => ary.to_vec(),
+        _ => vec![],
+    }
+}
 
-    Ok(Some(v))
+fn jvm_getStackTraceDepth(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let throwable = args.get(0).unwrap();
+    let depth = backtrace(throwable).len();
+    Ok(Some(OopDesc::new_int(depth as i32)))
 }
 
-fn jvm_getStackTraceElement(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+fn jvm_getStackTraceElement(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let throwable = args.get(0).unwrap();
     let index = util::oop::extract_int(args.get(1).unwrap().clone());
-    let cls = {
-        let v = throwable.lock().unwrap();
-        match &v.v {
-            Oop::Inst(inst) => inst.class.clone(),
-            _ => unreachable!(),
+
+    let frame = usize::try_from(index)
+        .ok()
+        .and_then(|i| backtrace(throwable).get(i).copied())
+        .and_then(exception::backtrace_frame);
+    let (mir, bci) = match frame {
+        Some(frame) => frame,
+        None => {
+            let ex = exception::new(jt, consts::J_INDEX_OUT_OF_BOUNDS, None);
+            return Err(ex);
         }
     };
-    let backtrace = {
-        let cls = cls.lock().unwrap();
-        let id = cls.get_field_id(b"backtrace", b"Ljava/lang/Object;", false);
-        cls.get_field_value(throwable.clone(), id)
-    };
 
-    let v = backtrace.lock().unwrap();
-    let v = match &v.v {
-        Oop::Array(ary) => {
-            if index >= 0 && (index as usize) < ary.elements.len() {
-                ary.elements[index as usize].clone()
-            } else {
-                oop::consts::get_null()
-            }
-        }
-        _ => unreachable!(),
+    let cls_name = mir.method.class.lock().unwrap().name.clone();
+    let cls_name = String::from_utf8_lossy(cls_name.as_slice()).replace("/", ".");
+    let method_name = String::from_utf8_lossy(mir.method.name.as_slice()).to_string();
+    let file_name = match &mir.method.src_file {
+        Some(name) => util::oop::new_java_lang_string2(jt, &String::from_utf8_lossy(name)),
+        None => oop::consts::get_null(),
     };
+    let line_number = exception::line_number(&mir, bci);
+
+    let elm_cls = oop::class::load_and_init(jt, b"java/lang/StackTraceElement");
+    let elm = OopDesc::new_inst(elm_cls.clone());
+    let args = vec![
+        elm.clone(),
+        util::oop::new_java_lang_string2(jt, &cls_name),
+        util::oop::new_java_lang_string2(jt, &method_name),
+        file_name,
+        OopDesc::new_int(line_number),
+    ];
+    runtime::java_call::invoke_ctor(
+        jt,
+        elm_cls,
+        b"(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;I)V",
+        args,
+    );
 
-    Ok(Some(v))
+    Ok(Some(elm))
 }
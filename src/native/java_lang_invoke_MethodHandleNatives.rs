@@ -0,0 +1,607 @@
+#![allow(non_snake_case)]
+
+/*
+MethodHandleNatives, MemberName resolution for java.lang.invoke
+
+A MemberName names a method, constructor or field by clazz, name, type (a
+MethodType, a Class, a descriptor String, or {rtype, ptypes}) & flags:
+  0x0000ffff  the modifiers
+  0x000f0000  IS_METHOD, IS_CONSTRUCTOR, IS_FIELD, IS_TYPE
+  0x0f000000  the reference kind, REF_getField ~ REF_invokeInterface
+
+HotSpot injects vmtarget & vmindex into MemberName, the method or field
+it resolved to. Nothing is injected here, the member is looked up again
+by clazz, name & type when a native needs it: init & getMembers set
+them all, Object.clone copies them.
+
+  vmindex  the vtable index of a virtual method, the itable index of an
+           interface method, -2 for a statically bound one, the offset of
+           a field
+*/
+
+use crate::classfile::access_flags::*;
+use crate::classfile::consts as cls_consts;
+use crate::classfile::signature::Type as ArgType;
+use crate::native::{java_lang_Class, java_lang_String};
+use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
+use crate::oop::{self, Oop, OopDesc};
+use crate::runtime::{exception, reflect, require_class3, JavaThread, Stack};
+use crate::types::{BytesRef, FieldIdRef, MethodIdRef, OopRef, U2};
+use crate::util;
+
+const MN_IS_METHOD: i32 = 0x0001_0000;
+const MN_IS_CONSTRUCTOR: i32 = 0x0002_0000;
+const MN_IS_FIELD: i32 = 0x0004_0000;
+const MN_REFERENCE_KIND_SHIFT: i32 = 24;
+const MN_REFERENCE_KIND_MASK: i32 = 0x0F;
+
+const REF_GET_FIELD: i32 = 1;
+const REF_GET_STATIC: i32 = 2;
+const REF_PUT_STATIC: i32 = 4;
+const REF_INVOKE_VIRTUAL: i32 = 5;
+const REF_INVOKE_STATIC: i32 = 6;
+const REF_INVOKE_SPECIAL: i32 = 7;
+const REF_INVOKE_INTERFACE: i32 = 9;
+
+//Method::nonvirtual_vtable_index
+const NONVIRTUAL_VTABLE_INDEX: i64 = -2;
+
+const RECOGNIZED_METHOD_MODIFIERS: U2 = 0x1DFF;
+const RECOGNIZED_FIELD_MODIFIERS: U2 = 0x50DF;
+
+pub fn get_native_methods() -> Vec<JNINativeMethod> {
+    vec![
+        new_fn("registerNatives", "()V", Box::new(jvm_registerNatives)),
+        new_fn("getConstant", "(I)I", Box::new(jvm_getConstant)),
+        new_fn(
+            "getNamedCon",
+            "(I[Ljava/lang/Object;)I",
+            Box::new(jvm_getNamedCon),
+        ),
+        new_fn(
+            "init",
+            "(Ljava/lang/invoke/MemberName;Ljava/lang/Object;)V",
+            Box::new(jvm_init),
+        ),
+        new_fn(
+            "expand",
+            "(Ljava/lang/invoke/MemberName;)V",
+            Box::new(jvm_expand),
+        ),
+        new_fn(
+            "resolve",
+            "(Ljava/lang/invoke/MemberName;Ljava/lang/Class;)Ljava/lang/invoke/MemberName;",
+            Box::new(jvm_resolve),
+        ),
+        new_fn(
+            "getMembers",
+            "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/String;ILjava/lang/Class;I[Ljava/lang/invoke/MemberName;)I",
+            Box::new(jvm_getMembers),
+        ),
+        new_fn(
+            "objectFieldOffset",
+            "(Ljava/lang/invoke/MemberName;)J",
+            Box::new(jvm_fieldOffset),
+        ),
+        new_fn(
+            "staticFieldOffset",
+            "(Ljava/lang/invoke/MemberName;)J",
+            Box::new(jvm_fieldOffset),
+        ),
+        new_fn(
+            "staticFieldBase",
+            "(Ljava/lang/invoke/MemberName;)Ljava/lang/Object;",
+            Box::new(jvm_staticFieldBase),
+        ),
+        new_fn(
+            "getMemberVMInfo",
+            "(Ljava/lang/invoke/MemberName;)Ljava/lang/Object;",
+            Box::new(jvm_getMemberVMInfo),
+        ),
+        new_fn(
+            "setCallSiteTargetNormal",
+            "(Ljava/lang/invoke/CallSite;Ljava/lang/invoke/MethodHandle;)V",
+            Box::new(jvm_setCallSiteTarget),
+        ),
+        new_fn(
+            "setCallSiteTargetVolatile",
+            "(Ljava/lang/invoke/CallSite;Ljava/lang/invoke/MethodHandle;)V",
+            Box::new(jvm_setCallSiteTarget),
+        ),
+    ]
+}
+
+enum Member {
+    Method(MethodIdRef),
+    Field(FieldIdRef),
+}
+
+fn jvm_registerNatives(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    Ok(None)
+}
+
+//the vm tuning constants, GC_COUNT_GWT..., all off
+fn jvm_getConstant(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    Ok(Some(OopDesc::new_int(0)))
+}
+
+//the named constants MethodHandleNatives verifies, none
+fn jvm_getNamedCon(_jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) -> JNIResult {
+    Ok(Some(OopDesc::new_int(0)))
+}
+
+//MemberName of a reflected Method, Constructor or Field
+fn jvm_init(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let (mn, target) = (&args[0], &args[1]);
+    let cls_name = match &target.lock().unwrap().v {
+        Oop::Inst(inst) => inst.class.lock().unwrap().name.clone(),
+        _ => return Err(exception::new(jt, cls_consts::J_NPE, None)),
+    };
+
+    let member = match cls_name.as_slice() {
+        cls_consts::J_METHOD => Member::Method(reflect::get_Method_mir(target.clone())),
+        cls_consts::J_METHOD_CTOR => {
+            let clazz = reflect::get_Constructor_clazz(target.clone());
+            let desc = reflect::get_Constructor_signature(target.clone());
+            let id = util::new_method_id(b"<init>", desc.as_bytes());
+            let cls = java_lang_Class::mirror_target(&clazz).unwrap();
+            let mir = cls.lock().unwrap().get_this_class_method(id).unwrap();
+            Member::Method(mir)
+        }
+        cls_consts::J_FIELD => {
+            let clazz = reflect::get_field(target, b"clazz", b"Ljava/lang/Class;");
+            let slot = reflect::get_field(target, b"slot", b"I");
+            let slot = util::oop::extract_int(slot) as usize;
+            let modifiers = reflect::get_field(target, b"modifiers", b"I");
+            let is_static = util::oop::extract_int(modifiers) as U2 & ACC_STATIC != 0;
+            let cls = java_lang_Class::mirror_target(&clazz).unwrap();
+            let cls = cls.lock().unwrap();
+            let fir = match &cls.kind {
+                oop::class::ClassKind::Instance(cls_obj) => match is_static {
+                    true => cls_obj.static_fields.values(),
+                    false => cls_obj.inst_fields.values(),
+                }
+                .find(|it| it.offset == slot)
+                .cloned(),
+                _ => None,
+            };
+            Member::Field(fir.unwrap())
+        }
+        _ => {
+            let msg = format!("unrecognized member {}", String::from_utf8_lossy(&cls_name));
+            return Err(exception::new(jt, cls_consts::J_INTERNAL_ERROR, Some(msg)));
+        }
+    };
+
+    fill(jt, mn, &member);
+    Ok(None)
+}
+
+//name & type are set by init, resolve & getMembers, there's no vmtarget
+//to fill them from
+fn jvm_expand(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let mn = &args[0];
+    let is_set = |name: &[u8], desc: &[u8]| !is_null(&reflect::get_field(mn, name, desc));
+    if is_set(b"clazz", b"Ljava/lang/Class;")
+        && is_set(b"name", b"Ljava/lang/String;")
+        && is_set(b"type", b"Ljava/lang/Object;")
+    {
+        return Ok(None);
+    }
+    let msg = Some("nothing to expand".to_string());
+    Err(exception::new(jt, cls_consts::J_IAE, msg))
+}
+
+//the member of clazz by name, type & reference kind, its flags set. A
+//LinkageError if there is none, MemberName.Factory keeps it as the
+//resolution
+fn jvm_resolve(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let mn = &args[0];
+    let member = lookup(jt, mn)?;
+    let flags = util::oop::extract_int(reflect::get_field(mn, b"flags", b"I"));
+    let ref_kind = (flags >> MN_REFERENCE_KIND_SHIFT) & MN_REFERENCE_KIND_MASK;
+    let flags = match &member {
+        Member::Field(fir) => field_flags(fir.field.acc_flags, ref_kind),
+        Member::Method(mir) => {
+            let is_itf = mir.method.class.lock().unwrap().is_interface();
+            let (flags, _) = method_kind(mir, is_itf);
+            //Object's methods named through an interface dispatch by vtable
+            let ref_kind = match ref_kind {
+                REF_INVOKE_INTERFACE if !is_itf => REF_INVOKE_VIRTUAL,
+                REF_INVOKE_VIRTUAL | REF_INVOKE_SPECIAL | REF_INVOKE_INTERFACE => ref_kind,
+                _ => (flags >> MN_REFERENCE_KIND_SHIFT) & MN_REFERENCE_KIND_MASK,
+            };
+            with_ref_kind(flags, ref_kind)
+        }
+    };
+    put_field(mn, b"flags", b"I", OopDesc::new_int(flags));
+    Ok(Some(mn.clone()))
+}
+
+/*
+The declared members of defc matching name & sig (null matches any) & the
+kinds of match_flags, from the skip'th on, set into the MemberNames of
+results. The number matched is returned, over results.length asks for a
+bigger array. Inherited members are not searched.
+*/
+fn jvm_getMembers(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let defc = match java_lang_Class::mirror_target(&args[0]) {
+        Some(defc) => defc,
+        None => return Ok(Some(OopDesc::new_int(0))),
+    };
+    let str_arg = |v: &OopRef| (!is_null(v)).then(|| util::oop::extract_str(v.clone()));
+    let (name, sig) = (str_arg(&args[1]), str_arg(&args[2]));
+    let match_flags = util::oop::extract_int(args[3].clone());
+    let skip = util::oop::extract_int(args[5].clone()).max(0) as usize;
+    let results = match &args[6].lock().unwrap().v {
+        Oop::Array(ary) => ary.elements.clone(),
+        _ => vec![],
+    };
+
+    let matches = |m_name: &BytesRef, m_desc: &BytesRef| {
+        name.as_ref()
+            .is_none_or(|it| it.as_bytes() == m_name.as_slice())
+            && sig
+                .as_ref()
+                .is_none_or(|it| it.as_bytes() == m_desc.as_slice())
+    };
+    //methods in class file order, then fields by id, stable for the skip
+    let (mut methods, mut fields) = (Vec::new(), Vec::new());
+    if let oop::class::ClassKind::Instance(cls_obj) = &defc.lock().unwrap().kind {
+        for mir in cls_obj.all_methods.values() {
+            let is_init = mir.method.name.as_slice() == b"<init>";
+            let kind = if is_init {
+                MN_IS_CONSTRUCTOR
+            } else {
+                MN_IS_METHOD
+            };
+            if match_flags & kind != 0
+                && mir.method.name.as_slice() != b"<clinit>"
+                && matches(&mir.method.name, &mir.method.desc)
+            {
+                methods.push(mir.clone());
+            }
+        }
+        if match_flags & MN_IS_FIELD != 0 {
+            let all = cls_obj
+                .static_fields
+                .values()
+                .chain(cls_obj.inst_fields.values());
+            for fir in all {
+                if matches(&fir.field.name, &fir.field.desc) {
+                    fields.push(fir.clone());
+                }
+            }
+        }
+    }
+    methods.sort_by_key(|it| it.offset);
+    fields.sort_by_key(|it| it.field.get_id());
+    let members: Vec<Member> = methods
+        .into_iter()
+        .map(Member::Method)
+        .chain(fields.into_iter().map(Member::Field))
+        .collect();
+
+    let n = members.len().saturating_sub(skip);
+    for (mn, member) in results.iter().zip(members.iter().skip(skip)) {
+        fill(jt, mn, member);
+    }
+    Ok(Some(OopDesc::new_int(n as i32)))
+}
+
+//objectFieldOffset & staticFieldOffset, the slot Unsafe uses
+fn jvm_fieldOffset(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    match lookup(jt, &args[0])? {
+        Member::Field(fir) => Ok(Some(OopDesc::new_long(fir.offset as i64))),
+        Member::Method(_) => Err(not_a_field(jt)),
+    }
+}
+
+fn jvm_staticFieldBase(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    match lookup(jt, &args[0])? {
+        Member::Field(fir) => Ok(Some(fir.field.class.lock().unwrap().get_mirror())),
+        Member::Method(_) => Err(not_a_field(jt)),
+    }
+}
+
+//{vmindex, vmtarget}: a field's class, or the MemberName of a method
+fn jvm_getMemberVMInfo(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let mn = &args[0];
+    let (vmindex, target) = match lookup(jt, mn)? {
+        Member::Field(fir) => {
+            let mirror = fir.field.class.lock().unwrap().get_mirror();
+            (fir.offset as i64, mirror)
+        }
+        Member::Method(mir) => {
+            let is_itf = mir.method.class.lock().unwrap().is_interface();
+            (method_kind(&mir, is_itf).1, mn.clone())
+        }
+    };
+
+    let mut stack = Stack::new(2);
+    stack.push_long(vmindex);
+    let vmindex = reflect::box_return(jt, &ArgType::Long, &mut stack);
+    let ary_cls = require_class3(None, b"[Ljava/lang/Object;").unwrap();
+    Ok(Some(OopDesc::new_ref_ary2(ary_cls, vec![vmindex, target])))
+}
+
+fn jvm_setCallSiteTarget(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let (site, target) = (&args[0], args[1].clone());
+    put_field(site, b"target", b"Ljava/lang/invoke/MethodHandle;", target);
+    Ok(None)
+}
+
+//the member a MemberName names by clazz, name, type & flags
+fn lookup(jt: &mut JavaThread, mn: &OopRef) -> Result<Member, OopRef> {
+    let clazz = reflect::get_field(mn, b"clazz", b"Ljava/lang/Class;");
+    let name = reflect::get_field(mn, b"name", b"Ljava/lang/String;");
+    let tp = reflect::get_field(mn, b"type", b"Ljava/lang/Object;");
+    let flags = util::oop::extract_int(reflect::get_field(mn, b"flags", b"I"));
+    let defc = java_lang_Class::mirror_target(&clazz);
+    let desc = type_desc(&tp);
+    let (defc, desc) = match (defc, desc, is_null(&name)) {
+        (Some(defc), Some(desc), false) => (defc, desc),
+        _ => {
+            let msg = Some("nothing to resolve".to_string());
+            return Err(exception::new(jt, cls_consts::J_INTERNAL_ERROR, msg));
+        }
+    };
+    let name = util::oop::extract_str(name);
+    let ref_kind = (flags >> MN_REFERENCE_KIND_SHIFT) & MN_REFERENCE_KIND_MASK;
+    let member_desc = || {
+        let cls = defc.lock().unwrap();
+        format!(
+            "{}.{}{}",
+            String::from_utf8_lossy(cls.name.as_slice()).replace('/', "."),
+            name,
+            String::from_utf8_lossy(&desc)
+        )
+    };
+
+    if flags & MN_IS_FIELD != 0 {
+        let is_static = ref_kind == REF_GET_STATIC || ref_kind == REF_PUT_STATIC;
+        let fir = defc
+            .lock()
+            .unwrap()
+            .find_field_id(name.as_bytes(), &desc, is_static);
+        return match fir {
+            Some(fir) => Ok(Member::Field(fir)),
+            None => {
+                let msg = Some(member_desc());
+                Err(exception::new(jt, cls_consts::J_NO_SUCH_FIELD_ERROR, msg))
+            }
+        };
+    }
+    if flags & (MN_IS_METHOD | MN_IS_CONSTRUCTOR) == 0 {
+        let msg = Some("unrecognized MemberName format".to_string());
+        return Err(exception::new(jt, cls_consts::J_INTERNAL_ERROR, msg));
+    }
+
+    let id = util::new_method_id(name.as_bytes(), &desc);
+    let mir = {
+        let cls = defc.lock().unwrap();
+        let by_itf = |id| match cls.is_instance() {
+            true => cls.get_interface_method(id),
+            false => Err(()),
+        };
+        match cls.is_interface() {
+            true => by_itf(id.clone()).or_else(|_| cls.get_class_method(id)),
+            false => cls.get_class_method(id.clone()).or_else(|_| by_itf(id)),
+        }
+    };
+    match mir {
+        Ok(mir) if mir.method.is_static() == (ref_kind == REF_INVOKE_STATIC) => {
+            Ok(Member::Method(mir))
+        }
+        Ok(_) => {
+            let msg = Some(member_desc());
+            Err(exception::new(jt, cls_consts::J_ICCE, msg))
+        }
+        Err(_) => {
+            let msg = Some(member_desc());
+            Err(exception::new(jt, cls_consts::J_NO_SUCH_METHOD_ERROR, msg))
+        }
+    }
+}
+
+//clazz, name, type (the descriptor) & flags of a resolved member
+fn fill(jt: &mut JavaThread, mn: &OopRef, member: &Member) {
+    let (cls, name, desc, flags) = match member {
+        Member::Method(mir) => {
+            let is_itf = mir.method.class.lock().unwrap().is_interface();
+            let m = &mir.method;
+            let flags = method_kind(mir, is_itf).0;
+            (m.class.clone(), m.name.clone(), m.desc.clone(), flags)
+        }
+        Member::Field(fir) => {
+            let f = &fir.field;
+            let ref_kind = if f.is_static() {
+                REF_GET_STATIC
+            } else {
+                REF_GET_FIELD
+            };
+            let flags = field_flags(f.acc_flags, ref_kind);
+            (f.class.clone(), f.name.clone(), f.desc.clone(), flags)
+        }
+    };
+
+    let clazz = cls.lock().unwrap().get_mirror();
+    let name = util::oop::new_java_lang_string3(jt, name.as_slice());
+    let name = java_lang_String::intern(name);
+    let desc = util::oop::new_java_lang_string3(jt, desc.as_slice());
+    put_field(mn, b"clazz", b"Ljava/lang/Class;", clazz);
+    put_field(mn, b"name", b"Ljava/lang/String;", name);
+    put_field(mn, b"type", b"Ljava/lang/Object;", desc);
+    put_field(mn, b"flags", b"I", OopDesc::new_int(flags));
+}
+
+/*
+flags & vmindex of a method, as HotSpot's CallInfo of a reflected method
+  static          REF_invokeStatic
+  <init>          REF_invokeSpecial, IS_CONSTRUCTOR
+  interface's     REF_invokeInterface, by the itable index
+  with a vtable   REF_invokeVirtual, by the vtable index
+  slot
+  else            REF_invokeSpecial, private methods
+*/
+fn method_kind(mir: &MethodIdRef, is_itf: bool) -> (i32, i64) {
+    let m = &mir.method;
+    let is_init = m.name.as_slice() == b"<init>";
+    kind_of(
+        m.acc_flags,
+        is_init,
+        is_itf,
+        mir.vtable_index,
+        mir.itable_index,
+    )
+}
+
+fn kind_of(
+    acc_flags: U2,
+    is_init: bool,
+    is_itf: bool,
+    vtable_index: Option<usize>,
+    itable_index: Option<usize>,
+) -> (i32, i64) {
+    let modifiers = (acc_flags & RECOGNIZED_METHOD_MODIFIERS) as i32;
+    let (kind, ref_kind, vmindex) = if acc_flags & ACC_STATIC != 0 {
+        (MN_IS_METHOD, REF_INVOKE_STATIC, NONVIRTUAL_VTABLE_INDEX)
+    } else if is_init {
+        (
+            MN_IS_CONSTRUCTOR,
+            REF_INVOKE_SPECIAL,
+            NONVIRTUAL_VTABLE_INDEX,
+        )
+    } else {
+        match (is_itf, vtable_index, itable_index) {
+            (true, _, Some(i)) => (MN_IS_METHOD, REF_INVOKE_INTERFACE, i as i64),
+            (false, Some(i), _) => (MN_IS_METHOD, REF_INVOKE_VIRTUAL, i as i64),
+            _ => (MN_IS_METHOD, REF_INVOKE_SPECIAL, NONVIRTUAL_VTABLE_INDEX),
+        }
+    };
+    (with_ref_kind(modifiers | kind, ref_kind), vmindex)
+}
+
+fn field_flags(acc_flags: U2, ref_kind: i32) -> i32 {
+    let modifiers = (acc_flags & RECOGNIZED_FIELD_MODIFIERS) as i32;
+    with_ref_kind(modifiers | MN_IS_FIELD, ref_kind)
+}
+
+fn with_ref_kind(flags: i32, ref_kind: i32) -> i32 {
+    let mask = MN_REFERENCE_KIND_MASK << MN_REFERENCE_KIND_SHIFT;
+    (flags & !mask) | (ref_kind << MN_REFERENCE_KIND_SHIFT)
+}
+
+//the descriptor of a MemberName type
+fn type_desc(tp: &OopRef) -> Option<Vec<u8>> {
+    let cls_name = match &tp.lock().unwrap().v {
+        Oop::Inst(inst) => inst.class.lock().unwrap().name.clone(),
+        Oop::Mirror(_) => return mirror_desc(tp),
+        //{rtype, ptypes}
+        Oop::Array(ary) if ary.elements.len() == 2 => {
+            let (rtype, ptypes) = (ary.elements[0].clone(), ary.elements[1].clone());
+            return method_desc(&rtype, &ptypes);
+        }
+        _ => return None,
+    };
+    match cls_name.as_slice() {
+        cls_consts::J_STRING => Some(util::oop::extract_str(tp.clone()).into_bytes()),
+        cls_consts::J_METHODTYPE => {
+            let rtype = reflect::get_field(tp, b"rtype", b"Ljava/lang/Class;");
+            let ptypes = reflect::get_field(tp, b"ptypes", b"[Ljava/lang/Class;");
+            method_desc(&rtype, &ptypes)
+        }
+        _ => None,
+    }
+}
+
+fn method_desc(rtype: &OopRef, ptypes: &OopRef) -> Option<Vec<u8>> {
+    let ptypes = match &ptypes.lock().unwrap().v {
+        Oop::Array(ary) => ary.elements.clone(),
+        _ => return None,
+    };
+    let mut desc = vec![b'('];
+    for it in ptypes.iter() {
+        desc.extend(mirror_desc(it)?);
+    }
+    desc.push(b')');
+    desc.extend(mirror_desc(rtype)?);
+    Some(desc)
+}
+
+fn mirror_desc(mirror: &OopRef) -> Option<Vec<u8>> {
+    let (target, value_type) = match &mirror.lock().unwrap().v {
+        Oop::Mirror(mirror) => (mirror.target.clone(), mirror.value_type),
+        _ => return None,
+    };
+    match target {
+        Some(cls) => {
+            let name = cls.lock().unwrap().name.clone();
+            Some(match name.first() {
+                Some(b'[') => name.to_vec(),
+                _ => [b"L", name.as_slice(), b";"].concat(),
+            })
+        }
+        None => {
+            let desc: &[u8] = value_type.into();
+            Some(desc.to_vec())
+        }
+    }
+}
+
+fn not_a_field(jt: &mut JavaThread) -> OopRef {
+    let msg = Some("not a field".to_string());
+    exception::new(jt, cls_consts::J_INTERNAL_ERROR, msg)
+}
+
+fn put_field(obj: &OopRef, name: &[u8], desc: &[u8], v: OopRef) {
+    let cls = match &obj.lock().unwrap().v {
+        Oop::Inst(inst) => inst.class.clone(),
+        _ => unreachable!(),
+    };
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(name, desc, false);
+    cls.put_field_value(obj.clone(), id, v);
+}
+
+fn is_null(v: &OopRef) -> bool {
+    matches!(v.lock().unwrap().v, Oop::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_kind_of() {
+        let ref_kind = |flags: i32| (flags >> MN_REFERENCE_KIND_SHIFT) & MN_REFERENCE_KIND_MASK;
+        let (flags, vmindex) = kind_of(ACC_PUBLIC | ACC_STATIC, false, false, None, None);
+        assert_eq!(ref_kind(flags), REF_INVOKE_STATIC);
+        assert_eq!(flags & 0xffff, (ACC_PUBLIC | ACC_STATIC) as i32);
+        assert_eq!(flags & MN_IS_METHOD, MN_IS_METHOD);
+        assert_eq!(vmindex, NONVIRTUAL_VTABLE_INDEX);
+
+        let (flags, _) = kind_of(ACC_PUBLIC, true, false, None, None);
+        assert_eq!(ref_kind(flags), REF_INVOKE_SPECIAL);
+        assert_eq!(flags & MN_IS_CONSTRUCTOR, MN_IS_CONSTRUCTOR);
+
+        let (flags, vmindex) = kind_of(ACC_PUBLIC, false, false, Some(5), None);
+        assert_eq!((ref_kind(flags), vmindex), (REF_INVOKE_VIRTUAL, 5));
+        let (flags, vmindex) = kind_of(ACC_PUBLIC, false, true, None, Some(2));
+        assert_eq!((ref_kind(flags), vmindex), (REF_INVOKE_INTERFACE, 2));
+        //private, no vtable slot
+        let (flags, _) = kind_of(ACC_PRIVATE, false, false, None, None);
+        assert_eq!(ref_kind(flags), REF_INVOKE_SPECIAL);
+    }
+
+    #[test]
+    fn t_field_flags() {
+        let flags = field_flags(ACC_PRIVATE | ACC_VOLATILE, REF_GET_FIELD);
+        assert_eq!(flags & 0xffff, (ACC_PRIVATE | ACC_VOLATILE) as i32);
+        assert_eq!(flags & MN_IS_FIELD, MN_IS_FIELD);
+        //changeReferenceKind of a setter
+        let flags = with_ref_kind(flags, REF_PUT_STATIC);
+        assert_eq!(flags >> MN_REFERENCE_KIND_SHIFT, REF_PUT_STATIC);
+        assert_eq!(flags & 0xffff, (ACC_PRIVATE | ACC_VOLATILE) as i32);
+    }
+}
@@ -2,7 +2,7 @@
 
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
 use crate::oop::{self, Oop, OopDesc};
-use crate::runtime::{require_class3, JavaThread};
+use crate::runtime::{exception, require_class3, JavaThread};
 use crate::types::OopRef;
 use crate::util;
 
@@ -14,7 +14,7 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
     )]
 }
 
-fn jvm_newArray(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+fn jvm_newArray(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
     let mirror = args.get(0).unwrap();
     let component_cls = {
         let v = mirror.lock().unwrap();
@@ -49,6 +49,8 @@ fn jvm_newArray(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIRes
 
     let ary_cls = require_class3(None, name.as_slice()).unwrap();
 
-    let v = OopDesc::new_ref_ary(ary_cls, length as usize);
-    Ok(Some(v))
+    match OopDesc::try_new_ref_ary(ary_cls, length as usize) {
+        Ok(v) => Ok(Some(v)),
+        Err(e) => Err(exception::out_of_memory(jt, e.msg())),
+    }
 }
@@ -0,0 +1,61 @@
+#![allow(non_snake_case)]
+
+use crate::classfile::consts;
+use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
+use crate::oop::method::Parameter;
+use crate::oop::{self, OopDesc};
+use crate::runtime::{self, exception, reflect, require_class3, JavaThread};
+use crate::types::OopRef;
+use crate::util;
+
+pub fn get_native_methods() -> Vec<JNINativeMethod> {
+    vec![new_fn(
+        "getParameters0",
+        "()[Ljava/lang/reflect/Parameter;",
+        Box::new(jvm_getParameters0),
+    )]
+}
+
+//null without a MethodParameters attribute, Executable synthesizes
+//"arg0", "arg1"... then, & verifies the names it is given
+fn jvm_getParameters0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let this = args.first().unwrap();
+    //Method & Constructor both keep clazz & slot
+    let mir = reflect::get_Method_mir(this.clone());
+    let params = match &mir.method.params {
+        Some(params) => params,
+        None => return Ok(Some(oop::consts::get_null())),
+    };
+    if !params.iter().all(Parameter::is_valid) {
+        let msg = Some("Invalid constant pool index".to_string());
+        return Err(exception::new(jt, consts::J_IAE, msg));
+    }
+
+    let param_cls = require_class3(None, consts::J_PARAMETER).unwrap();
+    let mut elms = Vec::with_capacity(params.len());
+    for (i, it) in params.iter().enumerate() {
+        let name = match &it.name {
+            Some(name) => util::oop::new_java_lang_string3(jt, name.as_slice()),
+            None => oop::consts::get_null(),
+        };
+
+        let v = OopDesc::new_inst(param_cls.clone());
+        let args = vec![
+            v.clone(),
+            name,
+            OopDesc::new_int(it.acc_flags as i32),
+            this.clone(),
+            OopDesc::new_int(i as i32),
+        ];
+        runtime::java_call::invoke_ctor(
+            jt,
+            param_cls.clone(),
+            b"(Ljava/lang/String;ILjava/lang/reflect/Executable;I)V",
+            args,
+        );
+        elms.push(v);
+    }
+
+    let ary_cls = require_class3(None, consts::J_PARAMETER_ARY).unwrap();
+    Ok(Some(OopDesc::new_ref_ary2(ary_cls, elms)))
+}
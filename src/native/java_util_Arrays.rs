@@ -0,0 +1,297 @@
+#![allow(non_snake_case)]
+
+/*
+Intrinsics of java.util.Arrays for primitive arrays
+
+fill, equals & hashCode are java loops in the class library, hot in
+collection heavy code. init_vm hacks them as native, these work on the
+TypeArrayValue storage directly (slice fill & compare). The results are
+the ones of the java versions, floats compare & hash by
+floatToIntBits/doubleToLongBits (NaN equals NaN, 0.0 != -0.0).
+*/
+
+use crate::classfile::consts;
+use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
+use crate::oop::{Oop, OopDesc, TypeArrayValue};
+use crate::runtime::{exception, JavaThread};
+use crate::types::OopRef;
+use crate::util;
+use std::ops::Range;
+use std::sync::Arc;
+
+//(name, desc) of the intrinsics, all hacked as native
+pub const INTRINSICS: &[(&str, &str)] = &[
+    ("fill", "([ZZ)V"),
+    ("fill", "([BB)V"),
+    ("fill", "([CC)V"),
+    ("fill", "([SS)V"),
+    ("fill", "([II)V"),
+    ("fill", "([JJ)V"),
+    ("fill", "([FF)V"),
+    ("fill", "([DD)V"),
+    ("fill", "([ZIIZ)V"),
+    ("fill", "([BIIB)V"),
+    ("fill", "([CIIC)V"),
+    ("fill", "([SIIS)V"),
+    ("fill", "([IIII)V"),
+    ("fill", "([JIIJ)V"),
+    ("fill", "([FIIF)V"),
+    ("fill", "([DIID)V"),
+    ("equals", "([Z[Z)Z"),
+    ("equals", "([B[B)Z"),
+    ("equals", "([C[C)Z"),
+    ("equals", "([S[S)Z"),
+    ("equals", "([I[I)Z"),
+    ("equals", "([J[J)Z"),
+    ("equals", "([F[F)Z"),
+    ("equals", "([D[D)Z"),
+    ("hashCode", "([Z)I"),
+    ("hashCode", "([B)I"),
+    ("hashCode", "([C)I"),
+    ("hashCode", "([S)I"),
+    ("hashCode", "([I)I"),
+    ("hashCode", "([J)I"),
+    ("hashCode", "([F)I"),
+    ("hashCode", "([D)I"),
+];
+
+pub fn get_native_methods() -> Vec<JNINativeMethod> {
+    INTRINSICS
+        .iter()
+        .map(|&(name, desc)| match name {
+            "fill" => new_fn(name, desc, Box::new(jvm_fill)),
+            "equals" => new_fn(name, desc, Box::new(jvm_equals)),
+            _ => new_fn(name, desc, Box::new(jvm_hashCode)),
+        })
+        .collect()
+}
+
+//fill(a, v) & fill(a, from, to, v)
+fn jvm_fill(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let ary = &args[0];
+    let v = args.last().unwrap().lock().unwrap().v.clone();
+
+    let mut ary = ary.lock().unwrap();
+    let ary = match &mut ary.v {
+        Oop::TypeArray(ary) => ary,
+        Oop::Null => return Err(exception::new(jt, consts::J_NPE, None)),
+        t => unreachable!("t = {:?}", t),
+    };
+
+    let (from, to) = if args.len() == 4 {
+        let from = util::oop::extract_int(args[1].clone());
+        let to = util::oop::extract_int(args[2].clone());
+        match range_check(ary.len(), from, to) {
+            Ok(r) => (r.start, r.end),
+            Err(None) => {
+                let msg = format!("fromIndex({}) > toIndex({})", from, to);
+                return Err(exception::new(jt, consts::J_IAE, Some(msg)));
+            }
+            //new ArrayIndexOutOfBoundsException(int)'s message
+            Err(Some(index)) => {
+                let msg = format!("Array index out of range: {}", index);
+                return Err(exception::new_array_index(jt, index, msg));
+            }
+        }
+    } else {
+        (0, ary.len())
+    };
+
+    match (ary, v) {
+        (TypeArrayValue::Bool(ary), Oop::Int(v)) => ary[from..to].fill(v as u8),
+        (TypeArrayValue::Byte(ary), Oop::Int(v)) => ary[from..to].fill(v as u8),
+        (TypeArrayValue::Char(ary), Oop::Int(v)) => ary[from..to].fill(v as u16),
+        (TypeArrayValue::Short(ary), Oop::Int(v)) => ary[from..to].fill(v as i16),
+        (TypeArrayValue::Int(ary), Oop::Int(v)) => ary[from..to].fill(v),
+        (TypeArrayValue::Long(ary), Oop::Long(v)) => ary[from..to].fill(v),
+        (TypeArrayValue::Float(ary), Oop::Float(v)) => ary[from..to].fill(v),
+        (TypeArrayValue::Double(ary), Oop::Double(v)) => ary[from..to].fill(v),
+        (_, t) => unreachable!("t = {:?}", t),
+    }
+
+    Ok(None)
+}
+
+fn jvm_equals(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let r = ary_equals(&args[0], &args[1]);
+    Ok(Some(OopDesc::new_int(r as i32)))
+}
+
+fn jvm_hashCode(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let ary = args[0].lock().unwrap();
+    let h = match &ary.v {
+        Oop::TypeArray(ary) => hash_code(ary),
+        Oop::Null => 0,
+        t => unreachable!("t = {:?}", t),
+    };
+
+    Ok(Some(OopDesc::new_int(h)))
+}
+
+//Arrays.rangeCheck, Err(None) if from > to, else the index out of range
+fn range_check(len: usize, from: i32, to: i32) -> Result<Range<usize>, Option<i32>> {
+    if from > to {
+        return Err(None);
+    }
+    //wraps only if from < 0, out of bounds anyway
+    let n = to.wrapping_sub(from);
+    exception::in_bounds(len, from, n).ok_or(Some(if from < 0 { from } else { to }))
+}
+
+//null & identity first, then a copied out before b is locked: never both
+//locks held, no lock order between two arrays
+fn ary_equals(a: &OopRef, b: &OopRef) -> bool {
+    if Arc::ptr_eq(a, b) {
+        return true;
+    }
+    let a = match &a.lock().unwrap().v {
+        Oop::TypeArray(a) => Some(a.clone()),
+        _ => None,
+    };
+    let b = b.lock().unwrap();
+    match (a, &b.v) {
+        (Some(a), Oop::TypeArray(b)) => equals(&a, b),
+        (None, Oop::Null) => true,
+        _ => false,
+    }
+}
+
+fn equals(a: &TypeArrayValue, b: &TypeArrayValue) -> bool {
+    match (a, b) {
+        (TypeArrayValue::Bool(a), TypeArrayValue::Bool(b)) => a[..] == b[..],
+        (TypeArrayValue::Byte(a), TypeArrayValue::Byte(b)) => a[..] == b[..],
+        (TypeArrayValue::Char(a), TypeArrayValue::Char(b)) => a[..] == b[..],
+        (TypeArrayValue::Short(a), TypeArrayValue::Short(b)) => a[..] == b[..],
+        (TypeArrayValue::Int(a), TypeArrayValue::Int(b)) => a[..] == b[..],
+        (TypeArrayValue::Long(a), TypeArrayValue::Long(b)) => a[..] == b[..],
+        (TypeArrayValue::Float(a), TypeArrayValue::Float(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b.iter())
+                    .all(|(x, y)| float_bits(*x) == float_bits(*y))
+        }
+        (TypeArrayValue::Double(a), TypeArrayValue::Double(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b.iter())
+                    .all(|(x, y)| double_bits(*x) == double_bits(*y))
+        }
+        _ => false,
+    }
+}
+
+fn hash_code(ary: &TypeArrayValue) -> i32 {
+    match ary {
+        TypeArrayValue::Bool(ary) => hash(ary, |&v| if v != 0 { 1231 } else { 1237 }),
+        TypeArrayValue::Byte(ary) => hash(ary, |&v| v as i8 as i32),
+        TypeArrayValue::Char(ary) => hash(ary, |&v| v as i32),
+        TypeArrayValue::Short(ary) => hash(ary, |&v| v as i32),
+        TypeArrayValue::Int(ary) => hash(ary, |&v| v),
+        TypeArrayValue::Long(ary) => hash(ary, |&v| long_hash(v)),
+        TypeArrayValue::Float(ary) => hash(ary, |&v| float_bits(v)),
+        TypeArrayValue::Double(ary) => hash(ary, |&v| long_hash(double_bits(v))),
+    }
+}
+
+//31 * h + e, from 1
+fn hash<T, F>(ary: &[T], f: F) -> i32
+where
+    F: Fn(&T) -> i32,
+{
+    ary.iter()
+        .fold(1i32, |h, v| h.wrapping_mul(31).wrapping_add(f(v)))
+}
+
+//Long.hashCode
+fn long_hash(v: i64) -> i32 {
+    (v ^ ((v as u64) >> 32) as i64) as i32
+}
+
+//Float.floatToIntBits, NaNs are canonical
+fn float_bits(v: f32) -> i32 {
+    if v.is_nan() {
+        0x7fc0_0000
+    } else {
+        v.to_bits() as i32
+    }
+}
+
+//Double.doubleToLongBits, NaNs are canonical
+fn double_bits(v: f64) -> i64 {
+    if v.is_nan() {
+        0x7ff8_0000_0000_0000
+    } else {
+        v.to_bits() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //expected values printed by the java versions
+    #[test]
+    fn t_hash_code() {
+        let cases = vec![
+            (TypeArrayValue::Int(Box::new(vec![1, 2, 3])), 30817),
+            (TypeArrayValue::Long(Box::new(vec![1 << 32, -1])), 992),
+            (TypeArrayValue::Bool(Box::new(vec![1, 0])), 40359),
+            (TypeArrayValue::Byte(Box::new(vec![0xff, 7])), 937),
+            (
+                TypeArrayValue::Char(Box::new(vec![b'a' as u16, 0xffff])),
+                69503,
+            ),
+            (TypeArrayValue::Short(Box::new(vec![-2])), 29),
+            (
+                TypeArrayValue::Float(Box::new(vec![1.0, f32::NAN, -0.0])),
+                1472230495,
+            ),
+            (
+                TypeArrayValue::Double(Box::new(vec![1.5, f64::NAN])),
+                1056965569,
+            ),
+            (TypeArrayValue::Int(Box::default()), 1),
+        ];
+        for (ary, h) in cases {
+            assert_eq!(hash_code(&ary), h, "{:?}", ary);
+        }
+    }
+
+    #[test]
+    fn t_equals() {
+        let nan = f32::from_bits(0x7fc0_0001);
+        let a = TypeArrayValue::Float(Box::new(vec![f32::NAN]));
+        let b = TypeArrayValue::Float(Box::new(vec![nan]));
+        assert!(equals(&a, &b));
+
+        let a = TypeArrayValue::Double(Box::new(vec![0.0]));
+        let b = TypeArrayValue::Double(Box::new(vec![-0.0]));
+        assert!(!equals(&a, &b));
+
+        let a = TypeArrayValue::Int(Box::new(vec![1, 2]));
+        let b = TypeArrayValue::Int(Box::new(vec![1, 2, 3]));
+        assert!(!equals(&a, &b));
+    }
+
+    #[test]
+    fn t_ary_equals() {
+        let a = OopDesc::new_int_ary(2);
+        let b = OopDesc::new_int_ary(2);
+        assert!(ary_equals(&a, &a));
+        assert!(ary_equals(&a, &b));
+        assert!(ary_equals(&OopDesc::new_null(), &OopDesc::new_null()));
+        assert!(!ary_equals(&a, &OopDesc::new_null()));
+        assert!(!ary_equals(&OopDesc::new_null(), &b));
+        assert!(!ary_equals(&a, &OopDesc::new_int_ary(3)));
+    }
+
+    #[test]
+    fn t_range_check() {
+        assert_eq!(range_check(4, 0, 4), Ok(0..4));
+        assert_eq!(range_check(4, 2, 2), Ok(2..2));
+        assert_eq!(range_check(4, 3, 2), Err(None));
+        assert_eq!(range_check(4, -1, 2), Err(Some(-1)));
+        assert_eq!(range_check(4, 0, 5), Err(Some(5)));
+        assert_eq!(range_check(4, -1, i32::MAX), Err(Some(-1)));
+    }
+}
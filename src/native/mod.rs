@@ -1,9 +1,12 @@
 #![allow(non_snake_case)]
 
+use crate::oop;
+use crate::runtime::handles::LocalRef;
 use crate::runtime::JavaThread;
-use crate::types::{ClassRef, OopRef};
+use crate::types::{ClassRef, MethodIdRef, OopRef};
 use crate::util;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 mod java_io_FileDescriptor;
@@ -16,19 +19,24 @@ mod java_lang_Double;
 mod java_lang_Float;
 mod java_lang_Object;
 mod java_lang_Runtime;
-mod java_lang_String;
-mod java_lang_System;
-mod java_lang_Thread;
+mod java_lang_Shutdown;
+pub mod java_lang_String;
+pub mod java_lang_System;
+pub mod java_lang_Thread;
 mod java_lang_Throwable;
+mod java_lang_invoke_MethodHandleNatives;
 mod java_lang_reflect_Array;
+mod java_lang_reflect_Executable;
 mod java_security_AccessController;
+pub mod java_util_Arrays;
 mod java_util_concurrent_atomic_AtomicLong;
 mod sun_misc_Signal;
 mod sun_misc_URLClassPath;
-mod sun_misc_Unsafe;
+pub mod sun_misc_Unsafe;
 mod sun_misc_VM;
 mod sun_nio_cs_StreamEncoder;
 mod sun_reflect_NativeConstructorAccessorImpl;
+mod sun_reflect_NativeMethodAccessorImpl;
 mod sun_reflect_Reflection;
 
 pub type JNIEnv = Arc<Mutex<Box<JNIEnvStruct>>>;
@@ -37,10 +45,20 @@ pub type NativeMethodPtr =
     Box<dyn Fn(&mut JavaThread, JNIEnv, Vec<OopRef>) -> JNIResult + Send + Sync>;
 pub type JNINativeMethod = Arc<JNINativeMethodStruct>;
 
+//a native taking & returning LocalRefs, see new_local_fn
+pub type LocalResult = Result<Option<LocalRef>, LocalRef>;
+pub type LocalNativePtr =
+    Box<dyn Fn(&mut JavaThread, JNIEnv, Vec<LocalRef>) -> LocalResult + Send + Sync>;
+
+enum FnPtr {
+    Oop(NativeMethodPtr),
+    Local(LocalNativePtr),
+}
+
 pub struct JNINativeMethodStruct {
     name: &'static str,
     signature: &'static str,
-    fnptr: NativeMethodPtr,
+    fnptr: FnPtr,
 }
 
 pub struct JNIEnvStruct {
@@ -56,6 +74,104 @@ lazy_static! {
     };
 }
 
+/*
+Missing natives, -XX:+BestEffortNatives
+
+A native method without implementation throws UnsatisfiedLinkError. With
+the flag it returns the default value of its return type (0, false,
+null) instead, and is recorded here with the number of calls. The report
+is printed when the vm exits, it shows how far an application gets &
+which natives to implement first.
+*/
+lazy_static! {
+    static ref MISSING: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+}
+
+pub fn record_missing(name: &str) {
+    util::sync_call_ctx(&MISSING, |h| *h.entry(name.to_string()).or_insert(0) += 1);
+}
+
+//(name, calls), most called first
+pub fn missing() -> Vec<(String, usize)> {
+    let mut v: Vec<(String, usize)> = util::sync_call_ctx(&MISSING, |h| {
+        h.iter().map(|(k, v)| (k.clone(), *v)).collect()
+    });
+    v.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    v
+}
+
+pub fn print_missing() {
+    if crate::runtime::flags::is_best_effort_natives() {
+        let missing = missing();
+        eprintln!("Missing natives: {}", missing.len());
+        for (name, calls) in missing {
+            eprintln!("  {:>8} {}", calls, name);
+        }
+    }
+}
+
+/*
+Overrides, for tests & embedders
+
+set_override replaces a method by a host fn, a mock of
+System.currentTimeMillis or of a file system native: guest tests run
+hermetic. Any method can be overridden, native, intrinsic or java, the
+override wins from the next call on. remove_override restores the
+method.
+*/
+
+//some override is set, checked before locking OVERRIDES
+static OVERRIDDEN: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref OVERRIDES: Mutex<HashMap<String, JNINativeMethod>> = Mutex::new(HashMap::new());
+}
+
+//class like "java/lang/System", '.' is accepted too
+pub fn set_override(class: &str, method: JNINativeMethod) {
+    let id = override_id(class, method.name, method.signature);
+    util::sync_call_ctx(&OVERRIDES, |h| {
+        h.insert(id, method);
+        OVERRIDDEN.store(true, Ordering::Relaxed);
+    });
+}
+
+pub fn remove_override(class: &str, name: &str, signature: &str) {
+    let id = override_id(class, name, signature);
+    util::sync_call_ctx(&OVERRIDES, |h| {
+        h.remove(&id);
+        OVERRIDDEN.store(!h.is_empty(), Ordering::Relaxed);
+    });
+}
+
+pub fn clear_overrides() {
+    util::sync_call_ctx(&OVERRIDES, |h| {
+        h.clear();
+        OVERRIDDEN.store(false, Ordering::Relaxed);
+    });
+}
+
+pub fn is_overridden(mir: &MethodIdRef) -> bool {
+    if !OVERRIDDEN.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    let id = {
+        let cls = mir.method.class.lock().unwrap();
+        [
+            String::from_utf8_lossy(cls.name.as_slice()),
+            String::from_utf8_lossy(mir.method.name.as_slice()),
+            String::from_utf8_lossy(mir.method.desc.as_slice()),
+        ]
+        .join(util::PATH_SEP)
+    };
+    util::sync_call_ctx(&OVERRIDES, |h| h.contains_key(&id))
+}
+
+fn override_id(class: &str, name: &str, signature: &str) -> String {
+    [class.replace('.', "/").as_str(), name, signature].join(util::PATH_SEP)
+}
+
 pub fn new_fn(
     name: &'static str,
     signature: &'static str,
@@ -64,7 +180,21 @@ pub fn new_fn(
     Arc::new(JNINativeMethodStruct {
         name,
         signature,
-        fnptr,
+        fnptr: FnPtr::Oop(fnptr),
+    })
+}
+
+//a native of an embedder, its args are locals of the native's frame, as
+//JNI passes them, see runtime::handles
+pub fn new_local_fn(
+    name: &'static str,
+    signature: &'static str,
+    fnptr: LocalNativePtr,
+) -> JNINativeMethod {
+    Arc::new(JNINativeMethodStruct {
+        name,
+        signature,
+        fnptr: FnPtr::Local(fnptr),
     })
 }
 
@@ -75,10 +205,15 @@ pub fn new_jni_env(jt: &mut JavaThread, class: ClassRef) -> JNIEnv {
     })))
 }
 
-pub fn find_symbol(package: &[u8], name: &[u8], desc: &[u8]) -> Option<JNINativeMethod> {
-    let id = vec![package, name, desc].join(util::PATH_SEP.as_bytes());
-    let id = String::from_utf8(id).unwrap();
-    util::sync_call_ctx(&NATIVES, |h| h.get(&id).map(|it| it.clone()))
+//id style: "java/lang/Object:hashCode:()I"
+pub fn find_symbol(id: &[u8]) -> Option<JNINativeMethod> {
+    let id = std::str::from_utf8(id).unwrap();
+    if OVERRIDDEN.load(Ordering::Relaxed) {
+        if let Some(it) = util::sync_call_ctx(&OVERRIDES, |h| h.get(id).cloned()) {
+            return Some(it);
+        }
+    }
+    util::sync_call_ctx(&NATIVES, |h| h.get(id).cloned())
 }
 
 pub fn init() {
@@ -109,11 +244,23 @@ pub fn init() {
         ("java/lang/Double", java_lang_Double::get_native_methods()),
         ("java/lang/Float", java_lang_Float::get_native_methods()),
         ("java/lang/Object", java_lang_Object::get_native_methods()),
+        (
+            "java/lang/invoke/MethodHandleNatives",
+            java_lang_invoke_MethodHandleNatives::get_native_methods(),
+        ),
         (
             "java/lang/reflect/Array",
             java_lang_reflect_Array::get_native_methods(),
         ),
+        (
+            "java/lang/reflect/Executable",
+            java_lang_reflect_Executable::get_native_methods(),
+        ),
         ("java/lang/Runtime", java_lang_Runtime::get_native_methods()),
+        (
+            "java/lang/Shutdown",
+            java_lang_Shutdown::get_native_methods(),
+        ),
         ("java/lang/String", java_lang_String::get_native_methods()),
         ("java/lang/System", java_lang_System::get_native_methods()),
         ("java/lang/Thread", java_lang_Thread::get_native_methods()),
@@ -125,6 +272,7 @@ pub fn init() {
             "java/security/AccessController",
             java_security_AccessController::get_native_methods(),
         ),
+        ("java/util/Arrays", java_util_Arrays::get_native_methods()),
         (
             "java/util/concurrent/atomic/AtomicLong",
             java_util_concurrent_atomic_AtomicLong::get_native_methods(),
@@ -144,27 +292,183 @@ pub fn init() {
             "sun/reflect/NativeConstructorAccessorImpl",
             sun_reflect_NativeConstructorAccessorImpl::get_native_methods(),
         ),
+        (
+            "sun/reflect/NativeMethodAccessorImpl",
+            sun_reflect_NativeMethodAccessorImpl::get_native_methods(),
+        ),
         (
             "sun/reflect/Reflection",
             sun_reflect_Reflection::get_native_methods(),
         ),
     ];
 
+    natives
+        .into_iter()
+        .for_each(|(package, methods)| register(package, methods));
+
+    java_lang_Class::init();
+}
+
+pub fn register(package: &str, methods: Vec<JNINativeMethod>) {
     util::sync_call_ctx(&NATIVES, |h| {
-        natives.iter().for_each(|(package, methods)| {
-            methods.iter().for_each(|it| {
-                let id = vec![package.as_ref(), it.name, it.signature].join(util::PATH_SEP);
+        methods.into_iter().for_each(|it| {
+            let id = [package, it.name, it.signature].join(util::PATH_SEP);
 
-                h.insert(id, it.clone());
-            });
+            h.insert(id, it);
         });
     });
+}
 
-    java_lang_Class::init();
+//the registered natives by class, sorted: ("java/lang/Object", ["hashCode()I", ...])
+pub fn implemented() -> Vec<(String, Vec<String>)> {
+    let ids: Vec<String> = util::sync_call_ctx(&NATIVES, |h| h.keys().cloned().collect());
+    group(ids.iter().map(|it| it.as_str()))
+}
+
+//ids are "class:name:desc", PATH_SEP may be ';' which desc holds too
+fn group<'a>(ids: impl Iterator<Item = &'a str>) -> Vec<(String, Vec<String>)> {
+    let mut classes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for id in ids {
+        let mut parts = id.splitn(3, util::PATH_SEP);
+        if let (Some(class), Some(name), Some(desc)) = (parts.next(), parts.next(), parts.next()) {
+            classes
+                .entry(class.to_string())
+                .or_default()
+                .push(format!("{}{}", name, desc));
+        }
+    }
+    classes
+        .into_iter()
+        .map(|(class, mut methods)| {
+            methods.sort();
+            (class, methods)
+        })
+        .collect()
 }
 
 impl JNINativeMethodStruct {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn signature(&self) -> &'static str {
+        self.signature
+    }
+
+    //in the frame of the call, the caller pushes & pops it
     pub fn invoke(&self, jt: &mut JavaThread, jni: JNIEnv, args: Vec<OopRef>) -> JNIResult {
-        (self.fnptr)(jt, jni, args)
+        match &self.fnptr {
+            FnPtr::Oop(f) => f(jt, jni, args),
+            FnPtr::Local(f) => {
+                let args = args
+                    .into_iter()
+                    .map(|v| jt.local_refs.new_local(v))
+                    .collect();
+                //a deleted local is null
+                let resolve = |jt: &JavaThread, r: LocalRef| {
+                    jt.local_refs
+                        .resolve(r)
+                        .unwrap_or_else(oop::consts::get_null)
+                };
+                match f(jt, jni, args) {
+                    Ok(v) => Ok(v.map(|r| resolve(jt, r))),
+                    Err(ex) => Err(resolve(jt, ex)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_missing() {
+        record_missing("test/Missing.a()V");
+        record_missing("test/Missing.b()I");
+        record_missing("test/Missing.b()I");
+
+        let missing: Vec<(String, usize)> = missing()
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("test/Missing."))
+            .collect();
+        assert_eq!(
+            missing,
+            vec![
+                ("test/Missing.b()I".to_string(), 2),
+                ("test/Missing.a()V".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn t_group() {
+        let sep = util::PATH_SEP;
+        let ids = [
+            ["java/lang/Object", "hashCode", "()I"].join(sep),
+            ["java/lang/Class", "forName0", "(Ljava/lang/String;Z)V"].join(sep),
+            ["java/lang/Object", "clone", "()Ljava/lang/Object;"].join(sep),
+        ];
+        let classes = group(ids.iter().map(|it| it.as_str()));
+        assert_eq!(
+            classes,
+            vec![
+                (
+                    "java/lang/Class".to_string(),
+                    vec!["forName0(Ljava/lang/String;Z)V".to_string()]
+                ),
+                (
+                    "java/lang/Object".to_string(),
+                    vec![
+                        "clone()Ljava/lang/Object;".to_string(),
+                        "hashCode()I".to_string()
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn t_override() {
+        let id = b"test/Override:now:()J";
+        assert!(find_symbol(id).is_none());
+
+        let now = new_fn("now", "()J", Box::new(|_, _, _| Ok(None)));
+        set_override("test.Override", now);
+        assert_eq!(find_symbol(id).map(|it| it.name()), Some("now"));
+
+        remove_override("test/Override", "now", "()J");
+        assert!(find_symbol(id).is_none());
+    }
+
+    #[test]
+    fn t_local_fn() {
+        use crate::oop::{Class, OopDesc, ValueType};
+        use crate::runtime::ClassLoader;
+
+        //deletes its first arg, returns the second
+        let f = new_local_fn(
+            "pick",
+            "(II)I",
+            Box::new(|jt, _, args| {
+                crate::runtime::handles::delete_local(jt, args[0]);
+                match jt.local_refs.resolve(args[1]) {
+                    Some(_) => Ok(Some(args[1])),
+                    None => Err(args[0]),
+                }
+            }),
+        );
+        let mut jt = JavaThread::new();
+        let cls = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::INT);
+        let env = new_jni_env(&mut jt, new_sync_ref!(cls));
+        let (a, b) = (OopDesc::new_int(1), OopDesc::new_int(2));
+
+        let depth = jt.local_refs.push_frame();
+        let v = f.invoke(&mut jt, env, vec![a, b.clone()]);
+        assert!(Arc::ptr_eq(&v.unwrap().unwrap(), &b));
+        assert_eq!(jt.local_refs.roots().len(), 1);
+        jt.local_refs.pop_to(depth);
+        assert!(jt.local_refs.roots().is_empty());
     }
 }
@@ -1,14 +1,63 @@
 #![allow(non_snake_case)]
 
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
-use crate::oop::{self, Oop, OopDesc};
-use crate::runtime::{require_class3, JavaThread};
+use crate::oop::{self, Oop, OopDesc, TypeArrayValue};
+use crate::runtime::{self, require_class3, JavaThread};
 use crate::types::OopRef;
 use crate::util;
 use std::os::raw::c_void;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/*
+Atomic read-modify-write: the getAndAdd & getAndSet family, and the
+compareAndSwaps
+
+A slot of a field or an element of a ref array is an OopRef, replaced on
+write, an int[] or long[] holds its values. The read & the write are
+done under one lock of the owner, no other access to the owner comes in
+between. The getAndXxx of JDK 8 are java CAS loops, hacked as native
+(init_vm), a single lock instead of a loop which retries under
+contention.
+*/
+pub const ATOMICS: &[(&str, &str)] = &[
+    ("getAndAddInt", "(Ljava/lang/Object;JI)I"),
+    ("getAndAddLong", "(Ljava/lang/Object;JJ)J"),
+    ("getAndSetInt", "(Ljava/lang/Object;JI)I"),
+    ("getAndSetLong", "(Ljava/lang/Object;JJ)J"),
+    (
+        "getAndSetObject",
+        "(Ljava/lang/Object;JLjava/lang/Object;)Ljava/lang/Object;",
+    ),
+];
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
     vec![
+        new_fn(
+            "getAndAddInt",
+            "(Ljava/lang/Object;JI)I",
+            Box::new(jvm_getAndAddInt),
+        ),
+        new_fn(
+            "getAndAddLong",
+            "(Ljava/lang/Object;JJ)J",
+            Box::new(jvm_getAndAddLong),
+        ),
+        new_fn(
+            "getAndSetInt",
+            "(Ljava/lang/Object;JI)I",
+            Box::new(jvm_getAndSetInt),
+        ),
+        new_fn(
+            "getAndSetLong",
+            "(Ljava/lang/Object;JJ)J",
+            Box::new(jvm_getAndSetLong),
+        ),
+        new_fn(
+            "getAndSetObject",
+            "(Ljava/lang/Object;JLjava/lang/Object;)Ljava/lang/Object;",
+            Box::new(jvm_getAndSetObject),
+        ),
         new_fn("registerNatives", "()V", Box::new(jvm_registerNatives)),
         new_fn(
             "arrayBaseOffset",
@@ -73,6 +122,8 @@ pub fn get_native_methods() -> Vec<JNINativeMethod> {
             Box::new(jvm_copyMemory),
         ),
         new_fn("getChar", "(J)C", Box::new(jvm_getChar)),
+        new_fn("park", "(ZJ)V", Box::new(jvm_park)),
+        new_fn("unpark", "(Ljava/lang/Object;)V", Box::new(jvm_unpark)),
     ]
 }
 
@@ -122,40 +173,24 @@ fn jvm_objectFieldOffset(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>)
     Ok(Some(OopDesc::new_long(v as i64)))
 }
 
-//fixme: 此处语义上要求是原子操作，这里需要重新实现
 fn jvm_compareAndSwapObject(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
-    let owner = args.get(1).unwrap();
-    let offset = util::oop::extract_long(args.get(2).unwrap().clone());
-    let old_data = args.get(3).unwrap();
-    let new_data = args.get(4).unwrap();
+    let owner = &args[1];
+    let offset = util::oop::extract_long(args[2].clone()) as usize;
+    let old_data = &args[3];
+    let new_data = &args[4];
 
-    let v_at_offset = {
-        let v = owner.lock().unwrap();
-        match &v.v {
-            Oop::Mirror(mirror) => mirror.field_values[offset as usize].clone(),
-            Oop::Array(ary) => ary.elements[offset as usize].clone(),
-            Oop::Inst(inst) => inst.field_values[offset as usize].clone(),
-            t => unreachable!("{:?}", t),
-        }
-    };
-
-    if util::oop::if_acmpeq(v_at_offset, old_data.clone()) {
-        let mut v = owner.lock().unwrap();
-        match &mut v.v {
-            Oop::Mirror(mirror) => {
-                mirror.field_values[offset as usize] = new_data.clone();
-            }
-            Oop::Array(ary) => {
-                ary.elements[offset as usize] = new_data.clone();
-            }
-            Oop::Inst(inst) => inst.field_values[offset as usize] = new_data.clone(),
-            _ => unreachable!(),
-        }
+    let swapped = cas_ref(&mut owner.lock().unwrap().v, offset, old_data, new_data);
+    Ok(Some(OopDesc::new_int(swapped as i32)))
+}
 
-        Ok(Some(OopDesc::new_int(1)))
-    } else {
-        Ok(Some(OopDesc::new_int(0)))
-    }
+//by identity as acmp, only the owner is locked
+fn cas_ref(v: &mut Oop, offset: usize, old: &OopRef, new: &OopRef) -> bool {
+    let mut swapped = false;
+    update_ref(v, offset, |cur| {
+        swapped = Arc::ptr_eq(cur, old);
+        swapped.then(|| new.clone())
+    });
+    swapped
 }
 
 fn jvm_getIntVolatile(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
@@ -172,32 +207,14 @@ fn jvm_getIntVolatile(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) ->
 }
 
 fn jvm_compareAndSwapInt(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
-    let owner = args.get(1).unwrap();
-    let offset = util::oop::extract_long(args.get(2).unwrap().clone());
-    let old_data = util::oop::extract_int(args.get(3).unwrap().clone());
-    let new_data = args.get(4).unwrap();
-
-    let v_at_offset = {
-        let v = owner.lock().unwrap();
-        let v = match &v.v {
-            Oop::Inst(inst) => inst.field_values[offset as usize].clone(),
-            _ => unreachable!(),
-        };
-
-        util::oop::extract_int(v)
-    };
-
-    if v_at_offset == old_data {
-        let mut v = owner.lock().unwrap();
-        match &mut v.v {
-            Oop::Inst(inst) => inst.field_values[offset as usize] = new_data.clone(),
-            _ => unreachable!(),
-        }
-
-        Ok(Some(OopDesc::new_int(1)))
-    } else {
-        Ok(Some(OopDesc::new_int(0)))
-    }
+    let offset = util::oop::extract_long(args[2].clone()) as usize;
+    let old_data = util::oop::extract_int(args[3].clone());
+    let new_data = util::oop::extract_int(args[4].clone());
+
+    let v = update_int(&mut args[1].lock().unwrap().v, offset, |v| {
+        Some(new_data).filter(|_| v == old_data)
+    });
+    Ok(Some(OopDesc::new_int((v == old_data) as i32)))
 }
 
 fn jvm_allocateMemory(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
@@ -237,32 +254,108 @@ fn jvm_getByte(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResu
 }
 
 fn jvm_compareAndSwapLong(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
-    let owner = args.get(1).unwrap();
-    let offset = util::oop::extract_long(args.get(2).unwrap().clone());
-    let old_data = util::oop::extract_long(args.get(3).unwrap().clone());
-    let new_data = args.get(4).unwrap();
+    let offset = util::oop::extract_long(args[2].clone()) as usize;
+    let old_data = util::oop::extract_long(args[3].clone());
+    let new_data = util::oop::extract_long(args[4].clone());
+
+    let v = update_long(&mut args[1].lock().unwrap().v, offset, |v| {
+        Some(new_data).filter(|_| v == old_data)
+    });
+    Ok(Some(OopDesc::new_int((v == old_data) as i32)))
+}
 
-    let v_at_offset = {
-        let v = owner.lock().unwrap();
-        let v = match &v.v {
-            Oop::Inst(inst) => inst.field_values[offset as usize].clone(),
-            _ => unreachable!(),
-        };
+fn jvm_getAndAddInt(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let offset = util::oop::extract_long(args[2].clone()) as usize;
+    let delta = util::oop::extract_int(args[3].clone());
+    let v = update_int(&mut args[1].lock().unwrap().v, offset, |v| {
+        Some(v.wrapping_add(delta))
+    });
+    Ok(Some(OopDesc::new_int(v)))
+}
 
-        util::oop::extract_long(v)
-    };
+fn jvm_getAndAddLong(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let offset = util::oop::extract_long(args[2].clone()) as usize;
+    let delta = util::oop::extract_long(args[3].clone());
+    let v = update_long(&mut args[1].lock().unwrap().v, offset, |v| {
+        Some(v.wrapping_add(delta))
+    });
+    Ok(Some(OopDesc::new_long(v)))
+}
 
-    if v_at_offset == old_data {
-        let mut v = owner.lock().unwrap();
-        match &mut v.v {
-            Oop::Inst(inst) => inst.field_values[offset as usize] = new_data.clone(),
-            _ => unreachable!(),
+fn jvm_getAndSetInt(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let offset = util::oop::extract_long(args[2].clone()) as usize;
+    let new_data = util::oop::extract_int(args[3].clone());
+    let v = update_int(&mut args[1].lock().unwrap().v, offset, |_| Some(new_data));
+    Ok(Some(OopDesc::new_int(v)))
+}
+
+fn jvm_getAndSetLong(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let offset = util::oop::extract_long(args[2].clone()) as usize;
+    let new_data = util::oop::extract_long(args[3].clone());
+    let v = update_long(&mut args[1].lock().unwrap().v, offset, |_| Some(new_data));
+    Ok(Some(OopDesc::new_long(v)))
+}
+
+fn jvm_getAndSetObject(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let offset = util::oop::extract_long(args[2].clone()) as usize;
+    let new_data = args[3].clone();
+    let v = update_ref(&mut args[1].lock().unwrap().v, offset, |_| Some(new_data));
+    Ok(Some(v))
+}
+
+//the slot at offset of a field owner or ref array
+fn slot(v: &mut Oop, offset: usize) -> &mut OopRef {
+    match v {
+        Oop::Inst(inst) => &mut inst.field_values[offset],
+        Oop::Mirror(mirror) => &mut mirror.field_values[offset],
+        Oop::Array(ary) => &mut ary.elements[offset],
+        t => unreachable!("t = {:?}", t),
+    }
+}
+
+//the value at offset of v, owner locked, f of it the new one, None
+//leaves it as is
+fn update_ref(v: &mut Oop, offset: usize, f: impl FnOnce(&OopRef) -> Option<OopRef>) -> OopRef {
+    let slot = slot(v, offset);
+    let old = slot.clone();
+    if let Some(new) = f(&old) {
+        *slot = new;
+    }
+    old
+}
+
+fn update_int(v: &mut Oop, offset: usize, f: impl FnOnce(i32) -> Option<i32>) -> i32 {
+    if let Oop::TypeArray(TypeArrayValue::Int(ary)) = v {
+        let old = ary[offset];
+        if let Some(new) = f(old) {
+            ary[offset] = new;
+        }
+        return old;
+    }
+
+    let slot = slot(v, offset);
+    let old = util::oop::extract_int(slot.clone());
+    if let Some(new) = f(old) {
+        *slot = OopDesc::new_int(new);
+    }
+    old
+}
+
+fn update_long(v: &mut Oop, offset: usize, f: impl FnOnce(i64) -> Option<i64>) -> i64 {
+    if let Oop::TypeArray(TypeArrayValue::Long(ary)) = v {
+        let old = ary[offset];
+        if let Some(new) = f(old) {
+            ary[offset] = new;
         }
+        return old;
+    }
 
-        Ok(Some(OopDesc::new_int(1)))
-    } else {
-        Ok(Some(OopDesc::new_int(0)))
+    let slot = slot(v, offset);
+    let old = util::oop::extract_long(slot.clone());
+    if let Some(new) = f(old) {
+        *slot = OopDesc::new_long(new);
     }
+    old
 }
 
 fn jvm_getObjectVolatile(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
@@ -387,3 +480,107 @@ fn jvm_getChar(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResu
     let v = unsafe { *ptr };
     Ok(Some(OopDesc::new_int(v as i32)))
 }
+
+//park(isAbsolute, time): time is a deadline in epoch millis if absolute,
+//else relative nanos, 0 parks until unparked
+fn jvm_park(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let is_absolute = util::oop::extract_int(args[1].clone()) != 0;
+    let time = util::oop::extract_long(args[2].clone());
+
+    let deadline = match (is_absolute, time) {
+        (_, t) if t < 0 => return Ok(None),
+        (true, 0) => return Ok(None),
+        (false, 0) => None,
+        (false, nanos) => Some(Instant::now() + Duration::from_nanos(nanos as u64)),
+        (true, millis) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |it| it.as_millis() as i64);
+            if millis <= now {
+                return Ok(None);
+            }
+            Some(Instant::now() + Duration::from_millis((millis - now) as u64))
+        }
+    };
+
+    let parker = jt.parker.clone();
+    parker.park(jt.java_thread_obj.as_ref(), deadline);
+    Ok(None)
+}
+
+fn jvm_unpark(_jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let thread = args[1].clone();
+    let is_null = matches!(thread.lock().unwrap().v, Oop::Null);
+    if !is_null {
+        runtime::park::unpark(&thread);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::{MirrorOopDesc, ValueType};
+
+    #[test]
+    fn t_get_and_add() {
+        //getAndAdd from 8 threads, no increment lost
+        let ints = OopDesc::new_int_ary(2);
+        let longs = OopDesc::new_long_ary(1);
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let (ints, longs) = (ints.clone(), longs.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        update_int(&mut ints.lock().unwrap().v, 1, |v| Some(v + 1));
+                        update_long(&mut longs.lock().unwrap().v, 0, |v| Some(v + 2));
+                    }
+                })
+            })
+            .collect();
+        for it in threads {
+            it.join().unwrap();
+        }
+        assert_eq!(update_int(&mut ints.lock().unwrap().v, 1, |_| None), 8000);
+        assert_eq!(update_int(&mut ints.lock().unwrap().v, 0, |_| None), 0);
+        assert_eq!(
+            update_long(&mut longs.lock().unwrap().v, 0, |_| None),
+            16000
+        );
+    }
+
+    #[test]
+    fn t_update_slot() {
+        let a = OopDesc::new_int(0);
+        let mut v = Oop::Mirror(MirrorOopDesc {
+            target: None,
+            field_values: vec![OopDesc::new_int(41), a.clone()],
+            value_type: ValueType::OBJECT,
+        });
+        //getAndAdd, then a failed & a successful CAS
+        assert_eq!(update_int(&mut v, 0, |v| Some(v + 1)), 41);
+        assert_eq!(update_int(&mut v, 0, |v| Some(0).filter(|_| v == 41)), 42);
+        assert_eq!(update_int(&mut v, 0, |v| Some(7).filter(|_| v == 42)), 42);
+        assert_eq!(update_int(&mut v, 0, |_| None), 7);
+
+        let b = OopDesc::new_int(1);
+        let old = update_ref(&mut v, 1, |_| Some(b.clone()));
+        assert!(Arc::ptr_eq(&old, &a));
+        assert!(Arc::ptr_eq(&update_ref(&mut v, 1, |_| None), &b));
+    }
+
+    #[test]
+    fn t_cas_ref() {
+        let a = OopDesc::new_int(1);
+        let mut v = Oop::Mirror(MirrorOopDesc {
+            target: None,
+            field_values: vec![a.clone()],
+            value_type: ValueType::OBJECT,
+        });
+        //the same value, another oop
+        let (b, c) = (OopDesc::new_int(1), OopDesc::new_int(2));
+        assert!(!cas_ref(&mut v, 0, &b, &c));
+        assert!(cas_ref(&mut v, 0, &a, &c));
+        assert!(Arc::ptr_eq(&update_ref(&mut v, 0, |_| None), &c));
+    }
+}
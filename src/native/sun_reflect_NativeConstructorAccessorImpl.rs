@@ -1,9 +1,11 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::consts;
 use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
 use crate::oop::{Oop, OopDesc};
-use crate::runtime::{self, JavaThread};
+use crate::runtime::{self, exception, reflect, JavaThread};
 use crate::types::OopRef;
+use crate::util;
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
     vec![new_fn(
@@ -26,31 +28,49 @@ fn jvm_newInstance0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNI
         }
     };
 
-    let name = {
+    //setAccessible(true) suppresses the access check
+    if !reflect::is_override(ctor.clone()) {
+        let modifiers = reflect::get_Constructor_modifiers(ctor.clone());
+        if let Some(caller) = reflect::caller_class(jt) {
+            if !reflect::verify_member_access(&caller, &target_cls, modifiers) {
+                let msg = reflect::access_denied_msg(&caller, &target_cls, modifiers);
+                return Err(exception::new(jt, consts::J_ILLEGAL_ACCESS, Some(msg)));
+            }
+        }
+    }
+
+    let (name, is_abstract, has_finalizer) = {
         let cls = target_cls.lock().unwrap();
-        cls.name.clone()
+        let is_abstract = cls.is_abstract() || cls.is_interface();
+        (cls.name.clone(), is_abstract, cls.has_finalizer())
     };
+    if is_abstract {
+        let msg = String::from_utf8_lossy(name.as_slice()).replace('/', ".");
+        return Err(exception::new(jt, consts::J_INSTANTIATION, Some(msg)));
+    }
 
     let signature = runtime::reflect::get_Constructor_signature(ctor.clone());
 
-    let name = unsafe { std::str::from_utf8_unchecked(name.as_slice()) };
-    info!("newInstance0 {}:{}", name, signature);
+    info!(
+        "newInstance0 {}:{}",
+        util::mutf8::display(name.as_slice()),
+        signature
+    );
 
-    let mut ctor_args = Vec::new();
-    {
-        let v = arguments.lock().unwrap();
-        match &v.v {
-            Oop::Array(ary) => {
-                ctor_args.extend_from_slice(ary.elements.as_slice());
-            }
-            Oop::Null => (),
-            t => unreachable!("t = {:?}", t),
-        }
-    }
+    let mut ctor_args = match reflect::convert_args(signature.as_bytes(), arguments.clone()) {
+        Ok(args) => args,
+        Err(msg) => return Err(exception::new(jt, consts::J_IAE, Some(msg))),
+    };
 
     let oop = OopDesc::new_inst(target_cls.clone());
+    if has_finalizer {
+        runtime::gc::refs::register_finalizer(jt, &oop);
+    }
     ctor_args.insert(0, oop.clone());
     runtime::java_call::invoke_ctor(jt, target_cls, signature.as_bytes(), ctor_args);
 
-    Ok(Some(oop))
+    match jt.take_ex() {
+        Some(ex) => Err(reflect::new_invocation_target_ex(jt, ex)),
+        None => Ok(Some(oop)),
+    }
 }
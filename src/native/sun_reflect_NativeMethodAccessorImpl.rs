@@ -0,0 +1,70 @@
+#![allow(non_snake_case)]
+
+use crate::classfile::consts;
+use crate::classfile::signature::MethodSignature;
+use crate::native::{new_fn, JNIEnv, JNINativeMethod, JNIResult};
+use crate::oop::Oop;
+use crate::runtime::{cmp, exception, reflect, JavaCall, JavaThread, Stack};
+use crate::types::OopRef;
+
+pub fn get_native_methods() -> Vec<JNINativeMethod> {
+    vec![new_fn(
+        "invoke0",
+        "(Ljava/lang/reflect/Method;Ljava/lang/Object;[Ljava/lang/Object;)Ljava/lang/Object;",
+        Box::new(jvm_invoke0),
+    )]
+}
+
+fn jvm_invoke0(jt: &mut JavaThread, _env: JNIEnv, args: Vec<OopRef>) -> JNIResult {
+    let (method, receiver, arguments) = (&args[0], &args[1], &args[2]);
+
+    let mir = reflect::get_Method_mir(method.clone());
+    let target_cls = mir.method.class.clone();
+
+    //setAccessible(true) suppresses the access check
+    if !reflect::is_override(method.clone()) {
+        let modifiers = reflect::get_Method_modifiers(method.clone());
+        if let Some(caller) = reflect::caller_class(jt) {
+            if !reflect::verify_member_access(&caller, &target_cls, modifiers) {
+                let msg = reflect::access_denied_msg(&caller, &target_cls, modifiers);
+                return Err(exception::new(jt, consts::J_ILLEGAL_ACCESS, Some(msg)));
+            }
+        }
+    }
+
+    let is_static = mir.method.is_static();
+    if !is_static {
+        let receiver_cls = {
+            let v = receiver.lock().unwrap();
+            match &v.v {
+                Oop::Null => return Err(exception::new(jt, consts::J_NPE, None)),
+                Oop::Inst(inst) => Some(inst.class.clone()),
+                _ => None,
+            }
+        };
+        let is_instance = receiver_cls.is_some_and(|cls| cmp::instance_of(cls, target_cls));
+        if !is_instance {
+            let msg = Some("object is not an instance of declaring class".to_string());
+            return Err(exception::new(jt, consts::J_IAE, msg));
+        }
+    }
+
+    let mut call_args = match reflect::convert_args(mir.method.desc.as_slice(), arguments.clone()) {
+        Ok(args) => args,
+        Err(msg) => return Err(exception::new(jt, consts::J_IAE, Some(msg))),
+    };
+    if !is_static {
+        call_args.insert(0, receiver.clone());
+    }
+
+    //virtual methods dispatch on the receiver
+    let sig = MethodSignature::new(mir.method.desc.as_slice());
+    let mut stack = Stack::new(2);
+    let mut jc = JavaCall::new_with_args(jt, mir, call_args);
+    jc.invoke(jt, &mut stack, is_static);
+
+    match jt.take_ex() {
+        Some(ex) => Err(reflect::new_invocation_target_ex(jt, ex)),
+        None => Ok(Some(reflect::box_return(jt, &sig.retype, &mut stack))),
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(non_snake_case)]
 
 use crate::native::{self, new_fn, JNIEnv, JNINativeMethod, JNIResult};
-use crate::runtime::JavaThread;
+use crate::runtime::{self, JavaThread};
 use crate::types::OopRef;
 
 pub fn get_native_methods() -> Vec<JNINativeMethod> {
@@ -27,9 +27,11 @@ fn jvm_getCallerClass(jt: &mut JavaThread, _env: JNIEnv, _args: Vec<OopRef>) ->
 
     loop {
         let caller = callers.pop().unwrap();
+        //reflective calls are made on behalf of their caller
         if caller
             .method
             .check_annotation(b"Lsun/reflect/CallerSensitive;")
+            || runtime::reflect::is_reflection_frame(&caller)
         {
             continue;
         }
@@ -0,0 +1,111 @@
+/*
+Class hierarchy analysis, dependencies of the inline caches
+
+A virtual method no loaded class overrides is effectively final, every
+receiver dispatches to it. A call site targeting such a method is
+cached as Final (valid for any receiver class) and registered as a
+dependent of the method, keyed by (declaring class, method id).
+
+When a newly linked class overrides the method, the dependent sites are
+invalidated, they start over from Empty, and the method is never treated
+as final again. There is no JIT, inline caches are the only dependents.
+A default method has no vtable slot, it is overridden when a class or a
+sub interface selects another method for its id (link_lookup).
+
+invokeinterface sites are never Final, each new receiver class must be
+checked to implement the interface.
+*/
+
+use crate::oop::inline_cache::InlineCaches;
+use crate::types::{BytesRef, MethodIdRef};
+use crate::util;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+
+//(declaring class address, method id)
+type Key = (usize, BytesRef);
+
+#[derive(Default)]
+struct Registry {
+    //call sites (caches, bci) depending on the method not being overridden
+    deps: HashMap<Key, Vec<(Weak<InlineCaches>, i32)>>,
+    overridden: HashSet<Key>,
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+}
+
+//cache mir as the Final target of the site, false if mir is overridden
+pub fn make_final(caches: &Arc<InlineCaches>, bci: i32, mir: &MethodIdRef) -> bool {
+    //set under the registry lock, an override can't slip in between
+    depend(key(mir), caches, bci, || caches.set_final(bci, mir.clone()))
+}
+
+//a class being linked replaces mir in its vtable, or a method it inherits
+//by an interface
+pub fn on_override(mir: &MethodIdRef) {
+    invalidate(key(mir));
+}
+
+fn key(mir: &MethodIdRef) -> Key {
+    (Arc::as_ptr(&mir.method.class) as usize, mir.method.get_id())
+}
+
+fn depend<F>(key: Key, caches: &Arc<InlineCaches>, bci: i32, f: F) -> bool
+where
+    F: FnOnce(),
+{
+    util::sync_call_ctx(&REGISTRY, |r| {
+        if r.overridden.contains(&key) {
+            return false;
+        }
+
+        f();
+        let deps = r.deps.entry(key).or_default();
+        //drop the sites of freed methods
+        deps.retain(|(it, _)| it.strong_count() > 0);
+        deps.push((Arc::downgrade(caches), bci));
+        true
+    })
+}
+
+fn invalidate(key: Key) {
+    util::sync_call_ctx(&REGISTRY, |r| {
+        if let Some(deps) = r.deps.remove(&key) {
+            for (caches, bci) in deps {
+                if let Some(caches) = caches.upgrade() {
+                    caches.invalidate(bci);
+                }
+            }
+        }
+        r.overridden.insert(key);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_invalidate() {
+        let caches = Arc::new(InlineCaches::default());
+        let key: Key = (1, util::new_method_id(b"toString", b"()Ljava/lang/String;"));
+
+        let mut set = 0;
+        assert!(depend(key.clone(), &caches, 3, || set += 1));
+        assert!(depend(key.clone(), &caches, 7, || set += 1));
+        assert_eq!(set, 2);
+        let n = util::sync_call_ctx(&REGISTRY, |r| r.deps[&key].len());
+        assert_eq!(n, 2);
+
+        invalidate(key.clone());
+        assert!(util::sync_call_ctx(&REGISTRY, |r| !r
+            .deps
+            .contains_key(&key)));
+
+        //overridden, never final again
+        assert!(!depend(key, &caches, 3, || set += 1));
+        assert_eq!(set, 2);
+    }
+}
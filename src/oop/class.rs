@@ -1,20 +1,27 @@
 use crate::classfile::{
-    access_flags::*, attr_info::AttrType, attr_info::EnclosingMethod, attr_info::InnerClass,
-    constant_pool, consts,
+    access_flags::*, attr_info::AttrType, attr_info::BootstrapMethod, attr_info::EnclosingMethod,
+    attr_info::InnerClass, attr_info::RecordComponent, constant_pool, consts,
 };
+use crate::oop::cp_cache::CpCache;
 use crate::oop::method::MethodId;
-use crate::oop::{consts as oop_consts, field, method, Oop, OopDesc, ValueType};
-use crate::runtime::{self, require_class2, ClassLoader, JavaCall, JavaThread, Stack};
+use crate::oop::{cha, consts as oop_consts, field, method, Oop, OopDesc, ValueType};
+use crate::runtime::{self, exception, require_class2, ClassLoader, JavaCall, JavaThread, Stack};
 use crate::types::*;
 use crate::util;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::ThreadId;
 
 #[derive(Debug)]
 pub struct Class {
     pub name: BytesRef,
     pub state: State,
+    //the thread running the init, while BeingIni
+    init_thread: Option<ThreadId>,
+    //what made it IniErr, the cause of the later NoClassDefFoundErrors
+    init_error: Option<OopRef>,
     pub acc_flags: U2,
 
     // None for java.lang.Object
@@ -23,11 +30,11 @@ pub struct Class {
     // None for the "bootstrap" loader
     pub class_loader: Option<ClassLoader>,
 
-    monitor: Mutex<usize>,
-
     pub kind: ClassKind,
 }
 
+//one per class, not worth boxing Instance
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum ClassKind {
     Instance(ClassObject),
@@ -35,6 +42,30 @@ pub enum ClassKind {
     TypeArray(ArrayClassObject),
 }
 
+//a lookup table of a class, the entries it adds or replaces, over those
+//of its parents shared with the classes they belong to: a class costs
+//its own methods, not the hierarchy's. The first parent having the id
+//wins, as the entries of a parent fill a table missing them
+#[derive(Debug, Default, Clone)]
+struct Lookup {
+    own: HashMap<BytesRef, MethodIdRef>,
+    parents: Vec<Arc<Lookup>>,
+}
+
+impl Lookup {
+    fn get(&self, id: &BytesRef) -> Option<&MethodIdRef> {
+        self.own
+            .get(id)
+            .or_else(|| self.parents.iter().find_map(|it| it.get(id)))
+    }
+
+    //every entry, those the table's own or an earlier parent's hide too
+    fn for_each(&self, f: &mut impl FnMut(&BytesRef, &MethodIdRef)) {
+        self.own.iter().for_each(|(id, m)| f(id, m));
+        self.parents.iter().for_each(|it| it.for_each(f));
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
 pub enum ClassKindType {
     Instance,
@@ -55,11 +86,29 @@ pub enum State {
 #[derive(Debug)]
 pub struct ClassObject {
     pub class_file: ClassFileRef,
+    pub cp_cache: Arc<CpCache>,
 
     pub n_inst_fields: usize,
 
     pub all_methods: HashMap<BytesRef, MethodIdRef>,
-    v_table: HashMap<BytesRef, MethodIdRef>,
+    /*
+    Lookup tables by id, built at link time, layered over those of the
+    super class & interfaces (see Lookup), no class lock taken walking up
+    the hierarchy:
+      methods    all_methods, then the super classes', get_class_method
+      v_table    the non-static methods, then the super classes',
+                 get_virtual_method
+      itf_methods  v_table, then the super interfaces', then the super
+                 class's, get_interface_method
+    */
+    methods: Arc<Lookup>,
+    v_table: Arc<Lookup>,
+    itf_methods: Arc<Lookup>,
+    //super's vtable, overridden slots replaced, new methods appended
+    vtable: Vec<MethodIdRef>,
+    //interface name -> implementation of each interface method, by
+    //itable_index. None, no implementation, AbstractMethodError
+    itable: HashMap<BytesRef, Vec<Option<MethodIdRef>>>,
 
     pub static_fields: HashMap<BytesRef, FieldIdRef>,
     pub inst_fields: HashMap<BytesRef, FieldIdRef>,
@@ -74,6 +123,12 @@ pub struct ClassObject {
     pub source_file: Option<BytesRef>,
     pub enclosing_method: Option<EnclosingMethod>,
     pub inner_classes: Option<Vec<InnerClass>>,
+    pub bootstrap_methods: Option<Vec<BootstrapMethod>>,
+    pub record_components: Option<Vec<RecordComponent>>,
+    pub permitted_subclasses: Option<Vec<U2>>,
+
+    //instances are registered for finalization, see gc::refs
+    pub has_finalizer: bool,
 }
 
 #[derive(Debug)]
@@ -89,44 +144,175 @@ pub struct ArrayClassObject {
     pub mirror: Option<OopRef>,
 }
 
-//invoke "<clinit>"
+/*
+Class initialization, JLS 12.4.2
+
+The class lock is the LC of the spec, never held while java code runs.
+A class Linked is claimed by a thread, BeingIni with init_thread set, it
+inits the super class & interfaces, then runs <clinit>, the class is
+FullyIni, or IniErr if any threw. Meanwhile:
+  the same thread    returns at once, the class is used as it is, a
+                     recursive request (a <clinit> touching its class,
+                     or a cycle of classes)
+  another thread     waits on INIT_DONE, then looks again
+
+An IniErr class never runs <clinit> again, every later init throws
+NoClassDefFoundError, caused by the error of the failed init, as JDK 17
+reports it. The exception of the failed <clinit> itself is thrown once,
+wrapped in ExceptionInInitializerError unless an Error, the exception
+of a failed super as it is. Array classes are FullyIni at once.
+*/
+lazy_static! {
+    static ref INIT_LOCK: Mutex<()> = Mutex::new(());
+    static ref INIT_DONE: Condvar = Condvar::new();
+}
+
+#[derive(Debug, PartialEq)]
+enum InitStep {
+    Done,
+    Wait,
+    Erroneous,
+    Run,
+}
+
+fn init_step(state: State, init_thread: Option<ThreadId>, me: ThreadId) -> InitStep {
+    match state {
+        State::BeingIni if init_thread == Some(me) => InitStep::Done,
+        State::BeingIni => InitStep::Wait,
+        State::IniErr => InitStep::Erroneous,
+        State::Linked => InitStep::Run,
+        _ => InitStep::Done,
+    }
+}
+
 pub fn init_class_fully(thread: &mut JavaThread, class: ClassRef) {
-    let need = { class.lock().unwrap().state == State::BeingIni };
+    let me = std::thread::current().id();
+    loop {
+        //taken before the class lock, a finish can't slip in between
+        //the look & the wait
+        let guard = INIT_LOCK.lock().unwrap();
+        let mut cls = class.lock().unwrap();
+        if !matches!(cls.kind, ClassKind::Instance(_)) {
+            cls.state = State::FullyIni;
+            return;
+        }
 
-    if need {
-        let (mir, name) = {
-            let mut class = class.lock().unwrap();
-            class.state = State::FullyIni;
+        match init_step(cls.state, cls.init_thread, me) {
+            InitStep::Done => return,
+            InitStep::Wait => {
+                drop(cls);
+                let _guard = runtime::thread::in_state(
+                    runtime::thread::THREAD_STATUS_IN_OBJECT_WAIT,
+                    || INIT_DONE.wait(guard).unwrap(),
+                );
+            }
+            InitStep::Erroneous => {
+                let msg = no_class_def_msg(cls.name.as_slice());
+                let cause = cls.init_error.clone();
+                drop(cls);
+                drop(guard);
+                let ex = exception::new(thread, consts::J_NO_CLASS_DEF_FOUND, Some(msg));
+                if let Some(cause) = cause {
+                    exception::set_cause(&ex, cause);
+                }
+                thread.set_ex(ex);
+                return;
+            }
+            InitStep::Run => {
+                cls.state = State::BeingIni;
+                cls.init_thread = Some(me);
+                break;
+            }
+        }
+    }
 
-            let id = util::new_method_id(b"<clinit>", b"()V");
-            let mir = class.get_this_class_method(id);
-            (mir, class.name.clone())
-        };
+    //the super class, then the super interfaces declaring default
+    //methods, an interface inits neither
+    let supers = {
+        let cls = class.lock().unwrap();
+        let mut supers: Vec<ClassRef> = cls.super_class.iter().cloned().collect();
+        if let ClassKind::Instance(cls_obj) = &cls.kind {
+            if (cls.acc_flags & ACC_INTERFACE) == 0 {
+                supers.extend(interfaces_to_init(cls_obj));
+            }
+        }
+        supers
+    };
+    for it in supers {
+        init_class_fully(thread, it);
+        //failed, this class is erroneous too, the exception is pending
+        if let Some(ex) = thread.take_ex() {
+            finish_init(&class, Err(ex.clone()));
+            thread.set_ex(ex);
+            return;
+        }
+    }
 
-        match mir {
-            Ok(mir) => {
-                info!("call {}:<clinit>", String::from_utf8_lossy(name.as_slice()));
-                let mut stack = Stack::new(0);
-                let jc = JavaCall::new(thread, &mut stack, mir);
-                jc.unwrap().invoke(thread, &mut stack, true);
+    let (mir, name, strings) = {
+        let mut cls = class.lock().unwrap();
+        let mut strings = Vec::new();
+        if let ClassKind::Instance(cls_obj) = &mut cls.kind {
+            cls_obj.init_static_fields();
+            strings = cls_obj.string_constant_values();
+        }
+        let id = util::new_method_id(b"<clinit>", b"()V");
+        (cls.get_this_class_method(id), cls.name.clone(), strings)
+    };
+
+    //String ConstantValues, interned as ldc's, the String ctor runs
+    //without the class lock
+    for (fir, utf8) in strings {
+        let v = util::oop::new_constant_string(thread, utf8.as_slice());
+        class.lock().unwrap().put_static_field_value(fir, v);
+    }
+
+    if let Ok(mir) = mir {
+        info!("call {}:<clinit>", String::from_utf8_lossy(name.as_slice()));
+        let mut stack = Stack::new(0);
+        let jc = JavaCall::new(thread, &mut stack, mir);
+        jc.unwrap().invoke(thread, &mut stack, true);
+    }
+
+    match thread.take_ex() {
+        Some(ex) => {
+            let ex = exception::new_init_error(thread, ex);
+            finish_init(&class, Err(ex.clone()));
+            thread.set_ex(ex);
+        }
+        None => finish_init(&class, Ok(())),
+    }
+}
+
+//the init of class is over, FullyIni or IniErr by the error, its
+//waiters look again
+fn finish_init(class: &ClassRef, r: Result<(), OopRef>) {
+    {
+        let mut cls = class.lock().unwrap();
+        cls.init_thread = None;
+        match r {
+            Ok(()) => cls.state = State::FullyIni,
+            Err(ex) => {
+                cls.state = State::IniErr;
+                cls.init_error = Some(ex);
             }
-            _ => (),
         }
     }
+    let _guard = INIT_LOCK.lock().unwrap();
+    INIT_DONE.notify_all();
+}
+
+fn no_class_def_msg(name: &[u8]) -> String {
+    format!(
+        "Could not initialize class {}",
+        String::from_utf8_lossy(name).replace('/', ".")
+    )
 }
 
 pub fn load_and_init(jt: &mut JavaThread, name: &[u8]) -> ClassRef {
     // trace!("load_and_init 1 name={}", String::from_utf8_lossy(name));
-    let cls_name = unsafe { std::str::from_utf8_unchecked(name) };
-    let class = runtime::require_class3(None, name).expect(cls_name);
+    let cls_name = util::mutf8::display(name);
+    let class = runtime::require_class3(None, name).expect(&cls_name);
     // trace!("load_and_init 2 name={}", String::from_utf8_lossy(name));
-    {
-        let mut class = class.lock().unwrap();
-        class.init_class(jt);
-        //                trace!("finish init_class: {}", String::from_utf8_lossy(*c));
-    }
-
-    // trace!("load_and_init 3, name={}", String::from_utf8_lossy(name));
     init_class_fully(jt, class.clone());
     //            trace!("finish init_class_fully: {}", String::from_utf8_lossy(*c));
     // trace!("load_and_init 4, name={}", String::from_utf8_lossy(name));
@@ -134,6 +320,124 @@ pub fn load_and_init(jt: &mut JavaThread, name: &[u8]) -> ClassRef {
     class
 }
 
+//the super interfaces init_class_fully inits, JLS 12.4.2: those declaring a
+//non-abstract, non-static method, in the order of a recursive
+//enumeration of the directly implemented interfaces, each after its own
+//super interfaces
+fn interfaces_to_init(class: &ClassObject) -> Vec<ClassRef> {
+    let all = post_order(&class.declared_interfaces(), &direct_interfaces, &|a, b| {
+        Arc::ptr_eq(a, b)
+    });
+    all.into_iter().filter(declares_default_method).collect()
+}
+
+fn direct_interfaces(class: &ClassRef) -> Vec<ClassRef> {
+    let class = class.lock().unwrap();
+    match &class.kind {
+        ClassKind::Instance(cls_obj) => cls_obj.declared_interfaces(),
+        _ => Vec::new(),
+    }
+}
+
+fn declares_default_method(class: &ClassRef) -> bool {
+    let class = class.lock().unwrap();
+    match &class.kind {
+        ClassKind::Instance(cls_obj) => cls_obj
+            .class_file
+            .methods
+            .iter()
+            .any(|it| (it.acc_flags & (ACC_ABSTRACT | ACC_STATIC)) == 0),
+        _ => false,
+    }
+}
+
+//the roots, each after its supers, each once
+fn post_order<T: Clone>(
+    roots: &[T],
+    supers: &dyn Fn(&T) -> Vec<T>,
+    same: &dyn Fn(&T, &T) -> bool,
+) -> Vec<T> {
+    fn visit<T: Clone>(
+        it: &T,
+        supers: &dyn Fn(&T) -> Vec<T>,
+        same: &dyn Fn(&T, &T) -> bool,
+        out: &mut Vec<T>,
+    ) {
+        if out.iter().any(|v| same(v, it)) {
+            return;
+        }
+        for s in supers(it) {
+            visit(&s, supers, same, out);
+        }
+        out.push(it.clone());
+    }
+
+    let mut out = Vec::new();
+    for it in roots {
+        visit(it, supers, same, &mut out);
+    }
+    out
+}
+
+//super interfaces & super classes' interfaces of class, each once
+fn collect_interfaces(class: &ClassRef, out: &mut Vec<ClassRef>) {
+    let (interfaces, super_class) = {
+        let class = class.lock().unwrap();
+        match &class.kind {
+            ClassKind::Instance(cls_obj) => (
+                cls_obj.interfaces.values().cloned().collect(),
+                class.super_class.clone(),
+            ),
+            _ => (Vec::new(), None),
+        }
+    };
+
+    for it in interfaces {
+        if !out.iter().any(|v| Arc::ptr_eq(v, &it)) {
+            out.push(it.clone());
+            collect_interfaces(&it, out);
+        }
+    }
+    if let Some(super_class) = super_class {
+        collect_interfaces(&super_class, out);
+    }
+}
+
+//the default method 'id' of the maximally specific interface, None when
+//there is none or more than one
+fn select_default_method(interfaces: &[ClassRef], id: BytesRef) -> Option<MethodIdRef> {
+    let candidates: Vec<(ClassRef, MethodIdRef)> = interfaces
+        .iter()
+        .filter_map(|itf| {
+            let m = itf.lock().unwrap().get_this_class_method(id.clone()).ok()?;
+            if m.method.is_abstract() || m.method.is_static() || m.method.is_private() {
+                None
+            } else {
+                Some((itf.clone(), m))
+            }
+        })
+        .collect();
+
+    //drop the ones a more specific candidate extends
+    let mut selected: Vec<MethodIdRef> = Vec::new();
+    for (itf, m) in candidates.iter() {
+        let overridden = candidates.iter().any(|(other, _)| {
+            let mut supers = Vec::new();
+            collect_interfaces(other, &mut supers);
+            supers.iter().any(|v| Arc::ptr_eq(v, itf))
+        });
+        if !overridden {
+            selected.push(m.clone());
+        }
+    }
+
+    if selected.len() == 1 {
+        selected.pop()
+    } else {
+        None
+    }
+}
+
 impl Class {
     pub fn get_class_state(&self) -> State {
         self.state
@@ -175,21 +479,39 @@ impl Class {
         (self.acc_flags & ACC_ABSTRACT) == ACC_ABSTRACT
     }
 
-    pub fn is_interface(&self) -> bool {
-        (self.acc_flags & ACC_INTERFACE) == ACC_INTERFACE
+    pub fn has_finalizer(&self) -> bool {
+        match &self.kind {
+            ClassKind::Instance(class_obj) => class_obj.has_finalizer,
+            _ => false,
+        }
     }
 
-    pub fn monitor_enter(&mut self) {
-        let mut v = self.monitor.lock().unwrap();
-        *v += 1;
+    //as HotSpot, a Record attribute on a direct subclass of java.lang.Record
+    pub fn record_components(&self) -> Option<&Vec<RecordComponent>> {
+        let super_class = self.super_class.as_ref()?;
+        if super_class.lock().unwrap().name.as_slice() != consts::J_RECORD {
+            return None;
+        }
+        match &self.kind {
+            ClassKind::Instance(class_obj) => class_obj.record_components.as_ref(),
+            _ => None,
+        }
     }
 
-    pub fn monitor_exit(&mut self) {
-        let mut v = self.monitor.lock().unwrap();
-        *v -= 1;
+    pub fn permitted_subclasses(&self) -> Option<&Vec<U2>> {
+        match &self.kind {
+            ClassKind::Instance(class_obj) => class_obj.permitted_subclasses.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn is_interface(&self) -> bool {
+        (self.acc_flags & ACC_INTERFACE) == ACC_INTERFACE
     }
 
     pub fn link_class(&mut self, self_ref: ClassRef) {
+        let is_interface = self.is_interface();
+        let this_ref = self_ref.clone();
         match &mut self.kind {
             ClassKind::Instance(class_obj) => {
                 self.super_class =
@@ -211,7 +533,12 @@ impl Class {
                 class_obj.link_fields(self_ref.clone(), self.name.clone(), n_super_inst);
 
                 class_obj.link_interfaces();
-                class_obj.link_methods(self_ref);
+                class_obj.link_methods(self_ref, self.super_class.as_ref(), is_interface);
+                class_obj.link_lookup(self.super_class.as_ref());
+                class_obj.has_finalizer = class_obj.find_finalizer(self.super_class.as_ref());
+                if !is_interface {
+                    class_obj.link_itable(self.super_class.as_ref());
+                }
                 class_obj.link_attributes();
             }
 
@@ -226,29 +553,19 @@ impl Class {
             }
         }
 
-        self.set_class_state(State::Linked);
-    }
-
-    pub fn init_class(&mut self, thread: &mut JavaThread) {
-        match &mut self.kind {
-            ClassKind::Instance(class_obj) => {
-                if self.state == State::Linked {
-                    self.state = State::BeingIni;
-
-                    if let Some(super_class) = self.super_class.as_ref() {
-                        {
-                            super_class.lock().unwrap().init_class(thread);
-                        }
-
-                        init_class_fully(thread, super_class.clone());
-                    }
-
-                    class_obj.init_static_fields();
-                }
-            }
+        //the fields of Reference, the super class of the reference classes
+        let slot = |name: &[u8], desc: &[u8]| {
+            let super_class = self.super_class.as_ref().unwrap().lock().unwrap();
+            super_class.get_field_id(name, desc, false).offset
+        };
+        runtime::gc::refs::on_link(
+            &this_ref,
+            self.name.as_slice(),
+            self.super_class.as_ref(),
+            slot,
+        );
 
-            _ => self.state = State::FullyIni,
-        }
+        self.set_class_state(State::Linked);
     }
 
     pub fn get_class_kind_type(&self) -> ClassKindType {
@@ -292,6 +609,15 @@ impl Class {
         }
     }
 
+    //None until the mirror is made
+    pub fn try_get_mirror(&self) -> Option<OopRef> {
+        match &self.kind {
+            ClassKind::Instance(cls_obj) => cls_obj.mirror.clone(),
+            ClassKind::TypeArray(typ_ary) => typ_ary.mirror.clone(),
+            ClassKind::ObjectArray(obj_ary) => obj_ary.mirror.clone(),
+        }
+    }
+
     pub fn set_mirror(&mut self, mirror: OopRef) {
         match &mut self.kind {
             ClassKind::Instance(cls_obj) => cls_obj.mirror = Some(mirror),
@@ -302,6 +628,11 @@ impl Class {
 }
 
 impl ArrayClassObject {
+    //the element class of a multi dimension array, int[] of int[][]
+    pub fn get_down_type(&self) -> Option<ClassRef> {
+        self.down_type.clone()
+    }
+
     pub fn get_dimension(&self) -> usize {
         match self.down_type.as_ref() {
             Some(down_type) => {
@@ -341,6 +672,47 @@ impl Class {
         self.get_interface_method_inner(id)
     }
 
+    //methods of an interface, in itable order
+    fn itable_methods(&self) -> Vec<MethodIdRef> {
+        match &self.kind {
+            ClassKind::Instance(cls_obj) => {
+                let mut methods: Vec<MethodIdRef> = cls_obj
+                    .all_methods
+                    .values()
+                    .filter(|it| it.itable_index.is_some())
+                    .cloned()
+                    .collect();
+                methods.sort_by_key(|it| it.itable_index);
+                methods
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /*
+    The implementation of the method at itable slot 'index' of interface
+      None           the class doesn't implement the interface
+      Some(None)     implemented, but no method, AbstractMethodError
+    */
+    pub fn get_itable_method(&self, itf: &BytesRef, index: usize) -> Option<Option<MethodIdRef>> {
+        match &self.kind {
+            ClassKind::Instance(cls_obj) => cls_obj
+                .itable
+                .get(itf)
+                .and_then(|it| it.get(index))
+                .cloned(),
+            _ => None,
+        }
+    }
+
+    //the override of a method at vtable slot 'index'
+    pub fn get_vtable_method(&self, index: usize) -> Option<MethodIdRef> {
+        match &self.kind {
+            ClassKind::Instance(cls_obj) => cls_obj.vtable.get(index).cloned(),
+            _ => None,
+        }
+    }
+
     pub fn get_field_id(&self, name: &[u8], desc: &[u8], is_static: bool) -> FieldIdRef {
         let field_id = util::new_field_id(self.name.as_slice(), name, desc);
         //        error!("get_field_id = {}", String::from_utf8_lossy(field_id.as_slice()));
@@ -371,6 +743,27 @@ impl Class {
             .get_field_id(name, desc, is_static)
     }
 
+    //get_field_id of a field which may not exist, the super interfaces'
+    //static fields searched too, JVMS 5.4.3.2
+    pub fn find_field_id(&self, name: &[u8], desc: &[u8], is_static: bool) -> Option<FieldIdRef> {
+        let cls_obj = match &self.kind {
+            ClassKind::Instance(cls_obj) => cls_obj,
+            _ => return None,
+        };
+        let field_id = util::new_field_id(self.name.as_slice(), name, desc);
+        let fields = match is_static {
+            true => &cls_obj.static_fields,
+            false => &cls_obj.inst_fields,
+        };
+        if let Some(fid) = fields.get(&field_id) {
+            return Some(fid.clone());
+        }
+
+        let itfs = cls_obj.interfaces.values().filter(|_| is_static);
+        itfs.chain(self.super_class.iter())
+            .find_map(|it| it.lock().unwrap().find_field_id(name, desc, is_static))
+    }
+
     pub fn put_field_value(&self, receiver: OopRef, fir: FieldIdRef, v: OopRef) {
         let mut rff = receiver.lock().unwrap();
         match &mut rff.v {
@@ -478,8 +871,24 @@ impl Class {
                     method.acc_flags |= ACC_NATIVE;
                     let m = Arc::new(method::MethodId {
                         offset: m.offset,
+                        vtable_index: m.vtable_index,
+                        itable_index: m.itable_index,
                         method,
                     });
+                    if let Some(i) = m.vtable_index {
+                        cls.vtable[i] = m.clone();
+                    }
+                    for table in [&mut cls.methods, &mut cls.v_table] {
+                        let table = Arc::make_mut(table);
+                        if let Some(it) = table.own.get_mut(&id) {
+                            *it = m.clone();
+                        }
+                    }
+                    //the v_table layer is a new one, its own are hidden
+                    let itf_methods = Arc::make_mut(&mut cls.itf_methods);
+                    if let Some(it) = itf_methods.parents.first_mut() {
+                        *it = cls.v_table.clone();
+                    }
                     cls.all_methods.insert(id.clone(), m);
                 }
 
@@ -502,11 +911,17 @@ impl Class {
         let cp = &class_file.cp;
         let name = constant_pool::get_class_name(cp, class_file.this_class as usize).unwrap();
         let acc_flags = class_file.acc_flags;
+        let cp_cache = Arc::new(CpCache::new(cp.len()));
         let class_obj = ClassObject {
             class_file,
+            cp_cache,
             n_inst_fields: 0,
             all_methods: HashMap::new(),
-            v_table: HashMap::new(),
+            methods: Arc::new(Lookup::default()),
+            v_table: Arc::new(Lookup::default()),
+            itf_methods: Arc::new(Lookup::default()),
+            vtable: Vec::new(),
+            itable: HashMap::new(),
             static_fields: HashMap::new(),
             inst_fields: HashMap::new(),
             static_field_values: vec![],
@@ -516,15 +931,20 @@ impl Class {
             source_file: None,
             enclosing_method: None,
             inner_classes: None,
+            bootstrap_methods: None,
+            record_components: None,
+            permitted_subclasses: None,
+            has_finalizer: false,
         };
 
         Self {
             name,
             state: State::Allocated,
+            init_thread: None,
+            init_error: None,
             acc_flags,
             super_class: None,
             class_loader,
-            monitor: Mutex::new(0),
 
             kind: ClassKind::Instance(class_obj),
         }
@@ -544,10 +964,11 @@ impl Class {
         Self {
             name,
             state: State::Allocated,
+            init_thread: None,
+            init_error: None,
             acc_flags: 0, //todo: should be 0?
             super_class: None,
             class_loader: Some(class_loader),
-            monitor: Mutex::new(0),
             kind: ClassKind::ObjectArray(ary_cls_obj),
         }
     }
@@ -567,10 +988,11 @@ impl Class {
         Self {
             name: new_ref!(name),
             state: State::Allocated,
+            init_thread: None,
+            init_error: None,
             acc_flags: 0, //todo: should be 0?
             super_class: None,
             class_loader: Some(class_loader),
-            monitor: Mutex::new(0),
             kind: ClassKind::TypeArray(ary_cls_obj),
         }
     }
@@ -615,15 +1037,27 @@ impl Class {
         Self {
             name: new_ref!(name2),
             state: State::Allocated,
+            init_thread: None,
+            init_error: None,
             acc_flags: 0, //todo: should be 0?
             super_class: None,
             class_loader: Some(class_loader),
-            monitor: Mutex::new(0),
             kind,
         }
     }
 }
 
+//for diagnostics, see oop::layout
+impl ClassObject {
+    pub fn vtable(&self) -> &[MethodIdRef] {
+        self.vtable.as_slice()
+    }
+
+    pub fn itable(&self) -> &HashMap<BytesRef, Vec<Option<MethodIdRef>>> {
+        &self.itable
+    }
+}
+
 //inner api for link
 impl ClassObject {
     fn link_super_class(
@@ -690,6 +1124,17 @@ impl ClassObject {
         }
     }
 
+    //the direct super interfaces, in the order of the class file
+    fn declared_interfaces(&self) -> Vec<ClassRef> {
+        let cp = &self.class_file.cp;
+        self.class_file
+            .interfaces
+            .iter()
+            .filter_map(|it| constant_pool::get_class_name(cp, *it as usize))
+            .filter_map(|name| self.interfaces.get(&name).cloned())
+            .collect()
+    }
+
     fn link_interfaces(&mut self) {
         let class_file = self.class_file.clone();
         let cp = &class_file.cp;
@@ -709,10 +1154,32 @@ impl ClassObject {
             });
     }
 
-    fn link_methods(&mut self, this_ref: ClassRef) {
+    fn link_methods(
+        &mut self,
+        this_ref: ClassRef,
+        super_class: Option<&ClassRef>,
+        is_interface: bool,
+    ) {
         let class_file = self.class_file.clone();
         let cp = &class_file.cp;
 
+        //interface methods are dispatched by name, no vtable
+        if !is_interface {
+            if let Some(super_class) = super_class {
+                let super_class = super_class.lock().unwrap();
+                if let ClassKind::Instance(super_obj) = &super_class.kind {
+                    self.vtable = super_obj.vtable.clone();
+                }
+            }
+        }
+        let super_slots: HashMap<BytesRef, usize> = self
+            .vtable
+            .iter()
+            .enumerate()
+            .map(|(i, it)| (it.method.get_id(), i))
+            .collect();
+        let mut n_itable = 0;
+
         class_file.methods.iter().enumerate().for_each(|(i, it)| {
             let mut vis_annos = Vec::new();
             let mut vis_param_annos = Vec::new();
@@ -730,44 +1197,190 @@ impl ClassObject {
 
             let method = method::Method::new(cp, it, this_ref.clone(), vis_annos, vis_param_annos);
             let id = method.get_id();
-            let method_id = Arc::new(method::MethodId { offset: i, method });
+            let vtable_index = if is_interface
+                || method.is_static()
+                || method.is_private()
+                || method.name.as_slice() == consts::METHOD_NAME_INIT
+            {
+                None
+            } else {
+                Some(super_slots.get(&id).cloned().unwrap_or(self.vtable.len()))
+            };
+            let itable_index = if is_interface && !method.is_static() && !method.is_private() {
+                n_itable += 1;
+                Some(n_itable - 1)
+            } else {
+                None
+            };
+            let method_id = Arc::new(method::MethodId {
+                offset: i,
+                vtable_index,
+                itable_index,
+                method,
+            });
+            match vtable_index {
+                Some(slot) if slot == self.vtable.len() => self.vtable.push(method_id.clone()),
+                Some(slot) => {
+                    cha::on_override(&self.vtable[slot]);
+                    self.vtable[slot] = method_id.clone();
+                }
+                None => (),
+            }
 
             self.all_methods.insert(id.clone(), method_id.clone());
 
             if !method_id.method.is_static() {
-                self.v_table.insert(id, method_id);
+                Arc::make_mut(&mut self.v_table).own.insert(id, method_id);
             }
         });
     }
 
+    fn link_lookup(&mut self, super_class: Option<&ClassRef>) {
+        //the table f of an instance class
+        fn table(class: &ClassRef, f: fn(&ClassObject) -> &Arc<Lookup>) -> Option<Arc<Lookup>> {
+            match &class.lock().unwrap().kind {
+                ClassKind::Instance(cls_obj) => Some(f(cls_obj).clone()),
+                _ => None,
+            }
+        }
+
+        let methods = Arc::make_mut(&mut self.methods);
+        methods.own = self.all_methods.clone();
+        if let Some(super_class) = super_class {
+            methods.parents.extend(table(super_class, |it| &it.methods));
+            let v_table = Arc::make_mut(&mut self.v_table);
+            v_table.parents.extend(table(super_class, |it| &it.v_table));
+        }
+
+        let mut parents = vec![self.v_table.clone()];
+        for itf in self.declared_interfaces() {
+            parents.extend(table(&itf, |it| &it.itf_methods));
+        }
+        if let Some(super_class) = super_class {
+            parents.extend(table(super_class, |it| &it.itf_methods));
+        }
+        let itf_methods = Lookup {
+            own: HashMap::new(),
+            parents,
+        };
+
+        //a default method has no vtable slot, link_methods can't see it
+        //overridden: any inherited method the class selects another for
+        let mut replaced = Vec::new();
+        for parent in &itf_methods.parents[1..] {
+            parent.for_each(&mut |id, m| match itf_methods.get(id) {
+                Some(it) if !Arc::ptr_eq(it, m) => replaced.push(m.clone()),
+                _ => (),
+            });
+        }
+        self.itf_methods = Arc::new(itf_methods);
+        replaced.iter().for_each(cha::on_override);
+    }
+
+    //the most derived finalize()V does something, Object's is empty
+    fn find_finalizer(&self, super_class: Option<&ClassRef>) -> bool {
+        let id = util::new_method_id(b"finalize", b"()V");
+        match self.all_methods.get(&id) {
+            Some(it) if !it.method.is_static() => !it.method.is_empty(),
+            _ => super_class.is_some_and(|it| it.lock().unwrap().has_finalizer()),
+        }
+    }
+
+    /*
+    For each interface the class implements (directly, by a super
+    interface, or by the super class), select the implementation of each
+    interface method:
+      1. the class's vtable method with the same name & descriptor
+      2. else the default method of the maximally specific interface
+         declaring it, when there is exactly one
+    */
+    fn link_itable(&mut self, super_class: Option<&ClassRef>) {
+        let mut interfaces = Vec::new();
+        for it in self.interfaces.values() {
+            if !interfaces.iter().any(|v| Arc::ptr_eq(v, it)) {
+                interfaces.push(it.clone());
+                collect_interfaces(it, &mut interfaces);
+            }
+        }
+        if let Some(super_class) = super_class {
+            collect_interfaces(super_class, &mut interfaces);
+        }
+
+        let by_vtable: HashMap<BytesRef, MethodIdRef> = self
+            .vtable
+            .iter()
+            .map(|it| (it.method.get_id(), it.clone()))
+            .collect();
+
+        for itf in interfaces.iter() {
+            let (name, methods) = {
+                let itf = itf.lock().unwrap();
+                (itf.name.clone(), itf.itable_methods())
+            };
+
+            let impls = methods
+                .iter()
+                .map(|m| {
+                    let id = m.method.get_id();
+                    match by_vtable.get(&id) {
+                        Some(m) => Some(m.clone()),
+                        None => select_default_method(interfaces.as_slice(), id),
+                    }
+                })
+                .collect();
+            self.itable.insert(name, impls);
+        }
+    }
+
     fn link_attributes(&mut self) {
         let class_file = self.class_file.clone();
         let cp = &class_file.cp;
 
-        class_file.attrs.iter().for_each(|a| {
-            match a {
-                AttrType::Signature { signature_index } => {
-                    if let Some(s) = constant_pool::get_utf8(cp, *signature_index as usize) {
-                        self.signature = Some(s);
-                    }
-                }
-                AttrType::SourceFile { source_file_index } => {
-                    if let Some(s) = constant_pool::get_utf8(cp, *source_file_index as usize) {
-                        self.source_file = Some(s);
-                    }
-                }
-                AttrType::EnclosingMethod { em } => {
-                    self.enclosing_method = Some(em.clone());
+        class_file.attrs.iter().for_each(|a| match a {
+            AttrType::Signature { signature_index } => {
+                if let Some(s) = constant_pool::get_utf8(cp, *signature_index as usize) {
+                    self.signature = Some(s);
                 }
-                AttrType::InnerClasses { classes } => {
-                    self.inner_classes = Some(classes.clone());
+            }
+            AttrType::SourceFile { source_file_index } => {
+                if let Some(s) = constant_pool::get_utf8(cp, *source_file_index as usize) {
+                    self.source_file = Some(s);
                 }
-                //todo: ATTRIBUTE_BootstrapMethods
-                _ => (),
             }
+            AttrType::EnclosingMethod { em } => {
+                self.enclosing_method = Some(em.clone());
+            }
+            AttrType::InnerClasses { classes } => {
+                self.inner_classes = Some(classes.clone());
+            }
+            AttrType::BootstrapMethods { methods, .. } => {
+                self.bootstrap_methods = Some(methods.clone());
+            }
+            AttrType::Record { components } => {
+                self.record_components = Some(components.clone());
+            }
+            AttrType::PermittedSubclasses { classes } => {
+                self.permitted_subclasses = Some(classes.clone());
+            }
+            _ => (),
         });
     }
 
+    //the static fields of a String ConstantValue, still the Utf8
+    fn string_constant_values(&self) -> Vec<(FieldIdRef, BytesRef)> {
+        self.static_fields
+            .values()
+            .filter_map(|it| {
+                let v = self.static_field_values[it.offset].clone();
+                let v = v.lock().unwrap();
+                match &v.v {
+                    Oop::ConstUtf8(utf8) => Some((it.clone(), utf8.clone())),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
     fn init_static_fields(&mut self) {
         let values = &mut self.static_field_values;
         self.static_fields.iter().for_each(|(_, it)| {
@@ -790,71 +1403,172 @@ impl Class {
         with_super: bool,
     ) -> Result<MethodIdRef, ()> {
         match &self.kind {
-            ClassKind::Instance(cls_obj) => match cls_obj.all_methods.get(&id) {
-                Some(m) => return Ok(m.clone()),
-                None => (),
-            },
-
-            ClassKind::ObjectArray(ary) => {
-                //use java/lang/Object, methods
+            ClassKind::Instance(cls_obj) => {
+                let it = match with_super {
+                    true => cls_obj.methods.get(&id),
+                    false => cls_obj.all_methods.get(&id),
+                };
+                it.cloned().ok_or(())
             }
-            _ => unreachable!(),
-        }
 
-        if with_super {
-            match self.super_class.as_ref() {
-                Some(super_class) => {
-                    return super_class
-                        .lock()
-                        .unwrap()
-                        .get_class_method_inner(id, with_super);
-                }
-                None => return Err(()),
-            }
+            //java/lang/Object's methods
+            ClassKind::ObjectArray(_) => match (with_super, self.super_class.as_ref()) {
+                (true, Some(super_class)) => super_class
+                    .lock()
+                    .unwrap()
+                    .get_class_method_inner(id, with_super),
+                _ => Err(()),
+            },
+            _ => unreachable!(),
         }
-
-        Err(())
     }
 
     pub fn get_virtual_method_inner(&self, id: BytesRef) -> Result<MethodIdRef, ()> {
         match &self.kind {
-            ClassKind::Instance(cls_obj) => match cls_obj.v_table.get(&id) {
-                Some(m) => return Ok(m.clone()),
-                None => (),
-            },
+            ClassKind::Instance(cls_obj) => cls_obj.v_table.get(&id).cloned().ok_or(()),
             _ => unreachable!(),
         }
-
-        match self.super_class.as_ref() {
-            Some(super_class) => {
-                return super_class.lock().unwrap().get_virtual_method_inner(id);
-            }
-            None => return Err(()),
-        }
     }
 
     pub fn get_interface_method_inner(&self, id: BytesRef) -> Result<MethodIdRef, ()> {
         match &self.kind {
-            ClassKind::Instance(cls_obj) => match cls_obj.v_table.get(&id) {
-                Some(m) => return Ok(m.clone()),
-                None => {
-                    for (_, itf) in cls_obj.interfaces.iter() {
-                        let cls = itf.lock().unwrap();
-                        match cls.get_interface_method(id.clone()) {
-                            Ok(m) => return Ok(m.clone()),
-                            _ => (),
-                        }
-                    }
-                }
-            },
+            ClassKind::Instance(cls_obj) => cls_obj.itf_methods.get(&id).cloned().ok_or(()),
             _ => unreachable!(),
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::test_class::{define, define_object};
 
-        match self.super_class.as_ref() {
-            Some(super_class) => {
-                return super_class.lock().unwrap().get_interface_method_inner(id);
+    //the class declaring the method id found by f
+    fn declarer<F>(class: &ClassRef, name: &[u8], desc: &[u8], f: F) -> Option<String>
+    where
+        F: FnOnce(&Class, BytesRef) -> Result<MethodIdRef, ()>,
+    {
+        let id = util::new_method_id(name, desc);
+        let mir = f(&class.lock().unwrap(), id).ok()?;
+        let class = mir.method.class.lock().unwrap();
+        Some(String::from_utf8_lossy(class.name.as_slice()).to_string())
+    }
+
+    #[test]
+    fn t_lookup() {
+        define_object();
+        let itf = ACC_PUBLIC | ACC_INTERFACE | ACC_ABSTRACT;
+        let (public, super_) = (ACC_PUBLIC, ACC_PUBLIC | ACC_SUPER);
+        //I { default m(); abstract n(); }
+        //A implements I { n(); static s(); }
+        //B extends A { m(); }
+        let object = Some("java/lang/Object");
+        let methods = [("m", "()V", public), ("n", "()V", public | ACC_ABSTRACT)];
+        define("test/lookup/I", itf, object, &[], &methods);
+        let methods = [("n", "()V", public), ("s", "()V", public | ACC_STATIC)];
+        let a = define(
+            "test/lookup/A",
+            super_,
+            object,
+            &["test/lookup/I"],
+            &methods,
+        );
+        let b = define(
+            "test/lookup/B",
+            super_,
+            Some("test/lookup/A"),
+            &[],
+            &[("m", "()V", public)],
+        );
+
+        let name = |it: &str| Some(it.to_string());
+        let method = |c: &Class, id| c.get_class_method_inner(id, true);
+        let own = |c: &Class, id| c.get_class_method_inner(id, false);
+        let virtual_ = |c: &Class, id| c.get_virtual_method_inner(id);
+        let interface = |c: &Class, id| c.get_interface_method_inner(id);
+
+        assert_eq!(declarer(&b, b"s", b"()V", method), name("test/lookup/A"));
+        assert_eq!(
+            declarer(&b, b"hashCode", b"()I", method),
+            name("java/lang/Object")
+        );
+        assert_eq!(declarer(&b, b"n", b"()V", own), None);
+        assert_eq!(declarer(&b, b"n", b"()V", virtual_), name("test/lookup/A"));
+        assert_eq!(declarer(&b, b"s", b"()V", virtual_), None);
+
+        //the class's own methods first, then the interfaces' defaults
+        assert_eq!(declarer(&a, b"m", b"()V", interface), name("test/lookup/I"));
+        assert_eq!(declarer(&a, b"n", b"()V", interface), name("test/lookup/A"));
+        assert_eq!(declarer(&b, b"m", b"()V", interface), name("test/lookup/B"));
+        assert_eq!(declarer(&b, b"x", b"()V", interface), None);
+
+        //B holds its own methods, A's tables are shared, not copied
+        let tables = |c: &ClassRef| match &c.lock().unwrap().kind {
+            ClassKind::Instance(it) => (it.methods.clone(), it.v_table.clone()),
+            _ => unreachable!(),
+        };
+        let ((a_methods, a_v_table), (b_methods, b_v_table)) = (tables(&a), tables(&b));
+        assert_eq!(b_v_table.own.len(), 1);
+        assert!(Arc::ptr_eq(&b_v_table.parents[0], &a_v_table));
+        assert!(Arc::ptr_eq(&b_methods.parents[0], &a_methods));
+    }
+
+    #[test]
+    fn t_default_override() {
+        use crate::oop::inline_cache::InlineCaches;
+
+        define_object();
+        let itf = ACC_PUBLIC | ACC_INTERFACE | ACC_ABSTRACT;
+        let object = Some("java/lang/Object");
+        //I { default m(); }, A implements I, B extends A { m(); }
+        define("test/cha/I", itf, object, &[], &[("m", "()V", ACC_PUBLIC)]);
+        let a = define("test/cha/A", ACC_SUPER, object, &["test/cha/I"], &[]);
+        let id = util::new_method_id(b"m", b"()V");
+        let mir = a.lock().unwrap().get_interface_method_inner(id).unwrap();
+
+        let caches = Arc::new(InlineCaches::default());
+        assert!(cha::make_final(&caches, 0, &mir));
+        assert_eq!(caches.state(0), Some("final"));
+
+        let methods = [("m", "()V", ACC_PUBLIC)];
+        define("test/cha/B", ACC_SUPER, Some("test/cha/A"), &[], &methods);
+        assert_eq!(caches.state(0), None);
+        assert!(!cha::make_final(&caches, 0, &mir));
+    }
+
+    #[test]
+    fn t_init_order() {
+        //C implements I, J; I extends K, L; J extends K
+        let supers = |it: &&str| -> Vec<&str> {
+            match *it {
+                "I" => vec!["K", "L"],
+                "J" => vec!["K"],
+                _ => vec![],
             }
-            None => return Err(()),
-        }
+        };
+        let order = post_order(&["I", "J"], &supers, &|a, b| a == b);
+        assert_eq!(order, vec!["K", "L", "I", "J"]);
+    }
+
+    #[test]
+    fn t_no_class_def_msg() {
+        assert_eq!(
+            no_class_def_msg(b"a/b/Foo"),
+            "Could not initialize class a.b.Foo"
+        );
+    }
+
+    #[test]
+    fn t_init_step() {
+        let me = std::thread::current().id();
+        let other = std::thread::spawn(|| std::thread::current().id())
+            .join()
+            .unwrap();
+        assert_eq!(init_step(State::Linked, None, me), InitStep::Run);
+        //recursive request, by the initializing thread
+        assert_eq!(init_step(State::BeingIni, Some(me), me), InitStep::Done);
+        assert_eq!(init_step(State::BeingIni, Some(other), me), InitStep::Wait);
+        assert_eq!(init_step(State::FullyIni, None, me), InitStep::Done);
+        assert_eq!(init_step(State::IniErr, None, me), InitStep::Erroneous);
     }
 }
@@ -0,0 +1,128 @@
+/*
+Constant pool cache, one per class, the quickened form of
+getfield/putfield/getstatic/putstatic, invoke*, new, checkcast,
+instanceof, anewarray & ldc
+
+The first execution resolves the cp entry (load the class, search the
+field or method, create the String), the result is kept here by cp
+index. Later executions, of any instruction referring to the same entry,
+take the resolved ClassRef/FieldIdRef/MethodIdRef directly.
+
+A String literal is created once per entry, so each ldc of it pushes
+the same java.lang.String. So are MethodType & MethodHandle constants,
+see runtime::invoke.
+
+Only successful resolutions are cached, a failed one is retried.
+*/
+
+use crate::types::{ClassRef, FieldIdRef, MethodIdRef, OopRef};
+use std::sync::RwLock;
+
+#[derive(Debug, Clone)]
+enum CpEntry {
+    Unresolved,
+    Class(ClassRef),
+    String(OopRef),
+    //MethodType & MethodHandle
+    Constant(OopRef),
+    Field(FieldIdRef),
+    Method(MethodIdRef),
+}
+
+#[derive(Debug)]
+pub struct CpCache {
+    entries: RwLock<Vec<CpEntry>>,
+}
+
+impl CpCache {
+    pub fn new(cp_len: usize) -> Self {
+        Self {
+            entries: RwLock::new(vec![CpEntry::Unresolved; cp_len]),
+        }
+    }
+
+    pub fn get_class(&self, idx: usize) -> Option<ClassRef> {
+        match self.entries.read().unwrap().get(idx) {
+            Some(CpEntry::Class(class)) => Some(class.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_string(&self, idx: usize) -> Option<OopRef> {
+        match self.entries.read().unwrap().get(idx) {
+            Some(CpEntry::String(s)) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_constant(&self, idx: usize) -> Option<OopRef> {
+        match self.entries.read().unwrap().get(idx) {
+            Some(CpEntry::Constant(v)) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_field(&self, idx: usize) -> Option<FieldIdRef> {
+        match self.entries.read().unwrap().get(idx) {
+            Some(CpEntry::Field(fir)) => Some(fir.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_method(&self, idx: usize) -> Option<MethodIdRef> {
+        match self.entries.read().unwrap().get(idx) {
+            Some(CpEntry::Method(mir)) => Some(mir.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn put_class(&self, idx: usize, class: ClassRef) {
+        self.put(idx, CpEntry::Class(class));
+    }
+
+    pub fn put_string(&self, idx: usize, s: OopRef) {
+        self.put(idx, CpEntry::String(s));
+    }
+
+    pub fn put_constant(&self, idx: usize, v: OopRef) {
+        self.put(idx, CpEntry::Constant(v));
+    }
+
+    pub fn put_field(&self, idx: usize, fir: FieldIdRef) {
+        self.put(idx, CpEntry::Field(fir));
+    }
+
+    pub fn put_method(&self, idx: usize, mir: MethodIdRef) {
+        self.put(idx, CpEntry::Method(mir));
+    }
+
+    fn put(&self, idx: usize, entry: CpEntry) {
+        if let Some(it) = self.entries.write().unwrap().get_mut(idx) {
+            *it = entry;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::OopDesc;
+    use std::sync::Arc;
+
+    #[test]
+    fn t_cp_cache() {
+        let cache = CpCache::new(4);
+        assert!(cache.get_string(1).is_none());
+
+        let s = OopDesc::new_int(1);
+        cache.put_string(1, s.clone());
+        assert!(Arc::ptr_eq(&cache.get_string(1).unwrap(), &s));
+
+        //entry kinds don't mix, out of range is ignored
+        assert!(cache.get_class(1).is_none());
+        assert!(cache.get_field(1).is_none());
+        assert!(cache.get_constant(1).is_none());
+        cache.put_string(9, s);
+        assert!(cache.get_string(9).is_none());
+    }
+}
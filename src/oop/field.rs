@@ -18,9 +18,6 @@ pub fn get_field_ref(
     //load Field's Class, then init it
     let class = require_class2(class_index, cp).unwrap();
     let (name, desc) = {
-        let mut class = class.lock().unwrap();
-        class.init_class(thread);
-
         let (name, desc) = constant_pool::get_name_and_type(cp, name_and_type_index as usize);
         let name = name.unwrap();
         let desc = desc.unwrap();
@@ -120,9 +117,11 @@ impl Field {
         let desc = constant_pool::get_utf8(cp, fi.desc_index as usize).unwrap();
         let value_type = desc.first().unwrap().into();
 
-        let id = vec![class_name, name.as_slice(), desc.as_slice()].join(PATH_SEP.as_bytes());
+        let id = util::intern_join(
+            &[class_name, name.as_slice(), desc.as_slice()],
+            PATH_SEP.as_bytes(),
+        );
         //        info!("id = {}", String::from_utf8_lossy(id.as_slice()));
-        let id = new_ref!(id);
         let acc_flags = fi.acc_flags;
 
         let mut attr_constant_value = None;
@@ -0,0 +1,72 @@
+/*
+Inline caches of invokevirtual & invokeinterface, one per call site (bci)
+of a method
+
+  Empty   the site never dispatched
+  Mono    one receiver class seen, its target is called directly, no
+          vtable/itable/name lookup
+  Mega    more receiver classes seen, always resolved
+  Final   the target is effectively final (see cha), called for any
+          receiver class
+
+A site goes Empty -> Mono -> Mega, or Empty -> Final. A Final site goes
+back to Empty when a new class overrides its target.
+*/
+
+use crate::types::{ClassRef, MethodIdRef};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Clone)]
+enum Site {
+    Mono(ClassRef, MethodIdRef),
+    Mega,
+    Final(MethodIdRef),
+}
+
+#[derive(Debug, Default)]
+pub struct InlineCaches {
+    sites: RwLock<HashMap<i32, Site>>,
+}
+
+impl InlineCaches {
+    //the cached target of the site for receiver class cls
+    pub fn get(&self, bci: i32, cls: &ClassRef) -> Option<MethodIdRef> {
+        match self.sites.read().unwrap().get(&bci) {
+            Some(Site::Mono(seen, mir)) if Arc::ptr_eq(seen, cls) => Some(mir.clone()),
+            Some(Site::Final(mir)) => Some(mir.clone()),
+            _ => None,
+        }
+    }
+
+    //record a dispatch of the site, a second receiver class makes it Mega
+    pub fn update(&self, bci: i32, cls: ClassRef, mir: MethodIdRef) {
+        let mut sites = self.sites.write().unwrap();
+        let site = match sites.get(&bci) {
+            None => Site::Mono(cls, mir),
+            Some(Site::Mono(seen, _)) if Arc::ptr_eq(seen, &cls) => return,
+            Some(_) => Site::Mega,
+        };
+        sites.insert(bci, site);
+    }
+
+    //done by cha::make_final, which tracks the dependency
+    pub(crate) fn set_final(&self, bci: i32, mir: MethodIdRef) {
+        self.sites.write().unwrap().insert(bci, Site::Final(mir));
+    }
+
+    //"mono", "mega" or "final", None for an empty site
+    pub fn state(&self, bci: i32) -> Option<&'static str> {
+        match self.sites.read().unwrap().get(&bci) {
+            Some(Site::Mono(..)) => Some("mono"),
+            Some(Site::Mega) => Some("mega"),
+            Some(Site::Final(_)) => Some("final"),
+            None => None,
+        }
+    }
+
+    //the site starts over from Empty
+    pub fn invalidate(&self, bci: i32) {
+        self.sites.write().unwrap().remove(&bci);
+    }
+}
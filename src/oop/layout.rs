@@ -0,0 +1,244 @@
+/*
+Class layout dumps, -XX:+PrintClassLayout or the diag server /layout/<name>
+
+  fields   instance fields by slot in field_values, inherited ones
+           included, then static fields by slot
+  vtable   slot -> selected method
+  itable   interface -> implementation of each interface method, by
+           itable_index
+  code     decoded bytecode of each method, invoke sites show their
+           inline cache state
+
+A method or field is printed as class.name desc, the class is where it
+is declared.
+*/
+
+use crate::oop::class::ClassKind;
+use crate::oop::method::{Instr, Operand, Switch};
+use crate::types::{BytesRef, ClassRef, FieldIdRef, MethodIdRef};
+use std::fmt::Write;
+
+pub fn dump(class: &ClassRef) -> String {
+    let mut out = String::new();
+
+    //copy out, the class is not locked while printing, the methods &
+    //fields refer back to it
+    let (name, super_class, cls_obj) = {
+        let cls = class.lock().unwrap();
+        let cls_obj = match &cls.kind {
+            ClassKind::Instance(cls_obj) => {
+                let mut static_fields: Vec<FieldIdRef> =
+                    cls_obj.static_fields.values().cloned().collect();
+                static_fields.sort_by_key(|it| it.offset);
+                let mut itable: Vec<(BytesRef, Vec<Option<MethodIdRef>>)> = cls_obj
+                    .itable()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                itable.sort_by(|a, b| a.0.cmp(&b.0));
+                let mut methods: Vec<MethodIdRef> = cls_obj.all_methods.values().cloned().collect();
+                methods.sort_by_key(|it| it.offset);
+                Some((
+                    cls_obj.n_inst_fields,
+                    static_fields,
+                    cls_obj.vtable().to_vec(),
+                    itable,
+                    methods,
+                ))
+            }
+            ClassKind::ObjectArray(ary) | ClassKind::TypeArray(ary) => {
+                let _ = writeln!(
+                    out,
+                    "class {} (array, dimension {})",
+                    String::from_utf8_lossy(cls.name.as_slice()),
+                    ary.get_dimension()
+                );
+                None
+            }
+        };
+        (cls.name.clone(), cls.super_class.clone(), cls_obj)
+    };
+
+    let (n_inst_fields, static_fields, vtable, itable, methods) = match cls_obj {
+        Some(v) => v,
+        None => return out,
+    };
+
+    let _ = writeln!(
+        out,
+        "class {}, super {}",
+        String::from_utf8_lossy(name.as_slice()),
+        super_class
+            .as_ref()
+            .map(class_name)
+            .unwrap_or_else(|| "-".to_string())
+    );
+
+    let mut inst_fields = Vec::new();
+    collect_inst_fields(class, &mut inst_fields);
+    inst_fields.sort_by_key(|it| it.offset);
+    let _ = writeln!(out, "  instance fields ({}):", n_inst_fields);
+    for it in inst_fields.iter() {
+        let _ = writeln!(out, "    @{} {}", it.offset, fmt_field(it));
+    }
+    let _ = writeln!(out, "  static fields ({}):", static_fields.len());
+    for it in static_fields.iter() {
+        let _ = writeln!(out, "    @{} {}", it.offset, fmt_field(it));
+    }
+
+    let _ = writeln!(out, "  vtable ({}):", vtable.len());
+    for (i, it) in vtable.iter().enumerate() {
+        let _ = writeln!(out, "    [{}] {}", i, fmt_method(it));
+    }
+
+    let _ = writeln!(out, "  itable ({}):", itable.len());
+    for (itf, impls) in itable.iter() {
+        let _ = writeln!(out, "    {}", String::from_utf8_lossy(itf.as_slice()));
+        for (i, it) in impls.iter().enumerate() {
+            match it {
+                Some(it) => {
+                    let _ = writeln!(out, "      [{}] {}", i, fmt_method(it));
+                }
+                None => {
+                    let _ = writeln!(out, "      [{}] abstract", i);
+                }
+            }
+        }
+    }
+
+    let _ = writeln!(out, "  code:");
+    for mir in methods.iter() {
+        let method = &mir.method;
+        let _ = writeln!(
+            out,
+            "    {}{}",
+            String::from_utf8_lossy(method.name.as_slice()),
+            String::from_utf8_lossy(method.desc.as_slice())
+        );
+        for instr in method.bytecode.instrs.iter() {
+            let _ = write!(out, "      {}", fmt_instr(instr));
+            match method.inline_caches.state(instr.bci) {
+                Some(state) => {
+                    let _ = writeln!(out, "  //ic {}", state);
+                }
+                None => out.push('\n'),
+            }
+        }
+        if let Some(err) = &method.bytecode.err {
+            let _ = writeln!(out, "      error: {}", err);
+        }
+    }
+
+    out
+}
+
+//own fields & those of the super classes
+fn collect_inst_fields(class: &ClassRef, out: &mut Vec<FieldIdRef>) {
+    let super_class = {
+        let cls = class.lock().unwrap();
+        if let ClassKind::Instance(cls_obj) = &cls.kind {
+            out.extend(cls_obj.inst_fields.values().cloned());
+        }
+        cls.super_class.clone()
+    };
+
+    if let Some(super_class) = super_class {
+        collect_inst_fields(&super_class, out);
+    }
+}
+
+fn class_name(class: &ClassRef) -> String {
+    let cls = class.lock().unwrap();
+    String::from_utf8_lossy(cls.name.as_slice()).to_string()
+}
+
+fn fmt_field(fid: &FieldIdRef) -> String {
+    let field = &fid.field;
+    format!(
+        "{}.{} {}",
+        class_name(&field.class),
+        String::from_utf8_lossy(field.name.as_slice()),
+        String::from_utf8_lossy(field.desc.as_slice())
+    )
+}
+
+fn fmt_method(mir: &MethodIdRef) -> String {
+    let method = &mir.method;
+    format!(
+        "{}.{}{}",
+        class_name(&method.class),
+        String::from_utf8_lossy(method.name.as_slice()),
+        String::from_utf8_lossy(method.desc.as_slice())
+    )
+}
+
+//"12: if_icmpge -> 30"
+fn fmt_instr(instr: &Instr) -> String {
+    let operand = match &instr.operand {
+        Operand::None => String::new(),
+        Operand::Int(v) => format!(" {}", v),
+        Operand::Branch(v) => format!(" -> {}", v),
+        Operand::Iinc(idx, v) => format!(" {}, {}", idx, v),
+        Operand::MultiANewArray(idx, dimension) => format!(" {}, {}", idx, dimension),
+        Operand::Switch(switch) => match switch.as_ref() {
+            Switch::Table {
+                low,
+                targets,
+                default,
+            } => format!(
+                " {}..{} -> {:?}, default -> {}",
+                low,
+                *low as i64 + targets.len() as i64 - 1,
+                targets,
+                default
+            ),
+            Switch::Lookup { pairs, default } => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(k, target)| format!("{} -> {}", k, target))
+                    .collect();
+                format!(" {{{}}}, default -> {}", pairs.join(", "), default)
+            }
+        },
+    };
+
+    format!("{}: {:?}{}", instr.bci, instr.op_code, operand)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::method::Bytecode;
+
+    #[test]
+    fn t_fmt_instr() {
+        //iload_1; iinc 1, -1; bipush 7; goto 0; lookupswitch {3 -> 21}
+        let code = [
+            0x1b, 0x84, 0x01, 0xff, 0x10, 0x07, 0xa7, 0xff, 0xfa, 0xab, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x0c, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0c,
+        ];
+        let bytecode = Bytecode::decode(&code);
+        assert!(bytecode.err.is_none());
+        let instrs: Vec<String> = bytecode.instrs.iter().map(fmt_instr).collect();
+        assert_eq!(
+            instrs,
+            vec![
+                "0: iload_1",
+                "1: iinc 1, -1",
+                "4: bipush 7",
+                "6: goto -> 0",
+                "9: lookupswitch {3 -> 21}, default -> 21",
+            ]
+        );
+    }
+
+    #[test]
+    fn t_dump_array() {
+        use crate::oop::{Class, ValueType};
+        use crate::runtime::ClassLoader;
+
+        let cls = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::INT);
+        let cls = new_sync_ref!(cls);
+        assert_eq!(dump(&cls), "class [I (array, dimension 1)\n");
+    }
+}
@@ -1,16 +1,18 @@
-use crate::classfile::attr_info::AnnotationEntry;
+use crate::classfile::attr_info::{AnnotationEntry, MethodParameter};
+use crate::classfile::opcode::OpCode;
 use crate::classfile::{
-    access_flags::*, attr_info::Code, attr_info::LineNumber, constant_pool, consts, AttrType,
-    FieldInfo, MethodInfo,
+    access_flags::*, attr_info::Code, attr_info::CodeException, attr_info::LineNumber,
+    constant_pool, consts, AttrType, FieldInfo, MethodInfo,
 };
+use crate::oop::inline_cache::InlineCaches;
 use crate::oop::{self, ClassRef, ValueType};
+use crate::runtime::intrinsics::Intrinsic;
 use crate::runtime::{self, require_class2, JavaThread};
 use crate::types::*;
 use crate::util;
 use crate::util::PATH_SEP;
-use std::collections::HashMap;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 
 pub fn get_method_ref(
     thread: &mut JavaThread,
@@ -22,11 +24,6 @@ pub fn get_method_ref(
     //load Method's Class, then init it
     let class = require_class2(class_index, cp).unwrap();
 
-    {
-        let mut class = class.lock().unwrap();
-        class.init_class(thread);
-    }
-
     let (name, typ) = {
         let (name, typ) = constant_pool::get_name_and_type(cp, name_and_type_index as usize);
         let name = name.unwrap();
@@ -41,9 +38,9 @@ pub fn get_method_ref(
 
     trace!(
         "get_method_ref cls={}, name={}, typ={}",
-        unsafe { std::str::from_utf8_unchecked(class.name.as_slice()) },
-        unsafe { std::str::from_utf8_unchecked(name.as_slice()) },
-        unsafe { std::str::from_utf8_unchecked(typ.as_slice()) },
+        util::mutf8::display(class.name.as_slice()),
+        util::mutf8::display(name.as_slice()),
+        util::mutf8::display(typ.as_slice()),
     );
 
     let id = util::new_method_id(name.as_slice(), typ.as_slice());
@@ -61,6 +58,12 @@ pub fn get_method_ref(
 #[derive(Debug, Clone)]
 pub struct MethodId {
     pub offset: usize,
+    //slot in the vtable of the class & its subclasses, None for
+    //static, private, <init>, and interface methods
+    pub vtable_index: Option<usize>,
+    //slot in the itable of the interface, None for static, private &
+    //methods of classes
+    pub itable_index: Option<usize>,
     pub method: Method,
 }
 
@@ -73,9 +76,18 @@ pub struct Method {
     pub acc_flags: U2,
 
     pub code: Option<Code>,
+    pub bytecode: Arc<Bytecode>,
+    pub inline_caches: Arc<InlineCaches>,
+    //found at the first call, see runtime::intrinsics
+    pub intrinsic: Arc<OnceLock<Option<Intrinsic>>>,
+    pub ex_handlers: Arc<Vec<ExHandler>>,
+    //a bad exception table, (error class, message), see check_ex_table
+    pub ex_table_err: Option<(&'static [u8], &'static str)>,
     //fixme: more readable name
     pub line_num_table: Vec<LineNumber>,
     pub src_file: Option<BytesRef>,
+    //the MethodParameters attribute, compiled with -parameters
+    pub params: Option<Vec<Parameter>>,
 
     vis_annos: Vec<AnnotationEntry>,
     vis_param_annos: Vec<AnnotationEntry>,
@@ -91,13 +103,28 @@ impl Method {
     ) -> Self {
         let name = constant_pool::get_utf8(cp, mi.name_index as usize).unwrap();
         let desc = constant_pool::get_utf8(cp, mi.desc_index as usize).unwrap();
-        let id = vec![name.as_slice(), desc.as_slice()].join(PATH_SEP.as_bytes());
-        let id = new_ref!(id);
+        let id = util::intern_join(&[name.as_slice(), desc.as_slice()], PATH_SEP.as_bytes());
         //        info!("id = {}", String::from_utf8_lossy(id.as_slice()));
         let acc_flags = mi.acc_flags;
         let code = mi.get_code();
+        let bytecode = match &code {
+            Some(code) => Bytecode::decode(code.code.as_slice()),
+            None => Bytecode::default(),
+        };
+        let bytecode = Arc::new(bytecode);
+        let ex_handlers = match &code {
+            Some(code) => ExHandler::build(cp, code.exceptions.as_slice()),
+            None => vec![],
+        };
+        let ex_table_err = code
+            .as_ref()
+            .and_then(|code| check_ex_table(cp, code, &bytecode).err());
+        let ex_handlers = Arc::new(ex_handlers);
         let line_num_table = mi.get_line_number_table();
         let src_file = mi.get_src_file(cp);
+        let params = mi
+            .get_method_parameters()
+            .map(|params| resolve_params(cp, params));
 
         Self {
             class,
@@ -106,8 +133,14 @@ impl Method {
             id,
             acc_flags,
             code,
+            bytecode,
+            inline_caches: Arc::new(InlineCaches::default()),
+            intrinsic: Arc::new(OnceLock::new()),
+            ex_handlers,
+            ex_table_err,
             line_num_table,
             src_file,
+            params,
             vis_annos,
             vis_param_annos,
         }
@@ -117,38 +150,12 @@ impl Method {
         self.id.clone()
     }
 
-    pub fn find_exception_handler(&self, cp: &ConstantPool, pc: U2, ex: ClassRef) -> Option<U2> {
-        match &self.code {
-            Some(code) => {
-                for e in code.exceptions.iter() {
-                    if e.contains(pc) {
-                        if e.is_finally() {
-                            return Some(e.handler_pc);
-                        }
-
-                        if let Some(class) = runtime::require_class2(e.catch_type, cp) {
-                            if runtime::cmp::instance_of(ex.clone(), class) {
-                                return Some(e.handler_pc);
-                            }
-                        }
-                    }
-                }
-            }
-
-            _ => (),
-        }
-
-        None
+    pub fn find_exception_handler(&self, pc: U2, ex: ClassRef) -> Option<U2> {
+        find_handler(self.ex_handlers.as_slice(), pc, ex)
     }
 
     pub fn get_line_num(&self, pc: U2) -> Option<U2> {
-        let mut number = None;
-        for it in self.line_num_table.iter().rev() {
-            if it.start_pc >= pc {
-                number = Some(it.number);
-            }
-        }
-        number
+        line_of(self.line_num_table.as_slice(), pc)
     }
 
     pub fn check_annotation(&self, name: &[u8]) -> bool {
@@ -187,6 +194,10 @@ impl Method {
         (self.acc_flags & ACC_STATIC) == ACC_STATIC
     }
 
+    pub fn is_varargs(&self) -> bool {
+        (self.acc_flags & ACC_VARARGS) == ACC_VARARGS
+    }
+
     pub fn is_synchronized(&self) -> bool {
         (self.acc_flags & ACC_SYNCHRONIZED) == ACC_SYNCHRONIZED
     }
@@ -199,7 +210,704 @@ impl Method {
         (self.acc_flags & ACC_ABSTRACT) == ACC_ABSTRACT
     }
 
+    //the code is a lone return, as Object.finalize
+    pub fn is_empty(&self) -> bool {
+        self.code
+            .as_ref()
+            .is_some_and(|it| it.code.as_slice() == [OpCode::return_void as u8])
+    }
+
     pub fn is_interface(&self) -> bool {
         (self.acc_flags & ACC_INTERFACE) == ACC_INTERFACE
     }
 }
+
+/*
+Exception table, built once when the method is linked
+
+Ranges & catch class names are read from the cp here. The catch class is
+resolved by the first exception reaching the handler, then kept. Not at
+link time: the declaring class is locked while linking, loading the
+catch classes there may deadlock, and loads classes never thrown.
+*/
+#[derive(Debug)]
+pub struct ExHandler {
+    pub start: U2,
+    //exclusive
+    pub end: U2,
+    pub handler: U2,
+    //None catches all, finally
+    catch: Option<BytesRef>,
+    class: Mutex<Option<ClassRef>>,
+}
+
+impl ExHandler {
+    fn build(cp: &ConstantPool, exceptions: &[CodeException]) -> Vec<Self> {
+        exceptions
+            .iter()
+            .map(|e| Self {
+                start: e.start_pc,
+                end: e.end_pc,
+                handler: e.handler_pc,
+                catch: if e.is_finally() {
+                    None
+                } else {
+                    constant_pool::get_class_name(cp, e.catch_type as usize)
+                },
+                class: Mutex::new(None),
+            })
+            .collect()
+    }
+
+    fn catches(&self, ex: &ClassRef) -> bool {
+        let name = match &self.catch {
+            Some(name) => name,
+            None => return true,
+        };
+
+        let class = { self.class.lock().unwrap().clone() };
+        let class = match class {
+            Some(class) => class,
+            None => match runtime::require_class3(None, name.as_slice()) {
+                Some(class) => {
+                    *self.class.lock().unwrap() = Some(class.clone());
+                    class
+                }
+                None => return false,
+            },
+        };
+
+        runtime::cmp::instance_of(ex.clone(), class)
+    }
+}
+
+/*
+The exception table, checked when the method is linked, as HotSpot's
+parser & verifier: a range start < end <= code length, the handler in
+the code, the catch type 0 or a Class constant, else ClassFormatError.
+The range & the handler on instruction boundaries, else VerifyError,
+unless decoding stopped short. The error is thrown invoking the method,
+see JavaCall, find_handler never walks a bad table.
+*/
+fn check_ex_table(
+    cp: &ConstantPool,
+    code: &Code,
+    bytecode: &Bytecode,
+) -> Result<(), (&'static [u8], &'static str)> {
+    let len = code.code.len();
+    let is_instr = |pc: U2| bytecode.err.is_some() || bytecode.get(pc as i32).is_some();
+    for e in code.exceptions.iter() {
+        let (start, end, handler) = (e.start_pc, e.end_pc, e.handler_pc);
+        if start >= end || end as usize > len {
+            return Err((
+                consts::J_CLASS_FORMAT_ERROR,
+                "Illegal exception table range",
+            ));
+        }
+        if handler as usize >= len {
+            return Err((
+                consts::J_CLASS_FORMAT_ERROR,
+                "Illegal exception table handler",
+            ));
+        }
+        if !e.is_finally() && constant_pool::get_class_name(cp, e.catch_type as usize).is_none() {
+            return Err((
+                consts::J_CLASS_FORMAT_ERROR,
+                "Catch type in exception table has bad constant type",
+            ));
+        }
+        if !is_instr(start) || (end as usize != len && !is_instr(end)) {
+            return Err((consts::J_VERIFY_ERROR, "Illegal exception table range"));
+        }
+        if !is_instr(handler) {
+            return Err((consts::J_VERIFY_ERROR, "Illegal exception table handler"));
+        }
+    }
+    Ok(())
+}
+
+//the first handler covering pc & catching ex, in table order
+pub fn find_handler(handlers: &[ExHandler], pc: U2, ex: ClassRef) -> Option<U2> {
+    handlers
+        .iter()
+        .filter(|it| (it.start..it.end).contains(&pc))
+        .find(|it| it.catches(&ex))
+        .map(|it| it.handler)
+}
+
+/*
+Decoded bytecode, built once when the method is linked
+
+Operands are read & resolved here: wide is folded into the instruction it
+widens, branch offsets become absolute bci, switch tables are parsed.
+Frame::interp walks instrs instead of re-reading the code bytes.
+
+Decoding stops at an unknown opcode or truncated code, err says why,
+executing up to there still works.
+*/
+#[derive(Debug, Default)]
+pub struct Bytecode {
+    pub instrs: Vec<Instr>,
+    //bci -> index in instrs + 1, 0 if no instruction starts at bci
+    index: Vec<u32>,
+    pub err: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Instr {
+    pub bci: i32,
+    //bci of the next instruction
+    pub next: i32,
+    pub op_code: OpCode,
+    pub operand: Operand,
+}
+
+impl Instr {
+    pub fn int(&self) -> i32 {
+        match self.operand {
+            Operand::Int(v) => v,
+            _ => unreachable!("{:?} has no int operand", self.op_code),
+        }
+    }
+
+    pub fn branch(&self) -> i32 {
+        match self.operand {
+            Operand::Branch(v) => v,
+            _ => unreachable!("{:?} is not a branch", self.op_code),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Operand {
+    None,
+    //bipush & sipush value, local var index, cp index, newarray type
+    Int(i32),
+    //absolute bci
+    Branch(i32),
+    //local var index, increment
+    Iinc(usize, i32),
+    //cp index, dimensions
+    MultiANewArray(usize, usize),
+    Switch(Box<Switch>),
+}
+
+#[derive(Debug)]
+pub enum Switch {
+    Table {
+        low: i32,
+        targets: Vec<i32>,
+        default: i32,
+    },
+    //(key, target) sorted by key, binary searched
+    Lookup {
+        pairs: Vec<(i32, i32)>,
+        default: i32,
+    },
+}
+
+impl Switch {
+    //bci to jump to for key
+    pub fn target(&self, key: i32) -> i32 {
+        match self {
+            Switch::Table {
+                low,
+                targets,
+                default,
+            } => {
+                let i = key as i64 - *low as i64;
+                if i >= 0 && i < targets.len() as i64 {
+                    targets[i as usize]
+                } else {
+                    *default
+                }
+            }
+            Switch::Lookup { pairs, default } => {
+                match pairs.binary_search_by_key(&key, |&(k, _)| k) {
+                    Ok(i) => pairs[i].1,
+                    Err(_) => *default,
+                }
+            }
+        }
+    }
+}
+
+impl Bytecode {
+    pub fn decode(code: &[U1]) -> Self {
+        let mut instrs = Vec::new();
+        let mut index = vec![0; code.len()];
+        let mut reader = CodeReader { code, pos: 0 };
+
+        while reader.pos < code.len() {
+            let bci = reader.pos;
+            match decode_instr(&mut reader) {
+                Ok((op_code, operand)) => {
+                    instrs.push(Instr {
+                        bci: bci as i32,
+                        next: reader.pos as i32,
+                        op_code,
+                        operand,
+                    });
+                    index[bci] = instrs.len() as u32;
+                }
+                Err(e) => {
+                    return Self {
+                        instrs,
+                        index,
+                        err: Some(format!("{} at bci={}", e, bci)),
+                    };
+                }
+            }
+        }
+
+        Self {
+            instrs,
+            index,
+            err: None,
+        }
+    }
+
+    //the instruction starting at bci
+    pub fn get(&self, bci: i32) -> Option<&Instr> {
+        match self.index.get(bci as usize) {
+            Some(&i) if bci >= 0 && i != 0 => self.instrs.get(i as usize - 1),
+            _ => None,
+        }
+    }
+}
+
+struct CodeReader<'a> {
+    code: &'a [U1],
+    pos: usize,
+}
+
+impl<'a> CodeReader<'a> {
+    fn u1(&mut self) -> Result<U1, String> {
+        match self.code.get(self.pos) {
+            Some(&v) => {
+                self.pos += 1;
+                Ok(v)
+            }
+            None => Err("truncated code".to_string()),
+        }
+    }
+
+    fn u2(&mut self) -> Result<U2, String> {
+        Ok((self.u1()? as U2) << 8 | self.u1()? as U2)
+    }
+
+    fn i4(&mut self) -> Result<i32, String> {
+        Ok((self.u2()? as i32) << 16 | self.u2()? as i32)
+    }
+
+    fn align4(&mut self) {
+        self.pos = (self.pos + 3) & !3;
+    }
+
+    //a switch table of n entries, size bytes each, must fit in the code
+    //left, checked before anything is allocated for it
+    fn table(&self, n: i64, size: i64) -> Result<usize, String> {
+        let left = self.code.len().saturating_sub(self.pos) as i64;
+        match n {
+            n if n < 0 || n * size > left => Err("switch table past the code".to_string()),
+            n => Ok(n as usize),
+        }
+    }
+}
+
+fn decode_instr(r: &mut CodeReader) -> Result<(OpCode, Operand), String> {
+    let bci = r.pos as i32;
+    let code = r.u1()?;
+    let op_code = OpCode::decode(code).ok_or(format!("unknown opcode 0x{:x}", code))?;
+
+    let operand = match op_code {
+        OpCode::bipush => Operand::Int(r.u1()? as i8 as i32),
+        OpCode::sipush => Operand::Int(r.u2()? as i16 as i32),
+        OpCode::ldc | OpCode::newarray => Operand::Int(r.u1()? as i32),
+        OpCode::iload
+        | OpCode::lload
+        | OpCode::fload
+        | OpCode::dload
+        | OpCode::aload
+        | OpCode::istore
+        | OpCode::lstore
+        | OpCode::fstore
+        | OpCode::dstore
+        | OpCode::astore
+        | OpCode::ret => Operand::Int(r.u1()? as i32),
+        OpCode::iinc => Operand::Iinc(r.u1()? as usize, r.u1()? as i8 as i32),
+        OpCode::ldc_w
+        | OpCode::ldc2_w
+        | OpCode::getstatic
+        | OpCode::putstatic
+        | OpCode::getfield
+        | OpCode::putfield
+        | OpCode::invokevirtual
+        | OpCode::invokespecial
+        | OpCode::invokestatic
+        | OpCode::new
+        | OpCode::anewarray
+        | OpCode::checkcast
+        | OpCode::instanceof => Operand::Int(r.u2()? as i32),
+        /*
+        count & 0. count, the arg slots + 1, is historical, the descriptor
+        gives the args. Old compilers got it wrong, so it is not checked
+        against the descriptor, as hotspot does, only 0 is rejected.
+        */
+        OpCode::invokeinterface => {
+            let cp_idx = r.u2()?;
+            let (count, zero) = (r.u1()?, r.u1()?);
+            if count == 0 {
+                return Err("invokeinterface count is 0".to_string());
+            }
+            if zero != 0 {
+                return Err("invokeinterface fourth operand byte is not 0".to_string());
+            }
+            Operand::Int(cp_idx as i32)
+        }
+        //0 & 0
+        OpCode::invokedynamic => {
+            let cp_idx = r.u2()?;
+            if r.u2()? != 0 {
+                return Err("invokedynamic operand bytes 3 & 4 are not 0".to_string());
+            }
+            Operand::Int(cp_idx as i32)
+        }
+        OpCode::multianewarray => Operand::MultiANewArray(r.u2()? as usize, r.u1()? as usize),
+        OpCode::ifeq
+        | OpCode::ifne
+        | OpCode::iflt
+        | OpCode::ifge
+        | OpCode::ifgt
+        | OpCode::ifle
+        | OpCode::if_icmpeq
+        | OpCode::if_icmpne
+        | OpCode::if_icmplt
+        | OpCode::if_icmpge
+        | OpCode::if_icmpgt
+        | OpCode::if_icmple
+        | OpCode::if_acmpeq
+        | OpCode::if_acmpne
+        | OpCode::goto
+        | OpCode::jsr
+        | OpCode::ifnull
+        | OpCode::ifnonnull => Operand::Branch(bci + r.u2()? as i16 as i32),
+        OpCode::goto_w | OpCode::jsr_w => Operand::Branch(bci + r.i4()?),
+        OpCode::tableswitch => {
+            r.align4();
+            let default = bci + r.i4()?;
+            let low = r.i4()?;
+            let high = r.i4()?;
+            if high < low {
+                return Err("bad tableswitch".to_string());
+            }
+            let n = r.table(high as i64 - low as i64 + 1, 4)?;
+            let mut targets = Vec::with_capacity(n);
+            for _ in low..=high {
+                targets.push(bci + r.i4()?);
+            }
+            Operand::Switch(Box::new(Switch::Table {
+                low,
+                targets,
+                default,
+            }))
+        }
+        OpCode::lookupswitch => {
+            r.align4();
+            let default = bci + r.i4()?;
+            let count = r.i4()?;
+            let mut pairs = Vec::with_capacity(r.table(count as i64, 8)?);
+            for _ in 0..count {
+                let key = r.i4()?;
+                pairs.push((key, bci + r.i4()?));
+            }
+            //javac emits them sorted, as the spec requires, not trusted
+            pairs.sort_unstable_by_key(|&(k, _)| k);
+            Operand::Switch(Box::new(Switch::Lookup { pairs, default }))
+        }
+        OpCode::wide => {
+            let code = r.u1()?;
+            let op_code = OpCode::decode(code).ok_or(format!("unknown opcode 0x{:x}", code))?;
+            let operand = match op_code {
+                OpCode::iinc => Operand::Iinc(r.u2()? as usize, r.u2()? as i16 as i32),
+                OpCode::iload
+                | OpCode::lload
+                | OpCode::fload
+                | OpCode::dload
+                | OpCode::aload
+                | OpCode::istore
+                | OpCode::lstore
+                | OpCode::fstore
+                | OpCode::dstore
+                | OpCode::astore
+                | OpCode::ret => Operand::Int(r.u2()? as i32),
+                _ => return Err(format!("bad wide {:?}", op_code)),
+            };
+            return Ok((op_code, operand));
+        }
+        _ => Operand::None,
+    };
+
+    Ok((op_code, operand))
+}
+
+//the line of the entry starting last at or before pc, javac emits the
+//table out of order for loops & finally blocks
+fn line_of(table: &[LineNumber], pc: U2) -> Option<U2> {
+    table
+        .iter()
+        .filter(|it| it.start_pc <= pc)
+        .max_by_key(|it| it.start_pc)
+        .map(|it| it.number)
+}
+
+//a MethodParameters entry, name None for a nameless parameter (name_index
+//0) or a name_index which is not a Utf8
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name_index: U2,
+    pub name: Option<BytesRef>,
+    pub acc_flags: U2,
+}
+
+impl Parameter {
+    pub fn is_valid(&self) -> bool {
+        self.name_index == 0 || self.name.is_some()
+    }
+}
+
+fn resolve_params(cp: &ConstantPool, params: &[MethodParameter]) -> Vec<Parameter> {
+    params
+        .iter()
+        .map(|it| Parameter {
+            name_index: it.name_index,
+            name: match it.name_index {
+                0 => None,
+                idx => constant_pool::get_utf8(cp, idx as usize),
+            },
+            acc_flags: it.acc_flags,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::constant_pool::ConstantType;
+    use crate::runtime::host_class::{push_class, push_utf8};
+
+    #[test]
+    fn t_find_handler() {
+        let finally = |start, end, handler| ExHandler {
+            start,
+            end,
+            handler,
+            catch: None,
+            class: Mutex::new(None),
+        };
+        let handlers = vec![finally(0, 4, 20), finally(2, 10, 30)];
+        let cls = oop::Class::new_prime_ary(runtime::ClassLoader::Bootstrap, ValueType::INT);
+        let cls = new_sync_ref!(cls);
+
+        assert_eq!(find_handler(&handlers, 3, cls.clone()), Some(20));
+        //end is exclusive
+        assert_eq!(find_handler(&handlers, 4, cls.clone()), Some(30));
+        assert_eq!(find_handler(&handlers, 10, cls), None);
+    }
+
+    #[test]
+    fn t_check_ex_table() {
+        //1: Utf8 "E", 2: Class E
+        let mut cp = vec![ConstantType::Nop];
+        push_class(&mut cp, "E");
+        let cp: ConstantPool = new_ref!(cp);
+        //0: iconst_0, 1: bipush 1, 3: pop, 4: return
+        let bytes = vec![0x03, 0x10, 0x01, 0x57, 0xb1];
+        let bytecode = Bytecode::decode(bytes.as_slice());
+        let check = |start_pc, end_pc, handler_pc, catch_type| {
+            let code = Code {
+                max_stack: 1,
+                max_locals: 0,
+                code: Arc::new(bytes.clone()),
+                exceptions: vec![CodeException {
+                    start_pc,
+                    end_pc,
+                    handler_pc,
+                    catch_type,
+                }],
+                attrs: vec![],
+            };
+            check_ex_table(&cp, &code, &bytecode).err().map(|it| it.0)
+        };
+
+        assert_eq!(check(0, 5, 4, 0), None);
+        assert_eq!(check(1, 3, 3, 2), None);
+        assert_eq!(check(3, 3, 4, 0), Some(consts::J_CLASS_FORMAT_ERROR));
+        assert_eq!(check(0, 6, 4, 0), Some(consts::J_CLASS_FORMAT_ERROR));
+        assert_eq!(check(0, 3, 5, 0), Some(consts::J_CLASS_FORMAT_ERROR));
+        //a Utf8, no Class
+        assert_eq!(check(0, 3, 4, 1), Some(consts::J_CLASS_FORMAT_ERROR));
+        assert_eq!(check(0, 2, 4, 0), Some(consts::J_VERIFY_ERROR));
+        assert_eq!(check(0, 3, 2, 0), Some(consts::J_VERIFY_ERROR));
+    }
+
+    #[test]
+    fn t_line_of() {
+        let line = |start_pc, number| LineNumber { start_pc, number };
+        let table = vec![line(0, 10), line(12, 14), line(5, 11)];
+        assert_eq!(line_of(&table, 0), Some(10));
+        assert_eq!(line_of(&table, 4), Some(10));
+        assert_eq!(line_of(&table, 5), Some(11));
+        assert_eq!(line_of(&table, 30), Some(14));
+        assert_eq!(line_of(&table[1..], 3), None);
+    }
+
+    #[test]
+    fn t_resolve_params() {
+        let mut cp = vec![ConstantType::Nop];
+        push_utf8(&mut cp, "name");
+        cp.push(ConstantType::Nop);
+        let cp: ConstantPool = new_ref!(cp);
+        let param = |name_index, acc_flags| MethodParameter {
+            name_index,
+            acc_flags,
+        };
+        let params = resolve_params(&cp, &[param(1, 0), param(0, ACC_FINAL), param(2, 0)]);
+
+        assert_eq!(params[0].name.as_ref().unwrap().as_slice(), b"name");
+        assert!(params[0].is_valid());
+        assert!(params[1].name.is_none() && params[1].is_valid());
+        assert_eq!(params[1].acc_flags, ACC_FINAL);
+        assert!(!params[2].is_valid());
+    }
+
+    #[test]
+    fn t_decode() {
+        let code = vec![
+            0x10, 0xfe, //0: bipush -2
+            0xc4, 0x84, 0x00, 0x05, 0xff, 0xff, //2: wide iinc 5, -1
+            0xaa, 0x00, 0x00, 0x00, //8: tableswitch, 3 bytes padding
+            0x00, 0x00, 0x00, 0x1b, //default +27
+            0x00, 0x00, 0x00, 0x01, //low 1
+            0x00, 0x00, 0x00, 0x02, //high 2
+            0x00, 0x00, 0x00, 0x18, //1 => +24
+            0x00, 0x00, 0x00, 0x1b, //2 => +27
+            0xa7, 0xff, 0xe0, //32: goto -32
+            0xb1, //35: return
+        ];
+        let bc = Bytecode::decode(code.as_slice());
+        assert!(bc.err.is_none());
+        assert_eq!(bc.instrs.len(), 5);
+
+        let instr = bc.get(2).unwrap();
+        assert_eq!(instr.op_code, OpCode::iinc);
+        assert_eq!(instr.next, 8);
+        match instr.operand {
+            Operand::Iinc(5, -1) => (),
+            _ => panic!("{:?}", instr.operand),
+        }
+
+        match &bc.get(8).unwrap().operand {
+            Operand::Switch(sw) => {
+                assert_eq!(sw.target(1), 32);
+                assert_eq!(sw.target(2), 35);
+                assert_eq!(sw.target(3), 35);
+            }
+            t => panic!("{:?}", t),
+        }
+
+        match bc.get(32).unwrap().operand {
+            Operand::Branch(v) => assert_eq!(v, 0),
+            _ => panic!(),
+        }
+        assert!(bc.get(1).is_none());
+
+        let bc = Bytecode::decode(&[0x10, 0x01, 0xcb]);
+        assert_eq!(bc.instrs.len(), 1);
+        assert!(bc.err.is_some());
+    }
+
+    #[test]
+    fn t_decode_lookup_switch() {
+        let code = vec![
+            0xab, 0x00, 0x00, 0x00, //0: lookupswitch, 3 bytes padding
+            0x00, 0x00, 0x00, 0x24, //default +36
+            0x00, 0x00, 0x00, 0x03, //npairs 3, unsorted
+            0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x24, //10 => +36
+            0xff, 0xff, 0xff, 0xfb, 0x00, 0x00, 0x00, 0x25, //-5 => +37
+            0x00, 0x00, 0x03, 0xe8, 0x00, 0x00, 0x00, 0x26, //1000 => +38
+            0xb1, 0xb1, 0xb1, //36: return
+        ];
+        let bc = Bytecode::decode(code.as_slice());
+        assert!(bc.err.is_none());
+
+        match &bc.get(0).unwrap().operand {
+            Operand::Switch(sw) => {
+                assert_eq!(sw.target(10), 36);
+                assert_eq!(sw.target(-5), 37);
+                assert_eq!(sw.target(1000), 38);
+                assert_eq!(sw.target(0), 36);
+                assert_eq!(sw.target(i32::MIN), 36);
+            }
+            t => panic!("{:?}", t),
+        }
+    }
+
+    #[test]
+    fn t_decode_switch_range() {
+        //tableswitch 0..0x7fffffff, a table of 8G in a few bytes of code
+        let code = vec![
+            0xaa, 0x00, 0x00, 0x00, //0: tableswitch
+            0x00, 0x00, 0x00, 0x10, //default
+            0x00, 0x00, 0x00, 0x00, //low 0
+            0x7f, 0xff, 0xff, 0xff, //high
+            0x00, 0x00, 0x00, 0x10,
+        ];
+        let bc = Bytecode::decode(code.as_slice());
+        assert_eq!(
+            bc.err.as_deref(),
+            Some("switch table past the code at bci=0")
+        );
+
+        //lookupswitch, npairs over the code & negative
+        for npairs in [0x10000000i32, -1] {
+            let mut code = vec![0xab, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10];
+            code.extend_from_slice(&npairs.to_be_bytes());
+            let bc = Bytecode::decode(code.as_slice());
+            assert!(bc.err.is_some());
+        }
+    }
+
+    #[test]
+    fn t_decode_wide_branch() {
+        let code = vec![
+            0xc8, 0x00, 0x01, 0x00, 0x05, //0: goto_w +65541, past 32K
+            0xc9, 0xff, 0xff, 0xff, 0xfb, //5: jsr_w -5
+            0xb1, //10: return
+        ];
+        let bc = Bytecode::decode(code.as_slice());
+        assert!(bc.err.is_none());
+
+        let instr = bc.get(0).unwrap();
+        assert_eq!(instr.next, 5);
+        assert_eq!(instr.branch(), 0x10005);
+        assert_eq!(bc.get(5).unwrap().branch(), 0);
+    }
+
+    #[test]
+    fn t_decode_invoke_interface() {
+        //invokeinterface #3, count 2 (not matched against the desc); return
+        let bc = Bytecode::decode(&[0xb9, 0x00, 0x03, 0x02, 0x00, 0xb1]);
+        assert!(bc.err.is_none());
+        assert_eq!(bc.get(0).unwrap().int(), 3);
+        assert_eq!(bc.get(0).unwrap().next, 5);
+
+        let bc = Bytecode::decode(&[0xb9, 0x00, 0x03, 0x00, 0x00]);
+        assert_eq!(
+            bc.err.as_deref(),
+            Some("invokeinterface count is 0 at bci=0")
+        );
+        let bc = Bytecode::decode(&[0xb9, 0x00, 0x03, 0x01, 0x01]);
+        assert!(bc.err.is_some());
+    }
+}
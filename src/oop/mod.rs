@@ -1,14 +1,20 @@
 #![allow(unused)]
 
-use std::sync::{Arc, Condvar, Mutex};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use crate::classfile::ClassFile;
-use crate::runtime::{require_class3, ClassLoader};
+use crate::runtime::{self, require_class3, ClassLoader};
 use crate::types::*;
 
+pub mod cha;
 pub mod class;
 pub mod consts;
+pub mod cp_cache;
 pub mod field;
+pub mod inline_cache;
+pub mod layout;
 pub mod method;
 
 pub use self::class::{Class, ClassKind};
@@ -48,12 +54,38 @@ pub enum Oop {
     Null,
 }
 
+/*
+object header, the monitor's lock word & the identity hash
+
+lock:  thin lock, the monitor recursion count
+owner: the host thread holding the monitor, 0 if none
+       both atomics, an uncontended monitorenter/monitorexit takes no
+       fat monitor. Contention & Object.wait inflate the monitor, see
+       runtime::monitor
+inflated: a fat monitor exists, set & cleared under the monitor
+       registry lock, exit & notify of a thin lock skip the registry
+size:  the bytes accounted to the heap, released on drop, see
+       runtime::gc::reserve
+
+The header sits in the OopDesc, behind the object's Mutex as the fields:
+OopRef is an Arc<Mutex<Box<OopDesc>>>. Reading the lock word, a field,
+an element or a boxed int takes that mutex, the Unsafe atomics are
+sections under it. Raw refs managed by the collector, no mutex but a
+contended monitor's, are not done, see note.txt, OopRef优化.
+*/
+#[derive(Debug)]
+pub struct OopHeader {
+    pub hash_code: Option<i32>,
+    lock: AtomicUsize,
+    owner: AtomicU64,
+    inflated: AtomicBool,
+    size: usize,
+}
+
 #[derive(Debug)]
 pub struct OopDesc {
+    pub header: OopHeader,
     pub v: Oop,
-    cond: Condvar,
-    monitor: Mutex<usize>,
-    pub hash_code: Option<i32>,
 }
 
 impl OopDesc {
@@ -79,7 +111,33 @@ impl OopDesc {
 
     pub fn new_inst(cls_obj: ClassRef) -> OopRef {
         let v = InstOopDesc::new(cls_obj);
-        Self::new(Oop::Inst(v))
+        let v = Self::new(Oop::Inst(v));
+        runtime::gc::register(&v);
+        v
+    }
+
+    //the new bytecode, Err over -Xmx
+    pub fn try_new_inst(cls_obj: ClassRef) -> Result<OopRef, AllocErr> {
+        let v = Oop::Inst(InstOopDesc::new(cls_obj));
+        let size = runtime::gc::shallow_size(&v);
+        runtime::gc::reserve(size)?;
+        let v = Self::new_reserved(v, size);
+        runtime::gc::register(&v);
+        Ok(v)
+    }
+
+    //Object.clone, an instance or array as v: the copy's fields or
+    //elements refer to the same objects. Err over -Xmx
+    pub fn try_new_clone(v: Oop) -> Result<OopRef, AllocErr> {
+        match &v {
+            Oop::Inst(_) | Oop::Array(_) | Oop::TypeArray(_) => (),
+            t => unreachable!("t = {:?}", t),
+        }
+        let size = runtime::gc::shallow_size(&v);
+        runtime::gc::reserve(size)?;
+        let v = Self::new_reserved(v, size);
+        runtime::gc::register(&v);
+        Ok(v)
     }
 
     pub fn new_ref_ary(ary_cls_obj: ClassRef, len: usize) -> OopRef {
@@ -93,7 +151,9 @@ impl OopDesc {
 
     pub fn new_ref_ary2(ary_cls_obj: ClassRef, elms: Vec<OopRef>) -> OopRef {
         let v = ArrayOopDesc::new(ary_cls_obj, elms);
-        Self::new(Oop::Array(v))
+        let v = Self::new(Oop::Array(v));
+        runtime::gc::register(&v);
+        v
     }
 
     pub fn new_mirror(target: ClassRef) -> OopRef {
@@ -208,71 +268,202 @@ impl OopDesc {
     pub fn new_byte_ary2(elms: Vec<u8>) -> OopRef {
         let ary = Box::new(elms);
         let v = TypeArrayValue::Byte(ary);
-        Self::new(Oop::TypeArray(v))
+        Self::new_type_ary(v)
     }
 
     pub fn new_bool_ary2(elms: Vec<u8>) -> OopRef {
         let ary = Box::new(elms);
         let v = TypeArrayValue::Bool(ary);
-        Self::new(Oop::TypeArray(v))
+        Self::new_type_ary(v)
     }
 
     pub fn new_char_ary2(elms: Vec<u16>) -> OopRef {
         let ary = Box::new(elms);
         let v = TypeArrayValue::Char(ary);
-        Self::new(Oop::TypeArray(v))
+        Self::new_type_ary(v)
     }
 
     pub fn new_short_ary2(elms: Vec<i16>) -> OopRef {
         let ary = Box::new(elms);
         let v = TypeArrayValue::Short(ary);
-        Self::new(Oop::TypeArray(v))
+        Self::new_type_ary(v)
     }
 
     pub fn new_int_ary2(elms: Vec<i32>) -> OopRef {
         let ary = Box::new(elms);
         let v = TypeArrayValue::Int(ary);
-        Self::new(Oop::TypeArray(v))
+        Self::new_type_ary(v)
     }
 
     pub fn new_float_ary2(elms: Vec<f32>) -> OopRef {
         let ary = Box::new(elms);
         let v = TypeArrayValue::Float(ary);
-        Self::new(Oop::TypeArray(v))
+        Self::new_type_ary(v)
     }
 
     pub fn new_double_ary2(elms: Vec<f64>) -> OopRef {
         let ary = Box::new(elms);
         let v = TypeArrayValue::Double(ary);
-        Self::new(Oop::TypeArray(v))
+        Self::new_type_ary(v)
     }
 
     pub fn new_long_ary2(elms: Vec<i64>) -> OopRef {
         let ary = Box::new(elms);
         let v = TypeArrayValue::Long(ary);
-        Self::new(Oop::TypeArray(v))
+        Self::new_type_ary(v)
+    }
+
+    /*
+    Arrays the guest asks for (newarray, anewarray, multianewarray), Err
+    instead of aborting the process if the memory can't be had, over -Xmx
+    or refused by the host, the interpreter throws OutOfMemoryError and
+    the vm goes on. t is the element desc, b'I', b'J'...
+    */
+    pub fn try_new_type_ary(t: u8, len: usize) -> Result<OopRef, AllocErr> {
+        let (v, size) = match t {
+            b'Z' => type_ary(len, 0, TypeArrayValue::Bool)?,
+            b'C' => type_ary(len, 0, TypeArrayValue::Char)?,
+            b'F' => type_ary(len, 0.0, TypeArrayValue::Float)?,
+            b'D' => type_ary(len, 0.0, TypeArrayValue::Double)?,
+            b'B' => type_ary(len, 0, TypeArrayValue::Byte)?,
+            b'S' => type_ary(len, 0, TypeArrayValue::Short)?,
+            b'I' => type_ary(len, 0, TypeArrayValue::Int)?,
+            b'J' => type_ary(len, 0, TypeArrayValue::Long)?,
+            t => unreachable!("t = {}", t as char),
+        };
+        let v = Self::new_reserved(Oop::TypeArray(v), size);
+        runtime::gc::register(&v);
+        Ok(v)
     }
 
+    pub fn try_new_ref_ary(ary_cls_obj: ClassRef, len: usize) -> Result<OopRef, AllocErr> {
+        let (elms, size) = alloc_elms(len, consts::get_null())?;
+        let v = ArrayOopDesc::new(ary_cls_obj, elms);
+        let v = Self::new_reserved(Oop::Array(v), size);
+        runtime::gc::register(&v);
+        Ok(v)
+    }
+
+    fn new_type_ary(v: TypeArrayValue) -> OopRef {
+        let v = Self::new(Oop::TypeArray(v));
+        runtime::gc::register(&v);
+        v
+    }
+
+    //accounted, never refused, the vm's own objects & the guest's which
+    //can't throw here
     fn new(v: Oop) -> OopRef {
+        let size = runtime::gc::shallow_size(&v);
+        runtime::gc::account(size);
+        Self::new_reserved(v, size)
+    }
+
+    //size is reserved already
+    fn new_reserved(v: Oop, size: usize) -> OopRef {
         let v = Self {
+            header: OopHeader::new(size),
             v,
-            cond: Condvar::new(),
-            monitor: Mutex::new(0),
-            hash_code: None,
         };
         new_sync_ref!(v)
     }
 }
 
 impl OopDesc {
-    pub fn monitor_enter(&mut self) {
-        let mut v = self.monitor.lock().unwrap();
-        *v += 1;
+    //Object.hashCode & System.identityHashCode, 0 for null
+    pub fn identity_hash(&mut self) -> i32 {
+        match self.v {
+            Oop::Null => 0,
+            _ => *self.header.hash_code.get_or_insert_with(next_identity_hash),
+        }
+    }
+}
+
+impl Drop for OopDesc {
+    fn drop(&mut self) {
+        runtime::gc::release(self.header.size);
+    }
+}
+
+impl OopHeader {
+    fn new(size: usize) -> Self {
+        Self {
+            hash_code: None,
+            lock: AtomicUsize::new(0),
+            owner: AtomicU64::new(0),
+            inflated: AtomicBool::new(false),
+            size,
+        }
+    }
+
+    //take or re-enter the thin lock for thread 'me'
+    pub fn try_lock(&self, me: u64) -> bool {
+        if self.owner.load(Ordering::Acquire) == me {
+            self.lock.fetch_add(1, Ordering::Relaxed);
+            true
+        } else if self
+            .owner
+            .compare_exchange(0, me, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.lock.store(1, Ordering::Relaxed);
+            true
+        } else {
+            false
+        }
+    }
+
+    //Err if 'me' is not the owner, Ok(true) once the monitor is free
+    pub fn unlock(&self, me: u64) -> Result<bool, ()> {
+        if !self.is_locked_by(me) {
+            return Err(());
+        }
+
+        if self.lock.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.owner.store(0, Ordering::Release);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    //release whatever the recursion count, returns it, for Object.wait
+    pub fn unlock_all(&self, me: u64) -> Result<usize, ()> {
+        if !self.is_locked_by(me) {
+            return Err(());
+        }
+
+        let count = self.lock.swap(0, Ordering::Relaxed);
+        self.owner.store(0, Ordering::Release);
+        Ok(count)
+    }
+
+    //restore the count saved by unlock_all, after try_lock
+    pub fn set_lock_count(&self, count: usize) {
+        self.lock.store(count, Ordering::Relaxed);
     }
 
-    pub fn monitor_exit(&mut self) {
-        let mut v = self.monitor.lock().unwrap();
-        *v -= 1;
+    pub fn is_locked_by(&self, me: u64) -> bool {
+        self.owner.load(Ordering::Acquire) == me
+    }
+
+    pub fn set_inflated(&self, v: bool) {
+        self.inflated.store(v, Ordering::Relaxed);
+    }
+
+    pub fn is_inflated(&self) -> bool {
+        self.inflated.load(Ordering::Relaxed)
+    }
+}
+
+//a reference slot holds an object, array, mirror or null, never a boxed primitive
+//an oop locked by the caller can't be checked, treated as valid
+pub fn is_valid_ref(v: &OopRef) -> bool {
+    match v.try_lock() {
+        Ok(v) => !matches!(
+            v.v,
+            Oop::Int(_) | Oop::Long(_) | Oop::Float(_) | Oop::Double(_)
+        ),
+        Err(_) => true,
     }
 }
 
@@ -430,8 +621,183 @@ impl TypeArrayValue {
             TypeArrayValue::Long(ary) => ary.len(),
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    //the array class name, "[I"...
+    pub fn desc(&self) -> &'static [u8] {
+        match self {
+            TypeArrayValue::Char(_) => b"[C",
+            TypeArrayValue::Byte(_) => b"[B",
+            TypeArrayValue::Bool(_) => b"[Z",
+            TypeArrayValue::Short(_) => b"[S",
+            TypeArrayValue::Float(_) => b"[F",
+            TypeArrayValue::Double(_) => b"[D",
+            TypeArrayValue::Int(_) => b"[I",
+            TypeArrayValue::Long(_) => b"[J",
+        }
+    }
+}
+
+//hotspot's limit, the array header takes a few words
+pub const MAX_ARRAY_LENGTH: usize = i32::MAX as usize - 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AllocErr {
+    //over MAX_ARRAY_LENGTH
+    TooLong,
+    //over -Xmx after a collection, or the host has no memory left
+    OutOfMemory,
+}
+
+impl AllocErr {
+    //the OutOfMemoryError message
+    pub fn msg(&self) -> &'static str {
+        match self {
+            AllocErr::TooLong => runtime::exception::OOM_ARRAY_SIZE,
+            AllocErr::OutOfMemory => runtime::exception::OOM_JAVA_HEAP,
+        }
+    }
+}
+
+//len elements of v, & the bytes reserved for the array, its shallow size
+fn alloc_elms<T: Clone>(len: usize, v: T) -> Result<(Vec<T>, usize), AllocErr> {
+    let size = ary_size::<T>(len)?;
+    runtime::gc::reserve(size)?;
+
+    let mut elms = Vec::new();
+    if elms.try_reserve_exact(len).is_err() {
+        runtime::gc::release(size);
+        return Err(AllocErr::OutOfMemory);
+    }
+    elms.resize(len, v);
+    Ok((elms, size))
+}
+
+//the shallow size of an array of len T
+fn ary_size<T>(len: usize) -> Result<usize, AllocErr> {
+    if len > MAX_ARRAY_LENGTH {
+        return Err(AllocErr::TooLong);
+    }
+    len.checked_mul(std::mem::size_of::<T>())
+        .and_then(|it| it.checked_add(std::mem::size_of::<OopDesc>()))
+        .ok_or(AllocErr::OutOfMemory)
+}
+
+fn type_ary<T: Clone>(
+    len: usize,
+    v: T,
+    f: fn(Box<Vec<T>>) -> TypeArrayValue,
+) -> Result<(TypeArrayValue, usize), AllocErr> {
+    let (elms, size) = alloc_elms(len, v)?;
+    Ok((f(Box::new(elms)), size))
+}
+
+/*
+Identity hashes, as HotSpot's hashCode=5: Marsaglia's xor-shift, a state
+per thread, 31 bits & never 0. Generated on first use, kept in the
+header, so the same whatever becomes of the object's address, and two
+objects alive at once seldom share one. Seeds are spread by a global
+counter, threads don't repeat each other's sequence.
+*/
+static HASH_SEED: AtomicU64 = AtomicU64::new(0x2545_f491);
+
+thread_local! {
+    static HASH_STATE: Cell<u32> = const { Cell::new(0) };
+}
+
+fn next_identity_hash() -> i32 {
+    HASH_STATE.with(|state| loop {
+        let mut x = state.get();
+        if x == 0 {
+            let seed = HASH_SEED.fetch_add(0x9e37_79b9, Ordering::Relaxed);
+            x = (seed ^ (seed >> 32)) as u32 | 1;
+        }
+        x = xorshift(x);
+        state.set(x);
+        let hash = (x & 0x7fff_ffff) as i32;
+        if hash != 0 {
+            return hash;
+        }
+    })
+}
+
+fn xorshift(mut x: u32) -> u32 {
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    x
 }
 
 pub fn init() {
     consts::init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_alloc_elms() {
+        let (elms, size) = alloc_elms(3, 7i64).unwrap();
+        assert_eq!(elms, vec![7, 7, 7]);
+        assert_eq!(size, std::mem::size_of::<OopDesc>() + 24);
+        runtime::gc::release(size);
+
+        //the sizes are checked, nothing is allocated
+        let header = std::mem::size_of::<OopDesc>();
+        assert_eq!(ary_size::<u8>(MAX_ARRAY_LENGTH + 1), Err(AllocErr::TooLong));
+        assert_eq!(
+            ary_size::<[u64; 1024]>(MAX_ARRAY_LENGTH),
+            Ok(MAX_ARRAY_LENGTH * 8192 + header)
+        );
+        //the size overflows
+        assert_eq!(
+            ary_size::<[u8; 1 << 46]>(MAX_ARRAY_LENGTH),
+            Err(AllocErr::OutOfMemory)
+        );
+    }
+
+    #[test]
+    fn t_accounted() {
+        let v = OopDesc::new_long_ary2(vec![0; 1000]);
+        let size = runtime::gc::shallow_size(&v.lock().unwrap().v);
+        assert_eq!(v.lock().unwrap().header.size, size);
+        assert_eq!(size, std::mem::size_of::<OopDesc>() + 8000);
+    }
+
+    #[test]
+    fn t_identity_hash() {
+        let a = OopDesc::new_int_ary(1);
+        let h = a.lock().unwrap().identity_hash();
+        assert!(h > 0);
+        assert_eq!(a.lock().unwrap().identity_hash(), h);
+        assert_eq!(consts::get_null().lock().unwrap().identity_hash(), 0);
+
+        let hashes: std::collections::HashSet<i32> =
+            (0..1000).map(|_| next_identity_hash()).collect();
+        assert!(hashes.len() > 990);
+        assert!(!hashes.contains(&0));
+    }
+
+    #[test]
+    fn t_clone() {
+        let a = OopDesc::new_int_ary2(vec![1, 2, 3]);
+        let b = OopDesc::try_new_clone(a.lock().unwrap().v.clone()).unwrap();
+        assert!(!Arc::ptr_eq(&a, &b));
+        if let Oop::TypeArray(TypeArrayValue::Int(ary)) = &mut b.lock().unwrap().v {
+            ary[0] = 7;
+        }
+        match (&a.lock().unwrap().v, &b.lock().unwrap().v) {
+            (Oop::TypeArray(TypeArrayValue::Int(a)), Oop::TypeArray(TypeArrayValue::Int(b))) => {
+                assert_eq!(a.as_slice(), &[1, 2, 3]);
+                assert_eq!(b.as_slice(), &[7, 2, 3]);
+            }
+            t => panic!("t = {:?}", t),
+        }
+        //a fresh identity
+        assert_eq!(b.lock().unwrap().header.hash_code, None);
+    }
+}
@@ -4,7 +4,7 @@ use crate::classfile::{
     constant_pool::*,
     field_info::FieldInfo,
     method_info::MethodInfo,
-    ClassFile, Version,
+    ClassFile, Version, MAX_MAJOR_VERSION, MIN_MAJOR_VERSION,
 };
 use crate::types::*;
 use bytes::Buf;
@@ -12,19 +12,20 @@ use std::io::{Cursor, Read};
 //use std::path::Path;
 use std::sync::Arc;
 
-struct Parser {
-    buf: Cursor<Vec<U1>>,
+//parses from a borrowed buf, a file mapping or a jar entry
+struct Parser<'a> {
+    buf: Cursor<&'a [U1]>,
 }
 
-impl Parser {
-    fn new(raw: Vec<U1>) -> Self {
+impl<'a> Parser<'a> {
+    fn new(raw: &'a [U1]) -> Self {
         Self {
             buf: Cursor::new(raw),
         }
     }
 }
 
-impl Parser {
+impl<'a> Parser<'a> {
     fn parse(&mut self) -> ClassFile {
         let magic = self.get_magic();
         assert_eq!(magic, 0xCAFEBABE);
@@ -162,7 +163,7 @@ trait ClassFileParser {
     fn get_attrs(&mut self, n: U2, cp: &ConstantPool) -> Vec<AttrType>;
 }
 
-impl ClassFileParser for Parser {
+impl ClassFileParser for Parser<'_> {
     fn get_magic(&mut self) -> U4 {
         self.get_u4()
     }
@@ -200,6 +201,7 @@ impl ClassFileParser for Parser {
                 ConstantTag::Utf8 => self.get_constant_utf8(),
                 ConstantTag::MethodHandle => self.get_constant_method_handle(),
                 ConstantTag::MethodType => self.get_constant_method_type(),
+                ConstantTag::Dynamic => self.get_constant_dynamic(),
                 ConstantTag::InvokeDynamic => self.get_constant_invoke_dynamic(),
                 _ => unreachable!(),
             };
@@ -295,10 +297,11 @@ trait ConstantPoolParser {
     fn get_constant_utf8(&mut self) -> ConstantType;
     fn get_constant_method_handle(&mut self) -> ConstantType;
     fn get_constant_method_type(&mut self) -> ConstantType;
+    fn get_constant_dynamic(&mut self) -> ConstantType;
     fn get_constant_invoke_dynamic(&mut self) -> ConstantType;
 }
 
-impl ConstantPoolParser for Parser {
+impl ConstantPoolParser for Parser<'_> {
     fn get_constant_class(&mut self) -> ConstantType {
         ConstantType::Class {
             name_index: self.get_u2(),
@@ -387,6 +390,13 @@ impl ConstantPoolParser for Parser {
         }
     }
 
+    fn get_constant_dynamic(&mut self) -> ConstantType {
+        ConstantType::Dynamic {
+            bootstrap_method_attr_index: self.get_u2(),
+            name_and_type_index: self.get_u2(),
+        }
+    }
+
     fn get_constant_invoke_dynamic(&mut self) -> ConstantType {
         ConstantType::InvokeDynamic {
             bootstrap_method_attr_index: self.get_u2(),
@@ -399,7 +409,7 @@ trait FieldParser {
     fn get_field(&mut self, cp: &ConstantPool) -> FieldInfo;
 }
 
-impl FieldParser for Parser {
+impl FieldParser for Parser<'_> {
     fn get_field(&mut self, cp: &ConstantPool) -> FieldInfo {
         let acc_flags = self.get_u2();
         let name_index = self.get_u2();
@@ -420,7 +430,7 @@ trait MethodParser {
     fn get_method(&mut self, cp: &ConstantPool) -> MethodInfo;
 }
 
-impl MethodParser for Parser {
+impl MethodParser for Parser<'_> {
     fn get_method(&mut self, cp: &ConstantPool) -> MethodInfo {
         let acc_flags = self.get_u2();
         let name_index = self.get_u2();
@@ -462,6 +472,8 @@ trait AttrTypeParser {
     fn get_attr_annotation_default(&mut self, cp: &ConstantPool) -> AttrType;
     fn get_attr_bootstrap_methods(&mut self) -> AttrType;
     fn get_attr_method_parameters(&mut self) -> AttrType;
+    fn get_attr_record(&mut self, cp: &ConstantPool) -> AttrType;
+    fn get_attr_permitted_subclasses(&mut self) -> AttrType;
     fn get_attr_unknown(&mut self) -> AttrType;
 }
 
@@ -474,7 +486,7 @@ trait AttrTypeParserUtils {
     fn get_attr_util_get_element_val(&mut self, cp: &ConstantPool) -> attr_info::ElementValueType;
 }
 
-impl AttrTypeParser for Parser {
+impl AttrTypeParser for Parser<'_> {
     fn get_attr_type(&mut self, cp: &ConstantPool) -> AttrType {
         let name_index = self.get_u2();
         let name = get_utf8(cp, name_index as usize).unwrap();
@@ -511,6 +523,8 @@ impl AttrTypeParser for Parser {
             AttrTag::AnnotationDefault => self.get_attr_annotation_default(cp),
             AttrTag::BootstrapMethods => self.get_attr_bootstrap_methods(),
             AttrTag::MethodParameters => self.get_attr_method_parameters(),
+            AttrTag::Record => self.get_attr_record(cp),
+            AttrTag::PermittedSubclasses => self.get_attr_permitted_subclasses(),
             AttrTag::Unknown => self.get_attr_unknown(),
         }
     }
@@ -827,6 +841,32 @@ impl AttrTypeParser for Parser {
         AttrType::MethodParameters { parameters }
     }
 
+    fn get_attr_record(&mut self, cp: &ConstantPool) -> AttrType {
+        let _length = self.get_u4();
+        let n = self.get_u2();
+        let mut components = Vec::with_capacity(n as usize);
+        for _ in 0..n {
+            let name_index = self.get_u2();
+            let descriptor_index = self.get_u2();
+            let attrs_count = self.get_attrs_count();
+            let attrs = self.get_attrs(attrs_count, cp);
+            components.push(attr_info::RecordComponent {
+                name_index,
+                descriptor_index,
+                attrs,
+            });
+        }
+
+        AttrType::Record { components }
+    }
+
+    fn get_attr_permitted_subclasses(&mut self) -> AttrType {
+        let _length = self.get_u4();
+        let n = self.get_u2();
+        let classes = (0..n).map(|_| self.get_u2()).collect();
+        AttrType::PermittedSubclasses { classes }
+    }
+
     fn get_attr_unknown(&mut self) -> AttrType {
         let len = self.get_u4();
         let _v = self.get_u1s(len as usize);
@@ -834,7 +874,7 @@ impl AttrTypeParser for Parser {
     }
 }
 
-impl AttrTypeParserUtils for Parser {
+impl AttrTypeParserUtils for Parser<'_> {
     fn get_attr_util_get_annotation(&mut self, cp: &ConstantPool) -> attr_info::AnnotationEntry {
         let type_index = self.get_u2();
         let n = self.get_u2();
@@ -1039,7 +1079,93 @@ pub fn parse<P: AsRef<Path>>(path: P) -> std::io::Result<ClassFile> {
 }
 */
 
-pub fn parse_buf(buf: Vec<u8>) -> std::io::Result<ClassFile> {
-    let mut parser = Parser::new(buf);
-    Ok(parser.parse())
+//a bad magic or a truncated buf, which panics in the cursor, is an
+//InvalidData Err, a major version out of MIN..=MAX_MAJOR_VERSION an
+//Unsupported one
+pub fn parse_buf(buf: &[u8]) -> std::io::Result<ClassFile> {
+    let bad = |msg| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+    if !buf.starts_with(&[0xCA, 0xFE, 0xBA, 0xBE]) {
+        return Err(bad("Incompatible magic value"));
+    }
+    if let [_, _, _, _, minor0, minor1, major0, major1, ..] = *buf {
+        let minor = u16::from_be_bytes([minor0, minor1]);
+        let major = u16::from_be_bytes([major0, major1]);
+        if !(MIN_MAJOR_VERSION..=MAX_MAJOR_VERSION).contains(&major) {
+            let msg = format!("Unsupported major.minor version {}.{}", major, minor);
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, msg));
+        }
+    }
+    std::panic::catch_unwind(|| Parser::new(buf).parse()).map_err(|_| bad("Truncated class file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::checker::Checker;
+
+    #[test]
+    fn t_version() {
+        let mut buf = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 62];
+        let e = parse_buf(&buf).err().unwrap();
+        assert_eq!(e.kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(e.to_string(), "Unsupported major.minor version 62.0");
+
+        buf[5] = 3;
+        buf[7] = 44;
+        let e = parse_buf(&buf).err().unwrap();
+        assert_eq!(e.to_string(), "Unsupported major.minor version 44.3");
+
+        //a supported version, parsed on, the rest is missing
+        buf[7] = 52;
+        let e = parse_buf(&buf).err().unwrap();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn t_cp_dynamic() {
+        //#1 Utf8 "x", #2 NameAndType #1:#1, #3 Dynamic #0:#2
+        let buf = [1, 0, 1, b'x', 12, 0, 1, 0, 1, 17, 0, 0, 0, 2];
+        let mut parser = Parser::new(&buf);
+        let cp = parser.get_cp(4);
+        match cp.get(3) {
+            Some(ConstantType::Dynamic {
+                bootstrap_method_attr_index: 0,
+                name_and_type_index: 2,
+            }) => (),
+            t => panic!("t = {:?}", t),
+        }
+        assert!(cp.get(3).unwrap().check(&cp).is_ok());
+    }
+
+    #[test]
+    fn t_attr_record_permitted_subclasses() {
+        //#1 Utf8 "Record", #2 "x", #3 "I", #4 "PermittedSubclasses"
+        let mut buf = vec![1, 0, 6];
+        buf.extend_from_slice(b"Record");
+        buf.extend_from_slice(&[1, 0, 1, b'x', 1, 0, 1, b'I', 1, 0, 19]);
+        buf.extend_from_slice(b"PermittedSubclasses");
+        let n = buf.len();
+        //Record, one component x:I, no attrs
+        buf.extend_from_slice(&[0, 1, 0, 0, 0, 8, 0, 1, 0, 2, 0, 3, 0, 0]);
+        //PermittedSubclasses, #7 & #9
+        buf.extend_from_slice(&[0, 4, 0, 0, 0, 6, 0, 2, 0, 7, 0, 9]);
+
+        let mut parser = Parser::new(&buf);
+        let cp = parser.get_cp(5);
+        assert_eq!(parser.buf.position() as usize, n);
+        match parser.get_attr_type(&cp) {
+            AttrType::Record { components } => {
+                assert_eq!(components.len(), 1);
+                assert_eq!(components[0].name_index, 2);
+                assert_eq!(components[0].descriptor_index, 3);
+                assert!(components[0].attrs.is_empty());
+            }
+            t => panic!("t = {:?}", t),
+        }
+        match parser.get_attr_type(&cp) {
+            AttrType::PermittedSubclasses { classes } => assert_eq!(classes, vec![7, 9]),
+            t => panic!("t = {:?}", t),
+        }
+        assert_eq!(parser.buf.position() as usize, buf.len());
+    }
 }
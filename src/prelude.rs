@@ -0,0 +1,426 @@
+/*
+The stable surface, for embedders & tools
+
+Everything else (frames, oops, class file structures, the interpreter)
+is internal and changes freely, code outside the vm core should only use
+what is here:
+
+  Vm, Config      start the vm, run a main class
+  RunResult       how main ended: returned, System.exit, uncaught exception
+  Value           a guest object, opaque
+  GlobalHandle, WeakHandle
+                  keep Values across calls
+  Env, LocalRef   the thread of a host native, its args & locals, a
+                  LocalRef is valid until the native returns
+  NativeMethod    a host fn, by new_fn & new_async_fn
+  Error           failures of Vm::new
+  instrumentation step hooks, object tags, events, host classes
+  find_instances  live instances of a class, -XX:+TrackHeapObjects
+  exception breakpoints  break when an exception of a class is constructed
+  overrides       replace a native, intrinsic or java method by a host fn
+  async natives   a Rust future behind a CompletableFuture
+  file systems    mounted for guest file I/O, in-memory or the embedder's
+  class providers class bytes by name, for classes generated at runtime
+
+Additions are fine, changing or removing an item here is a breaking
+change.
+*/
+
+use crate::native::{self, JNINativeMethod};
+use crate::oop::{Oop, OopDesc};
+use crate::runtime::thread::JavaMainThread;
+use crate::runtime::{self, flags, gc, handles, host_async, host_class, tags, JavaThread};
+use crate::types::OopRef;
+use crate::util;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Once};
+
+pub use crate::native::{clear_overrides, remove_override, LocalResult};
+pub use crate::runtime::events::{Event, EventKind};
+pub use crate::runtime::ex_break::{
+    add_filter as add_exception_breakpoint, remove_filter as remove_exception_breakpoint,
+    set_hook as set_exception_hook, ExAction, ExHook, ExceptionContext,
+};
+pub use crate::runtime::gc::{histogram, HistogramEntry};
+pub use crate::runtime::handles::{GlobalHandle, LocalRef, WeakHandle};
+pub use crate::runtime::step::{set_hook as set_step_hook, StepAction, StepContext, StepHook};
+pub use crate::runtime::thread::RunResult;
+pub use crate::runtime::vfs::{
+    mount, unmount, FileSystem, MemFs, Metadata, OpenMode, VFile, ACCESS_EXECUTE, ACCESS_READ,
+    ACCESS_WRITE,
+};
+pub use crate::runtime::{add_class_provider, ClassProvider};
+
+//a guest object, or a primitive value
+#[derive(Clone)]
+pub struct Value(OopRef);
+
+impl Value {
+    pub(crate) fn new(v: OopRef) -> Self {
+        Value(v)
+    }
+
+    pub fn null() -> Self {
+        Value(OopDesc::new_null())
+    }
+
+    pub fn from_int(v: i32) -> Self {
+        Value(OopDesc::new_int(v))
+    }
+
+    pub fn from_long(v: i64) -> Self {
+        Value(OopDesc::new_long(v))
+    }
+
+    pub fn from_float(v: f32) -> Self {
+        Value(OopDesc::new_float(v))
+    }
+
+    pub fn from_double(v: f64) -> Self {
+        Value(OopDesc::new_double(v))
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.0.lock().unwrap().v, Oop::Null)
+    }
+
+    //None if it's not an int (boolean, byte, char, short & int args)
+    pub fn as_int(&self) -> Option<i32> {
+        match self.0.lock().unwrap().v {
+            Oop::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_long(&self) -> Option<i64> {
+        match self.0.lock().unwrap().v {
+            Oop::Long(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self.0.lock().unwrap().v {
+            Oop::Float(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match self.0.lock().unwrap().v {
+            Oop::Double(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    //the same object, as ==
+    pub fn ptr_eq(&self, other: &Value) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Value({:p})", Arc::as_ptr(&self.0))
+    }
+}
+
+pub fn new_global(v: &Value) -> GlobalHandle {
+    handles::new_global(v.0.clone())
+}
+
+pub fn resolve_global(handle: GlobalHandle) -> Option<Value> {
+    handles::resolve_global(handle).map(Value)
+}
+
+pub fn delete_global(handle: GlobalHandle) {
+    handles::delete_global(handle)
+}
+
+pub fn new_weak(v: &Value) -> WeakHandle {
+    handles::new_weak(&v.0)
+}
+
+pub fn resolve_weak(handle: WeakHandle) -> Option<Value> {
+    handles::resolve_weak(handle).map(Value)
+}
+
+pub fn delete_weak(handle: WeakHandle) {
+    handles::delete_weak(handle)
+}
+
+pub fn set_tag(v: &Value, tag: u64) {
+    tags::set_tag(&v.0, tag)
+}
+
+pub fn get_tag(v: &Value) -> u64 {
+    tags::get_tag(&v.0)
+}
+
+pub fn objects_with_tags(tags: &[u64]) -> Vec<(Value, u64)> {
+    tags::objects_with_tags(tags)
+        .into_iter()
+        .map(|(v, tag)| (Value(v), tag))
+        .collect()
+}
+
+pub struct Instances {
+    pub objects: Vec<Value>,
+    //sum of the shallow sizes
+    pub shallow_size: usize,
+}
+
+//live instances of a class, "java.lang.String", None if it's not loaded
+pub fn find_instances(name: &str, subclasses: bool) -> Option<Instances> {
+    let found = gc::find_instances(name, subclasses)?;
+    Some(Instances {
+        objects: found.objects.into_iter().map(Value).collect(),
+        shallow_size: found.shallow_size,
+    })
+}
+
+pub fn shallow_size(v: &Value) -> usize {
+    gc::shallow_size(&v.0.lock().unwrap().v)
+}
+
+//the thread a host native runs on, the locals of its frame
+pub struct Env<'a> {
+    jt: &'a mut JavaThread,
+}
+
+impl Env<'_> {
+    pub fn new_local(&mut self, v: &Value) -> LocalRef {
+        handles::new_local(self.jt, v.0.clone())
+    }
+
+    //None if deleted, its frame popped, or of another thread
+    pub fn resolve_local(&self, r: LocalRef) -> Option<Value> {
+        handles::resolve_local(self.jt, r).map(Value)
+    }
+
+    pub fn delete_local(&mut self, r: LocalRef) {
+        handles::delete_local(self.jt, r)
+    }
+
+    pub fn push_local_frame(&mut self) {
+        handles::push_local_frame(self.jt)
+    }
+
+    //result is kept, a local of the frame below, as PopLocalFrame
+    pub fn pop_local_frame(&mut self, result: Option<LocalRef>) -> Option<LocalRef> {
+        let result = result.and_then(|r| handles::resolve_local(self.jt, r));
+        handles::pop_local_frame(self.jt, result)
+    }
+
+    //a java/lang/String
+    pub fn new_string(&mut self, s: &str) -> LocalRef {
+        let v = util::oop::new_java_lang_string2(self.jt, s);
+        handles::new_local(self.jt, v)
+    }
+}
+
+//a host fn as a native method, see new_fn
+#[derive(Clone)]
+pub struct NativeMethod(JNINativeMethod);
+
+//the args are locals of the native's frame, freed when it returns
+pub fn new_fn<F>(name: &'static str, signature: &'static str, f: F) -> NativeMethod
+where
+    F: Fn(&mut Env, Vec<LocalRef>) -> LocalResult + Send + Sync + 'static,
+{
+    NativeMethod(native::new_local_fn(
+        name,
+        signature,
+        Box::new(move |jt, _env, args| f(&mut Env { jt }, args)),
+    ))
+}
+
+//class like "java/lang/System", '.' is accepted too
+pub fn set_override(class: &str, method: NativeMethod) {
+    native::set_override(class, method.0)
+}
+
+pub fn register_host_class(name: &'static str, methods: Vec<NativeMethod>) {
+    host_class::register(name, methods.into_iter().map(|it| it.0).collect())
+}
+
+//builds the value of the CompletableFuture, on the completer thread: Ok
+//completes it, Err completes it exceptionally
+pub type Completion = Box<dyn FnOnce(&mut Env) -> Result<Option<Value>, Value> + Send>;
+
+//a Rust future behind a CompletableFuture, see runtime::host_async
+pub fn new_async_fn<F, Fut>(name: &'static str, signature: &'static str, f: F) -> NativeMethod
+where
+    F: Fn(Vec<Value>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Completion> + Send + 'static,
+{
+    NativeMethod(host_async::new_async_fn(name, signature, move |args| {
+        let future = f(args.into_iter().map(Value).collect());
+        async move {
+            let completion = future.await;
+            Box::new(move |jt: &mut JavaThread| {
+                completion(&mut Env { jt })
+                    .map(|v| v.map(|it| it.0))
+                    .map_err(|ex| ex.0)
+            }) as host_async::Completion
+        }
+    }))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    //directories & jars, separated by util::PATH_SEP
+    pub class_path: Option<String>,
+    //vm options as on the command line, "-XX:+VerifyStack"
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UnrecognizedOption(String),
+    //-Xms over -Xmx
+    InvalidHeapSize(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnrecognizedOption(msg) => write!(f, "{}", msg),
+            Error::InvalidHeapSize(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+pub struct Vm {
+    _private: (),
+}
+
+static INIT: Once = Once::new();
+
+impl Vm {
+    pub fn new(config: Config) -> Result<Self, Error> {
+        INIT.call_once(|| {
+            crate::oop::init();
+            runtime::init();
+            crate::native::init();
+        });
+
+        for opt in config.options.iter() {
+            let opt = opt.strip_prefix("-X").unwrap_or(opt);
+            flags::parse_x_opt(opt).map_err(Error::UnrecognizedOption)?;
+        }
+        flags::check_heap_sizes().map_err(Error::InvalidHeapSize)?;
+
+        #[cfg(feature = "diag-server")]
+        {
+            if let Some(port) = flags::diag_server_port() {
+                runtime::diag::start(port);
+            }
+        }
+        if let Some(path) = flags::control_socket() {
+            runtime::control::start(&path);
+        }
+
+        if let Some(cp) = config.class_path.as_ref() {
+            runtime::add_class_paths(cp);
+        }
+
+        Ok(Self { _private: () })
+    }
+
+    //run main_class.main(args), returns how it ended
+    pub fn run(&self, main_class: &str, args: Option<Vec<String>>) -> RunResult {
+        let result = run_main_thread(main_class.to_string(), args);
+        runtime::dump_reports();
+        result
+    }
+
+    //run main_class.main(args), returns the exit status
+    pub fn run_main(&self, main_class: &str, args: Option<Vec<String>>) -> i32 {
+        self.run(main_class, args).exit_status()
+    }
+}
+
+//main ending isn't notified, looked at again after this
+const HALT_WAIT: std::time::Duration = std::time::Duration::from_millis(10);
+
+//main runs on a host thread of -Xss stack size, guarded, see
+//thread::set_stack_size
+fn run_main_thread(main_class: String, args: Option<Vec<String>>) -> RunResult {
+    let stack_size = flags::thread_stack_size();
+    let spawned = std::thread::Builder::new()
+        .name("main".to_string())
+        .stack_size(stack_size)
+        .spawn(move || {
+            runtime::thread::set_stack_size(stack_size);
+            let mut thread = JavaMainThread::new(main_class, args);
+            thread.run()
+        });
+
+    //main returns, or any thread halts the vm, its threads are left
+    //stopped
+    match spawned {
+        Ok(handle) => loop {
+            if let Some(status) = runtime::safepoint::wait_halt(HALT_WAIT) {
+                break RunResult::Exit(status);
+            }
+            if handle.is_finished() {
+                match handle.join() {
+                    Ok(result) => break result,
+                    Err(e) => std::panic::resume_unwind(e),
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("Error: failed to create the main thread: {}", e);
+            RunResult::Exit(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_vm_options() {
+        let config = Config {
+            class_path: None,
+            options: vec!["-XX:+NoSuchFlag".to_string()],
+        };
+        match Vm::new(config) {
+            Err(Error::UnrecognizedOption(msg)) => assert!(msg.contains("NoSuchFlag")),
+            _ => panic!("option accepted"),
+        }
+    }
+
+    #[test]
+    fn t_value() {
+        let v = Value::from_int(7);
+        assert_eq!(
+            (v.as_int(), v.as_long(), v.is_null()),
+            (Some(7), None, false)
+        );
+        assert!(Value::null().is_null());
+
+        let g = new_global(&v);
+        assert!(resolve_global(g).unwrap().ptr_eq(&v));
+        delete_global(g);
+        assert!(resolve_global(g).is_none());
+
+        let w = new_weak(&v);
+        set_tag(&v, 42);
+        assert_eq!(get_tag(&v), 42);
+        drop(v);
+        assert!(resolve_weak(w).is_none());
+        delete_weak(w);
+    }
+
+    #[test]
+    fn t_run_result() {
+        assert_eq!(RunResult::Normal.exit_status(), 0);
+        assert_eq!(RunResult::Exit(3).exit_status(), 3);
+        assert_eq!(RunResult::Exception(String::new()).exit_status(), 1);
+    }
+}
@@ -0,0 +1,80 @@
+/*
+Per thread bump arena, for transient byte strings
+
+join appends to one growing buffer, reset rewinds it to a mark. The
+buffer capacity is kept, so after warm up no allocation happens.
+JavaCall::invoke takes a mark before a call and resets after it returns,
+anything allocated during the call is gone at the frame boundary.
+
+Only the symbol key of invoke_native is built here. Resolution, descriptor
+parsing (MethodSignature) & reflection still allocate their ids & Vecs on
+the heap: the tables they probe are keyed by BytesRef, the signatures own
+their types. The allocator pressure saved is not measured, the tree has
+no benchmark harness. See note.txt, 存在的问题.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaRef {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Arena {
+    buf: Vec<u8>,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    pub fn mark(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn reset(&mut self, mark: usize) {
+        self.buf.truncate(mark);
+    }
+
+    //parts joined by sep
+    pub fn join(&mut self, parts: &[&[u8]], sep: &[u8]) -> ArenaRef {
+        let start = self.buf.len();
+        for (i, it) in parts.iter().enumerate() {
+            if i != 0 {
+                self.buf.extend_from_slice(sep);
+            }
+            self.buf.extend_from_slice(it);
+        }
+
+        ArenaRef {
+            start,
+            end: self.buf.len(),
+        }
+    }
+
+    pub fn get(&self, v: ArenaRef) -> &[u8] {
+        &self.buf[v.start..v.end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arena;
+
+    #[test]
+    fn t_arena() {
+        let mut arena = Arena::new();
+        let a = arena.join(&[b"a", b"b"], b":");
+        let mark = arena.mark();
+        let b = arena.join(&[b"java/lang/Object", b"hashCode", b"()I"], b":");
+        assert_eq!(arena.get(a), b"a:b");
+        assert_eq!(arena.get(b), b"java/lang/Object:hashCode:()I");
+
+        arena.reset(mark);
+        assert_eq!(arena.mark(), 3);
+        let c = arena.join(&[b"c"], b":");
+        assert_eq!(arena.get(c), b"c");
+        assert_eq!(arena.get(a), b"a:b");
+    }
+}
@@ -0,0 +1,170 @@
+/*
+Call graph recorder
+
+  jvm -XX:CallGraph=calls.dot Main
+  dot -Tsvg calls.dot > calls.svg
+
+Every method invocation (JavaCall::invoke, java & native) records the
+edge caller -> callee with a count. When the vm exits the graph is
+written to the path, DOT if it ends with ".dot", else json:
+
+  {"nodes":["Main.main([Ljava/lang/String;)V",...],
+   "edges":[{"from":0,"to":1,"count":3},...]}
+
+A call from the vm itself (main, <clinit>, upcalls with no java caller)
+comes from the node "<vm>". Methods are keyed by address, names are
+made once at exit.
+*/
+
+use crate::runtime::flags;
+use crate::types::MethodIdRef;
+use crate::util;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+const VM_NODE: &str = "<vm>";
+
+struct Edge {
+    caller: Option<MethodIdRef>,
+    callee: MethodIdRef,
+    count: u64,
+}
+
+lazy_static! {
+    static ref EDGES: Mutex<HashMap<(usize, usize), Edge>> = Mutex::new(HashMap::new());
+}
+
+pub fn on_call(caller: Option<&MethodIdRef>, callee: &MethodIdRef) {
+    if !flags::is_record_calls() {
+        return;
+    }
+
+    let key = (
+        caller.map_or(0, |it| Arc::as_ptr(it) as usize),
+        Arc::as_ptr(callee) as usize,
+    );
+    util::sync_call_ctx(&EDGES, |edges| {
+        edges
+            .entry(key)
+            .or_insert_with(|| Edge {
+                caller: caller.cloned(),
+                callee: callee.clone(),
+                count: 0,
+            })
+            .count += 1;
+    });
+}
+
+//write the graph to -XX:CallGraph
+pub fn dump() {
+    if let Some(path) = flags::call_graph() {
+        let edges: Vec<(String, String, u64)> = util::sync_call_ctx(&EDGES, |edges| {
+            edges
+                .values()
+                .map(|it| {
+                    let caller = it
+                        .caller
+                        .as_ref()
+                        .map_or_else(|| VM_NODE.to_string(), method_name);
+                    (caller, method_name(&it.callee), it.count)
+                })
+                .collect()
+        });
+
+        let buf = if path.ends_with(".dot") {
+            to_dot(edges)
+        } else {
+            to_json(edges)
+        };
+        if !util::write(&path, buf.as_bytes()) {
+            warn!("write call graph failed: {}", path);
+        }
+    }
+}
+
+fn method_name(mir: &MethodIdRef) -> String {
+    let cls = mir.method.class.lock().unwrap();
+    format!(
+        "{}.{}{}",
+        String::from_utf8_lossy(cls.name.as_slice()),
+        String::from_utf8_lossy(mir.method.name.as_slice()),
+        String::from_utf8_lossy(mir.method.desc.as_slice())
+    )
+}
+
+//sorted, the output is stable
+fn sort(mut edges: Vec<(String, String, u64)>) -> Vec<(String, String, u64)> {
+    edges.sort();
+    edges
+}
+
+fn to_dot(edges: Vec<(String, String, u64)>) -> String {
+    let mut buf = String::from("digraph calls {\n");
+    for (caller, callee, count) in sort(edges) {
+        let _ = writeln!(
+            buf,
+            "  {} -> {} [label=\"{}\"];",
+            util::json_str(&caller),
+            util::json_str(&callee),
+            count
+        );
+    }
+    buf.push_str("}\n");
+    buf
+}
+
+fn to_json(edges: Vec<(String, String, u64)>) -> String {
+    let mut nodes: Vec<String> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut node = |name: String| -> usize {
+        *index.entry(name.clone()).or_insert_with(|| {
+            nodes.push(name);
+            nodes.len() - 1
+        })
+    };
+
+    let edges: Vec<String> = sort(edges)
+        .into_iter()
+        .map(|(caller, callee, count)| {
+            let (from, to) = (node(caller), node(callee));
+            format!("{{\"from\":{},\"to\":{},\"count\":{}}}", from, to, count)
+        })
+        .collect();
+    let nodes: Vec<String> = nodes.iter().map(|it| util::json_str(it)).collect();
+
+    format!(
+        "{{\"nodes\":[{}],\"edges\":[{}]}}",
+        nodes.join(","),
+        edges.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges() -> Vec<(String, String, u64)> {
+        vec![
+            ("A.b()V".to_string(), "A.c()V".to_string(), 3),
+            (VM_NODE.to_string(), "A.b()V".to_string(), 1),
+        ]
+    }
+
+    #[test]
+    fn t_to_dot() {
+        assert_eq!(
+            to_dot(edges()),
+            "digraph calls {\n  \"<vm>\" -> \"A.b()V\" [label=\"1\"];\n  \"A.b()V\" -> \"A.c()V\" [label=\"3\"];\n}\n"
+        );
+    }
+
+    #[test]
+    fn t_to_json() {
+        assert_eq!(
+            to_json(edges()),
+            "{\"nodes\":[\"<vm>\",\"A.b()V\",\"A.c()V\"],\
+             \"edges\":[{\"from\":0,\"to\":1,\"count\":1},{\"from\":1,\"to\":2,\"count\":3}]}"
+        );
+    }
+}
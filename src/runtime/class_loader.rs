@@ -1,12 +1,34 @@
-use crate::classfile::constant_pool;
+use crate::classfile::{constant_pool, consts, ClassFile};
 use crate::native;
 use crate::oop::{self, Class, OopDesc, ValueType};
 use crate::parser as class_parser;
+use crate::runtime::events::{self, EventKind};
 use crate::runtime::{self, ClassPathResult};
 use crate::types::*;
 use crate::util;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+//the classes of ClassLoader.defineClass
+#[derive(Default)]
+struct Defined {
+    //by (loader address, name), the loader is kept, its address is not
+    //reused
+    classes: HashMap<(usize, Vec<u8>), (OopRef, ClassRef)>,
+    //the first class defined under a name
+    first: HashMap<Vec<u8>, ClassRef>,
+}
+
+lazy_static! {
+    static ref DEFINED: Mutex<Defined> = Mutex::new(Defined::default());
+}
+
+thread_local! {
+    //why the last class path load of this thread failed, if it did
+    static LOAD_ERROR: RefCell<Option<(&'static [u8], String)>> = const { RefCell::new(None) };
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum ClassLoader {
     Base,
@@ -41,6 +63,10 @@ impl ClassLoader {
                     //                    info!("load_class in dic: {}", String::from_utf8_lossy(name));
                     return it;
                 }
+                let it = find_defined_by_name(name);
+                if it.is_some() {
+                    return it;
+                }
             }
         }
 
@@ -52,16 +78,7 @@ impl ClassLoader {
             match class.clone() {
                 Some(class) => match self {
                     ClassLoader::Base => (),
-                    ClassLoader::Bootstrap => {
-                        runtime::sys_dic_put(name, class.clone());
-                        let this_ref = class.clone();
-                        util::sync_call_ctx(&class, move |it| {
-                            it.set_class_state(oop::class::State::Loaded);
-                            it.link_class(this_ref);
-                        });
-
-                        native::java_lang_Class::create_mirror(class.clone());
-                    }
+                    ClassLoader::Bootstrap => enter_class(name, class),
                 },
 
                 None => (),
@@ -92,6 +109,7 @@ impl ClassLoader {
                                     ClassLoader::Base => (),
                                     ClassLoader::Bootstrap => {
                                         runtime::sys_dic_put(name, class.clone());
+                                        runtime::metaspace::on_class_load(&class);
                                     }
                                 }
 
@@ -119,6 +137,7 @@ impl ClassLoader {
                             ClassLoader::Base => (),
                             ClassLoader::Bootstrap => {
                                 runtime::sys_dic_put(name, class.clone());
+                                runtime::metaspace::on_class_load(&class);
                             }
                         }
 
@@ -142,6 +161,7 @@ impl ClassLoader {
                             ClassLoader::Base => (),
                             ClassLoader::Bootstrap => {
                                 runtime::sys_dic_put(name, class.clone());
+                                runtime::metaspace::on_class_load(&class);
                             }
                         }
 
@@ -157,20 +177,146 @@ impl ClassLoader {
     }
 
     fn load_class_from_path(&self, name: &[u8]) -> Option<ClassRef> {
-        let name = unsafe { std::str::from_utf8_unchecked(name) };
-        match runtime::find_class_in_classpath(name) {
-            Ok(ClassPathResult(_, buf)) => match class_parser::parse_buf(buf) {
-                Ok(cf) => {
-                    let cfr = new_ref!(cf);
-                    let class = Class::new_class(cfr, Some(*self));
-                    Some(new_sync_ref!(class))
-                }
+        if let Some(cfr) = runtime::host_class::find(name) {
+            let class = Class::new_class(cfr, Some(*self));
+            return Some(new_sync_ref!(class));
+        }
 
-                Err(_) => None,
+        LOAD_ERROR.with(|it| it.replace(None));
+        let name = util::mutf8::display(name);
+        //a parse error of the prefetch is the loader's own
+        let (source, parsed) = match runtime::prefetch::take(name.as_bytes()) {
+            Some(parsed) => ("prefetch".to_string(), parsed),
+            None => match runtime::find_class_in_classpath(&name) {
+                Ok(ClassPathResult(path, buf)) => {
+                    let parsed = class_parser::parse_buf(&buf).map(|cf| new_ref!(cf));
+                    (path, parsed)
+                }
+                Err(_) => return None,
             },
+        };
+
+        match parsed {
+            Ok(cfr) => {
+                trace_loaded(name.as_bytes(), &source);
+                let class = Class::new_class(cfr, Some(*self));
+                Some(new_sync_ref!(class))
+            }
+
+            Err(e) => {
+                let (cls, msg) = parse_error(e);
+                let msg = format!("{} : {}", name.replace('/', "."), msg);
+                LOAD_ERROR.with(|it| it.replace(Some((cls, msg))));
+                None
+            }
+        }
+    }
+}
+
+/*
+ClassLoader.defineClass
+
+The class from the bytes is entered in the dictionary of defined classes
+by (loader, name) & linked as a loaded one. A name the loader defined
+before is an Err, the dictionary keeps the first class, other loaders
+define the name again, each a class of its own.
 
-            Err(_) => None,
+Defined classes are not in the system dictionary, the bootstrap loader
+doesn't find them for a parent delegation. Constant pools are resolved
+by name though, without the loader, a name not found in the system
+dictionary resolves to the first class defined under it.
+*/
+pub fn define_class(loader: &OopRef, cf: ClassFile) -> Result<ClassRef, BytesRef> {
+    let name = constant_pool::get_class_name(&cf.cp, cf.this_class as usize).unwrap();
+    let class = Class::new_class(new_ref!(cf), Some(ClassLoader::Bootstrap));
+    let class = new_sync_ref!(class);
+    if !add_defined(loader, name.as_slice(), &class) {
+        return Err(name);
+    }
+
+    trace_loaded(name.as_slice(), "__JVM_DefineClass__");
+    events::record(EventKind::ClassLoad, || {
+        String::from_utf8_lossy(name.as_slice()).to_string()
+    });
+    link_loaded(class.clone());
+    Ok(class)
+}
+
+//ClassLoader.findLoadedClass0, a class the loader defined
+pub fn find_defined(loader: &OopRef, name: &[u8]) -> Option<ClassRef> {
+    let key = (Arc::as_ptr(loader) as usize, name.to_vec());
+    util::sync_call_ctx(&DEFINED, |it| it.classes.get(&key).map(|it| it.1.clone()))
+}
+
+//false if the loader defined name before
+fn add_defined(loader: &OopRef, name: &[u8], class: &ClassRef) -> bool {
+    let key = (Arc::as_ptr(loader) as usize, name.to_vec());
+    util::sync_call_ctx(&DEFINED, |it| {
+        if it.classes.contains_key(&key) {
+            return false;
         }
+        it.first
+            .entry(key.1.clone())
+            .or_insert_with(|| class.clone());
+        it.classes.insert(key, (loader.clone(), class.clone()));
+        true
+    })
+}
+
+//the first class defined under name, by any loader
+fn find_defined_by_name(name: &[u8]) -> Option<ClassRef> {
+    util::sync_call_ctx(&DEFINED, |it| it.first.get(name).cloned())
+}
+
+/*
+The error of the last require_class of this thread which found the class
+on the class path, but could not parse it: ClassFormatError or
+UnsupportedClassVersionError & the message. None if it was not found or
+did load.
+*/
+pub fn take_load_error() -> Option<(&'static [u8], String)> {
+    LOAD_ERROR.with(|it| it.borrow_mut().take())
+}
+
+//parse & check the format, the error class & message if bad
+pub fn parse_class(buf: &[u8]) -> Result<ClassFile, (&'static [u8], String)> {
+    let cf = class_parser::parse_buf(buf).map_err(parse_error)?;
+    let format_error = |msg| (consts::J_CLASS_FORMAT_ERROR, msg);
+    cf.check_format()
+        .map_err(|e| format_error(format!("{:?}", e)))?;
+    if constant_pool::get_class_name(&cf.cp, cf.this_class as usize).is_none() {
+        return Err(format_error("Invalid this_class index".to_string()));
+    }
+    Ok(cf)
+}
+
+fn parse_error(e: std::io::Error) -> (&'static [u8], String) {
+    match e.kind() {
+        std::io::ErrorKind::Unsupported => (consts::J_UNSUPPORTED_CLASS_VERSION, e.to_string()),
+        _ => (consts::J_CLASS_FORMAT_ERROR, e.to_string()),
+    }
+}
+
+fn enter_class(name: &[u8], class: ClassRef) {
+    runtime::sys_dic_put(name, class.clone());
+    events::record(EventKind::ClassLoad, || {
+        String::from_utf8_lossy(name).to_string()
+    });
+    link_loaded(class);
+}
+
+fn link_loaded(class: ClassRef) {
+    let this_ref = class.clone();
+    util::sync_call_ctx(&class, move |it| {
+        it.set_class_state(oop::class::State::Loaded);
+        it.link_class(this_ref);
+    });
+
+    native::java_lang_Class::create_mirror(class.clone());
+    runtime::metaspace::on_class_load(&class);
+
+    if runtime::flags::is_print_class_layout() {
+        print!("{}", oop::layout::dump(&class));
     }
 }
 
@@ -186,8 +332,198 @@ fn is_array(name: &[u8]) -> bool {
     name.starts_with(&[b'['])
 }
 
+//-verbose:class
+fn trace_loaded(name: &[u8], source: &str) {
+    if runtime::flags::is_trace_class_loading() {
+        let name = String::from_utf8_lossy(name).replace(util::FILE_SEP, ".");
+        println!("[Loaded {} from {}]", name, source);
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::classfile::attr_info::{AttrType, Code};
+    use crate::classfile::constant_pool::ConstantType;
+    use crate::classfile::opcode::OpCode;
+    use crate::oop::method::{Bytecode, Operand};
+
+    //test/asm/fixtures, generated by test/asm/GenFixtures.java
+    fn fixture(name: &str) -> ClassFile {
+        let path = format!(
+            "{}/test/asm/fixtures/{}.class",
+            env!("CARGO_MANIFEST_DIR"),
+            name.replace('/', "_")
+        );
+        let buf = std::fs::read(&path).unwrap();
+        let cf = parse_class(buf.as_slice()).unwrap_or_else(|e| panic!("{}: {}", name, e.1));
+        let this = constant_pool::get_class_name(&cf.cp, cf.this_class as usize).unwrap();
+        assert_eq!(this.as_slice(), name.as_bytes());
+        cf
+    }
+
+    fn codes(cf: &ClassFile) -> Vec<&Code> {
+        cf.methods
+            .iter()
+            .flat_map(|it| it.attrs.iter())
+            .filter_map(|it| match it {
+                AttrType::Code(code) => Some(code),
+                _ => None,
+            })
+            .collect()
+    }
+
+    //every instruction decodes, every jump lands on an instruction
+    fn check_code(name: &str, code: &Code) -> Bytecode {
+        let bc = Bytecode::decode(code.code.as_slice());
+        assert!(bc.err.is_none(), "{}: {:?}", name, bc.err);
+
+        let mut targets = vec![];
+        for it in bc.instrs.iter() {
+            match &it.operand {
+                Operand::Branch(v) => targets.push(*v),
+                Operand::Switch(sw) => {
+                    targets.extend((-8..8).map(|k| sw.target(k)));
+                    targets.extend([i32::MIN, 100_000].iter().map(|&k| sw.target(k)));
+                }
+                _ => (),
+            }
+        }
+        for it in code.exceptions.iter() {
+            targets.push(it.start_pc as i32);
+            targets.push(it.handler_pc as i32);
+        }
+        for target in targets {
+            assert!(bc.get(target).is_some(), "{}: bad target {}", name, target);
+        }
+        bc
+    }
+
+    //classes as bytecode libraries emit them, through the defineClass
+    //front end: parse, format check, decode
+    #[test]
+    fn t_asm_fixtures() {
+        let names = [
+            "asm/Switches",
+            "asm/WideLocals",
+            "asm/BigPool",
+            "asm/Frames",
+            "asm/Indy",
+            "asm/Condy",
+            "asm/Synthetic",
+            "asm/Defaults",
+        ];
+        for name in names.iter() {
+            let cf = fixture(name);
+            for code in codes(&cf) {
+                check_code(name, code);
+            }
+        }
+
+        let has_op = |cf: &ClassFile, op| {
+            codes(cf).iter().any(|code| {
+                let bc = Bytecode::decode(code.code.as_slice());
+                bc.instrs.iter().any(|it| it.op_code == op)
+            })
+        };
+        let cf = fixture("asm/Switches");
+        assert!(has_op(&cf, OpCode::tableswitch) && has_op(&cf, OpCode::lookupswitch));
+
+        let cf = fixture("asm/WideLocals");
+        assert!(codes(&cf)[0].max_locals >= 300);
+        assert!(has_op(&cf, OpCode::iinc) && has_op(&cf, OpCode::istore));
+
+        let cf = fixture("asm/BigPool");
+        assert!(cf.cp_count > 256);
+        assert!(has_op(&cf, OpCode::ldc_w) && has_op(&cf, OpCode::ldc2_w));
+
+        let cf = fixture("asm/Frames");
+        let frames = codes(&cf)[0].attrs.iter().any(|it| match it {
+            AttrType::StackMapTable { entries } => entries.len() == 4,
+            _ => false,
+        });
+        assert!(frames);
+
+        let cf = fixture("asm/Indy");
+        assert!(has_op(&cf, OpCode::invokedynamic));
+        let kinds =
+            |cf: &ClassFile, f: fn(&ConstantType) -> bool| cf.cp.iter().filter(|it| f(it)).count();
+        assert_eq!(
+            kinds(&cf, |it| matches!(it, ConstantType::MethodType { .. })),
+            3
+        );
+        assert!(kinds(&cf, |it| matches!(it, ConstantType::MethodHandle { .. })) >= 2);
+
+        let cf = fixture("asm/Condy");
+        assert_eq!(cf.version.major, 55);
+        assert_eq!(
+            kinds(&cf, |it| matches!(it, ConstantType::Dynamic { .. })),
+            1
+        );
+
+        //"\0" & U+1F600 in modified utf-8, a surrogate pair of 3 bytes each
+        let cf = fixture("asm/Synthetic");
+        let s = [0xc0, 0x80, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80];
+        let found = cf.cp.iter().any(|it| match it {
+            ConstantType::Utf8 { bytes, .. } => bytes.as_slice() == s,
+            _ => false,
+        });
+        assert!(found);
+    }
+
+    #[test]
+    fn t_define_by_loader() {
+        let cf = fixture("asm/Defaults");
+        let cf = new_ref!(cf);
+        let name = b"asm/Defaults";
+        let new_class = || {
+            let class = Class::new_class(cf.clone(), None);
+            new_sync_ref!(class)
+        };
+        let (loader1, loader2) = (OopDesc::new_int(1), OopDesc::new_int(2));
+        let (class1, class2) = (new_class(), new_class());
+
+        assert!(add_defined(&loader1, name, &class1));
+        assert!(!add_defined(&loader1, name, &new_class()));
+        assert!(add_defined(&loader2, name, &class2));
+        assert!(Arc::ptr_eq(&find_defined(&loader1, name).unwrap(), &class1));
+        assert!(Arc::ptr_eq(&find_defined(&loader2, name).unwrap(), &class2));
+        assert!(find_defined(&OopDesc::new_int(3), name).is_none());
+        assert!(Arc::ptr_eq(&find_defined_by_name(name).unwrap(), &class1));
+    }
+
+    #[test]
+    fn t_parse_class_err() {
+        let buf = std::fs::read(format!(
+            "{}/test/asm/fixtures/asm_Defaults.class",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let format_error = |msg: &str| Some((consts::J_CLASS_FORMAT_ERROR, msg.to_string()));
+        assert_eq!(
+            parse_class(&buf[..buf.len() / 2]).err(),
+            format_error("Truncated class file")
+        );
+
+        let mut bad = buf.clone();
+        bad[0] = 0;
+        assert_eq!(
+            parse_class(bad.as_slice()).err(),
+            format_error("Incompatible magic value")
+        );
+
+        //Java 18
+        let mut bad = buf.clone();
+        bad[4..8].copy_from_slice(&[0, 0, 0, 62]);
+        assert_eq!(
+            parse_class(bad.as_slice()).err(),
+            Some((
+                consts::J_UNSUPPORTED_CLASS_VERSION,
+                "Unsupported major.minor version 62.0".to_string()
+            ))
+        );
+    }
+
     #[test]
     fn t_basic() {
         use super::calc_dimension;
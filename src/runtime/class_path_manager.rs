@@ -1,5 +1,6 @@
 use crate::util;
 use bytes::{Buf, Bytes};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, BufReader, Cursor, Read, Seek};
 use std::path::{self, Path};
@@ -8,6 +9,7 @@ use zip::ZipArchive;
 
 lazy_static! {
     static ref CPM: Mutex<ClassPathManager> = { Mutex::new(ClassPathManager::new()) };
+    static ref PROVIDERS: Mutex<Vec<(String, ProviderRef)>> = Mutex::new(Vec::new());
 }
 
 pub fn init() {
@@ -15,7 +17,11 @@ pub fn init() {
 }
 
 pub fn find_class(name: &str) -> Result<ClassPathResult, io::Error> {
-    util::sync_call_ctx(&CPM, |cpm| cpm.search_class(name))
+    let r = util::sync_call_ctx(&CPM, |cpm| cpm.search_class(name));
+    match r {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => search_providers(name).ok_or(e),
+        r => r,
+    }
 }
 
 pub fn add_path(path: &str) {
@@ -26,8 +32,85 @@ pub fn add_paths(path: &str) {
     util::sync_call_ctx(&CPM, |cpm| cpm.add_class_paths(path));
 }
 
+/*
+Class bytes from the embedder, classes generated at runtime by the host
+need no file
+
+  add_provider("gen", |name: &str| generated.get(name).cloned());
+
+A provider is asked for the class file of a name ("demo/Gen$1", '/'
+separated) the class path has not, the first to give bytes wins, in the
+order added. A closure or a HashMap<String, Vec<u8>> of the names. It is
+called without any vm lock held, from any thread, & may be asked again
+for a name it refused, a class generated later is found then. The name
+is the source of the class, for -verbose:class.
+*/
+pub trait ClassProvider: Send + Sync {
+    fn class_bytes(&self, name: &str) -> Option<Vec<u8>>;
+}
+
+impl<F> ClassProvider for F
+where
+    F: Fn(&str) -> Option<Vec<u8>> + Send + Sync,
+{
+    fn class_bytes(&self, name: &str) -> Option<Vec<u8>> {
+        self(name)
+    }
+}
+
+impl ClassProvider for HashMap<String, Vec<u8>> {
+    fn class_bytes(&self, name: &str) -> Option<Vec<u8>> {
+        self.get(name).cloned()
+    }
+}
+
+type ProviderRef = Arc<dyn ClassProvider>;
+
+pub fn add_provider(name: &str, provider: impl ClassProvider + 'static) {
+    let provider: ProviderRef = Arc::new(provider);
+    util::sync_call_ctx(&PROVIDERS, |it| it.push((name.to_string(), provider)));
+}
+
+fn search_providers(name: &str) -> Option<ClassPathResult> {
+    //out of the lock, a provider may add another
+    let providers = util::sync_call_ctx(&PROVIDERS, |it| it.clone());
+    if providers.is_empty() {
+        return None;
+    }
+    let name = name.replace('.', "/");
+    providers.iter().find_map(|(source, it)| {
+        it.class_bytes(&name)
+            .map(|v| ClassPathResult(source.clone(), ClassBytes::Read(v)))
+    })
+}
+
 #[derive(Debug)]
-pub struct ClassPathResult(pub String, pub Vec<u8>);
+pub struct ClassPathResult(pub String, pub ClassBytes);
+
+/*
+A class file in a dir is mapped, parsed straight from the mapping, and
+unmapped when the ClassFile is built. A jar entry is compressed, it is
+inflated into a Vec.
+
+Utf8 constants are still copied out (interned symbols), the ClassFile
+never borrows from the mapping.
+*/
+#[derive(Debug)]
+pub enum ClassBytes {
+    Mapped(util::Mmap),
+    Read(Vec<u8>),
+}
+
+impl std::ops::Deref for ClassBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ClassBytes::Mapped(m) => m,
+            ClassBytes::Read(v) => v.as_slice(),
+        }
+    }
+}
 
 type ZipRef = Arc<Mutex<Box<ZipArchive<File>>>>;
 
@@ -66,7 +149,8 @@ impl ClassPathManager {
     }
 
     pub fn add_class_paths(&mut self, path: &str) {
-        path.split(util::PATH_SEP)
+        expand(path)
+            .iter()
             .for_each(|p| match self.add_class_path(p) {
                 Err(e) => error!("add class path error, path={}, e={:?}", p, e),
                 _ => (),
@@ -86,15 +170,8 @@ impl ClassPathManager {
                     p.push_str(util::FILE_SEP);
                     p.push_str(&name);
                     p.push_str(".class");
-                    match File::open(&p) {
-                        Ok(mut f) => {
-                            let mut v = Vec::with_capacity(f.metadata().unwrap().len() as usize);
-                            f.read_to_end(&mut v);
-
-                            return Ok(ClassPathResult(p, v));
-                        }
-
-                        _ => (),
+                    if let Ok(m) = util::Mmap::open(&p) {
+                        return Ok(ClassPathResult(p, ClassBytes::Mapped(m)));
                     }
                 }
 
@@ -110,7 +187,7 @@ impl ClassPathManager {
                             let mut v = Vec::with_capacity(zf.size() as usize);
                             let r = zf.read_to_end(&mut v);
                             assert!(r.is_ok());
-                            return Ok(ClassPathResult(it.1.clone(), v));
+                            return Ok(ClassPathResult(it.1.clone(), ClassBytes::Read(v)));
                         }
 
                         _ => (),
@@ -130,6 +207,67 @@ impl ClassPathManager {
     }
 }
 
+//A class path, as the java launcher takes it
+//
+//  lib/*   the jars (".jar" or ".JAR") of lib, sub directories are not
+//          searched, sorted by name
+//  *       the jars of the current directory
+//  ""      an empty entry is the current directory
+//
+//Entries are separated by util::PATH_SEP, ':' or ';' by platform, '/' is
+//a file separator on every platform. A '*' anywhere else is literal,
+//"lib/*.jar" is not expanded. Wildcards are expanded once, when the
+//entries are added at vm start.
+fn expand(path: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    for it in path.split(util::PATH_SEP) {
+        if it.is_empty() {
+            entries.push(".".to_string());
+            continue;
+        }
+
+        match wildcard_dir(it) {
+            Some(dir) => entries.extend(jars_in(dir)),
+            None => entries.push(it.to_string()),
+        }
+    }
+    entries
+}
+
+//"lib/" of "lib/*", "." of "*"
+fn wildcard_dir(entry: &str) -> Option<&str> {
+    if entry == "*" {
+        return Some(".");
+    }
+    let dir = entry.strip_suffix('*')?;
+    if dir.ends_with('/') || dir.ends_with(util::FILE_SEP) {
+        Some(dir)
+    } else {
+        None
+    }
+}
+
+fn jars_in(dir: &str) -> Vec<String> {
+    let mut jars: Vec<String> = match std::fs::read_dir(dir) {
+        Ok(rd) => rd
+            .filter_map(|it| it.ok())
+            .map(|it| it.path())
+            .filter(|it| it.is_file())
+            .filter(|it| {
+                it.extension()
+                    .is_some_and(|ext| ext == "jar" || ext == "JAR")
+            })
+            .filter_map(|it| it.to_str().map(|it| it.to_string()))
+            .collect(),
+        Err(e) => {
+            warn!("class path wildcard, dir={}, e={:?}", dir, e);
+            vec![]
+        }
+    };
+    jars.sort();
+    jars
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -171,6 +309,33 @@ mod tests {
         assert_eq!(2, cpm.size());
     }
 
+    #[test]
+    fn t_expand() {
+        let sep = super::util::PATH_SEP;
+        let path = format!("test/classloader/*{}test/{}", sep, sep);
+        assert_eq!(
+            super::expand(&path),
+            vec![
+                "test/classloader/class_path_test.jar",
+                "test/classloader/class_path_test_classpath.jar",
+                "test/",
+                ".",
+            ]
+        );
+        assert_eq!(
+            super::expand("test/classloader/*.jar"),
+            vec!["test/classloader/*.jar"]
+        );
+        assert!(super::expand("test_no_exist/*").is_empty());
+        assert_eq!(super::wildcard_dir("*"), Some("."));
+        assert_eq!(super::wildcard_dir("lib*"), None);
+
+        let mut cpm = super::ClassPathManager::new();
+        cpm.add_class_paths("test/classloader/*");
+        assert_eq!(2, cpm.size());
+        assert!(cpm.search_class("Foo").is_ok());
+    }
+
     #[test]
     fn t_search_cls() {
         let mut cpm = super::ClassPathManager::new();
@@ -178,6 +343,23 @@ mod tests {
         assert!(cpm.search_class("Foo").is_ok());
     }
 
+    #[test]
+    fn t_providers() {
+        let mut generated = super::HashMap::new();
+        generated.insert("gen/Map".to_string(), vec![0xca, 0xfe]);
+        super::add_provider("map", generated);
+        super::add_provider("closure", |name: &str| match name {
+            "gen/Closure" | "gen/Map" => Some(vec![1]),
+            _ => None,
+        });
+
+        let super::ClassPathResult(source, v) = super::find_class("gen/Map").unwrap();
+        assert_eq!((source.as_str(), &*v), ("map", &[0xca, 0xfe][..]));
+        let super::ClassPathResult(source, _) = super::find_class("gen.Closure").unwrap();
+        assert_eq!(source, "closure");
+        assert!(super::find_class("gen/None").is_err());
+    }
+
     #[test]
     fn t_search_cls2() {
         let mut cpm = super::ClassPathManager::new();
@@ -1,7 +1,7 @@
 use crate::classfile;
-use crate::oop;
+use crate::oop::{self, Oop, ValueType};
 use crate::runtime::require_class3;
-use crate::types::ClassRef;
+use crate::types::{ClassRef, OopRef};
 use std::sync::Arc;
 
 pub fn instance_of(s: ClassRef, t: ClassRef) -> bool {
@@ -75,42 +75,15 @@ pub fn instance_of(s: ClassRef, t: ClassRef) -> bool {
                 return Arc::ptr_eq(&t, &object);
             }
 
-            if t_kind == oop::class::ClassKindType::TypAry
-                && s_kind == oop::class::ClassKindType::TypAry
-            {
-                let cls = s.lock().unwrap();
-                let (s_dimension, s_value_type) = match &cls.kind {
-                    oop::class::ClassKind::TypeArray(cls) => (cls.get_dimension(), cls.value_type),
-                    _ => unreachable!(),
-                };
-
-                let cls = t.lock().unwrap();
-                let (t_dimension, t_value_type) = match &cls.kind {
-                    oop::class::ClassKind::TypeArray(cls) => (cls.get_dimension(), cls.value_type),
-                    _ => unreachable!(),
-                };
-                return s_dimension == t_dimension && s_value_type == t_value_type;
-            }
-
-            if t_kind == oop::class::ClassKindType::ObjectAry
-                && s_kind == oop::class::ClassKindType::ObjectAry
-            {
-                let cls = s.lock().unwrap();
-                let (s_dimension, s_component) = match &cls.kind {
-                    oop::class::ClassKind::ObjectArray(cls) => {
-                        (cls.get_dimension(), cls.component.clone().unwrap())
-                    }
-                    _ => unreachable!(),
-                };
-                let cls = t.lock().unwrap();
-                let (t_dimension, t_component) = match &cls.kind {
-                    oop::class::ClassKind::ObjectArray(cls) => {
-                        (cls.get_dimension(), cls.component.clone().unwrap())
-                    }
-                    _ => unreachable!(),
-                };
-                return s_dimension == t_dimension && check_inherit(s_component, t_component);
-            }
+            // If T is an array type TC[], that is, an array of components of type TC,
+            // then one of the following must be true:
+            //   TC and SC are the same primitive type
+            //   TC and SC are reference types, and type SC can be cast to TC
+            return match (array_element(&s), array_element(&t)) {
+                (Element::Prim(s), Element::Prim(t)) => s == t,
+                (Element::Ref(s), Element::Ref(t)) => instance_of(s, t),
+                _ => false,
+            };
         }
         _ => (),
     }
@@ -118,6 +91,42 @@ pub fn instance_of(s: ClassRef, t: ClassRef) -> bool {
     false
 }
 
+enum Element {
+    Prim(ValueType),
+    Ref(ClassRef),
+}
+
+//the component type of an array class, one dimension down:
+//int[][] -> int[], String[] -> String, int[] -> int
+fn array_element(cls: &ClassRef) -> Element {
+    let cls = cls.lock().unwrap();
+    match &cls.kind {
+        oop::class::ClassKind::TypeArray(ary) => match ary.get_down_type() {
+            Some(down) => Element::Ref(down),
+            None => Element::Prim(ary.value_type),
+        },
+        oop::class::ClassKind::ObjectArray(ary) => match ary.get_down_type() {
+            Some(down) => Element::Ref(down),
+            None => Element::Ref(ary.component.clone().unwrap()),
+        },
+        oop::class::ClassKind::Instance(_) => unreachable!(),
+    }
+}
+
+//the class of an object, None for null
+pub fn oop_class(rf: &OopRef) -> Option<ClassRef> {
+    let v = rf.lock().unwrap();
+    match &v.v {
+        Oop::Inst(inst) => Some(inst.class.clone()),
+        Oop::Array(ary) => Some(ary.class.clone()),
+        //TypeArray has no class object, find it by the desc, "[I"...
+        Oop::TypeArray(ary) => require_class3(None, ary.desc()),
+        //a mirror is an instance of java/lang/Class
+        Oop::Mirror(_) => require_class3(None, classfile::consts::J_CLASS),
+        _ => None,
+    }
+}
+
 pub fn check_inherit(s: ClassRef, t: ClassRef) -> bool {
     let mut super_cls = s;
 
@@ -135,3 +144,36 @@ pub fn check_inherit(s: ClassRef, t: ClassRef) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::Class;
+    use crate::runtime::ClassLoader;
+
+    fn prime_ary(value_type: ValueType) -> ClassRef {
+        let cls = Class::new_prime_ary(ClassLoader::Bootstrap, value_type);
+        new_sync_ref!(cls)
+    }
+
+    fn wrapped_ary(down_type: ClassRef) -> ClassRef {
+        let cls = Class::new_wrapped_ary(ClassLoader::Bootstrap, down_type);
+        new_sync_ref!(cls)
+    }
+
+    #[test]
+    fn t_prime_ary_instance_of() {
+        let ints = prime_ary(ValueType::INT);
+        let ints2 = prime_ary(ValueType::INT);
+        let longs = prime_ary(ValueType::LONG);
+        let ints_ints = wrapped_ary(ints.clone());
+        let ints2_ints = wrapped_ary(ints2.clone());
+
+        assert!(instance_of(ints.clone(), ints2.clone()));
+        assert!(!instance_of(ints.clone(), longs.clone()));
+        assert!(!instance_of(ints.clone(), ints_ints.clone()));
+        assert!(!instance_of(ints_ints.clone(), ints.clone()));
+        assert!(instance_of(ints_ints.clone(), ints2_ints));
+        assert!(!instance_of(ints_ints, wrapped_ary(longs)));
+    }
+}
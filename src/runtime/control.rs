@@ -0,0 +1,167 @@
+/*
+Control socket, jcmd for a running vm
+
+  jvm -XX:ControlSocket=/tmp/jvm.sock Main
+  echo Thread.print | nc -U /tmp/jvm.sock
+
+A command a connection: one line in, the answer in text, then closed.
+  help             the commands
+  Thread.print     live threads, their state & java frame depth
+  GC.run           a collection as System.gc, needs -XX:+UseMarkSweepGC
+  VM.flags         the -X options in effect
+  Class.histogram  instances & shallow bytes by class, needs
+                   -XX:+TrackHeapObjects (or -XX:+UseMarkSweepGC)
+
+A unix domain socket, in a background thread. A stale socket file is
+replaced, the new one is for its owner only (0600). Unix only, elsewhere
+the flag is ignored with a warning. As runtime::diag, data comes from
+the vm registries, no java code runs.
+*/
+
+use crate::runtime::{flags, gc, thread};
+
+const COMMANDS: &[(&str, &str)] = &[
+    ("help", "the commands"),
+    (
+        "Thread.print",
+        "live threads, their state & java frame depth",
+    ),
+    ("GC.run", "a collection as System.gc"),
+    ("VM.flags", "the -X options in effect"),
+    ("Class.histogram", "instances & shallow bytes by class"),
+];
+
+#[cfg(unix)]
+pub fn start(path: &str) {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+
+    let _ = std::fs::remove_file(path);
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("control socket: bind {} failed: {}", path, e);
+            return;
+        }
+    };
+    let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+    info!("control socket: listen on {}", path);
+
+    let _ = std::thread::Builder::new()
+        .name("control-socket".to_string())
+        .spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut line = String::new();
+                let answer = match BufReader::new(&stream).read_line(&mut line) {
+                    Ok(_) => execute(line.trim()),
+                    Err(e) => Err(e.to_string()),
+                };
+                let answer = answer.unwrap_or_else(|e| format!("error: {}\n", e));
+                if let Err(e) = stream.write_all(answer.as_bytes()) {
+                    warn!("control socket: {}", e);
+                }
+            }
+        });
+}
+
+#[cfg(not(unix))]
+pub fn start(path: &str) {
+    warn!("control socket: {} ignored, unix only", path);
+}
+
+fn execute(command: &str) -> Result<String, String> {
+    match command {
+        "help" => Ok(help()),
+        "Thread.print" => Ok(thread_print()),
+        "GC.run" => gc_run(),
+        "VM.flags" => Ok(vm_flags()),
+        "Class.histogram" => class_histogram(),
+        "" => Err("no command, try help".to_string()),
+        _ => Err(format!("unknown command {}, try help", command)),
+    }
+}
+
+fn help() -> String {
+    COMMANDS
+        .iter()
+        .map(|(name, desc)| format!("{:<16} {}\n", name, desc))
+        .collect()
+}
+
+fn thread_print() -> String {
+    thread::thread_stats()
+        .iter()
+        .map(|(id, depth, state)| {
+            format!(
+                "#{} {}, {} java frames\n",
+                id,
+                thread::state_name(*state),
+                depth
+            )
+        })
+        .collect()
+}
+
+//as System.gc, soft references kept, the pending ones are handed over at
+//the next safe point
+fn gc_run() -> Result<String, String> {
+    if !flags::is_use_gc() {
+        return Err("the collector is off, -XX:+UseMarkSweepGC".to_string());
+    }
+    let stats = gc::collect();
+    Ok(format!(
+        "live {}, roots {}, freed {}, cleared {}\n",
+        stats.live, stats.roots, stats.freed, stats.cleared
+    ))
+}
+
+fn vm_flags() -> String {
+    let mut s: String = flags::vm_options()
+        .iter()
+        .map(|it| format!("{}\n", it))
+        .collect();
+    s.push_str(&format!("-Xss{}\n", flags::thread_stack_size()));
+    s.push_str(&format!("-Xms{}\n", flags::initial_heap_size()));
+    if let Some(size) = flags::max_heap_size() {
+        s.push_str(&format!("-Xmx{}\n", size));
+    }
+    s
+}
+
+fn class_histogram() -> Result<String, String> {
+    if !flags::is_track_heap_objects() {
+        return Err("objects are not tracked, -XX:+TrackHeapObjects".to_string());
+    }
+    Ok(gc::format_histogram(&gc::histogram()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_execute() {
+        assert!(execute("help").unwrap().contains("Class.histogram"));
+        assert!(execute("VM.flags").unwrap().contains("-Xss"));
+        assert!(execute("Thread.dump").is_err());
+        assert!(execute("").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn t_socket() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+
+        let path = std::env::temp_dir().join(format!("jvm-control-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap();
+        start(path);
+        let mut stream = UnixStream::connect(path).unwrap();
+        stream.write_all(b"help\n").unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+        assert!(answer.contains("Thread.print"));
+        let _ = std::fs::remove_file(path);
+    }
+}
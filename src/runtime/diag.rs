@@ -0,0 +1,224 @@
+/*
+Diagnostics server, built with the "diag-server" feature
+
+  jvm -XX:DiagServerPort=8090 Main
+  curl http://127.0.0.1:8090/metrics
+
+Endpoints, all json:
+  /threads  live threads, their java frame depth & state
+  /heap     gc heap statistics
+  /classes  loaded class names
+  /exceptions  exception path profile
+  /metrics  summary counters
+  /metaspace  class metadata memory, see runtime::metaspace
+  /layout/<name>  fields, vtable, itable & code of a loaded class,
+                  name like java/lang/String
+  /histogram  count & shallow size of the live instances by class, the
+              most bytes first, needs -XX:+TrackHeapObjects
+  /instances/<name>  count & shallow size of the live instances of a
+                  class & its subclasses, needs -XX:+TrackHeapObjects
+
+Serves on 127.0.0.1 only, one request per connection, in a background
+thread. Data comes from the vm registries (sys_dic, gc, handles, threads).
+*/
+
+use crate::oop;
+use crate::runtime::{self, exception, gc, handles, metaspace, thread};
+use crate::util::{self, json_str};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+pub fn start(port: u16) {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("diag server: bind port {} failed: {}", port, e);
+            return;
+        }
+    };
+    info!("diag server: listen on 127.0.0.1:{}", port);
+
+    let _ = std::thread::Builder::new()
+        .name("diag-server".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = handle(stream) {
+                    warn!("diag server: {}", e);
+                }
+            }
+        });
+}
+
+fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    //"GET /heap HTTP/1.1"
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    let (status, body) = if method != "GET" {
+        ("405 Method Not Allowed", error_json("method not allowed"))
+    } else {
+        match route(path) {
+            Some(body) => ("200 OK", body),
+            None => ("404 Not Found", error_json("not found")),
+        }
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn route(path: &str) -> Option<String> {
+    match path {
+        "/threads" => Some(threads_json()),
+        "/heap" => Some(heap_json()),
+        "/classes" => Some(classes_json()),
+        "/exceptions" => Some(exceptions_json()),
+        "/metrics" => Some(metrics_json()),
+        "/metaspace" => Some(metaspace_json()),
+        "/histogram" => Some(histogram_json()),
+        _ => path
+            .strip_prefix("/layout/")
+            .and_then(layout_json)
+            .or_else(|| path.strip_prefix("/instances/").and_then(instances_json)),
+    }
+}
+
+fn threads_json() -> String {
+    let threads: Vec<String> = thread::thread_stats()
+        .iter()
+        .map(|(id, depth, state)| {
+            format!(
+                "{{\"id\":{},\"depth\":{},\"state\":\"{}\"}}",
+                id,
+                depth,
+                thread::state_name(*state)
+            )
+        })
+        .collect();
+    format!("[{}]", threads.join(","))
+}
+
+fn heap_json() -> String {
+    let stats = gc::heap_stats();
+    format!(
+        "{{\"enabled\":{},\"used\":{},\"tracked\":{},\"allocated\":{},\"collections\":{},\
+         \"last\":{{\"live\":{},\"roots\":{},\"freed\":{}}}}}",
+        runtime::flags::is_use_gc(),
+        stats.used,
+        stats.tracked,
+        stats.allocated,
+        stats.collections,
+        stats.last.live,
+        stats.last.roots,
+        stats.last.freed
+    )
+}
+
+fn classes_json() -> String {
+    let mut names = runtime::sys_dic_names();
+    names.sort();
+    let names: Vec<String> = names.iter().map(|it| json_str(it)).collect();
+    format!("[{}]", names.join(","))
+}
+
+fn exceptions_json() -> String {
+    let stats = exception::stats();
+    format!(
+        "{{\"created\":{},\"create_nanos\":{},\"filled\":{},\"fill_nanos\":{},\
+         \"handled\":{},\"handle_nanos\":{},\"fast_thrown\":{}}}",
+        stats.created,
+        stats.create_nanos,
+        stats.filled,
+        stats.fill_nanos,
+        stats.handled,
+        stats.handle_nanos,
+        stats.fast_thrown
+    )
+}
+
+fn metrics_json() -> String {
+    let (globals, weaks) = handles::count();
+    format!(
+        "{{\"classes\":{},\"threads\":{},\"gc_collections\":{},\
+         \"global_handles\":{},\"weak_handles\":{}}}",
+        runtime::sys_dic_names().len(),
+        thread::thread_stats().len(),
+        gc::heap_stats().collections,
+        globals,
+        weaks
+    )
+}
+
+fn metaspace_json() -> String {
+    let stats = metaspace::stats();
+    format!(
+        "{{\"metadata\":{},\"cp\":{},\"symbols\":{},\"code\":{},\"total\":{},\"max\":{}}}",
+        stats.metadata,
+        stats.cp,
+        stats.symbols,
+        stats.code,
+        stats.total(),
+        runtime::flags::max_metaspace_size().unwrap_or(0)
+    )
+}
+
+fn instances_json(name: &str) -> Option<String> {
+    let found = gc::find_instances(name, true)?;
+    Some(format!(
+        "{{\"name\":{},\"tracked\":{},\"count\":{},\"shallow_size\":{}}}",
+        json_str(name),
+        runtime::flags::is_track_heap_objects(),
+        found.count(),
+        found.shallow_size
+    ))
+}
+
+fn histogram_json() -> String {
+    let classes: Vec<String> = gc::histogram()
+        .iter()
+        .map(|it| {
+            format!(
+                "{{\"name\":{},\"count\":{},\"shallow_size\":{}}}",
+                json_str(&util::mutf8::display(it.name.as_slice())),
+                it.count,
+                it.bytes
+            )
+        })
+        .collect();
+    format!(
+        "{{\"tracked\":{},\"classes\":[{}]}}",
+        runtime::flags::is_track_heap_objects(),
+        classes.join(",")
+    )
+}
+
+fn layout_json(name: &str) -> Option<String> {
+    let class = runtime::sys_dic_find(name.replace('.', "/").as_bytes())?;
+    Some(format!(
+        "{{\"name\":{},\"layout\":{}}}",
+        json_str(name),
+        json_str(oop::layout::dump(&class).as_str())
+    ))
+}
+
+fn error_json(msg: &str) -> String {
+    format!("{{\"error\":{}}}", json_str(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn t_route() {
+        assert!(super::route("/metrics").is_some());
+        assert!(super::route("/nothing").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+/*
+Event recording, a light JFR
+
+  jvm -XX:EventLog=vm.evt Main
+  jvm --events-to-json vm.evt
+
+Events are kept in a ring of MAX_EVENTS in memory (the oldest are
+dropped), written to the file when the vm exits.
+
+Recorded: class load, gc, exception thrown & contended monitor enter,
+with the class of the object & the time blocked. There is no JIT, so no
+compilation events.
+
+File format, integers are big endian
+  magic "JEVT", version u8, count u32
+  count * (kind u8, nanos since vm start u64, detail len u32, detail utf8)
+*/
+
+use crate::runtime::flags;
+use crate::util;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Instant;
+
+const MAGIC: &[u8] = b"JEVT";
+const VERSION: u8 = 1;
+const MAX_EVENTS: usize = 64 * 1024;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    ClassLoad = 1,
+    Gc = 2,
+    Exception = 3,
+    MonitorContended = 4,
+}
+
+impl EventKind {
+    fn decode(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(EventKind::ClassLoad),
+            2 => Some(EventKind::Gc),
+            3 => Some(EventKind::Exception),
+            4 => Some(EventKind::MonitorContended),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EventKind::ClassLoad => "ClassLoad",
+            EventKind::Gc => "GC",
+            EventKind::Exception => "Exception",
+            EventKind::MonitorContended => "MonitorContended",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub nanos: u64,
+    pub detail: String,
+}
+
+struct Recorder {
+    start: Instant,
+    events: VecDeque<Event>,
+}
+
+lazy_static! {
+    static ref RECORDER: Mutex<Recorder> = Mutex::new(Recorder {
+        start: Instant::now(),
+        events: VecDeque::new(),
+    });
+}
+
+//detail is built only when recording is on
+pub fn record<F>(kind: EventKind, detail: F)
+where
+    F: FnOnce() -> String,
+{
+    if !flags::is_record_events() {
+        return;
+    }
+
+    let detail = detail();
+    util::sync_call_ctx(&RECORDER, |r| {
+        if r.events.len() == MAX_EVENTS {
+            r.events.pop_front();
+        }
+        let nanos = r.start.elapsed().as_nanos() as u64;
+        r.events.push_back(Event {
+            kind,
+            nanos,
+            detail,
+        });
+    });
+}
+
+//write the recorded events to -XX:EventLog
+pub fn dump() {
+    if let Some(path) = flags::event_log() {
+        let buf = util::sync_call_ctx(&RECORDER, |r| encode(r.events.iter()));
+        if !util::write(&path, buf.as_slice()) {
+            warn!("write event log failed: {}", path);
+        }
+    }
+}
+
+pub fn encode<'a, I>(events: I) -> Vec<u8>
+where
+    I: ExactSizeIterator<Item = &'a Event>,
+{
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.push(VERSION);
+    buf.extend_from_slice(&(events.len() as u32).to_be_bytes());
+    for it in events {
+        buf.push(it.kind as u8);
+        buf.extend_from_slice(&it.nanos.to_be_bytes());
+        buf.extend_from_slice(&(it.detail.len() as u32).to_be_bytes());
+        buf.extend_from_slice(it.detail.as_bytes());
+    }
+    buf
+}
+
+pub fn decode(buf: &[u8]) -> Result<Vec<Event>, String> {
+    let mut reader = Reader { buf, pos: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("not an event log".to_string());
+    }
+    let version = reader.take(1)?[0];
+    if version != VERSION {
+        return Err(format!("unsupported version {}", version));
+    }
+
+    let count = reader.u32()?;
+    let mut events = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let kind = reader.take(1)?[0];
+        let kind = EventKind::decode(kind).ok_or(format!("unknown event kind {}", kind))?;
+        let nanos = reader.u64()?;
+        let len = reader.u32()? as usize;
+        let detail = String::from_utf8_lossy(reader.take(len)?).to_string();
+        events.push(Event {
+            kind,
+            nanos,
+            detail,
+        });
+    }
+
+    Ok(events)
+}
+
+pub fn to_json(events: &[Event]) -> String {
+    let events: Vec<String> = events
+        .iter()
+        .map(|it| {
+            format!(
+                "{{\"kind\":\"{}\",\"nanos\":{},\"detail\":{}}}",
+                it.kind.name(),
+                it.nanos,
+                util::json_str(&it.detail)
+            )
+        })
+        .collect();
+    format!("[{}]", events.join(",\n"))
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        match self.buf.get(self.pos..self.pos + n) {
+            Some(v) => {
+                self.pos += n;
+                Ok(v)
+            }
+            None => Err("truncated event log".to_string()),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let v = self.take(4)?;
+        Ok(u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let v = self.take(8)?;
+        let mut b = [0u8; 8];
+        b.copy_from_slice(v);
+        Ok(u64::from_be_bytes(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_encode_decode() {
+        let events = vec![
+            Event {
+                kind: EventKind::ClassLoad,
+                nanos: 10,
+                detail: "java/lang/Object".to_string(),
+            },
+            Event {
+                kind: EventKind::Gc,
+                nanos: 20,
+                detail: "freed=1".to_string(),
+            },
+            Event {
+                kind: EventKind::MonitorContended,
+                nanos: 30,
+                detail: "java.lang.Object, 12us".to_string(),
+            },
+        ];
+
+        let buf = encode(events.iter());
+        assert_eq!(decode(buf.as_slice()), Ok(events.clone()));
+        assert!(decode(&buf[..buf.len() - 1]).is_err());
+        assert!(decode(b"JVMX").is_err());
+
+        let json = to_json(events.as_slice());
+        assert!(json.starts_with("[{\"kind\":\"ClassLoad\",\"nanos\":10,"));
+    }
+}
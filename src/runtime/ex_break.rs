@@ -0,0 +1,262 @@
+/*
+Exception breakpoints
+
+  jvm -XX:BreakOnException=java/lang/NullPointerException,java/io/IOException Main
+
+The moment an exception of a selected class (or a subclass) is
+constructed, thrown later or not, caught or not, the vm stops and hands
+an ExceptionContext to the exception hook. Construction is seen in
+Throwable.fillInStackTrace, which every Throwable ctor calls, the vm's
+own exceptions included. The preallocated fast-throw instances are not
+constructed again, they don't break.
+
+-XX:BreakOnException adds the filters, embedders & the debugger agent
+add them with add_filter and install a callback with set_hook, otherwise
+the thread pauses in a REPL on stdin:
+  c, <enter>   continue
+  p            print the java stack
+  d            delete this breakpoint, continue
+  q            quit the vm
+
+The hook is called with no lock of the registry held, it may add or
+remove filters & set another hook. The exceptions constructed by the
+hook itself don't break.
+*/
+
+use crate::prelude::Value;
+use crate::runtime::JavaThread;
+use crate::types::{ClassRef, OopRef};
+use crate::util;
+use std::cell::Cell;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub struct ExceptionContext<'a> {
+    pub exception: &'a Value,
+    //"java/lang/NullPointerException"
+    pub class: &'a str,
+    //the filter which matched, the class itself or a super class
+    pub filter: &'a str,
+    //"Main.foo()V", the first frame out of the exception ctors
+    pub site: &'a str,
+    //java frames, innermost first
+    pub frames: &'a [String],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExAction {
+    Continue,
+    //remove the matched filter
+    Delete,
+}
+
+pub type ExHook = Box<dyn FnMut(&ExceptionContext) -> ExAction + Send>;
+
+//some filter is set, checked before locking FILTERS
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref FILTERS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    static ref HOOK: Mutex<Option<Arc<Mutex<ExHook>>>> = Mutex::new(None);
+}
+
+thread_local! {
+    //the hook or the REPL runs on this thread
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+//class name like "java/lang/NullPointerException", '.' is accepted too
+pub fn add_filter(name: &str) {
+    let name = name.replace('.', "/");
+    util::sync_call_ctx(&FILTERS, |filters| {
+        if !filters.contains(&name) {
+            filters.push(name);
+        }
+        ENABLED.store(true, Ordering::Relaxed);
+    });
+}
+
+pub fn remove_filter(name: &str) {
+    let name = name.replace('.', "/");
+    util::sync_call_ctx(&FILTERS, |filters| {
+        filters.retain(|it| *it != name);
+        ENABLED.store(!filters.is_empty(), Ordering::Relaxed);
+    });
+}
+
+pub fn filters() -> Vec<String> {
+    util::sync_call_ctx(&FILTERS, |filters| filters.clone())
+}
+
+pub fn set_hook(hook: ExHook) {
+    util::sync_call_ctx(&HOOK, |h| *h = Some(Arc::new(Mutex::new(hook))));
+}
+
+//called by Throwable.fillInStackTrace, ex is being constructed
+pub fn on_construct(jt: &JavaThread, ex: &OopRef, class: &ClassRef) {
+    if !ENABLED.load(Ordering::Relaxed) || IN_HOOK.with(|it| it.get()) {
+        return;
+    }
+
+    let names = class_names(class);
+    let filter = util::sync_call_ctx(&FILTERS, |filters| {
+        names.iter().find(|it| filters.contains(it)).cloned()
+    });
+    let filter = match filter {
+        Some(filter) => filter,
+        None => return,
+    };
+
+    let frames: Vec<String> = jt
+        .callers
+        .iter()
+        .rev()
+        .map(|mir| {
+            let cls = mir.method.class.lock().unwrap();
+            format!(
+                "{}.{}{}",
+                String::from_utf8_lossy(cls.name.as_slice()),
+                String::from_utf8_lossy(mir.method.name.as_slice()),
+                String::from_utf8_lossy(mir.method.desc.as_slice())
+            )
+        })
+        .collect();
+    let site = frames
+        .iter()
+        .find(|it| !is_construction_frame(it))
+        .cloned()
+        .unwrap_or_default();
+
+    let ctx = ExceptionContext {
+        exception: &Value::new(ex.clone()),
+        class: names[0].as_str(),
+        filter: filter.as_str(),
+        site: site.as_str(),
+        frames: frames.as_slice(),
+    };
+    //out of the lock, the hook is free to set another
+    let hook = util::sync_call_ctx(&HOOK, |h| h.clone());
+    IN_HOOK.with(|it| it.set(true));
+    let action = match hook {
+        Some(hook) => {
+            let mut hook = hook.lock().unwrap();
+            (*hook)(&ctx)
+        }
+        None => repl(&ctx),
+    };
+    IN_HOOK.with(|it| it.set(false));
+
+    if action == ExAction::Delete {
+        remove_filter(&filter);
+    }
+}
+
+//the class & its super classes, the class first
+fn class_names(class: &ClassRef) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut class = Some(class.clone());
+    while let Some(cls) = class {
+        let cls = cls.lock().unwrap();
+        names.push(String::from_utf8_lossy(cls.name.as_slice()).to_string());
+        class = cls.super_class.clone();
+    }
+    names
+}
+
+//Throwable.fillInStackTrace & the ctors of the exception classes
+fn is_construction_frame(frame: &str) -> bool {
+    frame.starts_with("java/lang/Throwable.fillInStackTrace(") || frame.contains(".<init>(")
+}
+
+fn repl(ctx: &ExceptionContext) -> ExAction {
+    println!(
+        "[exception] {} (break on {}) at {}",
+        ctx.class, ctx.filter, ctx.site
+    );
+
+    let stdin = io::stdin();
+    loop {
+        print!("(exception) ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            //stdin closed
+            Ok(0) | Err(_) => return ExAction::Continue,
+            Ok(_) => (),
+        }
+
+        match line.trim() {
+            "" | "c" => return ExAction::Continue,
+            "d" => return ExAction::Delete,
+            "p" => ctx.frames.iter().for_each(|it| println!("  {}", it)),
+            "q" => std::process::exit(0),
+            cmd => println!("unknown command '{}', use c, p, d or q", cmd),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //the filters are global, tests adding some run one at a time
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn t_filters() {
+        let _serial = SERIAL.lock().unwrap();
+        add_filter("java.io.IOException");
+        add_filter("java/io/IOException");
+        assert_eq!(filters(), vec!["java/io/IOException".to_string()]);
+        assert!(ENABLED.load(Ordering::Relaxed));
+        remove_filter("java/io/IOException");
+        assert!(filters().is_empty());
+        assert!(!ENABLED.load(Ordering::Relaxed));
+    }
+
+    //a hook changing the filters & the hook, no deadlock
+    #[test]
+    fn t_hook_reenters() {
+        use crate::oop::{Class, OopDesc, ValueType};
+        use crate::runtime::ClassLoader;
+        use std::sync::mpsc;
+
+        let _serial = SERIAL.lock().unwrap();
+        let cls = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::INT);
+        let cls = new_sync_ref!(cls);
+        let ex = OopDesc::new_int(0);
+        let jt = JavaThread::new();
+
+        let (tx, rx) = mpsc::channel();
+        set_hook(Box::new(move |ctx| {
+            tx.send(ctx.filter.to_string()).unwrap();
+            add_filter("java/lang/Error");
+            remove_filter("java/lang/Error");
+            set_hook(Box::new(|_| ExAction::Continue));
+            ExAction::Delete
+        }));
+        add_filter("[I");
+        on_construct(&jt, &ex, &cls);
+        assert_eq!(rx.try_recv(), Ok("[I".to_string()));
+        assert!(filters().is_empty());
+
+        //the second hook is set, the first is not called again
+        add_filter("[I");
+        on_construct(&jt, &ex, &cls);
+        assert!(rx.try_recv().is_err());
+        remove_filter("[I");
+    }
+
+    #[test]
+    fn t_construction_frame() {
+        assert!(is_construction_frame(
+            "java/lang/Throwable.fillInStackTrace(I)Ljava/lang/Throwable;"
+        ));
+        assert!(is_construction_frame(
+            "java/lang/NullPointerException.<init>()V"
+        ));
+        assert!(!is_construction_frame("Main.main([Ljava/lang/String;)V"));
+    }
+}
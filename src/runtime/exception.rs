@@ -1,19 +1,29 @@
-use crate::oop::{self, OopDesc};
-use crate::runtime::{self, require_class3, JavaThread};
-use crate::types::{BytesRef, OopRef};
+use crate::classfile::consts::{
+    J_ARRAY_INDEX_OUT_OF_BOUNDS, J_ERROR, J_EXCEPTION_IN_INITIALIZER, J_NPE, J_OOM, J_SOE,
+    J_THROWABLE,
+};
+use crate::oop::{self, Oop, OopDesc, TypeArrayValue};
+use crate::runtime::{self, flags, require_class3, JavaThread};
+use crate::types::{BytesRef, ClassRef, FieldIdRef, MethodIdRef, OopRef};
 use crate::util;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+//implicit exceptions a site throws before it gets the preallocated one
+const FAST_THROW_LIMIT: usize = 100;
+const FAST_THROW_CLASSES: &[&[u8]] = &[J_NPE, J_ARRAY_INDEX_OUT_OF_BOUNDS];
 
 pub fn new(jt: &mut JavaThread, name: &[u8], msg: Option<String>) -> OopRef {
+    let start = Instant::now();
     let cls = match require_class3(None, name) {
         Some(cls) => cls,
         None => panic!("ClassNotFound: {}", String::from_utf8_lossy(name)),
     };
 
-    {
-        let mut cls = cls.lock().unwrap();
-        cls.init_class(jt);
-        //                trace!("finish init_class: {}", String::from_utf8_lossy(*c));
-    }
     oop::class::init_class_fully(jt, cls.clone());
 
     let ex = OopDesc::new_inst(cls.clone());
@@ -33,5 +43,683 @@ pub fn new(jt: &mut JavaThread, name: &[u8], msg: Option<String>) -> OopRef {
         }
     }
 
+    //the ctor threw, StackOverflowError near the end of the stack or
+    //OutOfMemoryError, that one is thrown instead, as hotspot does
+    if let Some(thrown) = jt.take_ex() {
+        return thrown;
+    }
+
+    CREATED.fetch_add(1, Ordering::Relaxed);
+    CREATE_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    ex
+}
+
+/*
+Implicit exceptions, raised by the interpreter (null checks, array bounds)
+
+A site (method, bci) which threw an NPE or AIOOBE more than
+FAST_THROW_LIMIT times throws a preallocated instance of the class
+instead, without message & stack trace, like hotspot does for compiled
+code. Saves the ctor and the fillInStackTrace walk on hot exception
+paths. -XX:-OmitStackTraceInFastThrow turns it off.
+
+The preallocated instance is shared by all sites, the last site which
+threw it is kept (rethrow metadata), see fast_throw_site.
+*/
+pub fn new_implicit(jt: &mut JavaThread, name: &[u8], msg: Option<String>) -> OopRef {
+    if flags::is_omit_stack_trace_in_fast_throw()
+        && FAST_THROW_CLASSES.contains(&name)
+        && is_hot_site(jt)
+    {
+        FAST_THROWN.fetch_add(1, Ordering::Relaxed);
+        let ex = preallocated(jt, name, None);
+        if let Some(mir) = jt.callers.last() {
+            let site = (mir.clone(), jt.bci);
+            util::sync_call_ctx(&FAST_THROW, |ft| ft.last_site.insert(name.to_vec(), site));
+        }
+        return ex;
+    }
+
+    new(jt, name, msg)
+}
+
+//the bounds check of every array access, by the interpreter, the natives
+//& the intrinsics: the index, or the ArrayIndexOutOfBoundsException
+pub fn check_index(jt: &mut JavaThread, len: usize, index: i32) -> Result<usize, OopRef> {
+    match in_bounds(len, index, 1) {
+        Some(r) => Ok(r.start),
+        None => Err(new_array_index(jt, index, index.to_string())),
+    }
+}
+
+//n elements from off, as System.arraycopy & the natives taking (b, off,
+//len) check them, hotspot 8 throws with no message
+pub fn check_range(
+    jt: &mut JavaThread,
+    len: usize,
+    off: i32,
+    n: i32,
+) -> Result<Range<usize>, OopRef> {
+    in_bounds(len, off, n).ok_or_else(|| new(jt, J_ARRAY_INDEX_OUT_OF_BOUNDS, None))
+}
+
+//off..off + n, if it's within an array of len
+pub fn in_bounds(len: usize, off: i32, n: i32) -> Option<Range<usize>> {
+    if off < 0 || n < 0 {
+        return None;
+    }
+    let (off, n) = (off as usize, n as usize);
+    match off.checked_add(n) {
+        Some(end) if end <= len => Some(off..end),
+        _ => None,
+    }
+}
+
+//ArrayIndexOutOfBoundsException of index. If the class library's exception
+//has an int 'index' field (JDK 9+), it is set too, but not on a
+//preallocated instance, which sites share
+pub fn new_array_index(jt: &mut JavaThread, index: i32, msg: String) -> OopRef {
+    let ex = new_implicit(jt, J_ARRAY_INDEX_OUT_OF_BOUNDS, Some(msg));
+
+    let is_preallocated = util::sync_call_ctx(&FAST_THROW, |ft| {
+        ft.preallocated.values().any(|it| Arc::ptr_eq(it, &ex))
+    });
+    if !is_preallocated {
+        let cls = require_class3(None, J_ARRAY_INDEX_OUT_OF_BOUNDS).unwrap();
+        if let Some((cls, id)) = find_inst_field(cls, b"index", b"I") {
+            let cls = cls.lock().unwrap();
+            cls.put_field_value(ex.clone(), id, OopDesc::new_int(index));
+        }
+    }
     ex
 }
+
+//the instance field declared by cls or a super class, None if there is none
+fn find_inst_field(cls: ClassRef, name: &[u8], desc: &[u8]) -> Option<(ClassRef, FieldIdRef)> {
+    let mut cls = Some(cls);
+    while let Some(it) = cls {
+        let found = {
+            let c = it.lock().unwrap();
+            let id = util::new_field_id(c.name.as_slice(), name, desc);
+            match &c.kind {
+                oop::class::ClassKind::Instance(cls_obj) => cls_obj.inst_fields.get(&id).cloned(),
+                _ => None,
+            }
+        };
+        if let Some(id) = found {
+            return Some((it, id));
+        }
+        cls = it.lock().unwrap().super_class.clone();
+    }
+    None
+}
+
+fn preallocated_key(name: &[u8], msg: Option<&str>) -> Vec<u8> {
+    match msg {
+        Some(msg) => [name, b": ", msg.as_bytes()].concat(),
+        None => name.to_vec(),
+    }
+}
+
+//the preallocated StackOverflowError, made by init_vm, there may be no
+//stack left to run a ctor when it is thrown
+pub fn stack_overflow(jt: &mut JavaThread) -> OopRef {
+    preallocated(jt, J_SOE, None)
+}
+
+//the messages of the preallocated OutOfMemoryErrors
+pub const OOM_JAVA_HEAP: &str = "Java heap space";
+pub const OOM_METASPACE: &str = "Metaspace";
+pub const OOM_ARRAY_SIZE: &str = "Requested array size exceeds VM limit";
+
+//a preallocated OutOfMemoryError, no memory may be left to construct one
+//when it is thrown. Shared, without stack trace, as hotspot's
+pub fn out_of_memory(jt: &mut JavaThread, msg: &str) -> OopRef {
+    preallocated(jt, J_OOM, Some(msg))
+}
+
+//made by init_vm, while there is stack & memory
+pub fn preallocate_errors(jt: &mut JavaThread) {
+    let _ = stack_overflow(jt);
+    for msg in [OOM_JAVA_HEAP, OOM_METASPACE, OOM_ARRAY_SIZE] {
+        let _ = out_of_memory(jt, msg);
+    }
+}
+
+//<clinit> threw ex: an Error is rethrown as is, anything else is wrapped
+//in ExceptionInInitializerError, JVMS 5.5
+pub fn new_init_error(jt: &mut JavaThread, ex: OopRef) -> OopRef {
+    let cls = {
+        let v = ex.lock().unwrap();
+        match &v.v {
+            Oop::Inst(inst) => inst.class.clone(),
+            t => unreachable!("t = {:?}", t),
+        }
+    };
+    let error_cls = require_class3(None, J_ERROR).unwrap();
+    if runtime::cmp::instance_of(cls, error_cls) {
+        return ex;
+    }
+
+    let cls = oop::class::load_and_init(jt, J_EXCEPTION_IN_INITIALIZER);
+    let error = OopDesc::new_inst(cls.clone());
+    let args = vec![error.clone(), ex];
+    runtime::java_call::invoke_ctor(jt, cls, b"(Ljava/lang/Throwable;)V", args);
+    error
+}
+
+struct FastThrow {
+    //(method, bci) -> implicit exceptions thrown
+    sites: HashMap<(usize, i32), usize>,
+    preallocated: HashMap<Vec<u8>, OopRef>,
+    //class name -> the last site which threw the preallocated instance
+    last_site: HashMap<Vec<u8>, (MethodIdRef, i32)>,
+}
+
+lazy_static! {
+    static ref FAST_THROW: Mutex<FastThrow> = Mutex::new(FastThrow {
+        sites: HashMap::new(),
+        preallocated: HashMap::new(),
+        last_site: HashMap::new(),
+    });
+}
+
+fn is_hot_site(jt: &JavaThread) -> bool {
+    let method = match jt.callers.last() {
+        Some(mir) => Arc::as_ptr(mir) as usize,
+        None => return false,
+    };
+    util::sync_call_ctx(&FAST_THROW, |ft| {
+        let n = ft.sites.entry((method, jt.bci)).or_insert(0);
+        *n += 1;
+        *n > FAST_THROW_LIMIT
+    })
+}
+
+//one instance per class & message, "java/lang/OutOfMemoryError: Metaspace"
+//is kept apart from "java/lang/OutOfMemoryError: Java heap space"
+fn preallocated(jt: &mut JavaThread, name: &[u8], msg: Option<&str>) -> OopRef {
+    let key = preallocated_key(name, msg);
+    if let Some(ex) = util::sync_call_ctx(&FAST_THROW, |ft| ft.preallocated.get(&key).cloned()) {
+        return ex;
+    }
+
+    //the ctor runs java code, don't hold the lock
+    let ex = new(jt, name, msg.map(|it| it.to_string()));
+    let throwable_cls = require_class3(None, J_THROWABLE).unwrap();
+    {
+        let cls = throwable_cls.lock().unwrap();
+        let id = cls.get_field_id(b"backtrace", b"Ljava/lang/Object;", false);
+        cls.put_field_value(ex.clone(), id, oop::consts::get_null());
+    }
+
+    util::sync_call_ctx(&FAST_THROW, |ft| {
+        ft.preallocated.entry(key).or_insert(ex).clone()
+    })
+}
+
+//"java/lang/Foo.bar:()V bci=3", if ex is a preallocated instance
+pub fn fast_throw_site(ex: &OopRef) -> Option<String> {
+    util::sync_call_ctx(&FAST_THROW, |ft| {
+        let (name, _) = ft.preallocated.iter().find(|(_, it)| Arc::ptr_eq(it, ex))?;
+        let (mir, bci) = ft.last_site.get(name)?;
+        let cls_name = mir.method.class.lock().unwrap().name.clone();
+        Some(format!(
+            "{}.{} bci={}",
+            String::from_utf8_lossy(cls_name.as_slice()),
+            String::from_utf8_lossy(mir.method.get_id().as_slice()),
+            bci
+        ))
+    })
+}
+
+/*
+Backtraces, captured by Throwable.fillInStackTrace
+
+The java frames at construction, innermost first, kept in
+Throwable.backtrace as a long[] of method index << 32 | bci, the methods
+interned in BACKTRACE_METHODS (classes are never unloaded). The
+fillInStackTrace frames & the ctors of the exception's own classes are
+skipped, as hotspot does. getStackTraceDepth & getStackTraceElement make
+the StackTraceElements on demand, the line from Method::get_line_num,
+most exceptions are caught before anyone looks at them.
+*/
+struct BacktraceMethods {
+    methods: Vec<MethodIdRef>,
+    index: HashMap<usize, usize>,
+}
+
+lazy_static! {
+    static ref BACKTRACE_METHODS: Mutex<BacktraceMethods> = Mutex::new(BacktraceMethods {
+        methods: Vec::new(),
+        index: HashMap::new(),
+    });
+}
+
+//the backtrace of ex_cls being constructed on jt
+pub fn capture_backtrace(jt: &JavaThread, ex_cls: &ClassRef) -> Vec<i64> {
+    let frames = jt.backtrace();
+    let skip = {
+        let names: Vec<(BytesRef, BytesRef)> = frames
+            .iter()
+            .map(|(mir, _)| {
+                let cls = mir.method.class.lock().unwrap().name.clone();
+                (cls, mir.method.name.clone())
+            })
+            .collect();
+        construction_frames(names.as_slice(), class_names(ex_cls).as_slice())
+    };
+
+    util::sync_call_ctx(&BACKTRACE_METHODS, |bm| {
+        let BacktraceMethods { methods, index } = bm;
+        frames[skip..]
+            .iter()
+            .map(|(mir, bci)| {
+                let key = Arc::as_ptr(mir) as usize;
+                let i = *index.entry(key).or_insert_with(|| {
+                    methods.push(mir.clone());
+                    methods.len() - 1
+                });
+                ((i as i64) << 32) | (*bci as u32 as i64)
+            })
+            .collect()
+    })
+}
+
+//(method, bci) of a backtrace entry
+pub fn backtrace_frame(code: i64) -> Option<(MethodIdRef, i32)> {
+    let mir = util::sync_call_ctx(&BACKTRACE_METHODS, |bm| {
+        bm.methods.get((code >> 32) as usize).cloned()
+    })?;
+    Some((mir, code as u32 as i32))
+}
+
+//StackTraceElement.lineNumber, -2 for a native method, -1 if unknown
+pub fn line_number(mir: &MethodIdRef, bci: i32) -> i32 {
+    if mir.method.is_native() {
+        return -2;
+    }
+    match mir.method.get_line_num(bci as u16) {
+        Some(n) => n as i32,
+        None => -1,
+    }
+}
+
+//leading frames of the construction: fillInStackTrace, then the ctors of
+//the exception class & its super classes
+fn construction_frames(frames: &[(BytesRef, BytesRef)], ex_classes: &[BytesRef]) -> usize {
+    let fill = frames
+        .iter()
+        .take_while(|(cls, name)| {
+            cls.as_slice() == J_THROWABLE && name.as_slice() == b"fillInStackTrace"
+        })
+        .count();
+    let ctors = frames[fill..]
+        .iter()
+        .take_while(|(cls, name)| name.as_slice() == b"<init>" && ex_classes.contains(cls))
+        .count();
+    fill + ctors
+}
+
+//the class & its super classes
+fn class_names(cls: &ClassRef) -> Vec<BytesRef> {
+    let mut names = Vec::new();
+    let mut cls = Some(cls.clone());
+    while let Some(it) = cls {
+        let it = it.lock().unwrap();
+        names.push(it.name.clone());
+        cls = it.super_class.clone();
+    }
+    names
+}
+
+/*
+Exception path profile
+
+  create: exception::new, includes the ctor & Throwable.fillInStackTrace
+  fill:   Throwable.fillInStackTrace alone
+  handle: Frame::try_handle_exception, the handler table search, per frame
+*/
+static CREATED: AtomicU64 = AtomicU64::new(0);
+static CREATE_NANOS: AtomicU64 = AtomicU64::new(0);
+static FILLED: AtomicU64 = AtomicU64::new(0);
+static FILL_NANOS: AtomicU64 = AtomicU64::new(0);
+static HANDLED: AtomicU64 = AtomicU64::new(0);
+static HANDLE_NANOS: AtomicU64 = AtomicU64::new(0);
+static FAST_THROWN: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExStats {
+    pub created: u64,
+    pub create_nanos: u64,
+    pub filled: u64,
+    pub fill_nanos: u64,
+    pub handled: u64,
+    pub handle_nanos: u64,
+    pub fast_thrown: u64,
+}
+
+pub fn record_fill(start: Instant) {
+    FILLED.fetch_add(1, Ordering::Relaxed);
+    FILL_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+}
+
+pub fn record_handle(start: Instant) {
+    HANDLED.fetch_add(1, Ordering::Relaxed);
+    HANDLE_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+}
+
+pub fn stats() -> ExStats {
+    ExStats {
+        created: CREATED.load(Ordering::Relaxed),
+        create_nanos: CREATE_NANOS.load(Ordering::Relaxed),
+        filled: FILLED.load(Ordering::Relaxed),
+        fill_nanos: FILL_NANOS.load(Ordering::Relaxed),
+        handled: HANDLED.load(Ordering::Relaxed),
+        handle_nanos: HANDLE_NANOS.load(Ordering::Relaxed),
+        fast_thrown: FAST_THROWN.load(Ordering::Relaxed),
+    }
+}
+
+//-XX:+PrintExceptionStats
+pub fn print_stats() {
+    if flags::is_print_ex_stats() {
+        let stats = stats();
+        eprintln!("Exception stats:");
+        eprintln!(
+            "  created:     {:>8} {:>12} ns",
+            stats.created, stats.create_nanos
+        );
+        eprintln!(
+            "  filled:      {:>8} {:>12} ns",
+            stats.filled, stats.fill_nanos
+        );
+        eprintln!(
+            "  handled:     {:>8} {:>12} ns",
+            stats.handled, stats.handle_nanos
+        );
+        eprintln!("  fast thrown: {:>8}", stats.fast_thrown);
+    }
+}
+
+/*
+Print ex & its causes to stderr, like Throwable.printStackTrace
+
+Done by the vm, without running java code, used when the java level
+handler (Thread.dispatchUncaughtException) is unavailable or failed
+*/
+pub fn print_stack_trace(ex: OopRef) {
+    eprint!("{}", render_stack_trace(ex));
+}
+
+//as Throwable.printStackTrace writes it
+pub fn render_stack_trace(ex: OopRef) -> String {
+    let mut buf = String::new();
+    let throwable_cls = require_class3(None, J_THROWABLE).unwrap();
+    let mut seen: Vec<OopRef> = Vec::new();
+    let mut enclosing: Vec<String> = Vec::new();
+    let mut caption = "";
+    let mut ex = ex;
+
+    loop {
+        let desc = to_string(throwable_cls.clone(), ex.clone());
+        if seen.iter().any(|it| Arc::ptr_eq(it, &ex)) {
+            let _ = writeln!(buf, "{}[CIRCULAR REFERENCE:{}]", caption, desc);
+            break;
+        }
+        seen.push(ex.clone());
+
+        //frames in common with the enclosing trace are folded into "... n more"
+        let trace = stack_trace(throwable_cls.clone(), ex.clone());
+        let in_common = trace
+            .iter()
+            .rev()
+            .zip(enclosing.iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let _ = writeln!(buf, "{}{}", caption, desc);
+        if let Some(site) = fast_throw_site(&ex) {
+            let _ = writeln!(
+                buf,
+                "\t<stack trace omitted in fast throw, last at {}>",
+                site
+            );
+        }
+        for it in &trace[..trace.len() - in_common] {
+            let _ = writeln!(buf, "\tat {}", it);
+        }
+        if in_common != 0 {
+            let _ = writeln!(buf, "\t... {} more", in_common);
+        }
+
+        match cause(throwable_cls.clone(), ex.clone()) {
+            Some(cause) => ex = cause,
+            None => break,
+        }
+        enclosing = trace;
+        caption = "Caused by: ";
+    }
+
+    buf
+}
+
+fn to_string(throwable_cls: ClassRef, ex: OopRef) -> String {
+    let name = {
+        let v = ex.lock().unwrap();
+        match &v.v {
+            Oop::Inst(inst) => inst.class.lock().unwrap().name.clone(),
+            _ => unreachable!(),
+        }
+    };
+    let name = String::from_utf8_lossy(name.as_slice()).replace("/", ".");
+
+    let msg = {
+        let cls = throwable_cls.lock().unwrap();
+        let id = cls.get_field_id(b"detailMessage", b"Ljava/lang/String;", false);
+        cls.get_field_value(ex, id)
+    };
+
+    if is_null(&msg) {
+        name
+    } else {
+        format!("{}: {}", name, util::oop::extract_str(msg))
+    }
+}
+
+//the StackTraceElements if the java side set or made them, the backtrace
+//otherwise
+fn stack_trace(throwable_cls: ClassRef, ex: OopRef) -> Vec<String> {
+    let (stack_trace, backtrace) = {
+        let cls = throwable_cls.lock().unwrap();
+        let id = cls.get_field_id(b"stackTrace", b"[Ljava/lang/StackTraceElement;", false);
+        let stack_trace = cls.get_field_value(ex.clone(), id);
+        let id = cls.get_field_id(b"backtrace", b"Ljava/lang/Object;", false);
+        (stack_trace, cls.get_field_value(ex, id))
+    };
+
+    let elms = {
+        let v = stack_trace.lock().unwrap();
+        match &v.v {
+            Oop::Array(ary) => ary.elements.clone(),
+            _ => vec![],
+        }
+    };
+    if !elms.is_empty() {
+        return elms.into_iter().map(stack_trace_element).collect();
+    }
+
+    let codes = {
+        let v = backtrace.lock().unwrap();
+        match &v.v {
+            Oop::TypeArray(TypeArrayValue::Long(ary)) => ary.to_vec(),
+            _ => vec![],
+        }
+    };
+    codes
+        .into_iter()
+        .filter_map(backtrace_frame)
+        .map(|(mir, bci)| backtrace_element(&mir, bci))
+        .collect()
+}
+
+//a backtrace frame, as stack_trace_element
+fn backtrace_element(mir: &MethodIdRef, bci: i32) -> String {
+    let cls_name = mir.method.class.lock().unwrap().name.clone();
+    let file_name = mir
+        .method
+        .src_file
+        .as_ref()
+        .map(|it| String::from_utf8_lossy(it.as_slice()).to_string());
+    let location = location(file_name, line_number(mir, bci));
+    format!(
+        "{}.{}({})",
+        String::from_utf8_lossy(cls_name.as_slice()).replace("/", "."),
+        String::from_utf8_lossy(mir.method.name.as_slice()),
+        location
+    )
+}
+
+//same format as StackTraceElement.toString
+fn stack_trace_element(elm: OopRef) -> String {
+    let cls = {
+        let v = elm.lock().unwrap();
+        match &v.v {
+            Oop::Inst(inst) => inst.class.clone(),
+            _ => unreachable!(),
+        }
+    };
+
+    let (declaring_class, method_name, file_name, line_number) = {
+        let cls = cls.lock().unwrap();
+        let str_field = |name: &[u8]| {
+            let id = cls.get_field_id(name, b"Ljava/lang/String;", false);
+            let v = cls.get_field_value(elm.clone(), id);
+            if is_null(&v) {
+                None
+            } else {
+                Some(util::oop::extract_str(v))
+            }
+        };
+        let id = cls.get_field_id(b"lineNumber", b"I", false);
+        let line_number = util::oop::extract_int(cls.get_field_value(elm.clone(), id));
+
+        (
+            str_field(b"declaringClass").unwrap_or_default(),
+            str_field(b"methodName").unwrap_or_default(),
+            str_field(b"fileName").filter(|it| !it.is_empty()),
+            line_number,
+        )
+    };
+
+    let location = location(file_name, line_number);
+
+    format!(
+        "{}.{}({})",
+        declaring_class.replace("/", "."),
+        method_name,
+        location
+    )
+}
+
+fn location(file_name: Option<String>, line_number: i32) -> String {
+    match (file_name, line_number) {
+        (_, -2) => "Native Method".to_string(),
+        (Some(file_name), n) if n > 0 => format!("{}:{}", file_name, n),
+        (Some(file_name), _) => file_name,
+        (None, _) => "Unknown Source".to_string(),
+    }
+}
+
+//Throwable.cause refers to itself, until initCause is called
+fn cause(throwable_cls: ClassRef, ex: OopRef) -> Option<OopRef> {
+    let cause = {
+        let cls = throwable_cls.lock().unwrap();
+        let id = cls.get_field_id(b"cause", b"Ljava/lang/Throwable;", false);
+        cls.get_field_value(ex.clone(), id)
+    };
+
+    if is_null(&cause) || Arc::ptr_eq(&cause, &ex) {
+        None
+    } else {
+        Some(cause)
+    }
+}
+
+//Throwable.initCause without its checks, for an ex the vm just created
+pub fn set_cause(ex: &OopRef, cause: OopRef) {
+    let cls = require_class3(None, J_THROWABLE).unwrap();
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(b"cause", b"Ljava/lang/Throwable;", false);
+    cls.put_field_value(ex.clone(), id, cause);
+}
+
+fn is_null(v: &OopRef) -> bool {
+    let v = v.lock().unwrap();
+    matches!(v.v, Oop::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_construction_frames() {
+        let b = |it: &str| Arc::new(Box::new(it.as_bytes().to_vec()));
+        let frame = |cls: &str, name: &str| (b(cls), b(name));
+        let ex_classes = vec![
+            b("Main$MyEx"),
+            b("java/lang/Exception"),
+            b("java/lang/Throwable"),
+        ];
+        let frames = vec![
+            frame("java/lang/Throwable", "fillInStackTrace"),
+            frame("java/lang/Throwable", "fillInStackTrace"),
+            frame("java/lang/Throwable", "<init>"),
+            frame("java/lang/Exception", "<init>"),
+            frame("Main$MyEx", "<init>"),
+            frame("Main$Other", "<init>"),
+            frame("Main", "main"),
+        ];
+        assert_eq!(construction_frames(&frames, &ex_classes), 5);
+        //thrown by a method of the exception class
+        assert_eq!(construction_frames(&frames[5..], &ex_classes), 0);
+        assert_eq!(construction_frames(&[], &ex_classes), 0);
+    }
+
+    #[test]
+    fn t_preallocated_key() {
+        assert_eq!(preallocated_key(J_SOE, None), J_SOE.to_vec());
+        assert_eq!(
+            preallocated_key(J_OOM, Some(OOM_METASPACE)),
+            b"java/lang/OutOfMemoryError: Metaspace".to_vec()
+        );
+        assert_ne!(
+            preallocated_key(J_OOM, Some(OOM_JAVA_HEAP)),
+            preallocated_key(J_OOM, Some(OOM_ARRAY_SIZE))
+        );
+    }
+
+    #[test]
+    fn t_location() {
+        assert_eq!(location(Some("Main.java".to_string()), 12), "Main.java:12");
+        assert_eq!(location(Some("Main.java".to_string()), -1), "Main.java");
+        assert_eq!(location(None, -2), "Native Method");
+        assert_eq!(location(None, 3), "Unknown Source");
+    }
+
+    #[test]
+    fn t_in_bounds() {
+        assert_eq!(in_bounds(3, 0, 3), Some(0..3));
+        assert_eq!(in_bounds(3, 3, 0), Some(3..3));
+        assert_eq!(in_bounds(3, 2, 1), Some(2..3));
+        assert_eq!(in_bounds(3, 3, 1), None);
+        assert_eq!(in_bounds(3, -1, 1), None);
+        assert_eq!(in_bounds(3, 1, -1), None);
+        assert_eq!(
+            in_bounds(usize::MAX, i32::MAX, i32::MAX),
+            Some(i32::MAX as usize..(i32::MAX as usize) * 2)
+        );
+        assert_eq!(in_bounds(0, i32::MAX, 1), None);
+    }
+}
@@ -0,0 +1,535 @@
+use crate::runtime::consts::THREAD_MAX_STACK_FRAMES;
+use crate::runtime::ex_break;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+//-XX:+VerifyStack, check operand stack depth before & after every instruction
+static VERIFY_STACK: AtomicBool = AtomicBool::new(false);
+
+pub fn is_verify_stack() -> bool {
+    VERIFY_STACK.load(Ordering::Relaxed)
+}
+
+//-XX:+UseMarkSweepGC, collect cyclic garbage, see runtime::gc
+static USE_GC: AtomicBool = AtomicBool::new(false);
+
+pub fn is_use_gc() -> bool {
+    USE_GC.load(Ordering::Relaxed)
+}
+
+//-XX:+TrackHeapObjects, track objects for heap queries without collecting,
+//see gc::instances_of
+static TRACK_HEAP_OBJECTS: AtomicBool = AtomicBool::new(false);
+
+//the heap registry is kept, by either flag
+pub fn is_track_heap_objects() -> bool {
+    TRACK_HEAP_OBJECTS.load(Ordering::Relaxed) || is_use_gc()
+}
+
+//-XX:+SingleStep, pause before every instruction, see runtime::step
+static SINGLE_STEP: AtomicBool = AtomicBool::new(false);
+
+pub fn is_single_step() -> bool {
+    SINGLE_STEP.load(Ordering::Relaxed)
+}
+
+//-XX:+OmitStackTraceInFastThrow, on by default, hot implicit NPE & AIOOBE
+//sites throw a preallocated exception without stack trace, see
+//exception::new_implicit
+static OMIT_STACK_TRACE_IN_FAST_THROW: AtomicBool = AtomicBool::new(true);
+
+pub fn is_omit_stack_trace_in_fast_throw() -> bool {
+    OMIT_STACK_TRACE_IN_FAST_THROW.load(Ordering::Relaxed)
+}
+
+//-XX:+UseIntrinsics, on by default, well-known methods run as intrinsics,
+//see runtime::intrinsics
+static USE_INTRINSICS: AtomicBool = AtomicBool::new(true);
+
+pub fn is_use_intrinsics() -> bool {
+    USE_INTRINSICS.load(Ordering::Relaxed)
+}
+
+//-XX:+UseAllocBudget, on by default, each thread reserves heap bytes & registers
+//objects in batches of its own, see gc::budget
+static USE_ALLOC_BUDGET: AtomicBool = AtomicBool::new(true);
+
+pub fn is_use_alloc_budget() -> bool {
+    USE_ALLOC_BUDGET.load(Ordering::Relaxed)
+}
+
+//-XX:+PrintExceptionStats, print the exception path profile when the vm exits
+static PRINT_EX_STATS: AtomicBool = AtomicBool::new(false);
+
+pub fn is_print_ex_stats() -> bool {
+    PRINT_EX_STATS.load(Ordering::Relaxed)
+}
+
+//-XX:+PrefetchClasses, parse the classes main refers to in background,
+//see runtime::prefetch
+static PREFETCH_CLASSES: AtomicBool = AtomicBool::new(false);
+
+pub fn is_prefetch_classes() -> bool {
+    PREFETCH_CLASSES.load(Ordering::Relaxed)
+}
+
+//-XX:+TraceClassLoading or -verbose:class, print each loaded class
+static TRACE_CLASS_LOADING: AtomicBool = AtomicBool::new(false);
+
+pub fn is_trace_class_loading() -> bool {
+    TRACE_CLASS_LOADING.load(Ordering::Relaxed)
+}
+
+//-XX:+PrintClassLayout, print fields, vtable, itable & code of each
+//loaded class, see oop::layout
+static PRINT_CLASS_LAYOUT: AtomicBool = AtomicBool::new(false);
+
+pub fn is_print_class_layout() -> bool {
+    PRINT_CLASS_LAYOUT.load(Ordering::Relaxed)
+}
+
+//-XX:+PrintMetaspaceStats, print the metaspace usage when the vm exits
+static PRINT_METASPACE_STATS: AtomicBool = AtomicBool::new(false);
+
+pub fn is_print_metaspace_stats() -> bool {
+    PRINT_METASPACE_STATS.load(Ordering::Relaxed)
+}
+
+//-XX:+PrintClassHistogram, print the live instances by class when the vm
+//exits, see gc::histogram
+static PRINT_CLASS_HISTOGRAM: AtomicBool = AtomicBool::new(false);
+
+pub fn is_print_class_histogram() -> bool {
+    PRINT_CLASS_HISTOGRAM.load(Ordering::Relaxed)
+}
+
+//-XX:MaxMetaspaceSize=n[k|m|g], see runtime::metaspace, 0 is unlimited
+static MAX_METASPACE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+pub fn max_metaspace_size() -> Option<usize> {
+    match MAX_METASPACE_SIZE.load(Ordering::Relaxed) {
+        0 => None,
+        size => Some(size),
+    }
+}
+
+//-XX:+BestEffortNatives, a missing native returns a default value instead
+//of throwing UnsatisfiedLinkError, see native::record_missing
+static BEST_EFFORT_NATIVES: AtomicBool = AtomicBool::new(false);
+
+pub fn is_best_effort_natives() -> bool {
+    BEST_EFFORT_NATIVES.load(Ordering::Relaxed)
+}
+
+//-XX:DiagServerPort=n, serve diagnostics json on 127.0.0.1:n, 0 is off
+static DIAG_SERVER_PORT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn diag_server_port() -> Option<u16> {
+    match DIAG_SERVER_PORT.load(Ordering::Relaxed) {
+        0 => None,
+        port => Some(port as u16),
+    }
+}
+
+//-XX:ParseThreads=n, threads parsing prefetched classes, 0 is the number
+//of cpus, see runtime::prefetch
+static PARSE_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn parse_threads() -> usize {
+    match PARSE_THREADS.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+//-XX:ParallelGCThreads=n, gc worker threads at most, 0 is the number of
+//cpus, see gc::parallel
+static PARALLEL_GC_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn parallel_gc_threads() -> usize {
+    match PARALLEL_GC_THREADS.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+//-Xss<size>, the host stack of the main java thread, java calls near its
+//end throw StackOverflowError, see thread::set_stack_size
+static THREAD_STACK_SIZE: AtomicUsize = AtomicUsize::new(8 << 20);
+//the reserve for unwinding & handlers must fit
+const MIN_THREAD_STACK_SIZE: usize = 1 << 20;
+
+pub fn thread_stack_size() -> usize {
+    THREAD_STACK_SIZE.load(Ordering::Relaxed)
+}
+
+//-Xmx<size>, the java heap, 0 is unlimited. An allocation over it throws
+//OutOfMemoryError, see gc::reserve
+static MAX_HEAP_SIZE: AtomicUsize = AtomicUsize::new(0);
+//-Xms<size>, the initial heap. Nothing is reserved up front, the heap
+//grows on demand, Runtime.totalMemory is at least this
+static INITIAL_HEAP_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+pub fn max_heap_size() -> Option<usize> {
+    match MAX_HEAP_SIZE.load(Ordering::Relaxed) {
+        0 => None,
+        size => Some(size),
+    }
+}
+
+pub fn initial_heap_size() -> usize {
+    INITIAL_HEAP_SIZE.load(Ordering::Relaxed)
+}
+
+//once all options are parsed, -Xms may come before or after -Xmx
+pub fn check_heap_sizes() -> Result<(), String> {
+    check_initial_heap(initial_heap_size(), max_heap_size())
+}
+
+fn check_initial_heap(initial: usize, max: Option<usize>) -> Result<(), String> {
+    match max {
+        Some(max) if initial > max => {
+            Err("Initial heap size set to a larger value than the maximum heap size".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+//-XX:MaxJavaStackDepth=n, java frames per thread before StackOverflowError
+static MAX_JAVA_STACK_DEPTH: AtomicUsize = AtomicUsize::new(THREAD_MAX_STACK_FRAMES);
+
+pub fn max_java_stack_depth() -> usize {
+    MAX_JAVA_STACK_DEPTH.load(Ordering::Relaxed)
+}
+
+//-XX:EventLog=path, record vm events, written to path when the vm exits
+static RECORD_EVENTS: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref EVENT_LOG: Mutex<Option<String>> = Mutex::new(None);
+}
+
+pub fn is_record_events() -> bool {
+    RECORD_EVENTS.load(Ordering::Relaxed)
+}
+
+pub fn event_log() -> Option<String> {
+    EVENT_LOG.lock().unwrap().clone()
+}
+
+//-XX:DumpLoadedClassList=path, write the loaded classes when the vm exits
+lazy_static! {
+    static ref DUMP_CLASS_LIST: Mutex<Option<String>> = Mutex::new(None);
+}
+
+pub fn dump_class_list() -> Option<String> {
+    DUMP_CLASS_LIST.lock().unwrap().clone()
+}
+
+//-XX:DumpHeapSnapshot=path, write the heap once the vm booted, see
+//runtime::snapshot
+lazy_static! {
+    static ref DUMP_HEAP_SNAPSHOT: Mutex<Option<String>> = Mutex::new(None);
+}
+
+pub fn dump_heap_snapshot() -> Option<String> {
+    DUMP_HEAP_SNAPSHOT.lock().unwrap().clone()
+}
+
+//-XX:RestoreHeapSnapshot=path, start from the heap written by
+//DumpHeapSnapshot instead of booting, see runtime::snapshot
+lazy_static! {
+    static ref RESTORE_HEAP_SNAPSHOT: Mutex<Option<String>> = Mutex::new(None);
+}
+
+pub fn restore_heap_snapshot() -> Option<String> {
+    RESTORE_HEAP_SNAPSHOT.lock().unwrap().clone()
+}
+
+//-XX:CallGraph=path, record caller -> callee edges, written to path when the vm exits
+static RECORD_CALLS: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref CALL_GRAPH: Mutex<Option<String>> = Mutex::new(None);
+}
+
+pub fn is_record_calls() -> bool {
+    RECORD_CALLS.load(Ordering::Relaxed)
+}
+
+pub fn call_graph() -> Option<String> {
+    CALL_GRAPH.lock().unwrap().clone()
+}
+
+//-XX:ControlSocket=path, take jcmd style commands on a unix socket, see
+//runtime::control
+lazy_static! {
+    static ref CONTROL_SOCKET: Mutex<Option<String>> = Mutex::new(None);
+}
+
+pub fn control_socket() -> Option<String> {
+    CONTROL_SOCKET.lock().unwrap().clone()
+}
+
+//the -X options accepted, in order, for VM.flags
+lazy_static! {
+    static ref VM_OPTIONS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+pub fn vm_options() -> Vec<String> {
+    VM_OPTIONS.lock().unwrap().clone()
+}
+
+/*
+"-X" options, same style as the java launcher
+  -XX:+Name, -XX:-Name  boolean flags
+  -XX:Name=value        value flags
+  -Xss<size>            thread stack size
+  -Xmx<size>            java heap size
+  -Xms<size>            initial java heap size
+
+clap strips the leading "-X", so "-XX:+VerifyStack" comes as "X:+VerifyStack"
+*/
+pub fn parse_x_opt(opt: &str) -> Result<(), String> {
+    parse_x(opt)?;
+    VM_OPTIONS.lock().unwrap().push(format!("-X{}", opt));
+    Ok(())
+}
+
+//-Xmx & -Xms, the flag & its size, None for another option
+fn parse_heap_size(opt: &str) -> Option<Result<(&'static AtomicUsize, usize), String>> {
+    let (flag, size, name) = match (opt.strip_prefix("mx"), opt.strip_prefix("ms")) {
+        (Some(size), _) => (&MAX_HEAP_SIZE, size, "maximum"),
+        (_, Some(size)) => (&INITIAL_HEAP_SIZE, size, "initial"),
+        _ => return None,
+    };
+    let size = parse_size(size).map_err(|_| format!("Invalid {} heap size: -X{}", name, opt));
+    Some(size.map(|size| (flag, size)))
+}
+
+fn parse_x(opt: &str) -> Result<(), String> {
+    if let Some(size) = opt.strip_prefix("ss") {
+        return parse_stack_size(size);
+    }
+    if let Some(it) = parse_heap_size(opt) {
+        let (flag, size) = it?;
+        flag.store(size, Ordering::Relaxed);
+        return Ok(());
+    }
+
+    match opt.strip_prefix("X:") {
+        Some(xx) => parse_xx_opt(xx).map_err(|_| format!("Unrecognized VM option '{}'", xx)),
+        None => Err(format!("Unrecognized option: -X{}", opt)),
+    }
+}
+
+fn parse_stack_size(value: &str) -> Result<(), String> {
+    let size =
+        parse_size(value).map_err(|_| format!("Invalid thread stack size: -Xss{}", value))?;
+    if size < MIN_THREAD_STACK_SIZE {
+        return Err(format!(
+            "The Java thread stack size specified is too small. Specify at least {}k",
+            MIN_THREAD_STACK_SIZE >> 10
+        ));
+    }
+    THREAD_STACK_SIZE.store(size, Ordering::Relaxed);
+    Ok(())
+}
+
+fn parse_xx_opt(opt: &str) -> Result<(), ()> {
+    if let Some(pos) = opt.find('=') {
+        return parse_xx_value_opt(&opt[..pos], &opt[pos + 1..]);
+    }
+
+    let (enable, name) = match opt.chars().next() {
+        Some('+') => (true, &opt[1..]),
+        Some('-') => (false, &opt[1..]),
+        _ => return Err(()),
+    };
+
+    match name {
+        "VerifyStack" => VERIFY_STACK.store(enable, Ordering::Relaxed),
+        "UseMarkSweepGC" => USE_GC.store(enable, Ordering::Relaxed),
+        "TrackHeapObjects" => TRACK_HEAP_OBJECTS.store(enable, Ordering::Relaxed),
+        "SingleStep" => SINGLE_STEP.store(enable, Ordering::Relaxed),
+        "OmitStackTraceInFastThrow" => {
+            OMIT_STACK_TRACE_IN_FAST_THROW.store(enable, Ordering::Relaxed)
+        }
+        "UseIntrinsics" => USE_INTRINSICS.store(enable, Ordering::Relaxed),
+        "UseAllocBudget" => USE_ALLOC_BUDGET.store(enable, Ordering::Relaxed),
+        "PrintExceptionStats" => PRINT_EX_STATS.store(enable, Ordering::Relaxed),
+        "PrefetchClasses" => PREFETCH_CLASSES.store(enable, Ordering::Relaxed),
+        "TraceClassLoading" => TRACE_CLASS_LOADING.store(enable, Ordering::Relaxed),
+        "PrintClassLayout" => PRINT_CLASS_LAYOUT.store(enable, Ordering::Relaxed),
+        "PrintMetaspaceStats" => PRINT_METASPACE_STATS.store(enable, Ordering::Relaxed),
+        "PrintClassHistogram" => PRINT_CLASS_HISTOGRAM.store(enable, Ordering::Relaxed),
+        "BestEffortNatives" => BEST_EFFORT_NATIVES.store(enable, Ordering::Relaxed),
+        _ => return Err(()),
+    }
+
+    Ok(())
+}
+
+fn parse_xx_value_opt(name: &str, value: &str) -> Result<(), ()> {
+    match name {
+        "EventLog" => {
+            if value.is_empty() {
+                return Err(());
+            }
+            *EVENT_LOG.lock().unwrap() = Some(value.to_string());
+            RECORD_EVENTS.store(true, Ordering::Relaxed);
+        }
+        "DumpLoadedClassList" => {
+            if value.is_empty() {
+                return Err(());
+            }
+            *DUMP_CLASS_LIST.lock().unwrap() = Some(value.to_string());
+        }
+        "DumpHeapSnapshot" => {
+            if value.is_empty() {
+                return Err(());
+            }
+            *DUMP_HEAP_SNAPSHOT.lock().unwrap() = Some(value.to_string());
+        }
+        "RestoreHeapSnapshot" => {
+            if value.is_empty() {
+                return Err(());
+            }
+            *RESTORE_HEAP_SNAPSHOT.lock().unwrap() = Some(value.to_string());
+        }
+        //-XX:BreakOnException=a,b, classes to break on, see runtime::ex_break
+        "BreakOnException" => {
+            if value.is_empty() {
+                return Err(());
+            }
+            value
+                .split(',')
+                .filter(|it| !it.is_empty())
+                .for_each(ex_break::add_filter);
+        }
+        "CallGraph" => {
+            if value.is_empty() {
+                return Err(());
+            }
+            *CALL_GRAPH.lock().unwrap() = Some(value.to_string());
+            RECORD_CALLS.store(true, Ordering::Relaxed);
+        }
+        "ControlSocket" => {
+            if value.is_empty() {
+                return Err(());
+            }
+            *CONTROL_SOCKET.lock().unwrap() = Some(value.to_string());
+        }
+        "MaxJavaStackDepth" => {
+            let n: usize = value.parse().map_err(|_| ())?;
+            if n == 0 {
+                return Err(());
+            }
+            MAX_JAVA_STACK_DEPTH.store(n, Ordering::Relaxed);
+        }
+        "ParseThreads" => {
+            let n: usize = value.parse().map_err(|_| ())?;
+            PARSE_THREADS.store(n, Ordering::Relaxed);
+        }
+        "ParallelGCThreads" => {
+            let n: usize = value.parse().map_err(|_| ())?;
+            PARALLEL_GC_THREADS.store(n, Ordering::Relaxed);
+        }
+        "MaxMetaspaceSize" => {
+            let size = parse_size(value)?;
+            MAX_METASPACE_SIZE.store(size, Ordering::Relaxed);
+        }
+        #[cfg(feature = "diag-server")]
+        "DiagServerPort" => {
+            let port: u16 = value.parse().map_err(|_| ())?;
+            DIAG_SERVER_PORT.store(port as usize, Ordering::Relaxed);
+        }
+        _ => return Err(()),
+    }
+
+    Ok(())
+}
+
+//"64m", "512k", "1g", or bytes
+fn parse_size(value: &str) -> Result<usize, ()> {
+    let (n, unit) = match value.chars().last() {
+        Some('k') | Some('K') => (&value[..value.len() - 1], 1 << 10),
+        Some('m') | Some('M') => (&value[..value.len() - 1], 1 << 20),
+        Some('g') | Some('G') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    let n: usize = n.parse().map_err(|_| ())?;
+    n.checked_mul(unit).ok_or(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_parse_xx() {
+        assert!(parse_x_opt("X:+VerifyStack").is_ok());
+        assert!(is_verify_stack());
+        assert!(parse_x_opt("X:-VerifyStack").is_ok());
+        assert!(!is_verify_stack());
+
+        assert!(is_omit_stack_trace_in_fast_throw());
+        assert!(parse_x_opt("X:-OmitStackTraceInFastThrow").is_ok());
+        assert!(!is_omit_stack_trace_in_fast_throw());
+        assert!(parse_x_opt("X:+OmitStackTraceInFastThrow").is_ok());
+
+        assert!(parse_x_opt("X:VerifyStack").is_err());
+        assert!(parse_x_opt("X:+NoSuchFlag").is_err());
+        assert!(parse_x_opt("abc").is_err());
+        assert!(parse_x_opt("X:NoSuchFlag=1").is_err());
+    }
+
+    #[test]
+    fn t_parse_stack_size() {
+        assert!(parse_x_opt("ss4m").is_ok());
+        assert_eq!(thread_stack_size(), 4 << 20);
+        assert!(parse_x_opt("ss64k").is_err());
+        assert!(parse_x_opt("ssabc").is_err());
+        assert_eq!(thread_stack_size(), 4 << 20);
+    }
+
+    //parsed, not stored: the heap of the tests running alongside is kept
+    #[test]
+    fn t_parse_heap_size() {
+        let parse = |opt| {
+            parse_heap_size(opt)
+                .unwrap()
+                .map(|(flag, size)| (flag as *const AtomicUsize, size))
+        };
+        assert_eq!(parse("mx64m"), Ok((&MAX_HEAP_SIZE as *const _, 64 << 20)));
+        assert_eq!(
+            parse("ms16m"),
+            Ok((&INITIAL_HEAP_SIZE as *const _, 16 << 20))
+        );
+        assert_eq!(parse("mx0").map(|it| it.1), Ok(0));
+        assert_eq!(
+            parse("mxabc"),
+            Err("Invalid maximum heap size: -Xmxabc".to_string())
+        );
+        assert_eq!(
+            parse("ms1x"),
+            Err("Invalid initial heap size: -Xms1x".to_string())
+        );
+        assert!(parse_heap_size("ss1m").is_none());
+
+        assert!(check_initial_heap(128 << 20, Some(64 << 20)).is_err());
+        assert!(check_initial_heap(64 << 20, Some(64 << 20)).is_ok());
+        assert!(check_initial_heap(128 << 20, None).is_ok());
+    }
+
+    #[test]
+    fn t_parse_size() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("64k"), Ok(64 << 10));
+        assert_eq!(parse_size("64M"), Ok(64 << 20));
+        assert_eq!(parse_size("2g"), Ok(2 << 30));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("m").is_err());
+        assert!(parse_size("-1k").is_err());
+    }
+}
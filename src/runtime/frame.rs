@@ -2,11 +2,16 @@ use crate::classfile::constant_pool::{self, ConstantType};
 use crate::classfile::consts;
 use crate::classfile::consts::J_STRING;
 use crate::classfile::opcode::OpCode;
+use crate::classfile::signature::{FieldSignature, Type as ArgType};
 use crate::classfile::ClassFile;
-use crate::oop::{self, consts as oop_consts, field, Oop, OopDesc, TypeArrayValue, ValueType};
+use crate::oop::cp_cache::CpCache;
+use crate::oop::method::{Bytecode, Operand, Switch};
+use crate::oop::{
+    self, cha, consts as oop_consts, field, AllocErr, Oop, OopDesc, TypeArrayValue, ValueType,
+};
 use crate::runtime::{
-    self, cmp, exception, require_class, require_class2, require_class3, JavaCall, JavaThread,
-    Local, Stack,
+    self, cmp, exception, intrinsics, monitor, require_class, require_class2, require_class3,
+    JavaCall, JavaThread, Local, ReturnAddress, Stack, StackErr,
 };
 use crate::types::*;
 use crate::util;
@@ -15,40 +20,39 @@ use std::borrow::BorrowMut;
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Instant;
 
+//an OutOfMemoryError is a preallocated instance, there may be no memory
+//left to construct one
 fn meet_ex(jt: &mut JavaThread, cls_name: &'static [u8], msg: Option<String>) {
-    let ex = exception::new(jt, cls_name, msg);
+    let ex = if cls_name == consts::J_OOM {
+        let msg = msg.as_deref().unwrap_or(exception::OOM_JAVA_HEAP);
+        exception::out_of_memory(jt, msg)
+    } else {
+        exception::new_implicit(jt, cls_name, msg)
+    };
     jt.set_ex(ex);
 }
 
+//the index of an array load & store, None with the exception pending
+fn check_index(jt: &mut JavaThread, len: usize, pos: i32) -> Option<usize> {
+    exception::check_index(jt, len, pos)
+        .map_err(|ex| jt.set_ex(ex))
+        .ok()
+}
+
 macro_rules! array_store {
     ($thread:ident, $ary:ident, $pos:ident, $v:ident) => {
-        let len = $ary.len();
-        if ($pos < 0) || ($pos as usize >= len) {
-            let msg = format!("length is {}, but index is {}", len, $pos);
-            meet_ex(
-                $thread,
-                crate::classfile::consts::J_ARRAY_INDEX_OUT_OF_BOUNDS,
-                Some(msg),
-            );
-        } else {
-            $ary[$pos as usize] = $v;
+        if let Some(i) = check_index($thread, $ary.len(), $pos) {
+            $ary[i] = $v;
         }
     };
 }
 
 macro_rules! iarray_load {
     ($thread:ident, $stack:ident, $ary:ident, $pos:ident) => {
-        let len = $ary.len();
-        if ($pos < 0) || ($pos as usize >= len) {
-            let msg = format!("length is {}, but index is {}", len, $pos);
-            meet_ex(
-                $thread,
-                crate::classfile::consts::J_ARRAY_INDEX_OUT_OF_BOUNDS,
-                Some(msg),
-            );
-        } else {
-            $stack.push_int($ary[$pos as usize] as i32);
+        if let Some(i) = check_index($thread, $ary.len(), $pos) {
+            $stack.push_int($ary[i] as i32);
         }
     };
 }
@@ -58,62 +62,54 @@ pub struct Frame {
     class: ClassRef,
     //avoid lock class to access cp
     cp: ConstantPool,
+    cp_cache: Arc<CpCache>,
     pub mir: MethodIdRef,
-    code: Arc<Vec<U1>>,
+    bytecode: Arc<Bytecode>,
 
     pub local: Local,
     pub stack: Stack,
+    //next instruction
     pub pc: i32,
+    //current instruction
+    bci: i32,
     pub return_v: Option<OopRef>,
-
-    op_widen: bool,
 }
 
 //new
 impl Frame {
     pub fn new(mir: MethodIdRef, frame_id: usize) -> Self {
         let class = mir.method.class.clone();
-        let cp = {
+        let (cp, cp_cache) = {
             let class = class.lock().unwrap();
             match &class.kind {
-                oop::ClassKind::Instance(cls_obj) => cls_obj.class_file.cp.clone(),
+                oop::ClassKind::Instance(cls_obj) => {
+                    (cls_obj.class_file.cp.clone(), cls_obj.cp_cache.clone())
+                }
                 _ => unreachable!(),
             }
         };
 
-        // trace!("method.code.is_some = {}", mir.method.code.is_some());
-        match &mir.method.code {
-            Some(code) => {
-                // trace!("max_locals = {}, max_stack = {}", code.max_locals, code.max_stack);
-                let local = Local::new(code.max_locals as usize);
-                let stack = Stack::new(code.max_stack as usize);
-                let code = code.code.clone();
-                Self {
-                    frame_id,
-                    class,
-                    cp,
-                    mir,
-                    code,
-                    local,
-                    stack,
-                    pc: 0,
-                    return_v: None,
-                    op_widen: false,
-                }
-            }
-
-            None => Self {
-                frame_id,
-                class,
-                cp: Arc::new(Box::new(Vec::new())),
-                mir,
-                code: Arc::new(vec![]),
-                local: Local::new(0),
-                stack: Stack::new(0),
-                pc: 0,
-                return_v: None,
-                op_widen: false,
-            },
+        //JavaCall doesn't make frames of abstract, native or code-less methods
+        let code = mir
+            .method
+            .code
+            .as_ref()
+            .expect("frame of a method without code");
+        let bytecode = mir.method.bytecode.clone();
+        let local = Local::new(code.max_locals as usize);
+        let stack = Stack::new(code.max_stack as usize);
+        Self {
+            frame_id,
+            class,
+            cp,
+            cp_cache,
+            mir,
+            bytecode,
+            local,
+            stack,
+            pc: 0,
+            bci: 0,
+            return_v: None,
         }
     }
 }
@@ -123,298 +119,402 @@ impl Frame {
         let frame_id = self.frame_id;
         //for debug
         let cls_name = { self.mir.method.class.lock().unwrap().name.clone() };
-        let cls_name = unsafe { std::str::from_utf8_unchecked(cls_name.as_slice()) };
+        let cls_name = util::mutf8::display(cls_name.as_slice());
+        let cls_name = cls_name.as_ref();
         let method = self.mir.method.get_id();
-        let method = unsafe { std::str::from_utf8_unchecked(method.as_slice()) };
+        let method = util::mutf8::display(method.as_slice());
+        let method = method.as_ref();
 
+        let bytecode = self.bytecode.clone();
         loop {
-            let code = self.read_opcode();
-            match code {
-                Some(code) => {
-                    let op_code = OpCode::from(*code);
-                    trace!(
-                        "interp: {:?} ({}/{}) {}:{}",
-                        op_code,
-                        *code,
-                        frame_id,
-                        cls_name,
-                        method
-                    );
-
-                    match op_code {
-                        OpCode::athrow => {
-                            self.athrow(thread);
-                            break;
-                        }
-                        OpCode::ireturn => {
-                            self.ireturn();
-                            break;
-                        }
-                        OpCode::lreturn => {
-                            self.lreturn();
-                            break;
-                        }
-                        OpCode::freturn => {
-                            self.freturn();
-                            break;
-                        }
-                        OpCode::dreturn => {
-                            self.dreturn();
-                            break;
-                        }
-                        OpCode::areturn => {
-                            self.areturn();
-                            break;
-                        }
-                        OpCode::return_void => {
-                            self.return_void();
-                            break;
-                        }
-                        OpCode::nop => self.nop(),
-                        OpCode::aconst_null => self.aconst_null(),
-                        OpCode::iconst_m1 => self.iconst_m1(),
-                        OpCode::iconst_0 => self.iconst_0(),
-                        OpCode::iconst_1 => self.iconst_1(),
-                        OpCode::iconst_2 => self.iconst_2(),
-                        OpCode::iconst_3 => self.iconst_3(),
-                        OpCode::iconst_4 => self.iconst_4(),
-                        OpCode::iconst_5 => self.iconst_5(),
-                        OpCode::lconst_0 => self.lconst_0(),
-                        OpCode::lconst_1 => self.lconst_1(),
-                        OpCode::fconst_0 => self.fconst_0(),
-                        OpCode::fconst_1 => self.fconst_1(),
-                        OpCode::fconst_2 => self.fconst_2(),
-                        OpCode::dconst_0 => self.dconst_0(),
-                        OpCode::dconst_1 => self.dconst_1(),
-                        OpCode::bipush => self.bipush(),
-                        OpCode::sipush => self.sipush(),
-                        OpCode::ldc => self.ldc(thread),
-                        OpCode::ldc_w => self.ldc_w(thread),
-                        OpCode::ldc2_w => self.ldc2_w(thread),
-                        OpCode::iload => self.iload(),
-                        OpCode::lload => self.lload(),
-                        OpCode::fload => self.fload(),
-                        OpCode::dload => self.dload(),
-                        OpCode::aload => self.aload(),
-                        OpCode::iload_0 => self.iload_0(),
-                        OpCode::iload_1 => self.iload_1(),
-                        OpCode::iload_2 => self.iload_2(),
-                        OpCode::iload_3 => self.iload_3(),
-                        OpCode::lload_0 => self.lload_0(),
-                        OpCode::lload_1 => self.lload_1(),
-                        OpCode::lload_2 => self.lload_2(),
-                        OpCode::lload_3 => self.lload_3(),
-                        OpCode::fload_0 => self.fload_0(),
-                        OpCode::fload_1 => self.fload_1(),
-                        OpCode::fload_2 => self.fload_2(),
-                        OpCode::fload_3 => self.fload_3(),
-                        OpCode::dload_0 => self.dload_0(),
-                        OpCode::dload_1 => self.dload_1(),
-                        OpCode::dload_2 => self.dload_2(),
-                        OpCode::dload_3 => self.dload_3(),
-                        OpCode::aload_0 => self.aload_0(),
-                        OpCode::aload_1 => self.aload_1(),
-                        OpCode::aload_2 => self.aload_2(),
-                        OpCode::aload_3 => self.aload_3(),
-                        OpCode::iaload => self.iaload(thread),
-                        OpCode::laload => self.laload(thread),
-                        OpCode::faload => self.faload(thread),
-                        OpCode::daload => self.daload(thread),
-                        OpCode::aaload => self.aaload(thread),
-                        OpCode::baload => self.baload(thread),
-                        OpCode::caload => self.caload(thread),
-                        OpCode::saload => self.saload(thread),
-                        OpCode::istore => self.istore(),
-                        OpCode::lstore => self.lstore(),
-                        OpCode::fstore => self.fstore(),
-                        OpCode::dstore => self.dstore(),
-                        OpCode::astore => self.astore(),
-                        OpCode::istore_0 => self.istore_0(),
-                        OpCode::istore_1 => self.istore_1(),
-                        OpCode::istore_2 => self.istore_2(),
-                        OpCode::istore_3 => self.istore_3(),
-                        OpCode::lstore_0 => self.lstore_0(),
-                        OpCode::lstore_1 => self.lstore_1(),
-                        OpCode::lstore_2 => self.lstore_2(),
-                        OpCode::lstore_3 => self.lstore_3(),
-                        OpCode::fstore_0 => self.fstore_0(),
-                        OpCode::fstore_1 => self.fstore_1(),
-                        OpCode::fstore_2 => self.fstore_2(),
-                        OpCode::fstore_3 => self.fstore_3(),
-                        OpCode::dstore_0 => self.dstore_0(),
-                        OpCode::dstore_1 => self.dstore_1(),
-                        OpCode::dstore_2 => self.dstore_2(),
-                        OpCode::dstore_3 => self.dstore_3(),
-                        OpCode::astore_0 => self.astore_0(),
-                        OpCode::astore_1 => self.astore_1(),
-                        OpCode::astore_2 => self.astore_2(),
-                        OpCode::astore_3 => self.astore_3(),
-                        OpCode::iastore => self.iastore(thread),
-                        OpCode::lastore => self.lastore(thread),
-                        OpCode::fastore => self.fastore(thread),
-                        OpCode::dastore => self.dastore(thread),
-                        OpCode::aastore => self.aastore(thread),
-                        OpCode::bastore => self.bastore(thread),
-                        OpCode::castore => self.castore(thread),
-                        OpCode::sastore => self.sastore(thread),
-                        OpCode::pop => self.pop(),
-                        OpCode::pop2 => self.pop2(),
-                        OpCode::dup => self.dup(),
-                        OpCode::dup_x1 => self.dup_x1(),
-                        OpCode::dup_x2 => self.dup_x2(),
-                        OpCode::dup2 => self.dup2(),
-                        OpCode::dup2_x1 => self.dup2_x1(),
-                        OpCode::dup2_x2 => self.dup2_x2(),
-                        OpCode::swap => self.swap(),
-                        OpCode::iadd => self.iadd(),
-                        OpCode::ladd => self.ladd(),
-                        OpCode::fadd => self.fadd(),
-                        OpCode::dadd => self.dadd(),
-                        OpCode::isub => self.isub(),
-                        OpCode::lsub => self.lsub(),
-                        OpCode::fsub => self.fsub(),
-                        OpCode::dsub => self.dsub(),
-                        OpCode::imul => self.imul(),
-                        OpCode::lmul => self.lmul(),
-                        OpCode::fmul => self.fmul(),
-                        OpCode::dmul => self.dmul(),
-                        OpCode::idiv => self.idiv(thread),
-                        OpCode::ldiv => self.ldiv(thread),
-                        OpCode::fdiv => self.fdiv(thread),
-                        OpCode::ddiv => self.ddiv(thread),
-                        OpCode::irem => self.irem(thread),
-                        OpCode::lrem => self.lrem(thread),
-                        OpCode::frem => self.frem(),
-                        OpCode::drem => self.drem(),
-                        OpCode::ineg => self.ineg(),
-                        OpCode::lneg => self.lneg(),
-                        OpCode::fneg => self.fneg(),
-                        OpCode::dneg => self.dneg(),
-                        OpCode::ishl => self.ishl(),
-                        OpCode::lshl => self.lshl(),
-                        OpCode::ishr => self.ishr(),
-                        OpCode::lshr => self.lshr(),
-                        OpCode::iushr => self.iushr(),
-                        OpCode::lushr => self.lushr(),
-                        OpCode::iand => self.iand(),
-                        OpCode::land => self.land(),
-                        OpCode::ior => self.ior(),
-                        OpCode::lor => self.lor(),
-                        OpCode::ixor => self.ixor(),
-                        OpCode::lxor => self.lxor(),
-                        OpCode::iinc => self.iinc(),
-                        OpCode::i2l => self.i2l(),
-                        OpCode::i2f => self.i2f(),
-                        OpCode::i2d => self.i2d(),
-                        OpCode::l2i => self.l2i(),
-                        OpCode::l2f => self.l2f(),
-                        OpCode::l2d => self.l2d(),
-                        OpCode::f2i => self.f2i(),
-                        OpCode::f2l => self.f2l(),
-                        OpCode::f2d => self.f2d(),
-                        OpCode::d2i => self.d2i(),
-                        OpCode::d2l => self.d2l(),
-                        OpCode::d2f => self.d2f(),
-                        OpCode::i2b => self.i2b(),
-                        OpCode::i2c => self.i2c(),
-                        OpCode::i2s => self.i2s(),
-                        OpCode::lcmp => self.lcmp(),
-                        OpCode::fcmpl => self.fcmpl(),
-                        OpCode::fcmpg => self.fcmpg(),
-                        OpCode::dcmpl => self.dcmpl(),
-                        OpCode::dcmpg => self.dcmpg(),
-                        OpCode::ifeq => self.ifeq(),
-                        OpCode::ifne => self.ifne(),
-                        OpCode::iflt => self.iflt(),
-                        OpCode::ifge => self.ifge(),
-                        OpCode::ifgt => self.ifgt(),
-                        OpCode::ifle => self.ifle(),
-                        OpCode::if_icmpeq => self.if_icmpeq(),
-                        OpCode::if_icmpne => self.if_icmpne(),
-                        OpCode::if_icmplt => self.if_icmplt(),
-                        OpCode::if_icmpge => self.if_icmpge(),
-                        OpCode::if_icmpgt => self.if_icmpgt(),
-                        OpCode::if_icmple => self.if_icmple(),
-                        OpCode::if_acmpeq => self.if_acmpeq(),
-                        OpCode::if_acmpne => self.if_acmpne(),
-                        OpCode::goto => self.goto(),
-                        OpCode::jsr => self.jsr(),
-                        OpCode::ret => self.ret(),
-                        OpCode::tableswitch => self.table_switch(),
-                        OpCode::lookupswitch => self.lookup_switch(),
-                        OpCode::getstatic => self.get_static(thread),
-                        OpCode::putstatic => self.put_static(thread),
-                        OpCode::getfield => self.get_field(thread),
-                        OpCode::putfield => self.put_field(thread),
-                        OpCode::invokevirtual => self.invoke_virtual(thread),
-                        OpCode::invokespecial => self.invoke_special(thread),
-                        OpCode::invokestatic => self.invoke_static(thread),
-                        OpCode::invokeinterface => self.invoke_interface(thread),
-                        OpCode::invokedynamic => self.invoke_dynamic(),
-                        OpCode::new => self.new_(thread),
-                        OpCode::newarray => self.new_array(thread),
-                        OpCode::anewarray => self.anew_array(thread),
-                        OpCode::arraylength => self.array_length(thread),
-                        OpCode::checkcast => self.check_cast(thread),
-                        OpCode::instanceof => self.instance_of(),
-                        OpCode::monitorenter => self.monitor_enter(thread),
-                        OpCode::monitorexit => self.monitor_exit(thread),
-                        OpCode::wide => self.wide(),
-                        OpCode::multianewarray => self.multi_anew_array(),
-                        OpCode::ifnull => self.if_null(),
-                        OpCode::ifnonnull => self.if_non_null(),
-                        OpCode::goto_w => self.goto_w(),
-                        OpCode::jsr_w => self.jsr_w(),
-                        _ => unreachable!(),
-                    }
+            //safe point
+            runtime::safepoint::poll();
+            if runtime::gc::should_collect() {
+                runtime::gc::collect();
+            }
 
-                    if thread.is_meet_ex() {
-                        // error!("meet ex: {:?}, frame_id = {}", op_code, self.frame_id);
-                        let ex = thread.take_ex().unwrap();
-                        match self.try_handle_exception(thread, ex) {
-                            Ok(_) => (),
-                            Err(ex) => {
-                                thread.set_ex(ex);
-                                break;
-                            }
-                        }
+            let instr = match bytecode.get(self.pc) {
+                Some(instr) => instr,
+                None => {
+                    //decoding stopped at a bad opcode
+                    if let Some(e) = &bytecode.err {
+                        let msg = format!("{} in {}:{}", e, cls_name, method);
+                        meet_ex(thread, consts::J_INTERNAL_ERROR, Some(msg));
                     }
+                    break;
                 }
+            };
+            let op_code = instr.op_code;
+            let bci = instr.bci;
+            trace!(
+                "interp: {:?} ({}/{}) {}:{}",
+                op_code,
+                bci,
+                frame_id,
+                cls_name,
+                method
+            );
 
-                None => break,
+            //fall through to the next instruction, branches overwrite pc
+            self.bci = bci;
+            thread.bci = bci;
+            self.pc = instr.next;
+
+            let verify_stack = runtime::flags::is_verify_stack();
+            if verify_stack {
+                let r = self.stack.check_underflow(op_code.min_stack_pops());
+                self.verify_stack_report(r, cls_name, method, bci, op_code);
             }
-        }
-    }
-}
 
-//helper methods
-impl Frame {
-    fn read_i2(&mut self) -> i32 {
-        let h = self.read_byte() as i16;
-        let l = self.read_byte() as i16;
-        (h << 8 | l) as i32
-    }
+            if runtime::step::is_stepping() {
+                let stack = self.stack.slots();
+                let locals = self.local.slots();
+                let ctx = runtime::step::StepContext {
+                    class: cls_name,
+                    method,
+                    bci,
+                    op_code,
+                    stack: stack.as_slice(),
+                    locals: locals.as_slice(),
+                };
+                runtime::step::on_instruction(&ctx);
+            }
+
+            match op_code {
+                OpCode::athrow => {
+                    self.athrow(thread);
+                    break;
+                }
+                OpCode::ireturn => {
+                    self.ireturn();
+                    break;
+                }
+                OpCode::lreturn => {
+                    self.lreturn();
+                    break;
+                }
+                OpCode::freturn => {
+                    self.freturn();
+                    break;
+                }
+                OpCode::dreturn => {
+                    self.dreturn();
+                    break;
+                }
+                OpCode::areturn => {
+                    self.areturn();
+                    break;
+                }
+                OpCode::return_void => {
+                    self.return_void();
+                    break;
+                }
+                OpCode::nop => self.nop(),
+                OpCode::aconst_null => self.aconst_null(),
+                OpCode::iconst_m1 => self.iconst_m1(),
+                OpCode::iconst_0 => self.iconst_0(),
+                OpCode::iconst_1 => self.iconst_1(),
+                OpCode::iconst_2 => self.iconst_2(),
+                OpCode::iconst_3 => self.iconst_3(),
+                OpCode::iconst_4 => self.iconst_4(),
+                OpCode::iconst_5 => self.iconst_5(),
+                OpCode::lconst_0 => self.lconst_0(),
+                OpCode::lconst_1 => self.lconst_1(),
+                OpCode::fconst_0 => self.fconst_0(),
+                OpCode::fconst_1 => self.fconst_1(),
+                OpCode::fconst_2 => self.fconst_2(),
+                OpCode::dconst_0 => self.dconst_0(),
+                OpCode::dconst_1 => self.dconst_1(),
+                OpCode::bipush => self.bipush(instr.int()),
+                OpCode::sipush => self.sipush(instr.int()),
+                OpCode::ldc => self.ldc(thread, instr.int() as usize),
+                OpCode::ldc_w => self.ldc_w(thread, instr.int() as usize),
+                OpCode::ldc2_w => self.ldc2_w(thread, instr.int() as usize),
+                OpCode::iload => self.iload(instr.int() as usize),
+                OpCode::lload => self.lload(instr.int() as usize),
+                OpCode::fload => self.fload(instr.int() as usize),
+                OpCode::dload => self.dload(instr.int() as usize),
+                OpCode::aload => self.aload(instr.int() as usize),
+                OpCode::iload_0 => self.iload_0(),
+                OpCode::iload_1 => self.iload_1(),
+                OpCode::iload_2 => self.iload_2(),
+                OpCode::iload_3 => self.iload_3(),
+                OpCode::lload_0 => self.lload_0(),
+                OpCode::lload_1 => self.lload_1(),
+                OpCode::lload_2 => self.lload_2(),
+                OpCode::lload_3 => self.lload_3(),
+                OpCode::fload_0 => self.fload_0(),
+                OpCode::fload_1 => self.fload_1(),
+                OpCode::fload_2 => self.fload_2(),
+                OpCode::fload_3 => self.fload_3(),
+                OpCode::dload_0 => self.dload_0(),
+                OpCode::dload_1 => self.dload_1(),
+                OpCode::dload_2 => self.dload_2(),
+                OpCode::dload_3 => self.dload_3(),
+                OpCode::aload_0 => self.aload_0(),
+                OpCode::aload_1 => self.aload_1(),
+                OpCode::aload_2 => self.aload_2(),
+                OpCode::aload_3 => self.aload_3(),
+                OpCode::iaload => self.iaload(thread),
+                OpCode::laload => self.laload(thread),
+                OpCode::faload => self.faload(thread),
+                OpCode::daload => self.daload(thread),
+                OpCode::aaload => self.aaload(thread),
+                OpCode::baload => self.baload(thread),
+                OpCode::caload => self.caload(thread),
+                OpCode::saload => self.saload(thread),
+                OpCode::istore => self.istore(instr.int() as usize),
+                OpCode::lstore => self.lstore(instr.int() as usize),
+                OpCode::fstore => self.fstore(instr.int() as usize),
+                OpCode::dstore => self.dstore(instr.int() as usize),
+                OpCode::astore => self.astore(instr.int() as usize),
+                OpCode::istore_0 => self.istore_0(),
+                OpCode::istore_1 => self.istore_1(),
+                OpCode::istore_2 => self.istore_2(),
+                OpCode::istore_3 => self.istore_3(),
+                OpCode::lstore_0 => self.lstore_0(),
+                OpCode::lstore_1 => self.lstore_1(),
+                OpCode::lstore_2 => self.lstore_2(),
+                OpCode::lstore_3 => self.lstore_3(),
+                OpCode::fstore_0 => self.fstore_0(),
+                OpCode::fstore_1 => self.fstore_1(),
+                OpCode::fstore_2 => self.fstore_2(),
+                OpCode::fstore_3 => self.fstore_3(),
+                OpCode::dstore_0 => self.dstore_0(),
+                OpCode::dstore_1 => self.dstore_1(),
+                OpCode::dstore_2 => self.dstore_2(),
+                OpCode::dstore_3 => self.dstore_3(),
+                OpCode::astore_0 => self.astore_0(),
+                OpCode::astore_1 => self.astore_1(),
+                OpCode::astore_2 => self.astore_2(),
+                OpCode::astore_3 => self.astore_3(),
+                OpCode::iastore => self.iastore(thread),
+                OpCode::lastore => self.lastore(thread),
+                OpCode::fastore => self.fastore(thread),
+                OpCode::dastore => self.dastore(thread),
+                OpCode::aastore => self.aastore(thread),
+                OpCode::bastore => self.bastore(thread),
+                OpCode::castore => self.castore(thread),
+                OpCode::sastore => self.sastore(thread),
+                OpCode::pop => self.pop(),
+                OpCode::pop2 => self.pop2(),
+                OpCode::dup => self.dup(),
+                OpCode::dup_x1 => self.dup_x1(),
+                OpCode::dup_x2 => self.dup_x2(),
+                OpCode::dup2 => self.dup2(),
+                OpCode::dup2_x1 => self.dup2_x1(),
+                OpCode::dup2_x2 => self.dup2_x2(),
+                OpCode::swap => self.swap(),
+                OpCode::iadd => self.iadd(),
+                OpCode::ladd => self.ladd(),
+                OpCode::fadd => self.fadd(),
+                OpCode::dadd => self.dadd(),
+                OpCode::isub => self.isub(),
+                OpCode::lsub => self.lsub(),
+                OpCode::fsub => self.fsub(),
+                OpCode::dsub => self.dsub(),
+                OpCode::imul => self.imul(),
+                OpCode::lmul => self.lmul(),
+                OpCode::fmul => self.fmul(),
+                OpCode::dmul => self.dmul(),
+                OpCode::idiv => self.idiv(thread),
+                OpCode::ldiv => self.ldiv(thread),
+                OpCode::fdiv => self.fdiv(),
+                OpCode::ddiv => self.ddiv(),
+                OpCode::irem => self.irem(thread),
+                OpCode::lrem => self.lrem(thread),
+                OpCode::frem => self.frem(),
+                OpCode::drem => self.drem(),
+                OpCode::ineg => self.ineg(),
+                OpCode::lneg => self.lneg(),
+                OpCode::fneg => self.fneg(),
+                OpCode::dneg => self.dneg(),
+                OpCode::ishl => self.ishl(),
+                OpCode::lshl => self.lshl(),
+                OpCode::ishr => self.ishr(),
+                OpCode::lshr => self.lshr(),
+                OpCode::iushr => self.iushr(),
+                OpCode::lushr => self.lushr(),
+                OpCode::iand => self.iand(),
+                OpCode::land => self.land(),
+                OpCode::ior => self.ior(),
+                OpCode::lor => self.lor(),
+                OpCode::ixor => self.ixor(),
+                OpCode::lxor => self.lxor(),
+                OpCode::iinc => match instr.operand {
+                    Operand::Iinc(pos, factor) => self.iinc(pos, factor),
+                    _ => unreachable!(),
+                },
+                OpCode::i2l => self.i2l(),
+                OpCode::i2f => self.i2f(),
+                OpCode::i2d => self.i2d(),
+                OpCode::l2i => self.l2i(),
+                OpCode::l2f => self.l2f(),
+                OpCode::l2d => self.l2d(),
+                OpCode::f2i => self.f2i(),
+                OpCode::f2l => self.f2l(),
+                OpCode::f2d => self.f2d(),
+                OpCode::d2i => self.d2i(),
+                OpCode::d2l => self.d2l(),
+                OpCode::d2f => self.d2f(),
+                OpCode::i2b => self.i2b(),
+                OpCode::i2c => self.i2c(),
+                OpCode::i2s => self.i2s(),
+                OpCode::lcmp => self.lcmp(),
+                OpCode::fcmpl => self.fcmpl(),
+                OpCode::fcmpg => self.fcmpg(),
+                OpCode::dcmpl => self.dcmpl(),
+                OpCode::dcmpg => self.dcmpg(),
+                OpCode::ifeq => self.ifeq(instr.branch()),
+                OpCode::ifne => self.ifne(instr.branch()),
+                OpCode::iflt => self.iflt(instr.branch()),
+                OpCode::ifge => self.ifge(instr.branch()),
+                OpCode::ifgt => self.ifgt(instr.branch()),
+                OpCode::ifle => self.ifle(instr.branch()),
+                OpCode::if_icmpeq => self.if_icmpeq(instr.branch()),
+                OpCode::if_icmpne => self.if_icmpne(instr.branch()),
+                OpCode::if_icmplt => self.if_icmplt(instr.branch()),
+                OpCode::if_icmpge => self.if_icmpge(instr.branch()),
+                OpCode::if_icmpgt => self.if_icmpgt(instr.branch()),
+                OpCode::if_icmple => self.if_icmple(instr.branch()),
+                OpCode::if_acmpeq => self.if_acmpeq(instr.branch()),
+                OpCode::if_acmpne => self.if_acmpne(instr.branch()),
+                OpCode::goto => self.goto(instr.branch()),
+                OpCode::jsr => self.jsr(instr.branch()),
+                OpCode::ret => self.ret(instr.int() as usize),
+                OpCode::tableswitch | OpCode::lookupswitch => match &instr.operand {
+                    Operand::Switch(sw) => self.switch(sw),
+                    _ => unreachable!(),
+                },
+                OpCode::getstatic => self.get_static(thread, instr.int()),
+                OpCode::putstatic => self.put_static(thread, instr.int()),
+                OpCode::getfield => self.get_field(thread, instr.int()),
+                OpCode::putfield => self.put_field(thread, instr.int()),
+                OpCode::invokevirtual => self.invoke_virtual(thread, instr.int()),
+                OpCode::invokespecial => self.invoke_special(thread, instr.int()),
+                OpCode::invokestatic => self.invoke_static(thread, instr.int()),
+                OpCode::invokeinterface => self.invoke_interface(thread, instr.int()),
+                OpCode::invokedynamic => self.invoke_dynamic(),
+                OpCode::new => self.new_(thread, instr.int()),
+                OpCode::newarray => self.new_array(thread, instr.int()),
+                OpCode::anewarray => self.anew_array(thread, instr.int()),
+                OpCode::arraylength => self.array_length(thread),
+                OpCode::checkcast => self.check_cast(thread, instr.int()),
+                OpCode::instanceof => self.instance_of(instr.int()),
+                OpCode::monitorenter => self.monitor_enter(thread),
+                OpCode::monitorexit => self.monitor_exit(thread),
+                //folded into the widened instruction by Bytecode::decode
+                OpCode::wide => unreachable!(),
+                OpCode::multianewarray => match instr.operand {
+                    Operand::MultiANewArray(cp_idx, dimension) => {
+                        self.multi_anew_array(thread, cp_idx, dimension)
+                    }
+                    _ => unreachable!(),
+                },
+                OpCode::ifnull => self.if_null(instr.branch()),
+                OpCode::ifnonnull => self.if_non_null(instr.branch()),
+                OpCode::goto_w => self.goto_w(instr.branch()),
+                OpCode::jsr_w => self.jsr_w(instr.branch()),
+                OpCode::breakpoint => self.breakpoint(thread),
+                OpCode::impdep1 | OpCode::impdep2 => self.impdep(thread, op_code),
+            }
+
+            if verify_stack && !thread.is_meet_ex() {
+                let r = self.stack.check_overflow();
+                self.verify_stack_report(r, cls_name, method, bci, op_code);
+            }
 
-    fn read_u1(&mut self) -> usize {
-        let v = self.code[self.pc as usize];
-        self.pc += 1;
-        v as usize
+            //the instruction loaded a class over -XX:MaxMetaspaceSize
+            if runtime::metaspace::take_exhausted() {
+                meet_ex(
+                    thread,
+                    consts::J_OOM,
+                    Some(exception::OOM_METASPACE.to_string()),
+                );
+                //loading OutOfMemoryError itself doesn't throw again
+                runtime::metaspace::take_exhausted();
+            }
+
+            if thread.is_meet_ex() {
+                // error!("meet ex: {:?}, frame_id = {}", op_code, self.frame_id);
+                let ex = thread.take_ex().unwrap();
+                let start = Instant::now();
+                let r = self.try_handle_exception(thread, ex);
+                exception::record_handle(start);
+                match r {
+                    Ok(_) => (),
+                    Err(ex) => {
+                        thread.set_ex(ex);
+                        break;
+                    }
+                }
+            }
+        }
     }
+}
 
-    fn read_byte(&mut self) -> u8 {
-        let v = self.code[self.pc as usize];
-        self.pc += 1;
-        v
+//the class of an object for checkcast & instanceof, None for null
+//name like "[[I", "[[Ljava/lang/String;", counts.len() <= dimension of name
+fn new_multi_array(
+    cl: Option<runtime::ClassLoader>,
+    name: &[u8],
+    counts: &[i32],
+) -> Result<OopRef, AllocErr> {
+    let len = counts[0] as usize;
+
+    if counts.len() == 1 {
+        return match name[1] {
+            t @ (b'Z' | b'C' | b'F' | b'D' | b'B' | b'S' | b'I' | b'J') => {
+                OopDesc::try_new_type_ary(t, len)
+            }
+            _ => {
+                let ary_cls = runtime::require_class3(cl, name).unwrap();
+                OopDesc::try_new_ref_ary(ary_cls, len)
+            }
+        };
     }
 
-    fn read_opcode(&mut self) -> Option<&U1> {
-        let v = self.code.get(self.pc as usize);
-        self.pc += 1;
-        v
+    let ary_cls = runtime::require_class3(cl, name).unwrap();
+    let ary = OopDesc::try_new_ref_ary(ary_cls, len)?;
+    {
+        let mut ary = ary.lock().unwrap();
+        if let Oop::Array(ary) = &mut ary.v {
+            for it in ary.elements.iter_mut() {
+                *it = new_multi_array(cl, &name[1..], &counts[1..])?;
+            }
+        }
     }
+    Ok(ary)
+}
 
-    fn read_u2(&mut self) -> usize {
-        self.read_u1() << 8 | self.read_u1()
+//helper methods
+impl Frame {
+    //-XX:+VerifyStack, a bad stack depth is an interpreter bug, report & abort
+    fn verify_stack_report(
+        &self,
+        r: Result<(), StackErr>,
+        cls_name: &str,
+        method: &str,
+        bci: i32,
+        op_code: OpCode,
+    ) {
+        if let Err(e) = r {
+            let reason = match e {
+                StackErr::Underflow { expected, actual } => format!(
+                    "stack underflow, expected at least {} slots, actual depth {}",
+                    expected, actual
+                ),
+                StackErr::Overflow { max, actual } => {
+                    format!("stack overflow, max_stack {}, actual depth {}", max, actual)
+                }
+            };
+            let report = format!(
+                "VerifyStack: {} at {}:{} bci={} op={:?}",
+                reason, cls_name, method, bci, op_code
+            );
+            error!("{}", report);
+            panic!("{}", report);
+        }
     }
 
     fn load_constant(&mut self, pos: usize, thread: &mut JavaThread) {
@@ -424,21 +524,33 @@ impl Frame {
             ConstantType::Long { v } => self.stack.push_long2(*v),
             ConstantType::Double { v } => self.stack.push_double2(*v),
             ConstantType::String { string_index } => {
-                let s = constant_pool::get_utf8(&self.cp, *string_index as usize).unwrap();
-                let s = util::oop::new_java_lang_string3(thread, s.as_slice());
+                let s = match self.cp_cache.get_string(pos) {
+                    Some(s) => s,
+                    None => {
+                        let s = constant_pool::get_utf8(&self.cp, *string_index as usize).unwrap();
+                        let s = util::oop::new_constant_string(thread, s.as_slice());
+                        self.cp_cache.put_string(pos, s.clone());
+                        s
+                    }
+                };
                 self.stack.push_ref(s);
             }
             ConstantType::Class { name_index } => {
-                let name = constant_pool::get_utf8(&self.cp, *name_index as usize).unwrap();
-                let name = unsafe { std::str::from_utf8_unchecked(name.as_slice()) };
-                let cl = { self.class.lock().unwrap().class_loader.clone() };
-                trace!("load_constant name={}, cl={:?}", name, cl);
-                let class = runtime::require_class3(cl, name.as_bytes()).unwrap();
-
-                {
-                    let mut class = class.lock().unwrap();
-                    class.init_class(thread);
-                }
+                let class = match self.cp_cache.get_class(pos) {
+                    Some(class) => class,
+                    None => {
+                        let name = constant_pool::get_utf8(&self.cp, *name_index as usize).unwrap();
+                        let cl = { self.class.lock().unwrap().class_loader.clone() };
+                        trace!(
+                            "load_constant name={}, cl={:?}",
+                            util::mutf8::display(name.as_slice()),
+                            cl
+                        );
+                        let class = runtime::require_class3(cl, name.as_slice()).unwrap();
+                        self.cp_cache.put_class(pos, class.clone());
+                        class
+                    }
+                };
 
                 oop::class::init_class_fully(thread, class.clone());
 
@@ -446,33 +558,192 @@ impl Frame {
 
                 self.stack.push_ref(mirror);
             }
+            ConstantType::MethodType { desc_index } => {
+                let mt = match self.cp_cache.get_constant(pos) {
+                    Some(mt) => mt,
+                    None => {
+                        let desc = constant_pool::get_utf8(&self.cp, *desc_index as usize).unwrap();
+                        match runtime::invoke::new_method_type(thread, desc.as_slice()) {
+                            Some(mt) => {
+                                self.cp_cache.put_constant(pos, mt.clone());
+                                mt
+                            }
+                            None => return,
+                        }
+                    }
+                };
+                self.stack.push_ref(mt);
+            }
+            ConstantType::MethodHandle {
+                ref_kind,
+                ref_index,
+            } => {
+                let mh = match self.cp_cache.get_constant(pos) {
+                    Some(mh) => mh,
+                    None => match self.resolve_method_handle(thread, *ref_kind, *ref_index) {
+                        Some(mh) => {
+                            self.cp_cache.put_constant(pos, mh.clone());
+                            mh
+                        }
+                        None => return,
+                    },
+                };
+                self.stack.push_ref(mh);
+            }
+            ConstantType::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                let (bsm_index, nt_index) = (*bootstrap_method_attr_index, *name_and_type_index);
+                let v = match self.cp_cache.get_constant(pos) {
+                    Some(v) => v,
+                    None => match self.resolve_dynamic(thread, bsm_index, nt_index) {
+                        Some(v) => {
+                            self.cp_cache.put_constant(pos, v.clone());
+                            v
+                        }
+                        None => return,
+                    },
+                };
+
+                let (_, desc) = constant_pool::get_name_and_type(&self.cp, nt_index as usize);
+                match FieldSignature::new(desc.unwrap().as_slice()).field_type {
+                    ArgType::Object(_) | ArgType::Array(_) => self.stack.push_ref(v),
+                    //primitives come boxed
+                    _ => match runtime::reflect::unbox(&v) {
+                        Some((_, v)) => match v.lock().unwrap().v {
+                            Oop::Int(v) => self.stack.push_int(v),
+                            Oop::Long(v) => self.stack.push_long(v),
+                            Oop::Float(v) => self.stack.push_float(v),
+                            Oop::Double(v) => self.stack.push_double(v),
+                            _ => unreachable!(),
+                        },
+                        None => {
+                            let msg = "condy of primitive type is not boxed".to_string();
+                            meet_ex(thread, consts::J_BOOTSTRAP_METHOD_ERROR, Some(msg));
+                        }
+                    },
+                }
+            }
             _ => unreachable!(),
         }
     }
 
-    fn goto_abs(&mut self, pc: i32) {
-        self.pc = pc;
+    //the member of a CONSTANT_MethodHandle is a Fieldref, Methodref or
+    //InterfaceMethodref
+    fn resolve_method_handle(
+        &mut self,
+        thread: &mut JavaThread,
+        ref_kind: u8,
+        ref_index: u16,
+    ) -> Option<OopRef> {
+        let (defc, name, desc) = self.member_ref(ref_index);
+        runtime::invoke::new_method_handle(
+            thread,
+            &self.class,
+            ref_kind,
+            &defc,
+            name.as_slice(),
+            desc.as_slice(),
+        )
+    }
+
+    //(class, name, descriptor) of a Fieldref, Methodref or
+    //InterfaceMethodref
+    fn member_ref(&self, ref_index: u16) -> (ClassRef, BytesRef, BytesRef) {
+        let (class_index, name_and_type_index) = match &self.cp[ref_index as usize] {
+            ConstantType::FieldRef {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantType::MethodRef {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantType::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => (*class_index, *name_and_type_index),
+            _ => unreachable!(),
+        };
+        let defc = require_class2(class_index, &self.cp).unwrap();
+        let (name, desc) = constant_pool::get_name_and_type(&self.cp, name_and_type_index as usize);
+        (defc, name.unwrap(), desc.unwrap())
     }
 
-    fn goto_by_offset(&mut self, branch: i32) {
-        self.pc += branch;
-    }
+    //the static arguments are loaded like ldc, primitive arguments boxed
+    fn resolve_dynamic(
+        &mut self,
+        thread: &mut JavaThread,
+        bsm_index: u16,
+        nt_index: u16,
+    ) -> Option<OopRef> {
+        let bsm = {
+            let cls = self.class.lock().unwrap();
+            match &cls.kind {
+                oop::class::ClassKind::Instance(cls_obj) => cls_obj
+                    .bootstrap_methods
+                    .as_ref()
+                    .and_then(|it| it.get(bsm_index as usize))
+                    .cloned(),
+                _ => None,
+            }
+        };
+        let bsm = match bsm {
+            Some(bsm) => bsm,
+            None => {
+                let msg = format!("no bootstrap method #{}", bsm_index);
+                meet_ex(thread, consts::J_BOOTSTRAP_METHOD_ERROR, Some(msg));
+                return None;
+            }
+        };
 
-    fn goto_by_offset_with_occupied(&mut self, branch: i32, occupied: i32) {
-        self.goto_by_offset(branch);
-        self.goto_by_offset(-(occupied - 1));
-    }
+        let bsm_method = match &self.cp[bsm.method_ref as usize] {
+            ConstantType::MethodHandle {
+                ref_kind,
+                ref_index,
+            } => {
+                let (defc, name, desc) = self.member_ref(*ref_index);
+                let (name, desc) = (name.as_slice(), desc.as_slice());
+                runtime::invoke::bootstrap_method(thread, *ref_kind, &defc, name, desc)?
+            }
+            _ => unreachable!(),
+        };
 
-    fn goto_by_offset_hardcoded(&mut self, occupied: i32) {
-        let high = self.code[self.pc as usize] as i16;
-        let low = self.code[(self.pc + 1) as usize] as i16;
-        let branch = (high << 8) | low;
-        self.goto_by_offset_with_occupied(branch as i32, occupied);
+        let mut args = Vec::with_capacity(bsm.args.len());
+        for &idx in bsm.args.iter() {
+            let t = match &self.cp[idx as usize] {
+                ConstantType::Integer { .. } => Some(ArgType::Int),
+                ConstantType::Float { .. } => Some(ArgType::Float),
+                ConstantType::Long { .. } => Some(ArgType::Long),
+                ConstantType::Double { .. } => Some(ArgType::Double),
+                _ => None,
+            };
+            self.load_constant(idx as usize, thread);
+            if thread.is_meet_ex() {
+                return None;
+            }
+            let v = match t {
+                Some(t) => runtime::reflect::box_return(thread, &t, &mut self.stack),
+                None => self.stack.pop_ref(),
+            };
+            args.push(v);
+        }
+
+        let (name, desc) = constant_pool::get_name_and_type(&self.cp, nt_index as usize);
+        let (name, desc) = (name.unwrap(), desc.unwrap());
+        runtime::invoke::new_dynamic_constant(
+            thread,
+            &self.class,
+            bsm_method,
+            name.as_slice(),
+            desc.as_slice(),
+            args,
+        )
     }
 
-    fn goto_abs_with_occupied(&mut self, pc: i32, occupied: i32) {
-        self.goto_abs(pc);
-        self.goto_by_offset(-(occupied - 1));
+    fn goto_abs(&mut self, pc: i32) {
+        self.pc = pc;
     }
 
     fn set_return(&mut self, v: Option<OopRef>) {
@@ -486,14 +757,18 @@ impl Frame {
         idx: i32,
         is_static: bool,
     ) {
-        let fir = { field::get_field_ref(thread, &self.cp, idx as usize, is_static) };
+        let fir = self.resolve_field(thread, idx as usize, is_static);
+        //the class failed to init
+        if thread.is_meet_ex() {
+            return;
+        }
 
         assert_eq!(fir.field.is_static(), is_static);
 
         let fid = fir.field.get_id();
         trace!(
             "get_field_helper = {}, is_static = {}",
-            unsafe { std::str::from_utf8_unchecked(fid.as_slice()) },
+            util::mutf8::display(fid.as_slice()),
             is_static
         );
 
@@ -534,14 +809,17 @@ impl Frame {
     }
 
     fn put_field_helper(&mut self, thread: &mut JavaThread, idx: i32, is_static: bool) {
-        let fir = { field::get_field_ref(thread, &self.cp, idx as usize, is_static) };
+        let fir = self.resolve_field(thread, idx as usize, is_static);
+        if thread.is_meet_ex() {
+            return;
+        }
 
         assert_eq!(fir.field.is_static(), is_static);
 
         let fid = fir.field.get_id();
         trace!(
             "put_field_helper={}, is_static={}",
-            unsafe { std::str::from_utf8_unchecked(fid.as_slice()) },
+            util::mutf8::display(fid.as_slice()),
             is_static
         );
 
@@ -585,6 +863,46 @@ impl Frame {
         }
     }
 
+    //resolve once, then from the class's cp cache
+    fn resolve_class(&self, idx: usize) -> Option<ClassRef> {
+        match self.cp_cache.get_class(idx) {
+            Some(class) => Some(class),
+            None => {
+                let class = require_class2(idx as U2, &self.cp)?;
+                self.cp_cache.put_class(idx, class.clone());
+                Some(class)
+            }
+        }
+    }
+
+    //not cached if the class failed to init, the next execution throws
+    //NoClassDefFoundError
+    fn resolve_field(&self, thread: &mut JavaThread, idx: usize, is_static: bool) -> FieldIdRef {
+        match self.cp_cache.get_field(idx) {
+            Some(fir) => fir,
+            None => {
+                let fir = field::get_field_ref(thread, &self.cp, idx, is_static);
+                if !thread.is_meet_ex() {
+                    self.cp_cache.put_field(idx, fir.clone());
+                }
+                fir
+            }
+        }
+    }
+
+    fn resolve_method(&self, jt: &mut JavaThread, idx: usize) -> Result<MethodIdRef, ()> {
+        match self.cp_cache.get_method(idx) {
+            Some(mir) => Ok(mir),
+            None => {
+                let mir = oop::method::get_method_ref(jt, &self.cp, idx)?;
+                if !jt.is_meet_ex() {
+                    self.cp_cache.put_method(idx, mir.clone());
+                }
+                Ok(mir)
+            }
+        }
+    }
+
     fn invoke_helper(
         &mut self,
         jt: &mut JavaThread,
@@ -592,13 +910,19 @@ impl Frame {
         idx: usize,
         force_no_resolve: bool,
     ) {
-        let mir = { oop::method::get_method_ref(jt, &self.cp, idx) };
-
-        match mir {
+        match self.resolve_method(jt, idx) {
+            //the class failed to init
+            Ok(_) if jt.is_meet_ex() => (),
             Ok(mir) => {
                 assert_eq!(mir.method.is_static(), is_static);
 
+                if let Some(intrinsic) = intrinsics::lookup(&mir) {
+                    intrinsic(jt, &mut self.stack);
+                    return;
+                }
+
                 match runtime::java_call::JavaCall::new(jt, &mut self.stack, mir) {
+                    Ok(jc) if !force_no_resolve => self.invoke_cached(jt, jc, idx),
                     Ok(mut jc) => {
                         jc.invoke(jt, &mut self.stack, force_no_resolve);
                     }
@@ -610,6 +934,79 @@ impl Frame {
             Err(_) => unreachable!("NotFound method"),
         }
     }
+
+    //invokevirtual & invokeinterface, through the inline cache of the site
+    fn invoke_cached(&mut self, jt: &mut JavaThread, mut jc: JavaCall, idx: usize) {
+        let receiver_cls = {
+            let this = jc.args[0].lock().unwrap();
+            match &this.v {
+                Oop::Inst(inst) => Some(inst.class.clone()),
+                _ => None,
+            }
+        };
+
+        let caches = self.mir.method.inline_caches.clone();
+        match receiver_cls {
+            Some(cls) => {
+                match caches.get(self.bci, &cls) {
+                    Some(mir) => jc.mir = mir,
+                    None => {
+                        //the receiver of a mono site was checked when cached,
+                        //interface sites are never final, any receiver
+                        //would hit
+                        let is_itf_site =
+                            matches!(self.cp[idx], ConstantType::InterfaceMethodRef { .. });
+                        if is_itf_site && !self.check_interface_receiver(jt, idx, &cls) {
+                            return;
+                        }
+
+                        let declared = jc.mir.clone();
+                        if jc.resolve_virtual_method(jt, false).is_err() {
+                            return;
+                        }
+
+                        //not overridden so far, any receiver gets the same target
+                        let is_final = !is_itf_site
+                            && Arc::ptr_eq(&declared, &jc.mir)
+                            && cha::make_final(&caches, self.bci, &jc.mir);
+                        if !is_final {
+                            caches.update(self.bci, cls, jc.mir.clone());
+                        }
+                    }
+                }
+                jc.invoke(jt, &mut self.stack, true);
+            }
+            None => jc.invoke(jt, &mut self.stack, false),
+        }
+    }
+
+    //invokeinterface, the receiver must implement the interface named by
+    //the InterfaceMethodref, else IncompatibleClassChangeError
+    fn check_interface_receiver(
+        &self,
+        jt: &mut JavaThread,
+        idx: usize,
+        receiver: &ClassRef,
+    ) -> bool {
+        let class_index = match &self.cp[idx] {
+            ConstantType::InterfaceMethodRef { class_index, .. } => *class_index,
+            _ => unreachable!(),
+        };
+        let itf = require_class2(class_index, &self.cp).unwrap();
+        if cmp::instance_of(receiver.clone(), itf.clone()) {
+            return true;
+        }
+
+        let receiver = { receiver.lock().unwrap().name.clone() };
+        let itf = { itf.lock().unwrap().name.clone() };
+        let msg = format!(
+            "Class {} does not implement the requested interface {}",
+            String::from_utf8_lossy(receiver.as_slice()).replace('/', "."),
+            String::from_utf8_lossy(itf.as_slice()).replace('/', ".")
+        );
+        meet_ex(jt, consts::J_ICCE, Some(msg));
+        false
+    }
 }
 
 //handle exception
@@ -631,7 +1028,7 @@ impl Frame {
         let handler = self
             .mir
             .method
-            .find_exception_handler(&self.cp, self.pc as u16, ex_cls);
+            .find_exception_handler(self.bci as u16, ex_cls);
         match handler {
             Some(pc) => {
                 self.stack.clear();
@@ -649,7 +1046,7 @@ impl Frame {
             }
 
             None => {
-                let line_num = self.mir.method.get_line_num(self.pc as u16).unwrap_or(0);
+                let line_num = self.mir.method.get_line_num(self.bci as u16).unwrap_or(0);
 
                 info!(
                     "NotFound Exception Handler: line={}, frame_id={}, {}:{}",
@@ -699,11 +1096,11 @@ impl Frame {
     }
 
     pub fn fconst_1(&mut self) {
-        self.stack.push_const1(false);
+        self.stack.push_float(1.0);
     }
 
     pub fn dconst_1(&mut self) {
-        self.stack.push_const1(true);
+        self.stack.push_double(1.0);
     }
 
     pub fn iconst_2(&mut self) {
@@ -711,7 +1108,7 @@ impl Frame {
     }
 
     pub fn fconst_2(&mut self) {
-        self.stack.push_const2();
+        self.stack.push_float(2.0);
     }
 
     pub fn iconst_3(&mut self) {
@@ -726,88 +1123,49 @@ impl Frame {
         self.stack.push_const5();
     }
 
-    pub fn sipush(&mut self) {
-        let v = self.read_i2();
+    pub fn sipush(&mut self, v: i32) {
         self.stack.push_int(v);
     }
 
-    pub fn bipush(&mut self) {
-        let v = (self.read_byte() as i8) as i32;
+    pub fn bipush(&mut self, v: i32) {
         self.stack.push_int(v);
     }
 
-    pub fn ldc(&mut self, thread: &mut JavaThread) {
-        let pos = self.read_u1();
+    pub fn ldc(&mut self, thread: &mut JavaThread, pos: usize) {
         self.load_constant(pos, thread);
     }
 
-    pub fn ldc_w(&mut self, thread: &mut JavaThread) {
-        let pos = self.read_u2();
+    pub fn ldc_w(&mut self, thread: &mut JavaThread, pos: usize) {
         self.load_constant(pos, thread);
     }
 
-    pub fn ldc2_w(&mut self, thread: &mut JavaThread) {
-        self.ldc_w(thread);
+    pub fn ldc2_w(&mut self, thread: &mut JavaThread, pos: usize) {
+        self.ldc_w(thread, pos);
     }
 
-    pub fn iload(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn iload(&mut self, pos: usize) {
         let v = self.local.get_int(pos);
         self.stack.push_int(v);
-
-        self.op_widen = false;
     }
 
-    pub fn lload(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn lload(&mut self, pos: usize) {
         let v = self.local.get_long(pos);
         self.stack.push_long(v);
-
-        self.op_widen = false;
     }
 
-    pub fn fload(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn fload(&mut self, pos: usize) {
         let v = self.local.get_float(pos);
         self.stack.push_float(v);
-
-        self.op_widen = false;
     }
 
-    pub fn dload(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn dload(&mut self, pos: usize) {
         let v = self.local.get_double(pos);
         self.stack.push_double(v);
-
-        self.op_widen = false;
     }
 
-    pub fn aload(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn aload(&mut self, pos: usize) {
         let v = self.local.get_ref(pos);
         self.stack.push_ref(v);
-
-        self.op_widen = false;
     }
 
     pub fn iload_0(&mut self) {
@@ -997,12 +1355,8 @@ impl Frame {
         match &rf.v {
             Oop::TypeArray(ary) => match ary {
                 oop::TypeArrayValue::Long(ary) => {
-                    let len = ary.len();
-                    if (pos < 0) || (pos as usize >= len) {
-                        let msg = format!("length is {}, but index is {}", len, pos);
-                        meet_ex(thread, consts::J_ARRAY_INDEX_OUT_OF_BOUNDS, Some(msg));
-                    } else {
-                        self.stack.push_long(ary[pos as usize]);
+                    if let Some(i) = check_index(thread, ary.len(), pos) {
+                        self.stack.push_long(ary[i]);
                     }
                 }
                 _ => unreachable!(),
@@ -1021,12 +1375,8 @@ impl Frame {
         match &rf.v {
             Oop::TypeArray(ary) => match ary {
                 oop::TypeArrayValue::Float(ary) => {
-                    let len = ary.len();
-                    if (pos < 0) || (pos as usize >= len) {
-                        let msg = format!("length is {}, but index is {}", len, pos);
-                        meet_ex(thread, consts::J_ARRAY_INDEX_OUT_OF_BOUNDS, Some(msg));
-                    } else {
-                        self.stack.push_float(ary[pos as usize]);
+                    if let Some(i) = check_index(thread, ary.len(), pos) {
+                        self.stack.push_float(ary[i]);
                     }
                 }
                 _ => unreachable!(),
@@ -1045,12 +1395,8 @@ impl Frame {
         match &rf.v {
             Oop::TypeArray(ary) => match ary {
                 oop::TypeArrayValue::Double(ary) => {
-                    let len = ary.len();
-                    if (pos < 0) || (pos as usize >= len) {
-                        let msg = format!("length is {}, but index is {}", len, pos);
-                        meet_ex(thread, consts::J_ARRAY_INDEX_OUT_OF_BOUNDS, Some(msg));
-                    } else {
-                        self.stack.push_double(ary[pos as usize]);
+                    if let Some(i) = check_index(thread, ary.len(), pos) {
+                        self.stack.push_double(ary[i]);
                     }
                 }
                 _ => unreachable!(),
@@ -1068,13 +1414,8 @@ impl Frame {
         let rf = rf.lock().unwrap();
         match &rf.v {
             Oop::Array(ary) => {
-                let len = ary.elements.len();
-                //                info!("aaload pos={}, len={}", pos, len);
-                if (pos < 0) || (pos as usize >= len) {
-                    let msg = format!("length is {}, but index is {}", len, pos);
-                    meet_ex(thread, consts::J_ARRAY_INDEX_OUT_OF_BOUNDS, Some(msg));
-                } else {
-                    let v = ary.elements[pos as usize].clone();
+                if let Some(i) = check_index(thread, ary.elements.len(), pos) {
+                    let v = ary.elements[i].clone();
                     self.stack.push_ref(v);
                 }
             }
@@ -1085,64 +1426,35 @@ impl Frame {
         }
     }
 
-    pub fn istore(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn istore(&mut self, pos: usize) {
         let v = self.stack.pop_int();
         self.local.set_int(pos, v);
-
-        self.op_widen = false;
     }
 
-    pub fn lstore(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn lstore(&mut self, pos: usize) {
         let v = self.stack.pop_long();
         self.local.set_long(pos, v);
-
-        self.op_widen = false;
     }
 
-    pub fn fstore(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn fstore(&mut self, pos: usize) {
         let v = self.stack.pop_float();
         self.local.set_float(pos, v);
-
-        self.op_widen = false;
     }
 
-    pub fn dstore(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
+    pub fn dstore(&mut self, pos: usize) {
         let v = self.stack.pop_double();
         self.local.set_double(pos, v);
-
-        self.op_widen = false;
     }
 
-    pub fn astore(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
+    pub fn astore(&mut self, pos: usize) {
+        if self.stack.is_ref_on_top() {
+            let v = self.stack.pop_ref();
+            self.local.set_ref(pos, v);
         } else {
-            self.read_u1()
-        };
-        let v = self.stack.pop_ref();
-        self.local.set_ref(pos, v);
-
-        self.op_widen = false;
+            //the returnAddress of a jsr, stored for ret
+            let v = self.stack.pop_return_address();
+            self.local.set_return_address(pos, v);
+        }
     }
 
     pub fn istore_0(&mut self) {
@@ -1226,23 +1538,19 @@ impl Frame {
     }
 
     pub fn astore_0(&mut self) {
-        let v = self.stack.pop_ref();
-        self.local.set_ref(0, v);
+        self.astore(0);
     }
 
     pub fn astore_1(&mut self) {
-        let v = self.stack.pop_ref();
-        self.local.set_ref(1, v);
+        self.astore(1);
     }
 
     pub fn astore_2(&mut self) {
-        let v = self.stack.pop_ref();
-        self.local.set_ref(2, v);
+        self.astore(2);
     }
 
     pub fn astore_3(&mut self) {
-        let v = self.stack.pop_ref();
-        self.local.set_ref(3, v);
+        self.astore(3);
     }
 
     pub fn bastore(&mut self, thread: &mut JavaThread) {
@@ -1517,6 +1825,8 @@ impl Frame {
         self.stack.push_double(v1 * v2);
     }
 
+    //JVMS idiv/ldiv/irem/lrem: MIN_VALUE / -1 overflows to MIN_VALUE, the
+    //remainder is 0, Rust / & % would panic. Only a zero divisor throws.
     pub fn idiv(&mut self, thread: &mut JavaThread) {
         let v2 = self.stack.pop_int();
         let v1 = self.stack.pop_int();
@@ -1527,7 +1837,7 @@ impl Frame {
                 Some("divide by zero".to_string()),
             );
         } else {
-            self.stack.push_int(v1 / v2);
+            self.stack.push_int(v1.wrapping_div(v2));
         }
     }
 
@@ -1541,36 +1851,21 @@ impl Frame {
                 Some("divide by zero".to_string()),
             );
         } else {
-            self.stack.push_long(v1 / v2);
+            self.stack.push_long(v1.wrapping_div(v2));
         }
     }
 
-    pub fn fdiv(&mut self, thread: &mut JavaThread) {
+    //IEEE division, no ArithmeticException: x / 0.0 is +-Infinity, 0.0 / 0.0 is NaN
+    pub fn fdiv(&mut self) {
         let v2 = self.stack.pop_float();
         let v1 = self.stack.pop_float();
-        if v2 == 0.0 {
-            meet_ex(
-                thread,
-                consts::J_ARITHMETIC_EX,
-                Some("divide by zero".to_string()),
-            );
-        } else {
-            self.stack.push_float(v1 / v2);
-        }
+        self.stack.push_float(v1 / v2);
     }
 
-    pub fn ddiv(&mut self, thread: &mut JavaThread) {
+    pub fn ddiv(&mut self) {
         let v2 = self.stack.pop_double();
         let v1 = self.stack.pop_double();
-        if v2 == 0.0 {
-            meet_ex(
-                thread,
-                consts::J_ARITHMETIC_EX,
-                Some("divide by zero".to_string()),
-            );
-        } else {
-            self.stack.push_double(v1 / v2);
-        }
+        self.stack.push_double(v1 / v2);
     }
 
     pub fn irem(&mut self, thread: &mut JavaThread) {
@@ -1583,7 +1878,7 @@ impl Frame {
                 Some("divide by zero".to_string()),
             );
         } else {
-            self.stack.push_int(v1 - (v1 / v2) * v2);
+            self.stack.push_int(v1.wrapping_rem(v2));
         }
     }
 
@@ -1597,16 +1892,25 @@ impl Frame {
                 Some("divide by zero".to_string()),
             );
         } else {
-            self.stack.push_long(v1 - (v1 / v2) * v2);
+            self.stack.push_long(v1.wrapping_rem(v2));
         }
     }
 
+    //JVMS frem/drem: truncating division, the result has the sign of the
+    //dividend (not IEEE 754 remainder, that is Math.IEEEremainder). NaN if
+    //either is NaN, the dividend is infinite or the divisor is zero; the
+    //dividend if the divisor is infinite. Rust % is exactly this (fmod).
+    //Never throws.
     pub fn frem(&mut self) {
-        panic!("Use of deprecated instruction frem, please check your Java compiler");
+        let v2 = self.stack.pop_float();
+        let v1 = self.stack.pop_float();
+        self.stack.push_float(v1 % v2);
     }
 
     pub fn drem(&mut self) {
-        panic!("Use of deprecated instruction drem, please check your Java compiler");
+        let v2 = self.stack.pop_double();
+        let v1 = self.stack.pop_double();
+        self.stack.push_double(v1 % v2);
     }
 
     pub fn ineg(&mut self) {
@@ -1619,12 +1923,15 @@ impl Frame {
         self.stack.push_long(-v);
     }
 
+    //flips the sign bit: -(0.0) is -0.0, not 0.0 - x; a NaN stays NaN
     pub fn fneg(&mut self) {
-        panic!("Use of deprecated instruction fneg, please check your Java compiler");
+        let v = self.stack.pop_float();
+        self.stack.push_float(-v);
     }
 
     pub fn dneg(&mut self) {
-        panic!("Use of deprecated instruction dneg, please check your Java compiler");
+        let v = self.stack.pop_double();
+        self.stack.push_double(-v);
     }
 
     pub fn ishl(&mut self) {
@@ -1720,23 +2027,10 @@ impl Frame {
         self.stack.push_long(v1 ^ v2);
     }
 
-    pub fn iinc(&mut self) {
-        let pos = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
-        let factor = if self.op_widen {
-            (self.read_u2() as i16) as i32
-        } else {
-            (self.read_byte() as i8) as i32
-        };
-
+    pub fn iinc(&mut self, pos: usize, factor: i32) {
         let v = self.local.get_int(pos);
         let v = v.wrapping_add(factor);
         self.local.set_int(pos, v);
-
-        self.op_widen = false;
     }
 
     pub fn i2l(&mut self) {
@@ -1925,274 +2219,133 @@ impl Frame {
         }
     }
 
-    pub fn ifeq(&mut self) {
+    pub fn ifeq(&mut self, target: i32) {
         let v = self.stack.pop_int();
         if v == 0 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn ifne(&mut self) {
+    pub fn ifne(&mut self, target: i32) {
         let v = self.stack.pop_int();
         if v != 0 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn iflt(&mut self) {
+    pub fn iflt(&mut self, target: i32) {
         let v = self.stack.pop_int();
         if v < 0 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn ifge(&mut self) {
+    pub fn ifge(&mut self, target: i32) {
         let v = self.stack.pop_int();
         if v >= 0 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn ifgt(&mut self) {
+    pub fn ifgt(&mut self, target: i32) {
         let v = self.stack.pop_int();
         if v > 0 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn ifle(&mut self) {
+    pub fn ifle(&mut self, target: i32) {
         let v = self.stack.pop_int();
         if v <= 0 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_icmpeq(&mut self) {
+    pub fn if_icmpeq(&mut self, target: i32) {
         let v2 = self.stack.pop_int();
         let v1 = self.stack.pop_int();
         if v1 == v2 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_icmpne(&mut self) {
+    pub fn if_icmpne(&mut self, target: i32) {
         let v2 = self.stack.pop_int();
         let v1 = self.stack.pop_int();
         if v1 != v2 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_icmplt(&mut self) {
+    pub fn if_icmplt(&mut self, target: i32) {
         let v2 = self.stack.pop_int();
         let v1 = self.stack.pop_int();
         if v1 < v2 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_icmpge(&mut self) {
+    pub fn if_icmpge(&mut self, target: i32) {
         let v2 = self.stack.pop_int();
         let v1 = self.stack.pop_int();
         if v1 >= v2 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_icmpgt(&mut self) {
+    pub fn if_icmpgt(&mut self, target: i32) {
         let v2 = self.stack.pop_int();
         let v1 = self.stack.pop_int();
         if v1 > v2 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_icmple(&mut self) {
+    pub fn if_icmple(&mut self, target: i32) {
         let v2 = self.stack.pop_int();
         let v1 = self.stack.pop_int();
         if v1 <= v2 {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_acmpeq(&mut self) {
+    pub fn if_acmpeq(&mut self, target: i32) {
         let v2 = self.stack.pop_ref();
         let v1 = self.stack.pop_ref();
 
         if util::oop::if_acmpeq(v1, v2) {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_acmpne(&mut self) {
+    pub fn if_acmpne(&mut self, target: i32) {
         let v2 = self.stack.pop_ref();
         let v1 = self.stack.pop_ref();
 
         if !util::oop::if_acmpeq(v1, v2) {
-            self.goto_by_offset_hardcoded(2);
-        } else {
-            self.pc += 2;
+            self.goto_abs(target);
         }
     }
 
-    pub fn goto(&mut self) {
-        self.goto_by_offset_hardcoded(2);
-    }
-
-    pub fn jsr(&mut self) {
-        self.pc += 2;
-        panic!("Use of deprecated instruction jsr, please check your Java compiler");
+    pub fn goto(&mut self, target: i32) {
+        self.goto_abs(target);
     }
 
-    pub fn ret(&mut self) {
-        let pc = if self.op_widen {
-            self.read_u2()
-        } else {
-            self.read_u1()
-        };
-        self.pc = pc as i32;
-
-        self.op_widen = true;
+    //subroutine call, old javac compiles finally this way
+    pub fn jsr(&mut self, target: i32) {
+        self.stack.push_return_address(ReturnAddress(self.pc));
+        self.goto_abs(target);
     }
 
-    pub fn table_switch(&mut self) {
-        let mut bc = self.pc - 1;
-        let origin_bc = bc;
-        if bc % 4 != 0 {
-            bc += (4 - bc % 4);
-        } else {
-            bc += 4;
-        }
-        let mut ptr = bc as usize;
-        let default_byte = [
-            self.code[ptr],
-            self.code[ptr + 1],
-            self.code[ptr + 2],
-            self.code[ptr + 3],
-        ];
-        let default_byte = i32::from_be_bytes(default_byte);
-        let low_byte = [
-            self.code[ptr + 4],
-            self.code[ptr + 5],
-            self.code[ptr + 6],
-            self.code[ptr + 7],
-        ];
-        let low_byte = i32::from_be_bytes(low_byte);
-        let high_byte = [
-            self.code[ptr + 8],
-            self.code[ptr + 9],
-            self.code[ptr + 10],
-            self.code[ptr + 11],
-        ];
-        let high_byte = i32::from_be_bytes(high_byte);
-        let num = high_byte - low_byte + 1;
-        ptr += 12;
-
-        // switch-case jump table
-        let mut jump_table = Vec::with_capacity(num as usize);
-        for pos in 0..num {
-            let pos = [
-                self.code[ptr],
-                self.code[ptr + 1],
-                self.code[ptr + 2],
-                self.code[ptr + 3],
-            ];
-            let pos = i32::from_be_bytes(pos);
-            let jump_pos = pos + origin_bc;
-            ptr += 4;
-            jump_table.push(jump_pos);
-        }
-        // default
-        jump_table.push(default_byte + origin_bc);
-
-        let top_value = self.stack.pop_int();
-        if (top_value > (jump_table.len() as i32 - 1 + low_byte)) || top_value < low_byte {
-            self.goto_abs_with_occupied(*jump_table.last().unwrap() as i32, 1);
-        } else {
-            self.goto_abs_with_occupied(
-                jump_table[(top_value - low_byte as i32) as usize] as i32,
-                1,
-            );
-        }
+    //the return address is stored to a local var by astore
+    pub fn ret(&mut self, pos: usize) {
+        let ReturnAddress(pc) = self.local.get_return_address(pos);
+        self.goto_abs(pc);
     }
 
-    pub fn lookup_switch(&mut self) {
-        let mut bc = self.pc - 1;
-        let origin_bc = bc;
-        if bc % 4 != 0 {
-            bc += (4 - bc % 4);
-        } else {
-            bc += 4;
-        }
-        let mut ptr = bc as usize;
-
-        let default_byte = [
-            self.code[ptr],
-            self.code[ptr + 1],
-            self.code[ptr + 2],
-            self.code[ptr + 3],
-        ];
-        let default_byte = u32::from_be_bytes(default_byte);
-        let count = [
-            self.code[ptr + 4],
-            self.code[ptr + 5],
-            self.code[ptr + 6],
-            self.code[ptr + 7],
-        ];
-        let count = u32::from_be_bytes(count);
-        ptr += 8;
-
-        let mut jump_table: HashMap<u32, u32> = HashMap::new();
-        for i in 0..count {
-            let value = [
-                self.code[ptr],
-                self.code[ptr + 1],
-                self.code[ptr + 2],
-                self.code[ptr + 3],
-            ];
-            let value = u32::from_be_bytes(value);
-            let position = [
-                self.code[ptr + 4],
-                self.code[ptr + 5],
-                self.code[ptr + 6],
-                self.code[ptr + 7],
-            ];
-            let position = u32::from_be_bytes(position) + origin_bc as u32;
-            ptr += 8;
-            jump_table.insert(value, position);
-        }
-
-        let top_value = self.stack.pop_int();
-        match jump_table.get(&(top_value as u32)) {
-            Some(position) => self.goto_abs_with_occupied(*position as i32, 1),
-            None => self.goto_abs_with_occupied(default_byte as i32 + origin_bc, 1),
-        }
+    pub fn switch(&mut self, sw: &Switch) {
+        let v = self.stack.pop_int();
+        self.goto_abs(sw.target(v));
     }
 
     pub fn ireturn(&mut self) {
@@ -2228,18 +2381,15 @@ impl Frame {
         self.set_return(None);
     }
 
-    pub fn get_static(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn get_static(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         self.get_field_helper(thread, oop_consts::get_null(), cp_idx, true);
     }
 
-    pub fn put_static(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn put_static(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         self.put_field_helper(thread, cp_idx, true);
     }
 
-    pub fn get_field(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn get_field(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         let rf = self.stack.pop_ref();
         if Arc::ptr_eq(&rf, &oop_consts::get_null()) {
             meet_ex(thread, consts::J_NPE, None);
@@ -2248,35 +2398,23 @@ impl Frame {
         }
     }
 
-    pub fn put_field(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn put_field(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         self.put_field_helper(thread, cp_idx, false);
     }
 
-    pub fn invoke_virtual(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn invoke_virtual(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         self.invoke_helper(thread, false, cp_idx as usize, false);
     }
 
-    pub fn invoke_special(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn invoke_special(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         self.invoke_helper(thread, false, cp_idx as usize, true);
     }
 
-    pub fn invoke_static(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn invoke_static(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         self.invoke_helper(thread, true, cp_idx as usize, true);
     }
 
-    pub fn invoke_interface(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
-        let _count = self.read_u1();
-        let zero = self.read_u1();
-
-        if zero != 0 {
-            warn!("interpreter: invalid invokeinterface: the value of the fourth operand byte must always be zero.");
-        }
-
+    pub fn invoke_interface(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         self.invoke_helper(thread, false, cp_idx as usize, false);
     }
 
@@ -2285,77 +2423,69 @@ impl Frame {
         unimplemented!()
     }
 
-    pub fn new_(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
-
+    pub fn new_(&mut self, thread: &mut JavaThread, cp_idx: i32) {
+        let mut has_finalizer = false;
         let class = {
-            match runtime::require_class2(cp_idx as u16, &self.cp) {
+            match self.resolve_class(cp_idx as usize) {
                 Some(class) => {
-                    {
-                        let mut class = class.lock().unwrap();
-                        class.init_class(thread);
-                    }
-
                     oop::class::init_class_fully(thread, class.clone());
+                    has_finalizer = class.lock().unwrap().has_finalizer();
 
                     class
                 }
                 None => unreachable!("Cannot get class info from constant pool"),
             }
         };
+        if thread.is_meet_ex() {
+            return;
+        }
 
-        let v = oop::OopDesc::new_inst(class);
-        self.stack.push_ref(v);
+        match oop::OopDesc::try_new_inst(class) {
+            Ok(v) => {
+                if has_finalizer {
+                    runtime::gc::refs::register_finalizer(thread, &v);
+                }
+                self.stack.push_ref(v);
+            }
+            Err(e) => meet_ex(thread, consts::J_OOM, Some(e.msg().to_string())),
+        }
     }
 
-    pub fn new_array(&mut self, thread: &mut JavaThread) {
-        let t = self.read_byte();
+    pub fn new_array(&mut self, thread: &mut JavaThread, t: i32) {
         let len = self.stack.pop_int();
         if len < 0 {
             meet_ex(thread, consts::J_NASE, Some("length < 0".to_string()));
         } else {
-            let len = len as usize;
-            let ary = match t {
-                //boolean
-                4 => OopDesc::new_bool_ary(len),
-                //char
-                5 => OopDesc::new_char_ary(len),
-                //float
-                6 => OopDesc::new_float_ary(len),
-                //double
-                7 => OopDesc::new_double_ary(len),
-                //byte
-                8 => OopDesc::new_byte_ary(len),
-                //short
-                9 => OopDesc::new_short_ary(len),
-                //int
-                10 => OopDesc::new_int_ary(len),
-                //long
-                11 => OopDesc::new_long_ary(len),
+            let t = match t {
+                4 => b'Z',
+                5 => b'C',
+                6 => b'F',
+                7 => b'D',
+                8 => b'B',
+                9 => b'S',
+                10 => b'I',
+                11 => b'J',
                 _ => unreachable!(),
             };
 
-            self.stack.push_ref(ary);
+            match OopDesc::try_new_type_ary(t, len as usize) {
+                Ok(ary) => self.stack.push_ref(ary),
+                Err(e) => meet_ex(thread, consts::J_OOM, Some(e.msg().to_string())),
+            }
         }
     }
 
-    pub fn anew_array(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn anew_array(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         let length = self.stack.pop_int();
         //        info!("anew_array length={}", length);
         if length < 0 {
             meet_ex(thread, consts::J_NASE, Some("length < 0".to_string()));
         } else {
-            let class = match runtime::require_class2(cp_idx as u16, &self.cp) {
+            let class = match self.resolve_class(cp_idx as usize) {
                 Some(class) => class,
                 None => panic!("Cannot get class info from constant pool"),
             };
 
-            {
-                let mut class = class.lock().unwrap();
-                class.init_class(thread);
-            }
-
             oop::class::init_class_fully(thread, class.clone());
 
             let (name, cl) = {
@@ -2384,22 +2514,15 @@ impl Frame {
                 (name, class.class_loader.clone())
             };
 
-            trace!("anew_array name={}", unsafe {
-                std::str::from_utf8_unchecked(name.as_slice())
-            });
+            trace!("anew_array name={}", util::mutf8::display(name.as_slice()));
             match runtime::require_class(cl, name) {
                 Some(ary_cls_obj) => {
-                    {
-                        {
-                            let mut class = ary_cls_obj.lock().unwrap();
-                            class.init_class(thread);
-                        }
+                    oop::class::init_class_fully(thread, ary_cls_obj.clone());
 
-                        oop::class::init_class_fully(thread, ary_cls_obj.clone());
+                    match OopDesc::try_new_ref_ary(ary_cls_obj, length as usize) {
+                        Ok(ary) => self.stack.push_ref(ary),
+                        Err(e) => meet_ex(thread, consts::J_OOM, Some(e.msg().to_string())),
                     }
-
-                    let ary = OopDesc::new_ref_ary(ary_cls_obj, length as usize);
-                    self.stack.push_ref(ary);
                 }
                 None => unreachable!(),
             }
@@ -2430,100 +2553,41 @@ impl Frame {
         jt.set_ex(ex);
     }
 
-    pub fn check_cast(&mut self, thread: &mut JavaThread) {
-        let cp_idx = self.read_i2();
+    pub fn check_cast(&mut self, thread: &mut JavaThread, cp_idx: i32) {
         let rf = self.stack.pop_ref();
 
-        let target_cls = require_class2(cp_idx as U2, &self.cp).unwrap();
-
-        let rf_back = rf.clone();
-        let rff = rf.lock().unwrap();
-        match &rff.v {
-            Oop::Null => self.stack.push_ref(rf_back),
-            Oop::Inst(inst) => {
-                let obj_cls = inst.class.clone();
-                let r = cmp::instance_of(obj_cls.clone(), target_cls.clone());
-                if r {
-                    self.stack.push_ref(rf_back);
-                } else {
-                    let s_name = { obj_cls.lock().unwrap().name.clone() };
-                    let t_name = { target_cls.lock().unwrap().name.clone() };
-
-                    let s_name =
-                        String::from_utf8_lossy(s_name.as_slice()).replace(util::FILE_SEP, ".");
-                    let t_name =
-                        String::from_utf8_lossy(t_name.as_slice()).replace(util::FILE_SEP, ".");
-
-                    let msg = format!("inst {} cannot be cast to {}", s_name, t_name);
-                    meet_ex(thread, consts::J_CCE, Some(msg));
-                }
-            }
-            Oop::Array(ary) => {
-                let obj_cls = ary.class.clone();
-                let r = cmp::instance_of(obj_cls.clone(), target_cls.clone());
-                if r {
-                    self.stack.push_ref(rf_back);
-                } else {
-                    let s_name = { obj_cls.lock().unwrap().name.clone() };
-                    let t_name = { target_cls.lock().unwrap().name.clone() };
-
-                    let s_name =
-                        String::from_utf8_lossy(s_name.as_slice()).replace(util::FILE_SEP, ".");
-                    let t_name =
-                        String::from_utf8_lossy(t_name.as_slice()).replace(util::FILE_SEP, ".");
-
-                    let msg = format!("array {} cannot be cast to {}", s_name, t_name);
-                    warn!("{}", msg);
-                    meet_ex(thread, consts::J_CCE, Some(msg));
-                }
-            }
-            Oop::Mirror(mirror) => {
-                //run here codes:
-                //$JDK_TEST/Appendable/Basic.java
-                //最终会调用java.security.Security.getSpiClass("MessageDigest")
-                //走到这里
-                //Exception in thread "main" java.lang.ClassCastException: java.security.MessageDigestSpi cannot be cast to java.lang.Class
-
-                let mirror_target = mirror.target.clone().unwrap();
-                let s_name = { mirror_target.lock().unwrap().name.clone() };
-                let t_name = { target_cls.lock().unwrap().name.clone() };
-                trace!(
-                    "mirror checkcast {} to {}",
-                    unsafe { std::str::from_utf8_unchecked(s_name.as_slice()) },
-                    unsafe { std::str::from_utf8_unchecked(t_name.as_slice()) }
-                );
+        let target_cls = self.resolve_class(cp_idx as usize).unwrap();
 
-                let r = cmp::instance_of(mirror_target.clone(), target_cls.clone());
-                if r || t_name.as_slice() == b"java/lang/Class" {
-                    self.stack.push_ref(rf_back);
-                } else {
-                    let s_name =
-                        String::from_utf8_lossy(s_name.as_slice()).replace(util::FILE_SEP, ".");
-                    let t_name =
-                        String::from_utf8_lossy(t_name.as_slice()).replace(util::FILE_SEP, ".");
-
-                    let msg = format!("mirror {} cannot be cast to {}", s_name, t_name);
-                    error!("{}", msg);
-                    meet_ex(thread, consts::J_CCE, Some(msg));
-                }
+        //null passes any cast
+        let obj_cls = match cmp::oop_class(&rf) {
+            Some(obj_cls) => obj_cls,
+            None => {
+                self.stack.push_ref(rf);
+                return;
             }
-            t => unimplemented!("t = {:?}", t),
+        };
+
+        if cmp::instance_of(obj_cls.clone(), target_cls.clone()) {
+            self.stack.push_ref(rf);
+        } else {
+            let s_name = { obj_cls.lock().unwrap().name.clone() };
+            let t_name = { target_cls.lock().unwrap().name.clone() };
+
+            let s_name = String::from_utf8_lossy(s_name.as_slice()).replace(util::FILE_SEP, ".");
+            let t_name = String::from_utf8_lossy(t_name.as_slice()).replace(util::FILE_SEP, ".");
+
+            let msg = format!("{} cannot be cast to {}", s_name, t_name);
+            meet_ex(thread, consts::J_CCE, Some(msg));
         }
     }
 
-    pub fn instance_of(&mut self) {
-        let cp_idx = self.read_i2();
-        let target_cls = require_class2(cp_idx as U2, &self.cp).unwrap();
+    pub fn instance_of(&mut self, cp_idx: i32) {
+        let target_cls = self.resolve_class(cp_idx as usize).unwrap();
 
         let rf = self.stack.pop_ref();
-        let rff = rf.lock().unwrap();
-        let result = match &rff.v {
-            Oop::Null => false,
-            Oop::Inst(inst) => {
-                let obj_cls = inst.class.clone();
-                cmp::instance_of(obj_cls, target_cls)
-            }
-            _ => unimplemented!(),
+        let result = match cmp::oop_class(&rf) {
+            Some(obj_cls) => cmp::instance_of(obj_cls, target_cls),
+            None => false,
         };
 
         if result {
@@ -2534,74 +2598,273 @@ impl Frame {
     }
 
     pub fn monitor_enter(&mut self, thread: &mut JavaThread) {
-        let mut rf = self.stack.pop_ref();
-        let mut rff = rf.lock().unwrap();
-        match rff.v {
-            Oop::Null => {
-                meet_ex(thread, consts::J_NPE, None);
-            }
-            _ => {
-                rff.monitor_enter();
-            }
+        let rf = self.stack.pop_ref();
+        let is_null = { matches!(rf.lock().unwrap().v, Oop::Null) };
+        if is_null {
+            meet_ex(thread, consts::J_NPE, None);
+        } else {
+            monitor::enter(&rf);
         }
     }
 
     pub fn monitor_exit(&mut self, thread: &mut JavaThread) {
-        let mut rf = self.stack.pop_ref();
-        let mut rff = rf.lock().unwrap();
-        match rff.v {
-            Oop::Null => {
-                meet_ex(thread, consts::J_NPE, None);
-            }
-            _ => {
-                rff.monitor_exit();
-            }
+        let rf = self.stack.pop_ref();
+        let is_null = { matches!(rf.lock().unwrap().v, Oop::Null) };
+        if is_null {
+            meet_ex(thread, consts::J_NPE, None);
+        } else if monitor::exit(&rf).is_err() {
+            meet_ex(thread, consts::J_IMSE, None);
         }
     }
 
-    pub fn wide(&mut self) {
-        info!("opcode wide");
-        self.op_widen = true;
-    }
+    pub fn multi_anew_array(&mut self, thread: &mut JavaThread, cp_idx: usize, dimension: usize) {
+        //the count of the outermost dimension is pushed first
+        let mut counts = Vec::with_capacity(dimension);
+        for _ in 0..dimension {
+            counts.push(self.stack.pop_int());
+        }
+        counts.reverse();
 
-    pub fn multi_anew_array(&mut self) {
-        //todo: impl
-        unimplemented!()
+        if counts.iter().any(|&it| it < 0) {
+            meet_ex(thread, consts::J_NASE, Some("length < 0".to_string()));
+            return;
+        }
+
+        let class = match self.resolve_class(cp_idx) {
+            Some(class) => class,
+            None => panic!("Cannot get class info from constant pool"),
+        };
+        let (name, cl) = {
+            let class = class.lock().unwrap();
+            (class.name.clone(), class.class_loader)
+        };
+
+        match new_multi_array(cl, name.as_slice(), counts.as_slice()) {
+            Ok(ary) => self.stack.push_ref(ary),
+            Err(e) => meet_ex(thread, consts::J_OOM, Some(e.msg().to_string())),
+        }
     }
 
-    pub fn if_null(&mut self) {
+    pub fn if_null(&mut self, target: i32) {
         let v = self.stack.pop_ref();
         let v = v.lock().unwrap();
-        match v.v {
-            Oop::Null => self.goto_by_offset_hardcoded(2),
-            _ => self.pc += 2,
+        if let Oop::Null = v.v {
+            self.goto_abs(target);
         }
     }
 
-    pub fn if_non_null(&mut self) {
+    pub fn if_non_null(&mut self, target: i32) {
         let v = self.stack.pop_ref();
         let v = v.lock().unwrap();
-        match v.v {
-            Oop::Null => self.pc += 2,
-            _ => self.goto_by_offset_hardcoded(2),
+        if !matches!(v.v, Oop::Null) {
+            self.goto_abs(target);
         }
     }
 
-    pub fn goto_w(&mut self) {
-        self.pc += 4;
-        panic!("Use of deprecated instruction goto_w, please check your Java compiler")
+    pub fn goto_w(&mut self, target: i32) {
+        self.goto_abs(target);
     }
 
-    pub fn jsr_w(&mut self) {
-        self.pc += 4;
-        panic!("Use of deprecated instruction jsr_w, please check your Java compiler")
+    pub fn jsr_w(&mut self, target: i32) {
+        self.jsr(target);
     }
 
-    pub fn other_wise(&mut self) {
-        let pc = self.pc - 1;
-        panic!(
-            "Use of undefined bytecode: {} at {}",
-            self.code[pc as usize], pc
-        );
+    pub fn breakpoint(&mut self, thread: &mut JavaThread) {
+        //todo: trap into the JDWP agent, when a debugger is attached
+        let pc = self.bci;
+        let msg = format!("breakpoint at {}, no debugger attached", pc);
+        meet_ex(thread, consts::J_INTERNAL_ERROR, Some(msg));
+    }
+
+    //impdep1 & impdep2 are reserved for the vm internal use, never in a class file
+    pub fn impdep(&mut self, thread: &mut JavaThread, op_code: OpCode) {
+        let pc = self.bci;
+        let msg = format!("reserved opcode {:?} at {}", op_code, pc);
+        meet_ex(thread, consts::J_INTERNAL_ERROR, Some(msg));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::access_flags::{ACC_PUBLIC, ACC_STATIC, ACC_SUPER};
+    use crate::oop::method::{Method, MethodId};
+    use crate::runtime::{host_class, test_class, Local};
+    use std::sync::Once;
+
+    //runs code as the static method "m" desc of a class of its own, the
+    //args set in the locals, what it returned
+    fn run(code: &[u8], desc: &str, args: impl FnOnce(&mut Local)) -> Option<OopRef> {
+        let (v, ex) = run_ex(code, desc, args);
+        assert!(ex.is_none());
+        v
+    }
+
+    //run, & the exception thrown
+    fn run_ex(
+        code: &[u8],
+        desc: &str,
+        args: impl FnOnce(&mut Local),
+    ) -> (Option<OopRef>, Option<OopRef>) {
+        let methods = vec![("m", desc, ACC_PUBLIC | ACC_STATIC, test_class::code(code))];
+        let acc_flags = ACC_PUBLIC | ACC_SUPER;
+        let cf = host_class::new_class_file("test/Interp", acc_flags, None, &[], methods);
+        let cf = new_ref!(cf);
+        let class = oop::Class::new_class(cf.clone(), None);
+        let class = new_sync_ref!(class);
+        let method = Method::new(&cf.cp, &cf.methods[0], class, vec![], vec![]);
+        let mir = Arc::new(MethodId {
+            offset: 0,
+            vtable_index: None,
+            itable_index: None,
+            method,
+        });
+
+        let mut jt = JavaThread::new();
+        let mut frame = Frame::new(mir, 0);
+        args(&mut frame.local);
+        frame.interp(&mut jt);
+        (frame.return_v, jt.take_ex())
+    }
+
+    //fload_0, fload_1, op, freturn
+    fn run_float(op: OpCode, v1: f32, v2: f32) -> f32 {
+        let code = [0x22, 0x23, op as u8, 0xae];
+        let v = run(&code, "(FF)F", |local| {
+            local.set_float(0, v1);
+            local.set_float(1, v2);
+        });
+        util::oop::extract_float(v.unwrap())
+    }
+
+    //dload_0, dload_2, op, dreturn
+    fn run_double(op: OpCode, v1: f64, v2: f64) -> f64 {
+        let code = [0x26, 0x28, op as u8, 0xaf];
+        let v = run(&code, "(DD)D", |local| {
+            local.set_double(0, v1);
+            local.set_double(2, v2);
+        });
+        util::oop::extract_double(v.unwrap())
+    }
+
+    //frem/drem, the JVMS cases: the sign of the dividend, NaN for an
+    //infinite dividend or a zero divisor, the dividend for an infinite one
+    #[test]
+    fn t_rem() {
+        assert_eq!(run_double(OpCode::drem, 5.5, 2.5), 0.5);
+        assert_eq!(run_double(OpCode::drem, -5.5, 2.5), -0.5);
+        assert_eq!(run_double(OpCode::drem, 5.5, -2.5), 0.5);
+        assert_eq!(run_float(OpCode::frem, 7.0, 2.5), 2.0);
+        assert!(run_double(OpCode::drem, -0.0, 1.0).is_sign_negative());
+        assert!(run_double(OpCode::drem, 0.0, 3.0).is_sign_positive());
+
+        assert!(run_double(OpCode::drem, f64::NAN, 1.0).is_nan());
+        assert!(run_double(OpCode::drem, 1.0, f64::NAN).is_nan());
+        assert!(run_double(OpCode::drem, f64::INFINITY, 2.0).is_nan());
+        assert!(run_double(OpCode::drem, 1.0, 0.0).is_nan());
+        assert!(run_float(OpCode::frem, f32::NEG_INFINITY, 0.0).is_nan());
+        assert_eq!(run_double(OpCode::drem, 1.5, f64::INFINITY), 1.5);
+        assert_eq!(run_float(OpCode::frem, -1.5, f32::NEG_INFINITY), -1.5);
+    }
+
+    //fdiv/ddiv by zero, Infinity or NaN, no ArithmeticException
+    #[test]
+    fn t_div_ieee() {
+        assert_eq!(run_float(OpCode::fdiv, 1.0, 0.0), f32::INFINITY);
+        assert_eq!(run_double(OpCode::ddiv, -1.0, 0.0), f64::NEG_INFINITY);
+        assert_eq!(run_double(OpCode::ddiv, 1.0, -0.0), f64::NEG_INFINITY);
+        assert!(run_float(OpCode::fdiv, 0.0, 0.0).is_nan());
+        assert!(run_double(OpCode::ddiv, f64::NAN, 2.0).is_nan());
+        assert_eq!(run_double(OpCode::ddiv, 1.0, f64::INFINITY), 0.0);
+        assert_eq!(run_float(OpCode::fdiv, 7.5, 2.5), 3.0);
+    }
+
+    //java/lang/ArithmeticException(String), & the String(char[]) its
+    //message is built with, both ctors only return
+    fn define_arithmetic_ex() {
+        static EX: Once = Once::new();
+        EX.call_once(|| {
+            test_class::define_object();
+            let object = Some("java/lang/Object");
+            let ctor = |desc| vec![("<init>", desc, ACC_PUBLIC, test_class::code(&[0xb1]))];
+            for (name, desc) in [
+                ("java/lang/String", "([C)V"),
+                ("java/lang/ArithmeticException", "(Ljava/lang/String;)V"),
+            ] {
+                let cf = host_class::new_class_file(name, ACC_SUPER, object, &[], ctor(desc));
+                test_class::define_class(cf);
+            }
+        });
+    }
+
+    //iload_0, iload_1, op, ireturn
+    fn run_int(op: OpCode, v1: i32, v2: i32) -> (Option<i32>, Option<OopRef>) {
+        let code = [0x1a, 0x1b, op as u8, 0xac];
+        let (v, ex) = run_ex(&code, "(II)I", |local| {
+            local.set_int(0, v1);
+            local.set_int(1, v2);
+        });
+        (v.map(util::oop::extract_int), ex)
+    }
+
+    //lload_0, lload_2, op, lreturn
+    fn run_long(op: OpCode, v1: i64, v2: i64) -> (Option<i64>, Option<OopRef>) {
+        let code = [0x1e, 0x20, op as u8, 0xad];
+        let (v, ex) = run_ex(&code, "(JJ)J", |local| {
+            local.set_long(0, v1);
+            local.set_long(2, v2);
+        });
+        (v.map(util::oop::extract_long), ex)
+    }
+
+    //idiv/ldiv/irem/lrem: MIN_VALUE / -1 overflows to MIN_VALUE, rem 0,
+    //a zero divisor throws ArithmeticException
+    #[test]
+    fn t_div_overflow() {
+        assert_eq!(run_int(OpCode::idiv, i32::MIN, -1).0, Some(i32::MIN));
+        assert_eq!(run_int(OpCode::irem, i32::MIN, -1).0, Some(0));
+        assert_eq!(run_long(OpCode::ldiv, i64::MIN, -1).0, Some(i64::MIN));
+        assert_eq!(run_long(OpCode::lrem, i64::MIN, -1).0, Some(0));
+        //truncated toward zero, the rem takes the dividend's sign
+        assert_eq!(run_int(OpCode::idiv, -7, 2).0, Some(-3));
+        assert_eq!(run_int(OpCode::irem, -7, 2).0, Some(-1));
+        assert_eq!(run_long(OpCode::lrem, 7, -2).0, Some(1));
+
+        define_arithmetic_ex();
+        //the class of the exception thrown, nothing returned
+        fn thrown<T>((v, ex): (Option<T>, Option<OopRef>)) -> String {
+            assert!(v.is_none());
+            let cls = match &ex.unwrap().lock().unwrap().v {
+                Oop::Inst(inst) => inst.class.clone(),
+                _ => unreachable!(),
+            };
+            let cls = cls.lock().unwrap();
+            String::from_utf8_lossy(cls.name.as_slice()).to_string()
+        }
+        let ex = "java/lang/ArithmeticException";
+        assert_eq!(thrown(run_int(OpCode::idiv, 1, 0)), ex);
+        assert_eq!(thrown(run_int(OpCode::irem, i32::MIN, 0)), ex);
+        assert_eq!(thrown(run_long(OpCode::ldiv, 1, 0)), ex);
+        assert_eq!(thrown(run_long(OpCode::lrem, -1, 0)), ex);
+    }
+
+    //fneg/dneg flip the sign bit: -0.0, & NaN keeps its payload
+    #[test]
+    fn t_neg() {
+        let fneg = |v: f32| {
+            let r = run(&[0x22, 0x76, 0xae], "(F)F", |local| local.set_float(0, v));
+            util::oop::extract_float(r.unwrap())
+        };
+        let dneg = |v: f64| {
+            let r = run(&[0x26, 0x77, 0xaf], "(D)D", |local| local.set_double(0, v));
+            util::oop::extract_double(r.unwrap())
+        };
+
+        assert_eq!(fneg(1.5), -1.5);
+        assert!(fneg(0.0).is_sign_negative());
+        assert!(dneg(-0.0).is_sign_positive());
+        assert_eq!(dneg(f64::INFINITY), f64::NEG_INFINITY);
+        assert!(fneg(f32::NAN).is_nan());
+        assert_eq!(dneg(f64::NAN).to_bits(), f64::NAN.to_bits() ^ (1 << 63));
     }
 }
@@ -0,0 +1,145 @@
+/*
+Thread-local allocation budgets, -XX:+UseAllocBudget
+
+This is not a TLAB. No memory is carved out for a thread & allocation
+is no pointer bump: an object is still a Box of the host allocator in
+an Arc (see note.txt, OopRef优化). A thread keeps only the accounting of
+its own, the -Xmx bytes & the heap registrations, so the common path
+takes no lock & touches no shared counter:
+  budget   a chunk of the heap, CHUNK_SIZE bytes reserved from HEAP_USED
+           at once, an object's size is taken off it by a subtraction.
+           Refilled when short, the rest given back. An object bigger
+           than a chunk is reserved alone, as is one near -Xmx, when no
+           chunk fits but the object does
+  objects  the tracked objects, registered with the heap BATCH_OBJECTS at
+           a time, allocated grows by the batch
+
+The unused budget counts as used, heap_used is over by less than
+CHUNK_SIZE a thread. The budget is retired when its thread ends: the
+budget left is released, the objects registered.
+
+A collection sees every budget: each thread flushes its own as it stops
+for the safepoint, see runtime::safepoint. The heap queries flush the
+budget of their own thread only, the objects of the others are counted
+once their batch is full.
+*/
+
+use super::{fits, ALLOCATED, HEAP, HEAP_USED};
+use crate::oop::OopDesc;
+use crate::types::OopRef;
+use crate::util;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, Weak};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const BATCH_OBJECTS: usize = 256;
+
+#[derive(Default)]
+struct Budget {
+    //bytes of the budget left
+    left: Cell<usize>,
+    objects: RefCell<Vec<Weak<Mutex<Box<OopDesc>>>>>,
+}
+
+impl Drop for Budget {
+    fn drop(&mut self) {
+        HEAP_USED.fetch_sub(self.left.replace(0), Ordering::Relaxed);
+        publish(std::mem::take(self.objects.get_mut()));
+    }
+}
+
+thread_local! {
+    static BUDGET: Budget = Budget::default();
+}
+
+//take size off the budget, a new chunk if short, false if no chunk fits
+//(under max) or size is too big for one, & after the thread's budget is
+//gone: the caller reserves size alone
+pub fn allocate(size: usize, max: Option<usize>) -> bool {
+    if size > CHUNK_SIZE {
+        return false;
+    }
+    BUDGET
+        .try_with(|t| {
+            let left = t.left.get();
+            if size <= left {
+                t.left.set(left - size);
+                return true;
+            }
+
+            //the rest of the old chunk is given back as the new one is taken
+            let refilled = HEAP_USED
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                    let used = used - left;
+                    match max {
+                        Some(max) => fits(used, CHUNK_SIZE, max),
+                        None => used.checked_add(CHUNK_SIZE),
+                    }
+                })
+                .is_ok();
+            if refilled {
+                t.left.set(CHUNK_SIZE - size);
+            }
+            refilled
+        })
+        .unwrap_or(false)
+}
+
+pub fn register(v: &OopRef) {
+    let full = BUDGET.try_with(|t| {
+        let mut objects = t.objects.borrow_mut();
+        objects.push(Arc::downgrade(v));
+        match objects.len() >= BATCH_OBJECTS {
+            true => std::mem::take(&mut *objects),
+            false => Vec::new(),
+        }
+    });
+    match full {
+        Ok(objects) => publish(objects),
+        Err(_) => publish(vec![Arc::downgrade(v)]),
+    }
+}
+
+//the objects of this thread's budget to the heap
+pub fn flush() {
+    let objects = BUDGET
+        .try_with(|t| std::mem::take(&mut *t.objects.borrow_mut()))
+        .unwrap_or_default();
+    publish(objects);
+}
+
+fn publish(objects: Vec<Weak<Mutex<Box<OopDesc>>>>) {
+    if objects.is_empty() {
+        return;
+    }
+    let n = objects.len();
+    util::sync_call_ctx(&HEAP, |h| h.objects.extend(objects));
+    ALLOCATED.fetch_add(n, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_allocate() {
+        //a thread of its own, a budget of its own
+        std::thread::spawn(|| {
+            let left = || BUDGET.with(|t| t.left.get());
+            assert_eq!(left(), 0);
+            assert!(allocate(100, None));
+            assert_eq!(left(), CHUNK_SIZE - 100);
+            assert!(!allocate(CHUNK_SIZE + 1, None));
+            assert!(allocate(CHUNK_SIZE - 100, None));
+            assert_eq!(left(), 0);
+
+            //no chunk fits under max, the budget is kept
+            assert!(allocate(8, None));
+            assert!(!allocate(CHUNK_SIZE, Some(CHUNK_SIZE - 1)));
+            assert_eq!(left(), CHUNK_SIZE - 8);
+        })
+        .join()
+        .unwrap();
+    }
+}
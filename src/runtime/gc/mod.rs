@@ -0,0 +1,729 @@
+/*
+Mark-sweep collector for cyclic garbage, enabled by -XX:+UseMarkSweepGC
+
+Objects are reference counted (OopRef is an Arc), an acyclic graph is
+freed as soon as the last ref drops. A cycle (doubly linked list, parent
+<-> child...) keeps itself alive forever, this collector frees them.
+
+Roots: frames, locals, operand stacks, static fields, JNI handles, the
+pending exception, and Rust locals of native methods all hold strong
+refs which the heap can't see. So roots are found by counting: an object
+referenced more times (Arc::strong_count) than by the fields & elements
+of the other heap objects has a reference from outside the heap, it is
+a root. Then
+  mark:  everything reachable from the roots
+  sweep: unmarked objects are only referenced by other garbage, their
+         fields & elements are cleared, the cycles break, and the Arcs
+         free the memory
+
+An object locked by someone else can't be scanned, it is treated as a
+root, its children look externally referenced too. This errs on the
+side of keeping objects alive.
+
+The scan, mark & sweep are parallel on a big heap, see gc::parallel.
+The referent of a Soft, Weak or Phantom reference is a weak edge, see
+gc::refs.
+
+Collection happens at the interpreter safe point (between two
+instructions), after GC_ALLOC_THRESHOLD allocations, or by System.gc(),
+with the other threads stopped, see runtime::safepoint.
+*/
+
+use crate::classfile::consts;
+use crate::oop::{consts as oop_consts, AllocErr, Oop, OopDesc, TypeArrayValue};
+use crate::runtime::events::{self, EventKind};
+use crate::runtime::{self, cmp, flags, tags};
+use crate::types::{ClassRef, OopRef};
+use crate::util;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+pub(crate) mod budget;
+mod parallel;
+pub mod refs;
+
+use refs::RefKind;
+
+//allocations between two automatic collections
+const GC_ALLOC_THRESHOLD: usize = 100_000;
+
+struct Heap {
+    objects: Vec<Weak<Mutex<Box<OopDesc>>>>,
+    collections: usize,
+    last: GcStats,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GcStats {
+    pub live: usize,
+    pub roots: usize,
+    pub freed: usize,
+    //references cleared & pending
+    pub cleared: usize,
+    pub workers: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    //bytes of the live objects & of the unused allocation budgets, see
+    //reserve
+    pub used: usize,
+    //registered objects, some may be freed already
+    pub tracked: usize,
+    //allocations since the last collection
+    pub allocated: usize,
+    pub collections: usize,
+    pub last: GcStats,
+}
+
+lazy_static! {
+    static ref HEAP: Mutex<Heap> = Mutex::new(Heap {
+        objects: Vec::new(),
+        collections: 0,
+        last: GcStats::default(),
+    });
+}
+
+//registered objects since the last collection, read at every safe point
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+//track heap objects, Inst & arrays, only Inst & Array may form cycles
+pub fn register(v: &OopRef) {
+    if !flags::is_track_heap_objects() {
+        return;
+    }
+    if flags::is_use_alloc_budget() {
+        budget::register(v);
+    } else {
+        util::sync_call_ctx(&HEAP, |h| h.objects.push(Arc::downgrade(v)));
+        ALLOCATED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn should_collect() -> bool {
+    flags::is_use_gc() && ALLOCATED.load(Ordering::Relaxed) >= GC_ALLOC_THRESHOLD
+}
+
+pub fn heap_stats() -> HeapStats {
+    budget::flush();
+    util::sync_call_ctx(&HEAP, |h| HeapStats {
+        used: heap_used(),
+        tracked: h.objects.len(),
+        allocated: ALLOCATED.load(Ordering::Relaxed),
+        collections: h.collections,
+        last: h.last,
+    })
+}
+
+/*
+Heap accounting, -Xmx
+
+Every object is accounted its shallow size when created, & released when
+the last ref drops (OopDesc's Drop). What the guest allocates by new,
+newarray, anewarray & multianewarray reserves first: over -Xmx it gets a
+collection, if -XX:+UseMarkSweepGC, then AllocErr::OutOfMemory & the
+interpreter throws OutOfMemoryError. The vm's own objects (mirrors,
+strings & arrays made by natives...) are accounted but never refused.
+Both take from the thread's allocation budget first, see gc::budget.
+*/
+static HEAP_USED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn heap_used() -> usize {
+    HEAP_USED.load(Ordering::Relaxed)
+}
+
+pub fn account(size: usize) {
+    if !(flags::is_use_alloc_budget() && budget::allocate(size, None)) {
+        HEAP_USED.fetch_add(size, Ordering::Relaxed);
+    }
+}
+
+pub fn release(size: usize) {
+    HEAP_USED.fetch_sub(size, Ordering::Relaxed);
+}
+
+pub fn reserve(size: usize) -> Result<(), AllocErr> {
+    let max = flags::max_heap_size();
+    if flags::is_use_alloc_budget() && budget::allocate(size, max) {
+        return Ok(());
+    }
+    let max = match max {
+        Some(max) => max,
+        None => {
+            HEAP_USED.fetch_add(size, Ordering::Relaxed);
+            return Ok(());
+        }
+    };
+
+    let try_account = || {
+        HEAP_USED
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                fits(used, size, max)
+            })
+            .is_ok()
+    };
+    if try_account() {
+        return Ok(());
+    }
+    //the last chance, soft references are cleared too
+    if flags::is_use_gc() {
+        collect_all();
+        if try_account() {
+            return Ok(());
+        }
+    }
+    Err(AllocErr::OutOfMemory)
+}
+
+//the heap used after allocating size, None over max
+fn fits(used: usize, size: usize, max: usize) -> Option<usize> {
+    used.checked_add(size).filter(|&it| it <= max)
+}
+
+/*
+Heap queries, for embedders & tools: the live instances of a class, for
+leak hunting & assertions in tests
+
+Walks the tracked objects, -XX:+TrackHeapObjects (or -XX:+UseMarkSweepGC)
+must be on from the start, objects allocated before are not seen. The
+shallow size is the object itself, header & its own slots, not what its
+fields refer to.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct Instances {
+    pub objects: Vec<OopRef>,
+    //sum of the shallow sizes
+    pub shallow_size: usize,
+}
+
+impl Instances {
+    pub fn count(&self) -> usize {
+        self.objects.len()
+    }
+}
+
+//live instances of class, of its subclasses & implementors too if subclasses
+pub fn instances_of(class: &ClassRef, subclasses: bool) -> Instances {
+    budget::flush();
+    let objects: Vec<OopRef> = util::sync_call_ctx(&HEAP, |h| {
+        h.objects.retain(|it| it.strong_count() > 0);
+        h.objects.iter().filter_map(|it| it.upgrade()).collect()
+    });
+
+    let mut found = Instances::default();
+    for it in objects {
+        let (cls, size) = {
+            let v = it.lock().unwrap();
+            (oop_class(&v.v), shallow_size(&v.v))
+        };
+        let is_instance = match cls {
+            Some(cls) if subclasses => cmp::instance_of(cls, class.clone()),
+            Some(cls) => Arc::ptr_eq(&cls, class),
+            None => false,
+        };
+        if is_instance {
+            found.objects.push(it);
+            found.shallow_size += size;
+        }
+    }
+
+    found
+}
+
+//by class name, "java/lang/String" or "java.lang.String", None if not loaded
+pub fn find_instances(name: &str, subclasses: bool) -> Option<Instances> {
+    let class = runtime::sys_dic_find(name.replace('.', "/").as_bytes())?;
+    Some(instances_of(&class, subclasses))
+}
+
+pub fn shallow_size(v: &Oop) -> usize {
+    let slots = match v {
+        Oop::Inst(inst) => inst.field_values.len() * size_of::<OopRef>(),
+        Oop::Array(ary) => ary.elements.len() * size_of::<OopRef>(),
+        Oop::TypeArray(ary) => {
+            let elm = match ary {
+                TypeArrayValue::Byte(_) | TypeArrayValue::Bool(_) => 1,
+                TypeArrayValue::Char(_) | TypeArrayValue::Short(_) => 2,
+                TypeArrayValue::Int(_) | TypeArrayValue::Float(_) => 4,
+                TypeArrayValue::Long(_) | TypeArrayValue::Double(_) => 8,
+            };
+            ary.len() * elm
+        }
+        _ => 0,
+    };
+    size_of::<OopDesc>() + slots
+}
+
+//instances & shallow bytes of one class, see histogram
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramEntry {
+    pub name: Vec<u8>,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+//the tracked objects by class, the most bytes first, as jmap -histo
+pub fn histogram() -> Vec<HistogramEntry> {
+    budget::flush();
+    let objects: Vec<OopRef> = util::sync_call_ctx(&HEAP, |h| {
+        h.objects.retain(|it| it.strong_count() > 0);
+        h.objects.iter().filter_map(|it| it.upgrade()).collect()
+    });
+
+    //the class lock is not taken with the object's
+    let sized: Vec<(Option<ClassRef>, &'static [u8], usize)> = objects
+        .iter()
+        .map(|it| {
+            let v = it.lock().unwrap();
+            let size = shallow_size(&v.v);
+            match &v.v {
+                Oop::Inst(inst) => (Some(inst.class.clone()), &b""[..], size),
+                Oop::Array(ary) => (Some(ary.class.clone()), &b""[..], size),
+                Oop::TypeArray(ary) => (None, ary.desc(), size),
+                Oop::Mirror(_) => (None, consts::J_CLASS, size),
+                _ => (None, &b""[..], size),
+            }
+        })
+        .collect();
+    let named = sized.into_iter().filter_map(|(cls, name, size)| match cls {
+        Some(cls) => Some((cls.lock().unwrap().name.to_vec(), size)),
+        None if !name.is_empty() => Some((name.to_vec(), size)),
+        None => None,
+    });
+    tally(named)
+}
+
+fn tally(objects: impl Iterator<Item = (Vec<u8>, usize)>) -> Vec<HistogramEntry> {
+    let mut by_name: HashMap<Vec<u8>, (usize, usize)> = HashMap::new();
+    for (name, size) in objects {
+        let it = by_name.entry(name).or_default();
+        it.0 += 1;
+        it.1 += size;
+    }
+    let mut entries: Vec<HistogramEntry> = by_name
+        .into_iter()
+        .map(|(name, (count, bytes))| HistogramEntry { name, count, bytes })
+        .collect();
+    entries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    entries
+}
+
+//a table, a line per class & the total
+pub fn format_histogram(entries: &[HistogramEntry]) -> String {
+    let mut s = format!(
+        "{:>5} {:>12} {:>14}  class name\n",
+        "num", "#instances", "#bytes"
+    );
+    for (i, it) in entries.iter().enumerate() {
+        s.push_str(&format!(
+            "{:>5} {:>12} {:>14}  {}\n",
+            i + 1,
+            it.count,
+            it.bytes,
+            util::mutf8::display(it.name.as_slice())
+        ));
+    }
+    let count: usize = entries.iter().map(|it| it.count).sum();
+    let bytes: usize = entries.iter().map(|it| it.bytes).sum();
+    s.push_str(&format!("{:>5} {:>12} {:>14}\n", "Total", count, bytes));
+    s
+}
+
+//-XX:+PrintClassHistogram, when the vm exits
+pub fn print_histogram() {
+    if flags::is_print_class_histogram() {
+        eprint!("{}", format_histogram(&histogram()));
+    }
+}
+
+//TypeArray has no class object, found by the desc
+fn oop_class(v: &Oop) -> Option<ClassRef> {
+    match v {
+        Oop::Inst(inst) => Some(inst.class.clone()),
+        Oop::Array(ary) => Some(ary.class.clone()),
+        Oop::TypeArray(ary) => runtime::require_class3(None, ary.desc()),
+        _ => None,
+    }
+}
+
+pub fn collect() -> GcStats {
+    let stats = runtime::safepoint::run(|| do_collect(false));
+    refs::publish_pending();
+    stats
+}
+
+//soft references are kept by a collection, but this one, before
+//OutOfMemoryError
+pub fn collect_all() -> GcStats {
+    let stats = runtime::safepoint::run(|| do_collect(true));
+    refs::publish_pending();
+    stats
+}
+
+fn do_collect(clear_soft: bool) -> GcStats {
+    budget::flush();
+    let objects: Vec<OopRef> = util::sync_call_ctx(&HEAP, |h| {
+        ALLOCATED.store(0, Ordering::Relaxed);
+        h.objects.retain(|it| it.strong_count() > 0);
+        h.objects.iter().filter_map(|it| it.upgrade()).collect()
+    });
+
+    let index: HashMap<usize, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, it)| (Arc::as_ptr(it) as usize, i))
+        .collect();
+    let n = parallel::workers(objects.len());
+
+    //scan, count the refs from inside the heap, referents are no edges
+    let ref_classes = refs::RefClasses::snapshot(clear_soft);
+    let internal: Vec<AtomicUsize> = objects.iter().map(|_| AtomicUsize::new(0)).collect();
+    let scans = parallel::run(n, |w| {
+        let range = parallel::range(w, n, objects.len());
+        let start = range.start;
+        let mut edges: Vec<Vec<usize>> = vec![vec![]; range.len()];
+        let mut scanned = vec![false; range.len()];
+        let mut referents = Vec::new();
+        for (k, it) in objects[range].iter().enumerate() {
+            if let Ok(v) = it.try_lock() {
+                scanned[k] = true;
+                let weak_ref = ref_classes.weak_ref(&v.v);
+                for (slot, child) in children(&v.v).iter().enumerate() {
+                    if let Some(&j) = index.get(&(Arc::as_ptr(child) as usize)) {
+                        internal[j].fetch_add(1, Ordering::Relaxed);
+                        match weak_ref {
+                            Some(class) if class.referent == slot => {
+                                referents.push((start + k, j, class))
+                            }
+                            _ => edges[k].push(j),
+                        }
+                    }
+                }
+            }
+        }
+        (edges, scanned, referents)
+    });
+    let mut edges = Vec::with_capacity(objects.len());
+    let mut scanned = Vec::with_capacity(objects.len());
+    let mut referents = Vec::new();
+    for (e, s, r) in scans {
+        edges.extend(e);
+        scanned.extend(s);
+        referents.extend(r);
+    }
+
+    //roots, minus 1 for the ref held by 'objects'
+    let marked: Vec<AtomicBool> = objects.iter().map(|_| AtomicBool::new(false)).collect();
+    let roots: Vec<usize> = parallel::run(n, |w| {
+        parallel::range(w, n, objects.len())
+            .filter(|&i| {
+                !scanned[i]
+                    || Arc::strong_count(&objects[i]) - 1 > internal[i].load(Ordering::Relaxed)
+            })
+            .collect::<Vec<usize>>()
+    })
+    .concat();
+    roots
+        .iter()
+        .for_each(|&i| marked[i].store(true, Ordering::Relaxed));
+
+    //mark
+    parallel::mark(&edges, &roots, &marked, n);
+
+    //references to the unmarked, the finalizable & the referents of
+    //those which can't be cleared live on, with all they reach
+    let keep = |kept: Vec<usize>| {
+        let kept: Vec<usize> = kept
+            .into_iter()
+            .filter(|&j| !marked[j].swap(true, Ordering::Relaxed))
+            .collect();
+        if !kept.is_empty() {
+            parallel::mark(&edges, &kept, &marked, n);
+        }
+    };
+    let (cleared, kept) = refs::process(
+        &objects,
+        &referents,
+        &marked,
+        &[RefKind::Soft, RefKind::Weak, RefKind::Final],
+    );
+    keep(kept);
+    let (phantoms, kept) = refs::process(&objects, &referents, &marked, &[RefKind::Phantom]);
+    keep(kept);
+    let cleared = cleared + phantoms;
+
+    //sweep
+    let live = objects.len();
+    let freed: usize = parallel::run(n, |w| {
+        parallel::range(w, n, objects.len())
+            .filter(|&i| !marked[i].load(Ordering::Relaxed))
+            .filter(|&i| match objects[i].try_lock() {
+                Ok(mut v) => {
+                    clear(&mut v.v);
+                    true
+                }
+                Err(_) => false,
+            })
+            .count()
+    })
+    .iter()
+    .sum();
+
+    //objects freed by this collection are gone once 'objects' drops
+    drop(objects);
+    tags::purge();
+
+    let stats = GcStats {
+        live: live - freed,
+        roots: roots.len(),
+        freed,
+        cleared,
+        workers: n,
+    };
+    info!("gc: {:?}", stats);
+    events::record(EventKind::Gc, || {
+        format!(
+            "live={} roots={} freed={} cleared={} workers={}",
+            stats.live, stats.roots, freed, cleared, n
+        )
+    });
+    util::sync_call_ctx(&HEAP, |h| {
+        h.collections += 1;
+        h.last = stats;
+    });
+
+    stats
+}
+
+fn children(v: &Oop) -> &[OopRef] {
+    match v {
+        Oop::Inst(inst) => inst.field_values.as_slice(),
+        Oop::Array(ary) => ary.elements.as_slice(),
+        _ => &[],
+    }
+}
+
+fn clear(v: &mut Oop) {
+    match v {
+        Oop::Inst(inst) => inst
+            .field_values
+            .iter_mut()
+            .for_each(|it| *it = oop_consts::get_null()),
+        Oop::Array(ary) => ary.elements.clear(),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::{Class, InstOopDesc, ValueType};
+    use crate::runtime::ClassLoader;
+
+    fn set_elements(ary: &OopRef, elms: Vec<OopRef>) {
+        let mut v = ary.lock().unwrap();
+        match &mut v.v {
+            Oop::Array(ary) => ary.elements = elms,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn t_collect_cycle() {
+        flags::parse_x_opt("X:+UseMarkSweepGC").unwrap();
+        let cls = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::INT);
+        let cls = new_sync_ref!(cls);
+
+        //a <-> b, garbage
+        let a = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+        let b = OopDesc::new_ref_ary2(cls.clone(), vec![a.clone()]);
+        set_elements(&a, vec![b.clone()]);
+        let (wa, wb) = (Arc::downgrade(&a), Arc::downgrade(&b));
+
+        //c <-> d, c is held
+        let c = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+        let d = OopDesc::new_ref_ary2(cls, vec![c.clone()]);
+        set_elements(&c, vec![d.clone()]);
+        let wd = Arc::downgrade(&d);
+
+        drop(a);
+        drop(b);
+        drop(d);
+        assert!(wa.upgrade().is_some());
+
+        collect();
+        assert!(wa.upgrade().is_none());
+        assert!(wb.upgrade().is_none());
+        assert!(wd.upgrade().is_some());
+        let c = c.lock().unwrap();
+        match &c.v {
+            Oop::Array(ary) => assert_eq!(ary.elements.len(), 1),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn t_collect_refs() {
+        flags::parse_x_opt("X:+UseMarkSweepGC").unwrap();
+        let new_cls = || {
+            let cls = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::INT);
+            new_sync_ref!(cls)
+        };
+        let cls = new_cls();
+        let ref_cls = |kind| {
+            let class = new_cls();
+            let v = refs::RefClass {
+                kind,
+                referent: 0,
+                next: 1,
+                discovered: 2,
+            };
+            refs::register(&class, v);
+            class
+        };
+        let (weak_cls, soft_cls) = (ref_cls(RefKind::Weak), ref_cls(RefKind::Soft));
+        let (final_cls, phantom_cls) = (ref_cls(RefKind::Final), ref_cls(RefKind::Phantom));
+
+        //a reference is an Inst of a reference class, the referent in slot
+        //0, next in 1, discovered in 2
+        let new_ref = |class: &ClassRef, referent: &OopRef| {
+            let v = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+            v.lock().unwrap().v = Oop::Inst(InstOopDesc {
+                class: class.clone(),
+                field_values: vec![
+                    referent.clone(),
+                    oop_consts::get_null(),
+                    oop_consts::get_null(),
+                ],
+            });
+            v
+        };
+        let field = |r: &OopRef, slot: usize| match &r.lock().unwrap().v {
+            Oop::Inst(inst) => inst.field_values[slot].clone(),
+            _ => unreachable!(),
+        };
+        let referent = |r: &OopRef| field(r, 0);
+
+        let gone = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+        let held = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+        let softly = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+        let (r1, r2) = (new_ref(&weak_cls, &gone), new_ref(&weak_cls, &held));
+        let r3 = new_ref(&soft_cls, &softly);
+        let (w_gone, w_softly) = (Arc::downgrade(&gone), Arc::downgrade(&softly));
+        drop(gone);
+        drop(softly);
+
+        collect();
+        assert!(w_gone.upgrade().is_none());
+        assert!(matches!(referent(&r1).lock().unwrap().v, Oop::Null));
+        //pending, active no more
+        assert!(Arc::ptr_eq(&field(&r1, 1), &r1));
+        assert!(Arc::ptr_eq(&referent(&r2), &held));
+        assert!(matches!(field(&r2, 1).lock().unwrap().v, Oop::Null));
+        assert!(w_softly.upgrade().is_some());
+
+        collect_all();
+        assert!(w_softly.upgrade().is_none());
+        assert!(matches!(referent(&r3).lock().unwrap().v, Oop::Null));
+
+        //finalizable, kept with what it reaches, weak refs to it cleared
+        let child = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+        let fin = OopDesc::new_ref_ary2(cls.clone(), vec![child.clone()]);
+        let (r4, r5) = (new_ref(&weak_cls, &fin), new_ref(&final_cls, &fin));
+        let w_child = Arc::downgrade(&child);
+        drop(child);
+        drop(fin);
+
+        collect();
+        assert!(matches!(referent(&r4).lock().unwrap().v, Oop::Null));
+        let fin = referent(&r5);
+        match &fin.lock().unwrap().v {
+            Oop::Array(ary) => assert_eq!(ary.elements.len(), 1),
+            _ => unreachable!(),
+        }
+        assert!(w_child.upgrade().is_some());
+
+        //the finalizer ran, an inactive reference is no weak edge: the
+        //referent stays
+        drop(fin);
+        collect();
+        assert!(matches!(referent(&r5).lock().unwrap().v, Oop::Array(_)));
+
+        //phantom reachable, kept until cleared (JDK 8)
+        let phantom = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+        let r6 = new_ref(&phantom_cls, &phantom);
+        drop(phantom);
+        collect();
+        assert!(matches!(referent(&r6).lock().unwrap().v, Oop::Array(_)));
+        assert!(Arc::ptr_eq(&field(&r6, 1), &r6));
+    }
+
+    #[test]
+    fn t_instances_of() {
+        flags::parse_x_opt("X:+TrackHeapObjects").unwrap();
+        let cls = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::INT);
+        let cls = new_sync_ref!(cls);
+        let other = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::LONG);
+        let other = new_sync_ref!(other);
+
+        let a = OopDesc::new_ref_ary2(cls.clone(), vec![]);
+        let b = OopDesc::new_ref_ary(cls.clone(), 2);
+        let _c = OopDesc::new_ref_ary2(other, vec![]);
+        let found = instances_of(&cls, false);
+        assert_eq!(found.count(), 2);
+        assert!(found.objects.iter().any(|it| Arc::ptr_eq(it, &a)));
+        assert_eq!(
+            found.shallow_size,
+            2 * size_of::<OopDesc>() + 2 * size_of::<OopRef>()
+        );
+
+        drop(found);
+        drop(b);
+        assert_eq!(instances_of(&cls, false).count(), 1);
+    }
+
+    #[test]
+    fn t_fits() {
+        assert_eq!(fits(10, 20, 30), Some(30));
+        assert_eq!(fits(10, 21, 30), None);
+        assert_eq!(fits(usize::MAX, 1, usize::MAX), None);
+    }
+
+    #[test]
+    fn t_shallow_size() {
+        let v = Oop::TypeArray(TypeArrayValue::Long(Box::new(vec![0; 3])));
+        assert_eq!(shallow_size(&v), size_of::<OopDesc>() + 24);
+        assert_eq!(shallow_size(&Oop::Null), size_of::<OopDesc>());
+    }
+
+    #[test]
+    fn t_tally() {
+        let objects = vec![
+            (b"[C".to_vec(), 40),
+            (b"java/lang/String".to_vec(), 32),
+            (b"[C".to_vec(), 24),
+            (b"java/lang/Object".to_vec(), 16),
+            (b"java/lang/Integer".to_vec(), 32),
+        ];
+        let entries = tally(objects.into_iter());
+        let names: Vec<&[u8]> = entries.iter().map(|it| it.name.as_slice()).collect();
+        assert_eq!(
+            names,
+            vec![
+                &b"[C"[..],
+                b"java/lang/Integer",
+                b"java/lang/String",
+                b"java/lang/Object"
+            ]
+        );
+        assert_eq!((entries[0].count, entries[0].bytes), (2, 64));
+
+        let table = format_histogram(&entries);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].ends_with(" 2             64  [C"));
+        assert!(lines[5].ends_with(" 5            144"));
+    }
+}
@@ -0,0 +1,142 @@
+/*
+Parallel phases of the collector
+
+A collection is split over up to -XX:ParallelGCThreads workers (the
+number of cpus by default), scoped threads which live for one phase, the
+collecting thread is worker 0:
+  scan:  each worker scans a contiguous range of the objects, & finds the
+         roots among them
+  mark:  the roots are dealt round robin to per-worker deques, a worker
+         pops the back of its own (depth first), an idle worker steals
+         the front of the others', until every deque is empty & no worker
+         is busy
+  sweep: ranges again
+
+A worker pushes only to its own deque & goes idle only with it empty, so
+all deques are empty once no worker is busy.
+
+A small heap is collected on the calling thread alone, spawning costs
+more than it saves under MIN_OBJECTS_PER_WORKER objects per worker.
+*/
+
+use crate::runtime::{flags, thread};
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const MIN_OBJECTS_PER_WORKER: usize = 10_000;
+
+//the workers for a heap of n objects
+pub fn workers(n: usize) -> usize {
+    (n / MIN_OBJECTS_PER_WORKER).clamp(1, flags::parallel_gc_threads())
+}
+
+//f(worker) on n workers, the results by worker
+pub fn run<T, F>(n: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    if n == 1 {
+        return vec![f(0)];
+    }
+
+    std::thread::scope(|s| {
+        let f = &f;
+        let handles: Vec<_> = (1..n).map(|i| s.spawn(move || f(i))).collect();
+        let mut out = vec![f(0)];
+        out.extend(handles.into_iter().map(|it| it.join().unwrap()));
+        out
+    })
+}
+
+//the range of worker i of n, over len items
+pub fn range(i: usize, n: usize, len: usize) -> Range<usize> {
+    let chunk = len.div_ceil(n);
+    (i * chunk).min(len)..((i + 1) * chunk).min(len)
+}
+
+//mark everything reachable from roots, the roots are marked already
+pub fn mark(edges: &[Vec<usize>], roots: &[usize], marked: &[AtomicBool], n: usize) {
+    let deques: Vec<Mutex<VecDeque<usize>>> = (0..n).map(|_| Mutex::new(VecDeque::new())).collect();
+    for (k, &it) in roots.iter().enumerate() {
+        deques[k % n].lock().unwrap().push_back(it);
+    }
+    let busy = AtomicUsize::new(n);
+
+    run(n, |me| loop {
+        let next = deques[me].lock().unwrap().pop_back();
+        let i = match next.or_else(|| steal(&deques, me)) {
+            Some(i) => i,
+            None if idle(&deques, &busy) => continue,
+            None => return,
+        };
+
+        let children = edges[i]
+            .iter()
+            .filter(|&&j| !marked[j].swap(true, Ordering::Relaxed));
+        deques[me].lock().unwrap().extend(children);
+    });
+}
+
+fn steal(deques: &[Mutex<VecDeque<usize>>], me: usize) -> Option<usize> {
+    let n = deques.len();
+    (1..n).find_map(|k| deques[(me + k) % n].lock().unwrap().pop_front())
+}
+
+//out of work, true once some deque has work again, false when no worker
+//is busy, the mark is done
+fn idle(deques: &[Mutex<VecDeque<usize>>], busy: &AtomicUsize) -> bool {
+    busy.fetch_sub(1, Ordering::SeqCst);
+    loop {
+        if deques.iter().any(|it| !it.lock().unwrap().is_empty()) {
+            busy.fetch_add(1, Ordering::SeqCst);
+            return true;
+        }
+        if busy.load(Ordering::SeqCst) == 0 {
+            return false;
+        }
+        thread::spin_wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_range() {
+        let ranges: Vec<Range<usize>> = (0..3).map(|i| range(i, 3, 10)).collect();
+        assert_eq!(ranges, vec![0..4, 4..8, 8..10]);
+        assert_eq!(range(3, 4, 2), 2..2);
+        assert_eq!(run(3, |i| i * 2), vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn t_mark() {
+        //two chains from 0 & 1, joined at the end, the odd tail unreachable
+        let len = 10_000;
+        let mut edges = vec![vec![]; len + 2];
+        for i in (0..len - 2).step_by(2) {
+            edges[i].push(i + 2);
+            edges[i + 1].push(i + 3);
+        }
+        edges[len - 2].push(len);
+        edges[len - 1].push(len);
+        edges[len + 1].push(0);
+
+        for n in [1, 4] {
+            let marked: Vec<AtomicBool> = (0..len + 2).map(|_| AtomicBool::new(false)).collect();
+            let roots = [0, 1];
+            roots
+                .iter()
+                .for_each(|&it| marked[it].store(true, Ordering::Relaxed));
+            mark(&edges, &roots, &marked, n);
+
+            let marked: Vec<bool> = marked.iter().map(|it| it.load(Ordering::Relaxed)).collect();
+            assert!(marked[..len + 1].iter().all(|&it| it));
+            assert!(!marked[len + 1]);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+/*
+java.lang.ref, Soft, Weak, Final & Phantom references
+
+A Reference holds its referent in a field like any other, a strong Arc.
+The collector scans that field of an active reference (next is null) as
+a weak edge: counted among the refs from inside the heap (a referent held
+only by references is no root), but not followed by the mark. A marked
+reference to an unmarked referent is pending, its next set to itself, it
+is active no more.
+
+  Weak     cleared at every collection, the referent is garbage & swept
+  Soft     followed as a strong edge, cleared only by the collection an
+           allocation over -Xmx asks for, before OutOfMemoryError
+  Final    not cleared, the referent is kept, with all it reaches, for
+           its finalizer to run. Weak refs to it are cleared first
+  Phantom  not cleared either (JDK 8), the referent is kept until clear()
+           or the reference is gone. Processed once the finalizable are
+           kept, they are not phantom reachable
+
+Finalization: an instance of a class with a finalize() of its own (not
+Object's, not empty) gets a Finalizer, a FinalReference, as it is
+allocated. Once only its Finalizer reaches it, the reference is pending,
+Finalizer's queue gets it & the finalizer thread (started by Finalizer's
+<clinit>) runs finalize(), then clears the referent. The object is
+reclaimed by the next collection, or at once if acyclic, finalize() is
+not run again. Only with -XX:+UseMarkSweepGC, a Finalizer keeps its
+object alive, only the collector tells when no one else does.
+
+The pending references are handed over as by HotSpot 8, after the
+collection, out of the safepoint: under the monitor of Reference.lock
+they are put in front of Reference.pending, linked by discovered, & the
+lock is notified. The Reference Handler thread (started by Reference's
+<clinit>) takes them, Cleaner.clean() for a sun.misc.Cleaner, the
+ReferenceQueue of others gets them. Kept for the next collection while
+Reference has no lock yet.
+
+Classes are registered when linked, a subclass of a reference class is
+one of the same kind, keyed by the class address: the collector looks it
+up without taking the class lock.
+*/
+
+use crate::classfile::consts;
+use crate::oop::{consts as oop_consts, Oop};
+use crate::runtime::{flags, monitor, sys_dic_find, JavaCall, JavaThread, Stack};
+use crate::types::{ClassRef, OopRef};
+use crate::util;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefKind {
+    Soft,
+    Weak,
+    Final,
+    Phantom,
+}
+
+//a reference class, & the slots of the Reference fields in its instances
+#[derive(Debug, Clone, Copy)]
+pub struct RefClass {
+    pub kind: RefKind,
+    pub referent: usize,
+    pub next: usize,
+    pub discovered: usize,
+}
+
+lazy_static! {
+    static ref REF_CLASSES: Mutex<HashMap<usize, RefClass>> = Mutex::new(HashMap::new());
+    //found by the collections, not handed over yet
+    static ref PENDING: Mutex<Vec<OopRef>> = Mutex::new(Vec::new());
+}
+
+fn kind_of(name: &[u8]) -> Option<RefKind> {
+    match name {
+        b"java/lang/ref/SoftReference" => Some(RefKind::Soft),
+        b"java/lang/ref/WeakReference" => Some(RefKind::Weak),
+        b"java/lang/ref/FinalReference" => Some(RefKind::Final),
+        b"java/lang/ref/PhantomReference" => Some(RefKind::Phantom),
+        _ => None,
+    }
+}
+
+//Class::link_class, slot is that of a Reference field (name, desc), asked
+//for only if the class is one of the reference classes
+pub fn on_link(
+    class: &ClassRef,
+    name: &[u8],
+    super_class: Option<&ClassRef>,
+    slot: impl Fn(&[u8], &[u8]) -> usize,
+) {
+    let super_ref = super_class.and_then(find);
+    let v = match (kind_of(name), super_ref) {
+        (Some(kind), _) => RefClass {
+            kind,
+            referent: slot(b"referent", b"Ljava/lang/Object;"),
+            next: slot(b"next", b"Ljava/lang/ref/Reference;"),
+            discovered: slot(b"discovered", b"Ljava/lang/ref/Reference;"),
+        },
+        (None, Some(v)) => v,
+        (None, None) => return,
+    };
+    register(class, v);
+}
+
+pub fn register(class: &ClassRef, v: RefClass) {
+    util::sync_call_ctx(&REF_CLASSES, |it| it.insert(addr(class), v));
+}
+
+fn find(class: &ClassRef) -> Option<RefClass> {
+    util::sync_call_ctx(&REF_CLASSES, |it| it.get(&addr(class)).copied())
+}
+
+fn addr(class: &ClassRef) -> usize {
+    Arc::as_ptr(class) as usize
+}
+
+//the reference classes, for one collection
+pub struct RefClasses {
+    classes: HashMap<usize, RefClass>,
+    clear_soft: bool,
+}
+
+impl RefClasses {
+    pub fn snapshot(clear_soft: bool) -> Self {
+        let classes = util::sync_call_ctx(&REF_CLASSES, |it| it.clone());
+        Self {
+            classes,
+            clear_soft,
+        }
+    }
+
+    //the reference class of v, its referent scanned as a weak edge, None
+    //if v is no active reference, or a SoftReference kept by this
+    //collection
+    pub fn weak_ref(&self, v: &Oop) -> Option<RefClass> {
+        let (class, inst) = match v {
+            Oop::Inst(inst) => (self.classes.get(&addr(&inst.class))?, inst),
+            _ => return None,
+        };
+        //next may be the reference itself, locked by the scan
+        let next = &inst.field_values[class.next];
+        let active = Arc::ptr_eq(next, &oop_consts::get_null())
+            || next.try_lock().is_ok_and(|it| matches!(it.v, Oop::Null));
+        if !active {
+            return None;
+        }
+        match class.kind {
+            RefKind::Soft if !self.clear_soft => None,
+            _ => Some(*class),
+        }
+    }
+}
+
+/*
+After the mark: (reference, referent, its class) as found by the scan,
+indexes of objects, those of kinds are processed. A marked reference to
+an unmarked referent is pending, & cleared if Soft or Weak. The referents
+to keep are returned, to be marked with all they reach: of Final &
+Phantom, & of references locked since the scan, which stay active.
+*/
+pub fn process(
+    objects: &[OopRef],
+    referents: &[(usize, usize, RefClass)],
+    marked: &[AtomicBool],
+    kinds: &[RefKind],
+) -> (usize, Vec<usize>) {
+    let is_marked = |i: usize| marked[i].load(Ordering::Relaxed);
+    let mut pending = Vec::new();
+    let mut kept = Vec::new();
+    for &(r, j, class) in referents {
+        if !kinds.contains(&class.kind) || !is_marked(r) || is_marked(j) {
+            continue;
+        }
+        let mut v = match objects[r].try_lock() {
+            Ok(v) => v,
+            Err(_) => {
+                kept.push(j);
+                continue;
+            }
+        };
+        if let Oop::Inst(inst) = &mut v.v {
+            match class.kind {
+                RefKind::Soft | RefKind::Weak => {
+                    inst.field_values[class.referent] = oop_consts::get_null()
+                }
+                RefKind::Final | RefKind::Phantom => kept.push(j),
+            }
+            inst.field_values[class.next] = objects[r].clone();
+        }
+        pending.push(objects[r].clone());
+    }
+
+    let n = pending.len();
+    if n > 0 {
+        util::sync_call_ctx(&PENDING, |it| it.extend(pending));
+    }
+    (n, kept)
+}
+
+//the pending references to Reference.pending, after a collection, on
+//the collecting thread. Out of the safepoint: the handler may hold the
+//lock, & is notified
+pub fn publish_pending() {
+    if util::sync_call_ctx(&PENDING, |it| it.is_empty()) {
+        return;
+    }
+    //not loaded or not initialized, no handler yet
+    let class = match sys_dic_find(consts::J_REFERENCE) {
+        Some(class) => class,
+        None => return,
+    };
+    let (lock, pending_id) = {
+        let cls = class.lock().unwrap();
+        let lock_id = cls.get_field_id(b"lock", b"Ljava/lang/ref/Reference$Lock;", true);
+        let pending_id = cls.get_field_id(b"pending", b"Ljava/lang/ref/Reference;", true);
+        (cls.get_static_field_value(lock_id), pending_id)
+    };
+    if matches!(lock.lock().unwrap().v, Oop::Null) {
+        return;
+    }
+
+    monitor::enter(&lock);
+    let pending = util::sync_call_ctx(&PENDING, std::mem::take);
+    let mut head = class
+        .lock()
+        .unwrap()
+        .get_static_field_value(pending_id.clone());
+    //in front of those not taken yet, linked by discovered
+    for r in pending.into_iter().rev() {
+        if let Oop::Inst(inst) = &mut r.lock().unwrap().v {
+            if let Some(v) = find(&inst.class) {
+                inst.field_values[v.discovered] = head;
+            }
+        }
+        head = r;
+    }
+    class
+        .lock()
+        .unwrap()
+        .put_static_field_value(pending_id, head);
+    let _ = monitor::notify(&lock, true);
+    let _ = monitor::exit(&lock);
+}
+
+//Finalizer.register(obj), at allocation, obj's class has_finalizer
+pub fn register_finalizer(jt: &mut JavaThread, obj: &OopRef) {
+    if !flags::is_use_gc() {
+        return;
+    }
+    //loaded by init_vm
+    let mir = sys_dic_find(consts::J_FINALIZER).and_then(|cls| {
+        let id = util::new_method_id(b"register", b"(Ljava/lang/Object;)V");
+        cls.lock().unwrap().get_static_method(id).ok()
+    });
+    if let Some(mir) = mir {
+        let mut stack = Stack::new(0);
+        let mut jc = JavaCall::new_with_args(jt, mir, vec![obj.clone()]);
+        jc.invoke(jt, &mut stack, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_kind_of() {
+        assert_eq!(kind_of(b"java/lang/ref/WeakReference"), Some(RefKind::Weak));
+        assert_eq!(kind_of(b"java/lang/ref/SoftReference"), Some(RefKind::Soft));
+        assert_eq!(kind_of(b"java/lang/ref/Reference"), None);
+        assert_eq!(
+            kind_of(b"java/lang/ref/FinalReference"),
+            Some(RefKind::Final)
+        );
+        assert_eq!(kind_of(b"java/lang/ref/Finalizer"), None);
+    }
+}
@@ -0,0 +1,268 @@
+/*
+Global, weak & local handles, for embedders holding guest objects across
+calls, as JNI references
+
+GlobalHandle keeps the object alive, until delete_global.
+WeakHandle doesn't, resolve_weak returns None after the object is gone.
+LocalRef keeps it alive until its frame is popped: each native call runs
+in a frame of its own, pushed & popped by the interpreter around it, a
+native may push inner frames to free its locals early (a loop over many
+objects). The locals of a thread are its LocalRefs, jt.local_refs. A
+native made by new_local_fn gets its args as locals of its frame.
+
+A LocalRef is a slot & a generation, unique in the vm: a slot is reused
+once its frame is popped, a stale LocalRef (or one of another thread)
+resolves to None, not to the object now in the slot.
+
+Objects are reference counted (OopRef is an Arc), a weak handle is a
+std::sync::Weak, the object is freed when the last strong ref drops.
+roots() lists the strong handles, the root set for a tracing collector,
+LocalRefs::roots those of a thread. Code holding handles instead of
+OopRefs sees the object where a moving collector put it.
+*/
+
+use crate::oop::OopDesc;
+use crate::runtime::JavaThread;
+use crate::types::OopRef;
+use crate::util;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlobalHandle(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WeakHandle(usize);
+
+struct HandleTable {
+    next_id: usize,
+    globals: HashMap<usize, OopRef>,
+    weaks: HashMap<usize, Weak<Mutex<Box<OopDesc>>>>,
+}
+
+lazy_static! {
+    static ref HANDLES: Mutex<HandleTable> = {
+        Mutex::new(HandleTable {
+            next_id: 0,
+            globals: HashMap::new(),
+            weaks: HashMap::new(),
+        })
+    };
+}
+
+pub fn new_global(v: OopRef) -> GlobalHandle {
+    util::sync_call_ctx(&HANDLES, |h| {
+        h.next_id += 1;
+        h.globals.insert(h.next_id, v);
+        GlobalHandle(h.next_id)
+    })
+}
+
+pub fn resolve_global(handle: GlobalHandle) -> Option<OopRef> {
+    util::sync_call_ctx(&HANDLES, |h| h.globals.get(&handle.0).cloned())
+}
+
+pub fn delete_global(handle: GlobalHandle) {
+    util::sync_call_ctx(&HANDLES, |h| {
+        h.globals.remove(&handle.0);
+    })
+}
+
+pub fn new_weak(v: &OopRef) -> WeakHandle {
+    util::sync_call_ctx(&HANDLES, |h| {
+        h.next_id += 1;
+        h.weaks.insert(h.next_id, Arc::downgrade(v));
+        WeakHandle(h.next_id)
+    })
+}
+
+//None, if the object has been freed or the handle deleted
+pub fn resolve_weak(handle: WeakHandle) -> Option<OopRef> {
+    util::sync_call_ctx(&HANDLES, |h| {
+        h.weaks.get(&handle.0).and_then(|it| it.upgrade())
+    })
+}
+
+pub fn delete_weak(handle: WeakHandle) {
+    util::sync_call_ctx(&HANDLES, |h| {
+        h.weaks.remove(&handle.0);
+    })
+}
+
+pub fn roots() -> Vec<OopRef> {
+    util::sync_call_ctx(&HANDLES, |h| h.globals.values().cloned().collect())
+}
+
+//(global, weak) handle counts
+pub fn count() -> (usize, usize) {
+    util::sync_call_ctx(&HANDLES, |h| (h.globals.len(), h.weaks.len()))
+}
+
+//drop weak handles whose object has been freed, returns the number removed
+pub fn purge_weaks() -> usize {
+    util::sync_call_ctx(&HANDLES, |h| {
+        let before = h.weaks.len();
+        h.weaks.retain(|_, it| it.strong_count() > 0);
+        before - h.weaks.len()
+    })
+}
+
+//the generation of the next LocalRef
+static NEXT_LOCAL: AtomicU64 = AtomicU64::new(1);
+
+//(slot, generation)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalRef(usize, u64);
+
+#[derive(Default)]
+pub struct LocalRefs {
+    //(generation, object), None once deleted
+    refs: Vec<(u64, Option<OopRef>)>,
+    //the start of each frame in refs
+    frames: Vec<usize>,
+}
+
+impl LocalRefs {
+    //the depth before, for pop_to
+    pub fn push_frame(&mut self) -> usize {
+        self.frames.push(self.refs.len());
+        self.frames.len() - 1
+    }
+
+    //the locals of the top frame are freed, result is a local of the frame
+    //below, as PopLocalFrame
+    pub fn pop_frame(&mut self, result: Option<OopRef>) -> Option<LocalRef> {
+        if let Some(start) = self.frames.pop() {
+            self.refs.truncate(start);
+        }
+        result.map(|v| self.new_local(v))
+    }
+
+    //pops the frames over depth, a native may leave its own pushed
+    pub fn pop_to(&mut self, depth: usize) {
+        if let Some(&start) = self.frames.get(depth) {
+            self.frames.truncate(depth);
+            self.refs.truncate(start);
+        }
+    }
+
+    pub fn new_local(&mut self, v: OopRef) -> LocalRef {
+        let gen = NEXT_LOCAL.fetch_add(1, Ordering::Relaxed);
+        self.refs.push((gen, Some(v)));
+        LocalRef(self.refs.len() - 1, gen)
+    }
+
+    //None if deleted or its frame popped
+    pub fn resolve(&self, r: LocalRef) -> Option<OopRef> {
+        match self.refs.get(r.0) {
+            Some((gen, v)) if *gen == r.1 => v.clone(),
+            _ => None,
+        }
+    }
+
+    pub fn delete(&mut self, r: LocalRef) {
+        match self.refs.get_mut(r.0) {
+            Some((gen, v)) if *gen == r.1 => *v = None,
+            _ => (),
+        }
+    }
+
+    pub fn roots(&self) -> Vec<OopRef> {
+        self.refs.iter().filter_map(|(_, v)| v.clone()).collect()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+pub fn new_local(jt: &mut JavaThread, v: OopRef) -> LocalRef {
+    jt.local_refs.new_local(v)
+}
+
+pub fn resolve_local(jt: &JavaThread, r: LocalRef) -> Option<OopRef> {
+    jt.local_refs.resolve(r)
+}
+
+pub fn delete_local(jt: &mut JavaThread, r: LocalRef) {
+    jt.local_refs.delete(r)
+}
+
+pub fn push_local_frame(jt: &mut JavaThread) {
+    jt.local_refs.push_frame();
+}
+
+pub fn pop_local_frame(jt: &mut JavaThread, result: Option<OopRef>) -> Option<LocalRef> {
+    jt.local_refs.pop_frame(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_handles() {
+        let v = OopDesc::new_int(1);
+        let g = new_global(v.clone());
+        let w = new_weak(&v);
+        drop(v);
+
+        //kept alive by the global handle
+        assert!(resolve_weak(w).is_some());
+        assert!(roots()
+            .iter()
+            .any(|it| Arc::ptr_eq(it, &resolve_global(g).unwrap())));
+
+        delete_global(g);
+        assert!(resolve_global(g).is_none());
+        assert!(resolve_weak(w).is_none());
+        assert!(purge_weaks() >= 1);
+    }
+
+    #[test]
+    fn t_local_refs() {
+        let mut locals = LocalRefs::default();
+        let depth = locals.push_frame();
+        let a = locals.new_local(OopDesc::new_int(1));
+        let b = locals.new_local(OopDesc::new_int(2));
+        locals.delete(a);
+        assert!(locals.resolve(a).is_none());
+        assert_eq!(locals.roots().len(), 1);
+
+        //an inner frame, its result outlives it
+        locals.push_frame();
+        let c = locals.new_local(OopDesc::new_int(3));
+        let v = locals.resolve(c);
+        let r = locals.pop_frame(v.clone()).unwrap();
+        assert!(Arc::ptr_eq(&locals.resolve(r).unwrap(), &v.unwrap()));
+        assert!(locals.resolve(b).is_some());
+
+        //a frame left pushed is popped too
+        locals.push_frame();
+        locals.new_local(OopDesc::new_int(4));
+        locals.pop_to(depth);
+        assert_eq!((locals.depth(), locals.roots().len()), (0, 0));
+        assert!(locals.resolve(b).is_none());
+    }
+
+    #[test]
+    fn t_local_reuse() {
+        let mut locals = LocalRefs::default();
+        locals.push_frame();
+        let a = locals.new_local(OopDesc::new_int(1));
+        locals.pop_frame(None);
+
+        //the slot of a, another object
+        locals.push_frame();
+        let b = locals.new_local(OopDesc::new_int(2));
+        assert_eq!(a.0, b.0);
+        assert!(locals.resolve(a).is_none());
+        locals.delete(a);
+        assert!(locals.resolve(b).is_some());
+
+        //a local of another thread
+        let other = LocalRefs::default().new_local(OopDesc::new_int(3));
+        assert!(locals.resolve(other).is_none());
+    }
+}
@@ -0,0 +1,238 @@
+/*
+Async host natives, a Rust future behind a CompletableFuture
+
+An embedder registers an async fn as a native, its java side returns a
+java/util/concurrent/CompletableFuture:
+
+  new_async_fn("fetch", "(I)Ljava/util/concurrent/CompletableFuture;",
+      |args| async move {
+          let body = service.fetch(...).await;
+          Box::new(move |jt: &mut JavaThread| -> JNIResult { ... }) as Completion
+      })
+
+The native returns a new, incomplete future at once. The Rust future is
+run by the host executor, one thread polling the ready tasks, woken by
+their Wakers. Its output, a Completion, builds the guest value: it runs
+on the completer, a daemon java thread, Ok completes the future with the
+value (null for None), Err exceptionally with the exception. Guest
+callbacks (thenApply, ...) registered before run on the completer too.
+
+Both threads are started by the first call, the completer gets its
+Thread object from the calling thread, in its group.
+*/
+
+use crate::classfile::consts;
+use crate::native::{new_fn, JNINativeMethod, JNIResult};
+use crate::oop::{self, OopDesc};
+use crate::runtime::{self, require_class3, thread, JavaCall, JavaThread, Stack};
+use crate::types::OopRef;
+use crate::util;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Wake, Waker};
+
+//builds the value of the CompletableFuture, on the completer
+pub type Completion = Box<dyn FnOnce(&mut JavaThread) -> JNIResult + Send>;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+struct Task {
+    //None once done
+    future: Mutex<Option<BoxFuture>>,
+    executor: Sender<Arc<Task>>,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        let executor = self.executor.clone();
+        let _ = executor.send(self);
+    }
+}
+
+lazy_static! {
+    static ref EXECUTOR: Mutex<Option<Sender<Arc<Task>>>> = Mutex::new(None);
+    static ref COMPLETER: Mutex<Option<Sender<(OopRef, Completion)>>> = Mutex::new(None);
+}
+
+pub fn new_async_fn<F, Fut>(name: &'static str, signature: &'static str, f: F) -> JNINativeMethod
+where
+    F: Fn(Vec<OopRef>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Completion> + Send + 'static,
+{
+    new_fn(
+        name,
+        signature,
+        Box::new(move |jt, _env, args| {
+            let completer = completer(jt);
+            let cls = oop::class::load_and_init(jt, consts::J_COMPLETABLE_FUTURE);
+            let cf = OopDesc::new_inst(cls.clone());
+            runtime::java_call::invoke_ctor(jt, cls, b"()V", vec![cf.clone()]);
+            if let Some(ex) = jt.take_ex() {
+                return Err(ex);
+            }
+
+            let future = f(args);
+            let target = cf.clone();
+            spawn(async move {
+                let completion = future.await;
+                let _ = completer.send((target, completion));
+            });
+            Ok(Some(cf))
+        }),
+    )
+}
+
+//run future on the host executor
+pub fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+    let executor = util::sync_call_ctx(&EXECUTOR, |it| {
+        it.get_or_insert_with(start_executor).clone()
+    });
+    let task = Arc::new(Task {
+        future: Mutex::new(Some(Box::pin(future))),
+        executor: executor.clone(),
+    });
+    let _ = executor.send(task);
+}
+
+fn start_executor() -> Sender<Arc<Task>> {
+    let (tx, rx) = mpsc::channel::<Arc<Task>>();
+    let _ = std::thread::Builder::new()
+        .name("host-async".to_string())
+        .spawn(move || {
+            for task in rx {
+                let mut future = task.future.lock().unwrap();
+                //woken again after it was done
+                let done = match future.as_mut() {
+                    Some(it) => {
+                        let waker = Waker::from(task.clone());
+                        let mut cx = Context::from_waker(&waker);
+                        it.as_mut().poll(&mut cx).is_ready()
+                    }
+                    None => false,
+                };
+                if done {
+                    *future = None;
+                }
+            }
+        });
+    tx
+}
+
+//the first caller makes the Thread object under the lock, java code runs,
+//racing callers wait for it blocked & use its completer
+fn completer(jt: &mut JavaThread) -> Sender<(OopRef, Completion)> {
+    let mut completer = thread::in_state(thread::THREAD_STATUS_BLOCKED_ON_MONITOR_ENTER, || {
+        COMPLETER.lock().unwrap()
+    });
+    completer
+        .get_or_insert_with(|| start_completer(new_thread_oop(jt, "host-async-completer")))
+        .clone()
+}
+
+fn new_thread_oop(jt: &mut JavaThread, name: &str) -> OopRef {
+    let cls = require_class3(None, consts::J_THREAD).unwrap();
+    let thread_oop = OopDesc::new_inst(cls.clone());
+    let name = util::oop::new_java_lang_string2(jt, name);
+    let args = vec![thread_oop.clone(), name];
+    runtime::java_call::invoke_ctor(jt, cls.clone(), b"(Ljava/lang/String;)V", args);
+
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(b"daemon", b"Z", false);
+    cls.put_field_value(thread_oop.clone(), id, OopDesc::new_int(1));
+    thread_oop
+}
+
+fn start_completer(thread_oop: OopRef) -> Sender<(OopRef, Completion)> {
+    let (tx, rx) = mpsc::channel::<(OopRef, Completion)>();
+    let stack_size = runtime::flags::thread_stack_size();
+    thread::attach(&thread_oop, true);
+    let _ = std::thread::Builder::new()
+        .name("host-async-completer".to_string())
+        .stack_size(stack_size)
+        .spawn(move || {
+            thread::set_stack_size(stack_size);
+            let mut jt = JavaThread::new();
+            jt.set_java_thread_obj(thread_oop);
+            for (cf, completion) in rx {
+                complete(&mut jt, cf, completion);
+            }
+        });
+    tx
+}
+
+fn complete(jt: &mut JavaThread, cf: OopRef, completion: Completion) {
+    let (name, desc, v): (&[u8], &[u8], OopRef) = match completion(jt) {
+        Ok(v) => (
+            b"complete",
+            b"(Ljava/lang/Object;)Z",
+            v.unwrap_or_else(oop::consts::get_null),
+        ),
+        Err(ex) => (b"completeExceptionally", b"(Ljava/lang/Throwable;)Z", ex),
+    };
+
+    let cls = require_class3(None, consts::J_COMPLETABLE_FUTURE).unwrap();
+    let mir = cls
+        .lock()
+        .unwrap()
+        .get_virtual_method(util::new_method_id(name, desc));
+    if let Ok(mir) = mir {
+        let mut stack = Stack::new(1);
+        let mut jc = JavaCall::new_with_args(jt, mir, vec![cf, v]);
+        jc.invoke(jt, &mut stack, false);
+    }
+    if let Some(ex) = jt.take_ex() {
+        warn!("host async: {} threw", String::from_utf8_lossy(name));
+        runtime::exception::print_stack_trace(ex);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::task::Poll;
+    use std::time::Duration;
+
+    //ready once another thread set the value, as a host service reply
+    struct Reply(Arc<Mutex<(Option<i32>, Option<Waker>)>>);
+
+    impl Future for Reply {
+        type Output = i32;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<i32> {
+            let mut it = self.0.lock().unwrap();
+            match it.0 {
+                Some(v) => Poll::Ready(v),
+                None => {
+                    it.1 = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn t_spawn() {
+        let state = Arc::new(Mutex::new((None, None)));
+        let (tx, rx) = channel();
+        let reply = Reply(state.clone());
+        spawn(async move {
+            let v = reply.await;
+            tx.send(v + 1).unwrap();
+        });
+
+        //pending until replied
+        assert!(rx.recv_timeout(Duration::from_millis(20)).is_err());
+        let waker = {
+            let mut it = state.lock().unwrap();
+            it.0 = Some(41);
+            it.1.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(42));
+    }
+}
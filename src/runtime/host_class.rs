@@ -0,0 +1,152 @@
+/*
+Host classes, whole classes implemented in Rust
+
+An embedder registers a class name & a method table, the class loader
+synthesizes the class from it, no ".class" file is needed. Guest code
+calls the methods as normal Java static methods, e.g. with
+
+  register("demo/Host", vec![new_fn("add", "(II)I", Box::new(jvm_add))]);
+
+"demo.Host.add(1, 2)" in java code dispatches to jvm_add.
+
+Every method is 'public static native', the super class is java/lang/Object.
+Register before the class is first loaded.
+*/
+
+use crate::classfile::access_flags::*;
+use crate::classfile::attr_info::AttrType;
+use crate::classfile::constant_pool::ConstantType;
+use crate::classfile::{consts, ClassFile, MethodInfo, Version};
+use crate::native::{self, JNINativeMethod};
+use crate::types::*;
+use crate::util;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    static ref HOST_CLASSES: Mutex<HashMap<Vec<u8>, ClassFileRef>> = {
+        let hm = HashMap::new();
+        Mutex::new(hm)
+    };
+}
+
+pub fn register(name: &'static str, methods: Vec<JNINativeMethod>) {
+    let cf = build_class_file(name, &methods);
+    let cf = new_ref!(cf);
+    util::sync_call_ctx(&HOST_CLASSES, |h| {
+        h.insert(Vec::from(name.as_bytes()), cf);
+    });
+
+    native::register(name, methods);
+}
+
+pub fn find(name: &[u8]) -> Option<ClassFileRef> {
+    util::sync_call_ctx(&HOST_CLASSES, |h| h.get(name).cloned())
+}
+
+fn build_class_file(name: &str, methods: &[JNINativeMethod]) -> ClassFile {
+    let methods = methods
+        .iter()
+        .map(|it| {
+            let acc_flags = ACC_PUBLIC | ACC_STATIC | ACC_NATIVE;
+            (it.name(), it.signature(), acc_flags, vec![])
+        })
+        .collect();
+    let acc_flags = ACC_PUBLIC | ACC_FINAL | ACC_SUPER;
+    new_class_file(name, acc_flags, Some("java/lang/Object"), &[], methods)
+}
+
+//a method of a synthesized class: name, desc, acc_flags & attrs
+pub(crate) type MethodDef<'a> = (&'a str, &'a str, U2, Vec<AttrType>);
+
+//a class file built in memory, no fields
+pub(crate) fn new_class_file(
+    name: &str,
+    acc_flags: U2,
+    super_name: Option<&str>,
+    itfs: &[&str],
+    methods: Vec<MethodDef>,
+) -> ClassFile {
+    //cp[0] is not used
+    let mut cp = vec![ConstantType::Nop];
+    let this_class = push_class(&mut cp, name);
+    let super_class = super_name.map_or(0, |it| push_class(&mut cp, it));
+    let interfaces: Vec<U2> = itfs.iter().map(|it| push_class(&mut cp, it)).collect();
+
+    let methods: Vec<MethodInfo> = methods
+        .into_iter()
+        .map(|(name, desc, acc_flags, attrs)| MethodInfo {
+            acc_flags,
+            name_index: push_utf8(&mut cp, name),
+            desc_index: push_utf8(&mut cp, desc),
+            attrs,
+        })
+        .collect();
+
+    let cp_count = cp.len() as U2;
+    let cp = new_ref!(cp);
+    ClassFile {
+        magic: consts::MAGIC,
+        version: Version {
+            minor: 0,
+            major: 52,
+        },
+        cp_count,
+        cp,
+        acc_flags,
+        this_class,
+        super_class,
+        interfaces_count: interfaces.len() as U2,
+        interfaces,
+        fields_count: 0,
+        fields: vec![],
+        methods_count: methods.len() as U2,
+        methods,
+        attrs_count: 0,
+        attrs: vec![],
+    }
+}
+
+pub(crate) fn push_utf8(cp: &mut Vec<ConstantType>, s: &str) -> U2 {
+    let bytes = Vec::from(s.as_bytes());
+    let length = bytes.len() as U2;
+    let bytes = new_ref!(bytes);
+    cp.push(ConstantType::Utf8 { length, bytes });
+    (cp.len() - 1) as U2
+}
+
+pub(crate) fn push_class(cp: &mut Vec<ConstantType>, name: &str) -> U2 {
+    let name_index = push_utf8(cp, name);
+    cp.push(ConstantType::Class { name_index });
+    (cp.len() - 1) as U2
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::classfile::constant_pool;
+    use crate::native::new_fn;
+    use crate::oop::OopDesc;
+
+    #[test]
+    fn t_register() {
+        super::register(
+            "test/HostClass",
+            vec![new_fn(
+                "one",
+                "()I",
+                Box::new(|_, _, _| Ok(Some(OopDesc::new_int(1)))),
+            )],
+        );
+
+        let cf = super::find(b"test/HostClass").unwrap();
+        let name = constant_pool::get_class_name(&cf.cp, cf.this_class as usize).unwrap();
+        assert_eq!(name.as_slice(), b"test/HostClass");
+        assert_eq!(cf.methods.len(), 1);
+        let desc = constant_pool::get_utf8(&cf.cp, cf.methods[0].desc_index as usize).unwrap();
+        assert_eq!(desc.as_slice(), b"()I");
+
+        let id = ["test/HostClass", "one", "()I"].join(crate::util::PATH_SEP);
+        assert!(crate::native::find_symbol(id.as_bytes()).is_some());
+        assert!(super::find(b"test/NoSuchHostClass").is_none());
+    }
+}
@@ -1,7 +1,8 @@
 use crate::classfile::consts::{
-    J_ARRAY_INDEX_OUT_OF_BOUNDS, J_CLASS, J_CLASS_NOT_FOUND, J_CLONEABLE, J_FIELD, J_INPUT_STREAM,
-    J_INTERNAL_ERROR, J_IOEXCEPTION, J_METHOD_CTOR, J_NPE, J_OBJECT, J_PRINT_STREAM,
-    J_SECURITY_MANAGER, J_SERIALIZABLE, J_STRING, J_SYSTEM, J_THREAD, J_THREAD_GROUP, J_THROWABLE,
+    J_ARRAY_INDEX_OUT_OF_BOUNDS, J_CLASS, J_CLASS_NOT_FOUND, J_CLONEABLE, J_FIELD, J_FINALIZER,
+    J_INPUT_STREAM, J_INTERNAL_ERROR, J_IOEXCEPTION, J_METHOD_CTOR, J_NPE, J_OBJECT,
+    J_PRINT_STREAM, J_SECURITY_MANAGER, J_SERIALIZABLE, J_STRING, J_SYSTEM, J_THREAD,
+    J_THREAD_GROUP, J_THROWABLE,
 };
 use crate::native;
 use crate::oop::{self, OopDesc};
@@ -30,6 +31,8 @@ pub fn initialize_jvm(jt: &mut JavaThread) {
     // JavaMainThread is created with java_thread_obj none
     // Now we have created a thread for it.
     jt.set_java_thread_obj(init_thread_oop.clone());
+    runtime::thread::attach(&init_thread_oop, false);
+    runtime::thread::set_host(&init_thread_oop);
 
     // Create and construct the system thread group.
     let system_thread_group = OopDesc::new_inst(thread_group_cls.clone());
@@ -77,6 +80,7 @@ pub fn initialize_jvm(jt: &mut JavaThread) {
         b"(Ljava/lang/ThreadGroup;Ljava/lang/String;)V",
         args,
     );
+    util::set_thread_name("main");
 
     hack_classes(jt);
 
@@ -93,6 +97,14 @@ pub fn initialize_jvm(jt: &mut JavaThread) {
 
     //todo: re-enable sun.security.util.Debug
 
+    //thrown when the stack or the heap is exhausted, made while they are not
+    runtime::exception::preallocate_errors(jt);
+
+    //finalization, Finalizer.<clinit> starts the finalizer thread
+    if runtime::flags::is_use_gc() {
+        let _ = oop::class::load_and_init(jt, J_FINALIZER);
+    }
+
     //setup security
     let _ = oop::class::load_and_init(jt, b"sun/security/provider/Sun");
     let _ = oop::class::load_and_init(jt, b"sun/security/rsa/SunRsaSign");
@@ -148,6 +160,12 @@ fn hack_classes(jt: &mut JavaThread) {
         cls.put_static_field_value(id, ascii_inst);
     }
 
+    hack_methods(jt);
+}
+
+//the java methods run as natives, their classes initialized. Done again
+//on a restored heap, see runtime::snapshot
+pub fn hack_methods(jt: &mut JavaThread) {
     let encoder = oop::class::load_and_init(jt, b"sun/nio/cs/StreamEncoder");
     {
         let mut cls = encoder.lock().unwrap();
@@ -171,6 +189,24 @@ fn hack_classes(jt: &mut JavaThread) {
         //        cls.hack_as_native(id);
     }
 
+    let unsafe_cls = oop::class::load_and_init(jt, b"sun/misc/Unsafe");
+    {
+        let mut cls = unsafe_cls.lock().unwrap();
+        for (name, desc) in native::sun_misc_Unsafe::ATOMICS {
+            let id = util::new_method_id(name.as_bytes(), desc.as_bytes());
+            cls.hack_as_native(id);
+        }
+    }
+
+    let arrays = oop::class::load_and_init(jt, b"java/util/Arrays");
+    {
+        let mut cls = arrays.lock().unwrap();
+        for (name, desc) in native::java_util_Arrays::INTRINSICS {
+            let id = util::new_method_id(name.as_bytes(), desc.as_bytes());
+            cls.hack_as_native(id);
+        }
+    }
+
     /*
     let mut mir = {
         let cls = encoder.lock().unwrap();
@@ -0,0 +1,317 @@
+/*
+Interpreter intrinsics
+
+Well-known small methods, called from invoke_helper directly on the
+operand stack of the caller: no JavaCall, no frame, no locals, no arg
+OopRefs. Hot loops over Math.min or String.charAt are dominated by the
+call overhead otherwise.
+
+An intrinsic pops the args & pushes the result the way the method would,
+and throws what it would throw. The methods are static, or of a final
+class, the resolved method is the one called. The vm sees no call: no
+call graph edge, no step into the method. -XX:-UseIntrinsics turns them
+off, the methods run as bytecode or native again. An overridden method
+(native::set_override) runs its override instead.
+
+The lookup is done once per method, cached in Method::intrinsic.
+*/
+
+use crate::classfile::consts;
+use crate::native::{self, java_lang_System};
+use crate::oop::{Oop, TypeArrayValue};
+use crate::runtime::{exception, flags, thread, JavaThread, Stack};
+use crate::types::{MethodIdRef, OopRef};
+use crate::util;
+
+pub type Intrinsic = fn(&mut JavaThread, &mut Stack);
+
+//(class, name, desc, intrinsic)
+const INTRINSICS: &[(&str, &str, &str, Intrinsic)] = &[
+    ("java/lang/Math", "min", "(II)I", min_int),
+    ("java/lang/Math", "min", "(JJ)J", min_long),
+    ("java/lang/Math", "min", "(FF)F", min_float),
+    ("java/lang/Math", "min", "(DD)D", min_double),
+    ("java/lang/Math", "max", "(II)I", max_int),
+    ("java/lang/Math", "max", "(JJ)J", max_long),
+    ("java/lang/Math", "max", "(FF)F", max_float),
+    ("java/lang/Math", "max", "(DD)D", max_double),
+    ("java/lang/Math", "abs", "(I)I", abs_int),
+    ("java/lang/Math", "abs", "(J)J", abs_long),
+    ("java/lang/Math", "abs", "(F)F", abs_float),
+    ("java/lang/Math", "abs", "(D)D", abs_double),
+    ("java/lang/String", "length", "()I", string_length),
+    ("java/lang/String", "charAt", "(I)C", string_char_at),
+    (
+        "java/lang/Integer",
+        "numberOfLeadingZeros",
+        "(I)I",
+        int_leading_zeros,
+    ),
+    (
+        "java/lang/Long",
+        "numberOfLeadingZeros",
+        "(J)I",
+        long_leading_zeros,
+    ),
+    (
+        "java/lang/System",
+        "arraycopy",
+        "(Ljava/lang/Object;ILjava/lang/Object;II)V",
+        arraycopy,
+    ),
+    //JDK 9+, an empty method
+    ("java/lang/Thread", "onSpinWait", "()V", on_spin_wait),
+];
+
+pub fn find(class: &[u8], name: &[u8], desc: &[u8]) -> Option<Intrinsic> {
+    INTRINSICS
+        .iter()
+        .find(|(c, n, d, _)| c.as_bytes() == class && n.as_bytes() == name && d.as_bytes() == desc)
+        .map(|it| it.3)
+}
+
+//the intrinsic of the resolved method, if on
+pub fn lookup(mir: &MethodIdRef) -> Option<Intrinsic> {
+    if !flags::is_use_intrinsics() || native::is_overridden(mir) {
+        return None;
+    }
+
+    *mir.method.intrinsic.get_or_init(|| {
+        let cls = mir.method.class.lock().unwrap();
+        find(
+            cls.name.as_slice(),
+            mir.method.name.as_slice(),
+            mir.method.desc.as_slice(),
+        )
+    })
+}
+
+fn min_int(_jt: &mut JavaThread, stack: &mut Stack) {
+    let b = stack.pop_int();
+    let a = stack.pop_int();
+    stack.push_int(a.min(b));
+}
+
+fn min_long(_jt: &mut JavaThread, stack: &mut Stack) {
+    let b = stack.pop_long();
+    let a = stack.pop_long();
+    stack.push_long(a.min(b));
+}
+
+fn min_float(_jt: &mut JavaThread, stack: &mut Stack) {
+    let b = stack.pop_float();
+    let a = stack.pop_float();
+    stack.push_float(fmin(a as f64, b as f64) as f32);
+}
+
+fn min_double(_jt: &mut JavaThread, stack: &mut Stack) {
+    let b = stack.pop_double();
+    let a = stack.pop_double();
+    stack.push_double(fmin(a, b));
+}
+
+fn max_int(_jt: &mut JavaThread, stack: &mut Stack) {
+    let b = stack.pop_int();
+    let a = stack.pop_int();
+    stack.push_int(a.max(b));
+}
+
+fn max_long(_jt: &mut JavaThread, stack: &mut Stack) {
+    let b = stack.pop_long();
+    let a = stack.pop_long();
+    stack.push_long(a.max(b));
+}
+
+fn max_float(_jt: &mut JavaThread, stack: &mut Stack) {
+    let b = stack.pop_float();
+    let a = stack.pop_float();
+    stack.push_float(fmax(a as f64, b as f64) as f32);
+}
+
+fn max_double(_jt: &mut JavaThread, stack: &mut Stack) {
+    let b = stack.pop_double();
+    let a = stack.pop_double();
+    stack.push_double(fmax(a, b));
+}
+
+//Math.abs(Integer.MIN_VALUE) is MIN_VALUE
+fn abs_int(_jt: &mut JavaThread, stack: &mut Stack) {
+    let a = stack.pop_int();
+    stack.push_int(a.wrapping_abs());
+}
+
+fn abs_long(_jt: &mut JavaThread, stack: &mut Stack) {
+    let a = stack.pop_long();
+    stack.push_long(a.wrapping_abs());
+}
+
+fn abs_float(_jt: &mut JavaThread, stack: &mut Stack) {
+    let a = stack.pop_float();
+    stack.push_float(fabs(a as f64) as f32);
+}
+
+fn abs_double(_jt: &mut JavaThread, stack: &mut Stack) {
+    let a = stack.pop_double();
+    stack.push_double(fabs(a));
+}
+
+/*
+As Math.min & max: NaN if either is NaN, -0.0 is less than 0.0. The
+float versions go through f64, exact for every f32, NaN & -0.0 included.
+*/
+fn fmin(a: f64, b: f64) -> f64 {
+    if a.is_nan() {
+        a
+    } else if a == 0.0 && b == 0.0 && b.is_sign_negative() {
+        b
+    } else if a <= b {
+        a
+    } else {
+        b
+    }
+}
+
+fn fmax(a: f64, b: f64) -> f64 {
+    if a.is_nan() {
+        a
+    } else if a == 0.0 && b == 0.0 && a.is_sign_negative() {
+        b
+    } else if a >= b {
+        a
+    } else {
+        b
+    }
+}
+
+//(a <= 0.0) ? 0.0 - a : a, abs(-0.0) is 0.0, a NaN is kept as is
+fn fabs(a: f64) -> f64 {
+    if a <= 0.0 {
+        0.0 - a
+    } else {
+        a
+    }
+}
+
+fn int_leading_zeros(_jt: &mut JavaThread, stack: &mut Stack) {
+    let a = stack.pop_int();
+    stack.push_int(a.leading_zeros() as i32);
+}
+
+fn long_leading_zeros(_jt: &mut JavaThread, stack: &mut Stack) {
+    let a = stack.pop_long();
+    stack.push_int(a.leading_zeros() as i32);
+}
+
+//the String.value char[], None & NPE pending for null
+fn string_value(jt: &mut JavaThread, stack: &mut Stack) -> Option<OopRef> {
+    let this = stack.pop_ref();
+    let is_null = matches!(this.lock().unwrap().v, Oop::Null);
+    if is_null {
+        let ex = exception::new_implicit(jt, consts::J_NPE, None);
+        jt.set_ex(ex);
+        return None;
+    }
+    Some(util::oop::java_lang_string_value(this))
+}
+
+fn string_length(jt: &mut JavaThread, stack: &mut Stack) {
+    if let Some(value) = string_value(jt, stack) {
+        let value = value.lock().unwrap();
+        match &value.v {
+            Oop::TypeArray(ary) => stack.push_int(ary.len() as i32),
+            t => unreachable!("t = {:?}", t),
+        }
+    }
+}
+
+fn string_char_at(jt: &mut JavaThread, stack: &mut Stack) {
+    let index = stack.pop_int();
+    let value = match string_value(jt, stack) {
+        Some(value) => value,
+        None => return,
+    };
+
+    let c = {
+        let value = value.lock().unwrap();
+        match &value.v {
+            Oop::TypeArray(TypeArrayValue::Char(ary)) if index >= 0 => {
+                ary.get(index as usize).copied()
+            }
+            Oop::TypeArray(_) => None,
+            t => unreachable!("t = {:?}", t),
+        }
+    };
+
+    match c {
+        Some(c) => stack.push_int(c as i32),
+        None => {
+            let msg = format!("String index out of range: {}", index);
+            let ex = exception::new(jt, consts::J_STRING_INDEX_OUT_OF_BOUNDS, Some(msg));
+            jt.set_ex(ex);
+        }
+    }
+}
+
+fn arraycopy(jt: &mut JavaThread, stack: &mut Stack) {
+    let length = stack.pop_int();
+    let dest_pos = stack.pop_int();
+    let dest = stack.pop_ref();
+    let src_pos = stack.pop_int();
+    let src = stack.pop_ref();
+    if let Err(ex) = java_lang_System::arraycopy(jt, &src, src_pos, &dest, dest_pos, length) {
+        jt.set_ex(ex);
+    }
+}
+
+fn on_spin_wait(_jt: &mut JavaThread, _stack: &mut Stack) {
+    thread::spin_wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_find() {
+        assert!(find(b"java/lang/Math", b"min", b"(II)I").is_some());
+        assert!(find(b"java/lang/Math", b"min", b"(IJ)I").is_none());
+        assert!(find(b"java/lang/StrictMath", b"min", b"(II)I").is_none());
+        assert!(find(b"java/lang/Thread", b"onSpinWait", b"()V").is_some());
+    }
+
+    #[test]
+    fn t_fmin_fmax() {
+        assert!(fmin(f64::NAN, 1.0).is_nan());
+        assert!(fmin(1.0, f64::NAN).is_nan());
+        assert!(fmax(1.0, f64::NAN).is_nan());
+        assert!(fmin(0.0, -0.0).is_sign_negative());
+        assert!(fmin(-0.0, 0.0).is_sign_negative());
+        assert!(fmax(-0.0, 0.0).is_sign_positive());
+        assert!(fmax(0.0, -0.0).is_sign_positive());
+        assert_eq!(fmin(1.0, 2.0), 1.0);
+        assert_eq!(fmax(1.0, 2.0), 2.0);
+        assert!(fabs(-0.0).is_sign_positive());
+        assert_eq!(fabs(-3.5), 3.5);
+    }
+
+    #[test]
+    fn t_math() {
+        let mut jt = JavaThread::new();
+        let mut stack = Stack::new(4);
+        stack.push_int(i32::MIN);
+        abs_int(&mut jt, &mut stack);
+        assert_eq!(stack.pop_int(), i32::MIN);
+
+        stack.push_long(3);
+        stack.push_long(-7);
+        min_long(&mut jt, &mut stack);
+        assert_eq!(stack.pop_long(), -7);
+
+        stack.push_int(1);
+        int_leading_zeros(&mut jt, &mut stack);
+        assert_eq!(stack.pop_int(), 31);
+        stack.push_long(0);
+        long_leading_zeros(&mut jt, &mut stack);
+        assert_eq!(stack.pop_int(), 64);
+    }
+}
@@ -0,0 +1,286 @@
+/*
+java.lang.invoke constants, resolved by upcalls to the class library,
+as HotSpot does
+
+  CONSTANT_MethodType    MethodHandleNatives.findMethodHandleType(rtype, ptypes)
+  CONSTANT_MethodHandle  MethodHandleNatives.linkMethodHandleConstant(caller,
+                         refKind, defc, name, type)
+  CONSTANT_Dynamic       the bootstrap method called directly, bsm(lookup,
+                         name, type, staticArguments...), JDK 8 has no
+                         MethodHandleNatives.linkDynamicConstant
+
+type is the field type Class for REF_getField ~ REF_putStatic & condy, a
+MethodType for the method kinds. The value of a primitive condy comes
+boxed.
+
+The bootstrap method of a condy must be a REF_invokeStatic, called as
+Method.invoke would (arguments unboxed & widened), trailing arguments
+collected into the array of a varargs one. lookup is
+MethodHandles.Lookup.IMPL_LOOKUP.in(caller), as JDK 8 gives the
+bootstrap method of an invokedynamic. What the bootstrap method throws,
+other than an Error, is wrapped in BootstrapMethodError.
+
+The frame caches the results in the CpCache. A failed resolution leaves
+the exception pending & returns None.
+*/
+
+use crate::classfile::consts;
+use crate::classfile::signature::{FieldSignature, MethodSignature, Type as ArgType};
+use crate::native::java_lang_String;
+use crate::oop::{self, Oop, OopDesc};
+use crate::runtime::{self, cmp, exception, reflect, require_class3, JavaCall, JavaThread, Stack};
+use crate::types::{ClassRef, MethodIdRef, OopRef};
+use crate::util;
+
+//reference kinds of CONSTANT_MethodHandle, JVMS 5.4.3.5
+pub const REF_GET_FIELD: u8 = 1;
+pub const REF_PUT_STATIC: u8 = 4;
+pub const REF_INVOKE_STATIC: u8 = 6;
+pub const REF_INVOKE_INTERFACE: u8 = 9;
+
+pub fn new_method_type(jt: &mut JavaThread, desc: &[u8]) -> Option<OopRef> {
+    let signature = MethodSignature::new(desc);
+    let rtype = reflect::create_value_type(signature.retype);
+    let ptypes = reflect::new_class_ary(signature.args.as_slice());
+
+    call_mhn(
+        jt,
+        b"findMethodHandleType",
+        b"(Ljava/lang/Class;[Ljava/lang/Class;)Ljava/lang/invoke/MethodType;",
+        vec![rtype, ptypes],
+    )
+}
+
+pub fn new_method_handle(
+    jt: &mut JavaThread,
+    caller: &ClassRef,
+    ref_kind: u8,
+    defc: &ClassRef,
+    name: &[u8],
+    desc: &[u8],
+) -> Option<OopRef> {
+    if !(REF_GET_FIELD..=REF_INVOKE_INTERFACE).contains(&ref_kind) {
+        let msg = format!("bad method handle kind {}", ref_kind);
+        let ex = exception::new(jt, consts::J_INTERNAL_ERROR, Some(msg));
+        jt.set_ex(ex);
+        return None;
+    }
+
+    let tp = if ref_kind <= REF_PUT_STATIC {
+        let signature = FieldSignature::new(desc);
+        reflect::create_value_type(signature.field_type)
+    } else {
+        new_method_type(jt, desc)?
+    };
+
+    let caller = { caller.lock().unwrap().get_mirror() };
+    let defc = { defc.lock().unwrap().get_mirror() };
+    let name = util::oop::new_java_lang_string3(jt, name);
+    let name = java_lang_String::intern(name);
+
+    call_mhn(
+        jt,
+        b"linkMethodHandleConstant",
+        b"(Ljava/lang/Class;ILjava/lang/Class;Ljava/lang/String;Ljava/lang/Object;)Ljava/lang/invoke/MethodHandle;",
+        vec![caller, OopDesc::new_int(ref_kind as i32), defc, name, tp],
+    )
+}
+
+//the bootstrap method of a condy, None with BootstrapMethodError pending
+pub fn bootstrap_method(
+    jt: &mut JavaThread,
+    ref_kind: u8,
+    defc: &ClassRef,
+    name: &[u8],
+    desc: &[u8],
+) -> Option<MethodIdRef> {
+    let mir = match ref_kind {
+        REF_INVOKE_STATIC => {
+            oop::class::init_class_fully(jt, defc.clone());
+            if jt.is_meet_ex() {
+                return None;
+            }
+            let id = util::new_method_id(name, desc);
+            defc.lock().unwrap().get_static_method(id).ok()
+        }
+        _ => None,
+    };
+    if mir.is_none() {
+        let msg = format!(
+            "bootstrap method {}{} of kind {} is not a static method",
+            String::from_utf8_lossy(name),
+            String::from_utf8_lossy(desc),
+            ref_kind
+        );
+        let ex = exception::new(jt, consts::J_BOOTSTRAP_METHOD_ERROR, Some(msg));
+        jt.set_ex(ex);
+    }
+    mir
+}
+
+//static_args are resolved by the frame, they are constants too
+pub fn new_dynamic_constant(
+    jt: &mut JavaThread,
+    caller: &ClassRef,
+    bsm: MethodIdRef,
+    name: &[u8],
+    desc: &[u8],
+    static_args: Vec<OopRef>,
+) -> Option<OopRef> {
+    let tp = reflect::create_value_type(FieldSignature::new(desc).field_type);
+    let lookup = new_lookup(jt, caller)?;
+    let name = util::oop::new_java_lang_string3(jt, name);
+    let name = java_lang_String::intern(name);
+
+    let mut args = vec![lookup, name, tp.clone()];
+    args.extend(static_args);
+    let sig = MethodSignature::new(bsm.method.desc.as_slice());
+    if bsm.method.is_varargs() && !sig.args.is_empty() && args.len() >= sig.args.len() - 1 {
+        if let Some(ArgType::Array(ary)) = sig.args.last() {
+            if let Some(ary_cls) = require_class3(None, ary.as_slice()) {
+                let rest = args.split_off(sig.args.len() - 1);
+                args.push(OopDesc::new_ref_ary2(ary_cls, rest));
+            }
+        }
+    }
+
+    let ary_cls = require_class3(None, b"[Ljava/lang/Object;").unwrap();
+    let args = OopDesc::new_ref_ary2(ary_cls, args);
+    let args = match reflect::convert_args(bsm.method.desc.as_slice(), args) {
+        Ok(args) => args,
+        Err(msg) => return bootstrap_error(jt, msg),
+    };
+
+    let mut stack = Stack::new(2);
+    let mut jc = JavaCall::new_with_args(jt, bsm, args);
+    jc.invoke(jt, &mut stack, true);
+    if let Some(ex) = jt.take_ex() {
+        let ex = new_bootstrap_error(jt, ex);
+        jt.set_ex(ex);
+        return None;
+    }
+    let v = reflect::box_return(jt, &sig.retype, &mut stack);
+
+    //Class.cast of the value to the type, a primitive is checked unboxed
+    let is_instance = {
+        let v = v.lock().unwrap();
+        match &v.v {
+            Oop::Null => true,
+            Oop::Inst(inst) => tp_accepts(&tp, &inst.class),
+            Oop::Array(ary) => tp_accepts(&tp, &ary.class),
+            _ => true,
+        }
+    };
+    match is_instance {
+        true => Some(v),
+        false => bootstrap_error(
+            jt,
+            "bootstrap method returned a value of a wrong type".to_string(),
+        ),
+    }
+}
+
+//the mirror tp of a reference type, any class passes a primitive one
+fn tp_accepts(tp: &OopRef, cls: &ClassRef) -> bool {
+    let target = {
+        let tp = tp.lock().unwrap();
+        match &tp.v {
+            Oop::Mirror(mirror) => mirror.target.clone(),
+            _ => None,
+        }
+    };
+    match target {
+        Some(target) => cmp::instance_of(cls.clone(), target),
+        None => true,
+    }
+}
+
+fn new_lookup(jt: &mut JavaThread, caller: &ClassRef) -> Option<OopRef> {
+    let cls = oop::class::load_and_init(jt, consts::J_METHOD_HANDLES_LOOKUP);
+    let (impl_lookup, mir) = {
+        let cls = cls.lock().unwrap();
+        let id = cls.get_field_id(
+            b"IMPL_LOOKUP",
+            b"Ljava/lang/invoke/MethodHandles$Lookup;",
+            true,
+        );
+        let id2 = util::new_method_id(
+            b"in",
+            b"(Ljava/lang/Class;)Ljava/lang/invoke/MethodHandles$Lookup;",
+        );
+        (
+            cls.get_static_field_value(id),
+            cls.get_virtual_method(id2).unwrap(),
+        )
+    };
+    let caller = { caller.lock().unwrap().get_mirror() };
+
+    let mut stack = Stack::new(1);
+    let mut jc = JavaCall::new_with_args(jt, mir, vec![impl_lookup, caller]);
+    jc.invoke(jt, &mut stack, true);
+    match jt.is_meet_ex() {
+        true => None,
+        false => Some(stack.pop_ref()),
+    }
+}
+
+fn bootstrap_error(jt: &mut JavaThread, msg: String) -> Option<OopRef> {
+    let ex = exception::new(jt, consts::J_BOOTSTRAP_METHOD_ERROR, Some(msg));
+    jt.set_ex(ex);
+    None
+}
+
+//an Error is rethrown as is, anything else is the cause of a
+//BootstrapMethodError
+fn new_bootstrap_error(jt: &mut JavaThread, ex: OopRef) -> OopRef {
+    let cls = {
+        let v = ex.lock().unwrap();
+        match &v.v {
+            Oop::Inst(inst) => inst.class.clone(),
+            t => unreachable!("t = {:?}", t),
+        }
+    };
+    let error_cls = require_class3(None, consts::J_ERROR).unwrap();
+    if cmp::instance_of(cls, error_cls) {
+        return ex;
+    }
+
+    let cls = oop::class::load_and_init(jt, consts::J_BOOTSTRAP_METHOD_ERROR);
+    let error = OopDesc::new_inst(cls.clone());
+    let args = vec![error.clone(), ex];
+    runtime::java_call::invoke_ctor(jt, cls, b"(Ljava/lang/Throwable;)V", args);
+    error
+}
+
+//call a static method of MethodHandleNatives, None with an exception pending
+fn call_mhn(jt: &mut JavaThread, name: &[u8], desc: &[u8], args: Vec<OopRef>) -> Option<OopRef> {
+    let cls = oop::class::load_and_init(jt, consts::J_METHOD_HANDLE_NATIVES);
+    let mir = {
+        let cls = cls.lock().unwrap();
+        let id = util::new_method_id(name, desc);
+        cls.get_static_method(id)
+    };
+    let mir = match mir {
+        Ok(mir) => mir,
+        Err(_) => {
+            let msg = format!(
+                "MethodHandleNatives.{}{} not found",
+                String::from_utf8_lossy(name),
+                String::from_utf8_lossy(desc)
+            );
+            let ex = exception::new(jt, consts::J_INTERNAL_ERROR, Some(msg));
+            jt.set_ex(ex);
+            return None;
+        }
+    };
+
+    let mut stack = Stack::new(1);
+    let mut jc = JavaCall::new_with_args(jt, mir, args);
+    jc.invoke(jt, &mut stack, true);
+
+    if jt.is_meet_ex() {
+        None
+    } else {
+        Some(stack.pop_ref())
+    }
+}
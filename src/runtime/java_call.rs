@@ -1,11 +1,15 @@
+use crate::classfile::access_flags::ACC_ABSTRACT;
 use crate::classfile::consts;
 use crate::classfile::signature::{self, MethodSignature, Type as ArgType};
 use crate::native;
-use crate::oop::{self, Oop, OopDesc, ValueType};
-use crate::runtime::{self, exception, frame::Frame, thread, FrameRef, JavaThread, Stack};
-use crate::types::{ClassRef, MethodIdRef, OopRef};
+use crate::oop::{self, Class, Oop, OopDesc, ValueType};
+use crate::runtime::{
+    self, callgraph, exception, flags, frame::Frame, monitor, thread, FrameRef, JavaThread, Stack,
+};
+use crate::types::{ClassRef, MethodIdRef, OopRef, U2};
 use crate::util;
 use std::borrow::BorrowMut;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 
 pub struct JavaCall {
@@ -89,7 +93,7 @@ impl JavaCall {
                         //快速失败，避免大量log，不容易定位问题
                         //                        panic!();
 
-                        let ex = exception::new(jt, consts::J_NPE, None);
+                        let ex = exception::new_implicit(jt, consts::J_NPE, None);
                         jt.set_ex(ex);
                         return Err(());
                     }
@@ -118,19 +122,44 @@ impl JavaCall {
 
             public native boolean checkAccess(File f, int access);
         */
-        self.resolve_virtual_method(force_no_resolve);
+        if self.resolve_virtual_method(jt, force_no_resolve).is_err() {
+            return;
+        }
         self.debug();
 
-        if self.mir.method.is_native() {
-            jt.callers.push(self.mir.clone());
+        //thrown at the call site, the callee is not entered
+        if let Some((name, msg)) = self.missing_body() {
+            let ex = exception::new(jt, name, Some(msg));
+            jt.set_ex(ex);
+            return;
+        }
+
+        if jt.frames.len() >= flags::max_java_stack_depth() || thread::is_stack_overflow() {
+            let ex = exception::stack_overflow(jt);
+            jt.set_ex(ex);
+            return;
+        }
+
+        let arena_mark = jt.arena.mark();
+        callgraph::on_call(jt.callers.last(), &self.mir);
+        jt.call_bcis.push(jt.bci);
+        jt.callers.push(self.mir.clone());
+        jt.stat.depth.store(jt.callers.len(), Ordering::SeqCst);
+        //out of java code a thread counts as stopped, not any more
+        runtime::safepoint::poll();
+        if self.mir.method.is_native() || native::is_overridden(&self.mir) {
             self.invoke_native(jt, stack);
         } else {
-            jt.callers.push(self.mir.clone());
             self.invoke_java(jt, stack);
             let _ = jt.frames.pop();
         }
 
         jt.callers.pop();
+        if let Some(bci) = jt.call_bcis.pop() {
+            jt.bci = bci;
+        }
+        jt.stat.depth.store(jt.callers.len(), Ordering::Relaxed);
+        jt.arena.reset(arena_mark);
     }
 }
 
@@ -169,14 +198,36 @@ impl JavaCall {
         };
         let desc = self.mir.method.desc.clone();
         let name = self.mir.method.name.clone();
-        let method = native::find_symbol(package.as_slice(), name.as_slice(), desc.as_slice());
+        let id = jt.arena.join(
+            &[package.as_slice(), name.as_slice(), desc.as_slice()],
+            util::PATH_SEP.as_bytes(),
+        );
+        let method = native::find_symbol(jt.arena.get(id));
         let v = match method {
             Some(method) => {
                 let class = self.mir.method.class.clone();
                 let env = native::new_jni_env(jt, class);
-                method.invoke(jt, env, self.args.clone())
+                let depth = jt.local_refs.push_frame();
+                let v = method.invoke(jt, env, self.args.clone());
+                jt.local_refs.pop_to(depth);
+                v
+            }
+            None => {
+                let name = format!(
+                    "{}.{}{}",
+                    String::from_utf8_lossy(package.as_slice()),
+                    String::from_utf8_lossy(name.as_slice()),
+                    String::from_utf8_lossy(desc.as_slice())
+                );
+                if runtime::flags::is_best_effort_natives() {
+                    warn!("missing native {}, return default value", name);
+                    native::record_missing(&name);
+                    Ok(default_value(&self.return_type))
+                } else {
+                    let name = name.replace(util::FILE_SEP, ".");
+                    Err(exception::new(jt, consts::J_UNSATISFIED_LINK, Some(name)))
+                }
             }
-            None => unreachable!("NotFound native method"),
         };
 
         match v {
@@ -198,37 +249,28 @@ impl JavaCall {
 
     fn prepare_sync(&mut self) {
         if self.mir.method.is_synchronized() {
-            if self.mir.method.is_static() {
-                let mut class = self.mir.method.class.lock().unwrap();
-                class.monitor_enter();
-            } else {
-                let mut v = self.args.first_mut().unwrap();
-                let mut v = v.lock().unwrap();
-                v.monitor_enter();
-            }
+            monitor::enter(&self.sync_obj());
         }
     }
 
     fn fin_sync(&mut self) {
         if self.mir.method.is_synchronized() {
-            if self.mir.method.is_static() {
-                let mut class = self.mir.method.class.lock().unwrap();
-                class.monitor_exit();
-            } else {
-                let mut v = self.args.first_mut().unwrap();
-                let mut v = v.lock().unwrap();
-                v.monitor_exit();
-            }
+            //entered by prepare_sync, balanced
+            let _ = monitor::exit(&self.sync_obj());
         }
     }
 
-    fn prepare_frame(&mut self, thread: &mut JavaThread) -> Result<FrameRef, ()> {
-        if thread.frames.len() >= runtime::consts::THREAD_MAX_STACK_FRAMES {
-            let ex = exception::new(thread, consts::J_SOE, None);
-            thread.set_ex(ex);
-            return Err(());
+    //the monitor of a synchronized method: the receiver, or the class
+    //mirror for a static method, the one synchronized (X.class) takes
+    fn sync_obj(&self) -> OopRef {
+        if self.mir.method.is_static() {
+            self.mir.method.class.lock().unwrap().get_mirror()
+        } else {
+            self.args.first().unwrap().clone()
         }
+    }
 
+    fn prepare_frame(&mut self, thread: &mut JavaThread) -> Result<FrameRef, ()> {
         let frame_id = thread.frames.len() + 1;
         let mut frame = Frame::new(self.mir.clone(), frame_id);
 
@@ -268,7 +310,12 @@ impl JavaCall {
         return Ok(frame_ref);
     }
 
-    fn resolve_virtual_method(&mut self, force_no_resolve: bool) {
+    //Err, no implementation, AbstractMethodError is thrown
+    pub fn resolve_virtual_method(
+        &mut self,
+        jt: &mut JavaThread,
+        force_no_resolve: bool,
+    ) -> Result<(), ()> {
         let resolve_again = if force_no_resolve {
             false
         } else {
@@ -293,26 +340,105 @@ impl JavaCall {
             self.mir.method.acc_flags
         );
         if resolve_again {
-            let this = self.args.get(0).unwrap();
-            let this = this.lock().unwrap();
-            match &this.v {
-                Oop::Inst(inst) => {
-                    let cls = inst.class.lock().unwrap();
-                    let id = self.mir.method.get_id();
-                    self.mir = cls.get_virtual_method(id).unwrap();
+            let selected = {
+                let this = self.args.get(0).unwrap();
+                let this = this.lock().unwrap();
+                match &this.v {
+                    Oop::Inst(inst) => {
+                        let cls = inst.class.lock().unwrap();
+                        Some(self.select_method(&cls))
+                    }
+                    _ => None,
                 }
-                _ => (),
             };
+
+            match selected {
+                Some(Some(mir)) if !mir.method.is_abstract() => self.mir = mir,
+                Some(_) => {
+                    let ex = exception::new(jt, consts::J_AME, Some(self.method_name()));
+                    jt.set_ex(ex);
+                    return Err(());
+                }
+                None => (),
+            }
         }
+
+        Ok(())
+    }
+
+    /*
+    The method can't be run: abstract, no implementation was selected
+    (invokespecial, invokestatic or a final target), neither native nor
+    with a Code attribute, or with a bad exception table. (exception
+    class, message), None if it can
+    */
+    fn missing_body(&self) -> Option<(&'static [u8], String)> {
+        let method = &self.mir.method;
+        if method.is_native() || native::is_overridden(&self.mir) {
+            return None;
+        }
+
+        match missing_body_ex(method.acc_flags, method.code.is_some()) {
+            Some(name) if name == consts::J_AME => Some((name, self.method_name())),
+            Some(name) => {
+                let cls_name = method.class.lock().unwrap().name.clone();
+                let msg = format!(
+                    "Absent Code attribute in method that is not native or abstract in class file {}",
+                    String::from_utf8_lossy(cls_name.as_slice())
+                );
+                Some((name, msg))
+            }
+            None => method.ex_table_err.map(|(name, msg)| {
+                let cls_name = method.class.lock().unwrap().name.clone();
+                let msg = format!(
+                    "{} in class file {}",
+                    msg,
+                    util::mutf8::display(cls_name.as_slice())
+                );
+                (name, msg)
+            }),
+        }
+    }
+
+    //"java.util.AbstractList.get:(I)Ljava/lang/Object;"
+    fn method_name(&self) -> String {
+        let cls_name = { self.mir.method.class.lock().unwrap().name.clone() };
+        format!(
+            "{}.{}",
+            String::from_utf8_lossy(cls_name.as_slice()).replace(util::FILE_SEP, "."),
+            String::from_utf8_lossy(self.mir.method.get_id().as_slice())
+        )
+    }
+
+    /*
+    The receiver class's implementation of self.mir
+      interface methods by itable slot
+      others by vtable slot
+      by name when there's no slot (the class doesn't implement the
+      interface, or the method isn't in the vtable)
+    None for AbstractMethodError
+    */
+    fn select_method(&self, cls: &Class) -> Option<MethodIdRef> {
+        if let Some(i) = self.mir.itable_index {
+            let itf = { self.mir.method.class.lock().unwrap().name.clone() };
+            if let Some(mir) = cls.get_itable_method(&itf, i) {
+                return mir;
+            }
+        } else if let Some(mir) = self.mir.vtable_index.and_then(|i| cls.get_vtable_method(i)) {
+            return Some(mir);
+        }
+
+        let id = self.mir.method.get_id();
+        cls.get_virtual_method(id).ok()
     }
 
     fn debug(&self) {
         let cls_name = { self.mir.method.class.lock().unwrap().name.clone() };
         let name = self.mir.method.name.clone();
         let desc = self.mir.method.desc.clone();
-        let cls_name = unsafe { std::str::from_utf8_unchecked(cls_name.as_slice()) };
-        let name = unsafe { std::str::from_utf8_unchecked(name.as_slice()) };
-        let desc = unsafe { std::str::from_utf8_unchecked(desc.as_slice()) };
+        let cls_name = util::mutf8::display(cls_name.as_slice());
+        let name = util::mutf8::display(name.as_slice());
+        let desc = util::mutf8::display(desc.as_slice());
         info!(
             "invoke method = {}:{}:{} static={} native={}",
             cls_name,
@@ -352,9 +478,35 @@ fn build_method_args(stack: &mut Stack, sig: MethodSignature) -> Vec<OopRef> {
         .collect()
 }
 
+//the error invoking a method of these flags throws, a native one is
+//bound by the native registry instead
+fn missing_body_ex(acc_flags: U2, has_code: bool) -> Option<&'static [u8]> {
+    if (acc_flags & ACC_ABSTRACT) != 0 {
+        Some(consts::J_AME)
+    } else if !has_code {
+        Some(consts::J_CLASS_FORMAT_ERROR)
+    } else {
+        None
+    }
+}
+
+//0, false, null...
+fn default_value(t: &ArgType) -> Option<OopRef> {
+    match t {
+        ArgType::Byte | ArgType::Char | ArgType::Short | ArgType::Int | ArgType::Boolean => {
+            Some(oop::consts::get_int0())
+        }
+        ArgType::Long => Some(oop::consts::get_long0()),
+        ArgType::Float => Some(oop::consts::get_float0()),
+        ArgType::Double => Some(oop::consts::get_double0()),
+        ArgType::Object(_) | ArgType::Array(_) => Some(oop::consts::get_null()),
+        ArgType::Void => None,
+    }
+}
+
 pub fn set_return(stack: &mut Stack, return_type: ArgType, v: Option<OopRef>) {
     match return_type {
-        ArgType::Byte | ArgType::Char | ArgType::Int | ArgType::Boolean => {
+        ArgType::Byte | ArgType::Char | ArgType::Short | ArgType::Int | ArgType::Boolean => {
             let v = v.unwrap();
             let v = v.lock().unwrap();
             match v.v {
@@ -391,6 +543,24 @@ pub fn set_return(stack: &mut Stack, return_type: ArgType, v: Option<OopRef>) {
             stack.push_ref(v);
         }
         ArgType::Void => (),
-        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::access_flags::{ACC_PUBLIC, ACC_STATIC};
+
+    #[test]
+    fn t_missing_body_ex() {
+        assert_eq!(
+            missing_body_ex(ACC_PUBLIC | ACC_ABSTRACT, false),
+            Some(consts::J_AME)
+        );
+        assert_eq!(
+            missing_body_ex(ACC_PUBLIC | ACC_STATIC, false),
+            Some(consts::J_CLASS_FORMAT_ERROR)
+        );
+        assert_eq!(missing_body_ex(ACC_PUBLIC, true), None);
     }
 }
@@ -1,102 +1,139 @@
-use crate::oop::OopDesc;
-use crate::runtime::Slot;
+use crate::oop::{self, OopDesc};
+use crate::runtime::{ReturnAddress, Slot};
 use crate::types::OopRef;
 use std::sync::Arc;
 
+/*
+Local variables, untagged 64-bit slots, same layout as the operand stack
+
+int, float, reference, returnAddress: one slot
+long, double: two slots, the value at pos, pos + 1 is padding (JVM spec 2.6.1)
+reference: kept in the reference map at pos, the slot holds 0
+
+Writing a slot drops the reference held there before.
+*/
 pub struct Local {
-    locals: Vec<Slot>,
+    slots: Vec<u64>,
+    //reference map, Some at the index of a reference slot
+    refs: Vec<Option<OopRef>>,
 }
 
-//添加类型信息, set_xx 是检测之
 impl Local {
     pub fn new(size: usize) -> Self {
         let size = size + 1;
-        let mut locals = Vec::with_capacity(size);
-        for _ in 0..size {
-            locals.push(Slot::Nop);
+        Self {
+            slots: vec![0; size],
+            refs: vec![None; size],
         }
+    }
 
-        Self { locals }
+    //for inspection
+    pub fn slots(&self) -> Vec<Slot> {
+        self.slots
+            .iter()
+            .zip(self.refs.iter())
+            .map(|(v, r)| match r {
+                Some(r) => Slot::Ref(r.clone()),
+                None => Slot::Primitive(v.to_be_bytes().to_vec()),
+            })
+            .collect()
     }
 
     pub fn set_int(&mut self, pos: usize, i: i32) {
-        let v = i.to_be_bytes();
-        self.set_primitive2(pos, v);
+        self.set_raw(pos, i as i64 as u64);
     }
 
     pub fn set_long(&mut self, pos: usize, l: i64) {
-        let v = l.to_be_bytes();
-        self.set_primitive3(pos, v);
+        self.set_raw2(pos, l as u64);
     }
 
     pub fn set_float(&mut self, pos: usize, f: f32) {
-        let v = f.to_bits().to_be_bytes();
-        self.set_primitive2(pos, v);
+        self.set_raw(pos, f.to_bits() as u64);
     }
 
     pub fn set_double(&mut self, pos: usize, d: f64) {
-        let v = d.to_bits().to_be_bytes();
-        self.set_primitive3(pos, v);
+        self.set_raw2(pos, d.to_bits());
     }
 
     pub fn set_ref(&mut self, pos: usize, v: OopRef) {
-        self.locals[pos] = Slot::Ref(v);
+        vm_assert!(
+            oop::is_valid_ref(&v),
+            "set_ref: not a reference, pos = {}",
+            pos
+        );
+        self.slots[pos] = 0;
+        self.refs[pos] = Some(v);
+    }
+
+    pub fn set_return_address(&mut self, pos: usize, v: ReturnAddress) {
+        self.set_raw(pos, v.0 as i64 as u64);
+    }
+
+    pub fn get_return_address(&self, pos: usize) -> ReturnAddress {
+        ReturnAddress(self.get_raw(pos) as i32)
     }
 
     pub fn get_int(&self, pos: usize) -> i32 {
-        if let Slot::Primitive(v) = self.locals.get(pos).unwrap() {
-            i32::from_be_bytes([v[0], v[1], v[2], v[3]])
-        } else {
-            panic!("Illegal type");
-        }
+        self.get_raw(pos) as i32
     }
 
     pub fn get_long(&self, pos: usize) -> i64 {
-        if let Slot::Primitive(v) = self.locals.get(pos).unwrap() {
-            i64::from_be_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]])
-        } else {
-            panic!("Illegal type");
-        }
+        self.get_raw(pos) as i64
     }
 
     pub fn get_float(&self, pos: usize) -> f32 {
-        if let Slot::Primitive(v) = self.locals.get(pos).unwrap() {
-            let v = u32::from_be_bytes([v[0], v[1], v[2], v[3]]);
-            f32::from_bits(v)
-        } else {
-            panic!("Illegal type");
-        }
+        f32::from_bits(self.get_raw(pos) as u32)
     }
 
     pub fn get_double(&self, pos: usize) -> f64 {
-        if let Slot::Primitive(v) = self.locals.get(pos).unwrap() {
-            let v = u64::from_be_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]);
-            f64::from_bits(v)
-        } else {
-            panic!("Illegal type");
-        }
+        f64::from_bits(self.get_raw(pos))
     }
 
     pub fn get_ref(&self, pos: usize) -> OopRef {
-        match self.locals.get(pos) {
-            Some(Slot::Ref(v)) => v.clone(),
-            t => panic!("Illegal type = {:?}", t),
+        match self.refs.get(pos) {
+            Some(Some(v)) => v.clone(),
+            _ => panic!("Illegal type, pos = {}, expect reference", pos),
         }
     }
 }
 
 impl Local {
-    fn set_primitive(&mut self, pos: usize, buf: Vec<u8>) {
-        self.locals[pos] = Slot::Primitive(buf);
+    fn set_raw(&mut self, pos: usize, v: u64) {
+        self.slots[pos] = v;
+        self.refs[pos] = None;
     }
 
-    fn set_primitive2(&mut self, pos: usize, v: [u8; 4]) {
-        let v = vec![v[0], v[1], v[2], v[3]];
-        self.set_primitive(pos, v);
+    //category 2, pos + 1 is padding
+    fn set_raw2(&mut self, pos: usize, v: u64) {
+        self.set_raw(pos, v);
+        self.set_raw(pos + 1, 0);
     }
 
-    fn set_primitive3(&mut self, pos: usize, v: [u8; 8]) {
-        let v = vec![v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]];
-        self.set_primitive(pos, v);
+    fn get_raw(&self, pos: usize) -> u64 {
+        match self.refs.get(pos) {
+            Some(None) => self.slots[pos],
+            _ => panic!("Illegal type, pos = {}, expect primitive", pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Local;
+    use crate::oop::consts;
+
+    #[test]
+    fn t_category2() {
+        let mut local = Local::new(4);
+        local.set_ref(1, consts::get_null());
+        local.set_long(0, -3);
+        local.set_double(2, 0.5);
+        assert_eq!(local.get_long(0), -3);
+        assert_eq!(local.get_double(2), 0.5);
+
+        //the long overwrote the reference at 1
+        local.set_int(0, 7);
+        assert_eq!(local.get_int(0), 7);
+        assert_eq!(local.get_int(1), 0);
     }
 }
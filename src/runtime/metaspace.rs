@@ -0,0 +1,149 @@
+/*
+Metaspace accounting, the memory of class metadata, apart from the java heap
+
+  metadata  Class, methods, fields, vtable & itable slots
+  cp        constant pool entries
+  symbols   utf8 constants, names, descriptors & string literals
+  code      bytecode & its decoded instructions
+
+The sizes are estimated from the loaded class, counted once when the
+class is loaded, classes are never unloaded. Reported by the diag server
+/metaspace, and at exit by -XX:+PrintMetaspaceStats.
+
+-XX:MaxMetaspaceSize=n[k|m|g] caps the total. A class loaded over the cap
+is still loaded, the interpreter's class resolution can't fail, but the
+instruction which loaded it ends with OutOfMemoryError: Metaspace.
+*/
+
+use crate::classfile::constant_pool::ConstantType;
+use crate::oop::class::{Class, ClassKind};
+use crate::oop::field::FieldId;
+use crate::oop::method::{Instr, MethodId};
+use crate::runtime::flags;
+use crate::types::ClassRef;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static METADATA: AtomicUsize = AtomicUsize::new(0);
+static CP: AtomicUsize = AtomicUsize::new(0);
+static SYMBOLS: AtomicUsize = AtomicUsize::new(0);
+static CODE: AtomicUsize = AtomicUsize::new(0);
+
+//the cap is exceeded, OutOfMemoryError pending
+static EXHAUSTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MetaspaceStats {
+    pub metadata: usize,
+    pub cp: usize,
+    pub symbols: usize,
+    pub code: usize,
+}
+
+impl MetaspaceStats {
+    pub fn total(&self) -> usize {
+        self.metadata + self.cp + self.symbols + self.code
+    }
+}
+
+pub fn stats() -> MetaspaceStats {
+    MetaspaceStats {
+        metadata: METADATA.load(Ordering::Relaxed),
+        cp: CP.load(Ordering::Relaxed),
+        symbols: SYMBOLS.load(Ordering::Relaxed),
+        code: CODE.load(Ordering::Relaxed),
+    }
+}
+
+pub fn on_class_load(class: &ClassRef) {
+    let usage = {
+        let class = class.lock().unwrap();
+        class_usage(&class)
+    };
+
+    METADATA.fetch_add(usage.metadata, Ordering::Relaxed);
+    CP.fetch_add(usage.cp, Ordering::Relaxed);
+    SYMBOLS.fetch_add(usage.symbols, Ordering::Relaxed);
+    CODE.fetch_add(usage.code, Ordering::Relaxed);
+
+    if let Some(max) = flags::max_metaspace_size() {
+        if stats().total() > max {
+            EXHAUSTED.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+//checked by the interpreter after each instruction
+pub fn take_exhausted() -> bool {
+    EXHAUSTED.load(Ordering::Relaxed) && EXHAUSTED.swap(false, Ordering::Relaxed)
+}
+
+fn class_usage(class: &Class) -> MetaspaceStats {
+    let mut usage = MetaspaceStats {
+        metadata: size_of::<Class>(),
+        ..MetaspaceStats::default()
+    };
+
+    let cls_obj = match &class.kind {
+        ClassKind::Instance(cls_obj) => cls_obj,
+        _ => return usage,
+    };
+
+    let cp = &cls_obj.class_file.cp;
+    usage.cp = cp.len() * size_of::<ConstantType>();
+    usage.symbols = cp
+        .iter()
+        .map(|it| match it {
+            ConstantType::Utf8 { bytes, .. } => bytes.len(),
+            _ => 0,
+        })
+        .sum();
+
+    let n_fields = cls_obj.inst_fields.len() + cls_obj.static_fields.len();
+    let n_itable: usize = cls_obj.itable().values().map(|it| it.len()).sum();
+    usage.metadata += n_fields * size_of::<FieldId>()
+        + cls_obj.all_methods.len() * size_of::<MethodId>()
+        + (cls_obj.vtable().len() + n_itable) * size_of::<usize>();
+
+    usage.code = cls_obj
+        .all_methods
+        .values()
+        .map(|it| {
+            let code = it.method.code.as_ref().map_or(0, |code| code.code.len());
+            code + it.method.bytecode.instrs.len() * size_of::<Instr>()
+        })
+        .sum();
+
+    usage
+}
+
+//-XX:+PrintMetaspaceStats
+pub fn print_stats() {
+    if flags::is_print_metaspace_stats() {
+        let stats = stats();
+        eprintln!("Metaspace stats:");
+        eprintln!("  metadata: {:>10} bytes", stats.metadata);
+        eprintln!("  cp:       {:>10} bytes", stats.cp);
+        eprintln!("  symbols:  {:>10} bytes", stats.symbols);
+        eprintln!("  code:     {:>10} bytes", stats.code);
+        eprintln!("  total:    {:>10} bytes", stats.total());
+        if let Some(max) = flags::max_metaspace_size() {
+            eprintln!("  max:      {:>10} bytes", max);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::ValueType;
+    use crate::runtime::ClassLoader;
+
+    #[test]
+    fn t_array_class_usage() {
+        let cls = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::INT);
+        let usage = class_usage(&cls);
+        assert_eq!(usage.metadata, size_of::<Class>());
+        assert_eq!(usage.total(), usage.metadata);
+    }
+}
@@ -1,33 +1,62 @@
 #![allow(unused)]
 
+mod arena;
+pub mod callgraph;
 mod class_loader;
 mod class_path_manager;
 pub mod cmp;
 mod consts;
+pub mod control;
+#[cfg(feature = "diag-server")]
+pub mod diag;
+pub mod events;
+pub mod ex_break;
 pub mod exception;
+pub mod flags;
 mod frame;
+pub mod gc;
+pub mod handles;
+pub mod host_async;
+pub mod host_class;
 mod init_vm;
+pub mod intrinsics;
+pub mod invoke;
 pub mod java_call;
 mod local;
+pub mod metaspace;
+pub mod monitor;
+pub mod park;
+pub mod prefetch;
 pub mod reflect;
+pub mod safepoint;
 mod slot;
+pub mod snapshot;
 mod stack;
+pub mod step;
 mod sys_dic;
+pub mod tags;
+#[cfg(test)]
+pub mod test_class;
 pub mod thread;
+pub mod vfs;
 
-pub use class_loader::{require_class, require_class2, require_class3, ClassLoader};
+pub use arena::{Arena, ArenaRef};
+pub use class_loader::{
+    define_class, find_defined, parse_class, require_class, require_class2, require_class3,
+    take_load_error, ClassLoader,
+};
 
 pub use class_path_manager::{
-    add_path as add_class_path, add_paths as add_class_paths,
-    find_class as find_class_in_classpath, ClassPathResult,
+    add_path as add_class_path, add_paths as add_class_paths, add_provider as add_class_provider,
+    find_class as find_class_in_classpath, ClassPathResult, ClassProvider,
 };
 pub use consts::THREAD_MAX_STACK_FRAMES;
 pub use frame::Frame;
 pub use java_call::JavaCall;
 pub use local::Local;
-pub use slot::Slot;
-pub use stack::Stack;
-pub use sys_dic::{find as sys_dic_find, put as sys_dic_put};
+pub use slot::{ReturnAddress, Slot};
+pub use stack::{Stack, StackErr};
+pub use sys_dic::{find as sys_dic_find, names as sys_dic_names, put as sys_dic_put};
 pub use thread::JavaThread;
 
 def_sync_ref!(FrameRef, Frame);
@@ -36,3 +65,15 @@ pub fn init() {
     sys_dic::init();
     class_path_manager::init();
 }
+
+//the -XX reports written when the vm exits
+pub fn dump_reports() {
+    events::dump();
+    snapshot::dump_class_list();
+    callgraph::dump();
+    exception::print_stats();
+    prefetch::print_stats();
+    metaspace::print_stats();
+    gc::print_histogram();
+    crate::native::print_missing();
+}
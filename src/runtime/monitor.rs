@@ -0,0 +1,595 @@
+/*
+Object monitors: monitorenter/monitorexit, synchronized methods,
+Object.wait/notify/notifyAll, Thread.sleep and Thread.interrupt
+
+Thin lock: the object header holds the owner (a host thread, each guest
+thread runs on its own) and the recursion count, taking a free monitor
+is a CAS. The header is read under the object's mutex (see oop::OopHeader),
+held for the CAS only.
+
+Fat lock: a contended enter, or a wait, inflates the monitor, a Mutex +
+Condvar kept in a side table by object address. Contenders & waiters
+park on the Condvar, exit & notify wake them. The fat monitor is dropped
+(deflated) when nobody parks on it anymore. The header tells if there is
+one, the exit of a thin lock & a notify nobody waits for don't look the
+side table up, synchronized code without contention (StringBuffer,
+Vector, Hashtable) takes no global lock.
+
+wait releases the monitor whatever the recursion count, parks until
+notified, interrupted or timed out, then re-acquires the monitor and
+restores the count. notify takes the longest waiting thread out of the
+wait set. This implementation wakes up only for those three reasons, but
+the JLS (17.2.1) allows spurious wakeups, callers must wait in a loop on
+their condition as usual.
+
+A synchronized method locks the receiver, a static one the class mirror,
+the same monitor synchronized (X.class) takes. An unbalanced
+monitorexit throws IllegalMonitorStateException.
+
+Object.wait(long, int) is java code, it rounds the nanos to a
+millisecond and calls the native wait(long).
+*/
+
+use crate::oop::{Oop, OopHeader};
+use crate::runtime::events::{self, EventKind};
+use crate::runtime::thread;
+use crate::types::OopRef;
+use crate::util;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaitErr {
+    NotOwner,
+    Interrupted,
+}
+
+#[derive(Default)]
+struct FatState {
+    //threads parked in enter or wait
+    parked: usize,
+    //wait set, in arrival order
+    waiting: Vec<u64>,
+    //taken out of the wait set by notify
+    notified: HashSet<u64>,
+}
+
+#[derive(Default)]
+struct FatMonitor {
+    state: Mutex<FatState>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct Registry {
+    //object address -> fat monitor
+    monitors: HashMap<usize, Arc<FatMonitor>>,
+    //guest thread address -> fat monitor it waits or sleeps on
+    waiting: HashMap<usize, Arc<FatMonitor>>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    //the thin lock owner id, never 0
+    static SELF_ID: u64 = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+//lock order: REGISTRY, then a FatMonitor state, then INTERRUPTED or an
+//object (its header)
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+    static ref INTERRUPTED: Mutex<HashSet<usize>> = Mutex::new(HashSet::new());
+}
+
+pub fn enter(obj: &OopRef) {
+    let me = self_id();
+    if with_header(obj, |h| h.try_lock(me)) {
+        return;
+    }
+
+    //contended, park until the owner exits
+    let start = Instant::now();
+    let m = inflate(obj);
+    thread::in_state(thread::THREAD_STATUS_BLOCKED_ON_MONITOR_ENTER, || {
+        let mut st = m.state.lock().unwrap();
+        while !with_header(obj, |h| h.try_lock(me)) {
+            st = m.cond.wait(st).unwrap();
+        }
+    });
+    deflate(obj, &m);
+    events::record(EventKind::MonitorContended, || {
+        contended_detail(obj, start.elapsed())
+    });
+}
+
+//"java.lang.Object, 12us", the class of the object & the time blocked
+fn contended_detail(obj: &OopRef, blocked: Duration) -> String {
+    let name = match &obj.lock().unwrap().v {
+        Oop::Inst(inst) => inst.class.lock().unwrap().name.to_vec(),
+        Oop::Array(ary) => ary.class.lock().unwrap().name.to_vec(),
+        Oop::TypeArray(ary) => ary.desc().to_vec(),
+        Oop::Mirror(_) => b"java/lang/Class".to_vec(),
+        _ => b"?".to_vec(),
+    };
+    let name = String::from_utf8_lossy(name.as_slice()).replace('/', ".");
+    format!("{}, {}us", name, blocked.as_micros())
+}
+
+//Thread.holdsLock
+pub fn holds_lock(obj: &OopRef) -> bool {
+    with_header(obj, |h| h.is_locked_by(self_id()))
+}
+
+//Err if the current thread doesn't own the monitor
+pub fn exit(obj: &OopRef) -> Result<(), ()> {
+    let released = with_header(obj, |h| h.unlock(self_id()).map(|it| it && h.is_inflated()))?;
+    if released {
+        let m = util::sync_call_ctx(&REGISTRY, |r| r.monitors.get(&addr(obj)).cloned());
+        if let Some(m) = m {
+            let _st = m.state.lock().unwrap();
+            m.cond.notify_all();
+        }
+    }
+    Ok(())
+}
+
+//thread: the current guest thread, for interruption
+//timeout: None waits until notified
+pub fn wait(
+    obj: &OopRef,
+    thread: Option<&OopRef>,
+    timeout: Option<Duration>,
+) -> Result<(), WaitErr> {
+    let me = self_id();
+    let thread = thread.map(addr);
+    if !with_header(obj, |h| h.is_locked_by(me)) {
+        return Err(WaitErr::NotOwner);
+    }
+    if take_interrupt(thread) {
+        return Err(WaitErr::Interrupted);
+    }
+
+    let m = inflate(obj);
+    if let Some(thread) = thread {
+        util::sync_call_ctx(&REGISTRY, |r| r.waiting.insert(thread, m.clone()));
+    }
+
+    let deadline = timeout.map(|it| Instant::now() + it);
+    let status = match timeout {
+        Some(_) => thread::THREAD_STATUS_IN_OBJECT_WAIT_TIMED,
+        None => thread::THREAD_STATUS_IN_OBJECT_WAIT,
+    };
+    let (count, interrupted) = thread::in_state(status, || {
+        //join the wait set before the monitor is released, a notify
+        //right after the release sees this thread
+        let mut st = m.state.lock().unwrap();
+        st.waiting.push(me);
+        let count = with_header(obj, |h| h.unlock_all(me)).unwrap();
+        let mut interrupted = false;
+        m.cond.notify_all();
+
+        loop {
+            if st.notified.remove(&me) {
+                break;
+            }
+
+            let timed_out = deadline.is_some_and(|it| Instant::now() >= it);
+            if timed_out || is_interrupted(thread) {
+                interrupted = !timed_out;
+                st.waiting.retain(|&it| it != me);
+                break;
+            }
+
+            st = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    m.cond.wait_timeout(st, left).unwrap().0
+                }
+                None => m.cond.wait(st).unwrap(),
+            };
+        }
+
+        //re-acquire, like a contended enter
+        while !with_header(obj, |h| h.try_lock(me)) {
+            st = m.cond.wait(st).unwrap();
+        }
+        (count, interrupted)
+    });
+    with_header(obj, |h| h.set_lock_count(count));
+
+    if let Some(thread) = thread {
+        util::sync_call_ctx(&REGISTRY, |r| r.waiting.remove(&thread));
+    }
+    deflate(obj, &m);
+
+    if interrupted && take_interrupt(thread) {
+        Err(WaitErr::Interrupted)
+    } else {
+        Ok(())
+    }
+}
+
+//all: notifyAll
+//Err if the current thread doesn't own the monitor
+pub fn notify(obj: &OopRef, all: bool) -> Result<(), ()> {
+    let (owned, inflated) = with_header(obj, |h| (h.is_locked_by(self_id()), h.is_inflated()));
+    if !owned {
+        return Err(());
+    }
+    //no fat monitor, nobody waits
+    if !inflated {
+        return Ok(());
+    }
+
+    let m = util::sync_call_ctx(&REGISTRY, |r| r.monitors.get(&addr(obj)).cloned());
+    if let Some(m) = m {
+        let mut st = m.state.lock().unwrap();
+        let n = if all {
+            st.waiting.len()
+        } else {
+            st.waiting.len().min(1)
+        };
+        let woken: Vec<u64> = st.waiting.drain(..n).collect();
+        st.notified.extend(woken);
+        m.cond.notify_all();
+    }
+    Ok(())
+}
+
+//Thread.interrupt, wakes the thread if it waits
+pub fn interrupt(thread: &OopRef) {
+    let thread = addr(thread);
+    util::sync_call_ctx(&INTERRUPTED, |it| it.insert(thread));
+
+    let m = util::sync_call_ctx(&REGISTRY, |r| r.waiting.get(&thread).cloned());
+    if let Some(m) = m {
+        let _st = m.state.lock().unwrap();
+        m.cond.notify_all();
+    }
+}
+
+//Thread.sleep, Err if interrupted before or while sleeping, the status
+//is cleared then. The sleeper parks on a fat monitor of its own, which
+//interrupt wakes like a waiter's
+pub fn sleep(thread: Option<&OopRef>, timeout: Duration) -> Result<(), ()> {
+    let thread = thread.map(addr);
+    if take_interrupt(thread) {
+        return Err(());
+    }
+
+    let m = Arc::new(FatMonitor::default());
+    if let Some(thread) = thread {
+        util::sync_call_ctx(&REGISTRY, |r| r.waiting.insert(thread, m.clone()));
+    }
+
+    let deadline = Instant::now() + timeout;
+    thread::in_state(thread::THREAD_STATUS_SLEEPING, || {
+        let mut st = m.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            if now >= deadline || is_interrupted(thread) {
+                break;
+            }
+            st = m.cond.wait_timeout(st, deadline - now).unwrap().0;
+        }
+    });
+
+    if let Some(thread) = thread {
+        util::sync_call_ctx(&REGISTRY, |r| r.waiting.remove(&thread));
+    }
+    if take_interrupt(thread) {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+//Thread.isInterrupted(clear)
+pub fn is_thread_interrupted(thread: &OopRef, clear: bool) -> bool {
+    let thread = Some(addr(thread));
+    if clear {
+        take_interrupt(thread)
+    } else {
+        is_interrupted(thread)
+    }
+}
+
+fn is_interrupted(thread: Option<usize>) -> bool {
+    match thread {
+        Some(thread) => util::sync_call_ctx(&INTERRUPTED, |it| it.contains(&thread)),
+        None => false,
+    }
+}
+
+//read & clear the interrupt status
+fn take_interrupt(thread: Option<usize>) -> bool {
+    match thread {
+        Some(thread) => util::sync_call_ctx(&INTERRUPTED, |it| it.remove(&thread)),
+        None => false,
+    }
+}
+
+//the fat monitor of obj, counted as parked on it
+fn inflate(obj: &OopRef) -> Arc<FatMonitor> {
+    util::sync_call_ctx(&REGISTRY, |r| {
+        let m = r.monitors.entry(addr(obj)).or_default().clone();
+        m.state.lock().unwrap().parked += 1;
+        with_header(obj, |h| h.set_inflated(true));
+        m
+    })
+}
+
+fn deflate(obj: &OopRef, m: &Arc<FatMonitor>) {
+    util::sync_call_ctx(&REGISTRY, |r| {
+        let mut st = m.state.lock().unwrap();
+        st.parked -= 1;
+        if st.parked == 0 {
+            r.monitors.remove(&addr(obj));
+            with_header(obj, |h| h.set_inflated(false));
+        }
+    });
+}
+
+//the oop mutex is held only for the atomics, never while parked
+fn with_header<F, R>(obj: &OopRef, f: F) -> R
+where
+    F: FnOnce(&OopHeader) -> R,
+{
+    let v = obj.lock().unwrap();
+    f(&v.header)
+}
+
+fn addr(v: &OopRef) -> usize {
+    Arc::as_ptr(v) as usize
+}
+
+fn self_id() -> u64 {
+    SELF_ID.with(|it| *it)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::OopDesc;
+    use std::collections::VecDeque;
+    use std::thread;
+
+    const CAPACITY: usize = 4;
+
+    //a bounded buffer guarded by the monitor of 'lock', the inner Mutex
+    //only makes the queue Sync, the monitor does the exclusion
+    struct Buffer {
+        lock: OopRef,
+        items: Mutex<VecDeque<i32>>,
+    }
+
+    impl Buffer {
+        fn put(&self, v: i32) {
+            enter(&self.lock);
+            while self.items.lock().unwrap().len() == CAPACITY {
+                wait(&self.lock, None, None).unwrap();
+            }
+            self.items.lock().unwrap().push_back(v);
+            notify(&self.lock, true).unwrap();
+            exit(&self.lock).unwrap();
+        }
+
+        fn take(&self) -> i32 {
+            enter(&self.lock);
+            //nested, wait must release both
+            enter(&self.lock);
+            let v = loop {
+                if let Some(v) = self.items.lock().unwrap().pop_front() {
+                    break v;
+                }
+                wait(&self.lock, None, None).unwrap();
+            };
+            notify(&self.lock, true).unwrap();
+            exit(&self.lock).unwrap();
+            exit(&self.lock).unwrap();
+            v
+        }
+    }
+
+    #[test]
+    fn t_producer_consumer() {
+        let (producers, consumers, per_thread) = (4, 4, 2_000);
+        let buf = Arc::new(Buffer {
+            lock: OopDesc::new_int(0),
+            items: Mutex::new(VecDeque::new()),
+        });
+
+        let mut handles = Vec::new();
+        for p in 0..producers {
+            let buf = buf.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..per_thread {
+                    buf.put(p * per_thread + i);
+                }
+                0
+            }));
+        }
+        for _ in 0..consumers {
+            let buf = buf.clone();
+            handles.push(thread::spawn(move || {
+                (0..per_thread).map(|_| buf.take() as i64).sum::<i64>()
+            }));
+        }
+
+        let sum: i64 = handles.into_iter().map(|it| it.join().unwrap()).sum();
+        let n = (producers * per_thread) as i64;
+        assert_eq!(sum, n * (n - 1) / 2);
+        assert!(buf.items.lock().unwrap().is_empty());
+        assert_eq!(exit(&buf.lock), Err(()));
+        assert!(util::sync_call_ctx(&REGISTRY, |r| !r
+            .monitors
+            .contains_key(&addr(&buf.lock))));
+    }
+
+    #[test]
+    fn t_contended_recursive_enter() {
+        let obj = OopDesc::new_int(0);
+        enter(&obj);
+        enter(&obj);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let contender = {
+            let obj = obj.clone();
+            thread::spawn(move || {
+                enter(&obj);
+                tx.send(()).unwrap();
+                //entered once, the second exit is unbalanced
+                exit(&obj).unwrap();
+                exit(&obj)
+            })
+        };
+
+        //held until the outermost exit
+        exit(&obj).unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        exit(&obj).unwrap();
+        rx.recv().unwrap();
+        assert_eq!(contender.join().unwrap(), Err(()));
+        assert_eq!(exit(&obj), Err(()));
+        //deflated, thin again
+        assert!(!with_header(&obj, |h| h.is_inflated()));
+    }
+
+    #[test]
+    fn t_contended_detail() {
+        let ary = OopDesc::new_int_ary(1);
+        let detail = contended_detail(&ary, Duration::from_micros(12));
+        assert_eq!(detail, "[I, 12us");
+    }
+
+    #[test]
+    fn t_thin_lock() {
+        //uncontended, no fat monitor, nothing in the registry
+        let obj = OopDesc::new_int(0);
+        enter(&obj);
+        enter(&obj);
+        assert_eq!(notify(&obj, true), Ok(()));
+        exit(&obj).unwrap();
+        exit(&obj).unwrap();
+        assert!(!with_header(&obj, |h| h.is_inflated()));
+        assert!(util::sync_call_ctx(&REGISTRY, |r| !r
+            .monitors
+            .contains_key(&addr(&obj))));
+    }
+
+    #[test]
+    fn t_wait_timeout_and_owner() {
+        let obj = OopDesc::new_int(0);
+        assert_eq!(wait(&obj, None, None), Err(WaitErr::NotOwner));
+        assert_eq!(notify(&obj, false), Err(()));
+
+        enter(&obj);
+        enter(&obj);
+        let start = Instant::now();
+        assert_eq!(wait(&obj, None, Some(Duration::from_millis(20))), Ok(()));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        //the recursion count is restored
+        exit(&obj).unwrap();
+        exit(&obj).unwrap();
+        assert_eq!(exit(&obj), Err(()));
+    }
+
+    #[test]
+    fn t_notify_one() {
+        let obj = OopDesc::new_int(0);
+        let woken = Arc::new(AtomicU64::new(0));
+
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let (obj, woken) = (obj.clone(), woken.clone());
+                thread::spawn(move || {
+                    enter(&obj);
+                    wait(&obj, None, None).unwrap();
+                    woken.fetch_add(1, Ordering::SeqCst);
+                    exit(&obj).unwrap();
+                })
+            })
+            .collect();
+        let n_waiting = || {
+            let m = util::sync_call_ctx(&REGISTRY, |r| r.monitors.get(&addr(&obj)).cloned());
+            m.map_or(0, |m| m.state.lock().unwrap().waiting.len())
+        };
+        while n_waiting() < 2 {
+            thread::yield_now();
+        }
+
+        //one waiter out of the wait set per notify
+        enter(&obj);
+        notify(&obj, false).unwrap();
+        assert_eq!(n_waiting(), 1);
+        exit(&obj).unwrap();
+        while woken.load(Ordering::SeqCst) < 1 {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(woken.load(Ordering::SeqCst), 1);
+
+        enter(&obj);
+        notify(&obj, true).unwrap();
+        exit(&obj).unwrap();
+        waiters.into_iter().for_each(|it| it.join().unwrap());
+        assert_eq!(woken.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn t_sleep_interrupt() {
+        let guest = OopDesc::new_int(0);
+        let start = Instant::now();
+        assert_eq!(sleep(Some(&guest), Duration::from_millis(20)), Ok(()));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        interrupt(&guest);
+        assert_eq!(sleep(Some(&guest), Duration::from_secs(60)), Err(()));
+        assert!(!is_thread_interrupted(&guest, false));
+
+        let sleeper = {
+            let guest = guest.clone();
+            thread::spawn(move || sleep(Some(&guest), Duration::from_secs(60)))
+        };
+        while util::sync_call_ctx(&REGISTRY, |r| !r.waiting.contains_key(&addr(&guest))) {
+            thread::yield_now();
+        }
+        interrupt(&guest);
+        assert_eq!(sleeper.join().unwrap(), Err(()));
+        assert!(!is_thread_interrupted(&guest, false));
+    }
+
+    #[test]
+    fn t_wait_interrupt() {
+        let obj = OopDesc::new_int(0);
+        let guest = OopDesc::new_int(0);
+
+        //interrupted before waiting, the status is cleared
+        enter(&obj);
+        interrupt(&guest);
+        assert_eq!(wait(&obj, Some(&guest), None), Err(WaitErr::Interrupted));
+        assert!(!is_thread_interrupted(&guest, false));
+        exit(&obj).unwrap();
+
+        let waiter = {
+            let (obj, guest) = (obj.clone(), guest.clone());
+            thread::spawn(move || {
+                enter(&obj);
+                let r = wait(&obj, Some(&guest), None);
+                //the monitor is owned again
+                exit(&obj).unwrap();
+                r
+            })
+        };
+        while util::sync_call_ctx(&REGISTRY, |r| !r.waiting.contains_key(&addr(&guest))) {
+            thread::yield_now();
+        }
+        interrupt(&guest);
+        assert_eq!(waiter.join().unwrap(), Err(WaitErr::Interrupted));
+        assert!(!is_thread_interrupted(&guest, false));
+    }
+}
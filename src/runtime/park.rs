@@ -0,0 +1,131 @@
+/*
+Parkers, LockSupport.park/unpark through sun.misc.Unsafe
+
+Every JavaThread has a Parker: a permit, at most one, & a Condvar.
+unpark makes the permit available, park consumes it or blocks until
+unparked, interrupted or timed out, as HotSpot's Parker. j.u.c (AQS,
+ReentrantLock, ...) parks the waiting threads here.
+
+unpark takes a Thread object, the parker is found in PARKERS by its
+address. A thread is registered by Thread.start0, before its host thread
+runs, or when it gets its Thread object, removed when detached. unpark of
+a thread not started or ended does nothing.
+Thread.interrupt unparks too, a parked thread returns with the interrupt
+status kept.
+
+park may return spuriously, callers recheck their condition, as with
+LockSupport.
+*/
+
+use crate::runtime::{monitor, thread};
+use crate::types::OopRef;
+use crate::util;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+#[derive(Default)]
+pub struct Parker {
+    permit: Mutex<bool>,
+    cond: Condvar,
+}
+
+lazy_static! {
+    //Thread oop address -> its parker
+    static ref PARKERS: Mutex<HashMap<usize, Arc<Parker>>> = Mutex::new(HashMap::new());
+}
+
+impl Parker {
+    //thread is the parking Thread, interrupt wakes it, None parks
+    //until the deadline or unpark
+    pub fn park(&self, thread: Option<&OopRef>, deadline: Option<Instant>) {
+        let status = match deadline {
+            Some(_) => thread::THREAD_STATUS_PARKED_TIMED,
+            None => thread::THREAD_STATUS_PARKED,
+        };
+        thread::in_state(status, || self.park_until(thread, deadline));
+    }
+
+    fn park_until(&self, thread: Option<&OopRef>, deadline: Option<Instant>) {
+        let mut permit = self.permit.lock().unwrap();
+        loop {
+            if *permit {
+                *permit = false;
+                return;
+            }
+            if thread.is_some_and(|it| monitor::is_thread_interrupted(it, false)) {
+                return;
+            }
+
+            permit = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return;
+                    }
+                    self.cond.wait_timeout(permit, deadline - now).unwrap().0
+                }
+                None => self.cond.wait(permit).unwrap(),
+            };
+        }
+    }
+
+    pub fn unpark(&self) {
+        let mut permit = self.permit.lock().unwrap();
+        *permit = true;
+        self.cond.notify_all();
+    }
+}
+
+pub fn register(thread_oop: &OopRef, parker: Arc<Parker>) {
+    util::sync_call_ctx(&PARKERS, |it| it.insert(addr(thread_oop), parker));
+}
+
+pub fn unregister(thread_oop: &OopRef) {
+    util::sync_call_ctx(&PARKERS, |it| it.remove(&addr(thread_oop)));
+}
+
+//Unsafe.unpark(thread)
+pub fn unpark(thread_oop: &OopRef) {
+    let parker = util::sync_call_ctx(&PARKERS, |it| it.get(&addr(thread_oop)).cloned());
+    if let Some(parker) = parker {
+        parker.unpark();
+    }
+}
+
+fn addr(v: &OopRef) -> usize {
+    Arc::as_ptr(v) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::OopDesc;
+    use std::time::Duration;
+
+    #[test]
+    fn t_park_unpark() {
+        //the permit is kept, park returns at once, & only once
+        let parker = Parker::default();
+        parker.unpark();
+        parker.unpark();
+        parker.park(None, None);
+        let start = Instant::now();
+        parker.park(None, Some(start + Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let guest = OopDesc::new_int(0);
+        let parker = Arc::new(Parker::default());
+        register(&guest, parker.clone());
+        let parked = {
+            let guest = guest.clone();
+            std::thread::spawn(move || parker.park(Some(&guest), None))
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        unpark(&guest);
+        parked.join().unwrap();
+
+        unregister(&guest);
+        unpark(&guest);
+    }
+}
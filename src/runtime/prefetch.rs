@@ -0,0 +1,295 @@
+/*
+Class prefetching, -XX:+PrefetchClasses
+
+After the main class is loaded, the classes its constant pool refers to
+are searched & parsed (not linked, not initialized) by a pool of parse
+threads, -XX:ParseThreads=n, the number of cpus by default. The super
+class & interfaces of a parsed class are queued too, defining the class
+loads them anyway. The loader takes the parsed ClassFile from here
+instead of reading the class path itself, the file I/O & parsing are
+hidden behind the interpretation of main. Definition & linking stay on
+the requesting thread.
+
+  hit   the class was parsed before the loader asked for it, or it was
+        being parsed & the loader waited for it
+  miss  the loader asked while it was still queued, it searched the
+        class path itself
+
+A parse error, a panic of the parser included, is kept for the loader as
+its own, it throws the ClassFormatError.
+
+With -verbose:class (-XX:+TraceClassLoading) each hit & miss is printed,
+and the totals when the vm exits.
+*/
+
+use crate::classfile::constant_pool::{self, ConstantType};
+use crate::classfile::ClassFile;
+use crate::parser as class_parser;
+use crate::runtime::{self, flags, ClassPathResult};
+use crate::types::ClassFileRef;
+use crate::util;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+enum Entry {
+    //queued
+    Pending,
+    //taken by a parse thread
+    Parsing,
+    Ready(ClassFileRef),
+    Failed(io::Error),
+    //loaded, not found, or asked for while still queued
+    Taken,
+}
+
+#[derive(Default)]
+struct Queue {
+    entries: HashMap<Vec<u8>, Entry>,
+    pending: VecDeque<Vec<u8>>,
+}
+
+lazy_static! {
+    static ref QUEUE: Mutex<Queue> = Mutex::new(Queue::default());
+    //a Parsing entry is done
+    static ref PARSED: Condvar = Condvar::new();
+}
+
+static HITS: AtomicUsize = AtomicUsize::new(0);
+static MISSES: AtomicUsize = AtomicUsize::new(0);
+
+//prefetch the classes referred by cf, when -XX:+PrefetchClasses
+pub fn start(cf: &ClassFile) {
+    if !flags::is_prefetch_classes() {
+        return;
+    }
+
+    let n = enqueue(referred_classes(cf));
+    let n = n.min(flags::parse_threads());
+    for i in 0..n {
+        let _ = std::thread::Builder::new()
+            .name(format!("class-parse-{}", i))
+            .spawn(parse_loop);
+    }
+}
+
+//queue the names not loaded or known yet, returns the number queued
+fn enqueue(names: Vec<Vec<u8>>) -> usize {
+    let names: Vec<Vec<u8>> = names
+        .into_iter()
+        .filter(|it| runtime::sys_dic_find(it).is_none())
+        .collect();
+    util::sync_call_ctx(&QUEUE, |q| {
+        let mut n = 0;
+        for it in names {
+            if !q.entries.contains_key(&it) {
+                q.entries.insert(it.clone(), Entry::Pending);
+                q.pending.push_back(it);
+                n += 1;
+            }
+        }
+        n
+    })
+}
+
+//a parse thread, ends when the queue is empty
+fn parse_loop() {
+    while let Some(name) = next_pending() {
+        let cf = fetch(name.as_slice());
+
+        let supers = match &cf {
+            Some(Ok(cf)) => super_classes(cf),
+            _ => vec![],
+        };
+        {
+            let mut q = QUEUE.lock().unwrap();
+            let entry = match cf {
+                Some(Ok(cf)) => Entry::Ready(new_ref!(cf)),
+                Some(Err(e)) => Entry::Failed(e),
+                None => Entry::Taken,
+            };
+            q.entries.insert(name, entry);
+        }
+        PARSED.notify_all();
+
+        enqueue(supers);
+    }
+}
+
+//the parsed class, None if not found. A panic is a malformed class, the
+//waiters of the entry must wake
+fn fetch(name: &[u8]) -> Option<io::Result<ClassFile>> {
+    let cls_name = String::from_utf8_lossy(name);
+    panic::catch_unwind(|| match runtime::find_class_in_classpath(&cls_name) {
+        Ok(ClassPathResult(_, buf)) => Some(class_parser::parse_buf(&buf)),
+        Err(_) => None,
+    })
+    .unwrap_or_else(|_| {
+        let e = io::Error::new(io::ErrorKind::InvalidData, "Malformed class file");
+        Some(Err(e))
+    })
+}
+
+fn next_pending() -> Option<Vec<u8>> {
+    util::sync_call_ctx(&QUEUE, |q| {
+        while let Some(name) = q.pending.pop_front() {
+            //already asked by the loader
+            if let Some(entry @ Entry::Pending) = q.entries.get_mut(&name) {
+                *entry = Entry::Parsing;
+                return Some(name);
+            }
+        }
+        None
+    })
+}
+
+//the prefetched ClassFile of name or its parse error, None if it isn't
+//ready
+pub fn take(name: &[u8]) -> Option<io::Result<ClassFileRef>> {
+    let r = {
+        let mut q = QUEUE.lock().unwrap();
+        //being parsed, parsing it again is no faster
+        while let Some(Entry::Parsing) = q.entries.get(name) {
+            q = PARSED.wait(q).unwrap();
+        }
+        match q.entries.get_mut(name) {
+            Some(entry) => match std::mem::replace(entry, Entry::Taken) {
+                Entry::Ready(cf) => Some(Some(Ok(cf))),
+                Entry::Failed(e) => Some(Some(Err(e))),
+                Entry::Pending => Some(None),
+                Entry::Parsing | Entry::Taken => None,
+            },
+            None => None,
+        }
+    };
+
+    let trace = flags::is_trace_class_loading();
+    let name = String::from_utf8_lossy(name);
+    match r {
+        Some(Some(cf)) => {
+            HITS.fetch_add(1, Ordering::Relaxed);
+            if trace {
+                println!("[Prefetch hit {}]", name);
+            }
+            Some(cf)
+        }
+        Some(None) => {
+            MISSES.fetch_add(1, Ordering::Relaxed);
+            if trace {
+                println!("[Prefetch miss {}]", name);
+            }
+            None
+        }
+        None => None,
+    }
+}
+
+//(hits, misses)
+pub fn stats() -> (usize, usize) {
+    (HITS.load(Ordering::Relaxed), MISSES.load(Ordering::Relaxed))
+}
+
+pub fn print_stats() {
+    if flags::is_prefetch_classes() && flags::is_trace_class_loading() {
+        let (hits, misses) = stats();
+        println!("[Prefetch hits={} misses={}]", hits, misses);
+    }
+}
+
+//the super class & interfaces
+fn super_classes(cf: &ClassFile) -> Vec<Vec<u8>> {
+    std::iter::once(cf.super_class)
+        .chain(cf.interfaces.iter().cloned())
+        .filter_map(|it| constant_pool::get_class_name(&cf.cp, it as usize))
+        .map(|it| it.to_vec())
+        .collect()
+}
+
+//class names in the constant pool, arrays excluded
+fn referred_classes(cf: &ClassFile) -> Vec<Vec<u8>> {
+    let mut names: Vec<Vec<u8>> = cf
+        .cp
+        .iter()
+        .filter_map(|it| match it {
+            ConstantType::Class { name_index } => {
+                constant_pool::get_utf8(&cf.cp, *name_index as usize)
+            }
+            _ => None,
+        })
+        .filter(|it| it.first() != Some(&b'['))
+        .map(|it| it.to_vec())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_take() {
+        util::sync_call_ctx(&QUEUE, |q| {
+            q.entries.insert(b"test/Pending".to_vec(), Entry::Pending);
+        });
+
+        let (_, misses) = stats();
+        assert!(take(b"test/Pending").is_none());
+        assert_eq!(stats().1, misses + 1);
+
+        //taken, the loader won't ask again
+        assert!(take(b"test/Pending").is_none());
+        assert!(take(b"test/NotPrefetched").is_none());
+        assert_eq!(stats().1, misses + 1);
+    }
+
+    #[test]
+    fn t_take_parsing() {
+        let name = b"test/Parsing";
+        util::sync_call_ctx(&QUEUE, |q| {
+            q.entries.insert(name.to_vec(), Entry::Parsing);
+        });
+
+        let wait = std::time::Duration::from_millis(20);
+        let parser = std::thread::spawn(move || {
+            std::thread::sleep(wait);
+            //not found in the class path
+            util::sync_call_ctx(&QUEUE, |q| {
+                q.entries.insert(name.to_vec(), Entry::Taken);
+            });
+            PARSED.notify_all();
+        });
+
+        //waits for the parse thread
+        let start = std::time::Instant::now();
+        assert!(take(name).is_none());
+        assert!(start.elapsed() >= wait);
+        parser.join().unwrap();
+    }
+
+    #[test]
+    fn t_take_failed() {
+        let name = b"test/Failed";
+        let wait = std::time::Duration::from_millis(20);
+        util::sync_call_ctx(&QUEUE, |q| {
+            q.entries.insert(name.to_vec(), Entry::Parsing);
+        });
+        let parser = std::thread::spawn(move || {
+            std::thread::sleep(wait);
+            let e = io::Error::new(io::ErrorKind::InvalidData, "Truncated class file");
+            util::sync_call_ctx(&QUEUE, |q| {
+                q.entries.insert(name.to_vec(), Entry::Failed(e));
+            });
+            PARSED.notify_all();
+        });
+
+        //the waiter gets the error, not a miss
+        let (hits, _) = stats();
+        assert!(matches!(take(name), Some(Err(_))));
+        assert_eq!(stats().0, hits + 1);
+        parser.join().unwrap();
+    }
+}
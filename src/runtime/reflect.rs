@@ -1,12 +1,16 @@
 #![allow(non_snake_case)]
 
+use crate::classfile::access_flags::*;
+use crate::classfile::attr_info::{AttrType, RecordComponent};
+use crate::classfile::constant_pool;
 use crate::classfile::consts as cls_const;
 use crate::classfile::signature::{FieldSignature, MethodSignature, Type as ArgType, Type};
-use crate::native::java_lang_Class;
+use crate::native::{java_lang_Class, java_lang_String};
 use crate::oop::{self, Oop, OopDesc, ValueType};
-use crate::runtime::{self, require_class3, JavaThread};
+use crate::runtime::{self, cmp, require_class3, JavaThread, Stack};
 use crate::types::*;
 use crate::util;
+use std::sync::Arc;
 
 pub fn new_field(jt: &mut JavaThread, fir: FieldIdRef) -> OopRef {
     let field_cls = runtime::require_class3(None, cls_const::J_FIELD).unwrap();
@@ -15,19 +19,14 @@ pub fn new_field(jt: &mut JavaThread, fir: FieldIdRef) -> OopRef {
 
     let field_sig = FieldSignature::new(fir.field.desc.as_slice());
     let typ_mirror = create_value_type(field_sig.field_type);
-    let desc = unsafe { std::str::from_utf8_unchecked(fir.field.desc.as_slice()) };
-    let signature = util::oop::new_java_lang_string2(jt, desc);
+    let signature = util::oop::new_java_lang_string3(jt, fir.field.desc.as_slice());
 
-    let field_name = unsafe { std::str::from_utf8_unchecked(fir.field.name.as_slice()) };
+    let field_name = util::oop::new_java_lang_string3(jt, fir.field.name.as_slice());
     let mut desc = Vec::new();
     desc.push(b'(');
     let mut args: Vec<OopRef> = vec![
         ("clazz", "Ljava/lang/Class;", clazz),
-        (
-            "name",
-            "Ljava/lang/String;",
-            util::oop::new_java_lang_string2(jt, field_name),
-        ),
+        ("name", "Ljava/lang/String;", field_name),
         ("type", "Ljava/lang/Class;", typ_mirror),
         (
             "modifiers",
@@ -53,6 +52,65 @@ pub fn new_field(jt: &mut JavaThread, fir: FieldIdRef) -> OopRef {
     oop
 }
 
+/*
+A java.lang.reflect.RecordComponent has no ctor to take its fields, they
+are set as HotSpot does. accessor is the component's public no-arg method
+of the same name, declared by the record itself.
+*/
+pub fn new_record_component(
+    jt: &mut JavaThread,
+    class: &ClassRef,
+    cp: &ConstantPool,
+    component: &RecordComponent,
+) -> OopRef {
+    let name = constant_pool::get_utf8(cp, component.name_index as usize).unwrap();
+    let desc = constant_pool::get_utf8(cp, component.descriptor_index as usize).unwrap();
+
+    let clazz = class.lock().unwrap().get_mirror();
+    let typ_mirror = create_value_type(FieldSignature::new(desc.as_slice()).field_type);
+    let accessor = {
+        let mut id = b"()".to_vec();
+        id.extend_from_slice(desc.as_slice());
+        let id = util::new_method_id(name.as_slice(), id.as_slice());
+        class.lock().unwrap().get_this_class_method(id)
+    };
+    let accessor = match accessor {
+        Ok(mir) => new_method(jt, mir),
+        Err(_) => oop::consts::get_null(),
+    };
+    let signature = component.attrs.iter().find_map(|it| match it {
+        AttrType::Signature { signature_index } => {
+            constant_pool::get_utf8(cp, *signature_index as usize)
+        }
+        _ => None,
+    });
+    let signature = match signature {
+        Some(s) => util::oop::new_java_lang_string3(jt, s.as_slice()),
+        None => oop::consts::get_null(),
+    };
+    let name = util::oop::new_java_lang_string3(jt, name.as_slice());
+    let name = java_lang_String::intern(name);
+
+    let cls = require_class3(None, cls_const::J_RECORD_COMPONENT).unwrap();
+    let oop = OopDesc::new_inst(cls.clone());
+    let cls = cls.lock().unwrap();
+    //fixme: annotations, typeAnnotations
+    [
+        ("clazz", "Ljava/lang/Class;", clazz),
+        ("name", "Ljava/lang/String;", name),
+        ("type", "Ljava/lang/Class;", typ_mirror),
+        ("accessor", "Ljava/lang/reflect/Method;", accessor),
+        ("signature", "Ljava/lang/String;", signature),
+    ]
+    .iter()
+    .for_each(|(n, t, v)| {
+        let id = cls.get_field_id(n.as_bytes(), t.as_bytes(), false);
+        cls.put_field_value(oop.clone(), id, v.clone());
+    });
+
+    oop
+}
+
 pub fn new_method_ctor(jt: &mut JavaThread, mir: MethodIdRef) -> OopRef {
     let ctor_cls = require_class3(None, cls_const::J_METHOD_CTOR).unwrap();
 
@@ -61,25 +119,17 @@ pub fn new_method_ctor(jt: &mut JavaThread, mir: MethodIdRef) -> OopRef {
 
     //parameterTypes
     let signature = MethodSignature::new(mir.method.desc.as_slice());
-    let params: Vec<OopRef> = signature
-        .args
-        .iter()
-        .map(|t| create_value_type(t.clone()))
-        .collect();
-    let cls = require_class3(None, b"[Ljava/lang/Class;").unwrap();
-    let parameter_types = OopDesc::new_ref_ary2(cls, params);
+    let parameter_types = new_class_ary(signature.args.as_slice());
 
     //fixme: checkedExceptions
-    let cls = require_class3(None, b"[Ljava/lang/Class;").unwrap();
-    let checked_exceptions = OopDesc::new_ref_ary2(cls, vec![]);
+    let checked_exceptions = new_class_ary(&[]);
 
     //modifiers
     let modifiers = mir.method.acc_flags;
     //slot
     let slot = mir.offset;
     //signature
-    let desc = unsafe { std::str::from_utf8_unchecked(mir.method.desc.as_slice()) };
-    let signature = util::oop::new_java_lang_string2(jt, desc);
+    let signature = util::oop::new_java_lang_string3(jt, mir.method.desc.as_slice());
     //fixme:
     let annotations = OopDesc::new_byte_ary(0);
     let parameter_annotations = OopDesc::new_byte_ary(0);
@@ -115,6 +165,88 @@ pub fn new_method_ctor(jt: &mut JavaThread, mir: MethodIdRef) -> OopRef {
     oop
 }
 
+pub fn new_method(jt: &mut JavaThread, mir: MethodIdRef) -> OopRef {
+    let method_cls = require_class3(None, cls_const::J_METHOD).unwrap();
+
+    let declaring_cls = { mir.method.class.lock().unwrap().get_mirror() };
+    //Class.searchMethods compares names by ==
+    let name = util::oop::new_java_lang_string3(jt, mir.method.name.as_slice());
+    let name = java_lang_String::intern(name);
+
+    let signature = MethodSignature::new(mir.method.desc.as_slice());
+    let parameter_types = new_class_ary(signature.args.as_slice());
+    let return_type = create_value_type(signature.retype.clone());
+    //fixme: checkedExceptions
+    let checked_exceptions = new_class_ary(&[]);
+
+    let signature = util::oop::new_java_lang_string3(jt, mir.method.desc.as_slice());
+    //fixme:
+    let annotations = OopDesc::new_byte_ary(0);
+    let parameter_annotations = OopDesc::new_byte_ary(0);
+    let annotation_default = OopDesc::new_byte_ary(0);
+
+    let mut desc = Vec::new();
+    desc.push(b'(');
+    let mut args: Vec<OopRef> = [
+        ("clazz", "Ljava/lang/Class;", declaring_cls),
+        ("name", "Ljava/lang/String;", name),
+        ("parameterTypes", "[Ljava/lang/Class;", parameter_types),
+        ("returnType", "Ljava/lang/Class;", return_type),
+        ("exceptionTypes", "[Ljava/lang/Class;", checked_exceptions),
+        (
+            "modifiers",
+            "I",
+            OopDesc::new_int(mir.method.acc_flags as i32),
+        ),
+        ("slot", "I", OopDesc::new_int(mir.offset as i32)),
+        ("signature", "Ljava/lang/String;", signature),
+        ("annotations", "[B", annotations),
+        ("parameterAnnotations", "[B", parameter_annotations),
+        ("annotationDefault", "[B", annotation_default),
+    ]
+    .iter()
+    .map(|(_, t, v)| {
+        desc.extend_from_slice(t.as_bytes());
+        v.clone()
+    })
+    .collect();
+    desc.extend_from_slice(")V".as_bytes());
+
+    let oop = OopDesc::new_inst(method_cls.clone());
+    args.insert(0, oop.clone());
+    runtime::java_call::invoke_ctor(jt, method_cls, desc.as_slice(), args);
+
+    oop
+}
+
+//the method a java.lang.reflect.Method stands for, by clazz & slot
+pub fn get_Method_mir(method: OopRef) -> MethodIdRef {
+    let clazz = get_field(&method, b"clazz", b"Ljava/lang/Class;");
+    let slot = util::oop::extract_int(get_field(&method, b"slot", b"I")) as usize;
+    let cls = {
+        let v = clazz.lock().unwrap();
+        match &v.v {
+            Oop::Mirror(mirror) => mirror.target.clone().unwrap(),
+            _ => unreachable!(),
+        }
+    };
+
+    let cls = cls.lock().unwrap();
+    match &cls.kind {
+        oop::class::ClassKind::Instance(inst) => inst
+            .all_methods
+            .values()
+            .find(|it| it.offset == slot)
+            .cloned()
+            .unwrap(),
+        _ => unreachable!(),
+    }
+}
+
+pub fn get_Method_modifiers(method: OopRef) -> U2 {
+    util::oop::extract_int(get_field(&method, b"modifiers", b"I")) as U2
+}
+
 pub fn get_Constructor_clazz(ctor: OopRef) -> OopRef {
     //todo: optimize, avoid obtain class
     let cls = {
@@ -167,7 +299,319 @@ pub fn get_Constructor_signature(ctor: OopRef) -> String {
     util::oop::extract_str(v)
 }
 
-fn create_value_type(t: ArgType) -> OopRef {
+pub fn get_Constructor_modifiers(ctor: OopRef) -> U2 {
+    let cls = {
+        let v = ctor.lock().unwrap();
+        match &v.v {
+            Oop::Inst(inst) => inst.class.clone(),
+            _ => unreachable!(),
+        }
+    };
+
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(b"modifiers", b"I", false);
+    let v = cls.get_field_value(ctor, id);
+    util::oop::extract_int(v) as U2
+}
+
+//AccessibleObject.override, set by setAccessible(true), the access
+//checks are skipped
+pub fn is_override(obj: OopRef) -> bool {
+    let cls = require_class3(None, cls_const::J_ACCESSIBLE_OBJECT).unwrap();
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(b"override", b"Z", false);
+    let v = cls.get_field_value(obj, id);
+    util::oop::extract_int(v) != 0
+}
+
+//Method.invoke, Constructor.newInstance, Class.newInstance and the
+//sun.reflect accessors, they call on behalf of their caller
+pub fn is_reflection_frame(mir: &MethodIdRef) -> bool {
+    let cls = mir.method.class.lock().unwrap();
+    let name = cls.name.as_slice();
+    name == cls_const::J_METHOD
+        || name == cls_const::J_METHOD_CTOR
+        || (name == cls_const::J_CLASS && mir.method.name.as_slice() == b"newInstance")
+        || name.starts_with(b"sun/reflect/")
+}
+
+//the class calling into reflection
+pub fn caller_class(jt: &JavaThread) -> Option<ClassRef> {
+    jt.callers
+        .iter()
+        .rev()
+        .find(|it| !is_reflection_frame(it))
+        .map(|it| it.method.class.clone())
+}
+
+//may caller access a member of member_cls with acc_flags, as
+//sun.reflect.Reflection.verifyMemberAccess
+pub fn verify_member_access(caller: &ClassRef, member_cls: &ClassRef, acc_flags: U2) -> bool {
+    if Arc::ptr_eq(caller, member_cls) {
+        return true;
+    }
+
+    let caller_name = { caller.lock().unwrap().name.clone() };
+    let (member_name, is_public_cls) = {
+        let cls = member_cls.lock().unwrap();
+        (cls.name.clone(), cls.is_public())
+    };
+    let same_package = is_same_package(caller_name.as_slice(), member_name.as_slice());
+
+    if !is_public_cls && !same_package {
+        return false;
+    }
+    if (acc_flags & ACC_PUBLIC) != 0 {
+        return true;
+    }
+    if (acc_flags & ACC_PRIVATE) != 0 {
+        return false;
+    }
+    if same_package {
+        return true;
+    }
+
+    (acc_flags & ACC_PROTECTED) != 0 && cmp::check_inherit(caller.clone(), member_cls.clone())
+}
+
+//"Class a.B can not access a member of class c.D with modifiers "private""
+pub fn access_denied_msg(caller: &ClassRef, member_cls: &ClassRef, acc_flags: U2) -> String {
+    let name = |cls: &ClassRef| {
+        let cls = cls.lock().unwrap();
+        String::from_utf8_lossy(cls.name.as_slice()).replace('/', ".")
+    };
+    let modifiers = if (acc_flags & ACC_PRIVATE) != 0 {
+        "private"
+    } else if (acc_flags & ACC_PROTECTED) != 0 {
+        "protected"
+    } else {
+        ""
+    };
+    format!(
+        "Class {} can not access a member of class {} with modifiers \"{}\"",
+        name(caller),
+        name(member_cls),
+        modifiers
+    )
+}
+
+/*
+Arguments of Method.invoke & Constructor.newInstance, converted to the
+parameter types of desc (JLS 5.3 method invocation conversion)
+  primitive: the wrapper is unboxed, then widened (int -> long, char ->
+             int, long -> float...), narrowing & null are rejected
+  reference: null, or an instance of the parameter type
+Err is the IllegalArgumentException message.
+*/
+pub fn convert_args(desc: &[u8], args: OopRef) -> Result<Vec<OopRef>, String> {
+    let sig = MethodSignature::new(desc);
+    let args = {
+        let v = args.lock().unwrap();
+        match &v.v {
+            Oop::Array(ary) => ary.elements.clone(),
+            Oop::Null => vec![],
+            t => unreachable!("t = {:?}", t),
+        }
+    };
+
+    if args.len() != sig.args.len() {
+        return Err("wrong number of arguments".to_string());
+    }
+
+    sig.args
+        .iter()
+        .zip(args)
+        .map(|(t, v)| convert_arg(t, v).ok_or_else(|| "argument type mismatch".to_string()))
+        .collect()
+}
+
+fn convert_arg(t: &ArgType, v: OopRef) -> Option<OopRef> {
+    match t {
+        Type::Object(desc) => {
+            let name = &desc.as_slice()[1..desc.len() - 1];
+            is_instance(&v, name).then_some(v)
+        }
+        Type::Array(desc) => is_instance(&v, desc.as_slice()).then_some(v),
+        Type::Void => unreachable!(),
+        _ => {
+            let (from, v) = unbox(&v)?;
+            if !can_widen(&from, t) {
+                return None;
+            }
+            let v = v.lock().unwrap();
+            Some(widen(&v.v, t))
+        }
+    }
+}
+
+//null is an instance of anything here
+fn is_instance(v: &OopRef, name: &[u8]) -> bool {
+    let target = match require_class3(None, name) {
+        Some(cls) => cls,
+        None => return false,
+    };
+    let v = v.lock().unwrap();
+    match &v.v {
+        Oop::Null => true,
+        Oop::Inst(inst) => cmp::instance_of(inst.class.clone(), target),
+        Oop::Array(ary) => cmp::instance_of(ary.class.clone(), target),
+        Oop::Mirror(_) => {
+            let cls = require_class3(None, cls_const::J_CLASS).unwrap();
+            cmp::instance_of(cls, target)
+        }
+        //primitive arrays have no class, matched by name
+        Oop::TypeArray(ary) => {
+            name == ary.desc()
+                || name == cls_const::J_OBJECT
+                || name == cls_const::J_CLONEABLE
+                || name == cls_const::J_SERIALIZABLE
+        }
+        _ => false,
+    }
+}
+
+//the primitive type & value (Int, Long, Float or Double) of a wrapper
+pub fn unbox(v: &OopRef) -> Option<(ArgType, OopRef)> {
+    let cls = {
+        let v = v.lock().unwrap();
+        match &v.v {
+            Oop::Inst(inst) => inst.class.clone(),
+            _ => return None,
+        }
+    };
+
+    let cls = cls.lock().unwrap();
+    let (t, desc): (ArgType, &[u8]) = match cls.name.as_slice() {
+        b"java/lang/Boolean" => (Type::Boolean, b"Z"),
+        b"java/lang/Byte" => (Type::Byte, b"B"),
+        b"java/lang/Character" => (Type::Char, b"C"),
+        b"java/lang/Short" => (Type::Short, b"S"),
+        b"java/lang/Integer" => (Type::Int, b"I"),
+        b"java/lang/Long" => (Type::Long, b"J"),
+        b"java/lang/Float" => (Type::Float, b"F"),
+        b"java/lang/Double" => (Type::Double, b"D"),
+        _ => return None,
+    };
+    let id = cls.get_field_id(b"value", desc, false);
+    Some((t, cls.get_field_value(v.clone(), id)))
+}
+
+//JLS 5.1.2 widening primitive conversion, or identity
+fn can_widen(from: &ArgType, to: &ArgType) -> bool {
+    use Type::*;
+    from == to
+        || match to {
+            Short => *from == Byte,
+            Int => matches!(from, Byte | Short | Char),
+            Long => matches!(from, Byte | Short | Char | Int),
+            Float => matches!(from, Byte | Short | Char | Int | Long),
+            Double => matches!(from, Byte | Short | Char | Int | Long | Float),
+            _ => false,
+        }
+}
+
+fn widen(v: &Oop, to: &ArgType) -> OopRef {
+    match (v, to) {
+        (Oop::Int(v), Type::Long) => OopDesc::new_long(*v as i64),
+        (Oop::Int(v), Type::Float) => OopDesc::new_float(*v as f32),
+        (Oop::Int(v), Type::Double) => OopDesc::new_double(*v as f64),
+        (Oop::Int(v), _) => OopDesc::new_int(*v),
+        (Oop::Long(v), Type::Float) => OopDesc::new_float(*v as f32),
+        (Oop::Long(v), Type::Double) => OopDesc::new_double(*v as f64),
+        (Oop::Long(v), _) => OopDesc::new_long(*v),
+        (Oop::Float(v), Type::Double) => OopDesc::new_double(*v as f64),
+        (Oop::Float(v), _) => OopDesc::new_float(*v),
+        (Oop::Double(v), _) => OopDesc::new_double(*v),
+        (t, _) => unreachable!("t = {:?}", t),
+    }
+}
+
+//the return value of Method.invoke, primitives are boxed, void is null
+pub fn box_return(jt: &mut JavaThread, t: &ArgType, stack: &mut Stack) -> OopRef {
+    let (name, desc, v): (&[u8], &[u8], OopRef) = match t {
+        Type::Boolean => (
+            b"java/lang/Boolean",
+            b"Z",
+            OopDesc::new_int(stack.pop_int()),
+        ),
+        Type::Byte => (b"java/lang/Byte", b"B", OopDesc::new_int(stack.pop_int())),
+        Type::Char => (
+            b"java/lang/Character",
+            b"C",
+            OopDesc::new_int(stack.pop_int()),
+        ),
+        Type::Short => (b"java/lang/Short", b"S", OopDesc::new_int(stack.pop_int())),
+        Type::Int => (
+            b"java/lang/Integer",
+            b"I",
+            OopDesc::new_int(stack.pop_int()),
+        ),
+        Type::Long => (b"java/lang/Long", b"J", OopDesc::new_long(stack.pop_long())),
+        Type::Float => (
+            b"java/lang/Float",
+            b"F",
+            OopDesc::new_float(stack.pop_float()),
+        ),
+        Type::Double => (
+            b"java/lang/Double",
+            b"D",
+            OopDesc::new_double(stack.pop_double()),
+        ),
+        Type::Object(_) | Type::Array(_) => return stack.pop_ref(),
+        Type::Void => return oop::consts::get_null(),
+    };
+
+    let cls = require_class3(None, name).unwrap();
+    oop::class::init_class_fully(jt, cls.clone());
+
+    let oop = OopDesc::new_inst(cls.clone());
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(b"value", desc, false);
+    cls.put_field_value(oop.clone(), id, v);
+    oop
+}
+
+//wraps ex thrown by the method or ctor called reflectively
+pub fn new_invocation_target_ex(jt: &mut JavaThread, ex: OopRef) -> OopRef {
+    let cls = require_class3(None, cls_const::J_INVOCATION_TARGET).unwrap();
+    oop::class::init_class_fully(jt, cls.clone());
+
+    let oop = OopDesc::new_inst(cls.clone());
+    let args = vec![oop.clone(), ex];
+    runtime::java_call::invoke_ctor(jt, cls, b"(Ljava/lang/Throwable;)V", args);
+    oop
+}
+
+//a field of obj, declared by its class or a super class
+pub fn get_field(obj: &OopRef, name: &[u8], desc: &[u8]) -> OopRef {
+    let cls = {
+        let v = obj.lock().unwrap();
+        match &v.v {
+            Oop::Inst(inst) => inst.class.clone(),
+            _ => unreachable!(),
+        }
+    };
+
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(name, desc, false);
+    cls.get_field_value(obj.clone(), id)
+}
+
+pub fn new_class_ary(types: &[ArgType]) -> OopRef {
+    let mirrors: Vec<OopRef> = types.iter().map(|t| create_value_type(t.clone())).collect();
+    let cls = require_class3(None, b"[Ljava/lang/Class;").unwrap();
+    OopDesc::new_ref_ary2(cls, mirrors)
+}
+
+fn is_same_package(a: &[u8], b: &[u8]) -> bool {
+    let package = |name: &[u8]| match name.iter().rposition(|&c| c == b'/') {
+        Some(i) => name[..i].to_vec(),
+        None => vec![],
+    };
+    package(a) == package(b)
+}
+
+pub fn create_value_type(t: ArgType) -> OopRef {
     match t {
         Type::Byte => java_lang_Class::get_primitive_class_mirror("B").unwrap(),
         Type::Char => java_lang_Class::get_primitive_class_mirror("C").unwrap(),
@@ -192,3 +636,39 @@ fn create_value_type(t: ArgType) -> OopRef {
         Type::Void => java_lang_Class::get_primitive_class_mirror("V").unwrap(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{can_widen, is_same_package};
+    use crate::classfile::signature::Type;
+
+    #[test]
+    fn t_same_package() {
+        assert!(is_same_package(b"java/lang/String", b"java/lang/Object"));
+        assert!(!is_same_package(
+            b"java/lang/String",
+            b"java/lang/reflect/Method"
+        ));
+        assert!(is_same_package(b"Main", b"Main$Inner"));
+        assert!(!is_same_package(b"Main", b"a/Main"));
+    }
+
+    #[test]
+    fn t_can_widen() {
+        assert!(can_widen(&Type::Int, &Type::Int));
+        assert!(can_widen(&Type::Byte, &Type::Short));
+        assert!(can_widen(&Type::Char, &Type::Int));
+        assert!(can_widen(&Type::Int, &Type::Long));
+        assert!(can_widen(&Type::Long, &Type::Float));
+        assert!(can_widen(&Type::Float, &Type::Double));
+
+        //narrowing
+        assert!(!can_widen(&Type::Long, &Type::Int));
+        assert!(!can_widen(&Type::Double, &Type::Float));
+        assert!(!can_widen(&Type::Int, &Type::Char));
+        assert!(!can_widen(&Type::Char, &Type::Short));
+        assert!(!can_widen(&Type::Short, &Type::Char));
+        assert!(!can_widen(&Type::Boolean, &Type::Int));
+        assert!(!can_widen(&Type::Int, &Type::Boolean));
+    }
+}
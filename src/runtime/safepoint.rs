@@ -0,0 +1,254 @@
+/*
+Safepoints, stopping the world
+
+The collector finds roots by counting refs (see runtime::gc): a thread
+moving a ref between its stack & a field while the heap is scanned makes
+the counts disagree, & a live object may be swept. So a collection runs
+with every other thread stopped:
+  run      the collector raises REQUESTED & waits until each live thread
+           is stopped, then runs alone & resumes them
+  poll     a thread running java checks REQUESTED between two
+           instructions & at each call, it flushes its allocation budget
+           & blocks until the collection is done
+  stopped  a thread in a safe region (blocked in a monitor, park, sleep,
+           class init..., see thread::in_state), or out of java code
+           (no frames: not started, a host thread between two calls)
+
+Leaving a safe region, or entering java code, waits for a running
+collection first. Host code out of java must not store refs into the heap
+while a collection runs, as natives blocked in a safe region.
+
+Shutdown.halt0 (System.exit, Runtime.halt) is a safepoint never resumed:
+the status is recorded, the halting thread & every other stop at their
+next poll for good, & Vm::run returns the status. Nothing is unwound, no
+monitor released, no class init finished, no java code runs after, the
+host process goes on.
+
+The collector holds no lock others may need to reach a poll (reserve is
+called before any is taken). Lock order: SP, then thread::THREADS.
+*/
+
+use crate::runtime::{gc, thread};
+use crate::util;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread::ThreadId;
+use std::time::Duration;
+
+//the depth of a thread entering java isn't notified, looked at again
+//after this
+const ARRIVE_WAIT: Duration = Duration::from_millis(10);
+
+#[derive(Default)]
+struct State {
+    active: bool,
+    //the host thread running the safepoint
+    owner: Option<ThreadId>,
+    //the status of Shutdown.halt0, the first one
+    exit: Option<i32>,
+}
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref SP: Mutex<State> = Mutex::new(State::default());
+    //a thread stopped
+    static ref ARRIVED: Condvar = Condvar::new();
+    //the safepoint is over, or the vm halted
+    static ref RESUMED: Condvar = Condvar::new();
+}
+
+impl State {
+    //a safepoint of another thread or a halt, this one has to wait
+    fn blocks_me(&self) -> bool {
+        let other = self.active && self.owner != Some(std::thread::current().id());
+        other || self.exit.is_some()
+    }
+}
+
+//f with the other threads stopped, nested runs are f alone
+pub fn run<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let me = std::thread::current().id();
+    let mut sp = SP.lock().unwrap();
+    if sp.owner == Some(me) {
+        drop(sp);
+        return f();
+    }
+
+    //another collection first, stopped meanwhile
+    if sp.active {
+        sp = stopped_while(sp, |sp| sp.active);
+    }
+    sp.active = true;
+    sp.owner = Some(me);
+    REQUESTED.store(true, Ordering::SeqCst);
+    while !thread::others_stopped() {
+        sp = ARRIVED.wait_timeout(sp, ARRIVE_WAIT).unwrap().0;
+    }
+    drop(sp);
+
+    //resumed even if f panics
+    let _resume = Resume;
+    f()
+}
+
+struct Resume;
+
+impl Drop for Resume {
+    fn drop(&mut self) {
+        util::sync_call_ctx(&SP, |sp| {
+            sp.active = false;
+            sp.owner = None;
+            REQUESTED.store(sp.exit.is_some(), Ordering::SeqCst);
+        });
+        RESUMED.notify_all();
+    }
+}
+
+//Shutdown.halt0, the current thread stops for good
+pub fn halt(status: i32) -> ! {
+    let mut sp = SP.lock().unwrap();
+    sp.exit.get_or_insert(status);
+    REQUESTED.store(true, Ordering::SeqCst);
+    RESUMED.notify_all();
+
+    let _sp = stopped_while(sp, |_| true);
+    unreachable!()
+}
+
+//the status once the vm halted
+pub fn exit_status() -> Option<i32> {
+    util::sync_call_ctx(&SP, |sp| sp.exit)
+}
+
+//blocks until the vm halted or timeout passed, the status if halted
+pub fn wait_halt(timeout: Duration) -> Option<i32> {
+    let sp = SP.lock().unwrap();
+    let (sp, _) = RESUMED
+        .wait_timeout_while(sp, timeout, |sp| sp.exit.is_none())
+        .unwrap();
+    sp.exit
+}
+
+//the safe point of a thread running java
+pub fn poll() {
+    if REQUESTED.load(Ordering::SeqCst) {
+        let sp = SP.lock().unwrap();
+        if sp.blocks_me() {
+            let _sp = stopped_while(sp, |sp| sp.blocks_me());
+        }
+    }
+}
+
+//f may block, a safepoint doesn't wait for it. The thread is stopped
+//until f returns & the safepoint is over
+pub fn safe_region<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let stat = match thread::current_stat() {
+        Some(stat) => stat,
+        None => return f(),
+    };
+
+    gc::budget::flush();
+    util::sync_call_ctx(&SP, |_| {
+        stat.safe.fetch_add(1, Ordering::SeqCst);
+        ARRIVED.notify_all();
+    });
+    let r = f();
+
+    let mut sp = SP.lock().unwrap();
+    while sp.blocks_me() {
+        sp = RESUMED.wait(sp).unwrap();
+    }
+    stat.safe.fetch_sub(1, Ordering::SeqCst);
+    r
+}
+
+//the thread counts as stopped, its objects seen by the collector, while
+//cond holds
+fn stopped_while<'a>(
+    mut sp: std::sync::MutexGuard<'a, State>,
+    cond: impl Fn(&State) -> bool,
+) -> std::sync::MutexGuard<'a, State> {
+    let stat = thread::current_stat();
+    drop(sp);
+    gc::budget::flush();
+    sp = SP.lock().unwrap();
+
+    if let Some(stat) = &stat {
+        stat.safe.fetch_add(1, Ordering::SeqCst);
+    }
+    ARRIVED.notify_all();
+    while cond(&sp) {
+        sp = RESUMED.wait(sp).unwrap();
+    }
+    if let Some(stat) = &stat {
+        stat.safe.fetch_sub(1, Ordering::SeqCst);
+    }
+    sp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::JavaThread;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    #[test]
+    fn t_run() {
+        //a thread "running java", stopped at its next poll
+        let done = Arc::new(AtomicBool::new(false));
+        let polls = Arc::new(AtomicUsize::new(0));
+        let (started_tx, started) = std::sync::mpsc::channel();
+        let mutator = {
+            let (done, polls) = (done.clone(), polls.clone());
+            std::thread::spawn(move || {
+                let jt = JavaThread::new();
+                jt.stat.depth.store(1, Ordering::SeqCst);
+                started_tx.send(jt.stat.clone()).unwrap();
+                while !done.load(Ordering::SeqCst) {
+                    poll();
+                    polls.fetch_add(1, Ordering::SeqCst);
+                }
+                jt.stat.depth.store(0, Ordering::SeqCst);
+            })
+        };
+        let stat = started.recv().unwrap();
+
+        run(|| {
+            assert_eq!(stat.safe.load(Ordering::SeqCst), 1);
+            let n = polls.load(Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            assert_eq!(polls.load(Ordering::SeqCst), n);
+            assert_eq!(run(|| 7), 7);
+        });
+        done.store(true, Ordering::SeqCst);
+        mutator.join().unwrap();
+        assert_eq!(stat.safe.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn t_blocks_me() {
+        let me = std::thread::current().id();
+        let other = std::thread::spawn(|| std::thread::current().id())
+            .join()
+            .unwrap();
+        let st = |active, owner, exit| State {
+            active,
+            owner,
+            exit,
+        };
+        assert!(!st(false, None, None).blocks_me());
+        assert!(!st(true, Some(me), None).blocks_me());
+        assert!(st(true, Some(other), None).blocks_me());
+        //halted, the owner stops too
+        assert!(st(false, None, Some(3)).blocks_me());
+        assert!(st(true, Some(me), Some(3)).blocks_me());
+    }
+}
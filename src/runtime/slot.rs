@@ -1,6 +1,10 @@
 use crate::types::{BytesRef, OopRef};
 use std::sync::Arc;
 
+//pushed by jsr, stored by astore, jumped to by ret, the bci after the jsr
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReturnAddress(pub i32);
+
 #[derive(Debug, Clone)]
 pub enum Slot {
     ConstM1,
@@ -0,0 +1,924 @@
+/*
+Startup snapshot
+
+  jvm -XX:DumpLoadedClassList=classes.lst Main
+  jvm -XX:DumpHeapSnapshot=heap.snap Main
+  jvm -XX:RestoreHeapSnapshot=heap.snap Main
+
+As a GraalVM isolate or a CDS archived heap, a later run starts from the
+state of a booted vm:
+  class list  which classes a run loads, when the vm exits
+  heap        the object graph once the vm booted, before main: the
+              classes, their field layout & static fields, & every object
+              they or the vm reach, with stable ids
+  restore     the heap file mapped & read back instead of the boot,
+              init_vm::initialize_jvm is not run
+
+Restoring loads & links the classes of the file, their class files
+parsed again (the metadata isn't archived, the class path must give the
+same classes: a super class or a field layout differing, the file is
+not used & the vm boots). Each object is made, its refs then filled by
+id, & the classes get their state, statics & mirror back. So does the vm
+side: the primitive mirrors, the string table, the main thread. Then
+the methods run as natives are hacked & the errors preallocated again,
+as at boot. Not kept:
+  - where the other threads were, they start again from run()
+    (Finalizer$FinalizerThread with running cleared)
+  - memory out of the heap: an Unsafe.allocateMemory address in a field
+    points nowhere, a file opened (but stdin, stdout, stderr) is closed
+  - a monitor held or a class being initialized, such a file is refused
+The objects are made one by one, Arc<Mutex<Box<OopDesc>>>, the file is
+not the heap itself. See note.txt, 启动快照.
+
+The heap is a text file, one line a record:
+  class <name> <state> <super>|-
+  field <class> <slot> <name> <desc> [static]
+  static <class> <name> <value>
+  mirror <class> <value>
+  prim <key> <value>                       the mirror of a primitive type
+  intern <value>                           an interned String
+  main <value>                             the Thread of the main thread
+  thread <value>                           another live Thread
+  object <id> <class> <value>...           an instance or object array
+  object <id> <[I...> <element>...         a primitive array
+  object <id> mirror <class>|- <type> <value>...
+  object <id> locked                       held by a stopped thread
+  hash <id> <int>                          the identity hash, if made
+A value is null, @<id>, I<int>, J<long>, F<bits>, D<bits> (hex) or
+U<utf8 hex>. Ids are given breadth first from the classes in name order,
+the statics by slot, then the mirror, then the vm's: two boots of the
+same class path number the objects alike. Written with the other threads
+stopped, see runtime::safepoint.
+*/
+
+use crate::classfile::consts::J_STRING;
+use crate::native::{java_lang_Class, java_lang_String, java_lang_Thread};
+use crate::oop::class::{ClassKind, State};
+use crate::oop::{consts, Oop, OopDesc, TypeArrayValue, ValueType};
+use crate::runtime::{self, exception, flags, gc, thread, JavaThread};
+use crate::types::{ClassRef, OopRef};
+use crate::util;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+//write the loaded class names to -XX:DumpLoadedClassList, one per line
+pub fn dump_class_list() {
+    if let Some(path) = flags::dump_class_list() {
+        let buf = class_list(runtime::sys_dic_names());
+        if !util::write(&path, buf.as_bytes()) {
+            warn!("write class list failed: {}", path);
+        }
+    }
+}
+
+fn class_list(mut names: Vec<String>) -> String {
+    names.sort();
+    let mut buf = names.join("\n");
+    buf.push('\n');
+    buf
+}
+
+//write the heap to -XX:DumpHeapSnapshot, jt the main thread
+pub fn dump_heap_snapshot(jt: &JavaThread) {
+    if let Some(path) = flags::dump_heap_snapshot() {
+        let mut names = runtime::sys_dic_names();
+        names.sort();
+        let classes: Vec<ClassRef> = names
+            .iter()
+            .filter_map(|it| runtime::sys_dic_find(it.as_bytes()))
+            .collect();
+        //the references found by a collection, into Reference.pending
+        gc::refs::publish_pending();
+        let roots = vm_roots(jt);
+        let buf = runtime::safepoint::run(|| heap_snapshot(&classes, &roots));
+        if !util::write(&path, buf.as_bytes()) {
+            warn!("write heap snapshot failed: {}", path);
+        }
+    }
+}
+
+//the objects the vm holds out of the heap
+#[derive(Default)]
+struct Roots {
+    prims: Vec<(String, OopRef)>,
+    interned: Vec<OopRef>,
+    main: Option<OopRef>,
+    threads: Vec<OopRef>,
+}
+
+//in an order two boots agree on, the other threads by name
+fn vm_roots(jt: &JavaThread) -> Roots {
+    let main = jt.java_thread_obj.clone();
+    let mut threads: Vec<(String, OopRef)> = thread::live_threads()
+        .into_iter()
+        .filter(|it| main.as_ref().is_none_or(|main| !Arc::ptr_eq(main, it)))
+        .map(|it| {
+            (
+                java_lang_Thread::guest_thread_name(&it).unwrap_or_default(),
+                it,
+            )
+        })
+        .collect();
+    threads.sort_by(|a, b| a.0.cmp(&b.0));
+    Roots {
+        prims: java_lang_Class::primitive_class_mirrors(),
+        interned: java_lang_String::interned(),
+        main,
+        threads: threads.into_iter().map(|it| it.1).collect(),
+    }
+}
+
+fn heap_snapshot(classes: &[ClassRef], roots: &Roots) -> String {
+    let mut g = Graph::default();
+    let mut buf = String::new();
+    for class in classes {
+        let cls = class.lock().unwrap();
+        let name = String::from_utf8_lossy(cls.name.as_slice()).to_string();
+        let super_name = cls
+            .super_class
+            .as_ref()
+            .map(class_name)
+            .unwrap_or_else(|| "-".to_string());
+        let _ = writeln!(buf, "class {} {:?} {}", name, cls.state, super_name);
+
+        let statics = match &cls.kind {
+            ClassKind::Instance(cls_obj) => {
+                let mut fields: Vec<(usize, String, bool)> = Vec::new();
+                let all = cls_obj
+                    .inst_fields
+                    .values()
+                    .map(|it| (it, false))
+                    .chain(cls_obj.static_fields.values().map(|it| (it, true)));
+                for (fid, is_static) in all {
+                    let line = format!(
+                        "field {} {} {} {}{}",
+                        name,
+                        fid.offset,
+                        String::from_utf8_lossy(fid.field.name.as_slice()),
+                        String::from_utf8_lossy(fid.field.desc.as_slice()),
+                        if is_static { " static" } else { "" }
+                    );
+                    fields.push((fid.offset, line, is_static));
+                }
+                fields.sort_by_key(|it| (it.2, it.0));
+                fields.iter().for_each(|it| {
+                    let _ = writeln!(buf, "{}", it.1);
+                });
+
+                let mut statics: Vec<_> = cls_obj.static_fields.values().cloned().collect();
+                statics.sort_by_key(|it| it.offset);
+                statics
+                    .into_iter()
+                    .map(|fid| {
+                        let field_name = String::from_utf8_lossy(fid.field.name.as_slice());
+                        (field_name.to_string(), cls.get_static_field_value(fid))
+                    })
+                    .collect::<Vec<(String, OopRef)>>()
+            }
+            _ => vec![],
+        };
+        let mirror = cls.try_get_mirror();
+        drop(cls);
+
+        for (field_name, v) in statics {
+            let v = g.value(&v);
+            let _ = writeln!(buf, "static {} {} {}", name, field_name, v);
+        }
+        if let Some(mirror) = mirror {
+            let _ = writeln!(buf, "mirror {} {}", name, g.value(&mirror));
+        }
+        g.drain(&mut buf);
+    }
+
+    for (key, v) in roots.prims.iter() {
+        let _ = writeln!(buf, "prim {} {}", key, g.value(v));
+    }
+    for v in roots.interned.iter() {
+        let _ = writeln!(buf, "intern {}", g.value(v));
+    }
+    if let Some(v) = &roots.main {
+        let _ = writeln!(buf, "main {}", g.value(v));
+    }
+    for v in roots.threads.iter() {
+        let _ = writeln!(buf, "thread {}", g.value(v));
+    }
+    g.drain(&mut buf);
+    buf
+}
+
+//the objects reached, numbered as found
+#[derive(Default)]
+struct Graph {
+    ids: HashMap<usize, usize>,
+    queue: VecDeque<(usize, OopRef)>,
+}
+
+impl Graph {
+    //inline if a primitive, else the id, the object queued the first time
+    fn value(&mut self, v: &OopRef) -> String {
+        if let Ok(it) = v.try_lock() {
+            match &it.v {
+                Oop::Null => return "null".to_string(),
+                Oop::Int(v) => return format!("I{}", v),
+                Oop::Long(v) => return format!("J{}", v),
+                Oop::Float(v) => return format!("F{:x}", v.to_bits()),
+                Oop::Double(v) => return format!("D{:x}", v.to_bits()),
+                Oop::ConstUtf8(v) => return format!("U{}", hex(v.as_slice())),
+                _ => (),
+            }
+        }
+
+        let next = self.ids.len();
+        let id = *self.ids.entry(Arc::as_ptr(v) as usize).or_insert(next);
+        if id == next {
+            self.queue.push_back((id, v.clone()));
+        }
+        format!("@{}", id)
+    }
+
+    //the queued objects to buf, & those they reach. The refs are copied
+    //out, one object locked at a time
+    fn drain(&mut self, buf: &mut String) {
+        while let Some((id, v)) = self.queue.pop_front() {
+            let mut hash = None;
+            let (head, refs, prims) = match v.try_lock() {
+                //a monitor held, the thread holding it doesn't resume
+                Ok(it) if !it.header.is_locked_by(0) => ("locked".to_string(), vec![], None),
+                Ok(it) => {
+                    hash = it.header.hash_code;
+                    match &it.v {
+                        Oop::Inst(inst) => {
+                            (class_name(&inst.class), inst.field_values.clone(), None)
+                        }
+                        Oop::Array(ary) => (class_name(&ary.class), ary.elements.clone(), None),
+                        Oop::TypeArray(ary) => {
+                            let desc = String::from_utf8_lossy(ary.desc()).to_string();
+                            (desc, vec![], Some(elements(ary)))
+                        }
+                        Oop::Mirror(mirror) => {
+                            let target = match &mirror.target {
+                                Some(target) => class_name(target),
+                                None => "-".to_string(),
+                            };
+                            let vt: &[u8] = mirror.value_type.into();
+                            (
+                                format!("mirror {} {}", target, String::from_utf8_lossy(vt)),
+                                mirror.field_values.clone(),
+                                None,
+                            )
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                Err(_) => ("locked".to_string(), vec![], None),
+            };
+
+            let _ = write!(buf, "object {} {}", id, head);
+            match prims {
+                Some(prims) => {
+                    if !prims.is_empty() {
+                        let _ = write!(buf, " {}", prims);
+                    }
+                }
+                None => {
+                    for it in refs.iter() {
+                        let _ = write!(buf, " {}", self.value(it));
+                    }
+                }
+            }
+            buf.push('\n');
+            if let Some(hash) = hash {
+                let _ = writeln!(buf, "hash {} {}", id, hash);
+            }
+        }
+    }
+}
+
+//-XX:RestoreHeapSnapshot, the heap of the file instead of the boot.
+//false without the flag, or if the file doesn't fit the class path: the
+//vm boots then
+pub fn restore_heap_snapshot(jt: &mut JavaThread) -> bool {
+    let path = match flags::restore_heap_snapshot() {
+        Some(path) => path,
+        None => return false,
+    };
+    let r = read(&path).and_then(|s| {
+        let classes = load_classes(&s)?;
+        Ok((s, classes))
+    });
+    let (s, classes) = match r {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("restore heap snapshot {} failed, boot: {}", path, e);
+            return false;
+        }
+    };
+
+    info!("restore heap snapshot {}", path);
+    //the mirrors of the classes loaded so far, replaced by the file's
+    java_lang_Class::create_delayed_mirrors();
+    java_lang_Class::create_delayed_ary_mirrors();
+    let objects = restore(&s, &classes);
+    restore_vm(jt, &s, &objects);
+    true
+}
+
+fn read(path: &str) -> Result<Snapshot, String> {
+    let m = util::Mmap::open(path).map_err(|e| e.to_string())?;
+    let text = std::str::from_utf8(&m).map_err(|e| e.to_string())?;
+    parse(text)
+}
+
+//a value of the file, a ref by id
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Ref(usize),
+    Int(i32),
+    Long(i64),
+    Float(u32),
+    Double(u64),
+    Utf8(Vec<u8>),
+}
+
+//an object of the file
+enum Head {
+    //class, field values
+    Inst(String, Vec<Value>),
+    //class, elements
+    Array(String, Vec<Value>),
+    //made already, no ref in it
+    Prims(OopRef),
+    //target class, value type, field values
+    Mirror(Option<String>, ValueType, Vec<Value>),
+    Locked,
+}
+
+struct ClassRec {
+    name: String,
+    state: State,
+    super_name: Option<String>,
+}
+
+struct FieldRec {
+    class: String,
+    slot: usize,
+    name: String,
+    desc: String,
+    is_static: bool,
+}
+
+//the records of a file, the objects by id
+#[derive(Default)]
+struct Snapshot {
+    classes: Vec<ClassRec>,
+    fields: Vec<FieldRec>,
+    //class, field name, value
+    statics: Vec<(String, String, Value)>,
+    mirrors: Vec<(String, Value)>,
+    objects: Vec<Head>,
+    hashes: Vec<(usize, i32)>,
+    prims: Vec<(String, Value)>,
+    interned: Vec<Value>,
+    main: Option<Value>,
+    threads: Vec<Value>,
+}
+
+fn parse(text: &str) -> Result<Snapshot, String> {
+    let mut s = Snapshot::default();
+    for (n, line) in text.lines().enumerate() {
+        let bad = || format!("line {}: {}", n + 1, line);
+        let v: Vec<&str> = line.split_whitespace().collect();
+        match v.as_slice() {
+            ["class", name, state, super_name] => s.classes.push(ClassRec {
+                name: name.to_string(),
+                state: class_state(state).ok_or_else(bad)?,
+                super_name: match *super_name {
+                    "-" => None,
+                    it => Some(it.to_string()),
+                },
+            }),
+            ["field", class, slot, name, desc, rest @ ..] => s.fields.push(FieldRec {
+                class: class.to_string(),
+                slot: slot.parse().map_err(|_| bad())?,
+                name: name.to_string(),
+                desc: desc.to_string(),
+                is_static: match rest {
+                    [] => false,
+                    ["static"] => true,
+                    _ => return Err(bad()),
+                },
+            }),
+            ["static", class, name, v] => {
+                let v = value(v).ok_or_else(bad)?;
+                s.statics.push((class.to_string(), name.to_string(), v));
+            }
+            ["mirror", class, v] => {
+                let v = value(v).ok_or_else(bad)?;
+                s.mirrors.push((class.to_string(), v));
+            }
+            ["object", id, rest @ ..] => {
+                if id.parse() != Ok(s.objects.len()) {
+                    return Err(bad());
+                }
+                s.objects.push(object(rest).ok_or_else(bad)?);
+            }
+            ["hash", id, hash] => {
+                let id = id.parse().map_err(|_| bad())?;
+                s.hashes.push((id, hash.parse().map_err(|_| bad())?));
+            }
+            ["prim", key, v] => s.prims.push((key.to_string(), value(v).ok_or_else(bad)?)),
+            ["intern", v] => s.interned.push(value(v).ok_or_else(bad)?),
+            ["main", v] => s.main = Some(value(v).ok_or_else(bad)?),
+            ["thread", v] => s.threads.push(value(v).ok_or_else(bad)?),
+            _ => return Err(bad()),
+        }
+    }
+
+    //every ref to an object of the file
+    let n = s.objects.len();
+    let mut values: Vec<&Value> = s
+        .statics
+        .iter()
+        .map(|it| &it.2)
+        .chain(s.mirrors.iter().map(|it| &it.1))
+        .chain(s.prims.iter().map(|it| &it.1))
+        .chain(s.interned.iter())
+        .chain(s.main.iter())
+        .chain(s.threads.iter())
+        .collect();
+    for it in s.objects.iter() {
+        match it {
+            Head::Inst(_, v) | Head::Array(_, v) | Head::Mirror(_, _, v) => values.extend(v),
+            _ => (),
+        }
+    }
+    let dangling = values
+        .into_iter()
+        .any(|it| matches!(it, Value::Ref(id) if *id >= n));
+    if dangling || s.hashes.iter().any(|it| it.0 >= n) {
+        return Err("a ref to no object".to_string());
+    }
+    Ok(s)
+}
+
+fn class_state(s: &str) -> Option<State> {
+    let state = match s {
+        "Allocated" => State::Allocated,
+        "Loaded" => State::Loaded,
+        "Linked" => State::Linked,
+        "BeingIni" => State::BeingIni,
+        "FullyIni" => State::FullyIni,
+        "IniErr" => State::IniErr,
+        _ => return None,
+    };
+    Some(state)
+}
+
+fn object(v: &[&str]) -> Option<Head> {
+    match v {
+        ["locked"] => Some(Head::Locked),
+        ["mirror", target, vt, rest @ ..] => {
+            let target = match *target {
+                "-" => None,
+                it => Some(it.to_string()),
+            };
+            let vt = match vt.as_bytes() {
+                [c] if b"BZCSIJFDVL[".contains(c) => ValueType::from(c),
+                _ => return None,
+            };
+            Some(Head::Mirror(target, vt, values(rest)?))
+        }
+        [class, rest @ ..] => match class.as_bytes() {
+            [b'[', b'L', ..] | [b'[', b'[', ..] => {
+                Some(Head::Array(class.to_string(), values(rest)?))
+            }
+            [b'[', t] => prims(*t, rest).map(Head::Prims),
+            _ => Some(Head::Inst(class.to_string(), values(rest)?)),
+        },
+        [] => None,
+    }
+}
+
+fn value(s: &str) -> Option<Value> {
+    if s == "null" {
+        return Some(Value::Null);
+    }
+    let v = s.get(1..)?;
+    match s.as_bytes().first()? {
+        b'@' => v.parse().ok().map(Value::Ref),
+        b'I' => v.parse().ok().map(Value::Int),
+        b'J' => v.parse().ok().map(Value::Long),
+        b'F' => u32::from_str_radix(v, 16).ok().map(Value::Float),
+        b'D' => u64::from_str_radix(v, 16).ok().map(Value::Double),
+        b'U' => unhex(v).map(Value::Utf8),
+        _ => None,
+    }
+}
+
+fn values(v: &[&str]) -> Option<Vec<Value>> {
+    v.iter().map(|it| value(it)).collect()
+}
+
+//a primitive array of type t, as written by elements
+fn prims(t: u8, v: &[&str]) -> Option<OopRef> {
+    fn all<T: FromStr>(v: &[&str]) -> Option<Vec<T>> {
+        v.iter().map(|it| it.parse().ok()).collect()
+    }
+    fn bits(v: &[&str]) -> Option<Vec<u64>> {
+        v.iter()
+            .map(|it| u64::from_str_radix(it, 16).ok())
+            .collect()
+    }
+    let bytes = || match v {
+        [] => Some(vec![]),
+        [it] => unhex(it),
+        _ => None,
+    };
+    let v = match t {
+        b'B' => OopDesc::new_byte_ary2(bytes()?),
+        b'Z' => OopDesc::new_bool_ary2(bytes()?),
+        b'C' => OopDesc::new_char_ary2(all(v)?),
+        b'S' => OopDesc::new_short_ary2(all(v)?),
+        b'I' => OopDesc::new_int_ary2(all(v)?),
+        b'J' => OopDesc::new_long_ary2(all(v)?),
+        b'F' => {
+            let v = bits(v)?.into_iter();
+            OopDesc::new_float_ary2(v.map(|it| f32::from_bits(it as u32)).collect())
+        }
+        b'D' => OopDesc::new_double_ary2(bits(v)?.into_iter().map(f64::from_bits).collect()),
+        _ => return None,
+    };
+    Some(v)
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .and_then(|it| u8::from_str_radix(it, 16).ok())
+        })
+        .collect()
+}
+
+//the classes of the file loaded, by name. Err if one is not found,
+//being initialized, or its super class or fields differ from the file's,
+//or an object can't be made again
+fn load_classes(s: &Snapshot) -> Result<HashMap<String, ClassRef>, String> {
+    let mut classes = HashMap::new();
+    for it in s.classes.iter() {
+        if it.state == State::BeingIni {
+            return Err(format!("{} being initialized", it.name));
+        }
+        let class = runtime::require_class3(None, it.name.as_bytes())
+            .ok_or_else(|| format!("{} not found", it.name))?;
+        let super_name = class.lock().unwrap().super_class.as_ref().map(class_name);
+        if super_name != it.super_name {
+            return Err(format!("{}, the super class differs", it.name));
+        }
+        classes.insert(it.name.clone(), class);
+    }
+    let find = |name: &String| {
+        classes
+            .get(name)
+            .ok_or_else(|| format!("{} not in the file", name))
+    };
+
+    //the field layout, slot by slot
+    let mut n_fields: HashMap<(&str, bool), usize> = HashMap::new();
+    for it in s.fields.iter() {
+        let found = match &find(&it.class)?.lock().unwrap().kind {
+            ClassKind::Instance(cls_obj) => {
+                let fields = if it.is_static {
+                    &cls_obj.static_fields
+                } else {
+                    &cls_obj.inst_fields
+                };
+                fields.values().any(|fid| {
+                    fid.offset == it.slot
+                        && fid.field.name.as_slice() == it.name.as_bytes()
+                        && fid.field.desc.as_slice() == it.desc.as_bytes()
+                })
+            }
+            _ => false,
+        };
+        if !found {
+            return Err(format!("{}.{}, the field differs", it.class, it.name));
+        }
+        *n_fields
+            .entry((it.class.as_str(), it.is_static))
+            .or_insert(0) += 1;
+    }
+    for (name, class) in classes.iter() {
+        if let ClassKind::Instance(cls_obj) = &class.lock().unwrap().kind {
+            let n = |is_static| n_fields.get(&(name.as_str(), is_static)).copied();
+            if n(false).unwrap_or(0) != cls_obj.inst_fields.len()
+                || n(true).unwrap_or(0) != cls_obj.static_fields.len()
+            {
+                return Err(format!("{}, the fields differ", name));
+            }
+        }
+    }
+
+    for (id, it) in s.objects.iter().enumerate() {
+        match it {
+            Head::Inst(class, v) => {
+                let n = match &find(class)?.lock().unwrap().kind {
+                    ClassKind::Instance(cls_obj) => Some(cls_obj.n_inst_fields),
+                    _ => None,
+                };
+                if n != Some(v.len()) {
+                    return Err(format!("object {}, not an instance of {}", id, class));
+                }
+            }
+            Head::Array(class, _) | Head::Mirror(Some(class), ..) => {
+                find(class)?;
+            }
+            Head::Locked => return Err(format!("object {} locked", id)),
+            _ => (),
+        }
+    }
+    for (class, name, _) in s.statics.iter() {
+        static_field(s, class, name)
+            .ok_or_else(|| format!("{}.{} not in the file", class, name))?;
+    }
+    for (class, _) in s.mirrors.iter() {
+        find(class)?;
+    }
+    Ok(classes)
+}
+
+fn static_field<'a>(s: &'a Snapshot, class: &str, name: &str) -> Option<&'a FieldRec> {
+    s.fields
+        .iter()
+        .find(|it| it.is_static && it.class == class && it.name == name)
+}
+
+//the objects of the file by id, the classes with their state, statics &
+//mirror. Each object is made, then its refs filled: a cycle needs the
+//objects first
+fn restore(s: &Snapshot, classes: &HashMap<String, ClassRef>) -> Vec<OopRef> {
+    let objects: Vec<OopRef> = s
+        .objects
+        .iter()
+        .map(|it| match it {
+            Head::Inst(class, _) => OopDesc::new_inst(classes[class].clone()),
+            Head::Array(class, _) => OopDesc::new_ref_ary2(classes[class].clone(), vec![]),
+            Head::Prims(v) => v.clone(),
+            Head::Mirror(_, vt, _) => OopDesc::new_prim_mirror(*vt),
+            Head::Locked => unreachable!(),
+        })
+        .collect();
+    let resolve_all = |v: &[Value]| v.iter().map(|it| resolve(it, &objects)).collect();
+    for (v, it) in objects.iter().zip(s.objects.iter()) {
+        match (&mut v.lock().unwrap().v, it) {
+            (Oop::Inst(inst), Head::Inst(_, v)) => inst.field_values = resolve_all(v),
+            (Oop::Array(ary), Head::Array(_, v)) => ary.elements = resolve_all(v),
+            (Oop::Mirror(mirror), Head::Mirror(target, _, v)) => {
+                mirror.target = target.as_ref().map(|it| classes[it].clone());
+                mirror.field_values = resolve_all(v);
+            }
+            _ => (),
+        }
+    }
+    for (id, hash) in s.hashes.iter() {
+        objects[*id].lock().unwrap().header.hash_code = Some(*hash);
+    }
+
+    for it in s.classes.iter() {
+        classes[&it.name].lock().unwrap().set_class_state(it.state);
+    }
+    for (class, name, v) in s.statics.iter() {
+        let desc = &static_field(s, class, name).unwrap().desc;
+        let mut cls = classes[class].lock().unwrap();
+        let id = cls.get_field_id(name.as_bytes(), desc.as_bytes(), true);
+        cls.put_static_field_value(id, resolve(v, &objects));
+    }
+    for (class, v) in s.mirrors.iter() {
+        let mirror = resolve(v, &objects);
+        classes[class].lock().unwrap().set_mirror(mirror);
+    }
+    objects
+}
+
+fn resolve(v: &Value, objects: &[OopRef]) -> OopRef {
+    match v {
+        Value::Null => consts::get_null(),
+        Value::Ref(id) => objects[*id].clone(),
+        Value::Int(v) => OopDesc::new_int(*v),
+        Value::Long(v) => OopDesc::new_long(*v),
+        Value::Float(v) => OopDesc::new_float(f32::from_bits(*v)),
+        Value::Double(v) => OopDesc::new_double(f64::from_bits(*v)),
+        Value::Utf8(v) => OopDesc::new_const_utf8(Arc::new(Box::new(v.clone()))),
+    }
+}
+
+//what the vm holds out of the heap, & what the boot makes that isn't in
+//the file
+fn restore_vm(jt: &mut JavaThread, s: &Snapshot, objects: &[OopRef]) {
+    for (key, v) in s.prims.iter() {
+        java_lang_Class::set_primitive_class_mirror(key, resolve(v, objects));
+    }
+    if let Some(class) = runtime::sys_dic_find(J_STRING) {
+        let cls = class.lock().unwrap();
+        let fir = cls.get_field_id(b"value", b"[C", false);
+        util::oop::set_java_lang_string_value_offset(fir.offset);
+    }
+    for v in s.interned.iter() {
+        let _ = java_lang_String::intern(resolve(v, objects));
+    }
+
+    if let Some(v) = &s.main {
+        let main = resolve(v, objects);
+        jt.set_java_thread_obj(main.clone());
+        thread::attach(&main, false);
+        thread::set_host(&main);
+        util::set_thread_name("main");
+    }
+    runtime::init_vm::hack_methods(jt);
+    exception::preallocate_errors(jt);
+
+    //started again from run(), where they were is lost
+    for v in s.threads.iter() {
+        let thread_oop = resolve(v, objects);
+        clear_running(&thread_oop);
+        let name = java_lang_Thread::guest_thread_name(&thread_oop).unwrap_or_default();
+        if let Err(e) = thread::start(thread_oop, name.clone()) {
+            warn!("restore thread {} failed: {}", name, e);
+        }
+    }
+}
+
+//FinalizerThread.run returns at once if running is set
+fn clear_running(thread_oop: &OopRef) {
+    let class = match &thread_oop.lock().unwrap().v {
+        Oop::Inst(inst) => inst.class.clone(),
+        _ => return,
+    };
+    let cls = class.lock().unwrap();
+    if cls.name.as_slice() == b"java/lang/ref/Finalizer$FinalizerThread" {
+        let id = cls.get_field_id(b"running", b"Z", false);
+        cls.put_field_value(thread_oop.clone(), id, OopDesc::new_int(0));
+    }
+}
+
+fn class_name(class: &ClassRef) -> String {
+    String::from_utf8_lossy(class.lock().unwrap().name.as_slice()).to_string()
+}
+
+//the elements of a primitive array, space separated, bytes as one hex
+//string
+fn elements(ary: &TypeArrayValue) -> String {
+    fn join<T: ToString>(v: impl Iterator<Item = T>) -> String {
+        v.map(|it| it.to_string()).collect::<Vec<_>>().join(" ")
+    }
+    match ary {
+        TypeArrayValue::Byte(v) => hex(v),
+        TypeArrayValue::Bool(v) => hex(v),
+        TypeArrayValue::Char(v) => join(v.iter()),
+        TypeArrayValue::Short(v) => join(v.iter()),
+        TypeArrayValue::Int(v) => join(v.iter()),
+        TypeArrayValue::Long(v) => join(v.iter()),
+        TypeArrayValue::Float(v) => join(v.iter().map(|it| format!("{:x}", it.to_bits()))),
+        TypeArrayValue::Double(v) => join(v.iter().map(|it| format!("{:x}", it.to_bits()))),
+    }
+}
+
+fn hex(v: &[u8]) -> String {
+    v.iter().map(|it| format!("{:02x}", it)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::access_flags::*;
+    use crate::oop::class::Class;
+    use crate::runtime::{host_class, test_class, ClassLoader};
+
+    #[test]
+    fn t_class_list() {
+        let names = vec![
+            "java/lang/String".to_string(),
+            "java/lang/Object".to_string(),
+        ];
+        assert_eq!(
+            super::class_list(names),
+            "java/lang/Object\njava/lang/String\n"
+        );
+    }
+
+    #[test]
+    fn t_graph() {
+        let cls = Class::new_prime_ary(ClassLoader::Bootstrap, ValueType::INT);
+        let cls = new_sync_ref!(cls);
+        let ints = OopDesc::new_int_ary2(vec![1, -2]);
+        let bytes = OopDesc::new_byte_ary2(vec![0xca, 0xfe]);
+        //a cycle, & an array reached twice
+        let a = OopDesc::new_ref_ary2(cls.clone(), vec![ints.clone(), OopDesc::new_int(7)]);
+        let b = OopDesc::new_ref_ary2(cls.clone(), vec![a.clone(), ints, bytes]);
+        if let Oop::Array(ary) = &mut a.lock().unwrap().v {
+            ary.elements.push(b.clone());
+        }
+
+        let mut g = Graph::default();
+        assert_eq!(g.value(&OopDesc::new_long(-1)), "J-1");
+        assert_eq!(g.value(&a), "@0");
+        assert_eq!(g.value(&a), "@0");
+        let mut buf = String::new();
+        g.drain(&mut buf);
+        assert_eq!(
+            buf,
+            "object 0 [I @1 I7 @2\n\
+             object 1 [I 1 -2\n\
+             object 2 [I @0 @1 @3\n\
+             object 3 [B cafe\n"
+        );
+
+        //break the cycle
+        let mut v = a.lock().unwrap();
+        if let Oop::Array(ary) = &mut v.v {
+            ary.elements.clear();
+        }
+    }
+
+    #[test]
+    fn t_restore() {
+        test_class::define_object();
+        let fields = [
+            ("next", "Ljava/lang/Object;", ACC_PUBLIC),
+            ("n", "I", ACC_PUBLIC),
+            ("d", "D", ACC_PUBLIC),
+            ("ROOT", "Ljava/lang/Object;", ACC_PUBLIC | ACC_STATIC),
+            ("ARY", "[I", ACC_PUBLIC | ACC_STATIC),
+        ];
+        let acc_flags = ACC_PUBLIC | ACC_SUPER;
+        let object = Some("java/lang/Object");
+        let cf = host_class::new_class_file("test/snap/Node", acc_flags, object, &[], vec![]);
+        let class = test_class::define_class(test_class::with_fields(cf, &fields));
+        let field = |name: &str, desc: &str, is_static| {
+            let cls = class.lock().unwrap();
+            cls.get_field_id(name.as_bytes(), desc.as_bytes(), is_static)
+        };
+        let next = field("next", "Ljava/lang/Object;", false);
+        let root = field("ROOT", "Ljava/lang/Object;", true);
+
+        //a cycle, boxed primitives, an identity hash
+        let a = OopDesc::new_inst(class.clone());
+        let b = OopDesc::new_inst(class.clone());
+        {
+            let mut cls = class.lock().unwrap();
+            cls.put_field_value(a.clone(), next.clone(), b.clone());
+            cls.put_field_value(b.clone(), next.clone(), a.clone());
+            cls.put_field_value(a.clone(), field_of(&cls, "n", "I"), OopDesc::new_int(1));
+            cls.put_field_value(
+                b.clone(),
+                field_of(&cls, "d", "D"),
+                OopDesc::new_double(-0.5),
+            );
+            cls.put_static_field_value(root.clone(), a.clone());
+            let ary = cls.get_field_id(b"ARY", b"[I", true);
+            cls.put_static_field_value(ary, OopDesc::new_int_ary2(vec![3, 4]));
+        }
+        a.lock().unwrap().header.hash_code = Some(42);
+        let roots = Roots {
+            main: Some(OopDesc::new_byte_ary2(vec![0xca, 0xfe])),
+            ..Roots::default()
+        };
+        let text = heap_snapshot(&[class.clone()], &roots);
+
+        //other objects, the same graph
+        let s = parse(&text).unwrap();
+        let classes = load_classes(&s).unwrap();
+        let objects = restore(&s, &classes);
+        let a2 = class.lock().unwrap().get_static_field_value(root);
+        assert!(!Arc::ptr_eq(&a, &a2));
+        assert_eq!(a2.lock().unwrap().header.hash_code, Some(42));
+        let roots = Roots {
+            main: s.main.as_ref().map(|it| resolve(it, &objects)),
+            ..Roots::default()
+        };
+        assert_eq!(heap_snapshot(&[class.clone()], &roots), text);
+
+        //a field differs, a duplicated id, a ref to no object
+        let other = parse(&text.replace(" d D", " d J")).unwrap();
+        assert!(load_classes(&other).is_err());
+        assert!(parse(&text.replace("object 1", "object 2")).is_err());
+        assert!(parse("main @0\n").is_err());
+
+        //break the cycles
+        for v in [&b, &objects[2]] {
+            let cls = class.lock().unwrap();
+            cls.put_field_value(v.clone(), next.clone(), consts::get_null());
+        }
+    }
+
+    fn field_of(cls: &Class, name: &str, desc: &str) -> crate::types::FieldIdRef {
+        cls.get_field_id(name.as_bytes(), desc.as_bytes(), false)
+    }
+}
@@ -1,275 +1,371 @@
 use crate::classfile::constant_pool::ConstantType;
 use crate::classfile::method_info::MethodInfo;
 use crate::classfile::ClassFile;
-use crate::oop::{consts, OopDesc};
-use crate::runtime::Slot;
+use crate::oop::{self, consts, OopDesc};
+use crate::runtime::{ReturnAddress, Slot};
 use crate::types::*;
 use std::sync::Arc;
 
+/*
+Operand stack, untagged 64-bit slots
+
+int, float: one slot, the value is in the low 32 bits (int sign extended)
+long, double: two slots, a padding slot (0) below the value
+reference: one slot, the OopRef is kept in the reference map at the same
+  index, the slot itself holds 0
+returnAddress: one slot, the bci, like an int
+
+Primitives are plain u64, pushing an intermediate allocates nothing.
+*/
 #[derive(Debug)]
 pub struct Stack {
-    inner: Vec<Slot>,
+    slots: Vec<u64>,
+    //reference map, Some at the index of a reference slot
+    refs: Vec<Option<OopRef>>,
+    max_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackErr {
+    Underflow { expected: usize, actual: usize },
+    Overflow { max: usize, actual: usize },
 }
 
 impl Stack {
     pub fn new(size: usize) -> Self {
         Self {
-            inner: Vec::with_capacity(size),
+            slots: Vec::with_capacity(size),
+            refs: Vec::with_capacity(size),
+            max_size: size,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    //bottom first, for inspection
+    pub fn slots(&self) -> Vec<Slot> {
+        self.slots
+            .iter()
+            .zip(self.refs.iter())
+            .map(|(v, r)| match r {
+                Some(r) => Slot::Ref(r.clone()),
+                None => Slot::Primitive(v.to_be_bytes().to_vec()),
+            })
+            .collect()
+    }
+
+    //the stack must hold at least n slots, before popping them
+    pub fn check_underflow(&self, n: usize) -> Result<(), StackErr> {
+        let actual = self.slots.len();
+        if actual < n {
+            Err(StackErr::Underflow {
+                expected: n,
+                actual,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    //the stack depth must not exceed max_stack of the Code attribute
+    pub fn check_overflow(&self) -> Result<(), StackErr> {
+        let actual = self.slots.len();
+        if actual > self.max_size {
+            Err(StackErr::Overflow {
+                max: self.max_size,
+                actual,
+            })
+        } else {
+            Ok(())
         }
     }
 
     pub fn push_int(&mut self, i: i32) {
-        let v = i.to_be_bytes();
-        self.push_primitive2(v);
+        self.push_raw(i as i64 as u64);
     }
 
     pub fn push_int2(&mut self, v: [u8; 4]) {
-        self.push_primitive2(v)
+        self.push_int(i32::from_be_bytes(v));
     }
 
     pub fn push_float(&mut self, f: f32) {
-        let v = f.to_bits().to_be_bytes();
-        self.push_primitive2(v);
+        self.push_raw(f.to_bits() as u64);
     }
 
     pub fn push_float2(&mut self, v: [u8; 4]) {
-        self.push_primitive2(v);
+        self.push_raw(u32::from_be_bytes(v) as u64);
     }
 
     pub fn push_double(&mut self, d: f64) {
-        let v = d.to_bits().to_be_bytes();
-        self.push_double2(v);
+        self.push_raw2(d.to_bits());
     }
 
     pub fn push_double2(&mut self, v: [u8; 8]) {
-        self.push_nop();
-        self.push_primitive3(v);
+        self.push_raw2(u64::from_be_bytes(v));
     }
 
     pub fn push_long(&mut self, l: i64) {
-        let v = l.to_be_bytes();
-        self.push_long2(v);
+        self.push_raw2(l as u64);
     }
 
     pub fn push_long2(&mut self, v: [u8; 8]) {
-        self.push_nop();
-        self.push_primitive3(v);
+        self.push_raw2(u64::from_be_bytes(v));
     }
 
     pub fn push_null(&mut self) {
-        self.inner.push(Slot::Ref(consts::get_null()));
+        self.push_slot(0, Some(consts::get_null()));
     }
 
     pub fn push_const_m1(&mut self) {
-        self.inner.push(Slot::ConstM1);
+        self.push_int(-1);
     }
 
     /*
+    int, float, long & double 0 are all bits 0
     double & long, with_nop = true
     */
     pub fn push_const0(&mut self, with_nop: bool) {
         if with_nop {
-            self.push_nop();
+            self.push_raw2(0);
+        } else {
+            self.push_raw(0);
         }
-        self.inner.push(Slot::Const0);
     }
 
     /*
-       double & long, with_nop = true
+    int & long only, float & double use push_float & push_double
+    long, with_nop = true
     */
     pub fn push_const1(&mut self, with_nop: bool) {
         if with_nop {
-            self.push_nop();
+            self.push_raw2(1);
+        } else {
+            self.push_raw(1);
         }
-        self.inner.push(Slot::Const1);
     }
 
     pub fn push_const2(&mut self) {
-        self.inner.push(Slot::Const2);
+        self.push_int(2);
     }
 
     pub fn push_const3(&mut self) {
-        self.inner.push(Slot::Const3);
+        self.push_int(3);
     }
 
     pub fn push_const4(&mut self) {
-        self.inner.push(Slot::Const4);
+        self.push_int(4);
     }
 
     pub fn push_const5(&mut self) {
-        self.inner.push(Slot::Const5);
+        self.push_int(5);
     }
 
     pub fn push_ref(&mut self, v: OopRef) {
-        self.inner.push(Slot::Ref(v));
+        vm_assert!(oop::is_valid_ref(&v), "push_ref: not a reference");
+        self.push_slot(0, Some(v));
+    }
+
+    pub fn push_return_address(&mut self, v: ReturnAddress) {
+        self.push_raw(v.0 as i64 as u64);
+    }
+
+    pub fn pop_return_address(&mut self) -> ReturnAddress {
+        ReturnAddress(self.pop_raw() as i32)
+    }
+
+    //astore takes a reference or a returnAddress
+    pub fn is_ref_on_top(&self) -> bool {
+        matches!(self.refs.last(), Some(Some(_)))
     }
 
     pub fn pop_int(&mut self) -> i32 {
-        match self.inner.pop().unwrap() {
-            Slot::ConstM1 => -1,
-            Slot::Const0 => 0,
-            Slot::Const1 => 1,
-            Slot::Const2 => 2,
-            Slot::Const3 => 3,
-            Slot::Const4 => 4,
-            Slot::Const5 => 5,
-            Slot::Primitive(v) => i32::from_be_bytes([v[0], v[1], v[2], v[3]]),
-            _ => panic!("Illegal type"),
-        }
+        self.pop_raw() as i32
     }
 
     pub fn pop_float(&mut self) -> f32 {
-        match self.inner.pop().unwrap() {
-            Slot::Const0 => 0.0,
-            Slot::Const1 => 1.0,
-            Slot::Const2 => 2.0,
-            Slot::Primitive(v) => {
-                let v = u32::from_be_bytes([v[0], v[1], v[2], v[3]]);
-                f32::from_bits(v)
-            }
-            _ => panic!("Illegal type"),
-        }
+        f32::from_bits(self.pop_raw() as u32)
     }
 
     pub fn pop_double(&mut self) -> f64 {
-        match self.inner.pop() {
-            Some(v) => {
-                self.pop_nop();
-                match v {
-                    Slot::Const0 => 0.0,
-                    Slot::Const1 => 1.0,
-                    Slot::Primitive(v) => {
-                        let v =
-                            u64::from_be_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]);
-                        f64::from_bits(v)
-                    }
-                    _ => panic!("Illegal type"),
-                }
-            }
-            None => panic!("Empty Stack!"),
-        }
+        f64::from_bits(self.pop_raw2())
     }
 
     pub fn pop_long(&mut self) -> i64 {
-        match self.inner.pop() {
-            Some(v) => {
-                self.pop_nop();
-                match v {
-                    Slot::Const0 => 0,
-                    Slot::Const1 => 1,
-                    Slot::Primitive(v) => {
-                        i64::from_be_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]])
-                    }
-                    _ => panic!("Illegal type"),
-                }
-            }
-            _ => panic!("Empty Stack!"),
-        }
+        self.pop_raw2() as i64
     }
 
     pub fn pop_ref(&mut self) -> OopRef {
-        match self.inner.pop() {
-            Some(Slot::Ref(v)) => v,
-            t => panic!("Illegal type = {:?}", t),
+        match self.pop_slot() {
+            (_, Some(v)) => v,
+            (v, None) => panic!("Illegal type = {}, expect reference", v),
         }
     }
 
     pub fn drop_top(&mut self) {
-        let _ = self.inner.pop();
+        if !self.slots.is_empty() {
+            let _ = self.pop_slot();
+        }
     }
 
     pub fn clear(&mut self) {
-        self.inner.clear();
+        self.slots.clear();
+        self.refs.clear();
     }
 
     pub fn dup(&mut self) {
-        let v = self.inner.pop().unwrap();
-        self.inner.push(v.clone());
-        self.inner.push(v.clone());
+        let v = self.pop_slot();
+        self.push_slot(v.0, v.1.clone());
+        self.push_slot(v.0, v.1);
     }
 
     pub fn dup_x1(&mut self) {
-        let v1 = self.inner.pop().unwrap();
-        let v2 = self.inner.pop().unwrap();
-        self.inner.push(v1.clone());
-        self.inner.push(v2);
-        self.inner.push(v1);
+        let v1 = self.pop_slot();
+        let v2 = self.pop_slot();
+        self.push_slot(v1.0, v1.1.clone());
+        self.push_slot(v2.0, v2.1);
+        self.push_slot(v1.0, v1.1);
     }
 
     pub fn dup_x2(&mut self) {
-        let v1 = self.inner.pop().unwrap();
-        let v2 = self.inner.pop().unwrap();
-        let v3 = self.inner.pop().unwrap();
-        self.inner.push(v1.clone());
-        self.inner.push(v3);
-        self.inner.push(v2);
-        self.inner.push(v1);
+        let v1 = self.pop_slot();
+        let v2 = self.pop_slot();
+        let v3 = self.pop_slot();
+        self.push_slot(v1.0, v1.1.clone());
+        self.push_slot(v3.0, v3.1);
+        self.push_slot(v2.0, v2.1);
+        self.push_slot(v1.0, v1.1);
     }
 
     pub fn dup2(&mut self) {
-        let v1 = self.inner.pop().unwrap();
-        let v2 = self.inner.pop().unwrap();
-        self.inner.push(v2.clone());
-        self.inner.push(v1.clone());
-        self.inner.push(v2);
-        self.inner.push(v1);
+        let v1 = self.pop_slot();
+        let v2 = self.pop_slot();
+        self.push_slot(v2.0, v2.1.clone());
+        self.push_slot(v1.0, v1.1.clone());
+        self.push_slot(v2.0, v2.1);
+        self.push_slot(v1.0, v1.1);
     }
 
     pub fn dup2_x1(&mut self) {
-        let v1 = self.inner.pop().unwrap();
-        let v2 = self.inner.pop().unwrap();
-        let v3 = self.inner.pop().unwrap();
-        self.inner.push(v2.clone());
-        self.inner.push(v1.clone());
-        self.inner.push(v3);
-        self.inner.push(v2);
-        self.inner.push(v1);
+        let v1 = self.pop_slot();
+        let v2 = self.pop_slot();
+        let v3 = self.pop_slot();
+        self.push_slot(v2.0, v2.1.clone());
+        self.push_slot(v1.0, v1.1.clone());
+        self.push_slot(v3.0, v3.1);
+        self.push_slot(v2.0, v2.1);
+        self.push_slot(v1.0, v1.1);
     }
 
     pub fn dup2_x2(&mut self) {
-        let v1 = self.inner.pop().unwrap();
-        let v2 = self.inner.pop().unwrap();
-        let v3 = self.inner.pop().unwrap();
-        let v4 = self.inner.pop().unwrap();
-        self.inner.push(v2.clone());
-        self.inner.push(v1.clone());
-        self.inner.push(v4);
-        self.inner.push(v3);
-        self.inner.push(v2);
-        self.inner.push(v1);
+        let v1 = self.pop_slot();
+        let v2 = self.pop_slot();
+        let v3 = self.pop_slot();
+        let v4 = self.pop_slot();
+        self.push_slot(v2.0, v2.1.clone());
+        self.push_slot(v1.0, v1.1.clone());
+        self.push_slot(v4.0, v4.1);
+        self.push_slot(v3.0, v3.1);
+        self.push_slot(v2.0, v2.1);
+        self.push_slot(v1.0, v1.1);
     }
 
     pub fn swap(&mut self) {
-        let v1 = self.inner.pop().unwrap();
-        let v2 = self.inner.pop().unwrap();
-        self.inner.push(v1);
-        self.inner.push(v2);
+        let v1 = self.pop_slot();
+        let v2 = self.pop_slot();
+        self.push_slot(v1.0, v1.1);
+        self.push_slot(v2.0, v2.1);
     }
 }
 
 impl Stack {
-    fn push_primitive(&mut self, b: Vec<u8>) {
-        self.inner.push(Slot::Primitive(b));
+    fn push_slot(&mut self, v: u64, r: Option<OopRef>) {
+        self.slots.push(v);
+        self.refs.push(r);
     }
 
-    fn push_primitive2(&mut self, v: [u8; 4]) {
-        let v = vec![v[0], v[1], v[2], v[3]];
-        self.push_primitive(v);
+    fn pop_slot(&mut self) -> (u64, Option<OopRef>) {
+        match (self.slots.pop(), self.refs.pop()) {
+            (Some(v), Some(r)) => (v, r),
+            _ => panic!("Empty Stack!"),
+        }
     }
 
-    fn push_primitive3(&mut self, v: [u8; 8]) {
-        let v = vec![v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]];
-        self.push_primitive(v);
+    fn push_raw(&mut self, v: u64) {
+        self.push_slot(v, None);
     }
 
-    fn push_nop(&mut self) {
-        self.inner.push(Slot::Nop);
+    //category 2, padding slot below the value
+    fn push_raw2(&mut self, v: u64) {
+        self.push_raw(0);
+        self.push_raw(v);
     }
 
-    fn pop_nop(&mut self) {
-        match self.inner.pop() {
-            Some(Slot::Nop) => (),
-            _ => panic!("Should be Nop!"),
+    fn pop_raw(&mut self) -> u64 {
+        match self.pop_slot() {
+            (v, None) => v,
+            (_, Some(_)) => panic!("Illegal type, expect primitive"),
         }
     }
+
+    fn pop_raw2(&mut self) -> u64 {
+        let v = self.pop_raw();
+        let _ = self.pop_raw();
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_primitives() {
+        let mut stack = Stack::new(8);
+        stack.push_int(-7);
+        stack.push_float(1.5);
+        stack.push_long(i64::MIN);
+        stack.push_double(-2.25);
+        assert_eq!(stack.len(), 6);
+
+        assert_eq!(stack.pop_double(), -2.25);
+        assert_eq!(stack.pop_long(), i64::MIN);
+        assert_eq!(stack.pop_float(), 1.5);
+        assert_eq!(stack.pop_int(), -7);
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn t_return_address() {
+        let mut stack = Stack::new(2);
+        stack.push_null();
+        stack.push_return_address(ReturnAddress(12));
+        assert!(!stack.is_ref_on_top());
+        assert_eq!(stack.pop_return_address(), ReturnAddress(12));
+        assert!(stack.is_ref_on_top());
+    }
+
+    #[test]
+    fn t_dup2_category2() {
+        let mut stack = Stack::new(4);
+        stack.push_const1(true);
+        stack.dup2();
+        assert_eq!(stack.pop_long(), 1);
+        assert_eq!(stack.pop_long(), 1);
+
+        stack.push_null();
+        stack.push_const5();
+        stack.swap();
+        let _ = stack.pop_ref();
+        assert_eq!(stack.pop_int(), 5);
+    }
 }
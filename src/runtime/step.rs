@@ -0,0 +1,171 @@
+/*
+Single-step mode, -XX:+SingleStep
+
+The interpreter pauses before each instruction and hands a StepContext
+(method, bci, opcode, operand stack & locals) to the step hook. The hook
+decides to step to the next instruction or to continue.
+
+Embedders install their own hook with set_hook, otherwise a simple REPL
+on stdin is used:
+  s, <enter>   step
+  c            continue, stop stepping
+  p            print operand stack & locals
+  q            quit the vm
+*/
+
+use crate::classfile::opcode::OpCode;
+use crate::oop::Oop;
+use crate::runtime::{flags, Slot};
+use crate::util;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+pub struct StepContext<'a> {
+    pub class: &'a str,
+    pub method: &'a str,
+    pub bci: i32,
+    pub(crate) op_code: OpCode,
+    pub(crate) stack: &'a [Slot],
+    pub(crate) locals: &'a [Slot],
+}
+
+impl StepContext<'_> {
+    //"iload_0"
+    pub fn op_code(&self) -> String {
+        format!("{:?}", self.op_code)
+    }
+
+    //"[1, null, <java/lang/String>]", as p prints them
+    pub fn stack(&self) -> String {
+        fmt_slots(self.stack)
+    }
+
+    pub fn locals(&self) -> String {
+        fmt_slots(self.locals)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepAction {
+    Step,
+    Continue,
+}
+
+pub type StepHook = Box<dyn FnMut(&StepContext) -> StepAction + Send>;
+
+//cleared by StepAction::Continue
+static STEPPING: AtomicBool = AtomicBool::new(true);
+
+lazy_static! {
+    static ref HOOK: Mutex<Option<StepHook>> = Mutex::new(None);
+}
+
+pub fn set_hook(hook: StepHook) {
+    util::sync_call_ctx(&HOOK, |h| *h = Some(hook));
+}
+
+pub fn is_stepping() -> bool {
+    flags::is_single_step() && STEPPING.load(Ordering::Relaxed)
+}
+
+pub fn on_instruction(ctx: &StepContext) {
+    let action = util::sync_call_ctx(&HOOK, |h| match h {
+        Some(hook) => hook(ctx),
+        None => repl(ctx),
+    });
+
+    if action == StepAction::Continue {
+        STEPPING.store(false, Ordering::Relaxed);
+    }
+}
+
+fn repl(ctx: &StepContext) -> StepAction {
+    println!(
+        "[step] {}.{} bci={} {:?}",
+        ctx.class, ctx.method, ctx.bci, ctx.op_code
+    );
+
+    let stdin = io::stdin();
+    loop {
+        print!("(step) ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            //stdin closed
+            Ok(0) | Err(_) => return StepAction::Continue,
+            Ok(_) => (),
+        }
+
+        match line.trim() {
+            "" | "s" => return StepAction::Step,
+            "c" => return StepAction::Continue,
+            "p" => {
+                println!("stack:  {}", fmt_slots(ctx.stack));
+                println!("locals: {}", fmt_slots(ctx.locals));
+            }
+            "q" => std::process::exit(0),
+            cmd => println!("unknown command '{}', use s, c, p or q", cmd),
+        }
+    }
+}
+
+pub fn fmt_slots(slots: &[Slot]) -> String {
+    let slots: Vec<String> = slots.iter().map(fmt_slot).collect();
+    format!("[{}]", slots.join(", "))
+}
+
+fn fmt_slot(slot: &Slot) -> String {
+    match slot {
+        Slot::ConstM1 => "-1".to_string(),
+        Slot::Const0 => "0".to_string(),
+        Slot::Const1 => "1".to_string(),
+        Slot::Const2 => "2".to_string(),
+        Slot::Const3 => "3".to_string(),
+        Slot::Const4 => "4".to_string(),
+        Slot::Const5 => "5".to_string(),
+        Slot::Primitive(v) if v.len() == 4 => {
+            let v = i32::from_be_bytes([v[0], v[1], v[2], v[3]]);
+            format!("{}", v)
+        }
+        Slot::Primitive(v) if v.len() == 8 => {
+            let mut b = [0u8; 8];
+            b.copy_from_slice(v);
+            format!("{}", i64::from_be_bytes(b))
+        }
+        Slot::Primitive(v) => format!("{:02x?}", v),
+        Slot::Ref(v) => match v.try_lock() {
+            Ok(v) => match &v.v {
+                Oop::Null => "null".to_string(),
+                Oop::Inst(inst) => match inst.class.try_lock() {
+                    Ok(cls) => format!("<{}>", String::from_utf8_lossy(cls.name.as_slice())),
+                    Err(_) => "<object>".to_string(),
+                },
+                Oop::Array(ary) => format!("<array, len={}>", ary.elements.len()),
+                Oop::TypeArray(_) => "<primitive array>".to_string(),
+                Oop::Mirror(_) => "<class>".to_string(),
+                t => format!("{:?}", t),
+            },
+            Err(_) => "<locked>".to_string(),
+        },
+        Slot::Nop => "_".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oop::consts as oop_consts;
+
+    #[test]
+    fn t_fmt_slots() {
+        let slots = vec![
+            Slot::ConstM1,
+            Slot::Primitive(7i32.to_be_bytes().to_vec()),
+            Slot::Ref(oop_consts::get_null()),
+            Slot::Nop,
+        ];
+        assert_eq!(fmt_slots(slots.as_slice()), "[-1, 7, null, _]");
+    }
+}
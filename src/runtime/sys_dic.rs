@@ -15,7 +15,7 @@ lazy_static! {
 pub fn put(key: &[u8], klass: ClassRef) {
     assert!(!key.contains(&b'.'));
     util::sync_call_ctx(&SYS_DIC, |dic| {
-        let key = String::from_utf8_lossy(key);
+        let key = util::mutf8::display(key);
         dic.insert(key.to_string(), klass);
     })
 }
@@ -23,8 +23,13 @@ pub fn put(key: &[u8], klass: ClassRef) {
 //key style: "sun/security/provider/Sun"
 pub fn find(key: &[u8]) -> Option<ClassRef> {
     assert!(!key.contains(&b'.'));
-    let key = unsafe { std::str::from_utf8_unchecked(key) };
-    util::sync_call(&SYS_DIC, |dic| dic.get(key).map(|it| it.clone()))
+    let key = util::mutf8::display(key);
+    util::sync_call(&SYS_DIC, |dic| dic.get(key.as_ref()).cloned())
+}
+
+//loaded class names, for diagnostics
+pub fn names() -> Vec<String> {
+    util::sync_call(&SYS_DIC, |dic| dic.keys().cloned().collect())
 }
 
 pub fn init() {
@@ -0,0 +1,95 @@
+/*
+Object tags, like JVMTI SetTag/GetTag, for embedders & tools
+
+A tag is a u64 attached to a guest object, 0 means untagged. Host tools
+keep their own data keyed by tag (object maps, allocation sites...).
+
+Tags are kept in a side table, not in the object, keyed by the object
+address. Objects never move, the table holds a Weak so the address
+can't be reused while the entry exists. Entries of freed objects are
+dropped by purge, which the collector calls after each collection.
+*/
+
+use crate::oop::OopDesc;
+use crate::types::OopRef;
+use crate::util;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+//object address -> (object, tag)
+type TagTable = HashMap<usize, (Weak<Mutex<Box<OopDesc>>>, u64)>;
+
+lazy_static! {
+    static ref TAGS: Mutex<TagTable> = Mutex::new(HashMap::new());
+}
+
+fn key(v: &OopRef) -> usize {
+    Arc::as_ptr(v) as usize
+}
+
+//tag 0 removes the tag
+pub fn set_tag(v: &OopRef, tag: u64) {
+    util::sync_call_ctx(&TAGS, |tags| {
+        if tag == 0 {
+            tags.remove(&key(v));
+        } else {
+            tags.insert(key(v), (Arc::downgrade(v), tag));
+        }
+    })
+}
+
+pub fn get_tag(v: &OopRef) -> u64 {
+    util::sync_call_ctx(&TAGS, |tags| tags.get(&key(v)).map_or(0, |it| it.1))
+}
+
+//live objects tagged with one of tags
+pub fn objects_with_tags(tags: &[u64]) -> Vec<(OopRef, u64)> {
+    util::sync_call_ctx(&TAGS, |table| {
+        table
+            .values()
+            .filter(|(_, tag)| tags.contains(tag))
+            .filter_map(|(v, tag)| v.upgrade().map(|v| (v, *tag)))
+            .collect()
+    })
+}
+
+//drop the entries of freed objects, returns their tags
+pub fn purge() -> Vec<u64> {
+    util::sync_call_ctx(&TAGS, |tags| {
+        let mut freed = Vec::new();
+        tags.retain(|_, (v, tag)| {
+            let live = v.strong_count() > 0;
+            if !live {
+                freed.push(*tag);
+            }
+            live
+        });
+        freed
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_tags() {
+        let a = OopDesc::new_int(1);
+        let b = OopDesc::new_int(2);
+        set_tag(&a, 7);
+        set_tag(&b, 8);
+        assert_eq!(get_tag(&a), 7);
+
+        let found = objects_with_tags(&[7]);
+        assert_eq!(found.len(), 1);
+        assert!(Arc::ptr_eq(&found[0].0, &a));
+        drop(found);
+
+        set_tag(&b, 0);
+        assert_eq!(get_tag(&b), 0);
+
+        drop(a);
+        assert!(purge().contains(&7));
+        assert!(objects_with_tags(&[7]).is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+/*
+Classes of the unit tests, built in memory by host_class's builder
+
+No rt.jar in a test: a test defines the classes it needs in the system
+dictionary, java/lang/Object by define_object, once for every test of
+the binary.
+*/
+
+use crate::classfile::access_flags::*;
+use crate::classfile::attr_info::{AttrType, Code};
+use crate::classfile::field_info::FieldInfo;
+use crate::classfile::ClassFile;
+use crate::oop::Class;
+use crate::runtime;
+use crate::runtime::host_class::{self, MethodDef};
+use crate::types::*;
+use std::sync::{Arc, Once};
+
+//a Code attribute, 8 stack slots & 8 locals
+pub fn code(code: &[u8]) -> Vec<AttrType> {
+    let code = Code {
+        max_stack: 8,
+        max_locals: 8,
+        code: Arc::new(code.to_vec()),
+        exceptions: vec![],
+        attrs: vec![],
+    };
+    vec![AttrType::Code(code)]
+}
+
+//(name, desc, acc_flags) without code
+pub fn methods<'a>(v: &[(&'a str, &'a str, U2)]) -> Vec<MethodDef<'a>> {
+    v.iter()
+        .map(|(name, desc, acc_flags)| (*name, *desc, *acc_flags, vec![]))
+        .collect()
+}
+
+//cf with the fields (name, desc, acc_flags) added
+pub fn with_fields(mut cf: ClassFile, v: &[(&str, &str, U2)]) -> ClassFile {
+    let mut cp = Vec::clone(&cf.cp);
+    for (name, desc, acc_flags) in v {
+        let field = FieldInfo {
+            acc_flags: *acc_flags,
+            name_index: host_class::push_utf8(&mut cp, name),
+            desc_index: host_class::push_utf8(&mut cp, desc),
+            attrs: vec![],
+        };
+        cf.fields.push(field);
+    }
+    cf.fields_count = cf.fields.len() as U2;
+    cf.cp_count = cp.len() as U2;
+    cf.cp = new_ref!(cp);
+    cf
+}
+
+//cf linked & put in the dictionary, no mirror. Its super class &
+//interfaces must be defined first
+pub fn define_class(cf: ClassFile) -> ClassRef {
+    let cf = new_ref!(cf);
+    let class = Class::new_class(cf, None);
+    let name = class.name.clone();
+    let class = new_sync_ref!(class);
+    class.lock().unwrap().link_class(class.clone());
+    runtime::sys_dic_put(name.as_slice(), class.clone());
+    class
+}
+
+//a class of methods (name, desc, acc_flags) without code
+pub fn define(
+    name: &str,
+    acc_flags: U2,
+    super_name: Option<&str>,
+    itfs: &[&str],
+    v: &[(&str, &str, U2)],
+) -> ClassRef {
+    let cf = host_class::new_class_file(name, acc_flags, super_name, itfs, methods(v));
+    define_class(cf)
+}
+
+pub fn define_object() {
+    static OBJECT: Once = Once::new();
+    OBJECT.call_once(|| {
+        let methods = [("hashCode", "()I", ACC_PUBLIC | ACC_NATIVE)];
+        define(
+            "java/lang/Object",
+            ACC_PUBLIC | ACC_SUPER,
+            None,
+            &[],
+            &methods,
+        );
+    });
+}
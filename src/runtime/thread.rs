@@ -1,12 +1,485 @@
 use crate::classfile::attr_info::AttrType::Exceptions;
 use crate::classfile::{self, signature};
 use crate::oop::{self, consts, InstOopDesc, Oop, OopDesc};
-use crate::runtime::{self, init_vm, require_class3, FrameRef, JavaCall, Local, Stack};
+use crate::runtime::events::{self, EventKind};
+use crate::runtime::handles::LocalRefs;
+use crate::runtime::park::{self, Parker};
+use crate::runtime::{self, init_vm, require_class3, Arena, FrameRef, JavaCall, Local, Stack};
 use crate::types::{ClassRef, MethodIdRef, OopRef};
 use crate::util;
 use crate::util::{new_field_id, new_method_id};
 use std::borrow::BorrowMut;
-use std::sync::{Arc, Mutex};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+
+//live threads, for diagnostics
+pub struct ThreadStat {
+    pub id: usize,
+    //java frames on the thread
+    pub depth: AtomicUsize,
+    //THREAD_STATUS_*, see set_state
+    pub state: AtomicI32,
+    //nested safe regions, see runtime::safepoint
+    pub safe: AtomicUsize,
+}
+
+static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(1);
+
+lazy_static! {
+    static ref THREADS: Mutex<Vec<Weak<ThreadStat>>> = Mutex::new(Vec::new());
+}
+
+//stopped for a safepoint: out of java code, or blocked in a safe region.
+//Called under the safepoint lock, before THREADS
+pub(crate) fn others_stopped() -> bool {
+    let me = current_stat();
+    util::sync_call_ctx(&THREADS, |threads| {
+        threads.retain(|it| it.strong_count() > 0);
+        threads
+            .iter()
+            .filter_map(|it| it.upgrade())
+            .filter(|it| !me.as_ref().is_some_and(|me| Arc::ptr_eq(me, it)))
+            .all(|it| it.depth.load(Ordering::SeqCst) == 0 || it.safe.load(Ordering::SeqCst) > 0)
+    })
+}
+
+//(id, depth, state) of the live threads
+pub fn thread_stats() -> Vec<(usize, usize, i32)> {
+    util::sync_call_ctx(&THREADS, |threads| {
+        threads.retain(|it| it.strong_count() > 0);
+        threads
+            .iter()
+            .filter_map(|it| it.upgrade())
+            .map(|it| {
+                let depth = it.depth.load(Ordering::Relaxed);
+                (it.id, depth, it.state.load(Ordering::Relaxed))
+            })
+            .collect()
+    })
+}
+
+/*
+Thread states
+
+java.lang.Thread.threadStatus holds JVMTI state bits as in HotSpot,
+Thread.getState maps them (sun.misc.VM.toThreadState):
+  NEW            0, not started
+  RUNNABLE       running java or native code
+  BLOCKED        a contended monitorenter or synchronized method
+  WAITING        Object.wait(), LockSupport.park()
+  TIMED_WAITING  Thread.sleep, wait(ms), parkNanos & parkUntil
+  TERMINATED     run() returned
+
+The blocking calls (monitor, park) switch the state of the current
+thread, which is kept in a thread local, for the field & ThreadStat. A
+host thread with no JavaThread has no state. A waiter re-acquiring the
+monitor stays WAITING.
+*/
+pub const THREAD_STATUS_NEW: i32 = 0;
+pub const THREAD_STATUS_RUNNABLE: i32 = 0x0005;
+pub const THREAD_STATUS_SLEEPING: i32 = 0x00e1;
+pub const THREAD_STATUS_IN_OBJECT_WAIT: i32 = 0x0191;
+pub const THREAD_STATUS_IN_OBJECT_WAIT_TIMED: i32 = 0x01a1;
+pub const THREAD_STATUS_PARKED: i32 = 0x0291;
+pub const THREAD_STATUS_PARKED_TIMED: i32 = 0x02a1;
+pub const THREAD_STATUS_BLOCKED_ON_MONITOR_ENTER: i32 = 0x0401;
+pub const THREAD_STATUS_TERMINATED: i32 = 0x0002;
+
+#[derive(Default)]
+struct Current {
+    stat: Option<Arc<ThreadStat>>,
+    thread_oop: Option<OopRef>,
+}
+
+thread_local! {
+    //the JavaThread running on this host thread
+    static CURRENT: RefCell<Current> = RefCell::new(Current::default());
+}
+
+//the Thread.State name of a threadStatus
+pub fn state_name(status: i32) -> &'static str {
+    if status & 0x0004 != 0 {
+        "RUNNABLE"
+    } else if status & 0x0400 != 0 {
+        "BLOCKED"
+    } else if status & 0x0010 != 0 {
+        "WAITING"
+    } else if status & 0x0020 != 0 {
+        "TIMED_WAITING"
+    } else if status & 0x0002 != 0 {
+        "TERMINATED"
+    } else {
+        "NEW"
+    }
+}
+
+pub(crate) fn current_stat() -> Option<Arc<ThreadStat>> {
+    CURRENT.with(|it| it.borrow().stat.clone())
+}
+
+//the state of the current thread
+pub fn set_state(status: i32) {
+    let (stat, thread_oop) = CURRENT.with(|it| {
+        let it = it.borrow();
+        (it.stat.clone(), it.thread_oop.clone())
+    });
+    if let Some(stat) = stat {
+        stat.state.store(status, Ordering::Relaxed);
+    }
+    if let Some(thread_oop) = thread_oop {
+        set_thread_status(&thread_oop, status);
+    }
+}
+
+//f blocks in status, RUNNABLE again after. f runs in a safe region, a
+//collection doesn't wait for it
+pub fn in_state<F, R>(status: i32, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    set_state(status);
+    let r = runtime::safepoint::safe_region(f);
+    set_state(THREAD_STATUS_RUNNABLE);
+    r
+}
+
+/*
+Spin hints
+
+A busy-wait loop, Thread.onSpinWait of the guest or a spin of the vm,
+waits for another host thread. Guest threads are host threads, the one
+waited for may not be scheduled while this one spins: spin_wait gives the
+cpu's spin loop hint (pause on x86), & every SPINS_PER_YIELD calls the
+host cpu to another thread.
+*/
+const SPINS_PER_YIELD: u32 = 64;
+
+thread_local! {
+    static SPINS: Cell<u32> = const { Cell::new(0) };
+}
+
+pub fn spin_wait() {
+    std::hint::spin_loop();
+    let n = SPINS.with(|it| {
+        let n = it.get().wrapping_add(1);
+        it.set(n);
+        n
+    });
+    if n.is_multiple_of(SPINS_PER_YIELD) {
+        std::thread::yield_now();
+    }
+}
+
+/*
+Host stack guard
+
+Java calls recurse on the host stack (invoke -> interp -> invoke), deep
+recursion would overflow it and abort the process. A host thread running
+java code calls set_stack_size first, JavaCall::invoke then throws
+StackOverflowError once less than STACK_RESERVE bytes are left, the
+reserve is for unwinding, handler search & the catch blocks.
+
+Each guest thread (Thread.start0) runs on a host thread of its own, with
+its own guard.
+*/
+const STACK_RESERVE: usize = 256 << 10;
+
+thread_local! {
+    //lowest stack address java calls may reach, 0 is unchecked
+    static STACK_LIMIT: Cell<usize> = const { Cell::new(0) };
+}
+
+//size of the host stack, from here
+pub fn set_stack_size(size: usize) {
+    let limit = stack_pointer().saturating_sub(size.saturating_sub(STACK_RESERVE));
+    STACK_LIMIT.with(|it| it.set(limit));
+}
+
+pub fn is_stack_overflow() -> bool {
+    let limit = STACK_LIMIT.with(|it| it.get());
+    limit != 0 && stack_pointer() < limit
+}
+
+//the stack grows down on the supported targets
+#[inline(never)]
+fn stack_pointer() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/*
+Guest threads, java.lang.Thread
+
+Thread.start0 spawns a host thread (-Xss stack, named after the Thread)
+with a JavaThread of its own, which runs Thread.run(). A running Thread
+is attached to the registry, keyed by its oop, until run() returns; the
+main thread is attached by init_vm. isAlive() is being attached. The vm
+exits after main once the non-daemon threads ended, daemon threads are
+left running.
+
+An uncaught exception goes to Thread.dispatchUncaughtException, then
+Thread.exit() cleans up. On detach threadStatus becomes TERMINATED and
+the joiners are woken: Thread.join is wait() on the Thread while
+isAlive(), as in the JDK.
+*/
+
+//how main ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunResult {
+    //main returned
+    Normal,
+    //System.exit or Runtime.halt, the status
+    Exit(i32),
+    //uncaught exception, its stack trace as printStackTrace renders it
+    Exception(String),
+}
+
+impl RunResult {
+    //the process exit status, as the java launcher's
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            RunResult::Normal => 0,
+            RunResult::Exit(status) => *status,
+            RunResult::Exception(_) => 1,
+        }
+    }
+}
+
+struct Guest {
+    thread_oop: OopRef,
+    daemon: bool,
+    //the host thread running it, once started
+    host: Option<util::HostThread>,
+    //set by Thread.setName before the host thread is known
+    pending_name: Option<String>,
+}
+
+lazy_static! {
+    //Thread oop address -> Thread
+    static ref GUESTS: Mutex<HashMap<usize, Guest>> = Mutex::new(HashMap::new());
+    //a thread detached
+    static ref DETACHED: Condvar = Condvar::new();
+}
+
+pub fn attach(thread_oop: &OopRef, daemon: bool) {
+    set_thread_status(thread_oop, THREAD_STATUS_RUNNABLE);
+    let guest = Guest {
+        thread_oop: thread_oop.clone(),
+        daemon,
+        host: None,
+        pending_name: None,
+    };
+    util::sync_call_ctx(&GUESTS, |guests| {
+        guests.insert(Arc::as_ptr(thread_oop) as usize, guest)
+    });
+}
+
+//the thread ended, wake Thread.join
+pub fn detach(thread_oop: &OopRef) {
+    set_thread_status(thread_oop, THREAD_STATUS_TERMINATED);
+    util::sync_call_ctx(&GUESTS, |guests| {
+        guests.remove(&(Arc::as_ptr(thread_oop) as usize))
+    });
+    DETACHED.notify_all();
+    park::unregister(thread_oop);
+
+    runtime::monitor::enter(thread_oop);
+    let _ = runtime::monitor::notify(thread_oop, true);
+    let _ = runtime::monitor::exit(thread_oop);
+}
+
+//the current host thread runs thread_oop until it detaches, its name set
+//by Thread.setName meanwhile is applied
+pub fn set_host(thread_oop: &OopRef) {
+    util::sync_call_ctx(&GUESTS, |guests| {
+        if let Some(guest) = guests.get_mut(&(Arc::as_ptr(thread_oop) as usize)) {
+            let host = util::current_host_thread();
+            guest.host = Some(host);
+            if let Some(name) = guest.pending_name.take() {
+                util::set_host_thread_name(host, &name);
+            }
+        }
+    });
+}
+
+//Thread.setName of another thread, its host thread renamed. Under GUESTS,
+//the host thread can't end meanwhile: it detaches first
+pub fn set_host_name(thread_oop: &OopRef, name: &str) {
+    util::sync_call_ctx(&GUESTS, |guests| {
+        if let Some(guest) = guests.get_mut(&(Arc::as_ptr(thread_oop) as usize)) {
+            match guest.host {
+                Some(host) => util::set_host_thread_name(host, name),
+                None => guest.pending_name = Some(name.to_string()),
+            }
+        }
+    });
+}
+
+pub fn is_alive(thread_oop: &OopRef) -> bool {
+    util::sync_call_ctx(&GUESTS, |guests| {
+        guests.contains_key(&(Arc::as_ptr(thread_oop) as usize))
+    })
+}
+
+//the attached threads
+pub fn live_threads() -> Vec<OopRef> {
+    util::sync_call_ctx(&GUESTS, |guests| {
+        guests.values().map(|it| it.thread_oop.clone()).collect()
+    })
+}
+
+//the vm exits once main & every non-daemon thread ended, as the java
+//launcher's DestroyJavaVM
+pub fn wait_non_daemon_threads() {
+    let mut guests = GUESTS.lock().unwrap();
+    while guests.values().any(|it| !it.daemon) {
+        guests = DETACHED.wait(guests).unwrap();
+    }
+}
+
+//Thread.start0, Err if no host thread could be created
+pub fn start(thread_oop: OopRef, name: String) -> std::io::Result<()> {
+    let run = {
+        let cls = thread_class(&thread_oop);
+        let cls = cls.lock().unwrap();
+        cls.get_virtual_method(new_method_id(b"run", b"()V"))
+            .unwrap()
+    };
+
+    //alive from start0 on, a join right after start must wait, & an
+    //unpark right after start is kept for the first park
+    attach(&thread_oop, is_daemon(&thread_oop));
+    let parker = Arc::new(Parker::default());
+    park::register(&thread_oop, parker.clone());
+    let stack_size = runtime::flags::thread_stack_size();
+    let guest = thread_oop.clone();
+    let spawned = std::thread::Builder::new()
+        .name(name.clone())
+        .stack_size(stack_size)
+        .spawn(move || {
+            set_host(&guest);
+            set_stack_size(stack_size);
+            let r = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut jt = JavaThread::new();
+                jt.parker = parker;
+                jt.set_java_thread_obj(guest.clone());
+
+                let mut stack = Stack::new(0);
+                let mut jc = JavaCall::new_with_args(&mut jt, run, vec![guest.clone()]);
+                jc.invoke(&mut jt, &mut stack, false);
+
+                if jt.is_meet_ex() {
+                    dispatch_uncaught_ex(&mut jt, &name);
+                }
+                call_thread_exit(&mut jt, &guest);
+            }));
+            detach(&guest);
+            if let Err(e) = r {
+                std::panic::resume_unwind(e);
+            }
+        });
+
+    match spawned {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            detach(&thread_oop);
+            Err(e)
+        }
+    }
+}
+
+//Thread.dispatchUncaughtException(ex), printed by the vm if there is no
+//Thread or it throws too
+fn dispatch_uncaught_ex(jt: &mut JavaThread, name: &str) {
+    let ex = jt.take_ex().unwrap();
+    let mir = jt.java_thread_obj.as_ref().and_then(|it| {
+        let cls = thread_class(it);
+        let cls = cls.lock().unwrap();
+        let id = new_method_id(b"dispatchUncaughtException", b"(Ljava/lang/Throwable;)V");
+        cls.get_virtual_method(id).ok()
+    });
+
+    match (mir, jt.java_thread_obj.clone()) {
+        (Some(mir), Some(thread_oop)) => {
+            let mut stack = Stack::new(0);
+            let mut jc = JavaCall::new_with_args(jt, mir, vec![thread_oop, ex]);
+            jc.invoke(jt, &mut stack, false);
+
+            if let Some(ex) = jt.take_ex() {
+                print_uncaught_ex(ex, name);
+            }
+        }
+        _ => print_uncaught_ex(ex, name),
+    }
+}
+
+fn print_uncaught_ex(ex: OopRef, name: &str) {
+    eprint!("Exception in thread \"{}\" ", name);
+    runtime::exception::print_stack_trace(ex);
+}
+
+//Thread.exit(), removes the thread from its group
+fn call_thread_exit(jt: &mut JavaThread, thread_oop: &OopRef) {
+    let mir = {
+        let cls = require_class3(None, classfile::consts::J_THREAD).unwrap();
+        let cls = cls.lock().unwrap();
+        cls.get_this_class_method(new_method_id(b"exit", b"()V"))
+    };
+    if let Ok(mir) = mir {
+        let mut stack = Stack::new(0);
+        let mut jc = JavaCall::new_with_args(jt, mir, vec![thread_oop.clone()]);
+        jc.invoke(jt, &mut stack, false);
+        let _ = jt.take_ex();
+    }
+}
+
+//Shutdown.shutdown(), runs the shutdown hooks, the last non-daemon thread
+//ended
+fn call_shutdown(jt: &mut JavaThread) {
+    let cls = match runtime::sys_dic_find(b"java/lang/Shutdown") {
+        Some(cls) => cls,
+        None => return,
+    };
+    let mir = {
+        let cls = cls.lock().unwrap();
+        cls.get_static_method(new_method_id(b"shutdown", b"()V"))
+    };
+    if let Ok(mir) = mir {
+        let mut stack = Stack::new(0);
+        let mut jc = JavaCall::new_with_args(jt, mir, vec![]);
+        jc.invoke(jt, &mut stack, true);
+        let _ = jt.take_ex();
+    }
+}
+
+fn thread_class(thread_oop: &OopRef) -> ClassRef {
+    let v = thread_oop.lock().unwrap();
+    match &v.v {
+        Oop::Inst(inst) => inst.class.clone(),
+        t => unreachable!("t = {:?}", t),
+    }
+}
+
+//Thread.daemon, set by setDaemon before start
+fn is_daemon(thread_oop: &OopRef) -> bool {
+    let cls = require_class3(None, classfile::consts::J_THREAD).unwrap();
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(b"daemon", b"Z", false);
+    util::oop::extract_int(cls.get_field_value(thread_oop.clone(), id)) != 0
+}
+
+fn set_thread_status(thread_oop: &OopRef, status: i32) {
+    let cls = match require_class3(None, classfile::consts::J_THREAD) {
+        Some(cls) => cls,
+        None => return,
+    };
+    let cls = cls.lock().unwrap();
+    let id = cls.get_field_id(b"threadStatus", b"I", false);
+    cls.put_field_value(thread_oop.clone(), id, OopDesc::new_int(status));
+}
 
 pub struct JavaThread {
     pub frames: Vec<FrameRef>,
@@ -16,16 +489,50 @@ pub struct JavaThread {
     ex: Option<OopRef>,
 
     pub callers: Vec<MethodIdRef>,
+    //bci of the instruction being interpreted, the site of implicit exceptions
+    pub bci: i32,
+    //bci in the caller when each of callers was invoked, the call sites
+    pub call_bcis: Vec<i32>,
+
+    //transient allocations, reset at frame boundaries
+    pub arena: Arena,
+
+    pub stat: Arc<ThreadStat>,
+
+    //Unsafe.park/unpark, see runtime::park
+    pub parker: Arc<Parker>,
+
+    //the local references of natives, see runtime::handles
+    pub local_refs: LocalRefs,
 }
 
 pub struct JavaMainThread {
     pub class: String,
     pub args: Option<Vec<String>>,
-    dispatch_uncaught_exception_called: bool,
+}
+
+impl Default for JavaThread {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl JavaThread {
     pub fn new() -> Self {
+        let stat = Arc::new(ThreadStat {
+            id: NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed),
+            depth: AtomicUsize::new(0),
+            state: AtomicI32::new(THREAD_STATUS_RUNNABLE),
+            safe: AtomicUsize::new(0),
+        });
+        util::sync_call_ctx(&THREADS, |threads| threads.push(Arc::downgrade(&stat)));
+        CURRENT.with(|it| {
+            *it.borrow_mut() = Current {
+                stat: Some(stat.clone()),
+                thread_oop: None,
+            }
+        });
+
         Self {
             frames: Vec::new(),
             in_safe_point: false,
@@ -34,17 +541,50 @@ impl JavaThread {
             ex: None,
 
             callers: vec![],
+            bci: 0,
+            call_bcis: vec![],
+
+            arena: Arena::new(),
+
+            stat,
+
+            parker: Arc::new(Parker::default()),
+
+            local_refs: LocalRefs::default(),
         }
     }
 
     pub fn set_java_thread_obj(&mut self, obj: OopRef) {
+        park::register(&obj, self.parker.clone());
+        CURRENT.with(|it| it.borrow_mut().thread_oop = Some(obj.clone()));
         self.java_thread_obj = Some(obj);
     }
 }
 
+impl JavaThread {
+    //(method, bci) of the frames, innermost first
+    pub fn backtrace(&self) -> Vec<(MethodIdRef, i32)> {
+        let bcis = self.call_bcis.iter().skip(1).chain(Some(&self.bci));
+        let mut frames: Vec<(MethodIdRef, i32)> =
+            self.callers.iter().cloned().zip(bcis.copied()).collect();
+        frames.reverse();
+        frames
+    }
+}
+
 //exception
 impl JavaThread {
     pub fn set_ex(&mut self, ex: OopRef) {
+        events::record(EventKind::Exception, || match ex.try_lock() {
+            Ok(v) => match &v.v {
+                Oop::Inst(inst) => match inst.class.try_lock() {
+                    Ok(cls) => String::from_utf8_lossy(cls.name.as_slice()).to_string(),
+                    Err(_) => String::new(),
+                },
+                _ => String::new(),
+            },
+            Err(_) => String::new(),
+        });
         self.ex = Some(ex);
     }
 
@@ -181,46 +721,90 @@ impl JavaThread {
 
 impl JavaMainThread {
     pub fn new(class: String, args: Option<Vec<String>>) -> Self {
-        Self {
-            class,
-            args,
-            dispatch_uncaught_exception_called: false,
-        }
+        Self { class, args }
     }
 
-    pub fn run(&mut self) {
+    //never returns after Shutdown.halt0, see runtime::safepoint
+    pub fn run(&mut self) -> RunResult {
         let mut jt = JavaThread::new();
 
-        info!("init vm start...");
-        init_vm::initialize_jvm(&mut jt);
-        info!("init vm end");
+        if !runtime::snapshot::restore_heap_snapshot(&mut jt) {
+            info!("init vm start...");
+            init_vm::initialize_jvm(&mut jt);
+            info!("init vm end");
+        }
+        runtime::snapshot::dump_heap_snapshot(&jt);
+
+        let result = self.call_main(&mut jt);
+        if let Some(thread_oop) = jt.java_thread_obj.clone() {
+            detach(&thread_oop);
+        }
 
-        let main_class = oop::class::load_and_init(&mut jt, self.class.as_bytes());
+        wait_non_daemon_threads();
+        call_shutdown(&mut jt);
+        result
+    }
+}
 
-        let mir = {
-            let cls = main_class.lock().unwrap();
-            let id = util::new_method_id(b"main", b"([Ljava/lang/String;)V");
-            cls.get_static_method(id)
+impl JavaMainThread {
+    //main(args), an exception out of main or the init of its class goes
+    //to the uncaught exception handler, the launcher exits with 1
+    fn call_main(&self, jt: &mut JavaThread) -> RunResult {
+        let main_class = match require_class3(None, self.class.as_bytes()) {
+            Some(_) => Some(oop::class::load_and_init(jt, self.class.as_bytes())),
+            None => match runtime::take_load_error() {
+                //found but not loaded, thrown as by the launcher's loadClass
+                Some((cls, msg)) => {
+                    let ex = runtime::exception::new(jt, cls, Some(msg));
+                    jt.set_ex(ex);
+                    None
+                }
+                None => {
+                    eprintln!("{}", main_class_not_found_msg(&self.class));
+                    return RunResult::Exit(1);
+                }
+            },
         };
 
-        match mir {
-            Ok(mir) => {
-                let mut stack = self.build_stack(&mut jt);
-                match JavaCall::new(&mut jt, &mut stack, mir) {
-                    Ok(mut jc) => jc.invoke(&mut jt, &mut stack, true),
-                    _ => unreachable!(),
+        if let Some(main_class) = main_class.filter(|_| !jt.is_meet_ex()) {
+            let cf = match &main_class.lock().unwrap().kind {
+                oop::class::ClassKind::Instance(cls_obj) => Some(cls_obj.class_file.clone()),
+                _ => None,
+            };
+            if let Some(cf) = cf {
+                runtime::prefetch::start(&cf);
+            }
+
+            let mir = {
+                let cls = main_class.lock().unwrap();
+                let id = util::new_method_id(b"main", b"([Ljava/lang/String;)V");
+                cls.get_static_method(id)
+            };
+            let mir = match mir {
+                Ok(mir) => mir,
+                Err(_) => {
+                    eprintln!("{}", main_method_not_found_msg(&self.class));
+                    return RunResult::Exit(1);
                 }
+            };
+
+            let mut stack = self.build_stack(jt);
+            match JavaCall::new(jt, &mut stack, mir) {
+                Ok(mut jc) => jc.invoke(jt, &mut stack, true),
+                _ => unreachable!(),
             }
-            _ => unimplemented!(),
         }
 
-        if jt.ex.is_some() {
-            self.uncaught_ex(&mut jt, main_class);
+        match jt.ex.clone() {
+            Some(ex) => {
+                let trace = runtime::exception::render_stack_trace(ex);
+                dispatch_uncaught_ex(jt, "main");
+                RunResult::Exception(trace)
+            }
+            None => RunResult::Normal,
         }
     }
-}
 
-impl JavaMainThread {
     fn build_stack(&self, jt: &mut JavaThread) -> Stack {
         let args = match &self.args {
             Some(args) => args
@@ -240,76 +824,175 @@ impl JavaMainThread {
 
         stack
     }
+}
+
+//the java launcher's errors
+fn main_class_not_found_msg(class: &str) -> String {
+    format!(
+        "Error: Could not find or load main class {}",
+        class.replace('/', ".")
+    )
+}
+
+fn main_method_not_found_msg(class: &str) -> String {
+    format!(
+        "Error: Main method not found in class {}, please define the main method as:\n   public static void main(String[] args)",
+        class.replace('/', ".")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn uncaught_ex(&mut self, jt: &mut JavaThread, main_cls: ClassRef) {
-        if self.dispatch_uncaught_exception_called {
-            self.uncaught_ex_internal(jt);
+    //recurse until the guard trips, the reserve is left untouched
+    fn depth(n: usize) -> usize {
+        let pad = std::hint::black_box([0u8; 512]);
+        if is_stack_overflow() {
+            n + pad[0] as usize
         } else {
-            self.dispatch_uncaught_exception_called = true;
-            self.call_dispatch_uncaught_exception(jt, main_cls);
+            depth(n + 1)
         }
     }
 
-    fn call_dispatch_uncaught_exception(&mut self, jt: &mut JavaThread, main_cls: ClassRef) {
-        let java_thread_obj = jt.java_thread_obj.clone();
-        match &java_thread_obj {
-            Some(java_thread_oop) => {
-                let cls = {
-                    let v = java_thread_oop.lock().unwrap();
-                    match &v.v {
-                        Oop::Inst(inst) => inst.class.clone(),
-                        _ => unreachable!(),
-                    }
-                };
-
-                let mir = {
-                    let cls = cls.lock().unwrap();
-                    let id =
-                        new_method_id(b"dispatchUncaughtException", b"(Ljava/lang/Throwable;)V");
-                    cls.get_this_class_method(id)
-                };
-
-                match mir {
-                    Ok(mir) => {
-                        let ex = { jt.take_ex().unwrap() };
-                        let args = vec![java_thread_oop.clone(), ex];
-                        let mut stack = Stack::new(0);
-                        let mut jc = JavaCall::new_with_args(jt, mir, args);
-                        jc.invoke(jt, &mut stack, false);
-                    }
-                    _ => self.uncaught_ex_internal(jt),
-                }
-            }
+    #[test]
+    fn t_launcher_msgs() {
+        assert_eq!(
+            main_class_not_found_msg("a/b/Main"),
+            "Error: Could not find or load main class a.b.Main"
+        );
+        assert!(main_method_not_found_msg("Main")
+            .starts_with("Error: Main method not found in class Main, please"));
+    }
+
+    //a contended enter is BLOCKED, RUNNABLE again once entered
+    #[test]
+    fn t_thread_state() {
+        assert_eq!(state_name(THREAD_STATUS_NEW), "NEW");
+        assert_eq!(state_name(THREAD_STATUS_RUNNABLE), "RUNNABLE");
+        assert_eq!(
+            state_name(THREAD_STATUS_BLOCKED_ON_MONITOR_ENTER),
+            "BLOCKED"
+        );
+        assert_eq!(state_name(THREAD_STATUS_IN_OBJECT_WAIT), "WAITING");
+        assert_eq!(state_name(THREAD_STATUS_PARKED), "WAITING");
+        assert_eq!(state_name(THREAD_STATUS_SLEEPING), "TIMED_WAITING");
+        assert_eq!(
+            state_name(THREAD_STATUS_IN_OBJECT_WAIT_TIMED),
+            "TIMED_WAITING"
+        );
+        assert_eq!(state_name(THREAD_STATUS_PARKED_TIMED), "TIMED_WAITING");
+        assert_eq!(state_name(THREAD_STATUS_TERMINATED), "TERMINATED");
+
+        let obj = OopDesc::new_int(0);
+        runtime::monitor::enter(&obj);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let blocked = {
+            let obj = obj.clone();
+            std::thread::spawn(move || {
+                let jt = JavaThread::new();
+                tx.send(jt.stat.clone()).unwrap();
+                runtime::monitor::enter(&obj);
+                let held = runtime::monitor::holds_lock(&obj);
+                runtime::monitor::exit(&obj).unwrap();
+                held
+            })
+        };
 
-            None => self.uncaught_ex_internal(jt),
+        let stat = rx.recv().unwrap();
+        while stat.state.load(Ordering::Relaxed) != THREAD_STATUS_BLOCKED_ON_MONITOR_ENTER {
+            std::thread::yield_now();
         }
-    }
+        assert!(runtime::monitor::holds_lock(&obj));
+        runtime::monitor::exit(&obj).unwrap();
+        assert!(!runtime::monitor::holds_lock(&obj));
 
-    fn uncaught_ex_internal(&mut self, jt: &mut JavaThread) {
-        let ex = { jt.take_ex().unwrap() };
+        assert!(blocked.join().unwrap());
+        assert_eq!(stat.state.load(Ordering::Relaxed), THREAD_STATUS_RUNNABLE);
+    }
 
-        let cls = {
-            let v = ex.lock().unwrap();
-            match &v.v {
-                oop::Oop::Inst(inst) => inst.class.clone(),
-                _ => unreachable!(),
-            }
+    //Thread.setName of another thread, before & after its host thread
+    //is known
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn t_set_host_name() {
+        let thread_oop = OopDesc::new_int(0);
+        attach(&thread_oop, true);
+        set_host_name(&thread_oop, "guest-pending");
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (renamed_tx, renamed) = std::sync::mpsc::channel::<()>();
+        let host = {
+            let thread_oop = thread_oop.clone();
+            std::thread::spawn(move || {
+                set_host(&thread_oop);
+                tx.send(util::thread_name()).unwrap();
+                renamed.recv().unwrap();
+                tx.send(util::thread_name()).unwrap();
+                detach(&thread_oop);
+            })
         };
-        let detail_message = {
-            let v = {
-                let cls = cls.lock().unwrap();
-                let id = cls.get_field_id(b"detailMessage", b"Ljava/lang/String;", false);
-                cls.get_field_value(ex.clone(), id)
-            };
+        assert_eq!(rx.recv().unwrap().as_deref(), Some("guest-pending"));
+        set_host_name(&thread_oop, "guest-renamed");
+        renamed_tx.send(()).unwrap();
+        assert_eq!(rx.recv().unwrap().as_deref(), Some("guest-renamed"));
+        host.join().unwrap();
+        //ended, nothing to rename
+        set_host_name(&thread_oop, "guest-gone");
+    }
 
-            util::oop::extract_str(v)
-        };
-        let name = {
-            let cls = cls.lock().unwrap();
-            cls.name.clone()
+    //Thread.join: wait() while isAlive(), the vm exit waits too
+    #[test]
+    fn t_join() {
+        let thread_oop = OopDesc::new_int(0);
+        attach(&thread_oop, false);
+        assert!(is_alive(&thread_oop));
+
+        let joiner = {
+            let thread_oop = thread_oop.clone();
+            std::thread::spawn(move || {
+                runtime::monitor::enter(&thread_oop);
+                while is_alive(&thread_oop) {
+                    runtime::monitor::wait(&thread_oop, None, None).unwrap();
+                }
+                runtime::monitor::exit(&thread_oop).unwrap();
+            })
         };
+        let exiting = std::thread::spawn(wait_non_daemon_threads);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(!exiting.is_finished());
+
+        detach(&thread_oop);
+        joiner.join().unwrap();
+        exiting.join().unwrap();
+        assert!(!is_alive(&thread_oop));
+    }
 
-        let cls_name = String::from_utf8_lossy(name.as_slice());
-        error!("Name={}, detailMessage={}", cls_name, detail_message);
+    #[test]
+    fn t_stack_guard() {
+        let size = 1 << 20;
+        let n = std::thread::Builder::new()
+            .stack_size(size)
+            .spawn(move || {
+                assert!(!is_stack_overflow());
+                set_stack_size(size);
+                depth(0)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(n > 0 && n < size / 512);
+
+        //unchecked without set_stack_size
+        assert!(!is_stack_overflow());
+    }
+
+    #[test]
+    fn t_spin_wait() {
+        let n = SPINS.with(|it| it.get());
+        for _ in 0..SPINS_PER_YIELD {
+            spin_wait();
+        }
+        assert_eq!(SPINS.with(|it| it.get()), n + SPINS_PER_YIELD);
     }
 }
@@ -0,0 +1,580 @@
+/*
+Virtual file system, what guest I/O sees
+
+The file natives (FileInputStream, FileOutputStream, UnixFileSystem) go
+through here, not to the host. A FileSystem is mounted at a path prefix,
+the longest prefix of a path wins, the host's is mounted at the root:
+
+  mount("/data", Arc::new(mem));   //"/data/a.txt" is mem's "/data/a.txt"
+
+A path is made absolute against user.dir & "." & ".." resolved before
+the prefix is looked up, "/data/../etc/passwd" is not under "/data". The
+mounted fs gets the whole of that path, the host the path as given. An
+in-memory MemFs is provided, a read-only overlay or a jar-backed tree is
+an impl of FileSystem.
+
+An open file is an fd of the vm's table, the int FileDescriptor.fd holds,
+the host's fds are never seen by guest code. 0, 1 & 2 are the host's
+stdin, stdout & stderr, FileDescriptor.in, out & err.
+*/
+
+use crate::util;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+//java.io.FileSystem, checkAccess
+pub const ACCESS_READ: i32 = 0x04;
+pub const ACCESS_WRITE: i32 = 0x02;
+pub const ACCESS_EXECUTE: i32 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metadata {
+    pub is_file: bool,
+    pub is_dir: bool,
+    pub len: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpenMode {
+    Read,
+    //created if missing, truncated unless append
+    Write { append: bool },
+}
+
+pub trait FileSystem: Send + Sync {
+    fn metadata(&self, path: &str) -> io::Result<Metadata>;
+    //access, ACCESS_* bits
+    fn check_access(&self, path: &str, access: i32) -> bool;
+    fn canonicalize(&self, path: &str) -> io::Result<String>;
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn VFile>>;
+    //Ok(false) if path exists
+    fn create_new(&self, path: &str) -> io::Result<bool>;
+}
+
+//closed when dropped
+pub trait VFile: Send {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
+    //bytes readable without blocking
+    fn available(&mut self) -> io::Result<u64>;
+}
+
+type FileRef = Arc<Mutex<Box<dyn VFile>>>;
+
+lazy_static! {
+    //(prefix, fs), longest prefix first
+    static ref MOUNTS: Mutex<Vec<(String, Arc<dyn FileSystem>)>> = Mutex::new(Vec::new());
+    static ref FILES: Mutex<HashMap<i32, FileRef>> = {
+        let mut files: HashMap<i32, FileRef> = HashMap::new();
+        for fd in 0..3 {
+            let stdio = HostFile { fd, owned: false };
+            files.insert(fd, Arc::new(Mutex::new(Box::new(stdio))));
+        }
+        Mutex::new(files)
+    };
+}
+
+pub fn mount(prefix: &str, fs: Arc<dyn FileSystem>) {
+    let prefix = prefix.trim_end_matches('/').to_string();
+    util::sync_call_ctx(&MOUNTS, |mounts| {
+        mounts.retain(|(it, _)| *it != prefix);
+        mounts.push((prefix, fs));
+        mounts.sort_by_key(|it| std::cmp::Reverse(it.0.len()));
+    });
+}
+
+pub fn unmount(prefix: &str) {
+    let prefix = prefix.trim_end_matches('/');
+    util::sync_call_ctx(&MOUNTS, |mounts| mounts.retain(|(it, _)| it != prefix));
+}
+
+//the fs of path & the path it gets
+fn fs_of(path: &str) -> (Arc<dyn FileSystem>, String) {
+    let abs = absolute(path);
+    let mounted = util::sync_call_ctx(&MOUNTS, |mounts| {
+        mounts
+            .iter()
+            .find(|(prefix, _)| is_under(&abs, prefix))
+            .map(|(_, fs)| fs.clone())
+    });
+    match mounted {
+        Some(fs) => (fs, abs),
+        None => (Arc::new(HostFs), path.to_string()),
+    }
+}
+
+//against user.dir, the current dir, "." & ".." resolved by name, ".." of
+//the root is the root
+fn absolute(path: &str) -> String {
+    let path = match path.starts_with('/') {
+        true => path.to_string(),
+        false => {
+            let dir = std::env::current_dir().unwrap_or_default();
+            format!("{}/{}", dir.to_string_lossy(), path)
+        }
+    };
+    let mut names = Vec::new();
+    for it in path.split('/') {
+        match it {
+            "" | "." => (),
+            ".." => {
+                names.pop();
+            }
+            it => names.push(it),
+        }
+    }
+    format!("/{}", names.join("/"))
+}
+
+//"/data" holds "/data" & "/data/a", not "/database"
+fn is_under(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.is_empty(),
+        None => false,
+    }
+}
+
+pub fn metadata(path: &str) -> io::Result<Metadata> {
+    let (fs, path) = fs_of(path);
+    fs.metadata(&path)
+}
+
+pub fn check_access(path: &str, access: i32) -> bool {
+    let (fs, path) = fs_of(path);
+    fs.check_access(&path, access)
+}
+
+pub fn canonicalize(path: &str) -> io::Result<String> {
+    let (fs, path) = fs_of(path);
+    fs.canonicalize(&path)
+}
+
+pub fn create_new(path: &str) -> io::Result<bool> {
+    let (fs, path) = fs_of(path);
+    fs.create_new(&path)
+}
+
+//the fd of the file opened, the lowest free
+pub fn open(path: &str, mode: OpenMode) -> io::Result<i32> {
+    let (fs, path) = fs_of(path);
+    let file = fs.open(&path, mode)?;
+    let file = Arc::new(Mutex::new(file));
+    let fd = util::sync_call_ctx(&FILES, |files| {
+        let fd = (0..).find(|it| !files.contains_key(it)).unwrap();
+        files.insert(fd, file);
+        fd
+    });
+    Ok(fd)
+}
+
+//the table is not locked while the file is, a read of stdin blocks only
+//its own reader
+fn file(fd: i32) -> io::Result<FileRef> {
+    util::sync_call_ctx(&FILES, |files| files.get(&fd).cloned())
+        .ok_or_else(|| io::Error::other("Stream Closed"))
+}
+
+//0 at the end of the file
+pub fn read(fd: i32, buf: &mut [u8]) -> io::Result<usize> {
+    file(fd)?.lock().unwrap().read(buf)
+}
+
+pub fn write_all(fd: i32, mut buf: &[u8]) -> io::Result<()> {
+    let file = file(fd)?;
+    let mut file = file.lock().unwrap();
+    while !buf.is_empty() {
+        match file.write(buf)? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            n => buf = &buf[n..],
+        }
+    }
+    Ok(())
+}
+
+pub fn available(fd: i32) -> io::Result<u64> {
+    file(fd)?.lock().unwrap().available()
+}
+
+pub fn close(fd: i32) {
+    util::sync_call_ctx(&FILES, |files| files.remove(&fd));
+}
+
+//the message of an exception about e, "No such file or directory"
+pub fn reason(e: &io::Error) -> String {
+    let s = e.to_string();
+    match s.find(" (os error") {
+        Some(i) => s[..i].to_string(),
+        None => s,
+    }
+}
+
+struct HostFs;
+
+impl FileSystem for HostFs {
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        let attr = std::fs::metadata(path)?;
+        Ok(Metadata {
+            is_file: attr.is_file(),
+            is_dir: attr.is_dir(),
+            len: attr.len(),
+        })
+    }
+
+    fn check_access(&self, path: &str, access: i32) -> bool {
+        let mut amode = 0;
+        if (access & ACCESS_READ) == ACCESS_READ {
+            amode |= libc::R_OK;
+        }
+        if (access & ACCESS_WRITE) == ACCESS_WRITE {
+            amode |= libc::W_OK;
+        }
+        if (access & ACCESS_EXECUTE) == ACCESS_EXECUTE {
+            amode |= libc::X_OK;
+        }
+        match CString::new(path) {
+            Ok(path) => unsafe { libc::access(path.as_ptr(), amode) == 0 },
+            Err(_) => false,
+        }
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        let path = std::path::Path::new(path).canonicalize()?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn VFile>> {
+        let flags = match mode {
+            OpenMode::Read => libc::O_RDONLY,
+            OpenMode::Write { append: true } => libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND,
+            OpenMode::Write { append: false } => libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+        };
+        let path =
+            CString::new(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let fd = unsafe { libc::open(path.as_ptr(), flags, 0o666) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Box::new(HostFile { fd, owned: true }))
+    }
+
+    fn create_new(&self, path: &str) -> io::Result<bool> {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+        {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+struct HostFile {
+    fd: i32,
+    //stdio is not closed
+    owned: bool,
+}
+
+impl VFile for HostFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = unsafe { libc::write(self.fd, buf.as_ptr() as *const libc::c_void, buf.len()) };
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    //a pipe, char device or socket: FIONREAD, a regular file: the size
+    //less the position
+    fn available(&mut self) -> io::Result<u64> {
+        let fd = self.fd;
+        let mut size = -1i64;
+        unsafe {
+            let mut stat: libc::stat = std::mem::zeroed();
+            if libc::fstat(fd, &mut stat) != -1 {
+                let mode = stat.st_mode;
+                if (mode & libc::S_IFIFO == libc::S_IFIFO)
+                    || (mode & libc::S_IFCHR == libc::S_IFCHR)
+                    || (mode & libc::S_IFSOCK == libc::S_IFSOCK)
+                {
+                    let mut n: libc::c_int = 0;
+                    if libc::ioctl(fd, libc::FIONREAD, &mut n) >= 0 {
+                        return Ok(n.max(0) as u64);
+                    }
+                } else if mode & libc::S_IFREG == libc::S_IFREG {
+                    size = stat.st_size;
+                }
+            }
+
+            let current = libc::lseek(fd, 0, libc::SEEK_CUR);
+            if current == -1 {
+                return Ok(0);
+            }
+            if size < current {
+                size = libc::lseek(fd, 0, libc::SEEK_END);
+                if size == -1 || libc::lseek(fd, current, libc::SEEK_SET) == -1 {
+                    return Ok(0);
+                }
+            }
+            Ok((size - current).max(0) as u64)
+        }
+    }
+}
+
+impl Drop for HostFile {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+}
+
+/*
+In-memory file system, files only, a directory is a prefix of a file
+path. Paths come absolute & normalized from the vfs, canonicalize
+returns the path itself.
+*/
+#[derive(Default)]
+pub struct MemFs {
+    files: Mutex<HashMap<String, Arc<Mutex<Vec<u8>>>>>,
+    read_only: bool,
+}
+
+impl MemFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //writes & creates fail with PermissionDenied
+    pub fn new_read_only() -> Self {
+        Self {
+            read_only: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn insert(&self, path: &str, content: Vec<u8>) {
+        let content = Arc::new(Mutex::new(content));
+        util::sync_call_ctx(&self.files, |files| files.insert(path.to_string(), content));
+    }
+
+    pub fn content(&self, path: &str) -> Option<Vec<u8>> {
+        let file = util::sync_call_ctx(&self.files, |files| files.get(path).cloned());
+        file.map(|it| it.lock().unwrap().clone())
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        let dir = format!("{}/", path.trim_end_matches('/'));
+        util::sync_call_ctx(&self.files, |files| {
+            files.keys().any(|it| it.starts_with(&dir))
+        })
+    }
+
+    fn check_writable(&self) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Read-only file system",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "No such file or directory")
+}
+
+impl FileSystem for MemFs {
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        if let Some(content) = self.content(path) {
+            return Ok(Metadata {
+                is_file: true,
+                is_dir: false,
+                len: content.len() as u64,
+            });
+        }
+        if self.is_dir(path) {
+            return Ok(Metadata {
+                is_file: false,
+                is_dir: true,
+                len: 0,
+            });
+        }
+        Err(not_found())
+    }
+
+    fn check_access(&self, path: &str, access: i32) -> bool {
+        let exists = self.metadata(path).is_ok();
+        let writable = (access & ACCESS_WRITE) == 0 || !self.read_only;
+        let executable = (access & ACCESS_EXECUTE) == 0;
+        exists && writable && executable
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        Ok(path.to_string())
+    }
+
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn VFile>> {
+        let file = util::sync_call_ctx(&self.files, |files| files.get(path).cloned());
+        let data = match (mode, file) {
+            (OpenMode::Read, Some(data)) => data,
+            (OpenMode::Read, None) => return Err(not_found()),
+            (OpenMode::Write { append }, file) => {
+                self.check_writable()?;
+                let data = file.unwrap_or_default();
+                if !append {
+                    data.lock().unwrap().clear();
+                }
+                util::sync_call_ctx(&self.files, |files| {
+                    files.insert(path.to_string(), data.clone())
+                });
+                data
+            }
+        };
+        Ok(Box::new(MemFile { data, pos: 0 }))
+    }
+
+    fn create_new(&self, path: &str) -> io::Result<bool> {
+        self.check_writable()?;
+        util::sync_call_ctx(&self.files, |files| {
+            if files.contains_key(path) {
+                Ok(false)
+            } else {
+                files.insert(path.to_string(), Arc::default());
+                Ok(true)
+            }
+        })
+    }
+}
+
+struct MemFile {
+    data: Arc<Mutex<Vec<u8>>>,
+    //of reads, writes append
+    pos: usize,
+}
+
+impl VFile for MemFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.data.lock().unwrap();
+        let rest = data.get(self.pos..).unwrap_or_default();
+        let n = rest.len().min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn available(&mut self) -> io::Result<u64> {
+        let len = self.data.lock().unwrap().len();
+        Ok(len.saturating_sub(self.pos) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_is_under() {
+        assert!(is_under("/data", "/data"));
+        assert!(is_under("/data/a.txt", "/data"));
+        assert!(!is_under("/database", "/data"));
+        assert!(is_under("/etc/hosts", ""));
+    }
+
+    #[test]
+    fn t_mem_fs() {
+        let mem = Arc::new(MemFs::new());
+        mem.insert("/vfs-test/in.txt", b"hello".to_vec());
+        mount("/vfs-test", mem.clone());
+
+        let meta = metadata("/vfs-test/in.txt").unwrap();
+        assert!(meta.is_file && meta.len == 5);
+        assert!(metadata("/vfs-test").unwrap().is_dir);
+        assert!(metadata("/vfs-test/none").is_err());
+        assert!(check_access("/vfs-test/in.txt", ACCESS_READ | ACCESS_WRITE));
+
+        let fd = open("/vfs-test/in.txt", OpenMode::Read).unwrap();
+        assert!(fd > 2);
+        let mut buf = [0u8; 3];
+        assert_eq!(read(fd, &mut buf).unwrap(), 3);
+        assert_eq!(&buf, b"hel");
+        assert_eq!(available(fd).unwrap(), 2);
+        close(fd);
+        assert!(read(fd, &mut buf).is_err());
+
+        let fd = open("/vfs-test/out.txt", OpenMode::Write { append: false }).unwrap();
+        write_all(fd, b"abc").unwrap();
+        close(fd);
+        let fd = open("/vfs-test/out.txt", OpenMode::Write { append: true }).unwrap();
+        write_all(fd, b"de").unwrap();
+        close(fd);
+        assert_eq!(mem.content("/vfs-test/out.txt").unwrap(), b"abcde");
+        assert!(!create_new("/vfs-test/out.txt").unwrap());
+
+        unmount("/vfs-test");
+        assert!(metadata("/vfs-test/in.txt").is_err());
+    }
+
+    #[test]
+    fn t_absolute() {
+        assert_eq!(absolute("/a/./b/../c/"), "/a/c");
+        assert_eq!(absolute("/../a"), "/a");
+        let dir = std::env::current_dir().unwrap();
+        assert_eq!(absolute("x/y"), format!("{}/x/y", dir.display()));
+        assert_eq!(absolute("x/.."), dir.display().to_string());
+    }
+
+    #[test]
+    fn t_mount_relative() {
+        let mem = Arc::new(MemFs::new());
+        let dir = std::env::current_dir().unwrap();
+        let prefix = format!("{}/vfs-rel-test", dir.display());
+        mem.insert(&format!("{}/in.txt", prefix), b"hi".to_vec());
+        mount(&prefix, mem.clone());
+
+        assert!(metadata("vfs-rel-test/in.txt").unwrap().is_file);
+        assert!(metadata("./vfs-rel-test/x/../in.txt").unwrap().is_file);
+        let fd = open("vfs-rel-test/out.txt", OpenMode::Write { append: false }).unwrap();
+        close(fd);
+        assert!(mem.content(&format!("{}/out.txt", prefix)).is_some());
+
+        //out of the mount by "..", to the host, not a name of mem
+        let escaped = format!("{}/../vfs-rel-test-none", prefix);
+        mem.insert(&escaped, vec![]);
+        assert!(metadata(&escaped).is_err());
+        unmount(&prefix);
+    }
+
+    #[test]
+    fn t_read_only() {
+        let mem = MemFs::new_read_only();
+        mem.insert("/a", vec![1]);
+        assert!(mem.open("/a", OpenMode::Read).is_ok());
+        let e = mem
+            .open("/a", OpenMode::Write { append: true })
+            .err()
+            .unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        assert!(!mem.check_access("/a", ACCESS_WRITE));
+        assert_eq!(reason(&not_found()), "No such file or directory");
+    }
+}
@@ -32,3 +32,13 @@ macro_rules! new_ref {
         std::sync::Arc::new(Box::new($name));
     };
 }
+
+//vm invariant check, compiled in only with the "vm-debug" feature
+#[macro_export]
+macro_rules! vm_assert {
+    ($($arg:tt)+) => {
+        if cfg!(feature = "vm-debug") {
+            assert!($($arg)+);
+        }
+    };
+}
@@ -0,0 +1,117 @@
+/*
+Read only file mapping, the bytes are paged in by the os on access
+instead of copied into a Vec first.
+
+Unix only, elsewhere the file is read into memory.
+*/
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+pub struct Mmap {
+    inner: Inner,
+}
+
+#[cfg(unix)]
+struct Inner {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+#[cfg(not(unix))]
+struct Inner(Vec<u8>);
+
+//the mapping is read only, never changes
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    #[cfg(unix)]
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let f = File::open(path)?;
+        let len = f.metadata()?.len() as usize;
+        //mmap of 0 bytes fails
+        if len == 0 {
+            let ptr = std::ptr::null_mut();
+            return Ok(Self {
+                inner: Inner { ptr, len },
+            });
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                f.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            inner: Inner { ptr, len },
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let v = std::fs::read(path)?;
+        Ok(Self { inner: Inner(v) })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    #[cfg(unix)]
+    fn deref(&self) -> &[u8] {
+        if self.inner.len == 0 {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.inner.ptr as *const u8, self.inner.len) }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn deref(&self) -> &[u8] {
+        self.inner.0.as_slice()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.inner.len != 0 {
+            unsafe {
+                libc::munmap(self.inner.ptr, self.inner.len);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Mmap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Mmap({} bytes)", self.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mmap;
+
+    #[test]
+    fn t_mmap() {
+        let m = Mmap::open("Cargo.toml").unwrap();
+        assert_eq!(&m[..], std::fs::read("Cargo.toml").unwrap().as_slice());
+        assert!(Mmap::open("no_such_file").is_err());
+    }
+}
@@ -3,13 +3,20 @@ pub mod macros;
 
 pub mod debug;
 mod file;
+mod json;
+mod mmap;
+pub mod mutf8;
 mod net;
 pub mod oop;
+mod symbol;
 mod sync;
 mod sys;
 
 pub use self::file::*;
+pub use self::json::*;
+pub use self::mmap::Mmap;
 pub use self::net::*;
+pub use self::symbol::*;
 pub use self::sync::*;
 pub use self::sys::*;
 
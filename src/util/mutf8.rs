@@ -0,0 +1,85 @@
+/*
+Modified UTF-8, the encoding of class file Utf8 constants (JVMS 4.4.7)
+
+As UTF-8, but NUL is 0xC0 0x80, and a supplementary character is its
+surrogate pair, each surrogate encoded in 3 bytes. Class names, method
+ids & descriptors are kept as these bytes, a malformed class file may
+hold any bytes at all.
+
+  decode   the UTF-16 units, the value of a java String
+  display  text for logs & exception messages
+
+Neither fails, a malformed sequence becomes U+FFFD, one byte consumed.
+*/
+
+use std::borrow::Cow;
+
+const REPLACEMENT: u16 = 0xfffd;
+
+pub fn decode(bs: &[u8]) -> Vec<u16> {
+    let mut units = Vec::with_capacity(bs.len());
+    let cont = |i: usize| {
+        bs.get(i)
+            .filter(|&&b| b & 0xc0 == 0x80)
+            .map(|&b| (b & 0x3f) as u16)
+    };
+
+    let mut pos = 0;
+    while pos < bs.len() {
+        let x = bs[pos] as u16;
+        if x & 0x80 == 0 {
+            units.push(x);
+            pos += 1;
+        } else if let (0xc0, Some(y)) = (x & 0xe0, cont(pos + 1)) {
+            units.push(((x & 0x1f) << 6) | y);
+            pos += 2;
+        } else if let (0xe0, Some(y), Some(z)) = (x & 0xf0, cont(pos + 1), cont(pos + 2)) {
+            units.push(((x & 0x0f) << 12) | (y << 6) | z);
+            pos += 3;
+        } else {
+            units.push(REPLACEMENT);
+            pos += 1;
+        }
+    }
+    units
+}
+
+//valid UTF-8 means the same in both, borrowed as is
+pub fn display(bs: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(bs) {
+        Ok(s) => Cow::Borrowed(s),
+        Err(_) => Cow::Owned(String::from_utf16_lossy(&decode(bs))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_decode() {
+        assert_eq!(
+            decode(b"java/lang/Object"),
+            "java/lang/Object".encode_utf16().collect::<Vec<u16>>()
+        );
+        //NUL
+        assert_eq!(decode(&[b'a', 0xc0, 0x80]), vec![0x61, 0]);
+        //U+1F600 as a surrogate pair, 3 bytes each
+        assert_eq!(
+            decode(&[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]),
+            vec![0xd83d, 0xde00]
+        );
+        //truncated, a stray continuation byte
+        assert_eq!(decode(&[0xe4, 0xb8]), vec![REPLACEMENT, REPLACEMENT]);
+        assert_eq!(decode(&[0x80, b'a']), vec![REPLACEMENT, 0x61]);
+    }
+
+    #[test]
+    fn t_display() {
+        assert!(matches!(display("Main".as_bytes()), Cow::Borrowed("Main")));
+        assert_eq!(display("中".as_bytes()), "中");
+        assert_eq!(display(&[b'M', 0xc0, 0x80]), "M\0");
+        assert_eq!(display(&[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]), "\u{1f600}");
+        assert_eq!(display(&[b'A', 0xff]), "A\u{fffd}");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::native::java_lang_String;
 use crate::oop::{self, Oop, OopDesc};
 use crate::runtime::{self, require_class3, JavaThread};
 use crate::types::OopRef;
@@ -14,27 +15,18 @@ pub fn set_java_lang_string_value_offset(offset: usize) {
     });
 }
 
-pub fn is_str(v: OopRef) -> bool {
-    let v = v.lock().unwrap();
-    match &v.v {
-        Oop::Inst(inst) => {
-            let cls = inst.class.lock().unwrap();
-            cls.name.as_slice() == b"java/lang/String"
-        }
-        _ => false,
-    }
-}
-
-pub fn extract_java_lang_string_value(v: OopRef) -> Vec<u16> {
+//String.value, the char[]
+pub fn java_lang_string_value(v: OopRef) -> OopRef {
     let offset: Option<usize> = util::sync_call(&JAVA_LANG_STRING_VALUE_OFFSET, |v| v.clone());
     let offset = offset.unwrap();
 
     let cls_string = require_class3(None, b"java/lang/String").unwrap();
-    let value_ary = {
-        let cls = cls_string.lock().unwrap();
-        cls.get_field_value2(v.clone(), offset)
-    };
+    let cls = cls_string.lock().unwrap();
+    cls.get_field_value2(v, offset)
+}
 
+pub fn extract_java_lang_string_value(v: OopRef) -> Vec<u16> {
+    let value_ary = java_lang_string_value(v);
     let value_ary = value_ary.lock().unwrap();
     match &value_ary.v {
         Oop::TypeArray(ary) => match ary {
@@ -82,27 +74,14 @@ pub fn extract_double(v: OopRef) -> f64 {
     }
 }
 
+//by identity, String constants are interned & null is one oop
 pub fn if_acmpeq(v1: OopRef, v2: OopRef) -> bool {
-    if Arc::ptr_eq(&v1, &v2) {
-        true
-    } else {
-        if is_str(v2.clone()) && is_str(v1.clone()) {
-            let v2 = extract_str(v2.clone());
-            let v1 = extract_str(v1.clone());
-            if v2 == v1 {
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
-    }
+    Arc::ptr_eq(&v1, &v2)
 }
 
 pub fn new_java_lang_string2(jt: &mut JavaThread, v: &str) -> OopRef {
     //build "char value[]"
-    let chars: Vec<u16> = v.as_bytes().iter().map(|v| *v as u16).collect();
+    let chars: Vec<u16> = v.encode_utf16().collect();
     let ary = OopDesc::char_ary_from1(chars.as_slice());
 
     //new String(char value[])
@@ -114,51 +93,15 @@ pub fn new_java_lang_string2(jt: &mut JavaThread, v: &str) -> OopRef {
     string_oop
 }
 
+//a String constant of a class file, interned, JVMS 5.1
+pub fn new_constant_string(jt: &mut JavaThread, bs: &[u8]) -> OopRef {
+    let s = new_java_lang_string3(jt, bs);
+    java_lang_String::intern(s)
+}
+
+//bs is modified UTF-8, a class file Utf8 constant
 pub fn new_java_lang_string3(jt: &mut JavaThread, bs: &[u8]) -> OopRef {
-    let length = bs.len();
-    let mut buffer: Vec<u16> = Vec::with_capacity(length);
-    let mut pos = 0;
-    while pos < length {
-        if bs[pos] & 0x80 == 0 {
-            let v = bs[pos] as u16;
-            buffer.push(v);
-            pos += 1;
-        } else if bs[pos] & 0xE0 == 0xC0 && (bs[pos + 1] & 0xC0) == 0x80 {
-            let x = bs[pos] as u16;
-            let y = bs[pos + 1] as u16;
-            let v = ((x & 0x1f) << 6) + (y & 0x3f);
-            buffer.push(v);
-            pos += 2;
-        } else if bs[pos] & 0xF0 == 0xE0
-            && (bs[pos + 1] & 0xC0) == 0x80
-            && (bs[pos + 2] & 0xC0) == 0x80
-        {
-            let x = bs[pos] as u16;
-            let y = bs[pos + 1] as u16;
-            let z = bs[pos + 2] as u16;
-            let v = ((x & 0xf) << 12) + ((y & 0x3f) << 6) + (z & 0x3f);
-            buffer.push(v);
-            pos += 3;
-        } else if bs[pos] == 0xED
-            && (bs[pos + 1] & 0xF0 == 0xA0)
-            && (bs[pos + 2] & 0xC0 == 0x80)
-            && (bs[pos + 3] == 0xED)
-            && (bs[pos + 4] & 0xF0 == 0xB0)
-            && (bs[pos + 5] & 0xC0 == 0x80)
-        {
-            let v = bs[pos + 1] as u32;
-            let w = bs[pos + 2] as u32;
-            let y = bs[pos + 4] as u32;
-            let z = bs[pos + 5] as u32;
-            let vv =
-                0x10000 + ((v & 0x0f) << 16) + ((w & 0x3f) << 10) + ((y & 0x0f) << 6) + (z & 0x3f);
-            buffer.push(vv as u16);
-
-            pos += 6;
-        } else {
-            unreachable!()
-        }
-    }
+    let buffer = util::mutf8::decode(bs);
 
     //build "char value[]"
     let ary = OopDesc::char_ary_from1(buffer.as_slice());
@@ -171,30 +114,3 @@ pub fn new_java_lang_string3(jt: &mut JavaThread, bs: &[u8]) -> OopRef {
 
     string_oop
 }
-
-pub fn hash_code(v: OopRef) -> i32 {
-    {
-        let v = v.lock().unwrap();
-        match v.v {
-            Oop::Null => return 0,
-            Oop::Int(_) | Oop::Long(_) | Oop::Float(_) | Oop::Double(_) => unreachable!(),
-            _ => (),
-        }
-    }
-
-    if is_str(v.clone()) {
-        let value = extract_java_lang_string_value(v);
-        return if value.len() == 0 {
-            0
-        } else {
-            let mut h = 0i32;
-            for v in value {
-                h = h.wrapping_mul(31).wrapping_add(v as i32);
-            }
-            h
-        };
-    } else {
-        let v = Arc::into_raw(v);
-        v as i32
-    }
-}
@@ -0,0 +1,107 @@
+/*
+Symbol table, one shared copy of each method / field id
+
+ids like "toString:()Ljava/lang/String;" exist in nearly every class,
+they are interned when classes link, so all classes share one BytesRef.
+intern_join looks the parts up as they are, by their hash, the joined id
+is only built the first time.
+*/
+
+use crate::types::BytesRef;
+use crate::util;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    static ref SYMBOLS: Mutex<HashMap<Vec<u8>, BytesRef>> = Mutex::new(HashMap::new());
+    //the hash of the joined parts -> the symbols of that hash
+    static ref JOINED: Mutex<HashMap<u64, Vec<BytesRef>>> = Mutex::new(HashMap::new());
+}
+
+pub fn intern(v: &[u8]) -> BytesRef {
+    util::sync_call_ctx(&SYMBOLS, |h| match h.get(v) {
+        Some(sym) => sym.clone(),
+        None => {
+            let sym = Vec::from(v);
+            let sym = new_ref!(sym);
+            h.insert(Vec::from(v), sym.clone());
+            sym
+        }
+    })
+}
+
+pub fn intern_join(parts: &[&[u8]], sep: &[u8]) -> BytesRef {
+    let mut hasher = DefaultHasher::new();
+    each_joined(parts, sep, |it| hasher.write(it));
+    let hash = hasher.finish();
+
+    let found = util::sync_call_ctx(&JOINED, |h| {
+        let syms = h.get(&hash)?;
+        syms.iter().find(|it| is_joined(it, parts, sep)).cloned()
+    });
+    found.unwrap_or_else(|| {
+        let sym = intern(parts.join(sep).as_slice());
+        util::sync_call_ctx(&JOINED, |h| {
+            //another thread may have missed it too
+            let syms = h.entry(hash).or_default();
+            if !syms.iter().any(|it| Arc::ptr_eq(it, &sym)) {
+                syms.push(sym.clone());
+            }
+        });
+        sym
+    })
+}
+
+fn each_joined(parts: &[&[u8]], sep: &[u8], mut f: impl FnMut(&[u8])) {
+    for (i, it) in parts.iter().enumerate() {
+        if i > 0 {
+            f(sep);
+        }
+        f(it);
+    }
+}
+
+//sym is parts joined by sep
+fn is_joined(sym: &[u8], parts: &[&[u8]], sep: &[u8]) -> bool {
+    let mut rest = sym;
+    let mut eq = true;
+    each_joined(parts, sep, |it| match rest.strip_prefix(it) {
+        Some(tail) if eq => rest = tail,
+        _ => eq = false,
+    });
+    eq && rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    #[test]
+    fn t_intern() {
+        let a = super::intern(b"hashCode:()I");
+        let b = super::intern_join(&[b"hashCode", b"()I"], b":");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(a.as_slice(), b"hashCode:()I");
+
+        let c = super::intern(b"hashCode:()J");
+        assert!(!Arc::ptr_eq(&a, &c));
+        //found by the parts the second time
+        let d = super::intern_join(&[b"hashCode", b"()J"], b":");
+        assert!(Arc::ptr_eq(
+            &super::intern_join(&[b"hashCode", b"()J"], b":"),
+            &d
+        ));
+        assert!(Arc::ptr_eq(&c, &d));
+    }
+
+    #[test]
+    fn t_is_joined() {
+        let parts: &[&[u8]] = &[b"a", b"()V"];
+        assert!(super::is_joined(b"a:()V", parts, b":"));
+        assert!(!super::is_joined(b"a:()V;", parts, b":"));
+        assert!(!super::is_joined(b"a:()", parts, b":"));
+        assert!(!super::is_joined(b"ab()V", parts, b":"));
+    }
+}
@@ -3,6 +3,13 @@
 use std::ops::DerefMut;
 use std::sync::{Mutex, MutexGuard};
 
+/*
+The global registries are locked through here. A thread which panicked
+holding one (an internal error, or a host fn of the embedder) poisons it,
+the data may be half updated: the poison propagates, the next lock
+panics too.
+*/
+
 pub fn sync_call<F, R, T>(mutex: &Mutex<T>, f: F) -> R
 where
     F: FnOnce(MutexGuard<T>) -> R,
@@ -19,3 +26,28 @@ where
     let obj: &mut T = lock.deref_mut();
     f(obj)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_poisoned() {
+        let m = std::sync::Arc::new(Mutex::new(1));
+        let poisoner = m.clone();
+        let r = std::thread::spawn(move || {
+            sync_call_ctx(&poisoner, |v| {
+                *v = 2;
+                panic!("guest thread died");
+            })
+        })
+        .join();
+        assert!(r.is_err());
+        assert!(m.is_poisoned());
+
+        let r = std::panic::catch_unwind(|| sync_call_ctx(&m, |v| *v));
+        assert!(r.is_err());
+        let r = std::panic::catch_unwind(|| sync_call(&m, |v| *v));
+        assert!(r.is_err());
+    }
+}
@@ -15,3 +15,79 @@ mod platform {
     pub const FILE_SEP: &str = "\\";
     pub const PATH_SEP: &str = ";";
 }
+
+/*
+Name of the host (os) thread running guest code, shown by debuggers,
+profilers & thread dumps. Linux keeps 15 bytes, longer names are cut.
+Another thread of the process is renamed through its HostThread, valid
+until that thread ends.
+*/
+#[cfg(target_os = "linux")]
+pub type HostThread = libc::pthread_t;
+
+#[cfg(not(target_os = "linux"))]
+pub type HostThread = ();
+
+#[cfg(target_os = "linux")]
+pub fn current_host_thread() -> HostThread {
+    unsafe { libc::pthread_self() }
+}
+
+#[cfg(target_os = "linux")]
+pub fn set_host_thread_name(thread: HostThread, name: &str) {
+    let mut buf = [0u8; 16];
+    let name = name.replace('\0', "");
+    let len = name.len().min(15);
+    buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+    unsafe {
+        libc::pthread_setname_np(thread, buf.as_ptr() as *const libc::c_char);
+    }
+}
+
+pub fn set_thread_name(name: &str) {
+    set_host_thread_name(current_host_thread(), name);
+}
+
+#[cfg(target_os = "linux")]
+pub fn thread_name() -> Option<String> {
+    let mut buf = [0u8; 16];
+    let r = unsafe {
+        libc::prctl(
+            libc::PR_GET_NAME,
+            buf.as_mut_ptr() as libc::c_ulong,
+            0,
+            0,
+            0,
+        )
+    };
+    if r != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current_host_thread() -> HostThread {}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_host_thread_name(_thread: HostThread, _name: &str) {}
+
+#[cfg(not(target_os = "linux"))]
+pub fn thread_name() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn t_thread_name() {
+        std::thread::spawn(|| {
+            super::set_thread_name("a-long-guest-thread-name");
+            assert_eq!(super::thread_name().as_deref(), Some("a-long-guest-th"));
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+class ClinitFail
+{
+    static class Bad
+    {
+        static int v = 1 / Integer.parseInt("0");
+
+        static void touch() {}
+    }
+
+    public static void main(String[] args)
+    {
+        //ExceptionInInitializerError, caused by ArithmeticException
+        try {
+            Bad.touch();
+        } catch (ExceptionInInitializerError e) {
+            System.out.println("first: " + e + ", cause = " + e.getCause());
+        }
+
+        //<clinit> is not run again, NoClassDefFoundError, caused by the
+        //first error
+        for (int i = 0; i < 2; i++) {
+            try {
+                System.out.println(Bad.v);
+            } catch (NoClassDefFoundError e) {
+                System.out.println("again: " + e + ", caused by init error = "
+                        + (e.getCause() instanceof ExceptionInInitializerError));
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+import java.util.LinkedList;
+
+class ProducerConsumer
+{
+    static final int CAPACITY = 4;
+    static final LinkedList<Integer> items = new LinkedList<>();
+
+    public static void main(String[] args) throws InterruptedException
+    {
+        put(1);
+        put(2);
+        System.out.println(take() + take());
+
+        //nothing notifies, returns after the timeout
+        synchronized (items) {
+            long start = System.currentTimeMillis();
+            items.wait(20, 500);
+            System.out.println(System.currentTimeMillis() - start >= 20);
+        }
+
+        //interrupted before waiting
+        Thread.currentThread().interrupt();
+        synchronized (items) {
+            try {
+                items.wait();
+            } catch (InterruptedException e) {
+                System.out.println(Thread.currentThread().isInterrupted());
+            }
+        }
+
+        try {
+            items.notify();
+        } catch (IllegalMonitorStateException e) {
+            System.out.println("not owner");
+        }
+    }
+
+    static void put(int v) throws InterruptedException
+    {
+        synchronized (items) {
+            while (items.size() == CAPACITY) {
+                items.wait();
+            }
+            items.add(v);
+            items.notifyAll();
+        }
+    }
+
+    static int take() throws InterruptedException
+    {
+        synchronized (items) {
+            while (items.isEmpty()) {
+                items.wait();
+            }
+            int v = items.removeFirst();
+            items.notifyAll();
+            return v;
+        }
+    }
+}
@@ -0,0 +1,110 @@
+/*
+lookupswitch benchmark: a 64 case switch over sparse keys, javac emits
+a lookupswitch, hit & missed in a loop
+
+  cargo run --release -- --cp $JDK:$MY_TEST SwitchBench
+*/
+class SwitchBench
+{
+    static final int[] KEYS = {
+            23130, 17748, 8608, 85150, 111926, 221096,
+            262156, 374466, 517858, 626924, 806088, 981526,
+            1129918, 1324800, 1567476, 1797050, 2009770, 2275204,
+            2585328, 2882318, 3149190, 3479064, 3810524, 4175090,
+            4572914, 4969180, 5367832, 5784454, 6219022, 6682864,
+            7117188, 7633066, 8119738, 8634612, 9169152, 9720766,
+            10273814, 10824392, 11459308, 12031202, 12648642, 13336076,
+            13988264, 14625590, 15314590, 16052128, 16772948, 17475674,
+            18232970, 19037988, 19809872, 20580910, 21403878, 22227128,
+            23083708, 23975570, 24816850, 25740412, 26663416, 27589798,
+            28529262, 29490448, 30465636, 31442250
+    };
+
+    public static void main(String[] args)
+    {
+        int rounds = args.length > 0 ? Integer.parseInt(args[0]) : 20000;
+        long start = System.nanoTime();
+        long sum = 0;
+        for (int r = 0; r < rounds; r++) {
+            for (int i = 0; i < KEYS.length; i++) {
+                sum += lookup(KEYS[i]);
+                sum += lookup(KEYS[i] + 1);
+            }
+        }
+        long ms = (System.nanoTime() - start) / 1000000;
+        //sum of 1..64 a round, the misses are 0
+        System.out.println(sum == 2080L * rounds);
+        System.out.println(rounds * KEYS.length * 2 + " switches in " + ms + " ms");
+    }
+
+    static int lookup(int k)
+    {
+        switch (k) {
+            case 23130: return 1;
+            case 17748: return 2;
+            case 8608: return 3;
+            case 85150: return 4;
+            case 111926: return 5;
+            case 221096: return 6;
+            case 262156: return 7;
+            case 374466: return 8;
+            case 517858: return 9;
+            case 626924: return 10;
+            case 806088: return 11;
+            case 981526: return 12;
+            case 1129918: return 13;
+            case 1324800: return 14;
+            case 1567476: return 15;
+            case 1797050: return 16;
+            case 2009770: return 17;
+            case 2275204: return 18;
+            case 2585328: return 19;
+            case 2882318: return 20;
+            case 3149190: return 21;
+            case 3479064: return 22;
+            case 3810524: return 23;
+            case 4175090: return 24;
+            case 4572914: return 25;
+            case 4969180: return 26;
+            case 5367832: return 27;
+            case 5784454: return 28;
+            case 6219022: return 29;
+            case 6682864: return 30;
+            case 7117188: return 31;
+            case 7633066: return 32;
+            case 8119738: return 33;
+            case 8634612: return 34;
+            case 9169152: return 35;
+            case 9720766: return 36;
+            case 10273814: return 37;
+            case 10824392: return 38;
+            case 11459308: return 39;
+            case 12031202: return 40;
+            case 12648642: return 41;
+            case 13336076: return 42;
+            case 13988264: return 43;
+            case 14625590: return 44;
+            case 15314590: return 45;
+            case 16052128: return 46;
+            case 16772948: return 47;
+            case 17475674: return 48;
+            case 18232970: return 49;
+            case 19037988: return 50;
+            case 19809872: return 51;
+            case 20580910: return 52;
+            case 21403878: return 53;
+            case 22227128: return 54;
+            case 23083708: return 55;
+            case 23975570: return 56;
+            case 24816850: return 57;
+            case 25740412: return 58;
+            case 26663416: return 59;
+            case 27589798: return 60;
+            case 28529262: return 61;
+            case 29490448: return 62;
+            case 30465636: return 63;
+            case 31442250: return 64;
+            default: return 0;
+        }
+    }
+}
@@ -0,0 +1,355 @@
+import org.objectweb.asm.*;
+
+import java.io.IOException;
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.nio.file.Paths;
+
+import static org.objectweb.asm.Opcodes.*;
+
+/*
+Generates the class fixtures of the defineClass suite, classes as
+bytecode libraries emit them, not as javac does: ASM's constant pool
+order & computed frames, Byte Buddy's synthetic members and naming.
+
+  javac -cp asm-9.7.1.jar GenFixtures.java
+  java -cp asm-9.7.1.jar:. GenFixtures fixtures
+
+Every class has "public static int run()", DefineRunner defines the
+classes & checks run() against EXPECTED.
+*/
+public class GenFixtures {
+    public static void main(String[] args) throws IOException {
+        Path dir = Paths.get(args.length > 0 ? args[0] : "fixtures");
+        Files.createDirectories(dir);
+        write(dir, "asm/Switches", switches());
+        write(dir, "asm/WideLocals", wideLocals());
+        write(dir, "asm/BigPool", bigPool());
+        write(dir, "asm/Frames", frames());
+        write(dir, "asm/Indy", indy());
+        write(dir, "asm/Condy", condy());
+        write(dir, "asm/Synthetic", synthetic());
+        write(dir, "asm/Defaults", defaults());
+    }
+
+    static void write(Path dir, String name, byte[] b) throws IOException {
+        Files.write(dir.resolve(name.replace('/', '_') + ".class"), b);
+    }
+
+    static ClassWriter begin(String name, int version) {
+        ClassWriter cw = new ClassWriter(ClassWriter.COMPUTE_FRAMES | ClassWriter.COMPUTE_MAXS);
+        cw.visit(version, ACC_PUBLIC | ACC_SUPER, name, null, "java/lang/Object", null);
+        return cw;
+    }
+
+    static MethodVisitor run(ClassWriter cw) {
+        MethodVisitor mv = cw.visitMethod(ACC_PUBLIC | ACC_STATIC, "run", "()I", null, null);
+        mv.visitCode();
+        return mv;
+    }
+
+    static void end(MethodVisitor mv) {
+        mv.visitMaxs(0, 0);
+        mv.visitEnd();
+    }
+
+    //tableswitch & lookupswitch at each of the 4 paddings, run() = 1+2+...
+    static byte[] switches() {
+        ClassWriter cw = begin("asm/Switches", V1_8);
+        for (int pad = 0; pad < 4; pad++) {
+            MethodVisitor mv = cw.visitMethod(ACC_STATIC, "table" + pad, "(I)I", null, null);
+            mv.visitCode();
+            for (int i = 0; i < pad; i++) {
+                mv.visitInsn(NOP);
+            }
+            mv.visitVarInsn(ILOAD, 0);
+            Label dflt = new Label();
+            Label[] cases = {new Label(), new Label(), new Label()};
+            mv.visitTableSwitchInsn(-1, 1, dflt, cases);
+            for (int i = 0; i < cases.length; i++) {
+                mv.visitLabel(cases[i]);
+                mv.visitIntInsn(BIPUSH, 10 + i);
+                mv.visitInsn(IRETURN);
+            }
+            mv.visitLabel(dflt);
+            mv.visitInsn(ICONST_0);
+            mv.visitInsn(IRETURN);
+            end(mv);
+
+            mv = cw.visitMethod(ACC_STATIC, "lookup" + pad, "(I)I", null, null);
+            mv.visitCode();
+            for (int i = 0; i < pad; i++) {
+                mv.visitInsn(NOP);
+            }
+            mv.visitVarInsn(ILOAD, 0);
+            Label l1 = new Label(), l2 = new Label(), l3 = new Label();
+            mv.visitLookupSwitchInsn(dflt = new Label(), new int[]{Integer.MIN_VALUE, -7, 100000}, new Label[]{l1, l2, l3});
+            mv.visitLabel(l1);
+            mv.visitInsn(ICONST_1);
+            mv.visitInsn(IRETURN);
+            mv.visitLabel(l2);
+            mv.visitInsn(ICONST_2);
+            mv.visitInsn(IRETURN);
+            mv.visitLabel(l3);
+            mv.visitInsn(ICONST_3);
+            mv.visitInsn(IRETURN);
+            mv.visitLabel(dflt);
+            mv.visitInsn(ICONST_0);
+            mv.visitInsn(IRETURN);
+            end(mv);
+        }
+
+        //sum of table(0) + lookup(-7) + lookup(5) over the paddings = 4 * (11 + 2 + 0)
+        MethodVisitor mv = run(cw);
+        mv.visitInsn(ICONST_0);
+        for (int pad = 0; pad < 4; pad++) {
+            mv.visitInsn(ICONST_0);
+            mv.visitMethodInsn(INVOKESTATIC, "asm/Switches", "table" + pad, "(I)I", false);
+            mv.visitInsn(IADD);
+            mv.visitIntInsn(BIPUSH, -7);
+            mv.visitMethodInsn(INVOKESTATIC, "asm/Switches", "lookup" + pad, "(I)I", false);
+            mv.visitInsn(IADD);
+            mv.visitInsn(ICONST_5);
+            mv.visitMethodInsn(INVOKESTATIC, "asm/Switches", "lookup" + pad, "(I)I", false);
+            mv.visitInsn(IADD);
+        }
+        mv.visitInsn(IRETURN);
+        end(mv);
+        cw.visitEnd();
+        return cw.toByteArray();
+    }
+
+    //300 int locals: wide iload, istore & iinc, run() = 1000 + 1000 - 200
+    static byte[] wideLocals() {
+        ClassWriter cw = begin("asm/WideLocals", V1_8);
+        MethodVisitor mv = run(cw);
+        for (int i = 0; i < 300; i++) {
+            mv.visitInsn(ICONST_0);
+            mv.visitVarInsn(ISTORE, i);
+        }
+        mv.visitIincInsn(299, 1000);
+        mv.visitIincInsn(256, 1000);
+        mv.visitIincInsn(1, -200);
+        mv.visitVarInsn(ILOAD, 299);
+        mv.visitVarInsn(ILOAD, 256);
+        mv.visitInsn(IADD);
+        mv.visitVarInsn(ILOAD, 1);
+        mv.visitInsn(IADD);
+        mv.visitInsn(IRETURN);
+        end(mv);
+        cw.visitEnd();
+        return cw.toByteArray();
+    }
+
+    //over 256 constants: ldc_w, ldc2_w, int & float outside sipush
+    //range, run() = 300 + 100000 + 3 + 2
+    static byte[] bigPool() {
+        ClassWriter cw = begin("asm/BigPool", V1_8);
+        MethodVisitor mv = run(cw);
+        mv.visitInsn(ICONST_0);
+        for (int i = 0; i < 300; i++) {
+            mv.visitLdcInsn("s" + i);
+            mv.visitMethodInsn(INVOKEVIRTUAL, "java/lang/String", "length", "()I", false);
+            mv.visitInsn(POP);
+            mv.visitInsn(ICONST_1);
+            mv.visitInsn(IADD);
+        }
+        mv.visitLdcInsn(100000);
+        mv.visitInsn(IADD);
+        mv.visitLdcInsn(3L);
+        mv.visitInsn(L2I);
+        mv.visitInsn(IADD);
+        mv.visitLdcInsn(2.5d);
+        mv.visitInsn(D2I);
+        mv.visitInsn(IADD);
+        mv.visitLdcInsn(1.0e10f);
+        mv.visitInsn(F2I);
+        mv.visitInsn(POP);
+        mv.visitInsn(IRETURN);
+        end(mv);
+        cw.visitEnd();
+        return cw.toByteArray();
+    }
+
+    //loops, try/catch & locals of changing types: append, same & full
+    //frames, one over an offset delta of 63, run() = 45 + 7 + 1
+    static byte[] frames() {
+        ClassWriter cw = begin("asm/Frames", V1_8);
+        MethodVisitor mv = run(cw);
+        //int sum = 0; for (int i = 0; i < 10; i++) sum += i;
+        mv.visitInsn(ICONST_0);
+        mv.visitVarInsn(ISTORE, 0);
+        mv.visitInsn(ICONST_0);
+        mv.visitVarInsn(ISTORE, 1);
+        Label cond = new Label(), body = new Label();
+        mv.visitJumpInsn(GOTO, cond);
+        mv.visitLabel(body);
+        mv.visitVarInsn(ILOAD, 0);
+        mv.visitVarInsn(ILOAD, 1);
+        mv.visitInsn(IADD);
+        mv.visitVarInsn(ISTORE, 0);
+        mv.visitIincInsn(1, 1);
+        mv.visitLabel(cond);
+        mv.visitVarInsn(ILOAD, 1);
+        mv.visitIntInsn(BIPUSH, 10);
+        mv.visitJumpInsn(IF_ICMPLT, body);
+
+        //the slot of i reused for a long & an Object
+        mv.visitLdcInsn(7L);
+        mv.visitVarInsn(LSTORE, 1);
+        mv.visitVarInsn(ILOAD, 0);
+        mv.visitVarInsn(LLOAD, 1);
+        mv.visitInsn(L2I);
+        mv.visitInsn(IADD);
+        mv.visitVarInsn(ISTORE, 0);
+        mv.visitLdcInsn("x");
+        mv.visitVarInsn(ASTORE, 1);
+
+        //try { throw new RuntimeException(); } catch (RuntimeException e) { sum++; }
+        Label start = new Label(), stop = new Label(), handler = new Label(), after = new Label();
+        mv.visitTryCatchBlock(start, stop, handler, "java/lang/RuntimeException");
+        mv.visitLabel(start);
+        mv.visitTypeInsn(NEW, "java/lang/RuntimeException");
+        mv.visitInsn(DUP);
+        mv.visitMethodInsn(INVOKESPECIAL, "java/lang/RuntimeException", "<init>", "()V", false);
+        mv.visitInsn(ATHROW);
+        mv.visitLabel(stop);
+        mv.visitLabel(handler);
+        mv.visitVarInsn(ASTORE, 2);
+        mv.visitIincInsn(0, 1);
+        //a branch over more than 63 bytes, with a value on the stack
+        mv.visitVarInsn(ILOAD, 0);
+        mv.visitVarInsn(ILOAD, 0);
+        mv.visitJumpInsn(IFNE, after);
+        for (int i = 0; i < 40; i++) {
+            mv.visitInsn(ICONST_0);
+            mv.visitInsn(POP);
+        }
+        mv.visitLabel(after);
+        mv.visitInsn(IRETURN);
+        end(mv);
+        cw.visitEnd();
+        return cw.toByteArray();
+    }
+
+    //invokedynamic through LambdaMetafactory, ldc MethodType & MethodHandle,
+    //run() = 42
+    static byte[] indy() {
+        ClassWriter cw = begin("asm/Indy", V1_8);
+        cw.visitInnerClass("java/lang/invoke/MethodHandles$Lookup", "java/lang/invoke/MethodHandles", "Lookup", ACC_PUBLIC | ACC_FINAL | ACC_STATIC);
+
+        MethodVisitor mv = cw.visitMethod(ACC_PRIVATE | ACC_STATIC | ACC_SYNTHETIC, "lambda$run$0", "()Ljava/lang/Integer;", null, null);
+        mv.visitCode();
+        mv.visitIntInsn(BIPUSH, 42);
+        mv.visitMethodInsn(INVOKESTATIC, "java/lang/Integer", "valueOf", "(I)Ljava/lang/Integer;", false);
+        mv.visitInsn(ARETURN);
+        end(mv);
+
+        Handle lmf = new Handle(H_INVOKESTATIC, "java/lang/invoke/LambdaMetafactory", "metafactory",
+                "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodHandle;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite;",
+                false);
+        Handle impl = new Handle(H_INVOKESTATIC, "asm/Indy", "lambda$run$0", "()Ljava/lang/Integer;", false);
+
+        mv = run(cw);
+        mv.visitLdcInsn(Type.getMethodType("()V"));
+        mv.visitInsn(POP);
+        mv.visitLdcInsn(impl);
+        mv.visitInsn(POP);
+        mv.visitInvokeDynamicInsn("get", "()Ljava/util/function/Supplier;", lmf,
+                Type.getType("()Ljava/lang/Object;"), impl, Type.getType("()Ljava/lang/Integer;"));
+        mv.visitMethodInsn(INVOKEINTERFACE, "java/util/function/Supplier", "get", "()Ljava/lang/Object;", true);
+        mv.visitTypeInsn(CHECKCAST, "java/lang/Integer");
+        mv.visitMethodInsn(INVOKEVIRTUAL, "java/lang/Integer", "intValue", "()I", false);
+        mv.visitInsn(IRETURN);
+        end(mv);
+        cw.visitEnd();
+        return cw.toByteArray();
+    }
+
+    //CONSTANT_Dynamic, class file 55, run() = 0, null is not an Integer
+    static byte[] condy() {
+        ClassWriter cw = begin("asm/Condy", V11);
+        Handle bsm = new Handle(H_INVOKESTATIC, "java/lang/invoke/ConstantBootstraps", "nullConstant",
+                "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/Object;",
+                false);
+        MethodVisitor mv = run(cw);
+        mv.visitLdcInsn(new ConstantDynamic("_", "Ljava/lang/Integer;", bsm));
+        mv.visitTypeInsn(INSTANCEOF, "java/lang/Integer");
+        mv.visitInsn(IRETURN);
+        end(mv);
+        cw.visitEnd();
+        return cw.toByteArray();
+    }
+
+    //as Byte Buddy emits: synthetic fields & bridges, $ names, no debug
+    //info, an unknown attribute, unused constants, a string of
+    //supplementary chars & NUL, run() = 3 + 5
+    static byte[] synthetic() {
+        ClassWriter cw = begin("asm/Synthetic", V1_8);
+        cw.visitAttribute(new Custom("net.bytebuddy.Marker"));
+        cw.newUTF8("unused$constant");
+        cw.newClass("net/bytebuddy/Unused");
+        cw.newConst(Long.MIN_VALUE);
+
+        cw.visitField(ACC_PRIVATE | ACC_STATIC | ACC_FINAL | ACC_SYNTHETIC, "delegate$q1w2e3", "Ljava/lang/Object;", null, null).visitEnd();
+        cw.visitField(ACC_PUBLIC | ACC_STATIC | ACC_FINAL, "cachedValue$ByteBuddy$0", "I", null, 5).visitEnd();
+
+        MethodVisitor mv = cw.visitMethod(ACC_PUBLIC | ACC_STATIC, "compareTo", "(Ljava/lang/String;)I", null, null);
+        mv.visitCode();
+        mv.visitVarInsn(ALOAD, 0);
+        mv.visitMethodInsn(INVOKEVIRTUAL, "java/lang/String", "length", "()I", false);
+        mv.visitInsn(IRETURN);
+        end(mv);
+
+        mv = cw.visitMethod(ACC_PUBLIC | ACC_STATIC | ACC_BRIDGE | ACC_SYNTHETIC, "compareTo", "(Ljava/lang/Object;)I", null, null);
+        mv.visitAttribute(new Custom("net.bytebuddy.Marker"));
+        mv.visitCode();
+        mv.visitVarInsn(ALOAD, 0);
+        mv.visitTypeInsn(CHECKCAST, "java/lang/String");
+        mv.visitMethodInsn(INVOKESTATIC, "asm/Synthetic", "compareTo", "(Ljava/lang/String;)I", false);
+        mv.visitInsn(IRETURN);
+        end(mv);
+
+        //"\0" + U+1F600, 3 chars in utf-16
+        mv = run(cw);
+        mv.visitLdcInsn("\0\uD83D\uDE00");
+        mv.visitMethodInsn(INVOKESTATIC, "asm/Synthetic", "compareTo", "(Ljava/lang/Object;)I", false);
+        mv.visitFieldInsn(GETSTATIC, "asm/Synthetic", "cachedValue$ByteBuddy$0", "I");
+        mv.visitInsn(IADD);
+        mv.visitInsn(IRETURN);
+        end(mv);
+        cw.visitEnd();
+        return cw.toByteArray();
+    }
+
+    //an interface with a default & a static method, run() = 7
+    static byte[] defaults() {
+        ClassWriter cw = new ClassWriter(ClassWriter.COMPUTE_FRAMES | ClassWriter.COMPUTE_MAXS);
+        cw.visit(V1_8, ACC_PUBLIC | ACC_INTERFACE | ACC_ABSTRACT, "asm/Defaults", null, "java/lang/Object", null);
+        MethodVisitor mv = cw.visitMethod(ACC_PUBLIC, "three", "()I", null, null);
+        mv.visitCode();
+        mv.visitInsn(ICONST_3);
+        mv.visitInsn(IRETURN);
+        end(mv);
+
+        mv = run(cw);
+        mv.visitInsn(ICONST_4);
+        mv.visitIntInsn(BIPUSH, 3);
+        mv.visitInsn(IADD);
+        mv.visitInsn(IRETURN);
+        end(mv);
+        cw.visitEnd();
+        return cw.toByteArray();
+    }
+
+    static class Custom extends Attribute {
+        Custom(String type) {
+            super(type);
+        }
+
+        @Override
+        protected ByteVector write(ClassWriter cw, byte[] code, int len, int maxStack, int maxLocals) {
+            return new ByteVector().putShort(0xcafe).putByte(1);
+        }
+    }
+}